@@ -0,0 +1,22 @@
+use std::process::Command;
+
+// 编译时未设置 MARIO_GIT_COMMIT 时取当前 git 提交，不在 git 仓库中或 git 不可用时不设置
+fn main() {
+    println!("cargo:rerun-if-env-changed=MARIO_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    if std::env::var("MARIO_GIT_COMMIT").is_ok() {
+        return;
+    }
+    let output = match Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return,
+    };
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !commit.is_empty() {
+        println!("cargo:rustc-env=MARIO_GIT_COMMIT={}", commit);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::cmd::{
+    ExitStatus, EXIT_CODE_CONFIG, EXIT_CODE_CONFLICT, EXIT_CODE_FAILURE, EXIT_CODE_INTERNAL,
+    EXIT_CODE_NOT_FOUND, EXIT_CODE_NOT_RUNNING, EXIT_CODE_USAGE,
+};
+use serde_json::json;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// --output json 时错误以 json 输出到标准错误
+static OUTPUT_JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_output_json(json: bool) {
+    OUTPUT_JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn output_json() -> bool {
+    OUTPUT_JSON.load(Ordering::Relaxed)
+}
+
+/// 命令行错误分类，决定退出码与 json 输出中的 code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CliErrorKind {
+    // 命令执行完成但结果未达预期，如任务失败、冒烟测试失败
+    Failure,
+    Usage,
+    NotRunning,
+    NotFound,
+    // 与当前状态冲突，如实例已在运行、任务运行中
+    Conflict,
+    Config,
+    Internal,
+}
+
+impl CliErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliErrorKind::Failure => EXIT_CODE_FAILURE,
+            CliErrorKind::Usage => EXIT_CODE_USAGE,
+            CliErrorKind::NotRunning => EXIT_CODE_NOT_RUNNING,
+            CliErrorKind::NotFound => EXIT_CODE_NOT_FOUND,
+            CliErrorKind::Conflict => EXIT_CODE_CONFLICT,
+            CliErrorKind::Config => EXIT_CODE_CONFIG,
+            CliErrorKind::Internal => EXIT_CODE_INTERNAL,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            CliErrorKind::Failure => "failure",
+            CliErrorKind::Usage => "usage",
+            CliErrorKind::NotRunning => "not_running",
+            CliErrorKind::NotFound => "not_found",
+            CliErrorKind::Conflict => "conflict",
+            CliErrorKind::Config => "config",
+            CliErrorKind::Internal => "internal",
+        }
+    }
+}
+
+/// 可携带分类的命令行错误，cmd_match 返回的其他错误均视为内部错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliError {
+    pub kind: CliErrorKind,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(kind: CliErrorKind, message: impl Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "error": {
+                "code": self.kind.code(),
+                "message": self.message,
+            }
+        })
+    }
+
+    /// 按输出模式打印到标准错误并返回对应退出状态
+    pub fn report(&self) -> ExitStatus {
+        match output_json() {
+            true => eprintln!("{}", self.to_json()),
+            false => eprintln!("{}", self.message),
+        }
+        ExitStatus::from_code(self.kind.exit_code())
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// 输出错误并返回退出状态
+pub fn report_error(kind: CliErrorKind, message: impl Display) -> ExitStatus {
+    CliError::new(kind, message).report()
+}
+
+/// 命令返回的错误转为退出状态，未分类的错误视为内部错误
+pub fn report_anyhow(e: &anyhow::Error) -> ExitStatus {
+    match e.downcast_ref::<CliError>() {
+        Some(c) => c.report(),
+        None => CliError::new(CliErrorKind::Internal, format!("{:#}", e)).report(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{report_anyhow, CliError, CliErrorKind};
+    use crate::cmd::ExitStatus;
+
+    //cargo test cmd::cli_error::test::test_cli_error_codes -- --nocapture
+    #[test]
+    fn test_cli_error_codes() {
+        let codes = [
+            (CliErrorKind::Failure, 1),
+            (CliErrorKind::Usage, 2),
+            (CliErrorKind::NotRunning, 3),
+            (CliErrorKind::NotFound, 4),
+            (CliErrorKind::Conflict, 5),
+            (CliErrorKind::Internal, 70),
+            (CliErrorKind::Config, 78),
+        ];
+        for (kind, code) in codes {
+            assert_eq!(kind.exit_code(), code);
+        }
+
+        let err = CliError::new(CliErrorKind::NotFound, "task 1 not exist");
+        assert_eq!(err.to_json()["error"]["code"], "not_found");
+        assert_eq!(err.to_json()["error"]["message"], "task 1 not exist");
+
+        let classified = anyhow::Error::new(err);
+        assert_eq!(report_anyhow(&classified), ExitStatus::Failure(4));
+        let internal = anyhow::anyhow!("rocksdb unreadable");
+        assert_eq!(report_anyhow(&internal), ExitStatus::Failure(70));
+    }
+}
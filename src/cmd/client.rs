@@ -0,0 +1,127 @@
+use crate::configure::get_config;
+use anyhow::{anyhow, Result};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use curl::easy::{Easy, List};
+use serde_json::Value;
+use std::io::Read;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// 指定 --socket 时经该 socket 访问；未显式指定 --server 且配置了 http.unix 时优先使用 unix socket
+pub(crate) fn cli_unix_socket(matches: &ArgMatches) -> Option<String> {
+    if let Ok(Some(path)) = matches.try_get_one::<String>("socket") {
+        return Some(path.clone());
+    }
+    match matches.value_source("server") {
+        Some(ValueSource::DefaultValue) => get_config().ok().and_then(|c| c.http.unix),
+        _ => None,
+    }
+}
+
+/// 访问 https 地址时的证书校验选项，由全局参数 --insecure、--cacert 设置
+#[derive(Debug, Clone, Default)]
+pub struct CliTlsOptions {
+    // 不校验服务端证书
+    pub insecure: bool,
+    // 校验服务端证书使用的 CA 证书文件
+    pub cacert: Option<String>,
+}
+
+static CLI_TLS_OPTIONS: RwLock<CliTlsOptions> = RwLock::new(CliTlsOptions {
+    insecure: false,
+    cacert: None,
+});
+
+pub fn set_cli_tls_options(options: CliTlsOptions) {
+    if let Ok(mut o) = CLI_TLS_OPTIONS.write() {
+        *o = options;
+    }
+}
+
+fn cli_tls_options() -> CliTlsOptions {
+    match CLI_TLS_OPTIONS.read() {
+        Ok(o) => o.clone(),
+        Err(_) => CliTlsOptions::default(),
+    }
+}
+
+// 命令行访问 api 使用的 token，环境变量优先于配置文件
+const API_TOKEN_ENV: &'static str = "MARIO_API_TOKEN";
+
+/// 依次取环境变量 MARIO_API_TOKEN、auth.api_token、auth.tokens 中的第一个
+pub(crate) fn cli_api_token() -> Option<String> {
+    if let Ok(token) = std::env::var(API_TOKEN_ENV) {
+        if !token.is_empty() {
+            return Some(token);
+        }
+    }
+    let auth = get_config().ok()?.auth;
+    auth.api_token.or_else(|| auth.tokens.first().cloned())
+}
+
+/// body 为空时发送 GET 请求，否则以 json 发送 POST 请求；指定 unix_socket 时经由 socket 连接
+pub(crate) fn http_request(
+    url: &str,
+    body: Option<Value>,
+    unix_socket: Option<&str>,
+) -> Result<Value> {
+    let mut easy = Easy::new();
+    easy.url(url)?;
+    if let Some(path) = unix_socket {
+        easy.unix_socket(path)?;
+    }
+    easy.timeout(Duration::from_secs(10))?;
+    let tls = cli_tls_options();
+    if tls.insecure {
+        easy.ssl_verify_peer(false)?;
+        easy.ssl_verify_host(false)?;
+    }
+    if let Some(cacert) = &tls.cacert {
+        easy.cainfo(cacert)?;
+    }
+    let mut headers = List::new();
+    if let Some(token) = cli_api_token() {
+        headers.append(&format!("Authorization: Bearer {}", token))?;
+    }
+    let payload = match body {
+        Some(b) => {
+            headers.append("Content-Type: application/json")?;
+            easy.post(true)?;
+            let payload = b.to_string().into_bytes();
+            easy.post_field_size(payload.len() as u64)?;
+            payload
+        }
+        None => vec![],
+    };
+    easy.http_headers(headers)?;
+
+    let mut resp = Vec::new();
+    {
+        let mut payload = payload.as_slice();
+        let mut transfer = easy.transfer();
+        transfer.read_function(|buf| Ok(payload.read(buf).unwrap_or(0)))?;
+        transfer.write_function(|data| {
+            resp.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    // 接口错误以非 200 状态码返回 {"code","message","details"}，交由调用方按 code 处理
+    let status = easy.response_code()?;
+    match serde_json::from_slice::<Value>(&resp) {
+        Ok(v) if status == 200 || v.get("code").is_some() => Ok(v),
+        Ok(_) => Err(anyhow!("{} response status {}", url, status)),
+        Err(_) if status != 200 => Err(anyhow!("{} response status {}", url, status)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 接口返回的错误信息，兼容 msg 与 message 两种字段
+pub(crate) fn response_message(resp: &Value) -> String {
+    match resp["message"].as_str().or_else(|| resp["msg"].as_str()) {
+        Some(m) => m.to_string(),
+        None => resp.to_string(),
+    }
+}
@@ -1,4 +1,9 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{report_error, CliErrorKind, ExitStatus};
+use crate::configure::{get_config, redacted_config, Config};
+use anyhow::Result;
 use clap::Arg;
+use clap::ArgAction;
 use clap::Command;
 
 pub fn new_config_cmd() -> Command {
@@ -6,11 +11,41 @@ pub fn new_config_cmd() -> Command {
         .about("config")
         .subcommand(config_show_cmd())
         .subcommand(config_generate_default())
+        .subcommand(config_validate_cmd())
+        .subcommand(config_set_cmd())
 }
 
 fn config_show_cmd() -> Command {
     clap::Command::new("show")
         .about("show some info ")
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["yaml", "json"])
+                .default_value("yaml")
+                .help("output format"),
+        )
+        .arg(
+            Arg::new("effective")
+                .long("effective")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("defaults")
+                .help("show config used by running server, including environment and command line overrides"),
+        )
+        .arg(
+            Arg::new("defaults")
+                .long("defaults")
+                .action(ArgAction::SetTrue)
+                .help("show built-in default config, same as gendefault"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url, used with --effective"),
+        )
         .subcommand(config_show_info_cmd())
         .subcommand(config_show_all_cmd())
 }
@@ -28,3 +63,140 @@ fn config_show_info_cmd() -> Command {
 fn config_show_all_cmd() -> Command {
     clap::Command::new("all").about("show all ")
 }
+
+fn config_set_cmd() -> Command {
+    clap::Command::new("set")
+        .about("set a config item in config file, e.g. `config set http.port 3001`")
+        .arg(
+            Arg::new("key")
+                .value_name("KEY")
+                .required(true)
+                .index(1)
+                .help("dotted path of config item"),
+        )
+        .arg(
+            Arg::new("value")
+                .value_name("VALUE")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("print resulting config without writing file"),
+        )
+}
+
+fn config_validate_cmd() -> Command {
+    clap::Command::new("validate").about("validate config file, exit 78 on failure")
+}
+
+/// 按格式序列化配置，凭证脱敏
+pub fn format_config(config: &Config, format: &str) -> Result<String> {
+    let config = redacted_config(config);
+    match format {
+        "json" => Ok(serde_json::to_string_pretty(&config)?),
+        _ => Ok(serde_yaml::to_string(&config)?),
+    }
+}
+
+/// 输出本地解析的配置，defaults 为 true 时输出内置默认配置
+pub fn print_config(defaults: bool, format: &str) -> ExitStatus {
+    let config = match defaults {
+        true => Ok(Config::default()),
+        false => get_config(),
+    };
+    match config.and_then(|c| format_config(&c, format)) {
+        Ok(s) => {
+            println!("{}", s);
+            ExitStatus::Success
+        }
+        Err(e) => report_error(CliErrorKind::Internal, e),
+    }
+}
+
+/// 从运行中的服务获取生效配置，环境变量与命令行覆盖项 yaml 格式以注释列于开头，json 格式输出到标准错误
+/// 服务未运行时输出本地解析的配置
+pub fn print_effective_config(server: &str, unix_socket: Option<&str>, format: &str) -> ExitStatus {
+    let server = server.trim_end_matches('/');
+    let config = match http_request(
+        &format!("{}/api/v1/currentconfig", server),
+        Some(serde_json::json!({})),
+        unix_socket,
+    ) {
+        Ok(resp) if resp["code"].as_i64() == Some(0) => {
+            match serde_json::from_value::<Config>(resp["data"].clone()) {
+                Ok(c) => c,
+                Err(e) => {
+                    return report_error(
+                        CliErrorKind::Internal,
+                        format!("parse effective config error: {}", e),
+                    )
+                }
+            }
+        }
+        Ok(resp) => {
+            return report_error(
+                CliErrorKind::Internal,
+                format!("get effective config error: {}", response_message(&resp)),
+            )
+        }
+        Err(e) => {
+            eprintln!(
+                "server not running ({}), showing config resolved locally",
+                e
+            );
+            return print_config(false, format);
+        }
+    };
+    if let Ok(info) = http_request(&format!("{}/api/v1/info", server), None, unix_socket) {
+        if let Some(fields) = info["data"]["config_env_overrides"].as_array() {
+            for field in fields.iter().filter_map(|f| f.as_str()) {
+                match format {
+                    "json" => eprintln!("{} overridden by environment", field),
+                    _ => println!("# {} overridden by environment", field),
+                }
+            }
+        }
+        let overrides = &info["data"]["config_overrides"];
+        for (field, value) in [
+            ("http.bind", &overrides["http_bind"]),
+            ("http.port", &overrides["http_port"]),
+        ] {
+            if value.is_null() {
+                continue;
+            }
+            match format {
+                "json" => eprintln!("{} overridden by command line: {}", field, value),
+                _ => println!("# {} overridden by command line: {}", field, value),
+            }
+        }
+    }
+    match format_config(&config, format) {
+        Ok(s) => {
+            println!("{}", s);
+            ExitStatus::Success
+        }
+        Err(e) => report_error(CliErrorKind::Internal, e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_config;
+    use crate::configure::Config;
+
+    //cargo test cmd::configcmd::test::test_format_config -- --nocapture
+    #[test]
+    fn test_format_config() {
+        let config = Config::default();
+        let json = format_config(&config, "json").unwrap();
+        let parsed = serde_json::from_str::<Config>(&json).unwrap();
+        assert_eq!(parsed.http, config.http);
+        assert!(!json.contains(&config.datasource_mysql.mysql_uri));
+
+        let yml = format_config(&config, "yaml").unwrap();
+        assert_eq!(serde_yaml::from_str::<Config>(&yml).unwrap(), parsed);
+    }
+}
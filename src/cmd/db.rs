@@ -0,0 +1,613 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{output_json, report_error, CliErrorKind, ExitStatus};
+use crate::commons::unix_secs_to_rfc3339;
+use crate::resources::{restore_backup, restore_latest, BackupInfo};
+use crate::server::{acquire_instance_lock, InstanceLockedError};
+use clap::{Arg, Command};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+// --wait 时查询作业的间隔
+const DB_JOB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn new_db_cmd() -> Command {
+    clap::Command::new("db")
+        .about("rocksdb maintenance on running server")
+        .subcommand(
+            clap::Command::new("compact")
+                .about("compact all column families or the one given by --cf")
+                .arg(
+                    Arg::new("cf")
+                        .long("cf")
+                        .value_name("COLUMN_FAMILY")
+                        .help("only compact this column family"),
+                )
+                .arg(db_wait_arg())
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("backup")
+                .about("back up rocksdb into db.backup_dir of server config")
+                .arg(db_wait_arg())
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("prune")
+                .about(
+                    "remove statuses and checkpoints of deleted tasks and expired stopped statuses",
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("only report entries to be removed"),
+                )
+                .arg(db_wait_arg())
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("reindex")
+                .about("rebuild task name index from task definitions")
+                .arg(db_wait_arg())
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("check")
+                .about("list rows that cannot be decoded, exit 1 when any found")
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("repair")
+                .about("delete rows that cannot be decoded after confirmation")
+                .arg(
+                    Arg::new("delete-corrupt")
+                        .long("delete-corrupt")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("delete task statuses that cannot be decoded"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(clap::ArgAction::SetTrue)
+                        .help("delete without confirmation"),
+                )
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("restore")
+                .about("restore rocksdb from latest backup, server must be stopped")
+                .arg(
+                    Arg::new("backup-id")
+                        .long("backup-id")
+                        .value_name("ID")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("restore this backup instead of latest, see GET /api/v1/admin/db/backups"),
+                )
+                .arg(
+                    Arg::new("backup-dir")
+                        .long("backup-dir")
+                        .value_name("DIR")
+                        .help("backup dir, default db.backup_dir of config"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("PATH")
+                        .help("restore into this dir, default rocksdb.path of config"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("stats")
+                .about("show column family sizes and compaction state")
+                .arg(db_server_arg()),
+        )
+        .subcommand(
+            clap::Command::new("job")
+                .about("show compaction or backup job")
+                .arg(
+                    Arg::new("job_id")
+                        .value_name("JOB_ID")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(db_server_arg()),
+        )
+}
+
+fn db_wait_arg() -> Arg {
+    Arg::new("wait")
+        .long("wait")
+        .action(clap::ArgAction::SetTrue)
+        .help("wait until job finished, exit 1 when job failed")
+}
+
+fn db_server_arg() -> Arg {
+    Arg::new("server")
+        .long("server")
+        .value_name("URL")
+        .default_value("http://127.0.0.1:3000")
+        .help("server url")
+}
+
+fn db_request(
+    url: &str,
+    body: Option<Value>,
+    unix_socket: Option<&str>,
+) -> Result<Value, ExitStatus> {
+    let resp = match http_request(url, body, unix_socket) {
+        Ok(r) => r,
+        Err(e) => {
+            return Err(report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            ))
+        }
+    };
+    if resp["code"].as_i64() == Some(0) {
+        return Ok(resp["data"].clone());
+    }
+    let kind = match resp["code"].as_str() {
+        Some("db_job_conflict") => CliErrorKind::Conflict,
+        Some("not_found") => CliErrorKind::NotFound,
+        Some("invalid_request") => CliErrorKind::Usage,
+        _ => CliErrorKind::Internal,
+    };
+    Err(report_error(kind, response_message(&resp)))
+}
+
+fn render_db_job(job: &Value) -> String {
+    let ts = |v: &Value| match v.as_u64() {
+        Some(t) => unix_secs_to_rfc3339(t),
+        None => "-".to_string(),
+    };
+    let mut lines = vec![
+        format!("job:      {}", job["job_id"].as_str().unwrap_or_default()),
+        format!(
+            "kind:     {}{}",
+            job["kind"].as_str().unwrap_or("-"),
+            match job["cf"].as_str() {
+                Some(cf) => format!(" ({})", cf),
+                None => String::new(),
+            }
+        ),
+        format!(
+            "state:    {} {}/{}",
+            job["state"].as_str().unwrap_or("-"),
+            job["steps_done"].as_u64().unwrap_or(0),
+            job["steps_total"].as_u64().unwrap_or(0)
+        ),
+        format!("started:  {}", ts(&job["started_at"])),
+        format!("finished: {}", ts(&job["finished_at"])),
+    ];
+    if let Some(id) = job["backup_id"].as_u64() {
+        lines.push(format!(
+            "backup:   {} ({} bytes)",
+            id,
+            job["backup_size"].as_u64().unwrap_or(0)
+        ));
+    }
+    let pruned = &job["pruned"];
+    if !pruned.is_null() {
+        let count = |k: &str| pruned[k].as_array().map(|a| a.len()).unwrap_or(0);
+        lines.push(format!(
+            "pruned:   {} orphan statuses, {} orphan checkpoints, {} expired statuses{}",
+            count("orphan_statuses"),
+            count("orphan_checkpoints"),
+            count("expired_statuses"),
+            match pruned["dry_run"].as_bool() {
+                Some(true) => " (dry run)",
+                _ => "",
+            }
+        ));
+    }
+    if let Some(names) = job["reindexed_names"].as_u64() {
+        lines.push(format!("reindex:  {} names", names));
+    }
+    if let Some(e) = job["error"].as_str() {
+        lines.push(format!("error:    {}", e));
+    }
+    lines.join("\n")
+}
+
+fn print_db_job(job: &Value) {
+    match output_json() {
+        true => println!("{}", job),
+        false => println!("{}", render_db_job(job)),
+    }
+}
+
+/// 查询作业直至结束，作业失败时返回非零退出码
+fn wait_db_job(server: &str, unix_socket: Option<&str>, job: Value) -> ExitStatus {
+    let job_id = job["job_id"].as_str().unwrap_or_default().to_string();
+    let url = format!("{}/api/v1/admin/db/jobs/{}", server, job_id);
+    let mut job = job;
+    while job["state"].as_str() == Some("running") {
+        thread::sleep(DB_JOB_POLL_INTERVAL);
+        job = match db_request(&url, None, unix_socket) {
+            Ok(j) => j,
+            Err(status) => return status,
+        };
+    }
+    print_db_job(&job);
+    match job["state"].as_str() {
+        Some("succeeded") => ExitStatus::Success,
+        _ => report_error(CliErrorKind::Failure, format!("db job {} failed", job_id)),
+    }
+}
+
+/// 提交压缩、备份、清理或重建索引作业，path 为 compact、backup、prune 或 reindex；已有作业运行时以冲突退出
+pub fn start_db_job(
+    server: &str,
+    unix_socket: Option<&str>,
+    path: &str,
+    query: &[(&str, String)],
+    wait: bool,
+) -> ExitStatus {
+    let server = server.trim_end_matches('/');
+    let query = match query.is_empty() {
+        true => String::new(),
+        false => format!(
+            "?{}",
+            query
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<String>>()
+                .join("&")
+        ),
+    };
+    let url = format!("{}/api/v1/admin/db/{}{}", server, path, query);
+    let job = match db_request(&url, Some(json!({})), unix_socket) {
+        Ok(j) => j,
+        Err(status) => return status,
+    };
+    if wait {
+        return wait_db_job(server, unix_socket, job);
+    }
+    print_db_job(&job);
+    ExitStatus::Success
+}
+
+pub fn show_db_job(server: &str, unix_socket: Option<&str>, job_id: &str) -> ExitStatus {
+    let url = format!(
+        "{}/api/v1/admin/db/jobs/{}",
+        server.trim_end_matches('/'),
+        job_id
+    );
+    match db_request(&url, None, unix_socket) {
+        Ok(job) => {
+            print_db_job(&job);
+            ExitStatus::Success
+        }
+        Err(status) => status,
+    }
+}
+
+fn render_db_stats(stats: &Value) -> String {
+    let ts = |v: &Value| match v.as_u64() {
+        Some(t) => unix_secs_to_rfc3339(t),
+        None => "-".to_string(),
+    };
+    let mut lines = vec![
+        format!(
+            "path:             {}",
+            stats["path"].as_str().unwrap_or("-")
+        ),
+        format!(
+            "total size:       {} bytes",
+            stats["total_size"].as_u64().unwrap_or(0)
+        ),
+        format!(
+            "auto compaction:  {}",
+            stats["auto_compaction"].as_bool().unwrap_or(false)
+        ),
+        format!(
+            "schedule:         {}",
+            match stats["compaction_schedule"].as_str() {
+                Some(s) if !s.is_empty() => s,
+                _ => "-",
+            }
+        ),
+        format!("next compaction:  {}", ts(&stats["next_compaction"])),
+    ];
+    let last = &stats["last_compaction"];
+    match last.is_null() {
+        true => lines.push("last compaction:  -".to_string()),
+        false => lines.push(format!(
+            "last compaction:  {} ({} ms)",
+            ts(&last["finished_at"]),
+            last["duration_ms"].as_u64().unwrap_or(0)
+        )),
+    }
+    if let Some(sizes) = stats["cf_sizes"].as_object() {
+        for (cf, size) in sizes {
+            lines.push(format!("  {:<24}{} bytes", cf, size.as_u64().unwrap_or(0)));
+        }
+    }
+    lines.join("\n")
+}
+
+pub fn show_db_stats(server: &str, unix_socket: Option<&str>) -> ExitStatus {
+    let url = format!("{}/api/v1/admin/db/stats", server.trim_end_matches('/'));
+    match db_request(&url, None, unix_socket) {
+        Ok(stats) => {
+            match output_json() {
+                true => println!("{}", stats),
+                false => println!("{}", render_db_stats(&stats)),
+            }
+            ExitStatus::Success
+        }
+        Err(status) => status,
+    }
+}
+
+fn corrupt_keys(report: &Value) -> Vec<String> {
+    report["corrupt_statuses"]
+        .as_array()
+        .map(|rows| {
+            rows.iter()
+                .filter_map(|row| row["key"].as_str().map(|k| k.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn render_corrupt_rows(lines: &mut Vec<String>, rows: &Value) {
+    for row in rows.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        lines.push(format!(
+            "  {:<24}{}",
+            row["key"].as_str().unwrap_or_default(),
+            row["error"].as_str().unwrap_or_default()
+        ));
+    }
+}
+
+fn render_db_check(report: &Value) -> String {
+    let mut lines = vec![format!(
+        "corrupt task statuses: {}",
+        corrupt_keys(report).len()
+    )];
+    render_corrupt_rows(&mut lines, &report["corrupt_statuses"]);
+    lines.join("\n")
+}
+
+fn render_db_repair(report: &Value) -> String {
+    let count = |k: &str| report[k].as_array().map(|a| a.len()).unwrap_or(0);
+    let mut lines = vec![
+        format!("deleted:   {} corrupt task statuses", count("deleted")),
+        format!("remaining: {} corrupt task statuses", count("remaining")),
+    ];
+    render_corrupt_rows(&mut lines, &report["remaining"]);
+    lines.join("\n")
+}
+
+fn db_check_url(server: &str) -> String {
+    format!("{}/api/v1/admin/db/check", server.trim_end_matches('/'))
+}
+
+/// 列出无法解码的记录，存在时以失败退出
+pub fn check_db(server: &str, unix_socket: Option<&str>) -> ExitStatus {
+    let report = match db_request(&db_check_url(server), None, unix_socket) {
+        Ok(r) => r,
+        Err(status) => return status,
+    };
+    match output_json() {
+        true => println!("{}", report),
+        false => println!("{}", render_db_check(&report)),
+    }
+    match corrupt_keys(&report).len() {
+        0 => ExitStatus::Success,
+        n => report_error(
+            CliErrorKind::Failure,
+            format!("{} corrupt rows found, run db repair --delete-corrupt", n),
+        ),
+    }
+}
+
+// 输入 y 或 yes 时确认，标准输入不可读时视为取消
+fn confirm(prompt: &str) -> bool {
+    eprint!("{} [y/N] ", prompt);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// 列出无法解码的记录，经操作员确认后删除；服务端只删除确认时仍无法解码的记录
+pub fn repair_db(server: &str, unix_socket: Option<&str>, yes: bool) -> ExitStatus {
+    let report = match db_request(&db_check_url(server), None, unix_socket) {
+        Ok(r) => r,
+        Err(status) => return status,
+    };
+    let keys = corrupt_keys(&report);
+    let result = match keys.is_empty() {
+        true => json!({"deleted": [], "remaining": []}),
+        false => {
+            eprintln!("{}", render_db_check(&report));
+            if !yes && !confirm(&format!("delete {} corrupt task statuses?", keys.len())) {
+                return report_error(CliErrorKind::Failure, "repair cancelled");
+            }
+            let url = format!("{}/api/v1/admin/db/repair", server.trim_end_matches('/'));
+            match db_request(&url, Some(json!({ "delete_corrupt": keys })), unix_socket) {
+                Ok(r) => r,
+                Err(status) => return status,
+            }
+        }
+    };
+    match output_json() {
+        true => println!("{}", result),
+        false => println!("{}", render_db_repair(&result)),
+    }
+    ExitStatus::Success
+}
+
+fn render_backup(backup: &BackupInfo, target: &str) -> String {
+    let created = match u64::try_from(backup.timestamp) {
+        Ok(t) => unix_secs_to_rfc3339(t),
+        Err(_) => "-".to_string(),
+    };
+    vec![
+        format!("backup:   {} ({} bytes)", backup.backup_id, backup.size),
+        format!("created:  {}", created),
+        format!("restored: {}", target),
+    ]
+    .join("\n")
+}
+
+/// 以指定 id 或最新的备份恢复 rocksdb，在目标目录持有实例锁期间执行，服务运行中时以冲突退出
+pub fn restore_db(backup_dir: &str, backup_id: Option<u32>, target: &str) -> ExitStatus {
+    if let Err(e) = acquire_instance_lock(target) {
+        return match e.downcast_ref::<InstanceLockedError>() {
+            Some(locked) => report_error(
+                CliErrorKind::Conflict,
+                format!("{}, stop server before restore", locked),
+            ),
+            None => report_error(CliErrorKind::Internal, e),
+        };
+    }
+    let restored = match backup_id {
+        Some(id) => restore_backup(backup_dir, id, target),
+        None => restore_latest(backup_dir, target),
+    };
+    match restored {
+        Ok(backup) => {
+            match output_json() {
+                true => println!("{}", json!({"backup": backup, "target": target})),
+                false => println!("{}", render_backup(&backup, target)),
+            }
+            ExitStatus::Success
+        }
+        Err(e) => report_error(
+            CliErrorKind::Failure,
+            format!("restore from {} error: {:#}", backup_dir, e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_backup, render_db_check, render_db_job, render_db_repair, render_db_stats};
+    use crate::resources::BackupInfo;
+    use serde_json::json;
+
+    //cargo test cmd::db::test::test_render_db_job -- --nocapture
+    #[test]
+    fn test_render_db_job() {
+        let job = json!({
+            "job_id": "j1",
+            "kind": "backup",
+            "cf": null,
+            "state": "succeeded",
+            "started_at": 0,
+            "finished_at": 60,
+            "steps_done": 1,
+            "steps_total": 1,
+            "backup_id": 3,
+            "backup_size": 4096,
+            "error": null
+        });
+        let text = render_db_job(&job);
+        println!("{}", text);
+        assert!(text.contains("kind:     backup\n"));
+        assert!(text.contains("state:    succeeded 1/1"));
+        assert!(text.contains("backup:   3 (4096 bytes)"));
+        assert!(!text.contains("error:"));
+        assert!(!text.contains("pruned:"));
+        assert!(!text.contains("reindex:"));
+
+        let job = json!({
+            "job_id": "j2",
+            "kind": "prune",
+            "state": "succeeded",
+            "pruned": {
+                "dry_run": true,
+                "orphan_statuses": ["1", "2"],
+                "orphan_checkpoints": ["1"],
+                "expired_statuses": []
+            }
+        });
+        let text = render_db_job(&job);
+        assert!(text.contains(
+            "pruned:   2 orphan statuses, 1 orphan checkpoints, 0 expired statuses (dry run)"
+        ));
+
+        let job = json!({
+            "job_id": "j3",
+            "kind": "reindex",
+            "state": "succeeded",
+            "reindexed_names": 5
+        });
+        assert!(render_db_job(&job).contains("reindex:  5 names"));
+    }
+
+    //cargo test cmd::db::test::test_render_db_stats -- --nocapture
+    #[test]
+    fn test_render_db_stats() {
+        let stats = json!({
+            "path": "/var/lib/mario/rocksdb",
+            "cf_sizes": {"cf_task": 1024, "cf_task_checkpoints": 2048},
+            "total_size": 3072,
+            "auto_compaction": false,
+            "compaction_schedule": "0 * * * *",
+            "next_compaction": 3600,
+            "last_compaction": {"finished_at": 60, "duration_ms": 1500, "cfs": 11}
+        });
+        let text = render_db_stats(&stats);
+        println!("{}", text);
+        assert!(text.contains("next compaction:  1970-01-01T01:00:00Z"));
+        assert!(text.contains("last compaction:  1970-01-01T00:01:00Z (1500 ms)"));
+        assert!(text.contains("cf_task_checkpoints"));
+
+        let stats = json!({"compaction_schedule": "", "last_compaction": null});
+        let text = render_db_stats(&stats);
+        assert!(text.contains("schedule:         -"));
+        assert!(text.contains("last compaction:  -"));
+    }
+
+    //cargo test cmd::db::test::test_render_db_check -- --nocapture
+    #[test]
+    fn test_render_db_check() {
+        let report = json!({
+            "corrupt_statuses": [
+                {"key": "t1", "error": "task status format version 2 not supported, current version 1"}
+            ]
+        });
+        let text = render_db_check(&report);
+        println!("{}", text);
+        assert!(text.contains("corrupt task statuses: 1"));
+        assert!(text.contains("  t1"));
+        assert!(text.contains("version 2 not supported"));
+        assert!(
+            render_db_check(&json!({"corrupt_statuses": []})).contains("corrupt task statuses: 0")
+        );
+
+        let result = json!({
+            "deleted": ["t1"],
+            "remaining": [{"key": "t2", "error": "io error"}]
+        });
+        let text = render_db_repair(&result);
+        println!("{}", text);
+        assert!(text.contains("deleted:   1 corrupt task statuses"));
+        assert!(text.contains("remaining: 1 corrupt task statuses"));
+        assert!(text.contains("  t2"));
+    }
+
+    //cargo test cmd::db::test::test_render_backup -- --nocapture
+    #[test]
+    fn test_render_backup() {
+        let backup = BackupInfo {
+            backup_id: 2,
+            timestamp: 60,
+            size: 4096,
+            num_files: 5,
+        };
+        let text = render_backup(&backup, "/var/lib/mario/rocksdb");
+        println!("{}", text);
+        assert!(text.contains("backup:   2 (4096 bytes)"));
+        assert!(text.contains("restored: /var/lib/mario/rocksdb"));
+    }
+}
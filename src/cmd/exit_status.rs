@@ -0,0 +1,44 @@
+// 命令执行完成但结果未达预期
+pub const EXIT_CODE_FAILURE: i32 = 1;
+// 命令行参数错误，与 clap 一致
+pub const EXIT_CODE_USAGE: i32 = 2;
+// 服务未运行
+pub const EXIT_CODE_NOT_RUNNING: i32 = 3;
+// 任务等对象不存在
+pub const EXIT_CODE_NOT_FOUND: i32 = 4;
+// 与当前状态冲突，如已有实例持有 rocksdb 目录锁、stop 等待超时后服务仍在运行
+pub const EXIT_CODE_CONFLICT: i32 = 5;
+// 内部错误，对应 sysexits EX_SOFTWARE
+pub const EXIT_CODE_INTERNAL: i32 = 70;
+// 配置或运行环境检查未通过，对应 sysexits EX_CONFIG
+pub const EXIT_CODE_CONFIG: i32 = 78;
+
+/// 命令执行结果对应的进程退出状态，由 main 统一调用 exit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success,
+    Failure(i32),
+}
+
+impl ExitStatus {
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            0 => ExitStatus::Success,
+            c => ExitStatus::Failure(c),
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Failure(c) => *c,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        match self {
+            ExitStatus::Success => true,
+            ExitStatus::Failure(_) => false,
+        }
+    }
+}
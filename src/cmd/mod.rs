@@ -1,9 +1,36 @@
+mod cli_error;
+mod client;
 mod configcmd;
+mod db;
+mod exit_status;
 mod rootcmd;
+mod server;
+mod smoke;
 mod start;
+mod status;
 mod stop;
+mod task;
 
-pub use configcmd::new_config_cmd;
-pub use rootcmd::run_app;
+pub use cli_error::{
+    output_json, report_anyhow, report_error, set_output_json, CliError, CliErrorKind,
+};
+pub(crate) use client::{cli_unix_socket, http_request};
+pub use client::{set_cli_tls_options, CliTlsOptions};
+pub use configcmd::{new_config_cmd, print_config, print_effective_config};
+pub use db::{
+    check_db, new_db_cmd, repair_db, restore_db, show_db_job, show_db_stats, start_db_job,
+};
+pub use exit_status::{
+    ExitStatus, EXIT_CODE_CONFIG, EXIT_CODE_CONFLICT, EXIT_CODE_FAILURE, EXIT_CODE_INTERNAL,
+    EXIT_CODE_NOT_FOUND, EXIT_CODE_NOT_RUNNING, EXIT_CODE_USAGE,
+};
+pub use rootcmd::run_from;
+pub use server::{new_server_cmd, reload_server};
+pub use smoke::{new_smoke_cmd, SmokeTest, SMOKE_TASK_NAME_PREFIX};
 pub use start::new_start_cmd;
-pub use stop::new_stop_cmd;
+pub use status::{new_status_cmd, print_server_status};
+pub use stop::{new_stop_cmd, stop_by_pid_file, stop_server};
+pub use task::{
+    export_tasks, import_tasks, list_local_tasks, list_tasks, new_task_cmd, print_task_status,
+    show_local_task, show_task, watch_task,
+};
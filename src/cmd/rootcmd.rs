@@ -1,28 +1,45 @@
-use crate::cmd::{new_config_cmd, new_start_cmd, new_stop_cmd};
+use crate::cmd::{
+    check_db, cli_unix_socket, export_tasks, import_tasks, list_local_tasks, list_tasks,
+    new_config_cmd, new_db_cmd, new_server_cmd, new_smoke_cmd, new_start_cmd, new_status_cmd,
+    new_stop_cmd, new_task_cmd, output_json, print_config, print_effective_config,
+    print_server_status, print_task_status, reload_server, repair_db, report_anyhow, report_error,
+    restore_db, set_cli_tls_options, set_output_json, show_db_job, show_db_stats, show_local_task,
+    show_task, start_db_job, stop_by_pid_file, watch_task, CliErrorKind, CliTlsOptions, ExitStatus,
+    SmokeTest, EXIT_CODE_CONFIG, EXIT_CODE_INTERNAL,
+};
 
-use crate::configure::{generate_default_config, set_config_file_path};
-use crate::configure::{get_config, get_config_file_path, get_current_config_yml, set_config};
+use crate::configure::{
+    generate_default_config, set_config_file_path, set_config_overrides, ConfigOverrides,
+};
+use crate::configure::{get_config, get_config_file_path, set_config, set_config_file_value};
 
 use crate::httpserver;
-use crate::resources::init_resources;
+use crate::logger::set_log_level;
+use crate::resources::{
+    get_rocksdb_path, init_resources, init_rocksdb_with_config, open_global_rocksdb,
+    spawn_db_backup_scheduler, spawn_db_compaction_scheduler,
+};
+use crate::server::{
+    acquire_instance_lock, build_runtime, clean_stale_pid_file, dump_state_on_signal,
+    graceful_shutdown_on_signal, init_metrics, install_panic_hook, notify_ready, preflight_config,
+    preflight_runtime, reload_config_on_signal, set_http_server_alive,
+    shutdown_on_bootstrap_failure, spawn_self_stats_sampler, spawn_stats_refresher,
+    spawn_systemd_watchdog, spawn_webhook_dispatcher, start_daemon, InstanceLockedError,
+    PreflightFailure, RuntimeThreads, PID_FILE,
+};
 use crate::tasks::{
-    init_tasks_status_server, GLOBAL_TASK_JOINSET, GLOBAL_TASK_RUNTIME, GLOBAL_TASK_STOP_MARK_MAP,
+    ensure_task_name_index, init_global_task_runtime, init_tasks_status_server, load_task_queue,
+    resume_interrupted_tasks, schedule_queued_tasks, GLOBAL_TASK_JOINSET, GLOBAL_TASK_RUNTIME,
 };
+use anyhow::{anyhow, Context, Result};
 use clap::{Arg, ArgAction, ArgMatches};
-use fork::{daemon, Fork};
 use lazy_static::lazy_static;
-use signal_hook::consts::{SIGTERM, TERM_SIGNALS};
-use signal_hook::iterator::exfiltrator::WithOrigin;
-use signal_hook::iterator::SignalsInfo;
-use std::net::{self, IpAddr};
-use std::process::{exit, Command};
-use std::str::FromStr;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use std::{env, fs, thread};
-use sysinfo::{Pid, RefreshKind, System};
-use tokio::net::TcpListener;
-use tokio::runtime::{self, Runtime};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
 
 lazy_static! {
     static ref CLIAPP: clap::Command = clap::Command::new("serverframe-rs")
@@ -37,6 +54,36 @@ lazy_static! {
                 .value_name("FILE")
                 .help("Sets a custom config file")
         )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true)
+                .help("error output format, json prints {\"error\": {\"code\", \"message\"}} on stderr")
+        )
+        .arg(
+            Arg::new("socket")
+                .long("socket")
+                .value_name("PATH")
+                .global(true)
+                .help("access server through this unix socket, overrides http.unix of config and --server")
+        )
+        .arg(
+            Arg::new("insecure")
+                .long("insecure")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("skip server certificate verification for https server url")
+        )
+        .arg(
+            Arg::new("cacert")
+                .long("cacert")
+                .value_name("FILE")
+                .global(true)
+                .help("CA bundle to verify server certificate for https server url")
+        )
         .subcommand(
             new_start_cmd().arg(
                 Arg::new("daemon")
@@ -47,25 +94,51 @@ lazy_static! {
             )
         )
         .subcommand(new_stop_cmd())
-        .subcommand(new_config_cmd());
+        .subcommand(new_server_cmd())
+        .subcommand(new_task_cmd())
+        .subcommand(new_status_cmd())
+        .subcommand(new_config_cmd())
+        .subcommand(new_db_cmd())
+        .subcommand(new_smoke_cmd());
     // static ref SUBCMDS: Vec<SubCmd> = subcommands();
 }
 
-pub fn run_app() {
-    let matches = CLIAPP.clone().get_matches();
-    cmd_match(&matches);
+pub fn run_from(args: Vec<String>) -> Result<ExitStatus> {
+    match CLIAPP.clone().try_get_matches_from(args.clone()) {
+        Ok(matches) => {
+            set_output_json(
+                matches.get_one::<String>("output").map(|o| o.as_str()) == Some("json"),
+            );
+            set_cli_tls_options(CliTlsOptions {
+                insecure: matches.get_flag("insecure"),
+                cacert: matches.get_one::<String>("cacert").cloned(),
+            });
+            // 未分类的错误按内部错误处理
+            match cmd_match(&matches, &args) {
+                Ok(status) => Ok(status),
+                Err(e) => Ok(report_anyhow(&e)),
+            }
+        }
+        Err(err) => {
+            // 参数解析失败时 --output 尚未解析，直接检查参数
+            if err.use_stderr() && args_output_json(&args) {
+                set_output_json(true);
+                return Ok(report_error(CliErrorKind::Usage, err.to_string().trim()));
+            }
+            // --help、--version 等情况同样以 clap 错误返回，按其退出码处理
+            let code = err.exit_code();
+            err.print()?;
+            Ok(ExitStatus::from_code(code))
+        }
+    }
 }
 
-// pub fn run_from(args: Vec<String>) {
-//     match clap::Command::try_get_matches_from(CLIAPP.to_owned(), args.clone()) {
-//         Ok(matches) => {
-//             cmd_match(&matches);
-//         }
-//         Err(err) => {
-//             err.print().expect("Error writing Error");
-//         }
-//     };
-// }
+fn args_output_json(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--output=json")
+        || args
+            .windows(2)
+            .any(|w| w[0] == "--output" && w[1] == "json")
+}
 
 // 获取全部子命令，用于构建commandcompleter
 // pub fn all_subcommand(app: &clap::Command, beginlevel: usize, input: &mut Vec<SubCmd>) {
@@ -99,35 +172,44 @@ pub fn run_app() {
 //     subcmds
 // }
 
-fn cmd_match(matches: &ArgMatches) {
+fn cmd_match(matches: &ArgMatches, args: &[String]) -> Result<ExitStatus> {
     if let Some(c) = matches.get_one::<String>("config") {
         set_config_file_path(c.to_string());
-        set_config(&get_config_file_path());
-    } else {
-        set_config("");
     }
+    if let Err(e) = set_config(&get_config_file_path()) {
+        return Ok(report_error(CliErrorKind::Config, e));
+    }
+    // rocksdb 路径来自配置，start 打开 rocksdb 及 db restore 恢复前均需设置
+    init_rocksdb_with_config(&get_config()?);
 
     if let Some(ref matches) = matches.subcommand_matches("start") {
-        if matches.get_flag("daemon") {
-            let args: Vec<String> = env::args().collect();
-            if let Ok(Fork::Child) = daemon(true, true) {
-                // Start child thread
-                let mut cmd = Command::new(&args[0]);
-                for idx in 1..args.len() {
-                    let arg = args.get(idx).expect("get cmd arg error!");
-                    // remove start as daemon variable
-                    // 去除后台启动参数,避免重复启动
-                    if arg.eq("-d") || arg.eq("-daemon") {
-                        continue;
-                    }
-                    cmd.arg(arg);
-                }
-
-                let child = cmd.spawn().expect("Child process failed to start.");
-                fs::write("pid", child.id().to_string()).expect("Write pid file error!");
+        // 命令行指定的日志等级覆盖配置文件
+        match matches.get_one::<String>("log-level") {
+            Some(level) => set_log_level(level)?,
+            // 配置文件中的日志等级错误由 preflight 统一报告
+            None => {
+                let _ = set_log_level(&get_config()?.log.level);
             }
+        }
+
+        // 命令行指定的监听地址仅对本次运行生效
+        let overrides = ConfigOverrides {
+            http_bind: matches.get_one::<String>("bind").cloned(),
+            http_port: matches.get_one::<u16>("port").copied(),
+        };
+        if let Err(e) = set_config_overrides(overrides.clone()) {
+            return Ok(report_error(CliErrorKind::Config, e));
+        }
+
+        // 上次异常退出残留的 pid 文件可能指向其他进程，启动前删除
+        if let Err(e) = clean_stale_pid_file(PID_FILE) {
+            log::warn!("{}", e);
+        }
+
+        if matches.get_flag("daemon") {
+            start_daemon(args)?;
             println!("{}", "daemon mod");
-            std::process::exit(0);
+            return Ok(ExitStatus::Success);
         }
 
         let banner = r" 
@@ -149,11 +231,72 @@ fn cmd_match(matches: &ArgMatches) {
         |___|                                                                       |___| 
        (_____)---------------------------------------------------------------------(_____)";
 
-        println!("{}", banner);
-        println!("current pid is:{}", std::process::id());
+        // daemon 模式下由子进程持有锁，父进程在上方已返回
+        if let Err(e) = acquire_instance_lock(&get_rocksdb_path()) {
+            if let Some(locked) = e.downcast_ref::<InstanceLockedError>() {
+                return Ok(report_error(CliErrorKind::Conflict, locked));
+            }
+            return Err(e);
+        }
+
+        // 启动前检查全部执行完毕后统一输出，避免逐个修复逐个重启
+        let config = get_config()?;
+        let mut failures = preflight_config(&config);
+        failures.append(&mut preflight_runtime(&config));
+        if !failures.is_empty() {
+            return Ok(report_preflight_failures(&failures));
+        }
+
+        // 输出被 journald 等采集时 banner 会污染日志，--quiet、--output json 或 banner: false 时仅写日志
+        if matches.get_flag("quiet") || output_json() || !config.banner {
+            log::info!("{}", banner);
+            log::info!("current pid is:{}", std::process::id());
+        } else {
+            println!("{}", banner);
+            println!("current pid is:{}", std::process::id());
+        }
+        // 启动关键信息单行输出，便于日志检索
+        let config_path = match get_config_file_path() {
+            p if !p.is_empty() => p,
+            _ if Path::new("config.yml").exists() => "config.yml".to_string(),
+            _ => "default".to_string(),
+        };
+        log::info!(
+            "server starting version={} pid={} bind={} config={}",
+            env!("CARGO_PKG_VERSION"),
+            std::process::id(),
+            config
+                .http
+                .endpoints()
+                .map(|v| v
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","))
+                .unwrap_or_default(),
+            config_path
+        );
+        if !overrides.is_empty() {
+            log::info!("command line overrides: {}", overrides.fields().join(","));
+            if !config.http.listeners.is_empty() {
+                log::warn!("http.listeners is set, --bind and --port are ignored");
+            }
+        }
+        install_panic_hook();
+        // rocksdb 与任务 runtime 在首次访问前显式初始化，失败时报告原因后退出
+        if let Err(e) = open_global_rocksdb() {
+            return Ok(report_error(CliErrorKind::Internal, e));
+        }
+        if let Err(e) = init_global_task_runtime() {
+            return Ok(report_error(CliErrorKind::Internal, e));
+        }
+        // 启动时刻需先于任务恢复记录，上次停机情况在此读取
+        if let Err(e) = record_server_start() {
+            log::warn!("record server start error: {}", e);
+        }
 
         //启动公共 tokio runtime
-        GLOBAL_TASK_RUNTIME.block_on(async {
+        GLOBAL_TASK_RUNTIME.get()?.block_on(async {
             log::info!("global runtime start!");
             log::info!(
                 "global task joinset is empty:{}",
@@ -162,10 +305,36 @@ fn cmd_match(matches: &ArgMatches) {
         });
 
         // 初始化外部资源
-        let rt = Runtime::new().unwrap();
-        rt.block_on(async { init_resources().await.unwrap() });
+        let rt = Runtime::new()?;
+        rt.block_on(async { init_resources().await })?;
+        init_metrics();
+
+        // 早期安装或升级前没有 CF_TASK_NAME_IDX，按现有任务重建
+        match ensure_task_name_index() {
+            Ok(Some(names)) => log::info!("task name index rebuilt, {} names", names),
+            Ok(None) => {}
+            Err(e) => log::error!("rebuild task name index error: {}", e),
+        }
+
+        if get_config()?.task.resume_tasks_on_start {
+            match resume_interrupted_tasks() {
+                Ok((resumed, skipped)) => {
+                    log::info!("{} tasks resumed, {} skipped", resumed, skipped)
+                }
+                Err(e) => log::error!("resume tasks error: {}", e),
+            }
+        }
 
-        rt.spawn(async move { init_tasks_status_server().await });
+        // 恢复的任务占用名额后再启动上次停机时排队中的任务
+        match load_task_queue() {
+            Ok(queued) => {
+                log::info!("{} queued tasks loaded", queued);
+                GLOBAL_TASK_RUNTIME.spawn(schedule_queued_tasks())?;
+            }
+            Err(e) => log::error!("load task queue error: {}", e),
+        }
+
+        let status_saver = rt.spawn(async move { init_tasks_status_server().await });
 
         // let (tx, rx) = tokio::sync::oneshot::channel::<()>();
         // let async_http_server = async {
@@ -183,107 +352,494 @@ fn cmd_match(matches: &ArgMatches) {
         //     let _http = tokio::join!(http_handler);
         // };
 
-        let async_http_server = async {
-            let config = get_config().unwrap();
-            let bind = config.http.bind;
-            let port = config.http.port;
-
-            let mut http_server = httpserver::HttpServer::default().await;
-            let ip = IpAddr::from_str(&bind).unwrap();
-            let addr = net::SocketAddr::from((ip, port));
+        let grace = Duration::from_secs(get_config()?.task.shutdown_grace_secs);
+        let (http_shutdown_tx, http_shutdown_rx) = watch::channel(false);
+        let http_stopped_by_signal = http_shutdown_rx.clone();
 
-            http_server.listener = TcpListener::bind(addr).await.unwrap();
+        // http 启动失败返回错误，由 http 线程停止已启动的任务后上报退出状态
+        let async_http_server = async move {
+            let http = get_config()?.http;
+            let listeners = httpserver::bind_listeners(&http).await.with_context(|| {
+                format!(
+                    "invalid http config bind={} port={} listeners={:?} unix={:?}",
+                    http.bind, http.port, http.listeners, http.unix
+                )
+            })?;
+            for l in listeners.iter() {
+                log::info!("http server listen on {}", l.endpoint());
+            }
+            let http_server = httpserver::HttpServer::new(listeners)
+                .with_drain_timeout(Duration::from_secs(http.drain_timeout_secs));
 
-            let http_handler = http_server.run().await;
+            let http_handler = http_server
+                .run_with_graceful_shutdown(http_shutdown_rx)
+                .await;
+            // 监听地址已绑定后通知 systemd 就绪，rocksdb 在启动流程开始时已打开
+            set_http_server_alive(true);
+            notify_ready();
             let _http = tokio::join!(http_handler);
+            set_http_server_alive(false);
+            Ok::<(), anyhow::Error>(())
         };
 
-        let thread_http = thread::spawn(|| {
+        // http 线程与信号处理通过 channel 上报退出状态，由主线程统一返回
+        let (status_tx, status_rx) = mpsc::channel::<ExitStatus>();
+        let http_status_tx = status_tx.clone();
+        let status_saver = status_saver.abort_handle();
+        let http_threads = get_config()?.http;
+        let thread_http = thread::spawn(move || {
             // let rt = Runtime::new().unwrap();
-            let rt = runtime::Builder::new_multi_thread()
-                .worker_threads(num_cpus::get())
-                .enable_all()
-                .max_io_events_per_tick(32)
-                .build()
-                .unwrap();
-            rt.block_on(async_http_server);
+            let rt = match build_runtime(
+                "http",
+                RuntimeThreads::resolve(
+                    http_threads.worker_threads,
+                    http_threads.max_io_events_per_tick,
+                ),
+            ) {
+                Ok(rt) => rt,
+                Err(e) => {
+                    log::error!("build http runtime error: {}", e);
+                    let _ = http_status_tx.send(ExitStatus::Failure(EXIT_CODE_INTERNAL));
+                    return;
+                }
+            };
+            if let Err(e) = rt.block_on(async_http_server) {
+                log::error!("http server bootstrap failed: {:#}", e);
+                eprintln!("http server bootstrap failed: {:#}", e);
+                rt.block_on(shutdown_on_bootstrap_failure(status_saver, grace));
+                let _ = http_status_tx.send(ExitStatus::Failure(EXIT_CODE_INTERNAL));
+                return;
+            }
+            // 非停机流程导致的 http 退出视为异常
+            if !*http_stopped_by_signal.borrow() {
+                let _ = http_status_tx.send(ExitStatus::Failure(EXIT_CODE_INTERNAL));
+            }
         });
 
-        let thread_signale = thread::spawn(|| {
-            // 添加signal处理机制
-            let mut sigs = vec![];
-            sigs.extend(TERM_SIGNALS);
-            let mut signals = SignalsInfo::<WithOrigin>::new(&sigs).unwrap();
-            for info in &mut signals {
-                // Will print info about signal + where it comes from.
-                log::info!("Received a signal {:?}", info);
-                for kv in GLOBAL_TASK_STOP_MARK_MAP.iter() {
-                    kv.store(true, std::sync::atomic::Ordering::SeqCst);
-                }
-                // GLOBAL_TASK_STOP_MARK_MAP.store(true, std::sync::atomic::Ordering::SeqCst);
-                match info.signal {
-                    SIGTERM => {
-                        println!("kill !");
-                        exit(1);
-                    }
-                    term_sig => {
-                        eprintln!("Terminating......");
-                        // do some before exit
-                        exit(0)
-                    }
-                }
+        spawn_systemd_watchdog()?;
+        spawn_self_stats_sampler()?;
+        spawn_stats_refresher()?;
+        spawn_db_backup_scheduler()?;
+        spawn_db_compaction_scheduler()?;
+        spawn_webhook_dispatcher()?;
+
+        rt.spawn(async move {
+            if let Err(e) = reload_config_on_signal().await {
+                log::error!("{}", e);
             }
         });
-        thread_http.join().unwrap();
-        thread_signale.join().unwrap();
+        rt.spawn(async move {
+            if let Err(e) = dump_state_on_signal().await {
+                log::error!("{}", e);
+            }
+        });
+
+        // 信号处理运行于 tokio runtime，收到终止信号后执行优雅停机
+        rt.spawn(async move {
+            let status = match graceful_shutdown_on_signal(http_shutdown_tx, grace).await {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("{}", e);
+                    ExitStatus::Failure(EXIT_CODE_INTERNAL)
+                }
+            };
+            let _ = status_tx.send(status);
+        });
+        let status = status_rx
+            .recv()
+            .map_err(|e| anyhow!("server threads exited unexpectedly: {}", e))?;
+        // 正常停机时等待 http 线程处理完剩余请求，强制退出时不等待
+        if status.is_success() {
+            if thread_http.join().is_err() {
+                log::error!("http thread panicked");
+            }
+        }
+        return Ok(status);
     }
 
-    if let Some(ref _matches) = matches.subcommand_matches("stop") {
+    if let Some(stop) = matches.subcommand_matches("stop") {
         println!("server stopping...");
+        let timeout = stop.get_one::<u64>("timeout").copied().unwrap_or(30);
+        return stop_by_pid_file(
+            PID_FILE,
+            Duration::from_secs(timeout),
+            stop.get_flag("kill"),
+            stop.get_flag("clean"),
+        );
+    }
 
-        // let sys = System::new_with_specifics(RefreshKind::everything().without_disks_list());
-        let sys =
-            System::new_with_specifics(RefreshKind::everything().without_cpu().without_memory());
-        let pidstr = String::from_utf8(fs::read("pid").unwrap()).unwrap();
-        let pid = Pid::from_str(pidstr.as_str()).unwrap();
+    if let Some(server_cmd) = matches.subcommand_matches("server") {
+        if let Some(reload) = server_cmd.subcommand_matches("reload") {
+            let server = reload
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let timeout = reload.get_one::<u64>("timeout").copied().unwrap_or(10);
+            return Ok(reload_server(
+                PID_FILE,
+                server,
+                cli_unix_socket(reload).as_deref(),
+                Duration::from_secs(timeout),
+            ));
+        }
+    }
 
-        if let Some(p) = sys.process(pid) {
-            println!("terminal process: {:?}", p.pid());
-        } else {
-            println!("Server not run!");
-            return;
-        };
-        Command::new("kill")
-            .args(["-15", pidstr.as_str()])
-            .output()
-            .expect("failed to execute process");
+    if let Some(db_cmd) = matches.subcommand_matches("db") {
+        for path in ["compact", "backup", "prune", "reindex"] {
+            if let Some(sub) = db_cmd.subcommand_matches(path) {
+                let server = sub
+                    .get_one::<String>("server")
+                    .ok_or_else(|| anyhow!("server not set"))?;
+                let mut query = vec![];
+                if let Some(cf) = sub.try_get_one::<String>("cf").ok().flatten() {
+                    query.push(("cf", cf.clone()));
+                }
+                if sub.try_get_one::<bool>("dry-run").ok().flatten() == Some(&true) {
+                    query.push(("dry_run", "true".to_string()));
+                }
+                return Ok(start_db_job(
+                    server,
+                    cli_unix_socket(sub).as_deref(),
+                    path,
+                    &query,
+                    sub.get_flag("wait"),
+                ));
+            }
+        }
+        if let Some(restore) = db_cmd.subcommand_matches("restore") {
+            let backup_dir = match restore.get_one::<String>("backup-dir") {
+                Some(d) => d.clone(),
+                None => get_config()?.db.backup_dir,
+            };
+            let target = match restore.get_one::<String>("target") {
+                Some(t) => t.clone(),
+                None => get_rocksdb_path(),
+            };
+            let backup_id = restore.get_one::<u32>("backup-id").copied();
+            return Ok(restore_db(&backup_dir, backup_id, &target));
+        }
+        if let Some(check) = db_cmd.subcommand_matches("check") {
+            let server = check
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            return Ok(check_db(server, cli_unix_socket(check).as_deref()));
+        }
+        if let Some(repair) = db_cmd.subcommand_matches("repair") {
+            if !repair.get_flag("delete-corrupt") {
+                return Ok(report_error(
+                    CliErrorKind::Usage,
+                    "nothing to repair, use --delete-corrupt",
+                ));
+            }
+            let server = repair
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            return Ok(repair_db(
+                server,
+                cli_unix_socket(repair).as_deref(),
+                repair.get_flag("yes"),
+            ));
+        }
+        if let Some(stats) = db_cmd.subcommand_matches("stats") {
+            let server = stats
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            return Ok(show_db_stats(server, cli_unix_socket(stats).as_deref()));
+        }
+        if let Some(job) = db_cmd.subcommand_matches("job") {
+            let server = job
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let job_id = job
+                .get_one::<String>("job_id")
+                .ok_or_else(|| anyhow!("job_id not set"))?;
+            return Ok(show_db_job(server, cli_unix_socket(job).as_deref(), job_id));
+        }
+    }
+
+    if let Some(task_cmd) = matches.subcommand_matches("task") {
+        if let Some(list) = task_cmd.subcommand_matches("list") {
+            if list.get_flag("local") {
+                return Ok(list_local_tasks(
+                    list.get_one::<String>("type"),
+                    list.get_one::<String>("name"),
+                ));
+            }
+            let server = list
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            return Ok(list_tasks(
+                server,
+                cli_unix_socket(list).as_deref(),
+                &[
+                    ("status", list.get_one::<String>("status")),
+                    ("type", list.get_one::<String>("type")),
+                    ("name", list.get_one::<String>("name")),
+                ],
+                list.get_one::<String>("search"),
+            ));
+        }
+        if let Some(status) = task_cmd.subcommand_matches("status") {
+            let server = status
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let task_id = status
+                .get_one::<String>("task_id")
+                .ok_or_else(|| anyhow!("task_id not set"))?;
+            return Ok(print_task_status(
+                server,
+                cli_unix_socket(status).as_deref(),
+                task_id,
+            ));
+        }
+        if let Some(show) = task_cmd.subcommand_matches("show") {
+            if show.get_flag("local") {
+                let task_id = show
+                    .get_one::<String>("task_id")
+                    .ok_or_else(|| anyhow!("task_id not set"))?;
+                return Ok(show_local_task(task_id));
+            }
+            let server = show
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let task_id = show
+                .get_one::<String>("task_id")
+                .ok_or_else(|| anyhow!("task_id not set"))?;
+            return Ok(show_task(
+                server,
+                cli_unix_socket(show).as_deref(),
+                task_id,
+                show.get_flag("runs"),
+            ));
+        }
+        if let Some(watch) = task_cmd.subcommand_matches("watch") {
+            let server = watch
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let task_id = watch
+                .get_one::<String>("task_id")
+                .ok_or_else(|| anyhow!("task_id not set"))?;
+            let interval = watch.get_one::<u64>("interval").copied().unwrap_or(2);
+            return Ok(watch_task(
+                server,
+                cli_unix_socket(watch).as_deref(),
+                task_id,
+                Duration::from_secs(interval),
+            ));
+        }
+        if let Some(export) = task_cmd.subcommand_matches("export") {
+            let server = export
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            return Ok(export_tasks(
+                server,
+                cli_unix_socket(export).as_deref(),
+                export.get_one::<String>("file"),
+                export.get_flag("redact"),
+            ));
+        }
+        if let Some(import) = task_cmd.subcommand_matches("import") {
+            let server = import
+                .get_one::<String>("server")
+                .ok_or_else(|| anyhow!("server not set"))?;
+            let file = import
+                .get_one::<String>("file")
+                .ok_or_else(|| anyhow!("file not set"))?;
+            return Ok(import_tasks(
+                server,
+                cli_unix_socket(import).as_deref(),
+                file,
+                import.get_flag("keep_ids"),
+            ));
+        }
+    }
+
+    if let Some(status) = matches.subcommand_matches("status") {
+        let server = status
+            .get_one::<String>("server")
+            .ok_or_else(|| anyhow!("server not set"))?;
+        return Ok(print_server_status(
+            server,
+            cli_unix_socket(status).as_deref(),
+        ));
+    }
+
+    if let Some(smoke) = matches.subcommand_matches("smoke") {
+        let server = smoke
+            .get_one::<String>("server")
+            .ok_or_else(|| anyhow!("server not set"))?;
+        let timeout = smoke.get_one::<u64>("timeout").copied().unwrap_or(60);
+        let mut smoke_test = SmokeTest::new(
+            server,
+            cli_unix_socket(smoke),
+            smoke.get_flag("keep"),
+            Duration::from_secs(timeout),
+        )?;
+        return Ok(smoke_test.run());
     }
 
     if let Some(config) = matches.subcommand_matches("config") {
-        if let Some(_show) = config.subcommand_matches("show") {
-            let yml = get_current_config_yml();
-            match yml {
-                Ok(str) => {
-                    println!("{}", str);
-                }
-                Err(e) => {
-                    eprintln!("{}", e);
+        if let Some(show) = config.subcommand_matches("show") {
+            let format = show
+                .get_one::<String>("format")
+                .map(|f| f.as_str())
+                .unwrap_or("yaml");
+            if show.get_flag("effective") {
+                let server = show
+                    .get_one::<String>("server")
+                    .ok_or_else(|| anyhow!("server not set"))?;
+                return Ok(print_effective_config(
+                    server,
+                    cli_unix_socket(show).as_deref(),
+                    format,
+                ));
+            }
+            return Ok(print_config(show.get_flag("defaults"), format));
+        }
+
+        if let Some(set) = config.subcommand_matches("set") {
+            let key = set
+                .get_one::<String>("key")
+                .ok_or_else(|| anyhow!("key not set"))?;
+            let value = set
+                .get_one::<String>("value")
+                .ok_or_else(|| anyhow!("value not set"))?;
+            let dry_run = set.get_flag("dry-run");
+            return match set_config_file_value(&get_config_file_path(), key, value, dry_run) {
+                Ok(yml) => {
+                    match dry_run {
+                        true => println!("{}", yml),
+                        false => println!("{} set to {}", key, value),
+                    }
+                    Ok(ExitStatus::Success)
                 }
+                Err(e) => Ok(report_error(CliErrorKind::Config, e)),
+            };
+        }
+
+        if let Some(_validate) = config.subcommand_matches("validate") {
+            let failures = preflight_config(&get_config()?);
+            if !failures.is_empty() {
+                return Ok(report_preflight_failures(&failures));
             }
+            println!("config ok");
+            return Ok(ExitStatus::Success);
         }
 
         if let Some(gen_config) = config.subcommand_matches("gendefault") {
             let mut file = String::from("");
-            if let Some(path) = gen_config.get_one::<&str>("filepath") {
+            if let Some(path) = gen_config.get_one::<String>("filepath") {
                 file.push_str(path);
             } else {
                 file.push_str("config_default.yml")
             }
-            if let Err(e) = generate_default_config(file.as_str()) {
-                log::error!("{}", e);
-                return;
-            };
+            generate_default_config(file.as_str())?;
             println!("{} created!", file);
+            return Ok(ExitStatus::Success);
         }
     }
+
+    Ok(ExitStatus::Success)
+}
+
+fn report_preflight_failures(failures: &[PreflightFailure]) -> ExitStatus {
+    if output_json() {
+        let message = failures
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>()
+            .join("; ");
+        return report_error(
+            CliErrorKind::Config,
+            format!("preflight check failed: {}", message),
+        );
+    }
+    eprintln!("preflight check failed:");
+    for f in failures {
+        eprintln!("  {}", f);
+    }
+    ExitStatus::Failure(EXIT_CODE_CONFIG)
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_from;
+    use crate::cmd::{ExitStatus, EXIT_CODE_CONFIG, EXIT_CODE_NOT_RUNNING, EXIT_CODE_USAGE};
+    use crate::resources::open_test_rocksdb;
+
+    fn args(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    //cargo test cmd::rootcmd::test::test_run_from_config -- --nocapture
+    #[test]
+    fn test_run_from_config() {
+        // 先打开测试共用的 rocksdb，配置中的路径不会覆盖全局 rocksdb 路径
+        open_test_rocksdb();
+        let config_file = "/tmp/run_from_test/config.yml";
+        std::fs::create_dir_all("/tmp/run_from_test").unwrap();
+
+        let status = run_from(args(&["mario", "config", "gendefault", config_file])).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+        assert!(std::path::Path::new(config_file).exists());
+
+        let status = run_from(args(&["mario", "-c", config_file, "config", "show"])).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+
+        let set = |key: &str, value: &str| {
+            run_from(args(&[
+                "mario",
+                "--output",
+                "json",
+                "-c",
+                config_file,
+                "config",
+                "set",
+                key,
+                value,
+            ]))
+            .unwrap()
+        };
+        assert_eq!(
+            set("http.port", "abc"),
+            ExitStatus::Failure(EXIT_CODE_CONFIG)
+        );
+        assert_eq!(set("http.port", "3001"), ExitStatus::Success);
+
+        let status = run_from(args(&[
+            "mario",
+            "-c",
+            config_file,
+            "status",
+            "--server",
+            "http://127.0.0.1:1",
+        ]))
+        .unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+        let status = run_from(args(&[
+            "mario",
+            "-c",
+            config_file,
+            "status",
+            "--socket",
+            "/tmp/run_from_test/no_such.sock",
+        ]))
+        .unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+
+        // 配置文件无法解析
+        std::fs::write(config_file, "http: [").unwrap();
+        let status = run_from(args(&["mario", "-c", config_file, "config", "show"])).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_CONFIG));
+
+        let _ = std::fs::remove_dir_all("/tmp/run_from_test");
+    }
+
+    //cargo test cmd::rootcmd::test::test_run_from_usage_error -- --nocapture
+    #[test]
+    fn test_run_from_usage_error() {
+        let status = run_from(args(&["mario", "no_such_cmd"])).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_USAGE));
+        let status = run_from(args(&["mario", "--output", "json", "no_such_cmd"])).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_USAGE));
+    }
 }
@@ -0,0 +1,230 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{report_error, CliErrorKind, ExitStatus};
+use crate::server::{check_pid_file, send_reload_signal, PidFileStatus};
+use clap::{Arg, Command};
+use serde_json::Value;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 轮询重载状态的间隔
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub fn new_server_cmd() -> Command {
+    clap::Command::new("server")
+        .about("manage running server")
+        .subcommand(server_reload_cmd())
+}
+
+fn server_reload_cmd() -> Command {
+    clap::Command::new("reload")
+        .about("send SIGHUP to running server and wait for config reload, exit 78 when rejected, 3 when server not running")
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("10")
+                .help("seconds to wait for reload result"),
+        )
+}
+
+/// 重载结果
+#[derive(Debug, Clone, PartialEq)]
+enum ReloadOutcome {
+    Applied {
+        applied: Vec<String>,
+        restart_required: Vec<String>,
+    },
+    Rejected(String),
+}
+
+fn string_list(v: &Value) -> Vec<String> {
+    match v.as_array() {
+        Some(a) => a
+            .iter()
+            .filter_map(|s| s.as_str().map(|s| s.to_string()))
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// 比较发送信号前后的重载状态，尚未完成重载时返回 None
+fn reload_outcome(before: &Value, after: &Value) -> Option<ReloadOutcome> {
+    if after["generation"].as_u64() > before["generation"].as_u64() {
+        return Some(ReloadOutcome::Applied {
+            applied: string_list(&after["applied"]),
+            restart_required: string_list(&after["restart_required"]),
+        });
+    }
+    if after["attempts"].as_u64() > before["attempts"].as_u64()
+        && after["last_reload_ok"].as_bool() == Some(false)
+    {
+        return Some(ReloadOutcome::Rejected(
+            after["last_error"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string(),
+        ));
+    }
+    None
+}
+
+fn get_reload_status(server: &str, unix_socket: Option<&str>) -> anyhow::Result<Value> {
+    let url = format!(
+        "{}/api/v1/admin/config/reload",
+        server.trim_end_matches('/')
+    );
+    let resp = http_request(&url, None, unix_socket)?;
+    match resp["code"].as_i64() {
+        Some(0) => Ok(resp["data"].clone()),
+        _ => Err(anyhow::anyhow!("{}", response_message(&resp))),
+    }
+}
+
+/// 经 pid 文件找到服务进程并发送 SIGHUP，轮询重载状态确认配置版本号递增
+pub fn reload_server(
+    pid_file: &str,
+    server: &str,
+    unix_socket: Option<&str>,
+    timeout: Duration,
+) -> ExitStatus {
+    let pid = match check_pid_file(pid_file) {
+        Ok(PidFileStatus::Running(pid)) => pid,
+        Ok(s) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", s),
+            )
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    let before = match get_reload_status(server, unix_socket) {
+        Ok(s) if s["generation"].is_u64() => s,
+        Ok(_) | Err(_) => {
+            return report_error(
+                CliErrorKind::Failure,
+                format!(
+                    "server {} does not report reload status, it may not support reload",
+                    pid
+                ),
+            );
+        }
+    };
+    if let Err(e) = send_reload_signal(pid.as_u32()) {
+        return report_error(CliErrorKind::Internal, e);
+    }
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        thread::sleep(RELOAD_POLL_INTERVAL);
+        let after = match get_reload_status(server, unix_socket) {
+            Ok(s) => s,
+            Err(e) => {
+                log::debug!("get reload status error: {}", e);
+                continue;
+            }
+        };
+        match reload_outcome(&before, &after) {
+            Some(ReloadOutcome::Applied {
+                applied,
+                restart_required,
+            }) => {
+                println!(
+                    "config reloaded, generation {}",
+                    after["generation"].as_u64().unwrap_or_default()
+                );
+                if applied.is_empty() && restart_required.is_empty() {
+                    println!("nothing changed");
+                }
+                if !applied.is_empty() {
+                    println!("applied: {}", applied.join(", "));
+                }
+                if !restart_required.is_empty() {
+                    println!("restart required: {}", restart_required.join(", "));
+                }
+                return ExitStatus::Success;
+            }
+            Some(ReloadOutcome::Rejected(e)) => {
+                return report_error(
+                    CliErrorKind::Config,
+                    format!("config reload rejected, running config kept: {}", e),
+                );
+            }
+            None => {}
+        }
+    }
+    report_error(
+        CliErrorKind::Failure,
+        format!(
+            "reload not confirmed in {}s, check server log",
+            timeout.as_secs()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{reload_outcome, reload_server, ReloadOutcome};
+    use crate::cmd::{ExitStatus, EXIT_CODE_NOT_RUNNING};
+    use serde_json::json;
+    use std::time::Duration;
+
+    //cargo test cmd::server::test::test_reload_server_not_running -- --nocapture
+    #[test]
+    fn test_reload_server_not_running() {
+        let status = reload_server(
+            "/tmp/mario_reload_test_no_such.pid",
+            "http://127.0.0.1:1",
+            None,
+            Duration::from_secs(1),
+        );
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+    }
+
+    //cargo test cmd::server::test::test_reload_outcome -- --nocapture
+    #[test]
+    fn test_reload_outcome() {
+        let before = json!({"generation": 1, "attempts": 2, "last_reload_ok": false});
+        assert_eq!(reload_outcome(&before, &before), None);
+
+        let applied = json!({
+            "generation": 2,
+            "attempts": 3,
+            "last_reload_ok": true,
+            "applied": ["log.level"],
+            "restart_required": ["http.port"]
+        });
+        assert_eq!(
+            reload_outcome(&before, &applied),
+            Some(ReloadOutcome::Applied {
+                applied: vec!["log.level".to_string()],
+                restart_required: vec!["http.port".to_string()]
+            })
+        );
+
+        let rejected = json!({
+            "generation": 1,
+            "attempts": 3,
+            "last_reload_ok": false,
+            "last_error": "task.checkpoint_interval must be greater than 0"
+        });
+        assert_eq!(
+            reload_outcome(&before, &rejected),
+            Some(ReloadOutcome::Rejected(
+                "task.checkpoint_interval must be greater than 0".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{report_error, CliErrorKind, ExitStatus};
+use crate::tasks::{ObjectStorage, Task, TransferTask};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, Command};
+use serde_json::{json, Value};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// 冒烟测试任务名前缀，便于识别残留任务
+pub const SMOKE_TASK_NAME_PREFIX: &'static str = "__mario_smoke__";
+const SMOKE_WORK_DIR: &'static str = "/tmp/mario_smoke";
+
+pub fn new_smoke_cmd() -> Command {
+    clap::Command::new("smoke")
+        .about("run smoke test against a running server, must run on the server host")
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .action(ArgAction::SetTrue)
+                .help("keep smoke task and files for debugging"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("60")
+                .help("seconds to wait for task completion"),
+        )
+}
+
+/// 冒烟测试，逐步打印执行结果，任一步骤失败则返回非零退出码
+pub struct SmokeTest {
+    server: String,
+    // 配置了 http.unix 且未指定 --server 时经由 unix socket 访问
+    unix_socket: Option<String>,
+    keep: bool,
+    timeout: Duration,
+    work_dir: String,
+    task_id: Option<String>,
+    // 启动返回的 run_id，任务排队时为 None
+    run_id: Option<String>,
+    failed: usize,
+}
+
+impl SmokeTest {
+    pub fn new(
+        server: &str,
+        unix_socket: Option<String>,
+        keep: bool,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            unix_socket,
+            keep,
+            timeout,
+            work_dir: format!("{}/{}", SMOKE_WORK_DIR, ts),
+            task_id: None,
+            run_id: None,
+            failed: 0,
+        })
+    }
+
+    pub fn run(&mut self) -> ExitStatus {
+        let created = self.step("health", |s| s.health())
+            && self.step("prepare source files", |s| s.prepare())
+            && self.step("create task", |s| s.create());
+        if created {
+            let _ = self.step("start task", |s| s.start())
+                && self.step("wait for completion", |s| s.wait_completion())
+                && self.step("verify checkpoint", |s| s.verify_checkpoint())
+                && self.step("verify run record", |s| s.verify_run())
+                && self.step("status", |s| s.status())
+                && self.step("show", |s| s.show())
+                && self.step("list", |s| s.list())
+                && self.step("list with filters", |s| s.list_filtered())
+                && self.step("history", |s| s.history());
+        }
+
+        if self.keep {
+            println!(
+                "[SKIP] cleanup (--keep), task_id: {:?}, work_dir: {}",
+                self.task_id, self.work_dir
+            );
+        } else {
+            if self.task_id.is_some() {
+                self.step("remove task", |s| s.remove());
+            }
+            self.step("remove source files", |s| {
+                fs::remove_dir_all(&s.work_dir)?;
+                Ok(())
+            });
+        }
+
+        match self.failed {
+            0 => {
+                println!("smoke test passed");
+                ExitStatus::Success
+            }
+            n => report_error(
+                CliErrorKind::Failure,
+                format!("smoke test failed, {} step(s) failed", n),
+            ),
+        }
+    }
+
+    fn step<F>(&mut self, name: &str, f: F) -> bool
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let begin = Instant::now();
+        match f(self) {
+            Ok(_) => {
+                println!("[PASS] {} ({}ms)", name, begin.elapsed().as_millis());
+                true
+            }
+            Err(e) => {
+                println!("[FAIL] {}: {}", name, e);
+                self.failed += 1;
+                false
+            }
+        }
+    }
+
+    fn task_id(&self) -> Result<String> {
+        self.task_id
+            .clone()
+            .ok_or_else(|| anyhow!("smoke task not created"))
+    }
+
+    fn health(&mut self) -> Result<()> {
+        http_request(
+            &format!("{}/api/v1/health", self.server),
+            None,
+            self.unix_socket.as_deref(),
+        )?;
+        Ok(())
+    }
+
+    fn prepare(&mut self) -> Result<()> {
+        let source = format!("{}/source", self.work_dir);
+        fs::create_dir_all(&source)?;
+        for i in 0..3 {
+            fs::write(
+                format!("{}/smoke_{}.txt", source, i),
+                format!("smoke {}", i),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn task_name(&self) -> String {
+        format!(
+            "{}{}",
+            SMOKE_TASK_NAME_PREFIX,
+            self.work_dir
+                .trim_start_matches(SMOKE_WORK_DIR)
+                .trim_matches('/')
+        )
+    }
+
+    fn create(&mut self) -> Result<()> {
+        let mut transfer = TransferTask::default();
+        transfer.name = self.task_name();
+        transfer.source = ObjectStorage::Local(format!("{}/source", self.work_dir));
+        transfer.target = ObjectStorage::Local(format!("{}/target", self.work_dir));
+        let body = serde_json::to_value(Task::Transfer(transfer))?;
+        let data = self.api("/api/v1/task/create", body)?;
+        let id = data["task_id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("task_id not in response"))?;
+        self.task_id = Some(id.to_string());
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let data = self.api("/api/v1/task/start", json!({"task_id": self.task_id()?}))?;
+        self.run_id = data["run_id"].as_str().map(|id| id.to_string());
+        Ok(())
+    }
+
+    fn wait_completion(&mut self) -> Result<()> {
+        let url = format!("{}/api/v1/task/{}/completion", self.server, self.task_id()?);
+        let begin = Instant::now();
+        loop {
+            if let Ok(resp) = http_request(&url, None, self.unix_socket.as_deref()) {
+                if resp["code"].as_i64() == Some(0) {
+                    return Ok(());
+                }
+            }
+            if begin.elapsed() > self.timeout {
+                return Err(anyhow!("not completed in {}s", self.timeout.as_secs()));
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    fn verify_checkpoint(&mut self) -> Result<()> {
+        let task_id = self.task_id()?;
+        let data = self.get(&format!("/api/v1/task/{}/checkpoint/export", task_id))?;
+        match data["checkpoint"]["task_id"].as_str() {
+            Some(id) if id == task_id => Ok(()),
+            _ => Err(anyhow!("checkpoint not exist")),
+        }
+    }
+
+    // 运行记录在执行协程退出后补全，经 wait 接口等待任务停止后读取
+    fn verify_run(&mut self) -> Result<()> {
+        let data = self.get(&format!("/api/v1/task/{}/wait?timeout=5", self.task_id()?))?;
+        let run = &data["run"];
+        if run.is_null() {
+            return Err(anyhow!("run record not exist"));
+        }
+        if let Some(run_id) = &self.run_id {
+            if run["run_id"].as_str() != Some(run_id.as_str()) {
+                return Err(anyhow!(
+                    "latest run is {}, expect {}",
+                    run["run_id"],
+                    run_id
+                ));
+            }
+        }
+        match run["state"].as_str() {
+            Some("completed") => Ok(()),
+            _ => Err(anyhow!("run not completed: {}", run)),
+        }
+    }
+
+    fn status(&mut self) -> Result<()> {
+        let data = self.api("/api/v1/task/status", json!({"task_id": self.task_id()?}))?;
+        if data["task_id"].as_str() != Some(self.task_id()?.as_str()) {
+            return Err(anyhow!("checkpoint not exist"));
+        }
+        if data["completed"].as_bool() != Some(true) {
+            return Err(anyhow!("completion marker not exist"));
+        }
+        Ok(())
+    }
+
+    fn show(&mut self) -> Result<()> {
+        let data = self.api("/api/v1/task/show", json!({"task_id": self.task_id()?}))?;
+        match data["name"].as_str() {
+            Some(name) if name.starts_with(SMOKE_TASK_NAME_PREFIX) => Ok(()),
+            _ => Err(anyhow!("unexpected task: {}", data)),
+        }
+    }
+
+    fn list(&mut self) -> Result<()> {
+        let task_id = self.task_id()?;
+        let data = self.api("/api/v1/task/all", json!({}))?;
+        let found = data
+            .as_array()
+            .map(|v| v.iter().any(|t| t["cf_id"].as_str() == Some(&task_id)))
+            .unwrap_or(false);
+        match found {
+            true => Ok(()),
+            false => Err(anyhow!("task {} not in list", task_id)),
+        }
+    }
+
+    fn list_filtered(&mut self) -> Result<()> {
+        let task_id = self.task_id()?;
+        let path = format!(
+            "/api/v1/task/all?type=transfer&status=stopped&name={}",
+            self.task_name()
+        );
+        let data = self.api(&path, json!({}))?;
+        let ids = data
+            .as_array()
+            .map(|v| {
+                v.iter()
+                    .filter_map(|t| t["cf_id"].as_str())
+                    .collect::<Vec<&str>>()
+            })
+            .unwrap_or_default();
+        match ids.as_slice() {
+            [id] if *id == task_id => Ok(()),
+            _ => Err(anyhow!("expect only task {}, got {:?}", task_id, ids)),
+        }
+    }
+
+    fn history(&mut self) -> Result<()> {
+        let data = self.get(&format!("/api/v1/task/{}/runs", self.task_id()?))?;
+        let runs = data["runs"].as_array().cloned().unwrap_or_default();
+        let found = match &self.run_id {
+            Some(run_id) => runs
+                .iter()
+                .any(|r| r["run_id"].as_str() == Some(run_id.as_str())),
+            None => !runs.is_empty(),
+        };
+        match found {
+            true => Ok(()),
+            false => Err(anyhow!("run not in history")),
+        }
+    }
+
+    fn remove(&mut self) -> Result<()> {
+        self.api(
+            "/api/v1/task/remove",
+            json!({"task_ids": [self.task_id()?]}),
+        )?;
+        Ok(())
+    }
+
+    /// 调用接口并校验返回码，返回 data 字段
+    fn api(&self, path: &str, body: Value) -> Result<Value> {
+        self.request(path, Some(body))
+    }
+
+    fn get(&self, path: &str) -> Result<Value> {
+        self.request(path, None)
+    }
+
+    fn request(&self, path: &str, body: Option<Value>) -> Result<Value> {
+        let url = format!("{}{}", self.server, path);
+        let resp = http_request(&url, body, self.unix_socket.as_deref())?;
+        if resp["code"].as_i64() != Some(0) {
+            return Err(anyhow!("{}: {}", path, response_message(&resp)));
+        }
+        Ok(resp["data"].clone())
+    }
+}
@@ -1,5 +1,50 @@
-use clap::Command;
+use crate::configure::parse_bind_ip;
+use crate::logger::parse_log_level;
+use clap::{Arg, ArgAction, Command};
 
 pub fn new_start_cmd() -> Command {
-    clap::Command::new("start").about("start")
+    clap::Command::new("start")
+        .about("start")
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .value_parser(log_level_parser)
+                .help("trace|debug|info|warn|error, support module override like file_pipe_server::tasks=debug,info"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .help("do not print banner to stdout"),
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("ADDR")
+                .value_parser(bind_parser)
+                .help("override http.bind for this run"),
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .value_name("PORT")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .help("override http.port for this run"),
+        )
+}
+
+fn bind_parser(bind: &str) -> Result<String, String> {
+    match parse_bind_ip(bind) {
+        Ok(_) => Ok(bind.trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn log_level_parser(level: &str) -> Result<String, String> {
+    match parse_log_level(level) {
+        Ok(_) => Ok(level.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
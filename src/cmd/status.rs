@@ -0,0 +1,225 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{report_error, CliErrorKind, ExitStatus};
+use crate::commons::unix_secs_to_rfc3339;
+use crate::configure::ConfigReloadStatus;
+use crate::httpserver::module::RespServerInfo;
+use crate::server::{check_pid_file, LastStop, PID_FILE};
+use clap::{Arg, Command};
+
+pub fn new_status_cmd() -> Command {
+    clap::Command::new("status")
+        .about("show server start time, uptime and whether last stop was clean")
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+/// 通过 /api/v1/info 接口获取服务状态，服务不可达时返回非零退出码
+pub fn print_server_status(server: &str, unix_socket: Option<&str>) -> ExitStatus {
+    match check_pid_file(PID_FILE) {
+        Ok(s) if s.is_stale() => eprintln!("{}, run `stop --clean` to remove it", s),
+        Ok(_) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+    let url = format!("{}/api/v1/info", server.trim_end_matches('/'));
+    let info = match http_request(&url, None, unix_socket) {
+        Ok(resp) if resp["code"].as_i64() == Some(0) => {
+            match serde_json::from_value::<RespServerInfo>(resp["data"].clone()) {
+                Ok(info) => info,
+                Err(e) => {
+                    return report_error(
+                        CliErrorKind::Internal,
+                        format!("parse server info error: {}", e),
+                    )
+                }
+            }
+        }
+        Ok(resp) => {
+            return report_error(
+                CliErrorKind::Internal,
+                format!("server status error: {}", response_message(&resp)),
+            )
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    for line in server_info_lines(&info) {
+        println!("{}", line);
+    }
+    ExitStatus::Success
+}
+
+fn server_info_lines(info: &RespServerInfo) -> Vec<String> {
+    let mut lines = vec![
+        match &info.build.git_commit {
+            Some(commit) => format!("version: {} ({})", info.version, commit),
+            None => format!("version: {}", info.version),
+        },
+        format!("pid: {}", info.pid),
+        format!(
+            "start time: {}",
+            info.start_time.as_deref().unwrap_or("unknown")
+        ),
+        format!("uptime: {}s", info.uptime_seconds),
+        format!("listen: {}", info.http_endpoints.join(",")),
+        format!("living tasks: {}", info.living_tasks),
+        format!("log level: {}", info.log_level),
+        format!("config generation: {}", info.config_generation),
+        config_reload_line(&info.config_reload),
+        format!("rocksdb: {}", info.rocksdb_path),
+        format!("meta dir: {}", info.meta_dir),
+    ];
+    if let Some(cpu) = info
+        .self_stats_last_hour
+        .as_ref()
+        .and_then(|s| s.cpu_percent.as_ref())
+    {
+        lines.push(format!(
+            "cpu last hour: min {:.1}% avg {:.1}% max {:.1}%",
+            cpu.min, cpu.avg, cpu.max
+        ));
+    }
+    for (name, threads) in info.runtime_threads.iter() {
+        lines.push(format!(
+            "{} runtime: {} worker threads",
+            name, threads.worker_threads
+        ));
+    }
+    lines.push(last_stop_line(info.last_stop.as_ref()));
+    if let Some(dirty) = info
+        .last_stop
+        .as_ref()
+        .and_then(|s| s.dirty_shutdown.as_ref())
+    {
+        lines.push(format!(
+            "previous run panicked at {}: {}",
+            dirty.panicked_at, dirty.message
+        ));
+    }
+    lines
+}
+
+fn config_reload_line(reload: &ConfigReloadStatus) -> String {
+    let at = match reload.last_reload_timestamp {
+        Some(ts) => unix_secs_to_rfc3339(ts),
+        None => return "last config reload: none".to_string(),
+    };
+    match (reload.last_reload_ok, &reload.last_error) {
+        (Some(false), Some(e)) => format!("last config reload failed at {}: {}", at, e),
+        _ => format!("last config reload ok at {}", at),
+    }
+}
+
+fn last_stop_line(last_stop: Option<&LastStop>) -> String {
+    let last_stop = match last_stop {
+        Some(s) => s,
+        None => return "last stop: none, first start".to_string(),
+    };
+    match last_stop.clean {
+        true => format!(
+            "last stop was clean at {}",
+            last_stop.stopped_at.as_deref().unwrap_or("unknown")
+        ),
+        false => format!(
+            "last stop was unclean, previous run started at {}, checkpoints may lag behind",
+            last_stop.started_at
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::server_info_lines;
+    use crate::configure::{ConfigOverrides, ConfigReloadStatus};
+    use crate::httpserver::module::{RespBuildInfo, RespServerInfo};
+    use crate::server::{LastStop, MinAvgMax, RuntimeThreads, SelfStatsSummary};
+    use std::collections::BTreeMap;
+
+    //cargo test cmd::status::test::test_server_info_lines -- --nocapture
+    #[test]
+    fn test_server_info_lines() {
+        let mut runtime_threads = BTreeMap::new();
+        runtime_threads.insert(
+            "task".to_string(),
+            RuntimeThreads {
+                worker_threads: 8,
+                max_io_events_per_tick: 1024,
+            },
+        );
+        let info = RespServerInfo {
+            version: "0.1.0".to_string(),
+            build: RespBuildInfo {
+                git_commit: Some("abc1234".to_string()),
+                profile: "release".to_string(),
+                target: "x86_64-linux".to_string(),
+            },
+            pid: 42,
+            start_time: Some("2024-01-01T00:00:00Z".to_string()),
+            uptime_seconds: 60,
+            last_stop: Some(LastStop {
+                clean: true,
+                started_at: "2023-12-31T00:00:00Z".to_string(),
+                stopped_at: Some("2023-12-31T01:00:00Z".to_string()),
+                dirty_shutdown: None,
+            }),
+            runtime_threads,
+            http_endpoints: vec!["127.0.0.1:3000".to_string()],
+            config_overrides: ConfigOverrides::default(),
+            config_env_overrides: vec![],
+            rocksdb_path: "/tmp/rocksdb".to_string(),
+            meta_dir: "meta_dir".to_string(),
+            log_level: "info".to_string(),
+            living_tasks: 2,
+            config_generation: 3,
+            config_reload: ConfigReloadStatus {
+                generation: 3,
+                attempts: 4,
+                last_reload_timestamp: Some(0),
+                last_reload_ok: Some(false),
+                last_error: Some("task.checkpoint_interval must be greater than 0".to_string()),
+                applied: vec![],
+                restart_required: vec![],
+            },
+            self_stats_last_hour: Some(SelfStatsSummary {
+                samples: 2,
+                cpu_percent: Some(MinAvgMax {
+                    min: 10.0,
+                    avg: 20.0,
+                    max: 30.0,
+                }),
+                rss_bytes: None,
+                open_fds: None,
+            }),
+        };
+        // 命令行按服务端同一结构解析
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(
+            serde_json::from_value::<RespServerInfo>(json).unwrap(),
+            info
+        );
+
+        let lines = server_info_lines(&info);
+        println!("{}", lines.join("\n"));
+        assert_eq!(lines[0], "version: 0.1.0 (abc1234)");
+        assert!(lines.contains(&"living tasks: 2".to_string()));
+        assert!(lines.contains(&"config generation: 3".to_string()));
+        assert!(lines.contains(&"cpu last hour: min 10.0% avg 20.0% max 30.0%".to_string()));
+        assert!(lines.contains(
+            &"last config reload failed at 1970-01-01T00:00:00Z: task.checkpoint_interval must be greater than 0"
+                .to_string()
+        ));
+        assert!(lines.contains(&"task runtime: 8 worker threads".to_string()));
+        assert_eq!(
+            lines.last().unwrap(),
+            "last stop was clean at 2023-12-31T01:00:00Z"
+        );
+    }
+}
@@ -1,5 +1,203 @@
-use clap::Command;
+use crate::cmd::{report_error, CliErrorKind, ExitStatus};
+use crate::server::{
+    check_pid_file, kill_process, process_alive, terminate_process, PidFileStatus,
+};
+use anyhow::{Context, Result};
+use clap::{Arg, ArgAction, Command};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+
+// 轮询进程是否退出的间隔
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
 
 pub fn new_stop_cmd() -> Command {
-    clap::Command::new("stop").about("stop")
+    clap::Command::new("stop")
+        .about("stop")
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("30")
+                .help("seconds to wait for server exit after SIGTERM"),
+        )
+        .arg(
+            Arg::new("kill")
+                .long("kill")
+                .action(ArgAction::SetTrue)
+                .help("send SIGKILL when server still running after timeout"),
+        )
+        .arg(
+            Arg::new("clean")
+                .long("clean")
+                .action(ArgAction::SetTrue)
+                .help("remove stale pid file whose process is not this server"),
+        )
+}
+
+/// 等待进程退出，超时返回 false
+fn wait_process_exit(sys: &mut System, pid: Pid, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if !process_alive(sys, pid) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+}
+
+/// 发送 SIGTERM 并等待进程退出，超时后 kill 为 true 时发送 SIGKILL 再次等待，否则返回非零退出码
+/// 进程已不存在时返回服务未运行
+pub fn stop_server(pid: Pid, timeout: Duration, kill: bool) -> Result<ExitStatus> {
+    let mut sys = System::new();
+    if !process_alive(&mut sys, pid) {
+        return Ok(report_error(CliErrorKind::NotRunning, "Server not run!"));
+    }
+    println!("terminal process: {:?}", pid);
+    let now = Instant::now();
+    terminate_process(pid.as_u32())?;
+    if wait_process_exit(&mut sys, pid, timeout) {
+        println!("server stopped in {:.1}s", now.elapsed().as_secs_f64());
+        return Ok(ExitStatus::Success);
+    }
+    if !kill {
+        return Ok(report_error(
+            CliErrorKind::Conflict,
+            format!(
+                "server {} still running after {}s, retry with --kill to send SIGKILL",
+                pid,
+                timeout.as_secs()
+            ),
+        ));
+    }
+
+    println!(
+        "server {} still running after {}s, sending SIGKILL",
+        pid,
+        timeout.as_secs()
+    );
+    kill_process(pid.as_u32())?;
+    if wait_process_exit(&mut sys, pid, timeout) {
+        println!("server killed in {:.1}s", now.elapsed().as_secs_f64());
+        return Ok(ExitStatus::Success);
+    }
+    Ok(report_error(
+        CliErrorKind::Conflict,
+        format!("server {} still running after SIGKILL", pid),
+    ))
+}
+
+/// 校验 pid 文件后停止服务，pid 已被其他进程复用时不发送信号，clean 为 true 时删除失效的 pid 文件
+/// pid 文件不存在或已失效时返回服务未运行，clean 删除失效的 pid 文件时返回成功
+pub fn stop_by_pid_file(
+    path: &str,
+    timeout: Duration,
+    kill: bool,
+    clean: bool,
+) -> Result<ExitStatus> {
+    let status = check_pid_file(path)?;
+    match &status {
+        PidFileStatus::Missing => {
+            return Ok(report_error(CliErrorKind::NotRunning, "Server not run!"));
+        }
+        PidFileStatus::Running(pid) => return stop_server(*pid, timeout, kill),
+        _ => {}
+    }
+    if clean {
+        eprintln!("{}", status);
+        fs::remove_file(path).with_context(|| format!("remove pid file {} error", path))?;
+        println!("pid file {} removed", path);
+        return Ok(ExitStatus::Success);
+    }
+    // 服务已退出或 pid 指向其他进程，服务均未运行
+    Ok(report_error(
+        CliErrorKind::NotRunning,
+        format!("{}, run `stop --clean` to remove it", status),
+    ))
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::{stop_by_pid_file, stop_server};
+    use crate::cmd::{ExitStatus, EXIT_CODE_CONFLICT, EXIT_CODE_NOT_RUNNING};
+    use std::process::Command;
+    use std::time::Duration;
+    use sysinfo::Pid;
+
+    // 后台线程回收子进程，避免残留僵尸进程
+    fn spawn_reaped(script: &str) -> Pid {
+        let mut child = Command::new("sh").args(["-c", script]).spawn().unwrap();
+        let pid = Pid::from_u32(child.id());
+        std::thread::spawn(move || child.wait());
+        // 等待 sh 设置 trap
+        std::thread::sleep(Duration::from_millis(300));
+        pid
+    }
+
+    //cargo test cmd::stop::test::test_stop_server_sigterm -- --nocapture
+    #[test]
+    fn test_stop_server_sigterm() {
+        let pid = spawn_reaped("sleep 30");
+        let status = stop_server(pid, Duration::from_secs(5), false).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+        // 进程已退出
+        let status = stop_server(pid, Duration::from_secs(1), false).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+    }
+
+    //cargo test cmd::stop::test::test_stop_server_timeout_and_kill -- --nocapture
+    #[test]
+    fn test_stop_server_timeout_and_kill() {
+        let pid = spawn_reaped("trap '' TERM; while true; do sleep 1; done");
+        let status = stop_server(pid, Duration::from_secs(1), false).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_CONFLICT));
+
+        let status = stop_server(pid, Duration::from_secs(1), true).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+    }
+
+    //cargo test cmd::stop::test::test_stop_by_stale_pid_file -- --nocapture
+    #[test]
+    fn test_stop_by_stale_pid_file() {
+        // pid 文件指向其他程序的进程
+        let pid = spawn_reaped("sleep 30");
+        let path = format!("/tmp/mario_stop_test_{}.pid", std::process::id());
+        std::fs::write(&path, pid.to_string()).unwrap();
+        let status = stop_by_pid_file(&path, Duration::from_secs(1), false, false).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+        assert!(std::path::Path::new(&path).exists());
+
+        let status = stop_by_pid_file(&path, Duration::from_secs(1), false, true).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+        assert!(!std::path::Path::new(&path).exists());
+        let _ = stop_server(pid, Duration::from_secs(5), true);
+    }
+
+    //cargo test cmd::stop::test::test_stop_not_running -- --nocapture
+    #[test]
+    fn test_stop_not_running() {
+        let path = format!("/tmp/mario_stop_test_missing_{}.pid", std::process::id());
+        let _ = std::fs::remove_file(&path);
+        // pid 文件不存在
+        let status = stop_by_pid_file(&path, Duration::from_secs(1), false, false).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+
+        // pid 文件指向已退出的进程
+        let pid = spawn_reaped("exit 0");
+        std::fs::write(&path, pid.to_string()).unwrap();
+        let status = stop_by_pid_file(&path, Duration::from_secs(1), false, false).unwrap();
+        assert_eq!(status, ExitStatus::Failure(EXIT_CODE_NOT_RUNNING));
+        assert!(std::path::Path::new(&path).exists());
+
+        // clean 删除失效的 pid 文件后返回成功
+        let status = stop_by_pid_file(&path, Duration::from_secs(1), false, true).unwrap();
+        assert_eq!(status, ExitStatus::Success);
+        assert!(!std::path::Path::new(&path).exists());
+    }
 }
@@ -0,0 +1,1065 @@
+use super::client::{http_request, response_message};
+use crate::cmd::{output_json, report_error, CliErrorKind, ExitStatus, EXIT_CODE_FAILURE};
+use crate::commons::unix_secs_to_rfc3339;
+use crate::resources::{find_task_from, get_rocksdb_path, list_tasks_from, with_secondary_rocksdb};
+use crate::tasks::{parse_task_export, redacted_definition};
+use clap::{Arg, Command};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 服务不可达时重试间隔上限
+const WATCH_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub fn new_task_cmd() -> Command {
+    clap::Command::new("task")
+        .about(
+            "task operations against running server, list and show also read rocksdb with --local",
+        )
+        .subcommand(task_watch_cmd())
+        .subcommand(task_list_cmd())
+        .subcommand(task_status_cmd())
+        .subcommand(task_show_cmd())
+        .subcommand(task_export_cmd())
+        .subcommand(task_import_cmd())
+}
+
+fn task_export_cmd() -> Command {
+    clap::Command::new("export")
+        .about("export all task definitions as one json document")
+        .arg(
+            Arg::new("file")
+                .long("file")
+                .value_name("FILE")
+                .help("write to file instead of stdout"),
+        )
+        .arg(
+            Arg::new("redact")
+                .long("redact")
+                .action(clap::ArgAction::SetTrue)
+                .help("redact credentials, always on for non-admin tokens"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+fn task_import_cmd() -> Command {
+    clap::Command::new("import")
+        .about("create tasks from task export json document or ndjson")
+        .arg(Arg::new("file").value_name("FILE").required(true).index(1))
+        .arg(
+            Arg::new("keep_ids")
+                .long("keep-ids")
+                .action(clap::ArgAction::SetTrue)
+                .help("keep exported task ids, entries whose id exists fail"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+fn task_show_cmd() -> Command {
+    clap::Command::new("show")
+        .about("show task definition")
+        .arg(
+            Arg::new("task_id")
+                .value_name("TASK_ID")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("runs")
+                .long("runs")
+                .action(clap::ArgAction::SetTrue)
+                .help("also show recent runs, newest first"),
+        )
+        .arg(
+            Arg::new("local")
+                .long("local")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("runs")
+                .help("read rocksdb directly in read-only mode instead of asking the server"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+fn task_status_cmd() -> Command {
+    clap::Command::new("status")
+        .about("show task state merged from persisted and live status")
+        .arg(
+            Arg::new("task_id")
+                .value_name("TASK_ID")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+fn task_list_cmd() -> Command {
+    clap::Command::new("list")
+        .about("list tasks, filters compose")
+        .arg(
+            Arg::new("status")
+                .long("status")
+                .value_parser(["living", "stopped", "error"])
+                .help("task status, error means broken or success criteria not met"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .value_parser(["transfer", "compare"])
+                .help("task type"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .value_name("SUBSTRING")
+                .help("case-insensitive substring of task name"),
+        )
+        .arg(
+            Arg::new("search")
+                .long("search")
+                .value_name("TEXT")
+                .conflicts_with_all(["status", "type", "name"])
+                .help("case-insensitive text matched against task name, endpoint, bucket, region or local path"),
+        )
+        .arg(
+            Arg::new("local")
+                .long("local")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["status", "search"])
+                .help("read rocksdb directly in read-only mode instead of asking the server"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+}
+
+fn task_watch_cmd() -> Command {
+    clap::Command::new("watch")
+        .about("show live task progress until task stopped")
+        .arg(
+            Arg::new("task_id")
+                .value_name("TASK_ID")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000")
+                .help("server url"),
+        )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("2")
+                .help("seconds between status polls"),
+        )
+}
+
+/// 过滤条件拼接为查询参数
+fn task_list_query(filters: &[(&str, Option<&String>)]) -> String {
+    let query = filters
+        .iter()
+        .filter_map(|(k, v)| v.map(|v| format!("{}={}", k, percent_encode(v))))
+        .collect::<Vec<String>>()
+        .join("&");
+    match query.is_empty() {
+        true => query,
+        false => format!("?{}", query),
+    }
+}
+
+// 查询参数编码，保留字母数字与 -_.~
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// 按过滤条件列出任务，指定 search 时改用搜索接口，每行输出 id、类型与名称
+pub fn list_tasks(
+    server: &str,
+    unix_socket: Option<&str>,
+    filters: &[(&str, Option<&String>)],
+    search: Option<&String>,
+) -> ExitStatus {
+    let server = server.trim_end_matches('/');
+    let (url, body) = match search {
+        Some(q) => (
+            format!(
+                "{}/api/v1/task/search{}",
+                server,
+                task_list_query(&[("q", Some(q))])
+            ),
+            None,
+        ),
+        None => (
+            format!("{}/api/v1/task/all{}", server, task_list_query(filters)),
+            Some(json!({})),
+        ),
+    };
+    let tasks = match http_request(&url, body, unix_socket) {
+        Ok(resp) if resp["code"].as_i64() == Some(0) => resp["data"].clone(),
+        Ok(resp) => {
+            return report_error(
+                CliErrorKind::Internal,
+                format!("list tasks error: {}", response_message(&resp)),
+            )
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    for t in tasks.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        println!("{}", task_list_row(t));
+    }
+    ExitStatus::Success
+}
+
+// 列表条目为 cf_id 与完整任务，搜索结果为任务摘要
+fn task_list_row(t: &Value) -> String {
+    let (id, summary) = match t.get("cf_id") {
+        Some(id) => (id, &t["task"]),
+        None => (&t["task_id"], t),
+    };
+    format!(
+        "{}\t{}\t{}",
+        id.as_str().unwrap_or_default(),
+        summary["type"].as_str().unwrap_or_default(),
+        summary["name"].as_str().unwrap_or_default()
+    )
+}
+
+// 与服务端的过滤条件一致：类型相同，名称为忽略大小写的子串
+fn local_task_matches(task: &Value, task_type: Option<&String>, name: Option<&String>) -> bool {
+    if let Some(t) = task_type {
+        if !task["type"]
+            .as_str()
+            .unwrap_or_default()
+            .eq_ignore_ascii_case(t)
+        {
+            return false;
+        }
+    }
+    match name {
+        Some(n) => task["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_lowercase()
+            .contains(&n.to_lowercase()),
+        None => true,
+    }
+}
+
+/// 不经服务端，以只读方式直接读取 rocksdb 列出任务，服务运行或停止时均可使用
+pub fn list_local_tasks(task_type: Option<&String>, name: Option<&String>) -> ExitStatus {
+    let tasks = match with_secondary_rocksdb(&get_rocksdb_path(), list_tasks_from) {
+        Ok(t) => t,
+        Err(e) => return report_error(CliErrorKind::Internal, format!("list tasks error: {}", e)),
+    };
+    for (task_id, task) in tasks {
+        let task = match redacted_definition(&task) {
+            Ok(t) => t,
+            Err(e) => return report_error(CliErrorKind::Internal, e.to_string()),
+        };
+        if local_task_matches(&task, task_type, name) {
+            println!(
+                "{}",
+                task_list_row(&json!({ "cf_id": task_id, "task": task }))
+            );
+        }
+    }
+    ExitStatus::Success
+}
+
+/// 以只读方式直接读取 rocksdb 输出任务定义，凭证脱敏
+pub fn show_local_task(task_id: &str) -> ExitStatus {
+    let task = match with_secondary_rocksdb(&get_rocksdb_path(), |db| find_task_from(db, task_id)) {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return report_error(
+                CliErrorKind::NotFound,
+                format!("task {} not exist", task_id),
+            )
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::Internal,
+                format!("show task {} error: {}", task_id, e),
+            )
+        }
+    };
+    let task = match redacted_definition(&task) {
+        Ok(t) => t,
+        Err(e) => return report_error(CliErrorKind::Internal, e.to_string()),
+    };
+    match output_json() {
+        true => println!("{}", task),
+        false => match serde_json::to_string_pretty(&task) {
+            Ok(s) => println!("{}", s),
+            Err(_) => println!("{}", task),
+        },
+    }
+    ExitStatus::Success
+}
+
+// 状态值输出为单行 json，不存在时为 -
+fn compact_value(v: &Value) -> String {
+    match v {
+        Value::Null => "-".to_string(),
+        v => v.to_string(),
+    }
+}
+
+// 原因为字符串，或 setup_failed 等以原因为 key、错误信息为值的对象
+fn skip_reason_line(record: &Value) -> Option<String> {
+    let reason = match &record["reason"] {
+        Value::String(s) => s.clone(),
+        Value::Object(o) => {
+            let (k, v) = o.iter().next()?;
+            format!("{}: {}", k, v.as_str().unwrap_or_default())
+        }
+        _ => return None,
+    };
+    Some(format!(
+        "last skip reason: {} at {}",
+        reason,
+        match record["timestamp"].as_u64().filter(|t| *t > 0) {
+            Some(t) => unix_secs_to_rfc3339(t),
+            None => "-".to_string(),
+        }
+    ))
+}
+
+fn render_task_status(data: &Value) -> String {
+    let mut lines = vec![format!(
+        "task {}: {}{}",
+        data["task_id"].as_str().unwrap_or_default(),
+        data["effective_state"].as_str().unwrap_or("-"),
+        match data["stale"].as_bool() {
+            Some(true) => " (stale: persisted and live status disagree)",
+            _ => "",
+        }
+    )];
+    if let Some(line) = skip_reason_line(&data["last_skip_reason"]) {
+        lines.push(line);
+    }
+    lines.push(format!(
+        "persisted: {}",
+        compact_value(&data["persisted"]["status"])
+    ));
+    lines.push(format!("live: {}", compact_value(&data["live"]["status"])));
+    let checkpoint = &data["checkpoint"];
+    lines.push(match checkpoint.is_null() {
+        true => "checkpoint: -".to_string(),
+        false => format!(
+            "checkpoint: stage {} line {} offset {} at {}",
+            checkpoint["task_stage"].as_str().unwrap_or("-"),
+            checkpoint["executing_file_position"]["line_num"]
+                .as_u64()
+                .unwrap_or(0),
+            checkpoint["executing_file_position"]["offset"]
+                .as_u64()
+                .unwrap_or(0),
+            match checkpoint["timestamp"].as_u64().filter(|t| *t > 0) {
+                Some(t) => unix_secs_to_rfc3339(t),
+                None => "-".to_string(),
+            }
+        ),
+    });
+    lines.join("\n")
+}
+
+/// 输出任务合并后的状态，--output json 时输出接口返回的完整状态
+pub fn print_task_status(server: &str, unix_socket: Option<&str>, task_id: &str) -> ExitStatus {
+    let url = format!(
+        "{}/api/v1/task/{}/status",
+        server.trim_end_matches('/'),
+        percent_encode(task_id)
+    );
+    let resp = match http_request(&url, None, unix_socket) {
+        Ok(r) => r,
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    if resp["code"].as_i64() != Some(0) {
+        return report_error(
+            status_error_kind(&resp),
+            format!(
+                "get task {} status error: {}",
+                task_id,
+                response_message(&resp)
+            ),
+        );
+    }
+    match output_json() {
+        true => println!("{}", resp["data"]),
+        false => println!("{}", render_task_status(&resp["data"])),
+    }
+    ExitStatus::Success
+}
+
+// 停止原因为字符串或以原因为 key 的对象
+fn stop_reason_label(reason: &Value) -> String {
+    match reason {
+        Value::String(s) => s.to_lowercase(),
+        Value::Object(o) => match o.keys().next() {
+            Some(k) => k.to_lowercase(),
+            None => "-".to_string(),
+        },
+        _ => "-".to_string(),
+    }
+}
+
+fn render_task_runs(runs: &Value) -> String {
+    let mut lines =
+        vec!["RUN_ID\tSTATE\tSTART\tEND\tOBJECTS\tBYTES\tERRORS\tSTOP_REASON".to_string()];
+    let ts = |v: &Value| match v.as_u64() {
+        Some(t) => unix_secs_to_rfc3339(t),
+        None => "-".to_string(),
+    };
+    for r in runs.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        lines.push(format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            r["run_id"].as_str().unwrap_or_default(),
+            r["state"].as_str().unwrap_or("-"),
+            ts(&r["start_ts"]),
+            ts(&r["end_ts"]),
+            r["objects_transferred"].as_u64().unwrap_or(0),
+            r["bytes_transferred"].as_u64().unwrap_or(0),
+            r["errors"].as_u64().unwrap_or(0),
+            stop_reason_label(&r["stop_reason"])
+        ));
+    }
+    lines.join("\n")
+}
+
+fn get_task_data(
+    url: &str,
+    body: Option<Value>,
+    unix_socket: Option<&str>,
+    task_id: &str,
+) -> Result<Value, ExitStatus> {
+    let resp = match http_request(url, body, unix_socket) {
+        Ok(r) => r,
+        Err(e) => {
+            return Err(report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            ))
+        }
+    };
+    match resp["code"].as_i64() == Some(0) {
+        true => Ok(resp["data"].clone()),
+        false => Err(report_error(
+            status_error_kind(&resp),
+            format!("show task {} error: {}", task_id, response_message(&resp)),
+        )),
+    }
+}
+
+/// 输出任务定义，runs 为 true 时一并输出最近的运行记录；--output json 时合并为一个对象
+pub fn show_task(server: &str, unix_socket: Option<&str>, task_id: &str, runs: bool) -> ExitStatus {
+    let server = server.trim_end_matches('/');
+    let task = match get_task_data(
+        &format!("{}/api/v1/task/show", server),
+        Some(json!({ "task_id": task_id })),
+        unix_socket,
+        task_id,
+    ) {
+        Ok(t) => t,
+        Err(status) => return status,
+    };
+    let runs = match runs {
+        true => match get_task_data(
+            &format!("{}/api/v1/task/{}/runs", server, percent_encode(task_id)),
+            None,
+            unix_socket,
+            task_id,
+        ) {
+            Ok(r) => Some(r["runs"].clone()),
+            Err(status) => return status,
+        },
+        false => None,
+    };
+    if output_json() {
+        match runs {
+            Some(runs) => println!("{}", json!({ "task": task, "runs": runs })),
+            None => println!("{}", task),
+        }
+        return ExitStatus::Success;
+    }
+    // 启动被拒绝的原因输出在任务定义之前，状态获取失败时不影响任务定义的输出
+    let status_url = format!("{}/api/v1/task/{}/status", server, percent_encode(task_id));
+    if let Ok(resp) = http_request(&status_url, None, unix_socket) {
+        if let Some(line) = skip_reason_line(&resp["data"]["last_skip_reason"]) {
+            println!("{}", line);
+        }
+    }
+    match serde_json::to_string_pretty(&task) {
+        Ok(s) => println!("{}", s),
+        Err(_) => println!("{}", task),
+    }
+    if let Some(runs) = runs {
+        println!();
+        println!("{}", render_task_runs(&runs));
+    }
+    ExitStatus::Success
+}
+
+/// 导出全部任务定义，未指定 file 时输出到标准输出
+pub fn export_tasks(
+    server: &str,
+    unix_socket: Option<&str>,
+    file: Option<&String>,
+    redact: bool,
+) -> ExitStatus {
+    let url = format!(
+        "{}/api/v1/task/export?redact={}",
+        server.trim_end_matches('/'),
+        redact
+    );
+    let export = match http_request(&url, None, unix_socket) {
+        Ok(resp) if resp["code"].as_i64() == Some(0) => resp["data"].clone(),
+        Ok(resp) => {
+            return report_error(
+                CliErrorKind::Internal,
+                format!("export tasks error: {}", response_message(&resp)),
+            )
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    let text = match serde_json::to_string_pretty(&export) {
+        Ok(s) => s,
+        Err(e) => return report_error(CliErrorKind::Internal, e.to_string()),
+    };
+    match file {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, text) {
+                return report_error(
+                    CliErrorKind::Failure,
+                    format!("write {} error: {}", path, e),
+                );
+            }
+            eprintln!(
+                "exported {} tasks to {}",
+                export["tasks"].as_array().map(|a| a.len()).unwrap_or(0),
+                path
+            );
+        }
+        None => println!("{}", text),
+    }
+    ExitStatus::Success
+}
+
+// 每行输出序号、原 task_id 与新 task_id 或错误
+fn render_import_results(results: &Value) -> String {
+    let mut failed = 0;
+    let mut lines = vec![];
+    for r in results.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        let source = r["source_task_id"].as_str().unwrap_or("-");
+        let line = match r["task_id"].as_str() {
+            Some(id) => format!("{}\t{}\t-> {}", r["index"], source, id),
+            None => {
+                failed += 1;
+                format!(
+                    "{}\t{}\terror: {}",
+                    r["index"],
+                    source,
+                    r["error"].as_str().unwrap_or("-")
+                )
+            }
+        };
+        lines.push(line);
+    }
+    lines.push(format!(
+        "imported {}, failed {}",
+        lines.len() - failed,
+        failed
+    ));
+    lines.join("\n")
+}
+
+/// 本地解析导出文件后提交，任一条目失败时返回非零退出码
+pub fn import_tasks(
+    server: &str,
+    unix_socket: Option<&str>,
+    file: &str,
+    keep_ids: bool,
+) -> ExitStatus {
+    let export = match std::fs::read(file)
+        .map_err(anyhow::Error::from)
+        .and_then(|b| parse_task_export(&b))
+        .and_then(|e| Ok(serde_json::to_value(e)?))
+    {
+        Ok(e) => e,
+        Err(e) => return report_error(CliErrorKind::Usage, format!("read {} error: {}", file, e)),
+    };
+    let url = format!(
+        "{}/api/v1/task/import?keep_ids={}",
+        server.trim_end_matches('/'),
+        keep_ids
+    );
+    let results = match http_request(&url, Some(export), unix_socket) {
+        Ok(resp) if resp["code"].as_i64() == Some(0) => resp["data"].clone(),
+        Ok(resp) => {
+            let kind = match resp["code"].as_str() {
+                Some("invalid_request") => CliErrorKind::Usage,
+                _ => CliErrorKind::Internal,
+            };
+            return report_error(
+                kind,
+                format!("import tasks error: {}", response_message(&resp)),
+            );
+        }
+        Err(e) => {
+            return report_error(
+                CliErrorKind::NotRunning,
+                format!("server not running: {}", e),
+            )
+        }
+    };
+    match output_json() {
+        true => println!("{}", results),
+        false => println!("{}", render_import_results(&results)),
+    }
+    let failed = results
+        .as_array()
+        .map(|a| a.iter().filter(|r| r["task_id"].is_null()).count())
+        .unwrap_or(0);
+    match failed {
+        0 => ExitStatus::Success,
+        n => report_error(CliErrorKind::Failure, format!("{} tasks not imported", n)),
+    }
+}
+
+/// 任务所处状态，stopped 为 Some 时任务已停止，值表示是否正常结束
+#[derive(Debug, Clone, PartialEq)]
+struct WatchState {
+    label: String,
+    stopped: Option<bool>,
+}
+
+fn watch_state(resp: &Value) -> WatchState {
+    let status = &resp["status"];
+    if let Some(reason) = status["Transfer"].get("Stopped") {
+        let finished = reason.as_str() == Some("Finish");
+        let label = match reason {
+            Value::String(s) => format!("stopped({})", s.to_lowercase()),
+            Value::Object(o) => match o.keys().next() {
+                Some(k) => format!("stopped({})", k.to_lowercase()),
+                None => "stopped".to_string(),
+            },
+            _ => "stopped".to_string(),
+        };
+        return WatchState {
+            label,
+            stopped: Some(finished),
+        };
+    }
+    if let Some(stage) = status["Transfer"].get("Running") {
+        return WatchState {
+            label: format!("running({})", stage.as_str().unwrap_or("").to_lowercase()),
+            stopped: None,
+        };
+    }
+    if status["Compare"].as_str() == Some("Stopped") {
+        return WatchState {
+            label: "stopped".to_string(),
+            stopped: Some(true),
+        };
+    }
+    // 完成标识存在即存量任务已结束
+    if resp["completed"].as_bool() == Some(true) {
+        return WatchState {
+            label: "completed".to_string(),
+            stopped: Some(true),
+        };
+    }
+    match status {
+        Value::Null => WatchState {
+            label: "not started".to_string(),
+            stopped: None,
+        },
+        _ => WatchState {
+            label: "starting".to_string(),
+            stopped: None,
+        },
+    }
+}
+
+/// 任务进度，对象数取自列表文件行数，列表字节数为列表文件读取位置
+/// bytes_transferred 为本次运行已传输的对象字节数，任务停止后为 None
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct WatchProgress {
+    objects_done: u64,
+    objects_total: u64,
+    bytes_transferred: Option<u64>,
+    list_bytes_done: u64,
+    list_bytes_total: u64,
+    checkpoint_timestamp: Option<u64>,
+}
+
+fn watch_progress(resp: &Value) -> WatchProgress {
+    // 执行中位置新于 checkpoint，任务停止后只有 checkpoint
+    let position = match resp["executing_position"].is_null() {
+        true => &resp["executing_file_position"],
+        false => &resp["executing_position"],
+    };
+    WatchProgress {
+        objects_done: position["line_num"].as_u64().unwrap_or(0),
+        objects_total: resp["executing_file"]["total_lines"].as_u64().unwrap_or(0),
+        bytes_transferred: resp["run_counters"]["bytes_transferred"].as_u64(),
+        list_bytes_done: position["offset"].as_u64().unwrap_or(0),
+        list_bytes_total: resp["executing_file"]["size"].as_u64().unwrap_or(0),
+        checkpoint_timestamp: resp["modify_checkpoint_timestamp"]
+            .as_u64()
+            .filter(|t| *t > 0),
+    }
+}
+
+fn format_eta(secs: u64) -> String {
+    match secs {
+        s if s >= 3600 => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// 单行进度，throughput 为对象数每秒
+fn render_progress(state: &WatchState, p: &WatchProgress, throughput: Option<f64>) -> String {
+    let eta = match throughput {
+        Some(t) if t > 0.0 => {
+            let remaining = p.objects_total.saturating_sub(p.objects_done);
+            format_eta((remaining as f64 / t).ceil() as u64)
+        }
+        _ => "-".to_string(),
+    };
+    format!(
+        "{} objects {}/{} bytes {} list bytes {}/{} {} eta {} checkpoint {}",
+        state.label,
+        p.objects_done,
+        p.objects_total,
+        match p.bytes_transferred {
+            Some(b) => b.to_string(),
+            None => "-".to_string(),
+        },
+        p.list_bytes_done,
+        p.list_bytes_total,
+        match throughput {
+            Some(t) => format!("{:.1} obj/s", t),
+            None => "- obj/s".to_string(),
+        },
+        eta,
+        match p.checkpoint_timestamp {
+            Some(t) => unix_secs_to_rfc3339(t),
+            None => "-".to_string(),
+        }
+    )
+}
+
+fn status_error_kind(resp: &Value) -> CliErrorKind {
+    match resp["code"].as_str() {
+        Some("task_not_found") => CliErrorKind::NotFound,
+        _ => CliErrorKind::Internal,
+    }
+}
+
+fn print_line(line: &str) {
+    print!("\r{}\x1b[K", line);
+    let _ = std::io::stdout().flush();
+}
+
+/// 轮询任务状态并在单行中刷新进度，任务停止后输出汇总并退出；服务不可达时按指数退避重试
+pub fn watch_task(
+    server: &str,
+    unix_socket: Option<&str>,
+    task_id: &str,
+    interval: Duration,
+) -> ExitStatus {
+    let url = format!("{}/api/v1/task/status", server.trim_end_matches('/'));
+    let begin = Instant::now();
+    let mut last: Option<(Instant, u64)> = None;
+    let mut backoff = interval;
+    loop {
+        let resp = match http_request(&url, Some(json!({ "task_id": task_id })), unix_socket) {
+            Ok(r) => r,
+            Err(e) => {
+                print_line(&format!(
+                    "server unreachable: {}, retry in {}s",
+                    e,
+                    backoff.as_secs()
+                ));
+                thread::sleep(backoff);
+                backoff = std::cmp::min(backoff * 2, WATCH_MAX_BACKOFF);
+                continue;
+            }
+        };
+        backoff = interval;
+        if resp["code"].as_i64() != Some(0) {
+            println!();
+            return report_error(
+                status_error_kind(&resp),
+                format!(
+                    "get task {} status error: {}",
+                    task_id,
+                    response_message(&resp)
+                ),
+            );
+        }
+
+        let data = &resp["data"];
+        let state = watch_state(data);
+        let progress = watch_progress(data);
+        let now = Instant::now();
+        let throughput = match last {
+            Some((at, done)) if now > at && progress.objects_done >= done => {
+                Some((progress.objects_done - done) as f64 / (now - at).as_secs_f64())
+            }
+            _ => None,
+        };
+        last = Some((now, progress.objects_done));
+        print_line(&render_progress(&state, &progress, throughput));
+
+        if let Some(finished) = state.stopped {
+            println!();
+            println!(
+                "task {} {}, objects {}/{}, watched {}",
+                task_id,
+                state.label,
+                progress.objects_done,
+                progress.objects_total,
+                format_eta(begin.elapsed().as_secs())
+            );
+            return match finished {
+                true => ExitStatus::Success,
+                false => ExitStatus::Failure(EXIT_CODE_FAILURE),
+            };
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        format_eta, local_task_matches, render_import_results, render_progress, render_task_runs,
+        render_task_status, skip_reason_line, task_list_query, task_list_row, watch_progress,
+        watch_state, WatchState,
+    };
+    use serde_json::{json, Value};
+
+    //cargo test cmd::task::test::test_render_task_status -- --nocapture
+    #[test]
+    fn test_render_task_status() {
+        let data = json!({
+            "task_id": "7",
+            "effective_state": "interrupted",
+            "stale": true,
+            "persisted": {"status": {"Transfer": {"Running": "Stock"}}},
+            "live": null,
+            "checkpoint": {
+                "executing_file_position": {"offset": 120, "line_num": 3},
+                "task_stage": "Stock",
+                "timestamp": 0
+            }
+        });
+        let out = render_task_status(&data);
+        let lines = out.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            lines[0],
+            "task 7: interrupted (stale: persisted and live status disagree)"
+        );
+        assert_eq!(lines[1], r#"persisted: {"Transfer":{"Running":"Stock"}}"#);
+        assert_eq!(lines[2], "live: -");
+        assert_eq!(lines[3], "checkpoint: stage Stock line 3 offset 120 at -");
+
+        let mut queued = data.clone();
+        queued["last_skip_reason"] = json!({"reason": "concurrency_limit", "timestamp": 0});
+        let out = render_task_status(&queued);
+        assert_eq!(
+            out.lines().nth(1),
+            Some("last skip reason: concurrency_limit at -")
+        );
+        let failed = json!({"reason": {"setup_failed": "source unreachable"}, "timestamp": 0});
+        assert_eq!(
+            skip_reason_line(&failed).as_deref(),
+            Some("last skip reason: setup_failed: source unreachable at -")
+        );
+        assert_eq!(skip_reason_line(&Value::Null), None);
+    }
+
+    //cargo test cmd::task::test::test_render_task_runs -- --nocapture
+    #[test]
+    fn test_render_task_runs() {
+        let runs = json!([
+            {"run_id": "2", "state": "running", "start_ts": 0, "end_ts": null,
+             "objects_transferred": 0, "bytes_transferred": 0, "errors": 0, "stop_reason": null},
+            {"run_id": "1", "state": "failed", "start_ts": 0, "end_ts": 0,
+             "objects_transferred": 10, "bytes_transferred": 1024, "errors": 3,
+             "stop_reason": {"Failed": {"Panicked": "x"}}}
+        ]);
+        let out = render_task_runs(&runs);
+        let lines = out.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("RUN_ID\tSTATE"));
+        assert!(lines[1].starts_with("2\trunning\t"));
+        assert!(lines[1].ends_with("\t-\t0\t0\t0\t-"));
+        assert!(lines[2].ends_with("\t10\t1024\t3\tfailed"));
+        assert_eq!(render_task_runs(&json!([])).lines().count(), 1);
+    }
+
+    //cargo test cmd::task::test::test_task_list_query -- --nocapture
+    #[test]
+    fn test_task_list_query() {
+        let status = "error".to_string();
+        let name = "nightly backup&x".to_string();
+        assert_eq!(task_list_query(&[("status", None), ("name", None)]), "");
+        assert_eq!(
+            task_list_query(&[
+                ("status", Some(&status)),
+                ("type", None),
+                ("name", Some(&name))
+            ]),
+            "?status=error&name=nightly%20backup%26x"
+        );
+    }
+
+    //cargo test cmd::task::test::test_task_list_row -- --nocapture
+    #[test]
+    fn test_task_list_row() {
+        let listed = json!({"cf_id": "7", "task": {"type": "transfer", "name": "nightly"}});
+        assert_eq!(task_list_row(&listed), "7\ttransfer\tnightly");
+        let summary = json!({"task_id": "8", "name": "sync", "type": "Compare", "state": "living"});
+        assert_eq!(task_list_row(&summary), "8\tCompare\tsync");
+    }
+
+    //cargo test cmd::task::test::test_local_task_matches -- --nocapture
+    #[test]
+    fn test_local_task_matches() {
+        let task = json!({"type": "transfer", "name": "Nightly Sync"});
+        let s = |v: &str| v.to_string();
+        assert!(local_task_matches(&task, None, None));
+        assert!(local_task_matches(
+            &task,
+            Some(&s("transfer")),
+            Some(&s("nightly"))
+        ));
+        assert!(!local_task_matches(&task, Some(&s("compare")), None));
+        assert!(!local_task_matches(&task, None, Some(&s("weekly"))));
+    }
+
+    //cargo test cmd::task::test::test_watch_state -- --nocapture
+    #[test]
+    fn test_watch_state() {
+        let running = json!({"status": {"Transfer": {"Running": "Stock"}}});
+        assert_eq!(
+            watch_state(&running),
+            WatchState {
+                label: "running(stock)".to_string(),
+                stopped: None
+            }
+        );
+        let finished = json!({"status": {"Transfer": {"Stopped": "Finish"}}});
+        assert_eq!(watch_state(&finished).stopped, Some(true));
+        let broken = json!({"status": {"Transfer": {"Stopped": "Broken"}}});
+        assert_eq!(watch_state(&broken).stopped, Some(false));
+        let failed = json!({"status": {"Transfer": {"Stopped": {"Failed": {"Panicked": "x"}}}}});
+        assert_eq!(
+            watch_state(&failed),
+            WatchState {
+                label: "stopped(failed)".to_string(),
+                stopped: Some(false)
+            }
+        );
+        let criteria = json!({"status": {"Transfer": {"Stopped": {"Failed": {"CriteriaNotMet": {
+            "max_failed_objects": {"limit": 1, "failed": 2}
+        }}}}}});
+        assert_eq!(watch_state(&criteria).stopped, Some(false));
+        assert_eq!(watch_state(&json!({"status": null})).stopped, None);
+    }
+
+    //cargo test cmd::task::test::test_render_import_results -- --nocapture
+    #[test]
+    fn test_render_import_results() {
+        let results = json!([
+            {"index": 0, "source_task_id": "1", "task_id": "9", "error": null},
+            {"index": 1, "source_task_id": "2", "task_id": null, "error": "task 2 already exist"}
+        ]);
+        let text = render_import_results(&results);
+        println!("{}", text);
+        assert!(text.contains("0\t1\t-> 9"));
+        assert!(text.contains("1\t2\terror: task 2 already exist"));
+        assert!(text.ends_with("imported 1, failed 1"));
+    }
+
+    //cargo test cmd::task::test::test_render_progress -- --nocapture
+    #[test]
+    fn test_render_progress() {
+        let resp = json!({
+            "executing_file": {"path": "/tmp/list", "size": 1000, "total_lines": 100},
+            "executing_file_position": {"offset": 100, "line_num": 10},
+            "executing_position": {"offset": 400, "line_num": 40},
+            "modify_checkpoint_timestamp": 0,
+            "run_counters": {"objects_transferred": 40, "bytes_transferred": 4096, "errors": 0}
+        });
+        let p = watch_progress(&resp);
+        assert_eq!((p.objects_done, p.objects_total), (40, 100));
+        assert_eq!(p.bytes_transferred, Some(4096));
+        assert_eq!(p.checkpoint_timestamp, None);
+        let state = watch_state(&json!({"status": {"Transfer": {"Running": "Stock"}}}));
+        let line = render_progress(&state, &p, Some(2.0));
+        assert!(line.contains("objects 40/100 bytes 4096"));
+        assert!(line.contains("2.0 obj/s"));
+        assert!(line.contains("eta 30s"));
+        assert_eq!(format_eta(3725), "1h02m");
+    }
+}
@@ -0,0 +1,38 @@
+use super::size_distributed;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// 对象大小分布统计的累计结果，统计超时或取消时可读取已统计的部分
+#[derive(Debug, Default)]
+pub struct AnalyzeProgress {
+    sizes: Mutex<BTreeMap<String, i128>>,
+    cancel: CancellationToken,
+}
+
+impl AnalyzeProgress {
+    pub fn record(&self, size: i128) {
+        if let Ok(mut sizes) = self.sizes.lock() {
+            *sizes.entry(size_distributed(size)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn sizes(&self) -> BTreeMap<String, i128> {
+        match self.sizes.lock() {
+            Ok(sizes) => sizes.clone(),
+            Err(_) => BTreeMap::new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+}
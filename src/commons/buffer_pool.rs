@@ -0,0 +1,275 @@
+use crate::configure::get_config;
+use anyhow::{anyhow, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+// 首次访问时读取配置，配置重载不影响已创建的缓冲池
+pub static GLOBAL_BUFFER_POOL: Lazy<Arc<BufferPool>> = Lazy::new(|| {
+    let pool = match get_config() {
+        Ok(c) => match c.buffer_pool.enabled {
+            true => BufferPool::new(&c.buffer_pool.tiers, c.buffer_pool.capacity),
+            false => BufferPool::new(&[], 0),
+        },
+        Err(e) => {
+            log::warn!("read buffer pool config error: {}, buffer pool disabled", e);
+            BufferPool::new(&[], 0)
+        }
+    };
+    Arc::new(pool)
+});
+
+struct BufferTier {
+    size: usize,
+    idle: Mutex<Vec<BytesMut>>,
+}
+
+/// 按大小分档的缓冲池，池中无可用缓冲或请求超过最大档位时直接分配，缓冲池容量不影响正确性
+pub struct BufferPool {
+    tiers: Vec<BufferTier>,
+    // 每档最多保留的空闲缓冲数
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    // 缓冲池实际发生的内存分配次数，包含未命中与归还时无法复用原内存的情况
+    allocations: AtomicU64,
+    outstanding: AtomicU64,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BufferPoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub allocations: u64,
+    // 已借出未归还的缓冲数
+    pub outstanding: u64,
+    pub idle: usize,
+}
+
+impl BufferPool {
+    pub fn new(tiers: &[usize], capacity: usize) -> Self {
+        let mut sizes = tiers
+            .iter()
+            .filter(|s| **s > 0)
+            .copied()
+            .collect::<Vec<usize>>();
+        sizes.sort();
+        sizes.dedup();
+        Self {
+            tiers: sizes
+                .into_iter()
+                .map(|size| BufferTier {
+                    size,
+                    idle: Mutex::new(vec![]),
+                })
+                .collect(),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            allocations: AtomicU64::new(0),
+            outstanding: AtomicU64::new(0),
+        }
+    }
+
+    /// 借出不小于 size 的缓冲，取满足大小的最小档位
+    pub fn checkout(self: &Arc<Self>, size: usize) -> PooledBuffer {
+        self.outstanding.fetch_add(1, Ordering::Relaxed);
+        let tier = self.tiers.iter().position(|t| t.size >= size);
+        let reused = match tier {
+            Some(i) => match self.tiers[i].idle.lock() {
+                Ok(mut idle) => idle.pop(),
+                Err(_) => None,
+            },
+            None => None,
+        };
+        let buf = match reused {
+            Some(b) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                b
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.allocations.fetch_add(1, Ordering::Relaxed);
+                let cap = match tier {
+                    Some(i) => self.tiers[i].size,
+                    None => size,
+                };
+                BytesMut::with_capacity(cap)
+            }
+        };
+        PooledBuffer {
+            base: buf.as_ptr() as usize,
+            buf,
+            tier,
+            pool: self.clone(),
+        }
+    }
+
+    fn checkin(&self, mut buf: BytesMut, base: usize, tier: Option<usize>) {
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+        let tier = match tier {
+            Some(i) => &self.tiers[i],
+            None => return,
+        };
+        let mut idle = match tier.idle.lock() {
+            Ok(idle) => idle,
+            Err(_) => return,
+        };
+        if idle.len() >= self.capacity {
+            return;
+        }
+        // 已冻结的数据全部释放后 reserve 复用原内存，否则重新分配
+        buf.clear();
+        buf.reserve(tier.size);
+        if buf.as_ptr() as usize != base {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+        }
+        idle.push(buf);
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let hit_rate = match hits + misses {
+            0 => 0.0,
+            total => hits as f64 / total as f64,
+        };
+        BufferPoolStats {
+            hits,
+            misses,
+            hit_rate,
+            allocations: self.allocations.load(Ordering::Relaxed),
+            outstanding: self.outstanding.load(Ordering::Relaxed),
+            idle: self
+                .tiers
+                .iter()
+                .map(|t| t.idle.lock().map(|i| i.len()).unwrap_or(0))
+                .sum(),
+        }
+    }
+}
+
+/// 借出的缓冲，drop 时归还缓冲池
+pub struct PooledBuffer {
+    buf: BytesMut,
+    // 借出时的内存地址，用于判断归还时是否复用了原内存
+    base: usize,
+    tier: Option<usize>,
+    pool: Arc<BufferPool>,
+}
+
+impl PooledBuffer {
+    /// 从 reader 读取 len 字节，返回的 Bytes 与缓冲共享内存，释放后缓冲方可复用
+    pub async fn fill_from<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+        len: usize,
+    ) -> Result<Bytes> {
+        self.buf.clear();
+        self.buf.reserve(len);
+        let mut limited = reader.take(len as u64);
+        while self.buf.len() < len {
+            if limited.read_buf(&mut self.buf).await? == 0 {
+                return Err(anyhow!(
+                    "unexpected eof, expect {} bytes, read {} bytes",
+                    len,
+                    self.buf.len()
+                ));
+            }
+        }
+        Ok(self.buf.split().freeze())
+    }
+
+    /// 从文件当前位置读取至多 len 字节
+    pub fn fill_from_file(&mut self, file: &mut File, len: usize) -> Result<Bytes> {
+        self.buf.clear();
+        self.buf.reserve(len);
+        let mut writer = (&mut self.buf).writer();
+        std::io::copy(&mut file.take(len as u64), &mut writer)?;
+        Ok(self.buf.split().freeze())
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let buf = std::mem::take(&mut self.buf);
+        self.pool.checkin(buf, self.base, self.tier);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BufferPool;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    const OBJECT_SIZE: usize = 256 * 1024;
+    const OBJECTS: usize = 2000;
+
+    // 模拟存储端：从内存读取对象并在上传完成后释放数据
+    fn fake_upload(body: bytes::Bytes) -> usize {
+        body.len()
+    }
+
+    // 上传完成即释放数据时，同一档位的缓冲只分配一次，其后均复用
+    //cargo test commons::buffer_pool::test::test_buffer_pool_reuse -- --nocapture
+    #[tokio::test]
+    async fn test_buffer_pool_reuse() {
+        let object = vec![7u8; OBJECT_SIZE];
+        let pool = Arc::new(BufferPool::new(&[64 * 1024, OBJECT_SIZE], 4));
+        let now = Instant::now();
+        for _ in 0..OBJECTS {
+            let mut reader = object.as_slice();
+            let mut buf = pool.checkout(OBJECT_SIZE);
+            let body = buf.fill_from(&mut reader, OBJECT_SIZE).await.unwrap();
+            assert_eq!(fake_upload(body), OBJECT_SIZE);
+        }
+        let stats = pool.stats();
+        println!("objects:{} {:?}, stats:{:?}", OBJECTS, now.elapsed(), stats);
+        assert_eq!(stats.allocations, 1);
+        assert_eq!(stats.hits, OBJECTS as u64 - 1);
+        assert_eq!(stats.outstanding, 0);
+    }
+
+    //cargo test commons::buffer_pool::test::test_buffer_pool_exhausted -- --nocapture
+    #[tokio::test]
+    async fn test_buffer_pool_exhausted() {
+        let pool = Arc::new(BufferPool::new(&[1024], 1));
+        let data = vec![1u8; 4096];
+
+        // 同时借出超过容量的缓冲，以及超过最大档位的缓冲
+        let mut a = pool.checkout(1024);
+        let mut b = pool.checkout(1024);
+        let mut c = pool.checkout(4096);
+        let body_a = a.fill_from(&mut data.as_slice(), 1024).await.unwrap();
+        let body_b = b.fill_from(&mut data.as_slice(), 512).await.unwrap();
+        let body_c = c.fill_from(&mut data.as_slice(), 4096).await.unwrap();
+        assert_eq!(pool.stats().outstanding, 3);
+        assert_eq!(
+            (body_a.len(), body_b.len(), body_c.len()),
+            (1024, 512, 4096)
+        );
+        assert!(a.fill_from(&mut data.as_slice(), 1024).await.is_ok());
+
+        // 数据仍被引用时归还，缓冲重新分配内存，已返回的数据不受影响
+        drop(a);
+        drop(b);
+        drop(c);
+        assert_eq!(body_a.as_ref(), &data[..1024]);
+        let stats = pool.stats();
+        assert_eq!(stats.outstanding, 0);
+        assert_eq!(stats.idle, 1);
+        assert_eq!(stats.misses, 3);
+        assert!(pool
+            .checkout(100)
+            .fill_from(&mut data.as_slice(), 8192)
+            .await
+            .is_err());
+    }
+}
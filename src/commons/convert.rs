@@ -73,9 +73,39 @@ pub fn byte_size_usize_to_str(byte_size: usize) -> String {
         }
     };
 }
+
+/// 由 1970-01-01 起的天数推算公历日期，返回年、月、日
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// unix 时间戳转换为 UTC RFC3339 字符串，如 2024-01-02T03:04:05Z
+pub fn unix_secs_to_rfc3339(secs: u64) -> String {
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
 #[cfg(test)]
 mod test {
-    use crate::commons::{byte_size_str_to_usize, byte_size_usize_to_str};
+    use crate::commons::{byte_size_str_to_usize, byte_size_usize_to_str, unix_secs_to_rfc3339};
 
     //cargo test commons::convert::test::test_byte_size_to_usize -- --nocapture
     #[test]
@@ -90,4 +120,12 @@ mod test {
         let r = byte_size_usize_to_str(1073741823);
         println!("{:?}", r);
     }
+
+    //cargo test commons::convert::test::test_unix_secs_to_rfc3339 -- --nocapture
+    #[test]
+    fn test_unix_secs_to_rfc3339() {
+        assert_eq!(unix_secs_to_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(unix_secs_to_rfc3339(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(unix_secs_to_rfc3339(1704164645), "2024-01-02T03:04:05Z");
+    }
 }
@@ -0,0 +1,160 @@
+use super::civil_from_days;
+use anyhow::{anyhow, Result};
+
+// 向后查找的最大天数，28 年内星期与闰年的组合完整循环一次
+const CRON_SEARCH_DAYS: u64 = 366 * 28;
+
+/// 五段式 cron 表达式：分 时 日 月 星期，按 UTC 计算
+/// 支持 `*`、数值、范围 `a-b`、列表 `a,b` 与步长 `/n`，星期 0 与 7 均为周日
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日与星期字段以 * 开头时不参与限制，均有限制时满足其一即可
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields = expr.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(anyhow!(
+                "cron expression \"{}\" must have 5 fields: minute hour day month weekday",
+                expr
+            ));
+        }
+        let mut weekdays = parse_cron_field(fields[4], "weekday", 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_cron_field(fields[0], "minute", 0, 59)?,
+            hours: parse_cron_field(fields[1], "hour", 0, 23)?,
+            days: parse_cron_field(fields[2], "day", 1, 31)?,
+            months: parse_cron_field(fields[3], "month", 1, 12)?,
+            weekdays,
+            days_any: fields[2].starts_with('*'),
+            weekdays_any: fields[4].starts_with('*'),
+        })
+    }
+
+    /// secs 之后（不含）最近一次触发的 unix 时间戳，精确到分钟；不会触发时为 None
+    pub fn next_after(&self, secs: u64) -> Option<u64> {
+        let start = secs / 60 * 60 + 60;
+        let first_day = start / 86400;
+        for day in first_day..first_day + CRON_SEARCH_DAYS {
+            if !self.day_matches(day) {
+                continue;
+            }
+            let begin = match day == first_day {
+                true => start % 86400 / 60,
+                false => 0,
+            };
+            for minute_of_day in begin..1440 {
+                if self.hours & (1 << (minute_of_day / 60)) != 0
+                    && self.minutes & (1 << (minute_of_day % 60)) != 0
+                {
+                    return Some(day * 86400 + minute_of_day * 60);
+                }
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, day: u64) -> bool {
+        let (_, month, dom) = civil_from_days(day as i64);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 为周四
+        let day_ok = self.days & (1 << dom) != 0;
+        let weekday_ok = self.weekdays & (1 << ((day + 4) % 7)) != 0;
+        match (self.days_any, self.weekdays_any) {
+            (true, true) => true,
+            (true, false) => weekday_ok,
+            (false, true) => day_ok,
+            (false, false) => day_ok || weekday_ok,
+        }
+    }
+}
+
+// 字段取值转换为位图，第 n 位表示取值 n
+fn parse_cron_field(field: &str, name: &str, min: u32, max: u32) -> Result<u64> {
+    let invalid = || anyhow!("invalid cron {} field \"{}\"", name, field);
+    let num = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, num(s)?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (num(a)?, num(b)?),
+                // 带步长的单个值表示由该值起至最大值
+                None if step > 1 => (num(r)?, max),
+                None => (num(r)?, num(r)?),
+            },
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(anyhow!(
+                "cron {} field \"{}\" out of range {}-{}",
+                name,
+                field,
+                min,
+                max
+            ));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod test {
+    use super::CronSchedule;
+
+    // 2024-01-02T03:04:05Z，周二
+    const NOW: u64 = 1704164645;
+
+    //cargo test commons::cron_schedule::test::test_cron_parse -- --nocapture
+    #[test]
+    fn test_cron_parse() {
+        assert!(CronSchedule::parse("0 * * * *").is_ok());
+        assert!(CronSchedule::parse("*/15 1-5,22 * * 1-5").is_ok());
+        assert!(CronSchedule::parse("0 3 * * 7").is_ok());
+        assert!(CronSchedule::parse("0 * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("0 5-1 * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 0 * *").is_err());
+        assert!(CronSchedule::parse("a * * * *").is_err());
+    }
+
+    //cargo test commons::cron_schedule::test::test_cron_next_after -- --nocapture
+    #[test]
+    fn test_cron_next_after() {
+        let next = |expr: &str, secs: u64| CronSchedule::parse(expr).unwrap().next_after(secs);
+        assert_eq!(next("* * * * *", NOW), Some(1704164700));
+        assert_eq!(next("0 * * * *", NOW), Some(1704168000));
+        assert_eq!(next("30 3 * * *", NOW), Some(1704166200));
+        // 触发时刻本身不算在内
+        assert_eq!(next("30 3 * * *", 1704166200), Some(1704166200 + 86400));
+        assert_eq!(next("0 0 29 2 *", NOW), Some(1709164800));
+        assert_eq!(next("0 0 * * 0", NOW), Some(1704585600));
+        assert_eq!(next("0 0 * * 7", NOW), Some(1704585600));
+        // 日与星期均有限制时满足其一即可
+        assert_eq!(next("0 0 7 * 3", NOW), Some(1704240000));
+        assert_eq!(next("0 0 31 2 *", NOW), None);
+    }
+}
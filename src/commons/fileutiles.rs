@@ -1,8 +1,7 @@
-use super::{rand_util::rand_string, size_distributed, LastModifyFilter, RegexFilter};
+use super::{rand_util::rand_string, AnalyzeProgress, LastModifyFilter, RegexFilter};
 use crate::tasks::FileDescription;
 use anyhow::Result;
 use std::{
-    collections::BTreeMap,
     fs::{self, File, OpenOptions},
     io::{self, BufRead, LineWriter, Read, Write},
     path::Path,
@@ -24,6 +23,30 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
+/// 文件大小，文件不存在或无法读取时返回 0
+pub fn file_len(path: &str) -> u64 {
+    match fs::metadata(path) {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// rename 替换文件后同步所在目录，替换在崩溃后依然有效
+/// windows 下无法以文件方式打开目录，不做同步
+pub fn sync_parent_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.as_ref().parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
 pub fn copy_file(
     source: &str,
     target: &str,
@@ -88,17 +111,21 @@ fn remove_dir_contents<P: AsRef<Path>>(path: P) -> io::Result<()> {
     Ok(())
 }
 
+/// 统计结果累计到 progress，取消时提前返回
 pub fn analyze_folder_files_size(
     folder: &str,
     regex_filter: Option<RegexFilter>,
     last_modify_filter: Option<LastModifyFilter>,
-) -> Result<BTreeMap<String, i128>> {
-    let mut size_map = BTreeMap::<String, i128>::new();
+    progress: &AnalyzeProgress,
+) -> Result<()> {
     for entry in WalkDir::new(folder)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
     {
+        if progress.is_cancelled() {
+            return Ok(());
+        }
         if let Some(p) = entry.path().to_str() {
             if p.eq(folder) {
                 continue;
@@ -121,17 +148,10 @@ pub fn analyze_folder_files_size(
                 }
             }
 
-            let obj_size = i128::from(entry.metadata()?.len());
-            let key = size_distributed(obj_size);
-            let mut size = match size_map.get(&key) {
-                Some(m) => *m,
-                None => 0,
-            };
-            size += 1;
-            size_map.insert(key, size);
+            progress.record(i128::from(entry.metadata()?.len()));
         };
     }
-    Ok(size_map)
+    Ok(())
 }
 
 // Todo
@@ -452,7 +472,22 @@ pub fn gen_file_part_plan(file_path: &str, chunk_size: usize) -> Result<Vec<File
 
 #[cfg(test)]
 mod test {
-    use crate::commons::{fileutiles::generate_file, fill_file_with_zero, multi_parts_copy_file};
+    use crate::commons::{
+        fileutiles::generate_file, fill_file_with_zero, multi_parts_copy_file, sync_parent_dir,
+    };
+
+    //cargo test commons::fileutiles::test::test_sync_parent_dir -- --nocapture
+    #[test]
+    fn test_sync_parent_dir() {
+        let dir = format!("/tmp/sync_parent_dir_test/{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/file", dir);
+        std::fs::write(&path, "1").unwrap();
+        sync_parent_dir(&path).unwrap();
+        // 相对路径的所在目录为当前目录
+        sync_parent_dir("file").unwrap();
+        assert!(sync_parent_dir(format!("{}/missing/file", dir)).is_err());
+    }
 
     //cargo test commons::fileutiles::test::test_gen_file -- --nocapture
     #[test]
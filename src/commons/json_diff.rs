@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const REDACTED_VALUE: &'static str = "******";
+
+/// 单个字段的变更，path 为 json pointer，字段不存在时为 None
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// 对象逐字段比较，数组与类型不同的值整体比较
+pub fn json_diff(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = vec![];
+    diff_value("", Some(old), Some(new), &mut changes);
+    changes
+}
+
+fn diff_value(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<JsonChange>) {
+    if let (Some(Value::Object(o)), Some(Value::Object(n))) = (old, new) {
+        let keys = o.keys().chain(n.keys().filter(|k| !o.contains_key(*k)));
+        for k in keys.cloned().collect::<Vec<String>>() {
+            let child = format!("{}/{}", path, escape_pointer_token(&k));
+            diff_value(&child, o.get(&k), n.get(&k), changes);
+        }
+        return;
+    }
+    if old != new {
+        changes.push(JsonChange {
+            path: path.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        });
+    }
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape_pointer_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// 递归将指定字段的值替换为脱敏值
+pub fn redact_json(value: &mut Value, fields: &[&str]) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if fields.contains(&k.as_str()) {
+                    *v = Value::String(REDACTED_VALUE.to_string());
+                } else {
+                    redact_json(v, fields);
+                }
+            }
+        }
+        Value::Array(arr) => arr.iter_mut().for_each(|v| redact_json(v, fields)),
+        _ => {}
+    }
+}
+
+/// 变更涉及脱敏字段时保留变更记录，仅替换新旧值
+pub fn redact_json_changes(changes: &mut Vec<JsonChange>, fields: &[&str]) {
+    for c in changes.iter_mut() {
+        let last = c.path.rsplit('/').next().map(unescape_pointer_token);
+        let redact_whole = match last {
+            Some(l) => fields.contains(&l.as_str()),
+            None => false,
+        };
+        for v in [&mut c.old, &mut c.new] {
+            if let Some(v) = v {
+                match redact_whole {
+                    true => *v = Value::String(REDACTED_VALUE.to_string()),
+                    false => redact_json(v, fields),
+                }
+            }
+        }
+    }
+}
+
+/// 变更中是否包含脱敏值，包含时无法据此还原原值
+pub fn json_changes_redacted(changes: &[JsonChange]) -> bool {
+    fn contains_redacted(v: &Value) -> bool {
+        match v {
+            Value::String(s) => s.eq(REDACTED_VALUE),
+            Value::Object(m) => m.values().any(contains_redacted),
+            Value::Array(a) => a.iter().any(contains_redacted),
+            _ => false,
+        }
+    }
+    changes
+        .iter()
+        .flat_map(|c| [&c.old, &c.new])
+        .any(|v| v.as_ref().map(contains_redacted).unwrap_or(false))
+}
+
+/// 反向应用变更：当前值须与变更后的值一致，否则视为已被后续修改覆盖
+pub fn revert_json_changes(value: &mut Value, changes: &[JsonChange]) -> Result<()> {
+    for c in changes.iter() {
+        if value.pointer(&c.path) != c.new.as_ref() {
+            return Err(anyhow!("{} has been modified since the change", c.path));
+        }
+    }
+    for c in changes.iter() {
+        set_pointer(value, &c.path, c.old.clone())?;
+    }
+    Ok(())
+}
+
+/// 按 RFC 7386 合并 patch：对象逐字段合并，null 删除字段，其余值整体替换
+pub fn json_merge_patch(target: &mut Value, patch: &Value) {
+    let patch_map = match patch {
+        Value::Object(m) => m,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(map) = target {
+        for (k, v) in patch_map.iter() {
+            match v {
+                Value::Null => {
+                    map.remove(k);
+                }
+                _ => json_merge_patch(map.entry(k.clone()).or_insert(Value::Null), v),
+            }
+        }
+    }
+}
+
+// value 为 None 时删除字段，父对象不存在时逐级创建
+fn set_pointer(root: &mut Value, path: &str, value: Option<Value>) -> Result<()> {
+    if path.is_empty() {
+        *root = value.unwrap_or(Value::Null);
+        return Ok(());
+    }
+    let tokens = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(unescape_pointer_token)
+        .collect::<Vec<String>>();
+    let (last, parents) = match tokens.split_last() {
+        Some(t) => t,
+        None => return Err(anyhow!("invalid path {}", path)),
+    };
+    let mut current = root;
+    for token in parents {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = match current.as_object_mut() {
+            Some(m) => m
+                .entry(token.clone())
+                .or_insert_with(|| Value::Object(Map::new())),
+            None => return Err(anyhow!("invalid path {}", path)),
+        };
+    }
+    let map = match current.as_object_mut() {
+        Some(m) => m,
+        None => return Err(anyhow!("{} parent is not an object", path)),
+    };
+    match value {
+        Some(v) => {
+            map.insert(last.clone(), v);
+        }
+        None => {
+            map.remove(last);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    //cargo test commons::json_diff::test::test_json_diff_nested -- --nocapture
+    #[test]
+    fn test_json_diff_nested() {
+        let old = json!({"a": {"b": 1, "c": [1, 2]}, "removed": true});
+        let new = json!({"a": {"b": 2, "c": [1, 2], "d": {"e": "x"}}});
+        let changes = json_diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&JsonChange {
+            path: "/a/b".to_string(),
+            old: Some(json!(1)),
+            new: Some(json!(2)),
+        }));
+        // 新增的对象整体记录
+        assert!(changes.contains(&JsonChange {
+            path: "/a/d".to_string(),
+            old: None,
+            new: Some(json!({"e": "x"})),
+        }));
+        assert!(changes.contains(&JsonChange {
+            path: "/removed".to_string(),
+            old: Some(json!(true)),
+            new: None,
+        }));
+    }
+
+    //cargo test commons::json_diff::test::test_json_diff_type_change -- --nocapture
+    #[test]
+    fn test_json_diff_type_change() {
+        let old = json!({"a": {"b": 1}, "list": [1]});
+        let new = json!({"a": "flat", "list": {"0": 1}});
+        let changes = json_diff(&old, &new);
+        let paths = changes
+            .iter()
+            .map(|c| c.path.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(paths, vec!["/a", "/list"]);
+        assert_eq!(changes[0].old, Some(json!({"b": 1})));
+    }
+
+    //cargo test commons::json_diff::test::test_json_merge_patch -- --nocapture
+    #[test]
+    fn test_json_merge_patch() {
+        let mut target = json!({"a": {"b": 1, "c": 2}, "list": [1, 2], "s": "x"});
+        let patch = json!({"a": {"b": 3, "c": null, "d": {"e": 1}}, "list": [3], "s": null});
+        json_merge_patch(&mut target, &patch);
+        assert_eq!(target, json!({"a": {"b": 3, "d": {"e": 1}}, "list": [3]}));
+
+        // 非对象 patch 整体替换
+        json_merge_patch(&mut target, &json!(["x"]));
+        assert_eq!(target, json!(["x"]));
+    }
+
+    //cargo test commons::json_diff::test::test_redact_and_revert -- --nocapture
+    #[test]
+    fn test_redact_and_revert() {
+        let old = json!({"source": {"secret": "s1", "bucket": "b1"}, "n": 1, "a/b": 0});
+        let new = json!({"source": {"secret": "s2", "bucket": "b2"}, "n": 2, "a/b": 1});
+        let mut changes = json_diff(&old, &new);
+        assert!(changes.iter().any(|c| c.path == "/a~1b"));
+
+        let mut current = new.clone();
+        revert_json_changes(&mut current, &changes).unwrap();
+        assert_eq!(current, old);
+
+        // 当前值已被修改时拒绝还原
+        let mut modified = new.clone();
+        modified["n"] = json!(3);
+        assert!(revert_json_changes(&mut modified, &changes).is_err());
+
+        redact_json_changes(&mut changes, &["secret"]);
+        let secret = changes.iter().find(|c| c.path == "/source/secret").unwrap();
+        assert_eq!(secret.new, Some(json!(REDACTED_VALUE)));
+        assert!(json_changes_redacted(&changes));
+    }
+}
@@ -1,16 +1,26 @@
+mod analyze_progress;
+mod buffer_pool;
 mod convert;
+mod cron_schedule;
 mod fileutiles;
 mod filters;
+mod json_diff;
 mod json_utile;
 mod notify_utile;
 mod processbar;
 mod rand_util;
+mod request_context;
 mod sysutiles;
 mod yamlutile;
+pub use analyze_progress::*;
+pub use buffer_pool::*;
 pub use convert::*;
+pub use cron_schedule::*;
 pub use fileutiles::*;
 pub use filters::*;
+pub use json_diff::*;
 pub use json_utile::*;
 pub use notify_utile::*;
 pub use processbar::*;
+pub use request_context::*;
 pub use yamlutile::*;
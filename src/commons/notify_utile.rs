@@ -1,3 +1,4 @@
+use super::inherit_request_context;
 use super::struct_to_json_string;
 use notify::{
     event::CreateKind, Config, Error, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
@@ -182,7 +183,7 @@ mod test {
         let mut set: JoinSet<()> = JoinSet::new();
         let file_size = Arc::new(AtomicU64::new(0));
         let _rt_rs = rt.block_on(async move {
-            let _rs_watch = set.spawn(async move {
+            let _rs_watch = set.spawn(inherit_request_context(async move {
                 println!("begin watch");
                 let file = OpenOptions::new()
                     .create(true)
@@ -194,10 +195,10 @@ mod test {
                 notify_watcher
                     .watch_to_file(file, Arc::clone(&file_size))
                     .await;
-            });
-            let _rs_read = set.spawn(async move {
+            }));
+            let _rs_read = set.spawn(inherit_request_context(async move {
                 println!("begin read watch file");
-            });
+            }));
             if set.len() > 0 {
                 set.join_next().await;
             }
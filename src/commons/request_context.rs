@@ -0,0 +1,56 @@
+use std::future::Future;
+use tokio::task::futures::TaskLocalFuture;
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+
+tokio::task_local! {
+    // 当前 http 请求的 request id，由 request id 中间件设置
+    static CURRENT_REQUEST_ID: Option<String>;
+}
+
+/// 当前协程所属请求的 request id，不在请求上下文中时返回 None
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok().flatten()
+}
+
+/// 在 request id 上下文中执行 future
+pub fn with_request_id<F: Future>(
+    request_id: String,
+    fut: F,
+) -> TaskLocalFuture<Option<String>, F> {
+    CURRENT_REQUEST_ID.scope(Some(request_id), fut)
+}
+
+/// 新 spawn 的协程继承当前请求的 request id 与 tracing span，使其日志可与请求关联
+pub fn inherit_request_context<F: Future>(
+    fut: F,
+) -> TaskLocalFuture<Option<String>, Instrumented<F>> {
+    CURRENT_REQUEST_ID.scope(
+        current_request_id(),
+        fut.instrument(tracing::Span::current()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{current_request_id, inherit_request_context, with_request_id};
+
+    //cargo test commons::request_context::test::test_inherit_request_context -- --nocapture
+    #[tokio::test]
+    async fn test_inherit_request_context() {
+        assert!(current_request_id().is_none());
+        let id = with_request_id("req-1".to_string(), async {
+            tokio::spawn(inherit_request_context(async { current_request_id() }))
+                .await
+                .unwrap()
+        })
+        .await;
+        assert_eq!(id.as_deref(), Some("req-1"));
+        // 未继承上下文的协程取不到 request id
+        let id = with_request_id("req-2".to_string(), async {
+            tokio::spawn(async { current_request_id() }).await.unwrap()
+        })
+        .await;
+        assert!(id.is_none());
+    }
+}
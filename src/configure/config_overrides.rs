@@ -0,0 +1,224 @@
+use super::config_global::{get_config, replace_config};
+use super::config_set::set_config_value;
+use super::Config;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+static CONFIG_OVERRIDES: Lazy<RwLock<ConfigOverrides>> =
+    Lazy::new(|| RwLock::new(ConfigOverrides::default()));
+static ENV_OVERRIDE_FIELDS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(vec![]));
+
+/// 覆盖配置的环境变量前缀，层级以 "__" 分隔，如 MARIO_CONFIG_HTTP__PORT 对应 http.port
+pub const CONFIG_ENV_PREFIX: &str = "MARIO_CONFIG_";
+
+/// 命令行对配置的覆盖，仅对本次运行生效，配置重载后依然保留
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    pub http_bind: Option<String>,
+    pub http_port: Option<u16>,
+}
+
+impl ConfigOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.http_bind.is_none() && self.http_port.is_none()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(bind) = &self.http_bind {
+            parse_bind_ip(bind)?;
+        }
+        if self.http_port == Some(0) {
+            return Err(anyhow!("port must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(bind) = &self.http_bind {
+            config.http.bind = bind.clone();
+        }
+        if let Some(port) = self.http_port {
+            config.http.port = port;
+        }
+    }
+
+    /// 以 "http.port=3001" 形式列出覆盖项
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        if let Some(bind) = &self.http_bind {
+            fields.push(format!("http.bind={}", bind));
+        }
+        if let Some(port) = self.http_port {
+            fields.push(format!("http.port={}", port));
+        }
+        fields
+    }
+}
+
+pub fn parse_bind_ip(bind: &str) -> Result<IpAddr> {
+    IpAddr::from_str(bind.trim()).map_err(|e| anyhow!("invalid bind address {}: {}", bind, e))
+}
+
+/// 校验后保存命令行覆盖并作用于当前配置
+pub fn set_config_overrides(overrides: ConfigOverrides) -> Result<()> {
+    overrides.validate()?;
+    {
+        let mut current = CONFIG_OVERRIDES
+            .write()
+            .map_err(|e| anyhow!("{}", e.to_string()))?;
+        *current = overrides;
+    }
+    replace_config(get_config()?)
+}
+
+pub fn get_config_overrides() -> ConfigOverrides {
+    match CONFIG_OVERRIDES.read() {
+        Ok(o) => o.clone(),
+        Err(_) => ConfigOverrides::default(),
+    }
+}
+
+/// 配置文件内容叠加命令行覆盖
+pub fn apply_config_overrides(config: &mut Config) {
+    get_config_overrides().apply(config);
+}
+
+/// 从环境变量中取出配置覆盖项，返回按配置项排序的 (配置项, 值)
+pub fn env_config_overrides<I: IntoIterator<Item = (String, String)>>(
+    vars: I,
+) -> Vec<(String, String)> {
+    let mut overrides = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(CONFIG_ENV_PREFIX)?;
+            Some((key.to_lowercase().replace("__", "."), value))
+        })
+        .collect::<Vec<(String, String)>>();
+    overrides.sort();
+    overrides
+}
+
+/// 按配置项覆盖配置，值的类型与校验同 `config set`
+pub fn apply_env_overrides(config: &mut Config, overrides: &[(String, String)]) -> Result<()> {
+    if overrides.is_empty() {
+        return Ok(());
+    }
+    let mut doc = serde_yaml::to_value(&*config)?;
+    for (key, value) in overrides {
+        set_config_value(&mut doc, key, value).map_err(|e| {
+            anyhow!(
+                "environment variable {}{} error: {}",
+                CONFIG_ENV_PREFIX,
+                key.to_uppercase().replace('.', "__"),
+                e
+            )
+        })?;
+    }
+    *config = serde_yaml::from_value::<Config>(doc)?;
+    Ok(())
+}
+
+/// 以当前进程的环境变量覆盖配置，并记录生效的配置项
+pub fn apply_process_env_overrides(config: &mut Config) -> Result<()> {
+    let overrides = env_config_overrides(std::env::vars());
+    apply_env_overrides(config, &overrides)?;
+    let mut fields = ENV_OVERRIDE_FIELDS
+        .write()
+        .map_err(|e| anyhow!("{}", e.to_string()))?;
+    *fields = overrides.into_iter().map(|(k, _)| k).collect();
+    Ok(())
+}
+
+/// 环境变量覆盖的配置项，不含值
+pub fn get_env_override_fields() -> Vec<String> {
+    match ENV_OVERRIDE_FIELDS.read() {
+        Ok(f) => f.clone(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_env_overrides, env_config_overrides, ConfigOverrides};
+    use crate::configure::Config;
+
+    //cargo test configure::config_overrides::test::test_config_overrides -- --nocapture
+    #[test]
+    fn test_config_overrides() {
+        let mut config = Config::default();
+        let empty = ConfigOverrides::default();
+        assert!(empty.is_empty() && empty.validate().is_ok());
+        empty.apply(&mut config);
+        assert_eq!(config, Config::default());
+
+        let overrides = ConfigOverrides {
+            http_bind: Some("127.0.0.1".to_string()),
+            http_port: Some(3001),
+        };
+        assert!(overrides.validate().is_ok());
+        overrides.apply(&mut config);
+        assert_eq!(config.http.bind, "127.0.0.1");
+        assert_eq!(config.http.port, 3001);
+        assert_eq!(
+            overrides.fields(),
+            vec!["http.bind=127.0.0.1", "http.port=3001"]
+        );
+
+        let invalid_bind = ConfigOverrides {
+            http_bind: Some("localhost:3000".to_string()),
+            http_port: None,
+        };
+        assert!(invalid_bind.validate().is_err());
+        let zero_port = ConfigOverrides {
+            http_bind: Some("::1".to_string()),
+            http_port: Some(0),
+        };
+        assert!(zero_port.validate().is_err());
+    }
+
+    //cargo test configure::config_overrides::test::test_env_overrides -- --nocapture
+    #[test]
+    fn test_env_overrides() {
+        let vars = vec![
+            ("MARIO_CONFIG_HTTP__PORT".to_string(), "3001".to_string()),
+            (
+                "MARIO_CONFIG_HTTP__MAX_BODY_SIZE".to_string(),
+                "2048".to_string(),
+            ),
+            ("MARIO_CONFIG_BANNER".to_string(), "false".to_string()),
+            ("MARIO_GIT_COMMIT".to_string(), "abc".to_string()),
+            ("PATH".to_string(), "/bin".to_string()),
+        ];
+        let overrides = env_config_overrides(vars);
+        assert_eq!(
+            overrides
+                .iter()
+                .map(|(k, _)| k.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["banner", "http.max_body_size", "http.port"]
+        );
+
+        let mut config = Config::default();
+        apply_env_overrides(&mut config, &overrides).unwrap();
+        assert_eq!(config.http.port, 3001);
+        assert_eq!(config.http.max_body_size, 2048);
+        assert!(!config.banner);
+
+        let invalid = env_config_overrides(vec![(
+            "MARIO_CONFIG_HTTP__PORT".to_string(),
+            "abc".to_string(),
+        )]);
+        let err = apply_env_overrides(&mut config, &invalid).unwrap_err();
+        assert!(err.to_string().contains("MARIO_CONFIG_HTTP__PORT"));
+        let unknown = env_config_overrides(vec![(
+            "MARIO_CONFIG_HTTP__NO_SUCH_KEY".to_string(),
+            "1".to_string(),
+        )]);
+        assert!(apply_env_overrides(&mut config, &unknown).is_err());
+        assert_eq!(config.http.port, 3001);
+    }
+}
@@ -0,0 +1,266 @@
+use super::config_global::{get_config, get_config_file_path, load_config_file, replace_config};
+use super::config_overrides::apply_config_overrides;
+use super::Config;
+use crate::logger::parse_log_level;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 修改后需重启服务才生效的配置项前缀，重载时保留运行中的值
+const RESTART_REQUIRED_FIELDS: [&'static str; 9] = [
+    "http.",
+    "meta_dir",
+    "tikv.",
+    "datasource_mysql.",
+    "task.shutdown_grace_secs",
+    "task_runtime.",
+    "buffer_pool.",
+    "rocksdb.",
+    "db.auto_compaction",
+];
+
+// RESTART_REQUIRED_FIELDS 中可在重载时生效的例外
+const RELOADABLE_FIELDS: [&'static str; 1] = ["http.rate_limit"];
+
+static LAST_CONFIG_RELOAD: Lazy<RwLock<ConfigReloadStatus>> =
+    Lazy::new(|| RwLock::new(ConfigReloadStatus::default()));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ConfigReloadStatus {
+    // 每次成功重载加一
+    pub generation: u64,
+    // 每次重载加一，包含校验失败的重载
+    pub attempts: u64,
+    pub last_reload_timestamp: Option<u64>,
+    pub last_reload_ok: Option<bool>,
+    pub last_error: Option<String>,
+    // 已生效的变更
+    pub applied: Vec<String>,
+    // 已变更但需重启生效
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    pub old: Config,
+    pub new: Config,
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+pub fn validate_config(config: &Config) -> Result<()> {
+    config.http.endpoints()?;
+    config.http.unix_socket_mode()?;
+    config.http.rate_limit.validate()?;
+    if let Some(cors) = &config.http.cors {
+        cors.validate()?;
+    }
+    parse_log_level(&config.log.level)?;
+    if config.task.checkpoint_interval == 0 {
+        return Err(anyhow!("task.checkpoint_interval must be greater than 0"));
+    }
+    Ok(())
+}
+
+/// 重新读取配置文件，校验通过后替换当前配置，需重启生效的字段保留运行中的值；校验失败保留原配置
+pub fn reload_config() -> Result<ConfigReload> {
+    let result = load_and_swap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .ok();
+    let mut status = LAST_CONFIG_RELOAD
+        .write()
+        .map_err(|e| anyhow!("{}", e.to_string()))?;
+    status.last_reload_timestamp = now;
+    status.attempts += 1;
+    match &result {
+        Ok(r) => {
+            status.generation += 1;
+            status.last_reload_ok = Some(true);
+            status.last_error = None;
+            status.applied = r.applied.clone();
+            status.restart_required = r.restart_required.clone();
+        }
+        Err(e) => {
+            status.last_reload_ok = Some(false);
+            status.last_error = Some(e.to_string());
+        }
+    }
+    result
+}
+
+pub fn get_config_reload_status() -> ConfigReloadStatus {
+    match LAST_CONFIG_RELOAD.read() {
+        Ok(s) => s.clone(),
+        Err(_) => ConfigReloadStatus::default(),
+    }
+}
+
+fn load_and_swap() -> Result<ConfigReload> {
+    let mut new = load_config_file(&get_config_file_path())?;
+    // 命令行覆盖项不视为变更
+    apply_config_overrides(&mut new);
+    validate_config(&new)?;
+    let old = get_config()?;
+    let (applied, restart_required): (Vec<String>, Vec<String>) = diff_config(&old, &new)?
+        .into_iter()
+        .partition(|f| reloadable_field(f));
+    let new = keep_restart_required(&old, &new)?;
+    replace_config(new.clone())?;
+    Ok(ConfigReload {
+        old,
+        new,
+        applied,
+        restart_required,
+    })
+}
+
+fn reloadable_field(field: &str) -> bool {
+    RELOADABLE_FIELDS.iter().any(|p| field.starts_with(p))
+        || !RESTART_REQUIRED_FIELDS.iter().any(|p| field.starts_with(p))
+}
+
+/// 以 new 为准，需重启生效的字段取 old 中运行中的值
+pub fn keep_restart_required(old: &Config, new: &Config) -> Result<Config> {
+    let old_value = serde_json::to_value(old)?;
+    let mut new_value = serde_json::to_value(new)?;
+    keep_restart_required_value("", &old_value, &mut new_value);
+    Ok(serde_json::from_value::<Config>(new_value)?)
+}
+
+fn keep_restart_required_value(path: &str, old: &Value, new: &mut Value) {
+    let (o, n) = match (old, &mut *new) {
+        (Value::Object(o), Value::Object(n)) => (o, n),
+        _ => {
+            if !reloadable_field(path) {
+                *new = old.clone();
+            }
+            return;
+        }
+    };
+    let mut keys = o.keys().chain(n.keys()).cloned().collect::<Vec<String>>();
+    keys.sort();
+    keys.dedup();
+    for k in keys {
+        let sub_path = match path.is_empty() {
+            true => k.to_string(),
+            false => format!("{}.{}", path, k),
+        };
+        let reloadable = reloadable_field(&sub_path);
+        match (o.get(&k), n.contains_key(&k)) {
+            (Some(ov), true) => {
+                if let Some(nv) = n.get_mut(&k) {
+                    keep_restart_required_value(&sub_path, ov, nv);
+                }
+            }
+            (Some(ov), false) if !reloadable => {
+                n.insert(k, ov.clone());
+            }
+            (None, true) if !reloadable => {
+                n.remove(&k);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 以 "http.port" 形式列出两份配置间存在差异的字段
+pub fn diff_config(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let old_value = serde_json::to_value(old)?;
+    let new_value = serde_json::to_value(new)?;
+    let mut changed = vec![];
+    diff_value("", &old_value, &new_value, &mut changed);
+    Ok(changed)
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(o), Value::Object(n)) => {
+            let mut keys = o.keys().chain(n.keys()).collect::<Vec<&String>>();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                let sub_path = match path.is_empty() {
+                    true => k.to_string(),
+                    false => format!("{}.{}", path, k),
+                };
+                diff_value(
+                    &sub_path,
+                    o.get(k).unwrap_or(&Value::Null),
+                    n.get(k).unwrap_or(&Value::Null),
+                    changed,
+                );
+            }
+        }
+        (o, n) => {
+            if o != n {
+                changed.push(path.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_config, keep_restart_required, reloadable_field};
+    use crate::configure::Config;
+
+    //cargo test configure::config_reload::test::test_diff_config -- --nocapture
+    #[test]
+    fn test_diff_config() {
+        let old = Config::default();
+        let mut new = Config::default();
+        assert!(diff_config(&old, &new).unwrap().is_empty());
+
+        new.http.port = 3001;
+        new.task.checkpoint_interval = 30;
+        let changed = diff_config(&old, &new).unwrap();
+        assert_eq!(
+            changed,
+            vec![
+                "http.port".to_string(),
+                "task.checkpoint_interval".to_string()
+            ]
+        );
+        assert!(!reloadable_field("http.port"));
+        assert!(reloadable_field("http.rate_limit.per_second"));
+        assert!(reloadable_field("http.rate_limit.routes"));
+        assert!(reloadable_field("task.checkpoint_interval"));
+        assert!(!reloadable_field("rocksdb.path"));
+    }
+
+    //cargo test configure::config_reload::test::test_keep_restart_required -- --nocapture
+    #[test]
+    fn test_keep_restart_required() {
+        let old = Config::default();
+        let mut new = Config::default();
+        new.http.port = 3001;
+        new.rocksdb.path = "/data/mario/rocksdb".to_string();
+        new.meta_dir = "/data/mario/meta".to_string();
+        new.http.rate_limit.per_second = 7.0;
+        new.task.checkpoint_interval = 30;
+        new.log.level = "debug".to_string();
+
+        let running = keep_restart_required(&old, &new).unwrap();
+        // 需重启生效的字段保留运行中的值
+        assert_eq!(running.http.port, old.http.port);
+        assert_eq!(running.rocksdb.path, old.rocksdb.path);
+        assert_eq!(running.meta_dir, old.meta_dir);
+        // 可热更新的字段取新值
+        assert_eq!(running.http.rate_limit.per_second, 7.0);
+        assert_eq!(running.task.checkpoint_interval, 30);
+        assert_eq!(running.log.level, "debug");
+        assert_eq!(
+            diff_config(&old, &running).unwrap(),
+            vec![
+                "http.rate_limit.per_second".to_string(),
+                "log.level".to_string(),
+                "task.checkpoint_interval".to_string()
+            ]
+        );
+    }
+}
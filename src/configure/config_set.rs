@@ -0,0 +1,311 @@
+use super::config_reload::validate_config;
+use super::Config;
+use crate::commons::sync_parent_dir;
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// 按点分路径修改配置文件中的一项，返回修改后的 yaml；dry_run 为 true 时不写入文件
+/// 该项在文件中为单行的值时只替换该行的值，注释与格式保留；否则按 yaml 整体写回，未知的字段原样保留
+pub fn set_config_file_value(path: &str, key: &str, raw: &str, dry_run: bool) -> Result<String> {
+    let path = match path.is_empty() {
+        true => "config.yml",
+        false => path,
+    };
+    let contents =
+        fs::read_to_string(path).map_err(|e| anyhow!("Read config file {} error: {}", path, e))?;
+    let mut doc = serde_yaml::from_str::<Value>(&contents)
+        .map_err(|e| anyhow!("Parse config file {} error: {}", path, e))?;
+    set_config_value(&mut doc, key, raw)?;
+    let config = serde_yaml::from_value::<Config>(doc.clone())
+        .map_err(|e| anyhow!("config invalid after setting {}: {}", key, e))?;
+    validate_config(&config).map_err(|e| anyhow!("config invalid after setting {}: {}", key, e))?;
+    let segments = key.split('.').collect::<Vec<&str>>();
+    let yml = match lookup(&doc, &segments)
+        .and_then(|v| set_value_in_text(&contents, &segments, v))
+        .filter(|t| serde_yaml::from_str::<Value>(t).ok().as_ref() == Some(&doc))
+    {
+        Some(t) => t,
+        None => serde_yaml::to_string(&doc)?,
+    };
+    if !dry_run {
+        write_file_atomic(path, &yml)?;
+    }
+    Ok(yml)
+}
+
+/// 修改 yaml 文档中的一项，值的类型取自文件中的原值，文件中不存在时取自默认配置
+pub fn set_config_value(doc: &mut Value, key: &str, raw: &str) -> Result<()> {
+    let segments = key.split('.').collect::<Vec<&str>>();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(anyhow!("invalid config key {}", key));
+    }
+    let defaults = serde_yaml::to_value(Config::default())?;
+    let template = match lookup(doc, &segments).or_else(|| lookup(&defaults, &segments)) {
+        Some(v) => v.clone(),
+        None => return Err(anyhow!("unknown config key {}", key)),
+    };
+    let value =
+        coerce_value(&template, raw).map_err(|e| anyhow!("invalid value for {}: {}", key, e))?;
+
+    let mut current = doc;
+    for (i, segment) in segments.iter().enumerate() {
+        if current.is_null() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        let map = match current.as_mapping_mut() {
+            Some(m) => m,
+            None => {
+                return Err(anyhow!(
+                    "config key {} is not a section",
+                    segments[..i].join(".")
+                ))
+            }
+        };
+        let k = Value::String(segment.to_string());
+        if i == segments.len() - 1 {
+            map.insert(k, value);
+            return Ok(());
+        }
+        if !map.contains_key(&k) {
+            map.insert(k.clone(), Value::Mapping(Mapping::new()));
+        }
+        current = match map.get_mut(&k) {
+            Some(v) => v,
+            None => return Err(anyhow!("config key {} not exist", key)),
+        };
+    }
+    Ok(())
+}
+
+fn lookup<'a>(doc: &'a Value, segments: &[&str]) -> Option<&'a Value> {
+    let mut current = doc;
+    for s in segments {
+        current = current.as_mapping()?.get(&Value::String(s.to_string()))?;
+    }
+    Some(current)
+}
+
+/// 在原文中替换单行的值，保留行尾注释；该项不存在、为多行结构或值无法单行表示时返回 None
+fn set_value_in_text(contents: &str, segments: &[&str], value: &Value) -> Option<String> {
+    let text = inline_value(value)?;
+    let mut stack: Vec<(usize, String)> = vec![];
+    let mut lines = contents
+        .split('\n')
+        .map(|l| l.to_string())
+        .collect::<Vec<String>>();
+    for line in lines.iter_mut() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('-') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        let (k, rest) = match trimmed
+            .find(": ")
+            .or_else(|| trimmed.strip_suffix(':').map(|s| s.len()))
+        {
+            Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+            None => continue,
+        };
+        let k = k
+            .trim()
+            .trim_matches(|c: char| c == '"' || c == '\'')
+            .to_string();
+        while stack.last().is_some_and(|(i, _)| *i >= indent) {
+            stack.pop();
+        }
+        let matched = stack.len() + 1 == segments.len()
+            && stack.iter().zip(segments).all(|((_, s), seg)| s == seg)
+            && k == segments[segments.len() - 1];
+        if !matched {
+            stack.push((indent, k));
+            continue;
+        }
+        let comment = inline_comment(rest)?;
+        if rest.len() == comment.len() {
+            // 值在后续行中
+            return None;
+        }
+        let key_end = line.len() - rest.len();
+        *line = format!("{} {}{}", &line[..key_end], text, comment);
+        return Some(lines.join("\n"));
+    }
+    None
+}
+
+// 值之后的行尾注释，含前导空白；值以引号开头时跳过引号内的内容
+fn inline_comment(rest: &str) -> Option<&str> {
+    let value = rest.trim_start();
+    let start = rest.len() - value.len();
+    let searched = match value.chars().next() {
+        Some(q @ ('"' | '\'')) => start + 1 + value[1..].find(q)? + 1,
+        _ => start,
+    };
+    match rest[searched..].find(" #") {
+        Some(i) => Some(&rest[searched + i..]),
+        None if value.starts_with('#') => Some(rest),
+        None => Some(""),
+    }
+}
+
+// 标量与标量列表以单行 yaml 表示
+fn inline_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Mapping(_) | Value::Tagged(_) => None,
+        Value::Sequence(items) => {
+            let items = items
+                .iter()
+                .map(|v| match v {
+                    Value::Sequence(_) => None,
+                    v => inline_value(v),
+                })
+                .collect::<Option<Vec<String>>>()?;
+            Some(format!("[{}]", items.join(", ")))
+        }
+        v => {
+            let text = serde_yaml::to_string(v).ok()?.trim_end().to_string();
+            (!text.contains('\n')).then_some(text)
+        }
+    }
+}
+
+/// 按原值类型转换命令行输入
+fn coerce_value(template: &Value, raw: &str) -> Result<Value> {
+    match template {
+        Value::Bool(_) => match raw {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(anyhow!("expect true or false, got {}", raw)),
+        },
+        Value::Number(n) if n.is_u64() => raw
+            .parse::<u64>()
+            .map(|v| Value::Number(v.into()))
+            .map_err(|_| anyhow!("expect unsigned integer, got {}", raw)),
+        Value::Number(n) if n.is_i64() => raw
+            .parse::<i64>()
+            .map(|v| Value::Number(v.into()))
+            .map_err(|_| anyhow!("expect integer, got {}", raw)),
+        Value::Number(_) => raw
+            .parse::<f64>()
+            .map(|v| Value::Number(v.into()))
+            .map_err(|_| anyhow!("expect number, got {}", raw)),
+        Value::String(_) => Ok(Value::String(raw.to_string())),
+        Value::Sequence(_) => match serde_yaml::from_str::<Value>(raw) {
+            Ok(v @ Value::Sequence(_)) => Ok(v),
+            _ => Err(anyhow!("expect list like [a, b], got {}", raw)),
+        },
+        Value::Mapping(_) => Err(anyhow!("is a section, set its fields instead")),
+        // Option 字段，未设置时按 yaml 标量解析
+        _ => match serde_yaml::from_str::<Value>(raw) {
+            Ok(v @ (Value::Mapping(_) | Value::Sequence(_))) => Err(anyhow!(
+                "expect scalar value, got {}",
+                serde_yaml::to_string(&v).unwrap_or_default().trim()
+            )),
+            Ok(v) => Ok(v),
+            Err(_) => Ok(Value::String(raw.to_string())),
+        },
+    }
+}
+
+// 写入临时文件后替换，避免中途失败留下不完整的配置
+fn write_file_atomic(path: &str, content: &str) -> Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.flush()?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{set_config_file_value, set_config_value, set_value_in_text};
+    use crate::configure::Config;
+    use serde_yaml::Value;
+
+    //cargo test configure::config_set::test::test_set_config_value -- --nocapture
+    #[test]
+    fn test_set_config_value() {
+        let mut doc = serde_yaml::from_str::<Value>("http:\n  port: 3000\ncustom: keep\n").unwrap();
+        set_config_value(&mut doc, "http.port", "3001").unwrap();
+        set_config_value(&mut doc, "task.checkpoint_interval", "30").unwrap();
+        set_config_value(&mut doc, "banner", "false").unwrap();
+        set_config_value(&mut doc, "http.unix", "/run/mario.sock").unwrap();
+        set_config_value(&mut doc, "http.listeners", "[127.0.0.1:3000]").unwrap();
+        assert_eq!(doc["http"]["port"].as_u64(), Some(3001));
+        assert_eq!(doc["task"]["checkpoint_interval"].as_u64(), Some(30));
+        assert_eq!(doc["banner"].as_bool(), Some(false));
+        assert_eq!(doc["http"]["unix"].as_str(), Some("/run/mario.sock"));
+        assert_eq!(doc["http"]["listeners"][0].as_str(), Some("127.0.0.1:3000"));
+        assert_eq!(doc["custom"].as_str(), Some("keep"));
+
+        assert!(set_config_value(&mut doc, "http.port", "abc").is_err());
+        assert!(set_config_value(&mut doc, "http.port", "-1").is_err());
+        assert!(set_config_value(&mut doc, "banner", "yes").is_err());
+        assert!(set_config_value(&mut doc, "http", "1").is_err());
+        assert!(set_config_value(&mut doc, "http.no_such_key", "1").is_err());
+        assert!(set_config_value(&mut doc, "http..port", "1").is_err());
+    }
+
+    //cargo test configure::config_set::test::test_set_value_in_text -- --nocapture
+    #[test]
+    fn test_set_value_in_text() {
+        let contents = "# mario\nhttp:\n  # 监听端口\n  port: 3000 # tcp\n  bind: '0.0.0.0'\n  listeners:\n  - 127.0.0.1:3000\ntask:\n  port: 1\n";
+        let port = Value::Number(3001.into());
+        let replaced = set_value_in_text(contents, &["http", "port"], &port).unwrap();
+        assert_eq!(
+            replaced,
+            contents.replace("port: 3000 # tcp", "port: 3001 # tcp")
+        );
+        let bind = Value::String("a: b".to_string());
+        let replaced = set_value_in_text(contents, &["http", "bind"], &bind).unwrap();
+        assert!(replaced.contains("  bind: 'a: b'\n"));
+        let task_port = set_value_in_text(contents, &["task", "port"], &port).unwrap();
+        assert!(task_port.contains("  port: 3000 # tcp") && task_port.contains("  port: 3001\n"));
+
+        // 多行结构与不存在的项整体写回
+        let list = Value::Sequence(vec![Value::String("[::1]:3000".to_string())]);
+        assert!(set_value_in_text(contents, &["http", "listeners"], &list).is_none());
+        assert!(set_value_in_text(contents, &["http", "unix"], &port).is_none());
+    }
+
+    //cargo test configure::config_set::test::test_set_config_file_value -- --nocapture
+    #[test]
+    fn test_set_config_file_value() {
+        let dir = "/tmp/config_set_test";
+        let path = format!("{}/config.yml", dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let mut yml = format!(
+            "# comment\n{}",
+            serde_yaml::to_string(&Config::default()).unwrap()
+        );
+        yml.push_str("unknown_field: 1\n");
+        std::fs::write(&path, &yml).unwrap();
+
+        let dry = set_config_file_value(&path, "http.port", "3001", true).unwrap();
+        assert!(dry.contains("port: 3001"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), yml);
+
+        // 数值超出字段范围或未通过校验时不写入
+        assert!(set_config_file_value(&path, "http.port", "70000", false).is_err());
+        assert!(set_config_file_value(&path, "task.checkpoint_interval", "0", false).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), yml);
+
+        set_config_file_value(&path, "http.port", "3001", false).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("unknown_field: 1"));
+        assert!(written.starts_with("# comment\n"));
+        let config = serde_yaml::from_str::<Config>(&written).unwrap();
+        assert_eq!(config.http.port, 3001);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
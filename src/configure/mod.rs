@@ -1,3 +1,9 @@
 mod config_error;
 mod config_global;
+mod config_overrides;
+mod config_reload;
+mod config_set;
 pub use config_global::*;
+pub use config_overrides::*;
+pub use config_reload::*;
+pub use config_set::*;
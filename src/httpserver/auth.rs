@@ -0,0 +1,204 @@
+use crate::configure::{get_config, AuthConfig, TokenScope};
+use crate::httpserver::module::ApiError;
+use axum::extract::Request;
+use axum::http::{header, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+
+// 无需鉴权的路径，供负载均衡与监控探测，同样不参与限流
+pub(crate) const AUTH_EXEMPT_PATHS: [&'static str; 5] = [
+    "/health",
+    "/api/v1/health",
+    "/healthz",
+    "/readyz",
+    "/metrics",
+];
+
+// 以此为前缀的接口暴露运行时内部状态或影响整个服务，需 admin 范围
+const ADMIN_PATH_PREFIX: &'static str = "/api/v1/admin/";
+
+// 仅查询的 POST 接口，read 范围即可访问
+pub(crate) const READ_SCOPE_POST_PATHS: [&'static str; 8] = [
+    "/api/v1/currentconfig",
+    "/api/v1/task/status",
+    "/api/v1/task/show",
+    "/api/v1/task/analyze",
+    "/api/v1/task/validate",
+    "/api/v1/task/all",
+    "/api/v1/task/all_living",
+    "/api/v1/task/all_stream",
+];
+
+// 会修改任务的 GET 接口后缀，需 write 范围
+const WRITE_SCOPE_GET_SUFFIXES: [&'static str; 1] = ["/revert"];
+
+// 逐字节比较全部内容，耗时与不匹配的位置无关
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+// 遍历全部 token，不提前返回
+fn token_in(token: &str, tokens: &[&str]) -> bool {
+    tokens.iter().fold(false, |found, t| {
+        constant_time_eq(token.as_bytes(), t.as_bytes()) | found
+    })
+}
+
+/// 校验 token，同一 token 配置了多个范围时取最大的，未匹配时返回 None
+pub fn resolve_token(auth: &AuthConfig, token: &str) -> Option<TokenScope> {
+    let scopes = auth.token_scopes();
+    // 各范围均比较完再取结果
+    let matched = [TokenScope::Admin, TokenScope::Write, TokenScope::Read].map(|scope| {
+        let tokens = scopes
+            .iter()
+            .filter(|(_, s)| *s == scope)
+            .map(|(t, _)| *t)
+            .collect::<Vec<&str>>();
+        (scope, token_in(token, &tokens))
+    });
+    matched
+        .into_iter()
+        .find(|(_, m)| *m)
+        .map(|(scope, _)| scope)
+}
+
+/// 接口所需的范围：/admin 接口需 admin，查询接口需 read，其余需 write
+pub fn required_scope(method: &Method, path: &str) -> TokenScope {
+    if path.starts_with(ADMIN_PATH_PREFIX) {
+        return TokenScope::Admin;
+    }
+    let query = match *method {
+        Method::GET | Method::HEAD => !WRITE_SCOPE_GET_SUFFIXES.iter().any(|s| path.ends_with(s)),
+        Method::POST => READ_SCOPE_POST_PATHS.contains(&path),
+        _ => false,
+    };
+    match query {
+        true => TokenScope::Read,
+        false => TokenScope::Write,
+    }
+}
+
+/// 鉴权中间件写入请求扩展的调用方范围，未启用鉴权或未经中间件时视为 admin
+pub fn caller_scope(scope: Option<Extension<TokenScope>>) -> TokenScope {
+    scope.map(|Extension(s)| s).unwrap_or(TokenScope::Admin)
+}
+
+fn bearer_token(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|t| t.trim())
+}
+
+/// 校验 Authorization: Bearer <token> 及其范围，未配置 token 时放行；token 不记录日志
+/// 通过后将调用方范围写入请求扩展，handler 据此决定是否脱敏等
+pub async fn auth_middleware(mut request: Request, next: Next) -> Response {
+    if AUTH_EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let auth = match get_config() {
+        Ok(c) => c.auth,
+        Err(e) => return ApiError::Internal(e.to_string()).into_response(),
+    };
+    if !auth.enabled() {
+        request.extensions_mut().insert(TokenScope::Admin);
+        return next.run(request).await;
+    }
+    let scope = match bearer_token(&request) {
+        Some(token) => resolve_token(&auth, token),
+        None => return ApiError::Unauthorized("missing bearer token".to_string()).into_response(),
+    };
+    let scope = match scope {
+        Some(s) => s,
+        None => return ApiError::Unauthorized("invalid bearer token".to_string()).into_response(),
+    };
+    let required = required_scope(request.method(), request.uri().path());
+    if scope < required {
+        return ApiError::Forbidden {
+            required_scope: required,
+            token_scope: scope,
+        }
+        .into_response();
+    }
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{constant_time_eq, required_scope, resolve_token};
+    use crate::configure::{AuthConfig, ScopedToken, TokenScope};
+    use axum::http::Method;
+
+    //cargo test httpserver::auth::test::test_resolve_token -- --nocapture
+    #[test]
+    fn test_resolve_token() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+
+        let mut auth = AuthConfig::default();
+        assert!(!auth.enabled());
+        auth.api_token = Some("admin".to_string());
+        auth.tokens = vec!["writer".to_string()];
+        auth.readonly_tokens = vec!["reader".to_string()];
+        assert!(auth.enabled());
+        assert_eq!(resolve_token(&auth, "admin"), Some(TokenScope::Admin));
+        assert_eq!(resolve_token(&auth, "writer"), Some(TokenScope::Admin));
+        assert_eq!(resolve_token(&auth, "reader"), Some(TokenScope::Read));
+        assert_eq!(resolve_token(&auth, "read"), None);
+        assert_eq!(resolve_token(&auth, ""), None);
+
+        auth.scoped_tokens = vec![
+            ScopedToken {
+                token: "dashboard".to_string(),
+                scope: TokenScope::Read,
+            },
+            ScopedToken {
+                token: "pipeline".to_string(),
+                scope: TokenScope::Write,
+            },
+            // 重复配置时取最大范围
+            ScopedToken {
+                token: "reader".to_string(),
+                scope: TokenScope::Write,
+            },
+        ];
+        assert_eq!(resolve_token(&auth, "dashboard"), Some(TokenScope::Read));
+        assert_eq!(resolve_token(&auth, "pipeline"), Some(TokenScope::Write));
+        assert_eq!(resolve_token(&auth, "reader"), Some(TokenScope::Write));
+    }
+
+    //cargo test httpserver::auth::test::test_required_scope -- --nocapture
+    #[test]
+    fn test_required_scope() {
+        let cases = [
+            (Method::GET, "/api/v1/task/1/status", TokenScope::Read),
+            (Method::HEAD, "/api/v1/info", TokenScope::Read),
+            (Method::POST, "/api/v1/task/show", TokenScope::Read),
+            (Method::POST, "/api/v1/task/create", TokenScope::Write),
+            (Method::PATCH, "/api/v1/task/1", TokenScope::Write),
+            (
+                Method::GET,
+                "/api/v1/task/1/changes/3/revert",
+                TokenScope::Write,
+            ),
+            (Method::GET, "/api/v1/admin/runtime", TokenScope::Admin),
+            (Method::POST, "/api/v1/admin/db/compact", TokenScope::Admin),
+        ];
+        for (method, path, scope) in cases {
+            assert_eq!(required_scope(&method, path), scope, "{} {}", method, path);
+        }
+        assert!(TokenScope::Read < TokenScope::Write && TokenScope::Write < TokenScope::Admin);
+    }
+}
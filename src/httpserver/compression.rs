@@ -0,0 +1,125 @@
+use crate::configure::HttpCompressionConfig;
+use tower_http::compression::predicate::{And, NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+/// 压缩判定：达到最小大小，且不是 SSE、grpc 与图片
+pub type CompressionPredicate =
+    And<And<And<SizeAbove, NotForContentType>, NotForContentType>, NotForContentType>;
+
+/// 按配置生成压缩中间件，仅支持 gzip 与 br；未启用时返回 None
+pub fn compression_layer(
+    config: &HttpCompressionConfig,
+) -> Option<CompressionLayer<CompressionPredicate>> {
+    if !config.enabled || !(config.gzip || config.br) {
+        return None;
+    }
+    // SSE 响应没有 Content-Length，需按 Content-Type 排除，避免事件被压缩缓冲
+    let predicate = SizeAbove::new(config.min_size)
+        .and(NotForContentType::SSE)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES);
+    Some(
+        CompressionLayer::new()
+            .gzip(config.gzip)
+            .br(config.br)
+            .deflate(false)
+            .zstd(false)
+            .compress_when(predicate),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::compression_layer;
+    use crate::configure::HttpCompressionConfig;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request};
+    use axum::response::sse::{Event, Sse};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use flate2::read::GzDecoder;
+    use serde_json::{json, Value};
+    use std::convert::Infallible;
+    use std::io::Read;
+    use tower::ServiceExt;
+
+    fn task_list() -> Value {
+        let tasks = (0..2000)
+            .map(
+                |i| json!({"cf_id": i.to_string(), "task": {"transfer": {"name": "default_name"}}}),
+            )
+            .collect::<Vec<Value>>();
+        json!({"code": 0, "msg": "OK", "data": tasks})
+    }
+
+    fn router(config: &HttpCompressionConfig) -> Router {
+        let router = Router::new()
+            .route("/api/v1/task/all", post(|| async { Json(task_list()) }))
+            .route(
+                "/api/v1/task/:task_id/events",
+                get(|| async {
+                    let events = futures::stream::iter(
+                        (0..200).map(|i| Ok::<_, Infallible>(Event::default().data(i.to_string()))),
+                    );
+                    Sse::new(events)
+                }),
+            );
+        match compression_layer(config) {
+            Some(layer) => router.layer(layer),
+            None => router,
+        }
+    }
+
+    async fn request(
+        router: Router,
+        method: &str,
+        uri: &str,
+        gzip: bool,
+    ) -> (Option<String>, Vec<u8>) {
+        let mut req = Request::builder().method(method).uri(uri);
+        if gzip {
+            req = req.header(header::ACCEPT_ENCODING, "gzip");
+        }
+        let resp = router
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let encoding = resp
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (encoding, body.to_vec())
+    }
+
+    //cargo test httpserver::compression::test::test_compress_task_list -- --nocapture
+    #[tokio::test]
+    async fn test_compress_task_list() {
+        let config = HttpCompressionConfig::default();
+        let (encoding, plain) = request(router(&config), "POST", "/api/v1/task/all", false).await;
+        assert!(encoding.is_none());
+
+        let (encoding, compressed) =
+            request(router(&config), "POST", "/api/v1/task/all", true).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert!(compressed.len() < plain.len());
+        let mut decompressed = vec![];
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&decompressed).unwrap(),
+            serde_json::from_slice::<Value>(&plain).unwrap()
+        );
+
+        // 事件流不压缩
+        let (encoding, _) = request(router(&config), "GET", "/api/v1/task/1/events", true).await;
+        assert!(encoding.is_none());
+
+        // 关闭后不压缩
+        let mut disabled = config.clone();
+        disabled.enabled = false;
+        let (encoding, _) = request(router(&disabled), "POST", "/api/v1/task/all", true).await;
+        assert!(encoding.is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::configure::HttpCorsConfig;
+use crate::httpserver::request_id::REQUEST_ID_HEADER;
+use anyhow::{anyhow, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+/// 按配置生成跨域中间件，预检请求由中间件直接应答；未启用时返回 None
+pub fn cors_layer(cors: &HttpCorsConfig) -> Result<Option<CorsLayer>> {
+    if !cors.enabled() {
+        return Ok(None);
+    }
+    cors.validate()?;
+
+    let origin = match cors.any_origin() {
+        true => AllowOrigin::any(),
+        false => AllowOrigin::list(
+            cors.allowed_origins
+                .iter()
+                .map(|o| {
+                    HeaderValue::from_str(o.trim_end_matches('/'))
+                        .map_err(|e| anyhow!("invalid http.cors.allowed_origins {}: {}", o, e))
+                })
+                .collect::<Result<Vec<HeaderValue>>>()?,
+        ),
+    };
+    let methods = match cors.allowed_methods.iter().any(|m| m == "*") {
+        true => AllowMethods::any(),
+        false => AllowMethods::list(
+            cors.allowed_methods
+                .iter()
+                .map(|m| {
+                    Method::from_bytes(m.to_uppercase().as_bytes())
+                        .map_err(|e| anyhow!("invalid http.cors.allowed_methods {}: {}", m, e))
+                })
+                .collect::<Result<Vec<Method>>>()?,
+        ),
+    };
+    let headers = match cors.allowed_headers.iter().any(|h| h == "*") {
+        true => AllowHeaders::any(),
+        false => AllowHeaders::list(
+            cors.allowed_headers
+                .iter()
+                .map(|h| {
+                    HeaderName::from_bytes(h.to_lowercase().as_bytes())
+                        .map_err(|e| anyhow!("invalid http.cors.allowed_headers {}: {}", h, e))
+                })
+                .collect::<Result<Vec<HeaderName>>>()?,
+        ),
+    };
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .allow_credentials(cors.allow_credentials)
+            .expose_headers([REQUEST_ID_HEADER])
+            .max_age(Duration::from_secs(cors.max_age_secs)),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::cors_layer;
+    use crate::configure::HttpCorsConfig;
+
+    //cargo test httpserver::cors::test::test_cors_layer -- --nocapture
+    #[test]
+    fn test_cors_layer() {
+        // 未配置来源时不启用
+        let disabled = HttpCorsConfig::default();
+        assert!(cors_layer(&disabled).unwrap().is_none());
+
+        let mut cors = HttpCorsConfig::default();
+        cors.allowed_origins = vec!["https://console.example.com".to_string()];
+        cors.allow_credentials = true;
+        assert!(cors_layer(&cors).unwrap().is_some());
+
+        cors.allowed_origins = vec!["*".to_string()];
+        let err = cors_layer(&cors).unwrap_err().to_string();
+        assert!(err.contains("allow_credentials"));
+
+        cors.allow_credentials = false;
+        assert!(cors_layer(&cors).unwrap().is_some());
+
+        cors.allowed_methods = vec!["NOT A METHOD".to_string()];
+        assert!(cors_layer(&cors).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+use axum::extract::Request;
+use axum::http::{header, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+pub const API_V1_PREFIX: &'static str = "/api/v1";
+pub const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+
+/// 未加版本前缀的旧路径对应的 v1 路径
+pub fn successor_path(path: &str) -> String {
+    format!("{}{}", API_V1_PREFIX, path)
+}
+
+/// 旧路径作为 v1 接口的别名继续提供服务，记录告警并通过响应头提示迁移
+pub async fn deprecated_alias_middleware(request: Request, next: Next) -> Response {
+    let successor = successor_path(request.uri().path());
+    log::warn!(
+        "deprecated api {} {} called, use {} instead",
+        request.method(),
+        request.uri().path(),
+        successor
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION_HEADER, HeaderValue::from_static("true"));
+    if let Ok(v) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.insert(header::LINK, v);
+    }
+    response
+}
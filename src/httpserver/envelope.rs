@@ -0,0 +1,140 @@
+use crate::httpserver::deprecation::API_V1_PREFIX;
+use axum::body::{to_bytes, Body, HttpBody};
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+
+// 旧结构中 code 不为 0 的失败响应，如部分任务失败的批量启停
+const REQUEST_FAILED_CODE: &'static str = "request_failed";
+
+/// 请求 v1 响应结构的媒体类型，请求头 Accept 中携带时 /api/v1 的响应改为 {"data","error"}
+pub const V1_ENVELOPE_MEDIA_TYPE: &'static str = "application/vnd.mario.v1+json";
+
+/// 请求头 Accept 中是否携带 V1_ENVELOPE_MEDIA_TYPE
+pub fn accepts_v1_envelope(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|t| {
+            t.split(';')
+                .next()
+                .map(|m| m.trim().eq_ignore_ascii_case(V1_ENVELOPE_MEDIA_TYPE))
+                .unwrap_or(false)
+        })
+}
+
+/// 将旧结构的响应体转换为 v1 的 {"data": ..., "error": ...}
+/// 成功响应 {"code": 0, "msg", "data"} 的 error 为 null；ApiError 的 {"code", "message", "details"} 整体作为 error，data 为 null
+/// 已是 v1 结构或不是上述结构的响应体原样返回
+pub fn v1_envelope(body: Value) -> Value {
+    let mut body = match body {
+        Value::Object(m) if m.contains_key("code") && !m.contains_key("error") => m,
+        other => return other,
+    };
+    match body.get("code") {
+        Some(Value::Number(n)) if n.as_i64() == Some(0) => {
+            json!({"data": body.remove("data").unwrap_or(Value::Null), "error": null})
+        }
+        Some(Value::Number(_)) => json!({
+            "data": body.remove("data").unwrap_or(Value::Null),
+            "error": {
+                "code": REQUEST_FAILED_CODE,
+                "message": body.remove("msg").unwrap_or(Value::Null),
+                "details": {},
+            },
+        }),
+        Some(Value::String(_)) => json!({"data": null, "error": Value::Object(body)}),
+        _ => Value::Object(body),
+    }
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.as_bytes().starts_with(b"application/json"))
+        .unwrap_or(false)
+}
+
+/// 请求头 Accept 携带 V1_ENVELOPE_MEDIA_TYPE 时，/api/v1 下的 json 响应改为 v1 结构
+/// 未携带时保持 {"code","msg","data"}，已有客户端不受影响；未加版本前缀的废弃别名总是旧结构
+/// 流式返回的响应体（如 /task/all_stream）由 handler 按请求头输出，不在此缓冲
+pub async fn v1_envelope_middleware(request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with(API_V1_PREFIX) || !accepts_v1_envelope(request.headers()) {
+        return next.run(request).await;
+    }
+    let response = next.run(request).await;
+    if !is_json(&response) || response.body().size_hint().exact().is_none() {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let value = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(v) => v,
+        Err(_) => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    let enveloped = match serde_json::to_vec(&v1_envelope(value)) {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(enveloped))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{accepts_v1_envelope, v1_envelope, V1_ENVELOPE_MEDIA_TYPE};
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use serde_json::json;
+
+    //cargo test httpserver::envelope::test::test_accepts_v1_envelope -- --nocapture
+    #[test]
+    fn test_accepts_v1_envelope() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_v1_envelope(&headers));
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        assert!(!accepts_v1_envelope(&headers));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/plain, Application/Vnd.Mario.V1+json; q=0.9"),
+        );
+        assert!(accepts_v1_envelope(&headers));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static(V1_ENVELOPE_MEDIA_TYPE),
+        );
+        assert!(accepts_v1_envelope(&headers));
+    }
+
+    //cargo test httpserver::envelope::test::test_v1_envelope -- --nocapture
+    #[test]
+    fn test_v1_envelope() {
+        assert_eq!(
+            v1_envelope(json!({"code": 0, "msg": "OK", "data": {"task_id": "1"}})),
+            json!({"data": {"task_id": "1"}, "error": null})
+        );
+        let error = json!({"code": "task_not_found", "message": "task 1 not exist", "details": {"task_id": "1"}});
+        assert_eq!(
+            v1_envelope(error.clone()),
+            json!({"data": null, "error": error})
+        );
+        assert_eq!(
+            v1_envelope(json!({"code": 1, "msg": "1 of 2 tasks failed", "data": []})),
+            json!({
+                "data": [],
+                "error": {"code": "request_failed", "message": "1 of 2 tasks failed", "details": {}},
+            })
+        );
+        // 已是 v1 结构或其他结构时不变
+        let enveloped = json!({"data": 1, "error": null});
+        assert_eq!(v1_envelope(enveloped.clone()), enveloped);
+        assert_eq!(v1_envelope(json!([1, 2])), json!([1, 2]));
+    }
+}
@@ -1,3 +0,0 @@
-mod error;
-pub use error::AppError;
-pub use error::AppErrorType;
@@ -0,0 +1,101 @@
+use crate::httpserver::module::ApiError;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::StatusCode;
+use axum_macros::{FromRequest, FromRequestParts};
+
+/// 请求体解析失败时返回 invalid_request，超出 http.max_body_size 时返回 payload_too_large
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// 查询参数解析失败时返回 invalid_request
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
+
+/// 路径参数解析失败时返回 invalid_request
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
+
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        match e.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge(e.body_text()),
+            _ => ApiError::InvalidRequest(e.body_text()),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        ApiError::InvalidRequest(e.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(e: PathRejection) -> Self {
+        match e.status() {
+            StatusCode::INTERNAL_SERVER_ERROR => ApiError::Internal(e.body_text()),
+            _ => ApiError::InvalidRequest(e.body_text()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ApiJson, ApiPath, ApiQuery};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request, StatusCode};
+    use axum::routing::{get, post};
+    use axum::Router;
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Body1 {
+        #[allow(dead_code)]
+        id: String,
+    }
+
+    async fn error_body(app: Router, req: Request<Body>) -> (StatusCode, Value) {
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    //cargo test httpserver::extract::test::test_rejection_json -- --nocapture
+    #[tokio::test]
+    async fn test_rejection_json() {
+        let app = Router::new()
+            .route("/json", post(|ApiJson(_): ApiJson<Body1>| async {}))
+            .route("/query", get(|ApiQuery(_): ApiQuery<Body1>| async {}))
+            .route("/path/:seq", get(|ApiPath(_): ApiPath<u64>| async {}));
+
+        let cases = vec![
+            Request::post("/json")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from("{\"id\":1}"))
+                .unwrap(),
+            Request::post("/json")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from("{\"id\":\"1\",\"x\":1}"))
+                .unwrap(),
+            Request::post("/json")
+                .body(Body::from("{\"id\":\"1\"}"))
+                .unwrap(),
+            Request::get("/query?x=1").body(Body::empty()).unwrap(),
+            Request::get("/path/abc").body(Body::empty()).unwrap(),
+        ];
+        for req in cases {
+            let uri = req.uri().to_string();
+            let (status, body) = error_body(app.clone(), req).await;
+            println!("{} {} {}", uri, status, body);
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["code"], "invalid_request", "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use super::HandlerResult;
+use crate::commons::{BufferPoolStats, GLOBAL_BUFFER_POOL};
+use crate::configure::{get_config, get_config_reload_status, ConfigReloadStatus};
+use crate::httpserver::extract::{ApiJson, ApiPath, ApiQuery};
+use crate::httpserver::module::{
+    ApiError, ReqDbCompact, ReqDbPrune, ReqDbRepair, ReqLogLevel, ReqSelfStats, ReqTaskQueue,
+    ReqTaskQueueLimit, RespCheckpointFlushAll, RespDbStats, RespSelfStats, RespTaskQueue, Response,
+};
+use crate::httpserver::service::service_task::service_flush_all_checkpoints;
+use crate::logger::{get_log_level, set_log_level};
+use crate::resources::{
+    check_rocksdb, get_db_job, get_rocksdb_path, last_db_compaction, list_backups,
+    next_db_compaction, repair_rocksdb, resolve_compact_cfs, rocksdb_auto_compaction,
+    start_db_backup, start_db_compaction, start_db_prune, start_db_reindex, BackupInfo,
+    DbCheckReport, DbJob, DbPruneOptions, DbRepairReport,
+};
+use crate::server::{
+    rocksdb_cf_sizes, runtime_threads, self_stats_since, self_stats_summary, RuntimeThreads,
+    SELF_STATS_SUMMARY_WINDOW,
+};
+use crate::tasks::{
+    dump_runtime_state, executing_task_count, live_task_states, max_concurrent_tasks,
+    namespace_queue_stats, queued_tasks, set_max_concurrent_tasks, LiveTaskState, RuntimeStateDump,
+};
+use axum::Json;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub async fn log_level_current() -> HandlerResult<Value> {
+    Ok(Json(Response::ok(json!({"level":get_log_level()}))))
+}
+
+pub async fn log_level_set(ApiJson(req): ApiJson<ReqLogLevel>) -> HandlerResult<Value> {
+    match set_log_level(&req.level) {
+        Ok(_) => Ok(Json(Response::ok(json!({"level":req.level})))),
+        Err(e) => Err(ApiError::InvalidRequest(e.to_string())),
+    }
+}
+
+pub async fn config_reload_status() -> HandlerResult<ConfigReloadStatus> {
+    Ok(Json(Response::ok(get_config_reload_status())))
+}
+
+/// 各 runtime 实际使用的线程参数
+pub async fn runtime_threads_current() -> HandlerResult<BTreeMap<String, RuntimeThreads>> {
+    Ok(Json(Response::ok(runtime_threads())))
+}
+
+pub async fn buffer_pool_stats() -> HandlerResult<BufferPoolStats> {
+    Ok(Json(Response::ok(GLOBAL_BUFFER_POOL.stats())))
+}
+
+/// 立即写入全部运行中任务的 checkpoint，用于计划内的重启前
+pub async fn checkpoint_flush_all() -> HandlerResult<RespCheckpointFlushAll> {
+    match service_flush_all_checkpoints().await {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 运行中任务的内存状态，含全部列表文件位置
+pub async fn runtime_tasks() -> HandlerResult<Vec<LiveTaskState>> {
+    Ok(Json(Response::ok(live_task_states().await)))
+}
+
+/// 并发上限与排队中的任务，group_by=namespace 时同时返回各 namespace 的运行与排队数
+pub async fn task_queue_current(
+    ApiQuery(req): ApiQuery<ReqTaskQueue>,
+) -> HandlerResult<RespTaskQueue> {
+    let group_by_namespace = match req.group_by.as_deref() {
+        None => false,
+        Some("namespace") => true,
+        Some(other) => {
+            return Err(ApiError::InvalidRequest(format!(
+                "unsupported group_by {}, expect namespace",
+                other
+            )))
+        }
+    };
+    let (dispatch_policy, namespaces) = namespace_queue_stats();
+    Ok(Json(Response::ok(RespTaskQueue {
+        max_concurrent_tasks: max_concurrent_tasks(),
+        executing: executing_task_count(),
+        queued: queued_tasks(),
+        dispatch_policy,
+        namespaces: group_by_namespace.then_some(namespaces),
+    })))
+}
+
+/// 运行时调整并发上限，服务重启后恢复为配置值
+pub async fn task_queue_limit_set(
+    ApiJson(req): ApiJson<ReqTaskQueueLimit>,
+) -> HandlerResult<Value> {
+    match set_max_concurrent_tasks(req.max_concurrent_tasks) {
+        Ok(()) => Ok(Json(Response::ok(
+            json!({"max_concurrent_tasks":req.max_concurrent_tasks}),
+        ))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn runtime_state_dump() -> HandlerResult<RuntimeStateDump> {
+    match dump_runtime_state().await {
+        Ok(dump) => Ok(Json(Response::ok(dump))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn self_stats(ApiQuery(req): ApiQuery<ReqSelfStats>) -> HandlerResult<RespSelfStats> {
+    let stats = self_stats_since(req.since.unwrap_or(0)).and_then(|samples| {
+        Ok(RespSelfStats {
+            samples,
+            last_hour: self_stats_summary(SELF_STATS_SUMMARY_WINDOW)?,
+        })
+    });
+    match stats {
+        Ok(s) => Ok(Json(Response::ok(s))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 后台压缩 rocksdb，可指定单个 column family；立即返回作业，经 `/admin/db/jobs/{id}` 查询进度
+pub async fn db_compact(ApiQuery(req): ApiQuery<ReqDbCompact>) -> HandlerResult<DbJob> {
+    let cfs = match resolve_compact_cfs(req.cf.as_deref()) {
+        Ok(cfs) => cfs,
+        Err(e) => return Err(ApiError::InvalidRequest(e.to_string())),
+    };
+    match start_db_compaction(req.cf, cfs, None) {
+        Ok(job) => Ok(Json(Response::ok(job))),
+        Err(job_id) => Err(ApiError::DbJobConflict { job_id }),
+    }
+}
+
+/// 后台清理任务已不存在的状态与 checkpoint，以及停止超过 db.status_retention_days 天的状态
+pub async fn db_prune(ApiQuery(req): ApiQuery<ReqDbPrune>) -> HandlerResult<DbJob> {
+    let db = get_config().map(|c| c.db).unwrap_or_default();
+    let options = DbPruneOptions {
+        dry_run: req.dry_run.unwrap_or(false),
+        status_retention_days: db.status_retention_days,
+    };
+    match start_db_prune(options) {
+        Ok(job) => Ok(Json(Response::ok(job))),
+        Err(job_id) => Err(ApiError::DbJobConflict { job_id }),
+    }
+}
+
+/// 后台按任务定义重建名称索引
+pub async fn db_reindex() -> HandlerResult<DbJob> {
+    match start_db_reindex() {
+        Ok(job) => Ok(Json(Response::ok(job))),
+        Err(job_id) => Err(ApiError::DbJobConflict { job_id }),
+    }
+}
+
+/// 列出无法解码的记录
+pub async fn db_check() -> HandlerResult<DbCheckReport> {
+    match check_rocksdb() {
+        Ok(report) => Ok(Json(Response::ok(report))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 删除操作员确认的损坏记录，确认后已恢复正常的记录不删除
+pub async fn db_repair(ApiJson(req): ApiJson<ReqDbRepair>) -> HandlerResult<DbRepairReport> {
+    if req.delete_corrupt.is_empty() {
+        return Err(ApiError::InvalidRequest(
+            "delete_corrupt is empty".to_string(),
+        ));
+    }
+    match repair_rocksdb(&req.delete_corrupt) {
+        Ok(report) => Ok(Json(Response::ok(report))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 后台备份 rocksdb 到 db.backup_dir，备份 id 与大小在作业结束后返回
+pub async fn db_backup() -> HandlerResult<DbJob> {
+    let db = get_config().map(|c| c.db).unwrap_or_default();
+    match start_db_backup(db.backup_dir, db.backup_keep) {
+        Ok(job) => Ok(Json(Response::ok(job))),
+        Err(job_id) => Err(ApiError::DbJobConflict { job_id }),
+    }
+}
+
+/// 各 column family 大小与压缩情况
+pub async fn db_stats() -> HandlerResult<RespDbStats> {
+    let db = get_config().map(|c| c.db).unwrap_or_default();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cf_sizes = rocksdb_cf_sizes()
+        .into_iter()
+        .map(|(cf, size)| (cf.to_string(), size))
+        .collect::<BTreeMap<String, u64>>();
+    Ok(Json(Response::ok(RespDbStats {
+        path: get_rocksdb_path(),
+        total_size: cf_sizes.values().sum(),
+        cf_sizes,
+        auto_compaction: rocksdb_auto_compaction(),
+        next_compaction: next_db_compaction(&db.compaction_schedule, now).unwrap_or(None),
+        compaction_schedule: db.compaction_schedule,
+        last_compaction: last_db_compaction(),
+    })))
+}
+
+/// db.backup_dir 中的备份，按 id 升序
+pub async fn db_backups() -> HandlerResult<Vec<BackupInfo>> {
+    let db = get_config().map(|c| c.db).unwrap_or_default();
+    match list_backups(&db.backup_dir) {
+        Ok(backups) => Ok(Json(Response::ok(backups))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn db_job(ApiPath(job_id): ApiPath<String>) -> HandlerResult<DbJob> {
+    match get_db_job(&job_id) {
+        Some(job) => Ok(Json(Response::ok(job))),
+        None => Err(ApiError::NotFound(format!("db job {} not exist", job_id))),
+    }
+}
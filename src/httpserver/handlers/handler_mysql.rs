@@ -1,8 +1,7 @@
 use axum::Json;
 
 use crate::httpserver::{
-    exception::{AppError, AppErrorType},
-    module::Response,
+    module::{ApiError, Response},
     service::insert_rbatis_t,
 };
 
@@ -12,13 +11,6 @@ pub async fn rbatis_t_insert() -> HandlerResult<()> {
     let result = insert_rbatis_t().await;
     match result {
         Ok(str) => Ok(Json(Response::ok(str))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+        Err(e) => Err(ApiError::from(e)),
     }
 }
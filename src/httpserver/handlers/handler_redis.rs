@@ -1,24 +1,17 @@
+use crate::httpserver::extract::ApiJson;
 use axum::Json;
 
 use crate::httpserver::{
-    exception::{AppError, AppErrorType},
-    module::{Response, KV},
+    module::{ApiError, Response, KV},
     service::put,
 };
 
 use super::HandlerResult;
 
-pub async fn redis_put(Json(payload): Json<KV>) -> HandlerResult<()> {
+pub async fn redis_put(ApiJson(payload): ApiJson<KV>) -> HandlerResult<()> {
     let result = put(payload);
     match result {
         Ok(str) => Ok(Json(Response::ok(str))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+        Err(e) => Err(ApiError::from(e)),
     }
 }
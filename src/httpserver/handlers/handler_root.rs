@@ -1,4 +1,17 @@
-use crate::httpserver::module::Response;
+use crate::configure::{
+    get_config, get_config_overrides, get_config_reload_status, get_env_override_fields,
+};
+use crate::httpserver::module::{
+    ApiError, RespBuildInfo, RespServerInfo, RespServerStats, Response,
+};
+use crate::logger::get_log_level;
+use crate::resources::get_rocksdb_path;
+use crate::server::{
+    check_readiness, runtime_threads, self_stats_summary, server_last_stop, server_start_time,
+    server_stats, server_uptime, Readiness, GLOBAL_METRICS, SELF_STATS_SUMMARY_WINDOW,
+};
+use crate::tasks::GLOBAL_LIVING_TRANSFER_TASK_MAP;
+use axum::http::{header, StatusCode};
 use axum::Json;
 use serde_json::{json, Value};
 
@@ -7,3 +20,92 @@ use super::HandlerResult;
 pub async fn root() -> HandlerResult<Value> {
     Ok(Json(Response::ok(json!({"health":"ok"}))))
 }
+
+fn build_info() -> RespBuildInfo {
+    RespBuildInfo {
+        git_commit: option_env!("MARIO_GIT_COMMIT").map(|c| c.to_string()),
+        profile: match cfg!(debug_assertions) {
+            true => "debug".to_string(),
+            false => "release".to_string(),
+        },
+        target: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+    }
+}
+
+/// 服务实例信息：版本、启动时间、运行时长、上次停机是否正常、监听地址、存储路径、日志等级、线程数、运行中任务数、最近一次配置重载与最近一小时的资源使用汇总
+pub async fn server_info() -> HandlerResult<RespServerInfo> {
+    let config = match get_config() {
+        Ok(c) => c,
+        Err(e) => return Err(ApiError::from(e)),
+    };
+    let reload = get_config_reload_status();
+    let self_stats_last_hour = match self_stats_summary(SELF_STATS_SUMMARY_WINDOW) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            log::warn!("summarize self stats error: {}", e);
+            None
+        }
+    };
+    Ok(Json(Response::ok(RespServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        build: build_info(),
+        pid: std::process::id(),
+        start_time: server_start_time(),
+        uptime_seconds: server_uptime().map(|d| d.as_secs()).unwrap_or(0),
+        last_stop: server_last_stop(),
+        runtime_threads: runtime_threads(),
+        http_endpoints: match config.http.endpoints() {
+            Ok(v) => v.iter().map(|e| e.to_string()).collect(),
+            Err(_) => vec![],
+        },
+        config_overrides: get_config_overrides(),
+        config_env_overrides: get_env_override_fields(),
+        rocksdb_path: get_rocksdb_path(),
+        meta_dir: config.meta_dir.clone(),
+        log_level: get_log_level(),
+        living_tasks: GLOBAL_LIVING_TRANSFER_TASK_MAP.len(),
+        config_generation: reload.generation,
+        config_reload: reload,
+        self_stats_last_hour,
+    })))
+}
+
+/// 运行中任务的汇总统计，返回后台定期刷新的快照
+pub async fn server_stats_snapshot() -> HandlerResult<RespServerStats> {
+    let resp = match server_stats() {
+        Some((age, stats)) => RespServerStats {
+            age_seconds: Some(age),
+            stats: Some(stats),
+        },
+        None => RespServerStats {
+            age_seconds: None,
+            stats: None,
+        },
+    };
+    Ok(Json(Response::ok(resp)))
+}
+
+/// 就绪检查，rocksdb 不可读、任务 runtime 无响应、TasksStatusSaver 停滞、写入延迟持续过高或停机中时返回 503 及未通过的检查
+pub async fn readyz() -> (StatusCode, Json<Response<Readiness>>) {
+    let readiness = check_readiness().await;
+    match readiness.reason() {
+        Some(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Response::new(1, reason, Some(readiness))),
+        ),
+        None => (StatusCode::OK, Json(Response::ok(readiness))),
+    }
+}
+
+/// Prometheus 文本格式的指标
+pub async fn metrics() -> (StatusCode, [(header::HeaderName, &'static str); 1], String) {
+    let content_type = [(header::CONTENT_TYPE, "text/plain; version=0.0.4")];
+    match GLOBAL_METRICS.render() {
+        Ok(text) => (StatusCode::OK, content_type, text),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            content_type,
+            e.to_string(),
+        ),
+    }
+}
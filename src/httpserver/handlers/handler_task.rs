@@ -1,92 +1,189 @@
 use super::HandlerResult;
-use crate::httpserver::service::service_task::service_task_checkpoint;
+use crate::configure::TokenScope;
+use crate::httpserver::auth::caller_scope;
+use crate::httpserver::envelope::accepts_v1_envelope;
+use crate::httpserver::extract::{ApiJson, ApiPath, ApiQuery};
+use crate::httpserver::service::service_task::{
+    service_checkpoint_history, service_export_checkpoint, service_export_tasks,
+    service_flush_checkpoint, service_import_checkpoint, service_import_tasks, service_pause_task,
+    service_reset_checkpoint, service_resume_task, service_revert_task_change,
+    service_rollback_checkpoint, service_stream_export_tasks, service_task_changes,
+    service_task_checkpoint, service_task_completion, service_task_errors,
+    service_task_errors_archive, service_task_events, service_task_progress,
+    service_task_run_definition, service_task_runs, service_task_unified_status, service_task_wait,
+    TASK_WAIT_DEFAULT_TIMEOUT,
+};
 use crate::resources::living_tasks;
-use crate::tasks::{CheckPoint, TaskStatus};
+use crate::tasks::{
+    parse_task_export, redacted_task, CheckPoint, CheckpointExport, CheckpointFlush,
+    CompletionMarker, TaskChangeEntry, TaskExport, TaskImportResult, TaskStartOutcome, TaskStatus,
+    TASK_EXPORT_SCHEMA_VERSION,
+};
 use crate::{
     httpserver::{
-        exception::{AppError, AppErrorType},
-        module::{ReqTaskId, ReqTaskIds, ReqTaskUpdate, RespListTask, Response},
+        module::{
+            ApiError, ReqCheckpointReset, ReqCheckpointRollback, ReqTaskClone, ReqTaskErrors,
+            ReqTaskExport, ReqTaskId, ReqTaskIds, ReqTaskImport, ReqTaskListFilter, ReqTaskRemove,
+            ReqTaskRuns, ReqTaskSearch, ReqTaskStop, ReqTaskUpdate, ReqTaskValidate, ReqTaskWait,
+            RespCheckpointReset, RespListTask, RespRunDefinition, RespTaskAnalyze,
+            RespTaskBatchItem, RespTaskErrors, RespTaskProgress, RespTaskRuns, RespTaskShow,
+            RespTaskStatus, RespTaskSummary, RespTaskUnifiedStatus, RespTaskValidate, RespTaskWait,
+            Response, TaskStopState,
+        },
+        service::service_analyze::service_analyze_task,
         service::service_task::{
-            service_analyze_task, service_list_all_tasks, service_remove_task, service_show_task,
-            service_start_task, service_stop_task, service_task_create, service_update_task,
+            service_batch_tasks, service_clone_task, service_list_all_tasks, service_patch_task,
+            service_remove_tasks, service_search_tasks, service_show_task,
+            service_show_task_by_name, service_start_task, service_stop_task,
+            service_stop_task_wait, service_stop_tasks_wait, service_stream_tasks,
+            service_task_create, service_task_create_idempotent, service_update_task,
+            STOP_WAIT_DEFAULT_TIMEOUT,
         },
     },
     tasks::Task,
 };
-use axum::Json;
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderName, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use futures::Stream;
 use serde_json::{json, Value};
-use std::collections::BTreeMap;
-
-pub async fn task_create(Json(mut task): Json<Task>) -> HandlerResult<Value> {
-    match service_task_create(&mut task) {
-        Ok(id) => Ok(Json(Response::ok(json!({"task_id":id.to_string()})))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+use std::convert::Infallible;
+use std::time::Duration;
+
+/// 携带 Idempotency-Key 时，有效期内的重试返回原任务 id，replayed 为 true
+pub async fn task_create(
+    headers: HeaderMap,
+    ApiJson(mut task): ApiJson<Task>,
+) -> HandlerResult<Value> {
+    let created = match headers.get("idempotency-key") {
+        Some(v) => match v.to_str() {
+            Ok(key) => service_task_create_idempotent(&mut task, key),
+            Err(_) => Err(ApiError::InvalidRequest(
+                "idempotency key must be visible ascii".to_string(),
+            )
+            .into()),
+        },
+        None => service_task_create(&mut task).map(|id| (id.to_string(), false)),
+    };
+    match created {
+        Ok((id, replayed)) => Ok(Json(Response::ok(json!({
+            "task_id":id,
+            "replayed":replayed,
+            "consistency_warnings":task.validate_consistency().warnings
+        })))),
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_update(Json(mut update): Json<ReqTaskUpdate>) -> HandlerResult<Value> {
-    match service_update_task(&update.task_id, &mut update.task) {
-        Ok(_) => Ok(Json(Response::ok(json!({"update":"ok"})))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+/// 复制任务定义创建新任务，请求体可省略
+pub async fn task_clone(ApiPath(task_id): ApiPath<String>, body: Bytes) -> HandlerResult<Value> {
+    let overrides = match body.is_empty() {
+        true => ReqTaskClone::default(),
+        false => serde_json::from_slice::<ReqTaskClone>(&body)
+            .map_err(|e| ApiError::InvalidRequest(e.to_string()))?,
+    };
+    match service_clone_task(&task_id, &overrides) {
+        Ok((id, task)) => Ok(Json(Response::ok(json!({
+            "task_id":id.to_string(),
+            "source_task_id":task_id,
+            "consistency_warnings":task.validate_consistency().warnings
+        })))),
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_remove(Json(ids): Json<ReqTaskIds>) -> HandlerResult<()> {
-    match service_remove_task(ids.task_ids) {
-        Ok(_) => Ok(Json(Response::ok(()))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+// 暂无用户体系，操作人由调用方通过 x-actor 头传入
+fn request_actor(headers: &HeaderMap) -> String {
+    match headers.get("x-actor").and_then(|v| v.to_str().ok()) {
+        Some(actor) if !actor.is_empty() => actor.to_string(),
+        _ => "anonymous".to_string(),
+    }
+}
+
+pub async fn task_update(
+    headers: HeaderMap,
+    ApiJson(mut update): ApiJson<ReqTaskUpdate>,
+) -> HandlerResult<Value> {
+    match service_update_task(
+        &update.task_id,
+        &mut update.task,
+        &request_actor(&headers),
+        update.reset_meta,
+    ) {
+        Ok(_) => Ok(Json(Response::ok(json!({
+            "update":"ok",
+            "consistency_warnings":update.task.validate_consistency().warnings
+        })))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 以 json merge patch 部分修改任务定义
+pub async fn task_patch(
+    headers: HeaderMap,
+    ApiPath(task_id): ApiPath<String>,
+    ApiJson(patch): ApiJson<Value>,
+) -> HandlerResult<Value> {
+    match service_patch_task(&task_id, &patch, &request_actor(&headers)) {
+        Ok(task) => Ok(Json(Response::ok(json!({
+            "update":"ok",
+            "consistency_warnings":task.validate_consistency().warnings
+        })))),
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_analyze(Json(id): Json<ReqTaskId>) -> HandlerResult<BTreeMap<String, i128>> {
+/// 仅校验任务定义，不创建任务；connect 为 true 时并发检查源与目标存储的连通性
+pub async fn task_validate(
+    ApiQuery(req): ApiQuery<ReqTaskValidate>,
+    ApiJson(task): ApiJson<Task>,
+) -> HandlerResult<RespTaskValidate> {
+    task.validate_fields()
+        .map_err(|e| ApiError::from(anyhow::Error::new(e)))?;
+    let checks = match req.connect {
+        true => Some(task.check_connectivity().await),
+        false => None,
+    };
+    Ok(Json(Response::ok(RespTaskValidate {
+        report: task.validate_consistency(),
+        checks,
+    })))
+}
+
+pub async fn task_remove(
+    ApiJson(req): ApiJson<ReqTaskRemove>,
+) -> (StatusCode, Json<Response<Vec<RespTaskBatchItem>>>) {
+    batch_response(service_remove_tasks(req.task_ids, req.force).await)
+}
+
+pub async fn task_analyze(ApiJson(id): ApiJson<ReqTaskId>) -> HandlerResult<RespTaskAnalyze> {
     match service_analyze_task(&id.task_id).await {
-        Ok(map) => Ok(Json(Response::ok(map))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+        Ok(analyze) => Ok(Json(Response::ok(analyze))),
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_start(Json(id): Json<ReqTaskId>) -> HandlerResult<Value> {
-    match service_start_task(id.task_id.as_str()) {
-        Ok(_) => Ok(Json(Response::ok(json!({"start":&id.task_id})))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
+// 单个与批量启动返回相同的结构
+fn start_outcome_json(task_id: &str, outcome: TaskStartOutcome) -> Value {
+    match outcome {
+        TaskStartOutcome::Started { run_id } => {
+            json!({"start":task_id, "run_id": run_id, "queued": false})
+        }
+        TaskStartOutcome::Queued { position } => {
+            json!({"start":task_id, "queued": true, "queue_position": position})
         }
     }
 }
 
+pub async fn task_start(ApiJson(id): ApiJson<ReqTaskId>) -> HandlerResult<Value> {
+    match service_start_task(id.task_id.as_str()).await {
+        Ok(outcome) => Ok(Json(Response::ok(start_outcome_json(&id.task_id, outcome)))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
 // pub async fn task_start(Json(mut payload): Json<TestGlobalJoinsetTask>) -> HandlerResult<Value> {
 //     payload.task_id = task_id_generator().to_string();
 //     GLOBAL_TASK_RUNTIME.spawn(async move {
@@ -96,61 +193,442 @@ pub async fn task_start(Json(id): Json<ReqTaskId>) -> HandlerResult<Value> {
 //     Ok(Json(Response::ok(json!({"start":"ok"}))))
 // }
 
-pub async fn task_stop(Json(id): Json<ReqTaskId>) -> HandlerResult<Value> {
-    match service_stop_task(id.task_id.as_str()) {
-        Ok(_) => Ok(Json(Response::ok(json!({"stop":&id.task_id})))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
+/// 批量结果全部成功返回 200，任一失败返回 422，data 中为逐个任务的结果
+fn batch_response(
+    results: Vec<RespTaskBatchItem>,
+) -> (StatusCode, Json<Response<Vec<RespTaskBatchItem>>>) {
+    let failed = results.iter().filter(|r| !r.ok).count();
+    match failed {
+        0 => (StatusCode::OK, Json(Response::ok(results))),
+        n => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(Response::new(
+                1,
+                format!("{} of {} tasks failed", n, results.len()),
+                Some(results),
+            )),
+        ),
+    }
+}
+
+pub async fn task_start_batch(
+    ApiJson(ids): ApiJson<ReqTaskIds>,
+) -> (StatusCode, Json<Response<Vec<RespTaskBatchItem>>>) {
+    batch_response(
+        service_batch_tasks(ids.task_ids, |id| async move {
+            service_start_task(&id)
+                .await
+                .map(|outcome| start_outcome_json(&id, outcome))
+        })
+        .await,
+    )
+}
+
+/// wait 时 result 为各任务的等待结果，全部成功但有任务超时仍在退出时返回 202
+pub async fn task_stop_batch(
+    ApiQuery(req): ApiQuery<ReqTaskStop>,
+    ApiJson(ids): ApiJson<ReqTaskIds>,
+) -> (StatusCode, Json<Response<Vec<RespTaskBatchItem>>>) {
+    if !req.wait {
+        return batch_response(
+            service_batch_tasks(
+                ids.task_ids,
+                |id| async move { service_stop_task(&id).await },
+            )
+            .await,
+        );
+    }
+    let timeout = match req.timeout_secs {
+        Some(secs) => Duration::from_secs(secs),
+        None => STOP_WAIT_DEFAULT_TIMEOUT,
+    };
+    let (status, body) = batch_response(service_stop_tasks_wait(ids.task_ids, timeout).await);
+    let stopping = json!(TaskStopState::Stopping);
+    let still_stopping = body
+        .data
+        .iter()
+        .flatten()
+        .any(|item| matches!(&item.result, Some(r) if r["state"] == stopping));
+    match status == StatusCode::OK && still_stopping {
+        true => (StatusCode::ACCEPTED, body),
+        false => (status, body),
+    }
+}
+
+/// wait 时等待任务退出，退出返回 200 与最终状态，超时返回 202 与 stopping 状态
+pub async fn task_stop(
+    ApiQuery(req): ApiQuery<ReqTaskStop>,
+    ApiJson(id): ApiJson<ReqTaskId>,
+) -> crate::httpserver::module::Result<(StatusCode, Json<Response<Value>>)> {
+    if !req.wait {
+        return match service_stop_task(id.task_id.as_str()).await {
+            Ok(_) => Ok((
+                StatusCode::OK,
+                Json(Response::ok(json!({"stop":&id.task_id}))),
+            )),
+            Err(e) => Err(ApiError::from(e)),
+        };
+    }
+    let timeout = match req.timeout_secs {
+        Some(secs) => Duration::from_secs(secs),
+        None => STOP_WAIT_DEFAULT_TIMEOUT,
+    };
+    match service_stop_task_wait(id.task_id.as_str(), timeout).await {
+        Ok(resp) => {
+            let status = match resp.state {
+                TaskStopState::Stopped => StatusCode::OK,
+                TaskStopState::Stopping => StatusCode::ACCEPTED,
             };
-            return Err(err);
+            Ok((status, Json(Response::ok(json!(resp)))))
         }
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_status(Json(id): Json<ReqTaskId>) -> HandlerResult<CheckPoint> {
+pub async fn task_pause(ApiPath(task_id): ApiPath<String>) -> HandlerResult<Value> {
+    match service_pause_task(&task_id) {
+        Ok(_) => Ok(Json(Response::ok(json!({ "pause": &task_id })))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_resume(ApiPath(task_id): ApiPath<String>) -> HandlerResult<Value> {
+    match service_resume_task(&task_id) {
+        Ok(_) => Ok(Json(Response::ok(json!({ "resume": &task_id })))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 清除任务进度，`?hard=true` 时同时删除错误记录
+pub async fn task_checkpoint_reset(
+    ApiPath(task_id): ApiPath<String>,
+    ApiQuery(req): ApiQuery<ReqCheckpointReset>,
+) -> HandlerResult<RespCheckpointReset> {
+    match service_reset_checkpoint(&task_id, &req) {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_checkpoint_export(
+    ApiPath(task_id): ApiPath<String>,
+) -> HandlerResult<CheckpointExport> {
+    match service_export_checkpoint(&task_id) {
+        Ok(export) => Ok(Json(Response::ok(export))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_checkpoint_import(
+    ApiPath(task_id): ApiPath<String>,
+    ApiJson(export): ApiJson<CheckpointExport>,
+) -> HandlerResult<CheckPoint> {
+    match service_import_checkpoint(&task_id, export).await {
+        Ok(checkpoint) => Ok(Json(Response::ok(checkpoint))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// checkpoint 历史，由新到旧
+pub async fn task_checkpoint_history(
+    ApiPath(task_id): ApiPath<String>,
+) -> HandlerResult<Vec<CheckPoint>> {
+    match service_checkpoint_history(&task_id) {
+        Ok(history) => Ok(Json(Response::ok(history))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_checkpoint_rollback(
+    ApiPath(task_id): ApiPath<String>,
+    ApiQuery(req): ApiQuery<ReqCheckpointRollback>,
+) -> HandlerResult<CheckPoint> {
+    match service_rollback_checkpoint(&task_id, &req).await {
+        Ok(checkpoint) => Ok(Json(Response::ok(checkpoint))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_checkpoint_flush(
+    ApiPath(task_id): ApiPath<String>,
+) -> HandlerResult<CheckpointFlush> {
+    match service_flush_checkpoint(&task_id).await {
+        Ok(flush) => Ok(Json(Response::ok(flush))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_status(ApiJson(id): ApiJson<ReqTaskId>) -> HandlerResult<RespTaskStatus> {
     match service_task_checkpoint(&id.task_id) {
         Ok(c) => Ok(Json(Response::ok(c))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+        Err(e) => Err(ApiError::from(e)),
     }
 }
 
-pub async fn task_show(Json(id): Json<ReqTaskId>) -> HandlerResult<Task> {
-    match service_show_task(&id.task_id) {
-        Ok(task) => Ok(Json(Response::ok(task))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
-            };
-            return Err(err);
-        }
+/// 合并持久化状态、内存状态与 checkpoint 的任务状态
+pub async fn task_unified_status(
+    ApiPath(task_id): ApiPath<String>,
+) -> HandlerResult<RespTaskUnifiedStatus> {
+    match service_task_unified_status(&task_id) {
+        Ok(s) => Ok(Json(Response::ok(s))),
+        Err(e) => Err(ApiError::from(e)),
     }
 }
-pub async fn task_all() -> HandlerResult<Vec<RespListTask>> {
-    match service_list_all_tasks() {
+
+/// 等待任务停止，超时返回 408 及当前状态
+pub async fn task_wait(
+    ApiPath(task_id): ApiPath<String>,
+    ApiQuery(req): ApiQuery<ReqTaskWait>,
+) -> HandlerResult<RespTaskWait> {
+    let timeout = match req.timeout {
+        Some(secs) => Duration::from_secs(secs),
+        None => TASK_WAIT_DEFAULT_TIMEOUT,
+    };
+    match service_task_wait(&task_id, timeout).await {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 任务事件流，任务停止后推送 finished 事件并关闭
+pub async fn task_events(
+    ApiPath(task_id): ApiPath<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let subscription = match service_task_events(&task_id) {
+        Ok(s) => s,
+        Err(e) => Err(ApiError::from(e)),
+    };
+    let stream = futures::stream::unfold(subscription, |mut s| async move {
+        let e = s.next().await?;
+        let event = match Event::default().event(e.name()).json_data(&e) {
+            Ok(event) => event,
+            Err(err) => Event::default().event("error").data(err.to_string()),
+        };
+        Some((Ok(event), s))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+pub async fn task_completion(ApiPath(task_id): ApiPath<String>) -> HandlerResult<CompletionMarker> {
+    match service_task_completion(&task_id) {
+        Ok(marker) => Ok(Json(Response::ok(marker))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_errors(
+    ApiPath(task_id): ApiPath<String>,
+    ApiQuery(req): ApiQuery<ReqTaskErrors>,
+) -> HandlerResult<RespTaskErrors> {
+    match service_task_errors(&task_id, &req) {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 错误记录文件打包为 tar.gz 下载，边打包边返回
+pub async fn task_errors_download(
+    ApiPath(task_id): ApiPath<String>,
+) -> Result<([(HeaderName, String); 2], Body), ApiError> {
+    let rx = match service_task_errors_archive(&task_id) {
+        Ok(rx) => rx,
+        Err(e) => return Err(ApiError::from(e)),
+    };
+    let chunks = futures::stream::unfold(rx, |mut rx| async move {
+        let chunk = rx.recv().await?;
+        Some((Ok::<_, Infallible>(chunk), rx))
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}_errors.tar.gz\"", task_id),
+            ),
+        ],
+        Body::from_stream(chunks),
+    ))
+}
+
+/// 执行中各批次的列表文件位置与进度，标记快照间未前进的批次
+pub async fn task_progress(ApiPath(task_id): ApiPath<String>) -> HandlerResult<RespTaskProgress> {
+    match service_task_progress(&task_id) {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 任务运行记录，按开始时间倒序分页
+pub async fn task_runs(
+    ApiPath(task_id): ApiPath<String>,
+    ApiQuery(req): ApiQuery<ReqTaskRuns>,
+) -> HandlerResult<RespTaskRuns> {
+    match service_task_runs(&task_id, &req) {
+        Ok(runs) => Ok(Json(Response::ok(runs))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_run_definition(
+    ApiPath((task_id, run_id)): ApiPath<(String, String)>,
+) -> HandlerResult<RespRunDefinition> {
+    match service_task_run_definition(&task_id, &run_id) {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+// read 范围的调用方查看任务定义时凭证脱敏
+fn task_for_scope(task: Task, scope: TokenScope) -> anyhow::Result<Task> {
+    match scope {
+        TokenScope::Read => redacted_task(&task),
+        _ => Ok(task),
+    }
+}
+
+fn task_show_response(
+    task: anyhow::Result<Task>,
+    scope: TokenScope,
+) -> HandlerResult<RespTaskShow> {
+    let resp = task.and_then(|task| {
+        Ok(RespTaskShow {
+            consistency_warnings: task.validate_consistency().warnings,
+            task: task_for_scope(task, scope)?,
+        })
+    });
+    match resp {
+        Ok(resp) => Ok(Json(Response::ok(resp))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_show(
+    scope: Option<Extension<TokenScope>>,
+    ApiJson(id): ApiJson<ReqTaskId>,
+) -> HandlerResult<RespTaskShow> {
+    task_show_response(service_show_task(&id.task_id), caller_scope(scope))
+}
+/// 经名称索引查找任务
+pub async fn task_show_by_name(
+    scope: Option<Extension<TokenScope>>,
+    ApiPath(name): ApiPath<String>,
+) -> HandlerResult<RespTaskShow> {
+    task_show_response(service_show_task_by_name(&name), caller_scope(scope))
+}
+
+pub async fn task_all(
+    scope: Option<Extension<TokenScope>>,
+    ApiQuery(filter): ApiQuery<ReqTaskListFilter>,
+) -> HandlerResult<Vec<RespListTask>> {
+    let scope = caller_scope(scope);
+    let tasks = service_list_all_tasks(&filter).and_then(|tasks| {
+        tasks
+            .into_iter()
+            .map(|t| {
+                Ok(RespListTask {
+                    cf_id: t.cf_id,
+                    task: task_for_scope(t.task, scope)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<RespListTask>>>()
+    });
+    match tasks {
         Ok(task_vec) => Ok(Json(Response::ok(task_vec))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_search(
+    ApiQuery(req): ApiQuery<ReqTaskSearch>,
+) -> HandlerResult<Vec<RespTaskSummary>> {
+    match service_search_tasks(&req) {
+        Ok(tasks) => Ok(Json(Response::ok(tasks))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+/// 边遍历边返回任务列表，data 之后的 meta 为返回数、跳过的损坏条目数与下一页游标
+/// 响应流不经过 v1 结构转换中间件，按请求头 Accept 直接输出对应结构
+pub async fn task_all_stream(
+    headers: HeaderMap,
+    scope: Option<Extension<TokenScope>>,
+    ApiQuery(filter): ApiQuery<ReqTaskListFilter>,
+) -> Result<([(HeaderName, &'static str); 1], Body), ApiError> {
+    let redact = caller_scope(scope) == TokenScope::Read;
+    let rx = match service_stream_tasks(filter, redact, accepts_v1_envelope(&headers)) {
+        Ok(rx) => rx,
+        Err(e) => return Err(ApiError::from(e)),
+    };
+    let chunks = futures::stream::unfold(rx, |mut rx| async move {
+        let chunk = rx.recv().await?;
+        Some((Ok::<_, Infallible>(chunk), rx))
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(chunks),
+    ))
+}
+
+// 导出格式版本的响应头
+const TASK_EXPORT_SCHEMA_HEADER: &str = "x-task-export-schema";
+
+/// 导出全部任务定义，format=ndjson 时逐行返回；非 admin 权限的 token 凭证总是脱敏
+pub async fn task_export(
+    scope: Option<Extension<TokenScope>>,
+    ApiQuery(req): ApiQuery<ReqTaskExport>,
+) -> Result<axum::response::Response, ApiError> {
+    let redact = req.redact || caller_scope(scope) < TokenScope::Admin;
+    let schema = HeaderName::from_static(TASK_EXPORT_SCHEMA_HEADER);
+    let version = TASK_EXPORT_SCHEMA_VERSION.to_string();
+    match req.format.as_deref() {
+        None | Some("json") => match service_export_tasks(redact) {
+            Ok(export) => Ok((
+                [(schema, version)],
+                Json(Response::<TaskExport>::ok(export)),
+            )
+                .into_response()),
+            Err(e) => Err(ApiError::from(e)),
+        },
+        Some("ndjson") => {
+            let rx = match service_stream_export_tasks(redact) {
+                Ok(rx) => rx,
+                Err(e) => return Err(ApiError::from(e)),
             };
-            return Err(err);
+            let lines = futures::stream::unfold(rx, |mut rx| async move {
+                let line = rx.recv().await?;
+                Some((Ok::<_, Infallible>(line), rx))
+            });
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                    (schema, version),
+                ],
+                Body::from_stream(lines),
+            )
+                .into_response())
         }
+        Some(f) => Err(ApiError::InvalidRequest(format!(
+            "export format {} not supported",
+            f
+        ))),
     }
 }
 
+/// 导入 task export 的 json 文档或 ndjson，逐条校验并创建，返回每条的结果
+pub async fn task_import(
+    ApiQuery(req): ApiQuery<ReqTaskImport>,
+    body: Bytes,
+) -> HandlerResult<Vec<TaskImportResult>> {
+    let export = match parse_task_export(&body) {
+        Ok(e) => e,
+        Err(e) => return Err(ApiError::InvalidRequest(e.to_string())),
+    };
+    Ok(Json(Response::ok(service_import_tasks(
+        export,
+        req.keep_ids,
+    ))))
+}
+
 // pub async fn task_all_living() -> HandlerResult<HashMap<String, TransferTaskStatus>> {
 //     let mut map = HashMap::new();
 //     for item in GLOBAL_LIVING_TRANSFER_TASK_MAP.iter() {
@@ -165,13 +643,147 @@ pub async fn task_all() -> HandlerResult<Vec<RespListTask>> {
 pub async fn task_all_living() -> HandlerResult<Vec<TaskStatus>> {
     match living_tasks() {
         Ok(v) => Ok(Json(Response::ok(v))),
-        Err(e) => {
-            let err = AppError {
-                message: Some(e.to_string()),
-                cause: None,
-                error_type: AppErrorType::UnknowErr,
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_changes(
+    ApiPath(task_id): ApiPath<String>,
+) -> HandlerResult<Vec<TaskChangeEntry>> {
+    match service_task_changes(&task_id) {
+        Ok(changes) => Ok(Json(Response::ok(changes))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+pub async fn task_change_revert(
+    headers: HeaderMap,
+    ApiPath((task_id, seq)): ApiPath<(String, u64)>,
+) -> HandlerResult<Task> {
+    match service_revert_task_change(&task_id, seq, &request_actor(&headers)) {
+        Ok(task) => Ok(Json(Response::ok(task))),
+        Err(e) => Err(ApiError::from(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{batch_response, start_outcome_json, task_pause, task_resume, task_stop};
+    use crate::httpserver::extract::{ApiJson, ApiPath, ApiQuery};
+    use crate::httpserver::module::{ReqTaskId, ReqTaskStop, RespTaskBatchItem};
+    use crate::tasks::{
+        register_task_cancellation, task_is_paused, TaskStartOutcome, TransferStage,
+        TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIVING_TRANSFER_TASK_MAP,
+    };
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    //cargo test httpserver::handlers::handler_task::test::test_task_stop_not_living -- --nocapture
+    #[tokio::test]
+    async fn test_task_stop_not_living() {
+        let resp = task_stop(
+            ApiQuery(ReqTaskStop::default()),
+            ApiJson(ReqTaskId {
+                task_id: "handler_test_not_living".to_string(),
+            }),
+        )
+        .await;
+        let err = match resp {
+            Ok(_) => panic!("stop a task not living should fail"),
+            Err(e) => e,
+        };
+        assert_eq!(err.code(), "task_not_living");
+        assert_eq!(err.details()["task_id"], "handler_test_not_living");
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+    }
+
+    //cargo test httpserver::handlers::handler_task::test::test_task_pause_not_living -- --nocapture
+    #[tokio::test]
+    async fn test_task_pause_not_living() {
+        let task_id = "handler_test_pause_not_living".to_string();
+        for resp in [
+            task_pause(ApiPath(task_id.clone())).await,
+            task_resume(ApiPath(task_id.clone())).await,
+        ] {
+            let err = match resp {
+                Ok(_) => panic!("pause or resume a task not living should fail"),
+                Err(e) => e,
             };
-            return Err(err);
+            assert_eq!(err.code(), "task_not_living");
+            assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+        }
+    }
+
+    //cargo test httpserver::handlers::handler_task::test::test_task_pause_resume -- --nocapture
+    #[tokio::test]
+    async fn test_task_pause_resume() {
+        let task_id = "handler_test_pause_resume".to_string();
+        register_task_cancellation(&task_id);
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task_id.clone(),
+            TransferTaskStatus {
+                task_id: task_id.clone(),
+                start_time: 0,
+                run_id: String::new(),
+                status: TransferTaskStatusType::Running(TransferStage::Stock),
+            },
+        );
+        let status = || {
+            GLOBAL_LIVING_TRANSFER_TASK_MAP
+                .get(&task_id)
+                .unwrap()
+                .status
+                .clone()
+        };
+
+        assert!(task_pause(ApiPath(task_id.clone())).await.is_ok());
+        assert!(task_is_paused(&task_id));
+        assert!(status().is_paused());
+        match task_pause(ApiPath(task_id.clone())).await {
+            Ok(_) => panic!("pause a paused task should fail"),
+            Err(e) => assert_eq!(e.code(), "task_already_paused"),
+        }
+
+        assert!(task_resume(ApiPath(task_id.clone())).await.is_ok());
+        assert!(!task_is_paused(&task_id));
+        assert!(status().is_stock_running());
+        match task_resume(ApiPath(task_id.clone())).await {
+            Ok(_) => panic!("resume a running task should fail"),
+            Err(e) => assert_eq!(e.code(), "task_not_paused"),
         }
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&task_id);
+    }
+
+    //cargo test httpserver::handlers::handler_task::test::test_batch_response -- --nocapture
+    #[test]
+    fn test_batch_response() {
+        let item = |ok: bool| RespTaskBatchItem {
+            task_id: "1".to_string(),
+            ok,
+            code: (!ok).then(|| "task_not_found".to_string()),
+            error: (!ok).then(|| "task 1 not exist".to_string()),
+            result: None,
+        };
+        assert_eq!(batch_response(vec![item(true)]).0, StatusCode::OK);
+        assert_eq!(
+            batch_response(vec![item(true), item(false)]).0,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+
+    //cargo test httpserver::handlers::handler_task::test::test_start_outcome_json -- --nocapture
+    #[test]
+    fn test_start_outcome_json() {
+        let started = start_outcome_json(
+            "1",
+            TaskStartOutcome::Started {
+                run_id: "run".to_string(),
+            },
+        );
+        assert_eq!(started["run_id"], "run");
+        assert_eq!(started["queued"], false);
+        let queued = start_outcome_json("1", TaskStartOutcome::Queued { position: 2 });
+        assert_eq!(queued["queue_position"], 2);
+        assert!(queued.get("run_id").is_none());
     }
 }
@@ -1,4 +1,5 @@
 mod config;
+mod handler_admin;
 mod handler_mysql;
 mod handler_redis;
 mod handler_root;
@@ -7,9 +8,10 @@ mod handler_task_template;
 
 use axum::Json;
 pub use config::current_config;
+pub use handler_admin::*;
 pub use handler_mysql::rbatis_t_insert;
 pub use handler_redis::*;
-pub use handler_root::root;
+pub use handler_root::{metrics, readyz, root, server_info};
 pub use handler_task::*;
 pub use handler_task_template::*;
 
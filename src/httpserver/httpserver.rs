@@ -1,33 +1,508 @@
+use crate::configure::{HttpConfig, HttpEndpoint};
 use crate::httpserver::routers::router_root;
+use crate::httpserver::tls::{init_tls, tls_acceptor};
+use anyhow::{anyhow, Result};
+use axum::extract::ConnectInfo;
 use axum::Router;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 use tokio::spawn;
-use tokio::task::JoinHandle;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// 已绑定的监听，同一 router 在各监听上提供服务
+pub enum HttpListener {
+    Tcp(TcpListener),
+    // 配置 http.tls 后 tcp 监听提供 https
+    Tls(TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: tokio::net::UnixListener,
+        path: String,
+    },
+}
+
+impl HttpListener {
+    pub fn endpoint(&self) -> String {
+        match self {
+            HttpListener::Tcp(l) => match l.local_addr() {
+                Ok(addr) => addr.to_string(),
+                Err(_) => "tcp".to_string(),
+            },
+            HttpListener::Tls(l) => match l.local_addr() {
+                Ok(addr) => format!("https://{}", addr),
+                Err(_) => "https".to_string(),
+            },
+            #[cfg(unix)]
+            HttpListener::Unix { path, .. } => HttpEndpoint::Unix(path.clone()).to_string(),
+        }
+    }
+}
 
 pub struct HttpServer {
-    pub listener: TcpListener,
+    pub listeners: Vec<HttpListener>,
     pub router: Router,
+    // 停机时等待处理中请求完成的最长时间，超时后断开剩余连接
+    pub drain_timeout: Duration,
 }
 
 impl HttpServer {
-    pub async fn default() -> Self {
-        // let port: u16 = 3000;
-        // let addr_ipv4 = net::SocketAddr::from((net::Ipv4Addr::UNSPECIFIED, port));
-        // let band_addr = SocketAddr::from(addr_ipv4);
-
-        let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
-        let router_root = router_root();
+    pub fn new(listeners: Vec<HttpListener>) -> Self {
         Self {
-            listener,
-            router: router_root,
+            listeners,
+            router: router_root(),
+            drain_timeout: Duration::from_secs(HttpConfig::drain_timeout_secs_default()),
         }
     }
+
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
     pub async fn run(self) -> JoinHandle<()> {
-        let server = axum::serve(self.listener, self.router.into_make_service());
-        let handle = spawn(async {
-            server.await.unwrap();
-        });
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = self.run_with_graceful_shutdown(shutdown_rx).await;
+        spawn(async move {
+            // 持有 sender，避免 channel 关闭触发停机
+            let _shutdown_tx = shutdown_tx;
+            let _ = handle.await;
+        })
+    }
+
+    /// shutdown 置为 true 后各监听停止接收新连接，unix socket 文件随即删除；
+    /// 处理中的请求在 drain_timeout 内完成后返回，超时则断开剩余连接
+    pub async fn run_with_graceful_shutdown(
+        self,
+        shutdown: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        let mut handles = JoinSet::new();
+        for listener in self.listeners {
+            let router = self.router.clone();
+            let shutdown = shutdown.clone();
+            match listener {
+                HttpListener::Tcp(l) => handles.spawn(serve_tcp(l, router, shutdown)),
+                HttpListener::Tls(l) => handles.spawn(serve_tls(l, router, shutdown)),
+                #[cfg(unix)]
+                HttpListener::Unix { listener, path } => {
+                    handles.spawn(serve_unix(listener, path, router, shutdown))
+                }
+            };
+        }
         log::info!("httpserver start");
-        return handle;
+        let drain_timeout = self.drain_timeout;
+        spawn(async move {
+            let drain_deadline = async {
+                wait_shutdown(shutdown).await;
+                tokio::time::sleep(drain_timeout).await;
+            };
+            let drained = tokio::select! {
+                _ = async { while handles.join_next().await.is_some() {} } => true,
+                _ = drain_deadline => false,
+            };
+            match drained {
+                true => log::info!("httpserver stopped"),
+                false => {
+                    log::warn!(
+                        "httpserver drain timeout after {:?}, close remaining connections",
+                        drain_timeout
+                    );
+                    handles.shutdown().await;
+                }
+            }
+        })
+    }
+}
+
+async fn wait_shutdown(mut shutdown: watch::Receiver<bool>) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, router: Router, shutdown: watch::Receiver<bool>) {
+    let server = axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        wait_shutdown(shutdown).await;
+        log::info!("httpserver stop accepting connections");
+    });
+    if let Err(e) = server.await {
+        log::error!("{}", e);
+    }
+}
+
+// axum 0.7 的 serve 仅支持明文 tcp，unix socket 与 tls 上的连接由 hyper 逐个处理
+// 停机时处理完当前请求后关闭连接；tcp 连接附带对端地址，供限流按 ip 区分客户端
+async fn serve_connection<I>(
+    io: I,
+    peer: Option<SocketAddr>,
+    router: Router,
+    shutdown: watch::Receiver<bool>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    use hyper::body::Incoming;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto;
+    use tower::Service;
+
+    let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+        if let Some(addr) = peer {
+            request.extensions_mut().insert(ConnectInfo(addr));
+        }
+        router.clone().call(request)
+    });
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::pin!(conn);
+    let stop = wait_shutdown(shutdown);
+    tokio::pin!(stop);
+    let result = tokio::select! {
+        r = conn.as_mut() => r,
+        _ = &mut stop => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(e) = result {
+        log::debug!("serve connection error: {}", e);
+    }
+}
+
+// tls 握手超时，避免未完成握手的连接长期占用
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// accept 失败后重试前的等待时长
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// 对端已断开等连接级错误立即重试，文件描述符耗尽等错误等待后重试，避免空转
+async fn accept_error_backoff(e: &std::io::Error) {
+    match e.kind() {
+        ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::ConnectionReset => {}
+        _ => tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await,
+    }
+}
+
+/// 每个连接握手时取当前证书，证书重新加载后新连接即使用新证书
+async fn serve_tls(listener: TcpListener, router: Router, shutdown: watch::Receiver<bool>) {
+    let mut connections = JoinSet::new();
+    let stop = wait_shutdown(shutdown.clone());
+    tokio::pin!(stop);
+    loop {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(a) => a,
+                Err(e) => {
+                    log::warn!("accept tls connection error: {}", e);
+                    accept_error_backoff(&e).await;
+                    continue;
+                }
+            },
+            _ = &mut stop => break,
+        };
+        let acceptor = match tls_acceptor() {
+            Some(a) => a,
+            None => {
+                log::error!("tls not initialized, drop connection from {}", peer);
+                continue;
+            }
+        };
+        let router = router.clone();
+        let shutdown = shutdown.clone();
+        connections.spawn(async move {
+            let stream =
+                match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+                    Ok(Ok(s)) => s,
+                    Ok(Err(e)) => {
+                        log::debug!("tls handshake with {} error: {}", peer, e);
+                        return;
+                    }
+                    Err(_) => {
+                        log::debug!("tls handshake with {} timeout", peer);
+                        return;
+                    }
+                };
+            serve_connection(stream, Some(peer), router, shutdown).await;
+        });
+        // 回收已结束的连接
+        while connections.try_join_next().is_some() {}
+    }
+    drop(listener);
+    log::info!("httpserver stop accepting tls connections");
+    while connections.join_next().await.is_some() {}
+}
+
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    path: String,
+    router: Router,
+    shutdown: watch::Receiver<bool>,
+) {
+    let mut connections = JoinSet::new();
+    let stop = wait_shutdown(shutdown.clone());
+    tokio::pin!(stop);
+    loop {
+        let socket = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    log::warn!("accept on unix:{} error: {}", path, e);
+                    accept_error_backoff(&e).await;
+                    continue;
+                }
+            },
+            _ = &mut stop => break,
+        };
+        connections.spawn(serve_connection(
+            socket,
+            None,
+            router.clone(),
+            shutdown.clone(),
+        ));
+        while connections.try_join_next().is_some() {}
+    }
+    drop(listener);
+    log::info!("httpserver stop accepting connections on unix:{}", path);
+    remove_unix_socket(&path);
+    while connections.join_next().await.is_some() {}
+}
+
+/// 绑定配置中的全部监听，任一失败则返回错误；配置 http.tls 时先加载证书
+pub async fn bind_listeners(http: &HttpConfig) -> Result<Vec<HttpListener>> {
+    if let Some(tls) = &http.tls {
+        init_tls(tls)?;
+    }
+    let mut listeners = vec![];
+    for endpoint in http.endpoints()? {
+        let listener = match endpoint {
+            HttpEndpoint::Tcp(addr) if http.tls.is_some() => {
+                HttpListener::Tls(bind_listener(addr).await?)
+            }
+            HttpEndpoint::Tcp(addr) => HttpListener::Tcp(bind_listener(addr).await?),
+            HttpEndpoint::Unix(path) => bind_unix_listener(&path, http.unix_socket_mode()?)?,
+        };
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+/// 绑定监听地址，端口被占用时提示修改 http.port
+pub async fn bind_listener(addr: SocketAddr) -> Result<TcpListener> {
+    match TcpListener::bind(addr).await {
+        Ok(l) => Ok(l),
+        Err(e) if e.kind() == ErrorKind::AddrInUse => Err(anyhow!(
+            "http port {} already in use on {}, stop the process holding it or change `http.port` in config",
+            addr.port(),
+            addr.ip()
+        )),
+        Err(e) => Err(anyhow!("bind http server on {} error: {}", addr, e)),
+    }
+}
+
+/// 创建 unix socket 并设置权限，残留的 socket 文件（无进程监听）先删除
+#[cfg(unix)]
+pub fn bind_unix_listener(path: &str, mode: u32) -> Result<HttpListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if std::path::Path::new(path).exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(anyhow!(
+                "unix socket {} already in use, stop the process holding it or change `http.unix` in config",
+                path
+            ));
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| anyhow!("remove stale unix socket {} error: {}", path, e))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .map_err(|e| anyhow!("bind unix socket {} error: {}", path, e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(HttpListener::Unix {
+        listener,
+        path: path.to_string(),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn bind_unix_listener(path: &str, _mode: u32) -> Result<HttpListener> {
+    Err(anyhow!(
+        "unix socket {} not supported on this platform",
+        path
+    ))
+}
+
+#[cfg(unix)]
+fn remove_unix_socket(path: &str) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != ErrorKind::NotFound {
+            log::warn!("remove unix socket {} error: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::bind_listener;
+
+    //cargo test httpserver::httpserver::test::test_bind_listener_port_in_use -- --nocapture
+    #[tokio::test]
+    async fn test_bind_listener_port_in_use() {
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = occupied.local_addr().unwrap();
+        let err = bind_listener(addr).await.unwrap_err().to_string();
+        assert!(err.contains(&addr.port().to_string()));
+        assert!(err.contains("http.port"));
+
+        drop(occupied);
+        assert!(bind_listener(addr).await.is_ok());
+    }
+
+    //cargo test httpserver::httpserver::test::test_graceful_shutdown_drains_requests -- --nocapture
+    #[tokio::test]
+    async fn test_graceful_shutdown_drains_requests() {
+        use super::{HttpListener, HttpServer};
+        use axum::routing::get;
+        use axum::Router;
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let router = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "done"
+            }),
+        );
+        let listener = bind_listener("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer {
+            listeners: vec![HttpListener::Tcp(listener)],
+            router,
+            drain_timeout: Duration::from_secs(5),
+        };
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let handle = server.run_with_graceful_shutdown(shutdown_rx).await;
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown_tx.send(true).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // 停机后不再接收新连接，处理中的请求正常完成
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+        assert!(resp.ends_with("done"));
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    //cargo test httpserver::httpserver::test::test_serve_unix_socket -- --nocapture
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        use super::{bind_unix_listener, HttpServer};
+        use std::os::unix::fs::PermissionsExt;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let path = format!("/tmp/mario_http_test_{}.sock", std::process::id());
+        // 残留的 socket 文件可被替换
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = bind_unix_listener(&path, 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(bind_unix_listener(&path, 0o600).is_err());
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let handle = HttpServer::new(vec![listener])
+            .run_with_graceful_shutdown(shutdown_rx)
+            .await;
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"));
+
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    //cargo test httpserver::httpserver::test::test_task_cycle_over_unix_socket -- --nocapture
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_task_cycle_over_unix_socket() {
+        use super::{bind_unix_listener, HttpServer};
+        use crate::cmd::http_request;
+        use crate::resources::{delete_task_all, open_test_rocksdb};
+        use crate::tasks::{init_global_task_runtime, ObjectStorage, Task, TransferTask};
+        use serde_json::json;
+
+        // 经 socket 访问完整的服务路由，任务写入测试共用的 rocksdb
+        open_test_rocksdb();
+        init_global_task_runtime().unwrap();
+        let path = format!("/tmp/mario_task_cycle_{}.sock", std::process::id());
+        let listener = bind_unix_listener(&path, 0o600).unwrap();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let handle = HttpServer::new(vec![listener])
+            .run_with_graceful_shutdown(shutdown_rx)
+            .await;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let dir = format!("/tmp/unix_socket_cycle_test/{}", id);
+        let name = format!("over_socket_{}", id);
+        let mut transfer = TransferTask::default();
+        transfer.name = name.clone();
+        transfer.source = ObjectStorage::Local(format!("{}/source", dir));
+        transfer.target = ObjectStorage::Local(format!("{}/target", dir));
+        let body = serde_json::to_value(Task::Transfer(transfer)).unwrap();
+        let socket = path.clone();
+        let list_url = format!("http://localhost/api/v1/task/all?name={}", name);
+        // 命令行客户端为阻塞请求，主机部分不参与连接
+        let (create, list) = tokio::task::spawn_blocking(move || {
+            let create = http_request(
+                "http://localhost/api/v1/task/create",
+                Some(body),
+                Some(&socket),
+            )
+            .unwrap();
+            let list = http_request(&list_url, Some(json!({})), Some(&socket)).unwrap();
+            (create, list)
+        })
+        .await
+        .unwrap();
+        assert_eq!(create["code"].as_i64(), Some(0), "{}", create);
+        let task_id = create["data"]["task_id"].as_str().unwrap().to_string();
+        let all = list["data"].as_array().unwrap();
+        assert_eq!(all.len(), 1, "{}", list);
+        assert_eq!(all[0]["cf_id"].as_str(), Some(task_id.as_str()));
+        assert_eq!(all[0]["task"]["name"].as_str(), Some(name.as_str()));
+
+        shutdown_tx.send(true).unwrap();
+        handle.await.unwrap();
+        assert!(!std::path::Path::new(&path).exists());
+        delete_task_all(&task_id).unwrap();
     }
 }
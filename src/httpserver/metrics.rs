@@ -0,0 +1,21 @@
+use crate::server::record_http_request;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::time::Instant;
+
+// 未匹配任何路由的请求统一归入该标签
+const UNMATCHED_PATH: &str = "unmatched";
+
+/// 统计 http 请求数与耗时，按路由模板而非实际路径打标签
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(p) => p.as_str().to_string(),
+        None => UNMATCHED_PATH.to_string(),
+    };
+    let response = next.run(request).await;
+    record_http_request(&method, &path, response.status().as_u16(), start.elapsed());
+    response
+}
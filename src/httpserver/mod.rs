@@ -1,8 +1,19 @@
-pub use httpserver::HttpServer;
+pub use cors::cors_layer;
+pub use httpserver::{bind_listener, bind_listeners, bind_unix_listener, HttpListener, HttpServer};
+pub use tls::{load_tls_config, reload_tls_certs};
+mod auth;
+mod compression;
+mod cors;
 mod dao;
-mod exception;
+mod deprecation;
+mod envelope;
+mod extract;
 mod handlers;
 mod httpserver;
+mod metrics;
 pub(crate) mod module;
+mod rate_limit;
+mod request_id;
 mod routers;
 mod service;
+mod tls;
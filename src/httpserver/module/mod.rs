@@ -1,12 +1,16 @@
 mod common_module;
+mod module_admin;
+mod module_error;
 mod module_task;
 mod request_module;
 mod response_module;
 
 pub use common_module::*;
+pub use module_admin::*;
+pub use module_error::*;
 pub use module_task::*;
 pub use request_module::*;
 pub use response_module::*;
 
 /// 定义自己的 Result
-pub type Result<T> = std::result::Result<T, crate::httpserver::exception::AppError>;
+pub type Result<T> = std::result::Result<T, ApiError>;
@@ -0,0 +1,125 @@
+use crate::configure::{ConfigOverrides, ConfigReloadStatus, DispatchPolicy};
+use crate::resources::DbCompactionRecord;
+use crate::server::{LastStop, RuntimeThreads, SelfStatsSample, SelfStatsSummary, ServerStats};
+use crate::tasks::{NamespaceQueueStats, QueuedTask};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqLogLevel {
+    pub level: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqSelfStats {
+    // unix 时间戳，缺省返回全部采样点
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskQueue {
+    // 目前仅支持 namespace
+    pub group_by: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskQueueLimit {
+    // 0 表示不限制
+    pub max_concurrent_tasks: usize,
+}
+
+/// rocksdb 概况，大小为各 column family 估算的有效数据大小
+#[derive(Debug, Serialize)]
+pub struct RespDbStats {
+    pub path: String,
+    pub cf_sizes: BTreeMap<String, u64>,
+    pub total_size: u64,
+    pub auto_compaction: bool,
+    pub compaction_schedule: String,
+    // 按当前配置计算的下次定时压缩时间，未启用时为空
+    pub next_compaction: Option<u64>,
+    pub last_compaction: Option<DbCompactionRecord>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqDbPrune {
+    // 只返回将要删除的条目
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqDbRepair {
+    // 操作员确认删除的损坏记录 key，仅删除仍无法解码的
+    pub delete_corrupt: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqDbCompact {
+    // 缺省压缩全部 column family
+    pub cf: Option<String>,
+}
+
+/// 并发上限与排队中的任务，queued 按启动顺序排列
+#[derive(Debug, Serialize)]
+pub struct RespTaskQueue {
+    pub max_concurrent_tasks: usize,
+    pub executing: usize,
+    pub queued: Vec<QueuedTask>,
+    pub dispatch_policy: DispatchPolicy,
+    // group_by=namespace 时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespaces: Option<Vec<NamespaceQueueStats>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RespSelfStats {
+    pub samples: Vec<SelfStatsSample>,
+    pub last_hour: SelfStatsSummary,
+}
+
+/// 全局统计快照，age_seconds 为快照生成至今的秒数，后台尚未完成首次刷新时均为 None
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespServerStats {
+    pub age_seconds: Option<u64>,
+    pub stats: Option<ServerStats>,
+}
+
+/// 编译信息，git_commit 取自编译时的 MARIO_GIT_COMMIT 环境变量，未设置时由 build.rs 读取当前 git 提交
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespBuildInfo {
+    pub git_commit: Option<String>,
+    pub profile: String,
+    pub target: String,
+}
+
+/// 服务实例信息，命令行 status 经 http 或 unix socket 获取后按同一结构解析；不包含 token 与任务凭证
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespServerInfo {
+    pub version: String,
+    pub build: RespBuildInfo,
+    pub pid: u32,
+    // RFC3339，UTC
+    pub start_time: Option<String>,
+    pub uptime_seconds: u64,
+    // 首次启动时为 None
+    pub last_stop: Option<LastStop>,
+    pub runtime_threads: BTreeMap<String, RuntimeThreads>,
+    // 生效的 http 监听地址，包含命令行覆盖
+    pub http_endpoints: Vec<String>,
+    pub config_overrides: ConfigOverrides,
+    // 经环境变量覆盖的配置项，不含值
+    #[serde(default)]
+    pub config_env_overrides: Vec<String>,
+    pub rocksdb_path: String,
+    pub meta_dir: String,
+    pub log_level: String,
+    pub living_tasks: usize,
+    // 配置重载成功次数
+    pub config_generation: u64,
+    // 最近一次 SIGHUP 重载的时间、结果与错误，未重载过时各项为空
+    #[serde(default)]
+    pub config_reload: ConfigReloadStatus,
+    // 最近一小时的资源使用汇总，读取采样点失败时为空
+    #[serde(default)]
+    pub self_stats_last_hour: Option<SelfStatsSummary>,
+}
@@ -0,0 +1,610 @@
+use crate::commons::current_request_id;
+use crate::configure::TokenScope;
+use crate::resources::{ResourceError, CF_TASK};
+use crate::tasks::{
+    ConsistencyIssue, FieldProblem, SetupStage, TaskConsistencyError, TaskExistsError,
+    TaskSetupError, TaskValidationError,
+};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// 接口错误，每个错误对应固定的 http 状态码与字符串错误码
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// 请求参数不合法
+    InvalidRequest(String),
+    /// 任务定义校验未通过
+    InvalidTaskDefinition {
+        message: String,
+        errors: Vec<ConsistencyIssue>,
+    },
+    /// 任务字段取值不合法
+    InvalidTaskFields { problems: Vec<FieldProblem> },
+    /// 请求体超出大小限制
+    PayloadTooLarge(String),
+    /// 未认证
+    Unauthorized(String),
+    /// token 范围不足
+    Forbidden {
+        required_scope: TokenScope,
+        token_scope: TokenScope,
+    },
+    /// 任务不存在
+    TaskNotFound { task_id: String },
+    /// 任务以外的对象不存在，如运行记录、变更记录、完成标记
+    NotFound(String),
+    /// 任务启动前准备失败
+    TaskSetupFailed {
+        stage: SetupStage,
+        storage: String,
+        message: String,
+    },
+    /// 任务已存在，task_id 为 None 时为源与目标均相同的任务已创建
+    TaskAlreadyExists { task_id: Option<String> },
+    /// 任务已在运行
+    TaskAlreadyLiving { task_id: String },
+    /// 任务未运行
+    TaskNotLiving { task_id: String },
+    /// 任务已暂停
+    TaskAlreadyPaused { task_id: String },
+    /// 任务未暂停
+    TaskNotPaused { task_id: String },
+    /// 幂等键已用于不同的创建请求
+    IdempotencyKeyConflict { key: String, task_id: String },
+    /// 任务名称已被其他任务使用
+    TaskNameConflict { name: String, task_ids: Vec<String> },
+    /// 已有 rocksdb 压缩或备份作业在运行
+    DbJobConflict { job_id: String },
+    /// 请求过于频繁
+    TooManyRequests { group: String },
+    /// 源端统计超时，附带已统计的部分结果
+    AnalyzeTimeout {
+        task_id: String,
+        sizes: BTreeMap<String, i128>,
+    },
+    /// 等待任务停止超时，附带当前的持久化状态
+    WaitTimeout { task_id: String, status: Value },
+    /// 请求处理超出接口超时，与等待任务停止超时区分
+    RequestTimeout { timeout_ms: u64 },
+    /// 服务停机中，不再接收新任务
+    ServerDraining,
+    /// rocksdb 不可用
+    StorageUnavailable(String),
+    /// 未分类的内部错误
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidTaskDefinition { .. } | ApiError::InvalidTaskFields { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            // 存储不可达为上游问题，meta_dir 创建失败为服务端问题，其余为任务配置问题
+            ApiError::TaskSetupFailed { stage, .. } => match stage {
+                SetupStage::Unreachable => StatusCode::BAD_GATEWAY,
+                SetupStage::MetaDir => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            ApiError::TaskNotFound { .. } | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TaskAlreadyExists { .. }
+            | ApiError::TaskAlreadyLiving { .. }
+            | ApiError::TaskNotLiving { .. }
+            | ApiError::TaskAlreadyPaused { .. }
+            | ApiError::TaskNotPaused { .. }
+            | ApiError::IdempotencyKeyConflict { .. }
+            | ApiError::TaskNameConflict { .. }
+            | ApiError::DbJobConflict { .. } => StatusCode::CONFLICT,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::AnalyzeTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::WaitTimeout { .. } => StatusCode::REQUEST_TIMEOUT,
+            ApiError::RequestTimeout { .. }
+            | ApiError::ServerDraining
+            | ApiError::StorageUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// 稳定的错误码，客户端据此区分错误，不依赖 message 文本
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidRequest(_) => "invalid_request",
+            ApiError::InvalidTaskDefinition { .. } => "invalid_task_definition",
+            ApiError::InvalidTaskFields { .. } => "invalid_task_fields",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::TaskSetupFailed { .. } => "task_setup_failed",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden { .. } => "forbidden",
+            ApiError::TaskNotFound { .. } => "task_not_found",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::TaskAlreadyExists { .. } => "task_already_exists",
+            ApiError::TaskAlreadyLiving { .. } => "task_already_living",
+            ApiError::TaskNotLiving { .. } => "task_not_living",
+            ApiError::TaskAlreadyPaused { .. } => "task_already_paused",
+            ApiError::TaskNotPaused { .. } => "task_not_paused",
+            ApiError::IdempotencyKeyConflict { .. } => "idempotency_key_conflict",
+            ApiError::TaskNameConflict { .. } => "task_name_conflict",
+            ApiError::DbJobConflict { .. } => "db_job_conflict",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::AnalyzeTimeout { .. } => "analyze_timeout",
+            ApiError::WaitTimeout { .. } => "wait_timeout",
+            ApiError::RequestTimeout { .. } => "request_timeout",
+            ApiError::ServerDraining => "server_draining",
+            ApiError::StorageUnavailable(_) => "storage_unavailable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn details(&self) -> Value {
+        match self {
+            ApiError::TaskNotFound { task_id }
+            | ApiError::TaskAlreadyLiving { task_id }
+            | ApiError::TaskNotLiving { task_id }
+            | ApiError::TaskAlreadyPaused { task_id }
+            | ApiError::TaskNotPaused { task_id } => json!({ "task_id": task_id }),
+            ApiError::TaskAlreadyExists { task_id } => json!({ "task_id": task_id }),
+            ApiError::InvalidTaskDefinition { errors, .. } => json!({ "errors": errors }),
+            ApiError::InvalidTaskFields { problems } => json!({ "problems": problems }),
+            ApiError::TooManyRequests { group } => json!({ "group": group }),
+            ApiError::Forbidden {
+                required_scope,
+                token_scope,
+            } => json!({ "required_scope": required_scope, "token_scope": token_scope }),
+            ApiError::IdempotencyKeyConflict { key, task_id } => {
+                json!({ "key": key, "task_id": task_id })
+            }
+            ApiError::TaskNameConflict { name, task_ids } => {
+                json!({ "name": name, "task_ids": task_ids })
+            }
+            ApiError::DbJobConflict { job_id } => json!({ "job_id": job_id }),
+            ApiError::AnalyzeTimeout { task_id, sizes } => {
+                json!({ "task_id": task_id, "partial": true, "sizes": sizes })
+            }
+            ApiError::WaitTimeout { task_id, status } => {
+                json!({ "task_id": task_id, "status": status })
+            }
+            ApiError::RequestTimeout { timeout_ms } => json!({ "timeout_ms": timeout_ms }),
+            ApiError::TaskSetupFailed { stage, storage, .. } => {
+                json!({ "stage": stage, "storage": storage })
+            }
+            _ => json!({}),
+        }
+    }
+
+    /// 在请求上下文中生成时附带 request_id
+    pub fn body(&self) -> Value {
+        let mut body = json!({
+            "code": self.code(),
+            "message": self.to_string(),
+            "details": self.details(),
+        });
+        if let Some(id) = current_request_id() {
+            body["request_id"] = json!(id);
+        }
+        body
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::InvalidRequest(m)
+            | ApiError::Unauthorized(m)
+            | ApiError::PayloadTooLarge(m)
+            | ApiError::NotFound(m)
+            | ApiError::StorageUnavailable(m)
+            | ApiError::Internal(m) => write!(f, "{}", m),
+            ApiError::InvalidTaskDefinition { message, .. } => write!(f, "{}", message),
+            ApiError::InvalidTaskFields { problems } => write!(
+                f,
+                "{}",
+                TaskValidationError {
+                    problems: problems.clone()
+                }
+            ),
+            ApiError::TaskSetupFailed {
+                stage,
+                storage,
+                message,
+            } => write!(
+                f,
+                "{}",
+                TaskSetupError {
+                    stage: *stage,
+                    storage: storage.clone(),
+                    message: message.clone(),
+                }
+            ),
+            ApiError::TaskNotFound { task_id } => write!(f, "task {} not exist", task_id),
+            ApiError::TaskAlreadyExists { task_id } => write!(
+                f,
+                "{}",
+                TaskExistsError {
+                    task_id: task_id.clone()
+                }
+            ),
+            ApiError::TaskAlreadyLiving { task_id } => write!(f, "task {} is living", task_id),
+            ApiError::TaskNotLiving { task_id } => write!(f, "task {} not living", task_id),
+            ApiError::TaskAlreadyPaused { task_id } => write!(f, "task {} already paused", task_id),
+            ApiError::TaskNotPaused { task_id } => write!(f, "task {} not paused", task_id),
+            ApiError::IdempotencyKeyConflict { key, task_id } => write!(
+                f,
+                "idempotency key {} already used by task {} with a different request body",
+                key, task_id
+            ),
+            ApiError::TaskNameConflict { name, task_ids } => write!(
+                f,
+                "task name {} already used by task {}",
+                name,
+                task_ids.join(",")
+            ),
+            ApiError::DbJobConflict { job_id } => {
+                write!(f, "db job {} is running", job_id)
+            }
+            ApiError::Forbidden {
+                required_scope,
+                token_scope,
+            } => write!(
+                f,
+                "{} scope required, token has {} scope",
+                required_scope, token_scope
+            ),
+            ApiError::AnalyzeTimeout { task_id, .. } => {
+                write!(f, "task {} analyze timeout", task_id)
+            }
+            ApiError::WaitTimeout { task_id, .. } => {
+                write!(f, "task {} not stopped before timeout", task_id)
+            }
+            ApiError::TooManyRequests { group } => {
+                write!(f, "rate limit exceeded for {} requests", group)
+            }
+            ApiError::RequestTimeout { timeout_ms } => {
+                write!(f, "request not finished within {}ms", timeout_ms)
+            }
+            ApiError::ServerDraining => write!(f, "server is draining"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// service 层以 anyhow 返回错误，已分类的错误原样取出，rocksdb 错误视为存储不可用，其余为内部错误
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(api_error) = e.downcast_ref::<ApiError>() {
+            return api_error.clone();
+        }
+        if let Some(consistency) = e.downcast_ref::<TaskConsistencyError>() {
+            return ApiError::InvalidTaskDefinition {
+                message: consistency.to_string(),
+                errors: consistency.errors.clone(),
+            };
+        }
+        if let Some(validation) = e.downcast_ref::<TaskValidationError>() {
+            return ApiError::InvalidTaskFields {
+                problems: validation.problems.clone(),
+            };
+        }
+        if let Some(setup) = e.downcast_ref::<TaskSetupError>() {
+            return ApiError::TaskSetupFailed {
+                stage: setup.stage,
+                storage: setup.storage.clone(),
+                message: setup.message.clone(),
+            };
+        }
+        if let Some(exists) = e.downcast_ref::<TaskExistsError>() {
+            return ApiError::TaskAlreadyExists {
+                task_id: exists.task_id.clone(),
+            };
+        }
+        if let Some(resource) = e.downcast_ref::<ResourceError>() {
+            return resource_api_error(resource);
+        }
+        if e.downcast_ref::<rocksdb::Error>().is_some() {
+            return ApiError::StorageUnavailable(e.to_string());
+        }
+        ApiError::Internal(e.to_string())
+    }
+}
+
+// 记录不存在为 404，存储不可用为 503，记录损坏等为 500
+fn resource_api_error(e: &ResourceError) -> ApiError {
+    match e {
+        ResourceError::KeyNotFound { cf, key } if cf == CF_TASK => ApiError::TaskNotFound {
+            task_id: key.clone(),
+        },
+        ResourceError::KeyNotFound { .. } => ApiError::NotFound(e.to_string()),
+        ResourceError::PathNotConfigured
+        | ResourceError::NotOpened
+        | ResourceError::ColumnFamilyMissing { .. }
+        | ResourceError::Io { .. } => ApiError::StorageUnavailable(e.to_string()),
+        ResourceError::InvalidArgument(_)
+        | ResourceError::Corrupt { .. }
+        | ResourceError::Serialization { .. } => ApiError::Internal(e.to_string()),
+    }
+}
+
+impl From<ResourceError> for ApiError {
+    fn from(e: ResourceError) -> Self {
+        resource_api_error(&e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ApiError;
+    use crate::configure::TokenScope;
+    use crate::resources::{ResourceError, CF_TASK, CF_TASK_CHECKPOINTS};
+    use crate::tasks::{
+        ConsistencyIssue, ConsistencySeverity, FieldProblem, SetupStage, TaskConsistencyError,
+        TaskExistsError, TaskSetupError, TaskValidationError,
+    };
+    use anyhow::anyhow;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use serde_json::Value;
+
+    fn setup_failed(stage: SetupStage) -> ApiError {
+        ApiError::TaskSetupFailed {
+            stage,
+            storage: "source".to_string(),
+            message: "x".to_string(),
+        }
+    }
+
+    //cargo test httpserver::module::module_error::test::test_api_error_status -- --nocapture
+    #[test]
+    fn test_api_error_status() {
+        let cases = vec![
+            (
+                ApiError::InvalidRequest("bad".to_string()),
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+            ),
+            (
+                ApiError::InvalidTaskFields { problems: vec![] },
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_task_fields",
+            ),
+            (
+                ApiError::PayloadTooLarge("length limit exceeded".to_string()),
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+            ),
+            (
+                setup_failed(SetupStage::Credentials),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "task_setup_failed",
+            ),
+            (
+                setup_failed(SetupStage::Unreachable),
+                StatusCode::BAD_GATEWAY,
+                "task_setup_failed",
+            ),
+            (
+                setup_failed(SetupStage::MetaDir),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "task_setup_failed",
+            ),
+            (
+                ApiError::TaskNotFound {
+                    task_id: "1".to_string(),
+                },
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                ApiError::NotFound("run not exist".to_string()),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                ApiError::TaskAlreadyExists { task_id: None },
+                StatusCode::CONFLICT,
+                "task_already_exists",
+            ),
+            (
+                ApiError::TaskAlreadyLiving {
+                    task_id: "1".to_string(),
+                },
+                StatusCode::CONFLICT,
+                "task_already_living",
+            ),
+            (
+                ApiError::TaskNotLiving {
+                    task_id: "1".to_string(),
+                },
+                StatusCode::CONFLICT,
+                "task_not_living",
+            ),
+            (
+                ApiError::TaskAlreadyPaused {
+                    task_id: "1".to_string(),
+                },
+                StatusCode::CONFLICT,
+                "task_already_paused",
+            ),
+            (
+                ApiError::IdempotencyKeyConflict {
+                    key: "k".to_string(),
+                    task_id: "1".to_string(),
+                },
+                StatusCode::CONFLICT,
+                "idempotency_key_conflict",
+            ),
+            (
+                ApiError::TaskNameConflict {
+                    name: "nightly".to_string(),
+                    task_ids: vec!["1".to_string()],
+                },
+                StatusCode::CONFLICT,
+                "task_name_conflict",
+            ),
+            (
+                ApiError::Forbidden {
+                    required_scope: TokenScope::Admin,
+                    token_scope: TokenScope::Write,
+                },
+                StatusCode::FORBIDDEN,
+                "forbidden",
+            ),
+            (
+                ApiError::DbJobConflict {
+                    job_id: "j".to_string(),
+                },
+                StatusCode::CONFLICT,
+                "db_job_conflict",
+            ),
+            (
+                ApiError::TooManyRequests {
+                    group: "analyze".to_string(),
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_requests",
+            ),
+            (
+                ApiError::AnalyzeTimeout {
+                    task_id: "1".to_string(),
+                    sizes: Default::default(),
+                },
+                StatusCode::GATEWAY_TIMEOUT,
+                "analyze_timeout",
+            ),
+            (
+                ApiError::WaitTimeout {
+                    task_id: "1".to_string(),
+                    status: Value::Null,
+                },
+                StatusCode::REQUEST_TIMEOUT,
+                "wait_timeout",
+            ),
+            (
+                ApiError::RequestTimeout { timeout_ms: 2000 },
+                StatusCode::SERVICE_UNAVAILABLE,
+                "request_timeout",
+            ),
+            (
+                ApiError::ServerDraining,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_draining",
+            ),
+            (
+                ApiError::StorageUnavailable("io".to_string()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "storage_unavailable",
+            ),
+            (
+                ApiError::Internal("x".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+            ),
+        ];
+        for (err, status, code) in cases {
+            assert_eq!(err.status(), status);
+            assert_eq!(err.code(), code);
+            assert_eq!(err.clone().into_response().status(), status);
+        }
+
+        let body = ApiError::TaskNotFound {
+            task_id: "42".to_string(),
+        }
+        .body();
+        assert_eq!(body["code"], "task_not_found");
+        assert_eq!(body["message"], "task 42 not exist");
+        assert_eq!(body["details"]["task_id"], "42");
+        assert!(body.get("request_id").is_none());
+    }
+
+    //cargo test httpserver::module::module_error::test::test_api_error_from_anyhow -- --nocapture
+    #[test]
+    fn test_api_error_from_anyhow() {
+        let typed = anyhow::Error::new(ApiError::TaskAlreadyLiving {
+            task_id: "7".to_string(),
+        });
+        assert_eq!(
+            ApiError::from(typed.context("start task")),
+            ApiError::TaskAlreadyLiving {
+                task_id: "7".to_string()
+            }
+        );
+
+        let consistency = anyhow::Error::new(TaskConsistencyError {
+            errors: vec![ConsistencyIssue {
+                rule_id: "R1".to_string(),
+                severity: ConsistencySeverity::Error,
+                message: "conflict".to_string(),
+                suggestion: "fix".to_string(),
+            }],
+        });
+        let err = ApiError::from(consistency);
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.details()["errors"][0]["rule_id"], "R1");
+
+        let validation = anyhow::Error::new(TaskValidationError {
+            problems: vec![FieldProblem {
+                field: "source.bucket".to_string(),
+                problem: "is empty".to_string(),
+            }],
+        });
+        let err = ApiError::from(validation);
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.details()["problems"][0]["field"], "source.bucket");
+
+        let setup = anyhow::Error::new(TaskSetupError {
+            stage: SetupStage::Unreachable,
+            storage: "target".to_string(),
+            message: "dns error".to_string(),
+        });
+        let err = ApiError::from(setup);
+        assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.details()["stage"], "unreachable");
+        assert_eq!(err.details()["storage"], "target");
+
+        let exists = anyhow::Error::new(TaskExistsError {
+            task_id: Some("9".to_string()),
+        });
+        let err = ApiError::from(exists);
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(err.to_string(), "task 9 already exist");
+
+        let untyped = ApiError::from(anyhow!("boom"));
+        assert_eq!(untyped, ApiError::Internal("boom".to_string()));
+    }
+
+    //cargo test httpserver::module::module_error::test::test_api_error_from_resource -- --nocapture
+    #[test]
+    fn test_api_error_from_resource() {
+        let missing_task = anyhow::Error::new(ResourceError::not_found(CF_TASK, "42"));
+        assert_eq!(
+            ApiError::from(missing_task),
+            ApiError::TaskNotFound {
+                task_id: "42".to_string()
+            }
+        );
+        let err = ApiError::from(ResourceError::not_found(CF_TASK_CHECKPOINTS, "42"));
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        assert!(err.to_string().contains("cf_task_checkpoints"));
+
+        let corrupt = ResourceError::corrupt(CF_TASK, "42", anyhow!("expected value"));
+        assert_eq!(
+            ApiError::from(corrupt).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let io = ResourceError::io("get key 42 in column family cf_task", anyhow!("IO error"));
+        assert_eq!(ApiError::from(io).status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::tasks::Task;
+use crate::server::TaskCounters;
+
+use crate::tasks::{
+    BatchProgress, CheckPoint, CheckpointFlush, ConnectivityCheck, ConsistencyIssue,
+    ConsistencyReport, DefinitionChange, FileDescription, FilePosition, PercentSummary, Status,
+    Task, TaskErrorRecord, TaskRun, TaskRunDefinition, TaskSkipRecord, TaskStatus, TaskType,
+    TransferStage, TransferTaskStatus,
+};
 
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct ReqTaskId {
@@ -12,10 +20,175 @@ pub struct ReqTaskIds {
     pub task_ids: Vec<String>,
 }
 
+/// 删除任务，force 为 true 时先停止运行中的任务
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskRemove {
+    pub task_ids: Vec<String>,
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// 克隆任务时覆盖的字段，未指定的字段沿用源任务
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskClone {
+    pub name: Option<String>,
+    pub source_prefix: Option<String>,
+    pub target_prefix: Option<String>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ReqTaskUpdate {
     pub task_id: String,
     pub task: Task,
+    // 已有 checkpoint 时改用按全局配置新生成的 meta_dir 并清除 checkpoint
+    #[serde(default)]
+    pub reset_meta: bool,
+}
+
+/// 任务列表按状态过滤，error 为异常中止或未满足成功判定条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskListStatus {
+    Living,
+    Stopped,
+    Error,
+}
+
+/// 任务列表过滤条件，各条件同时满足，name 为忽略大小写的子串
+/// after 为上一页最后一个任务 id，从其后开始列出，limit 为本页最多返回的任务数
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskListFilter {
+    pub status: Option<TaskListStatus>,
+    #[serde(rename = "type")]
+    pub task_type: Option<TaskType>,
+    pub name: Option<String>,
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// 按名称、源与目标的 endpoint、bucket、region 或本地路径搜索任务，q 为忽略大小写的子串
+/// name 存在时经名称索引查找名称完全相同的任务，q 可为空
+/// after 与 limit 的含义与任务列表相同
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskSearch {
+    #[serde(default)]
+    pub q: String,
+    pub name: Option<String>,
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// 搜索结果只返回任务摘要
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespTaskSummary {
+    pub task_id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub task_type: TaskType,
+    pub state: TaskListStatus,
+}
+
+/// 错误记录分页，offset 为跳过的记录数，limit 缺省为 100，最大 1000
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskErrors {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// next_offset 为下一页的 offset，没有更多记录时为 None
+#[derive(Debug, Clone, Serialize)]
+pub struct RespTaskErrors {
+    pub records: Vec<TaskErrorRecord>,
+    pub next_offset: Option<usize>,
+}
+
+/// 流式任务列表结尾的统计，next_after 为下一页的游标，没有更多任务时为 None
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TaskListMeta {
+    pub returned: usize,
+    pub skipped_corrupt: usize,
+    pub next_after: Option<String>,
+}
+
+/// 回滚到历史中 modify_checkpoint_timestamp 为 timestamp 的 checkpoint
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqCheckpointRollback {
+    pub timestamp: i64,
+}
+
+/// 重置 checkpoint，hard 时同时删除错误记录
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqCheckpointReset {
+    #[serde(default)]
+    pub hard: bool,
+}
+
+/// 停止任务，wait 时等待任务实际退出，timeout_secs 缺省为 30 秒
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskStop {
+    #[serde(default)]
+    pub wait: bool,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStopState {
+    Stopped,
+    // 等待超时，任务仍在退出中
+    Stopping,
+}
+
+/// 等待停止的结果，status 为任务退出后记录的最终状态
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespTaskStop {
+    pub task_id: String,
+    pub state: TaskStopState,
+    pub run_id: String,
+    pub status: Option<Status>,
+    // 本次运行至今或至退出的时长
+    pub duration_secs: u64,
+}
+
+/// 源端对象大小分布，cached 时 timestamp 为缓存结果的统计完成时间
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespTaskAnalyze {
+    pub task_id: String,
+    pub sizes: BTreeMap<String, i128>,
+    pub timestamp: u64,
+    pub cached: bool,
+}
+
+/// 重置 checkpoint 时删除的内容
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespCheckpointReset {
+    pub task_id: String,
+    pub checkpoint_removed: bool,
+    pub file_positions_removed: usize,
+    pub files_removed: Vec<String>,
+}
+
+/// 全部运行中任务的 checkpoint 快照结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RespCheckpointFlushAll {
+    pub flushed: Vec<CheckpointFlush>,
+    pub failed: Vec<RespTaskBatchItem>,
+}
+
+/// 批量启停中单个任务的结果
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RespTaskBatchItem {
+    pub task_id: String,
+    pub ok: bool,
+    // 失败时的错误码，与单个接口的 code 一致
+    pub code: Option<String>,
+    pub error: Option<String>,
+    // 成功时单个任务的返回内容，如等待停止的最终状态
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,3 +196,152 @@ pub struct RespListTask {
     pub cf_id: String,
     pub task: Task,
 }
+
+#[derive(Debug, Serialize)]
+pub struct RespTaskStatus {
+    #[serde(flatten)]
+    pub checkpoint: Option<CheckPoint>,
+    // meta_dir 中是否存在完成标识
+    pub completed: bool,
+    pub last_skip_reason: Option<TaskSkipRecord>,
+    // 任务状态，从未启动的任务为 None
+    pub status: Option<Status>,
+    // 执行中批次的最小列表文件位置，新于 checkpoint 中的位置
+    pub executing_position: Option<FilePosition>,
+    // 排队中任务在队列中的位置，从 1 开始
+    pub queue_position: Option<usize>,
+    // 运行中任务本次运行已传输的对象数、字节数与错误数
+    pub run_counters: Option<TaskCounters>,
+}
+
+/// 合并内存与持久化状态后的任务状态，判定顺序：
+/// 1. 内存中的运行状态优先于持久化状态，排队中的任务为 queued
+/// 2. 停止状态中异常停止（broken、failed）优先于正常停止
+///    内存中为暂停状态时为 paused，持久化状态仍为运行中
+/// 3. 持久化为运行中但内存中无该任务为 interrupted，通常为服务异常退出，重启后恢复
+/// 4. 均不存在为 not_started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectiveTaskState {
+    NotStarted,
+    Queued,
+    Starting,
+    Running,
+    Paused,
+    Interrupted,
+    Stopped,
+    Failed,
+}
+
+/// checkpoint 摘要
+#[derive(Debug, Serialize)]
+pub struct RespCheckpointSummary {
+    pub executing_file_position: FilePosition,
+    pub task_stage: TransferStage,
+    pub timestamp: i128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RespTaskUnifiedStatus {
+    pub task_id: String,
+    pub effective_state: EffectiveTaskState,
+    // 内存与持久化状态不一致
+    pub stale: bool,
+    // CF_TASK_STATUS 中的状态
+    pub persisted: Option<TaskStatus>,
+    // 运行中或排队中任务的内存状态
+    pub live: Option<TransferTaskStatus>,
+    pub checkpoint: Option<RespCheckpointSummary>,
+    pub queue_position: Option<usize>,
+    // 最近一次启动被拒绝或进入队列的原因，任务实际启动后清除
+    pub last_skip_reason: Option<TaskSkipRecord>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskValidate {
+    // 是否检查源与目标存储的连通性
+    #[serde(default)]
+    pub connect: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskWait {
+    // 秒，缺省 60，最长 3600
+    pub timeout: Option<u64>,
+}
+
+/// 等待结果，waited 为 false 表示请求时任务未运行，status 与 run 为当时的持久化状态与最近一次运行
+#[derive(Debug, Serialize)]
+pub struct RespTaskWait {
+    pub task_id: String,
+    pub waited: bool,
+    pub status: Option<Status>,
+    pub run: Option<TaskRun>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskExport {
+    // ndjson 时逐行返回，缺省为单个 json 文档
+    pub format: Option<String>,
+    // 凭证脱敏，read 权限的 token 总是脱敏
+    #[serde(default)]
+    pub redact: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskImport {
+    // 保留导出时的 task_id，id 已存在的条目失败
+    #[serde(default)]
+    pub keep_ids: bool,
+}
+
+/// 校验结果，connect 为 true 时 checks 为各项连通性检查的结果
+#[derive(Debug, Serialize)]
+pub struct RespTaskValidate {
+    #[serde(flatten)]
+    pub report: ConsistencyReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checks: Option<Vec<ConnectivityCheck>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RespTaskShow {
+    #[serde(flatten)]
+    pub task: Task,
+    // 不阻止创建的字段组合冲突
+    pub consistency_warnings: Vec<ConsistencyIssue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RespRunDefinition {
+    pub run: TaskRunDefinition,
+    // 与当前任务定义的差异，任务已删除时为 None
+    pub diff: Option<Vec<DefinitionChange>>,
+}
+
+/// 任务执行中各批次的进度，list_file 为 checkpoint 中记录的列表文件，任务未启动时为 None
+#[derive(Debug, Clone, Serialize)]
+pub struct RespTaskProgress {
+    pub task_id: String,
+    pub list_file: Option<FileDescription>,
+    pub executing_position: Option<FilePosition>,
+    pub overall_percent: Option<f64>,
+    pub batches: Vec<BatchProgress>,
+    // 已登记范围的批次百分比分布
+    pub summary: Option<PercentSummary>,
+    pub stalled: usize,
+}
+
+/// 运行记录分页，按开始时间倒序，limit 缺省为 20，最大 500
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReqTaskRuns {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// next_offset 为下一页的 offset，没有更多记录时为 None
+#[derive(Debug, Clone, Serialize)]
+pub struct RespTaskRuns {
+    pub runs: Vec<TaskRun>,
+    pub next_offset: Option<usize>,
+}
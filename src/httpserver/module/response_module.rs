@@ -18,7 +18,4 @@ where
     pub fn ok(data: T) -> Self {
         Self::new(0, "OK".to_string(), Some(data))
     }
-    pub fn err(code: i32, msg: String) -> Self {
-        Self::new(code, msg, None)
-    }
 }
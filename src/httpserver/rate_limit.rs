@@ -0,0 +1,205 @@
+use crate::configure::{get_config, HttpRateLimitConfig};
+use crate::httpserver::auth::AUTH_EXEMPT_PATHS;
+use crate::httpserver::module::ApiError;
+use crate::server::record_rate_limited;
+use axum::extract::{ConnectInfo, MatchedPath, Request};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// 未命中 routes 的请求使用的分组
+const DEFAULT_GROUP: &str = "default";
+// 桶数量超过该值时清理已回满的桶
+const MAX_RATE_LIMIT_BUCKETS: usize = 10000;
+
+static GLOBAL_RATE_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::default);
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    per_second: f64,
+    burst: u32,
+}
+
+impl TokenBucket {
+    fn new(now: Instant, per_second: f64, burst: u32) -> Self {
+        Self {
+            tokens: burst as f64,
+            updated: now,
+            per_second,
+            burst,
+        }
+    }
+
+    // 限额重载后按新参数补充令牌，超出新容量的部分丢弃
+    fn take(&mut self, now: Instant, per_second: f64, burst: u32) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(burst as f64);
+        self.updated = now;
+        self.per_second = per_second;
+        self.burst = burst;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.per_second >= self.burst as f64
+    }
+}
+
+/// 按 (分组, 客户端) 维护令牌桶
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: DashMap<(String, String), TokenBucket>,
+}
+
+impl RateLimiter {
+    /// 取一个令牌，令牌不足时返回需等待的时长
+    pub fn check(
+        &self,
+        group: &str,
+        client: &str,
+        per_second: f64,
+        burst: u32,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if self.buckets.len() > MAX_RATE_LIMIT_BUCKETS {
+            self.buckets.retain(|_, b| !b.is_full(now));
+        }
+        let mut bucket = self
+            .buckets
+            .entry((group.to_string(), client.to_string()))
+            .or_insert_with(|| TokenBucket::new(now, per_second, burst));
+        bucket.take(now, per_second, burst)
+    }
+}
+
+/// 请求路径对应的分组及其限额
+pub fn rate_limit_rule<'a>(config: &'a HttpRateLimitConfig, path: &str) -> (&'a str, f64, u32) {
+    match config
+        .routes
+        .iter()
+        .find(|r| r.paths.iter().any(|p| p == path))
+    {
+        Some(r) => (r.name.as_str(), r.per_second, r.burst),
+        None => (DEFAULT_GROUP, config.per_second, config.burst),
+    }
+}
+
+// token 仅以哈希值作为 key，不在内存中保留原文
+fn client_key(config: &HttpRateLimitConfig, request: &Request) -> String {
+    if config.per_token {
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if let Some(token) = token {
+            let mut hasher = DefaultHasher::new();
+            token.trim().hash(&mut hasher);
+            return format!("token:{:x}", hasher.finish());
+        }
+    }
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        // unix socket 上的请求共用一个桶
+        None => "local".to_string(),
+    }
+}
+
+fn too_many_requests(group: &str, retry_after: Duration) -> Response {
+    // Retry-After 为整秒，向上取整且不小于 1
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = ApiError::TooManyRequests {
+        group: group.to_string(),
+    }
+    .into_response();
+    if let Ok(v) = HeaderValue::from_str(&secs.max(1).to_string()) {
+        response.headers_mut().insert(header::RETRY_AFTER, v);
+    }
+    response
+}
+
+/// 按 http.rate_limit 限流，超出限额返回 429 及 Retry-After；探测路径不限流
+pub async fn rate_limit_middleware(request: Request, next: Next) -> Response {
+    if AUTH_EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let config = match get_config() {
+        Ok(c) => c.http.rate_limit,
+        Err(_) => return next.run(request).await,
+    };
+    if !config.enabled {
+        return next.run(request).await;
+    }
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(p) => p.as_str().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let (group, per_second, burst) = rate_limit_rule(&config, &path);
+    let client = client_key(&config, &request);
+    match GLOBAL_RATE_LIMITER.check(group, &client, per_second, burst, Instant::now()) {
+        Ok(_) => next.run(request).await,
+        Err(retry_after) => {
+            record_rate_limited(group);
+            too_many_requests(group, retry_after)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rate_limit_rule, RateLimiter};
+    use crate::configure::HttpRateLimitConfig;
+    use std::time::{Duration, Instant};
+
+    //cargo test httpserver::rate_limit::test::test_rate_limiter -- --nocapture
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        assert!(limiter.check("analyze", "ip:1", 0.5, 2, now).is_ok());
+        assert!(limiter.check("analyze", "ip:1", 0.5, 2, now).is_ok());
+        let retry = limiter.check("analyze", "ip:1", 0.5, 2, now).unwrap_err();
+        assert_eq!(retry, Duration::from_secs(2));
+        // 其他客户端与分组不受影响
+        assert!(limiter.check("analyze", "ip:2", 0.5, 2, now).is_ok());
+        assert!(limiter.check("default", "ip:1", 0.5, 2, now).is_ok());
+        // 令牌按速率补充
+        let later = now + Duration::from_secs(2);
+        assert!(limiter.check("analyze", "ip:1", 0.5, 2, later).is_ok());
+        assert!(limiter.check("analyze", "ip:1", 0.5, 2, later).is_err());
+        // 重载后的限额立即生效
+        let reloaded = later + Duration::from_secs(1);
+        assert!(limiter.check("analyze", "ip:1", 10.0, 20, reloaded).is_ok());
+    }
+
+    //cargo test httpserver::rate_limit::test::test_rate_limit_rule -- --nocapture
+    #[test]
+    fn test_rate_limit_rule() {
+        let config = HttpRateLimitConfig::default();
+        assert_eq!(
+            rate_limit_rule(&config, "/api/v1/task/analyze"),
+            ("analyze", 0.2, 2)
+        );
+        assert_eq!(
+            rate_limit_rule(&config, "/api/v1/task/all"),
+            ("default", 50.0, 100)
+        );
+        assert!(config.validate().is_ok());
+        let mut invalid = config.clone();
+        invalid.routes[0].burst = 0;
+        assert!(invalid.validate().is_err());
+    }
+}
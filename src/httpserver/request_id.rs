@@ -0,0 +1,63 @@
+use crate::commons::with_request_id;
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+// 调用方传入的 request id 长度上限，超出或含非法字符时重新生成
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// 请求的 request id，handler 可通过 Extension 取得
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// 沿用请求头中合法的 X-Request-Id，否则生成 uuid
+pub fn request_id_from(header: Option<&HeaderValue>) -> String {
+    match header.and_then(|v| v.to_str().ok()) {
+        Some(id) if valid_request_id(id) => id.to_string(),
+        _ => uuid::Uuid::new_v4().to_string(),
+    }
+}
+
+/// 为请求分配 request id，请求处理期间的日志均在携带 request_id 的 span 中输出，响应头回传该 id
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let id = request_id_from(request.headers().get(&REQUEST_ID_HEADER));
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let span = tracing::info_span!("request", request_id = %id);
+    let mut response = with_request_id(id.clone(), next.run(request).instrument(span)).await;
+    if let Ok(v) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::request_id_from;
+    use axum::http::HeaderValue;
+
+    //cargo test httpserver::request_id::test::test_request_id_from -- --nocapture
+    #[test]
+    fn test_request_id_from() {
+        let incoming = HeaderValue::from_static("lb-1234:abcd");
+        assert_eq!(request_id_from(Some(&incoming)), "lb-1234:abcd");
+
+        let generated = request_id_from(None);
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+
+        let invalid = HeaderValue::from_static("has space");
+        assert_ne!(request_id_from(Some(&invalid)), "has space");
+        let too_long = HeaderValue::from_str(&"a".repeat(129)).unwrap();
+        assert_eq!(request_id_from(Some(&too_long)).len(), 36);
+    }
+}
@@ -1,26 +1,55 @@
 use crate::httpserver::handlers::{
-    current_config, rbatis_t_insert, redis_put, root, task_all, task_all_living, task_analyze,
-    task_create, task_remove, task_show, task_start, task_status, task_stop,
-    task_template_transfer_local2local, task_template_transfer_local2oss,
-    task_template_transfer_oss2local, task_template_transfer_oss2oss, task_update,
+    buffer_pool_stats, checkpoint_flush_all, config_reload_status, current_config, db_backup,
+    db_backups, db_check, db_compact, db_job, db_prune, db_reindex, db_repair, db_stats,
+    log_level_current, log_level_set, metrics, rbatis_t_insert, readyz, redis_put, root,
+    runtime_state_dump, runtime_tasks, runtime_threads_current, self_stats, server_info,
+    server_stats_snapshot, task_all, task_all_living, task_all_stream, task_analyze,
+    task_change_revert, task_changes, task_checkpoint_export, task_checkpoint_flush,
+    task_checkpoint_history, task_checkpoint_import, task_checkpoint_reset,
+    task_checkpoint_rollback, task_clone, task_completion, task_create, task_errors,
+    task_errors_download, task_events, task_export, task_import, task_patch, task_pause,
+    task_progress, task_queue_current, task_queue_limit_set, task_remove, task_resume,
+    task_run_definition, task_runs, task_search, task_show, task_show_by_name, task_start,
+    task_start_batch, task_status, task_stop, task_stop_batch, task_template_transfer_local2local,
+    task_template_transfer_local2oss, task_template_transfer_oss2local,
+    task_template_transfer_oss2oss, task_unified_status, task_update, task_validate, task_wait,
 };
 
+use crate::configure::{get_config, HttpCompressionConfig, HttpConfig};
+use crate::httpserver::auth::auth_middleware;
+use crate::httpserver::compression::compression_layer;
+use crate::httpserver::cors::cors_layer;
+use crate::httpserver::deprecation::deprecated_alias_middleware;
+use crate::httpserver::envelope::v1_envelope_middleware;
+use crate::httpserver::metrics::metrics_middleware;
+use crate::httpserver::module::ApiError;
+use crate::httpserver::rate_limit::rate_limit_middleware;
+use crate::httpserver::request_id::request_id_middleware;
 use axum::error_handling::HandleErrorLayer;
-use axum::http::StatusCode;
-use axum::routing::{get, post};
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, patch, post, put};
 use axum::{BoxError, Router};
 
 use std::time::Duration;
 use tower::ServiceBuilder;
-use tower_http::{compression::CompressionLayer, trace::TraceLayer};
+use tower_http::trace::TraceLayer;
+
+// 未自带超时的接口统一的处理超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
 
 pub fn router_root() -> Router {
     let tracer = TraceLayer::new_for_http();
+    // 压缩配置修改后需重启生效
+    let compression = match get_config() {
+        Ok(c) => compression_layer(&c.http.compression),
+        Err(_) => compression_layer(&HttpCompressionConfig::default()),
+    };
+    // 长轮询与等待停止的接口自带超时，任务定义导入导出随任务数增长，均不经过 2s 超时层
+    let long_poll_stack = ServiceBuilder::new().layer(tracer.clone()).into_inner();
     let middleware_stack = ServiceBuilder::new()
         .layer(tracer)
-        .layer(CompressionLayer::new())
         .layer(HandleErrorLayer::new(handle_timeout_error))
-        .layer(tower::timeout::TimeoutLayer::new(Duration::from_secs(2)))
+        .layer(tower::timeout::TimeoutLayer::new(REQUEST_TIMEOUT))
         .into_inner();
 
     // let static_files_service: MethodRouter<Body> = get_service(
@@ -33,22 +62,66 @@ pub fn router_root() -> Router {
     //     )
     // });
 
+    // 探测与监控路径按惯例保留在根路径，不加版本前缀
     let root = Router::new()
         // .route("/gethead", post(get_headers))
-        .route("/health", get(root))
-        .route("/health", post(root));
+        .route("/healthz", get(root))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .merge(
+            versioned_root_routes().layer(axum::middleware::from_fn(deprecated_alias_middleware)),
+        );
+
+    // 任务定义接口限制请求体大小，超出时不再读取剩余内容
+    let body_limit = DefaultBodyLimit::max(match get_config() {
+        Ok(c) => c.http.max_body_size,
+        Err(_) => HttpConfig::max_body_size_default(),
+    });
 
     let task_router = Router::new()
-        .route("/create", post(task_create))
-        .route("/update", post(task_update))
+        .route("/create", post(task_create).layer(body_limit))
+        .route("/update", post(task_update).layer(body_limit))
+        .route("/validate", post(task_validate).layer(body_limit))
         .route("/remove", post(task_remove))
         .route("/start", post(task_start))
-        .route("/stop", post(task_stop))
+        .route("/start_batch", post(task_start_batch))
         .route("/status", post(task_status))
         .route("/show", post(task_show))
         .route("/analyze", post(task_analyze))
         .route("/all", post(task_all))
         .route("/all_living", post(task_all_living))
+        .route("/all_stream", post(task_all_stream))
+        .route("/search", get(task_search))
+        .route("/by-name/:name", get(task_show_by_name))
+        .route("/:task_id", patch(task_patch).layer(body_limit))
+        .route("/:task_id/clone", post(task_clone))
+        .route("/:task_id/checkpoint/reset", post(task_checkpoint_reset))
+        .route("/:task_id/checkpoint/export", get(task_checkpoint_export))
+        .route("/:task_id/checkpoint/flush", post(task_checkpoint_flush))
+        .route("/:task_id/checkpoint/history", get(task_checkpoint_history))
+        .route(
+            "/:task_id/checkpoint/rollback",
+            post(task_checkpoint_rollback),
+        )
+        .route(
+            "/:task_id/checkpoint/import",
+            post(task_checkpoint_import).layer(body_limit),
+        )
+        .route("/:task_id/pause", post(task_pause))
+        .route("/:task_id/resume", post(task_resume))
+        .route("/:task_id/completion", get(task_completion))
+        .route("/:task_id/events", get(task_events))
+        .route("/:task_id/status", get(task_unified_status))
+        .route("/:task_id/changes", get(task_changes))
+        .route("/:task_id/changes/:seq/revert", get(task_change_revert))
+        .route("/:task_id/errors", get(task_errors))
+        .route("/:task_id/errors/download", get(task_errors_download))
+        .route("/:task_id/progress", get(task_progress))
+        .route("/:task_id/runs", get(task_runs))
+        .route(
+            "/:task_id/runs/:run_id/definition",
+            get(task_run_definition),
+        )
         .route(
             "/template/transfer/oss2oss",
             get(task_template_transfer_oss2oss),
@@ -65,28 +138,475 @@ pub fn router_root() -> Router {
             "/template/transfer/local2local",
             get(task_template_transfer_local2local),
         )
+        .layer(middleware_stack.clone())
+        .merge(
+            Router::new()
+                .route("/stop", post(task_stop))
+                .route("/stop_batch", post(task_stop_batch))
+                .route("/:task_id/wait", get(task_wait))
+                .route("/export", get(task_export))
+                .route("/import", post(task_import).layer(body_limit))
+                .layer(long_poll_stack),
+        );
+
+    let admin_router = Router::new()
+        .route("/loglevel", get(log_level_current))
+        .route("/loglevel", put(log_level_set))
+        .route("/config/reload", get(config_reload_status))
+        .route("/dump", get(runtime_state_dump))
+        .route("/runtime", get(runtime_threads_current))
+        .route("/runtime/tasks", get(runtime_tasks))
+        .route("/buffer-pool", get(buffer_pool_stats))
+        .route("/self-stats", get(self_stats))
+        .route("/checkpoint/flush_all", post(checkpoint_flush_all))
+        .route("/task_queue", get(task_queue_current))
+        .route("/task_queue/limit", put(task_queue_limit_set))
+        .route("/db/compact", post(db_compact))
+        .route("/db/backup", post(db_backup))
+        .route("/db/prune", post(db_prune))
+        .route("/db/reindex", post(db_reindex))
+        .route("/db/check", get(db_check))
+        .route("/db/repair", post(db_repair))
+        .route("/db/backups", get(db_backups))
+        .route("/db/stats", get(db_stats))
+        .route("/db/jobs/:job_id", get(db_job))
         .layer(middleware_stack.clone());
 
     let api = Router::new()
         .route("/v1/currentconfig", post(current_config))
+        .route("/v1/stats", get(server_stats_snapshot))
+        .route("/v1/queue", get(task_queue_current))
         .route("/v1/redis/put", post(redis_put))
         .route("/v1/mysql/insert", post(rbatis_t_insert))
         .layer(middleware_stack.clone())
-        .nest("/v1/task", task_router);
+        .nest("/v1", versioned_root_routes())
+        .nest("/v1/task", task_router)
+        .nest("/v1/admin", admin_router);
 
-    return root.nest("/api", api);
+    // 鉴权作用于全部路由，/health 等探测路径在中间件内放行
+    // 限流先于鉴权，未通过鉴权的请求同样消耗限额
+    // v1 响应结构在压缩之前转换，请求 v1 结构时鉴权与限流的错误响应同样转换
+    let router = root
+        .nest("/api", api)
+        .layer(axum::middleware::from_fn(auth_middleware))
+        .layer(axum::middleware::from_fn(rate_limit_middleware))
+        .layer(axum::middleware::from_fn(v1_envelope_middleware));
+    let router = match compression {
+        Some(layer) => router.layer(layer),
+        None => router,
+    };
+    // 跨域预检不携带 token，需在鉴权与限流之前应答；配置已在启动检查中校验
+    let router = match get_config().map(|c| c.http.cors) {
+        Ok(Some(cors)) => match cors_layer(&cors) {
+            Ok(Some(layer)) => router.layer(layer),
+            Ok(None) => router,
+            Err(e) => {
+                log::error!("{}", e);
+                router
+            }
+        },
+        _ => router,
+    };
+    // request id 在鉴权、限流与跨域之外分配，被拒绝的请求同样回传 request id
+    let router = router.layer(axum::middleware::from_fn(request_id_middleware));
+    // 指标中间件在最外层，被拒绝的请求同样计数
+    return router.layer(axum::middleware::from_fn(metrics_middleware));
 }
 
-async fn handle_timeout_error(err: BoxError) -> (StatusCode, String) {
+// 早期未加版本前缀的接口，挂载于 /api/v1 下，根路径保留为废弃别名
+fn versioned_root_routes() -> Router {
+    Router::new()
+        .route("/health", get(root).post(root))
+        .route("/info", get(server_info))
+}
+
+async fn handle_timeout_error(err: BoxError) -> ApiError {
     if err.is::<tower::timeout::error::Elapsed>() {
-        (
-            StatusCode::REQUEST_TIMEOUT,
-            "Request took too long".to_string(),
-        )
+        ApiError::RequestTimeout {
+            timeout_ms: REQUEST_TIMEOUT.as_millis() as u64,
+        }
     } else {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Unhandled internal error: {}", err),
+        ApiError::Internal(format!("Unhandled internal error: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::router_root;
+    use crate::configure::HttpConfig;
+    use crate::httpserver::deprecation::{successor_path, DEPRECATION_HEADER};
+    use crate::httpserver::envelope::V1_ENVELOPE_MEDIA_TYPE;
+    use crate::resources::{open_test_rocksdb, CF_TASK};
+    use crate::tasks::{
+        init_global_task_runtime, CompareTask, ObjectStorage, Task, TransferTask,
+        TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIVING_TRANSFER_TASK_MAP,
+    };
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn request(
+        method: &str,
+        uri: &str,
+        body: Option<&str>,
+        v1: bool,
+    ) -> (StatusCode, bool, Value) {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-request-id", "route-test")
+            .header("content-type", "application/json");
+        if v1 {
+            req = req.header("accept", V1_ENVELOPE_MEDIA_TYPE);
+        }
+        let req = req
+            .body(match body {
+                Some(b) => Body::from(b.to_string()),
+                None => Body::empty(),
+            })
+            .unwrap();
+        let resp = router_root().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let deprecated = resp.headers().contains_key(DEPRECATION_HEADER);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, deprecated, serde_json::from_slice(&body).unwrap())
+    }
+
+    //cargo test httpserver::routers::root::test::test_versioned_aliases -- --nocapture
+    #[tokio::test]
+    async fn test_versioned_aliases() {
+        // 旧路径与 /api/v1 在未请求 v1 结构时响应相同
+        for (method, legacy) in [("GET", "/health"), ("POST", "/health"), ("GET", "/info")] {
+            let (legacy_status, legacy_deprecated, legacy_body) =
+                request(method, legacy, None, false).await;
+            let (status, deprecated, body) =
+                request(method, &successor_path(legacy), None, false).await;
+            assert_eq!(legacy_status, status, "{} {}", method, legacy);
+            assert_eq!(legacy_body, body, "{} {}", method, legacy);
+            assert_eq!(legacy_body["code"], 0, "{} {}", method, legacy);
+            assert!(legacy_deprecated);
+            assert!(!deprecated);
+            // 废弃别名即使请求 v1 结构也保持旧结构
+            let (_, _, legacy_v1) = request(method, legacy, None, true).await;
+            assert_eq!(legacy_v1["code"], 0, "{} {}", method, legacy);
+        }
+
+        // 已有的 /api/v1 接口默认保持 {"code","msg","data"}，Accept 携带 v1 媒体类型时为 {"data","error"}
+        let mut routes = vec![
+            ("GET", "/api/v1/health".to_string()),
+            ("POST", "/api/v1/health".to_string()),
+            ("GET", "/api/v1/info".to_string()),
+        ];
+        for template in ["oss2oss", "local2oss", "oss2local", "local2local"] {
+            routes.push((
+                "GET",
+                format!("/api/v1/task/template/transfer/{}", template),
+            ));
+        }
+        for (method, uri) in routes {
+            let (status, deprecated, legacy) = request(method, &uri, None, false).await;
+            let (v1_status, _, v1) = request(method, &uri, None, true).await;
+            assert_eq!(status, StatusCode::OK, "{} {}", method, uri);
+            assert_eq!(status, v1_status, "{} {}", method, uri);
+            assert!(!deprecated, "{} {}", method, uri);
+            assert_eq!(legacy["code"], 0, "{} {}", method, uri);
+            assert_eq!(legacy["msg"], "OK", "{} {}", method, uri);
+            assert!(legacy.get("error").is_none(), "{} {}", method, uri);
+            assert!(v1.get("code").is_none(), "{} {}", method, uri);
+            assert!(v1["error"].is_null(), "{} {}", method, uri);
+            assert_eq!(legacy["data"], v1["data"], "{} {}", method, uri);
+        }
+
+        // 错误响应默认为 {"code","message","details"}，v1 结构中整体作为 error
+        let invalid = r#"{"type":"transfer","source":"/tmp/a","target":"/tmp/b","attributes":{"paralellism":4}}"#;
+        let (status, _, legacy) =
+            request("POST", "/api/v1/task/validate", Some(invalid), false).await;
+        let (v1_status, _, v1) =
+            request("POST", "/api/v1/task/validate", Some(invalid), true).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(status, v1_status);
+        assert_eq!(legacy["code"], "invalid_request");
+        assert!(legacy.get("error").is_none());
+        assert!(v1["data"].is_null());
+        assert_eq!(v1["error"]["code"], legacy["code"]);
+        assert_eq!(v1["error"]["message"], legacy["message"]);
+    }
+
+    //cargo test httpserver::routers::root::test::test_server_info -- --nocapture
+    #[tokio::test]
+    async fn test_server_info() {
+        let (status, _, body) = request("GET", "/api/v1/info", None, false).await;
+        assert_eq!(status, StatusCode::OK);
+        let info = &body["data"];
+        assert!(info["config_reload"]["generation"].is_u64());
+        assert!(info["config_reload"]["last_reload_ok"].is_null());
+        assert!(info["self_stats_last_hour"]["samples"].is_u64());
+    }
+
+    //cargo test httpserver::routers::root::test::test_task_queue_group_by -- --nocapture
+    #[tokio::test]
+    async fn test_task_queue_group_by() {
+        let (status, resp) = json_request("GET", "/api/v1/queue", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(resp["data"]["dispatch_policy"], "fifo");
+        assert!(resp["data"].get("namespaces").is_none());
+
+        let (status, resp) = json_request("GET", "/api/v1/queue?group_by=namespace", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(resp["data"]["namespaces"].is_array());
+
+        let (status, resp) = json_request("GET", "/api/v1/queue?group_by=name", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(resp["code"], "invalid_request");
+    }
+
+    async fn json_request(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(match body {
+                Some(b) => Body::from(b.to_string()),
+                None => Body::empty(),
+            })
+            .unwrap();
+        let resp = router_root().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn local_task(task: Task, dir: &str) -> Value {
+        let mut task = serde_json::to_value(task).unwrap();
+        task["source"] = json!(format!("{}/source", dir));
+        task["target"] = json!(format!("{}/target", dir));
+        task
+    }
+
+    //cargo test httpserver::routers::root::test::test_task_error_status -- --nocapture
+    #[tokio::test]
+    async fn test_task_error_status() {
+        let db = open_test_rocksdb();
+        init_global_task_runtime().unwrap();
+        let dir = format!("/tmp/route_error_test/{}", uuid::Uuid::new_v4());
+        let transfer = local_task(Task::Transfer(TransferTask::default()), &dir);
+        let compare = local_task(Task::Compare(CompareTask::default()), &dir);
+        let (status, body) =
+            json_request("POST", "/api/v1/task/create", Some(transfer.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let transfer_id = body["data"]["task_id"].as_str().unwrap().to_string();
+        let (status, body) = json_request("POST", "/api/v1/task/create", Some(compare)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let compare_id = body["data"]["task_id"].as_str().unwrap().to_string();
+        let mut invalid = transfer.clone();
+        invalid["attributes"]["objects_per_batch"] = json!(0);
+
+        let missing = json!({"task_id": "route_error_missing"});
+        let cases = vec![
+            (
+                "POST",
+                "/api/v1/task/show".to_string(),
+                Some(missing.clone()),
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/status".to_string(),
+                Some(missing.clone()),
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/start".to_string(),
+                Some(missing.clone()),
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/stop".to_string(),
+                Some(missing.clone()),
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/analyze".to_string(),
+                Some(missing),
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "GET",
+                "/api/v1/task/route_error_missing/status".to_string(),
+                None,
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "GET",
+                "/api/v1/task/route_error_missing/progress".to_string(),
+                None,
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "GET",
+                "/api/v1/task/route_error_missing/completion".to_string(),
+                None,
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/route_error_missing/clone".to_string(),
+                None,
+                StatusCode::NOT_FOUND,
+                "task_not_found",
+            ),
+            (
+                "POST",
+                "/api/v1/task/stop".to_string(),
+                Some(json!({"task_id": transfer_id})),
+                StatusCode::CONFLICT,
+                "task_not_living",
+            ),
+            (
+                "POST",
+                "/api/v1/task/create".to_string(),
+                Some(transfer),
+                StatusCode::CONFLICT,
+                "task_already_exists",
+            ),
+            (
+                "POST",
+                format!("/api/v1/task/{}/clone", transfer_id),
+                None,
+                StatusCode::CONFLICT,
+                "task_already_exists",
+            ),
+            (
+                "POST",
+                "/api/v1/task/create".to_string(),
+                Some(invalid),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_task_fields",
+            ),
+            (
+                "POST",
+                "/api/v1/task/analyze".to_string(),
+                Some(json!({"task_id": compare_id})),
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+            ),
+            (
+                "GET",
+                format!("/api/v1/task/{}/completion", transfer_id),
+                None,
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+        ];
+        for (method, uri, body, status, code) in cases {
+            let (s, resp) = json_request(method, &uri, body).await;
+            assert_eq!(
+                (s, resp["code"].as_str()),
+                (status, Some(code)),
+                "{} {}",
+                method,
+                uri
+            );
+        }
+
+        // 运行中的任务再次启动返回冲突
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            transfer_id.clone(),
+            TransferTaskStatus {
+                task_id: transfer_id.clone(),
+                start_time: 0,
+                run_id: String::new(),
+                status: TransferTaskStatusType::Starting,
+            },
+        );
+        let (status, resp) = json_request(
+            "POST",
+            "/api/v1/task/start",
+            Some(json!({"task_id": transfer_id})),
+        )
+        .await;
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&transfer_id);
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(resp["code"], "task_already_living");
+
+        // 损坏的任务定义为服务端错误
+        let cf = db.cf_handle(CF_TASK).unwrap();
+        let corrupt_id = format!("route_error_corrupt_{}", uuid::Uuid::new_v4());
+        db.put_cf(&cf, &corrupt_id, b"\x00not json").unwrap();
+        let (status, resp) = json_request(
+            "POST",
+            "/api/v1/task/show",
+            Some(json!({"task_id": corrupt_id})),
         )
+        .await;
+        db.delete_cf(&cf, &corrupt_id).unwrap();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp["code"], "internal");
+    }
+
+    //cargo test httpserver::routers::root::test::test_task_body_limit -- --nocapture
+    #[tokio::test]
+    async fn test_task_body_limit() {
+        let body = vec![b' '; HttpConfig::max_body_size_default() + 1];
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/v1/task/create")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = router_root().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "payload_too_large");
+
+        // 拼写错误的字段不会被忽略
+        let task = r#"{"type":"transfer","source":"/tmp/a","target":"/tmp/b","attributes":{"paralellism":4}}"#;
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/v1/task/validate")
+            .header("content-type", "application/json")
+            .body(Body::from(task))
+            .unwrap();
+        let resp = router_root().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "invalid_request");
+
+        // 请求体、查询参数与路径参数解析失败同样返回 json 错误
+        for (method, uri, body) in [
+            ("POST", "/api/v1/task/start", "{\"id\":1}"),
+            (
+                "POST",
+                "/api/v1/task/stop?wait=maybe",
+                "{\"task_id\":\"1\"}",
+            ),
+            ("GET", "/api/v1/task/1/changes/abc/revert", ""),
+        ] {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let resp = router_root().oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "invalid_request", "{}", uri);
+        }
     }
 }
@@ -1,3 +1,4 @@
+pub(crate) mod service_analyze;
 mod service_mysql;
 mod service_redis;
 pub(crate) mod service_task;
@@ -0,0 +1,245 @@
+use super::service_task::service_show_task;
+use crate::{
+    commons::{inherit_request_context, AnalyzeProgress},
+    configure::get_config,
+    httpserver::module::{ApiError, RespTaskAnalyze},
+    tasks::{Task, TransferTask, GLOBAL_TASK_RUNTIME},
+};
+use anyhow::Result;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+type AnalyzeResult = std::result::Result<RespTaskAnalyze, ApiError>;
+
+// 进行中的统计，同一任务的后续请求等待同一次统计
+static ANALYZE_IN_FLIGHT: Lazy<DashMap<String, Arc<AnalyzeRun>>> = Lazy::new(DashMap::new);
+
+// 各任务最近一次完成的统计
+static ANALYZE_CACHE: Lazy<DashMap<String, RespTaskAnalyze>> = Lazy::new(DashMap::new);
+
+/// 一次统计及其等待方，最后一个等待方离开时取消统计
+struct AnalyzeRun {
+    progress: AnalyzeProgress,
+    waiters: Mutex<usize>,
+    done: watch::Sender<Option<AnalyzeResult>>,
+}
+
+impl AnalyzeRun {
+    fn new() -> Self {
+        Self {
+            progress: AnalyzeProgress::default(),
+            waiters: Mutex::new(0),
+            done: watch::channel(None).0,
+        }
+    }
+
+    // 已取消的统计不再接受等待方，返回 false
+    fn attach(&self) -> bool {
+        match self.waiters.lock() {
+            Ok(mut waiters) if !self.progress.is_cancelled() => {
+                *waiters += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn detach(&self) {
+        if let Ok(mut waiters) = self.waiters.lock() {
+            *waiters = waiters.saturating_sub(1);
+            if *waiters == 0 && self.done.borrow().is_none() {
+                self.progress.cancel();
+            }
+        }
+    }
+}
+
+/// 请求结束（含客户端断开时请求被丢弃）时离开统计
+struct AnalyzeWaiter(Arc<AnalyzeRun>);
+
+impl Drop for AnalyzeWaiter {
+    fn drop(&mut self) {
+        self.0.detach();
+    }
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// 统计源端对象大小分布，缓存有效期内直接返回缓存结果，超时返回已统计的部分
+pub async fn service_analyze_task(task_id: &str) -> Result<RespTaskAnalyze> {
+    let transfer = match service_show_task(task_id)? {
+        Task::Transfer(t) => t,
+        _ => {
+            return Err(
+                ApiError::InvalidRequest(format!("task {} not transfer task", task_id)).into(),
+            )
+        }
+    };
+    let config = get_config()?.task;
+    analyze_transfer(
+        transfer,
+        Duration::from_secs(config.analyze_timeout_secs),
+        Duration::from_secs(config.analyze_cache_secs),
+    )
+    .await
+}
+
+async fn analyze_transfer(
+    transfer: TransferTask,
+    timeout: Duration,
+    cache_ttl: Duration,
+) -> Result<RespTaskAnalyze> {
+    let task_id = transfer.task_id.clone();
+    if let Some(cached) = cached_analysis(&task_id, cache_ttl) {
+        return Ok(cached);
+    }
+    let waiter = attach_analysis(transfer)?;
+    let mut done = waiter.0.done.subscribe();
+    match tokio::time::timeout(timeout, wait_analysis(&mut done)).await {
+        Ok(Some(result)) => Ok(result?),
+        Ok(None) => Err(ApiError::Internal(format!("task {} analyze aborted", task_id)).into()),
+        Err(_) => Err(ApiError::AnalyzeTimeout {
+            task_id,
+            sizes: waiter.0.progress.sizes(),
+        }
+        .into()),
+    }
+}
+
+fn cached_analysis(task_id: &str, ttl: Duration) -> Option<RespTaskAnalyze> {
+    let cached = ANALYZE_CACHE.get(task_id)?.value().clone();
+    match now_secs().saturating_sub(cached.timestamp) < ttl.as_secs() {
+        true => Some(RespTaskAnalyze {
+            cached: true,
+            ..cached
+        }),
+        false => None,
+    }
+}
+
+/// 任务修改或删除后清除缓存的统计结果
+pub fn clear_task_analysis(task_id: &str) {
+    ANALYZE_CACHE.remove(task_id);
+}
+
+// 加入进行中的统计，不存在或已取消时在任务 runtime 上开始新的统计
+fn attach_analysis(transfer: TransferTask) -> Result<AnalyzeWaiter> {
+    let task_id = transfer.task_id.clone();
+    let runtime = GLOBAL_TASK_RUNTIME.get()?;
+    let mut entry = ANALYZE_IN_FLIGHT
+        .entry(task_id.clone())
+        .or_insert_with(|| Arc::new(AnalyzeRun::new()));
+    if entry.value().attach() {
+        return Ok(AnalyzeWaiter(entry.value().clone()));
+    }
+    let run = Arc::new(AnalyzeRun::new());
+    run.attach();
+    *entry.value_mut() = run.clone();
+    drop(entry);
+    // 新建的统计在首次 attach 后才启动，避免无等待方时被取消
+    let spawned = run.clone();
+    runtime.spawn(inherit_request_context(async move {
+        run_analysis(transfer, spawned).await;
+    }));
+    Ok(AnalyzeWaiter(run))
+}
+
+async fn run_analysis(transfer: TransferTask, run: Arc<AnalyzeRun>) {
+    let task_id = transfer.task_id.clone();
+    let result = match transfer.analyze(&run.progress).await {
+        Ok(()) if run.progress.is_cancelled() => Err(ApiError::Internal(format!(
+            "task {} analyze cancelled",
+            task_id
+        ))),
+        Ok(()) => {
+            let resp = RespTaskAnalyze {
+                task_id: task_id.clone(),
+                sizes: run.progress.sizes(),
+                timestamp: now_secs(),
+                cached: false,
+            };
+            ANALYZE_CACHE.insert(task_id.clone(), resp.clone());
+            Ok(resp)
+        }
+        Err(e) => Err(ApiError::from(e)),
+    };
+    if let Err(e) = &result {
+        log::warn!("task {} analyze: {}", task_id, e);
+    }
+    let _ = run.done.send(Some(result));
+    ANALYZE_IN_FLIGHT.remove_if(&task_id, |_, r| Arc::ptr_eq(r, &run));
+}
+
+async fn wait_analysis(done: &mut watch::Receiver<Option<AnalyzeResult>>) -> Option<AnalyzeResult> {
+    loop {
+        let result = done.borrow_and_update().clone();
+        if result.is_some() {
+            return result;
+        }
+        if done.changed().await.is_err() {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{analyze_transfer, AnalyzeRun, AnalyzeWaiter};
+    use crate::tasks::{init_global_task_runtime, ObjectStorage, TransferTask};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    //cargo test httpserver::service::service_analyze::test::test_analyze_cached -- --nocapture
+    #[tokio::test]
+    async fn test_analyze_cached() {
+        init_global_task_runtime().unwrap();
+        let dir = "/tmp/mario_analyze_test";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(format!("{}/source", dir)).unwrap();
+        for i in 0..10 {
+            std::fs::write(format!("{}/source/{}.txt", dir, i), vec![0u8; i * 100]).unwrap();
+        }
+        let mut transfer = TransferTask::default();
+        transfer.task_id = "analyze_cached".to_string();
+        transfer.source = ObjectStorage::Local(format!("{}/source", dir));
+        transfer.target = ObjectStorage::Local(format!("{}/target", dir));
+        transfer.attributes.meta_dir = format!("{}/meta", dir);
+
+        let timeout = Duration::from_secs(10);
+        let ttl = Duration::from_secs(300);
+        let first = analyze_transfer(transfer.clone(), timeout, ttl)
+            .await
+            .unwrap();
+        assert!(!first.cached);
+        assert!(!first.sizes.is_empty());
+        let second = analyze_transfer(transfer, timeout, ttl).await.unwrap();
+        assert!(second.cached);
+        assert_eq!(first.sizes, second.sizes);
+        assert_eq!(first.timestamp, second.timestamp);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    //cargo test httpserver::service::service_analyze::test::test_last_waiter_cancels -- --nocapture
+    #[test]
+    fn test_last_waiter_cancels() {
+        let run = Arc::new(AnalyzeRun::new());
+        assert!(run.attach());
+        assert!(run.attach());
+        let first = AnalyzeWaiter(run.clone());
+        let second = AnalyzeWaiter(run.clone());
+        drop(first);
+        assert!(!run.progress.is_cancelled());
+        drop(second);
+        assert!(run.progress.is_cancelled());
+        // 已取消的统计不再接受新的等待方
+        assert!(!run.attach());
+    }
+}
@@ -1,64 +1,856 @@
+use super::service_analyze::clear_task_analysis;
 use crate::{
-    commons::{json_to_struct, struct_to_json_string},
+    commons::{
+        inherit_request_context, json_changes_redacted, json_merge_patch, json_to_struct,
+        revert_json_changes, struct_to_json_string,
+    },
     configure::get_config,
-    httpserver::module::RespListTask,
-    resources::{get_checkpoint, get_task, CF_TASK, GLOBAL_ROCKSDB},
-    tasks::{gen_file_path, task_is_living, CheckPoint, Task, GLOBAL_TASK_RUNTIME},
+    httpserver::module::{
+        ApiError, EffectiveTaskState, ReqCheckpointReset, ReqCheckpointRollback, ReqTaskClone,
+        ReqTaskErrors, ReqTaskListFilter, ReqTaskRuns, ReqTaskSearch, RespCheckpointFlushAll,
+        RespCheckpointReset, RespCheckpointSummary, RespListTask, RespRunDefinition,
+        RespTaskBatchItem, RespTaskErrors, RespTaskProgress, RespTaskRuns, RespTaskStatus,
+        RespTaskStop, RespTaskSummary, RespTaskUnifiedStatus, RespTaskWait, TaskListMeta,
+        TaskListStatus, TaskStopState,
+    },
+    resources::{
+        commit_task_writes, delete_checkpoint, delete_task_all, delete_task_status,
+        find_tasks_by_name, get_checkpoint, get_checkpoint_history, get_task, get_task_status,
+        list_checkpoint_history, living_tasks, restore_checkpoint, save_checkpoint_to_cf,
+        task_update_writes, ResourceError, CF_TASK, GLOBAL_ROCKSDB,
+    },
+    server::{clear_task_notified, remove_task_metrics, task_run_counters},
+    tasks::{
+        check_idempotency, clear_start_skipped, clear_task_file_positions,
+        completion_marker_exists, definition_redacted, dequeue_task, diff_definition, enqueue_task,
+        error_record_files, export_definition, flush_task_checkpoint, forget_task_state,
+        gen_file_path, get_completion_marker, get_idempotency_record,
+        get_live_transfer_task_status, get_run_definition, get_start_skipped, get_task_change,
+        idempotency_body_hash, list_task_changes, list_task_runs, lock_task_names,
+        mark_living_task_paused, name_conflicts, percent_summary, queued_task_status,
+        record_start_skipped, record_task_change, redacted_definition, redacted_task,
+        release_task_slot, remove_expired_idempotency_records, remove_idle_task_start_lock,
+        remove_listing_files, restore_task_file_positions, save_idempotency_record,
+        server_is_draining, spawn_task_execute, task_batch_progress, task_file_positions,
+        task_is_executing, task_is_living, task_min_file_position, task_queue_position,
+        task_run_exited, task_start_lock, try_reserve_task_slot, write_error_archive, CheckPoint,
+        CheckpointExport, CheckpointFlush, CompletionMarker, ErrorRecordIter, IdempotencyCheck,
+        IdempotencyRecord, ObjectStorage, StartSkipReason, Status, Task, TaskChangeEntry,
+        TaskDefaultParameters, TaskErrorRecord, TaskEventSubscription, TaskExport,
+        TaskExportHeader, TaskImportResult, TaskRun, TaskStartOutcome, TaskStatus, TaskType,
+        TransferTaskStatus, TransferTaskStatusType, CHECKPOINT_EXPORT_VERSION,
+        GLOBAL_TASK_PAUSE_MAP, GLOBAL_TASK_RUNTIME, TASK_EXPORT_SCHEMA_VERSION,
+    },
 };
-use anyhow::anyhow;
-use anyhow::Result;
-use rocksdb::IteratorMode;
-use std::{collections::BTreeMap, fs};
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use rocksdb::{Direction, IteratorMode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc, Semaphore};
+
+// 批量启停同时处理的任务数上限
+const TASK_BATCH_CONCURRENCY: usize = 8;
+// 流式任务列表在内存中缓存的片段数
+const TASK_LIST_STREAM_BUFFER: usize = 64;
+// 强制删除时等待任务停止的最长时间
+const FORCE_REMOVE_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+// 停止时等待任务退出的缺省与最长时间
+pub const STOP_WAIT_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const STOP_WAIT_MAX_TIMEOUT: Duration = Duration::from_secs(300);
+const TASK_STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+// 等待任务停止的缺省与最长时间
+pub const TASK_WAIT_DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const TASK_WAIT_MAX_TIMEOUT: Duration = Duration::from_secs(3600);
+// 幂等键的最大长度
+const IDEMPOTENCY_KEY_MAX_LEN: usize = 255;
+// 带幂等键的创建请求串行处理
+static IDEMPOTENT_CREATE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+// 错误记录分页的缺省与最大条数
+const TASK_ERRORS_DEFAULT_LIMIT: usize = 100;
+const TASK_ERRORS_MAX_LIMIT: usize = 1000;
+// 运行记录分页的缺省与最大条数
+const TASK_RUNS_DEFAULT_LIMIT: usize = 20;
+const TASK_RUNS_MAX_LIMIT: usize = 500;
+// 错误记录打包下载时每次写出的字节数
+const TASK_ERRORS_ARCHIVE_CHUNK: usize = 64 * 1024;
+
+fn cf_not_exist() -> anyhow::Error {
+    ApiError::StorageUnavailable("column family not exist".to_string()).into()
+}
 
 pub fn service_task_create(task: &mut Task) -> Result<i64> {
+    let _guard = lock_task_names();
+    check_task_name_unique(&task.name(), "")?;
     task.create()
 }
 
-pub fn service_remove_task(task_ids: Vec<String>) -> Result<()> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
+// 开启唯一名称时，名称已被其他任务使用返回冲突
+fn check_task_name_unique(name: &str, task_id: &str) -> Result<()> {
+    if !get_config()?.task.unique_task_names {
+        return Ok(());
+    }
+    let task_ids = name_conflicts(&find_tasks_by_name(name)?, task_id);
+    match task_ids.is_empty() {
+        true => Ok(()),
+        false => Err(ApiError::TaskNameConflict {
+            name: name.to_string(),
+            task_ids,
+        }
+        .into()),
+    }
+}
+
+/// 经名称索引查找任务，同名任务有多个时返回冲突
+pub fn service_show_task_by_name(name: &str) -> Result<Task> {
+    let mut task_ids = find_tasks_by_name(name)?;
+    match task_ids.len() {
+        0 => Err(ApiError::NotFound(format!("task named {} not exist", name)).into()),
+        1 => service_show_task(&task_ids.remove(0)),
+        _ => Err(ApiError::TaskNameConflict {
+            name: name.to_string(),
+            task_ids,
+        }
+        .into()),
+    }
+}
+
+/// 带幂等键创建任务，返回 task_id 及是否为重试请求
+/// 有效期内同一请求体的重试返回原任务，不同请求体返回冲突
+pub fn service_task_create_idempotent(task: &mut Task, key: &str) -> Result<(String, bool)> {
+    if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_LEN {
+        return Err(ApiError::InvalidRequest(format!(
+            "idempotency key length must be between 1 and {}",
+            IDEMPOTENCY_KEY_MAX_LEN
+        ))
+        .into());
+    }
+    let ttl = get_config()?.task.idempotency_key_ttl_secs;
+    let body_hash = idempotency_body_hash(task)?;
+    // 同一键的并发重试串行，避免都未命中而重复创建
+    let _guard = match IDEMPOTENT_CREATE_LOCK.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    match check_idempotency(get_idempotency_record(key)?.as_ref(), &body_hash, now, ttl) {
+        IdempotencyCheck::Replay(task_id) => return Ok((task_id, true)),
+        IdempotencyCheck::Conflict(task_id) => {
+            return Err(ApiError::IdempotencyKeyConflict {
+                key: key.to_string(),
+                task_id,
+            }
+            .into())
+        }
+        IdempotencyCheck::New => {}
+    }
+    let task_id = service_task_create(task)?.to_string();
+    let record = IdempotencyRecord {
+        task_id: task_id.clone(),
+        body_hash,
+        created_at: now,
+    };
+    save_idempotency_record(key, &record)?;
+    if let Err(e) = remove_expired_idempotency_records(now, ttl) {
+        log::warn!("remove expired idempotency keys error: {}", e);
+    }
+    Ok((task_id, false))
+}
+
+/// 逐个删除任务，单个任务失败不影响其余任务，结果顺序与请求一致
+/// 运行中的任务需指定 force，先停止再删除
+pub async fn service_remove_tasks(task_ids: Vec<String>, force: bool) -> Vec<RespTaskBatchItem> {
+    remove_tasks_with(task_ids, force, task_exists, purge_task).await
+}
+
+async fn remove_tasks_with(
+    task_ids: Vec<String>,
+    force: bool,
+    exists: fn(&str) -> Result<bool>,
+    purge: fn(&str) -> Result<()>,
+) -> Vec<RespTaskBatchItem> {
+    let mut results = vec![];
+    for task_id in task_ids {
+        let result = match exists(&task_id) {
+            Ok(exists) => remove_task(&task_id, exists, force, purge).await,
+            Err(e) => Err(e),
+        };
+        results.push(batch_item(task_id, result.err().map(ApiError::from)));
+    }
+    results
+}
+
+async fn remove_task(
+    task_id: &str,
+    exists: bool,
+    force: bool,
+    purge: fn(&str) -> Result<()>,
+) -> Result<()> {
+    if check_task_removable(task_id, exists, force)? {
+        // 停止请求与任务自行结束之间存在竞争，任务已结束时忽略停止失败
+        if let Err(e) = service_stop_task(task_id).await {
+            log::warn!("task {} stop before remove: {}", task_id, e);
+        }
+        wait_task_stopped(task_id).await?;
+    }
+    purge(task_id)
+}
+
+/// 删除前检查，任务不存在返回 404，运行中且未指定 force 返回 409，返回是否需要先停止任务
+fn check_task_removable(task_id: &str, exists: bool, force: bool) -> Result<bool> {
+    if !exists {
+        return Err(ApiError::TaskNotFound {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    match (task_is_living(task_id), force) {
+        (false, _) => Ok(false),
+        (true, true) => Ok(true),
+        (true, false) => Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into()),
+    }
+}
+
+async fn wait_task_stopped(task_id: &str) -> Result<()> {
+    match wait_until(FORCE_REMOVE_STOP_TIMEOUT, || !task_is_living(task_id)).await {
+        true => Ok(()),
+        false => Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into()),
+    }
+}
+
+// 轮询直至条件满足，超时返回 false
+async fn wait_until(timeout: Duration, done: impl Fn() -> bool) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while !done() {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(TASK_STOP_POLL_INTERVAL).await;
+    }
+    true
+}
+
+fn task_exists(task_id: &str) -> Result<bool> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(cf_not_exist()),
     };
+    Ok(db.get_cf(&cf, task_id)?.is_some())
+}
 
-    for id in task_ids {
-        let global_meta_dir = get_config()?.meta_dir;
-        let meta_dir = gen_file_path(&global_meta_dir, id.as_str(), "");
-        GLOBAL_ROCKSDB.delete_cf(&cf, id)?;
-        fs::remove_dir(meta_dir)?
+// 任务在 rocksdb 中的全部记录与名称索引在同一批次中删除，避免只删除部分记录
+fn purge_task(task_id: &str) -> Result<()> {
+    let _guard = lock_task_names();
+    delete_task_all(task_id)?;
+    remove_task_metrics(task_id);
+    clear_task_notified(task_id);
+    clear_task_analysis(task_id);
+    dequeue_task(task_id)?;
+    forget_task_state(task_id);
+    // 任务执行后 meta_dir 中存在对象列表等文件
+    let meta_dir = gen_file_path(&get_config()?.meta_dir, task_id, "");
+    remove_meta_dir(&meta_dir)
+}
+
+// meta_dir 不存在视为已删除
+fn remove_meta_dir(meta_dir: &str) -> Result<()> {
+    match fs::remove_dir_all(meta_dir) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn batch_item(task_id: String, error: Option<ApiError>) -> RespTaskBatchItem {
+    RespTaskBatchItem {
+        task_id,
+        ok: error.is_none(),
+        code: error.as_ref().map(|e| e.code().to_string()),
+        error: error.map(|e| e.to_string()),
+        result: None,
     }
+}
 
+/// 以源任务定义创建新任务，新任务使用新的 task_id 与 meta_dir，不继承 checkpoint 与运行状态
+pub fn service_clone_task(task_id: &str, overrides: &ReqTaskClone) -> Result<(i64, Task)> {
+    let mut task = service_show_task(task_id)?;
+    apply_clone_overrides(&mut task, overrides)?;
+    let id = service_task_create(&mut task)?;
+    Ok((id, task))
+}
+
+fn override_prefix(storage: &mut ObjectStorage, prefix: &str, field: &str) -> Result<()> {
+    match storage {
+        ObjectStorage::OSS(oss) => {
+            oss.prefix = match prefix.is_empty() {
+                true => None,
+                false => Some(prefix.to_string()),
+            };
+            Ok(())
+        }
+        ObjectStorage::Local(_) => {
+            Err(ApiError::InvalidRequest(format!("{} only applies to oss storage", field)).into())
+        }
+    }
+}
+
+fn apply_clone_overrides(task: &mut Task, overrides: &ReqTaskClone) -> Result<()> {
+    let (name, source, target, include, exclude, start_from_checkpoint) = match task {
+        Task::Transfer(t) => (
+            &mut t.name,
+            &mut t.source,
+            &mut t.target,
+            &mut t.attributes.include,
+            &mut t.attributes.exclude,
+            &mut t.attributes.start_from_checkpoint,
+        ),
+        Task::Compare(c) => (
+            &mut c.name,
+            &mut c.source,
+            &mut c.target,
+            &mut c.attributes.include,
+            &mut c.attributes.exclude,
+            &mut c.attributes.start_from_checkpoint,
+        ),
+    };
+    if let Some(n) = &overrides.name {
+        *name = n.clone();
+    }
+    if let Some(p) = &overrides.source_prefix {
+        override_prefix(source, p, "source_prefix")?;
+    }
+    if let Some(p) = &overrides.target_prefix {
+        override_prefix(target, p, "target_prefix")?;
+    }
+    if let Some(i) = &overrides.include {
+        *include = Some(i.clone());
+    }
+    if let Some(e) = &overrides.exclude {
+        *exclude = Some(e.clone());
+    }
+    // 新任务没有 checkpoint，从头开始执行
+    *start_from_checkpoint = false;
     Ok(())
 }
 
-pub fn service_update_task(task_id: &str, task: &mut Task) -> Result<()> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
-        Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+/// 更新时 meta_dir 的处理方式
+#[derive(Debug, Clone, PartialEq)]
+enum MetaDirUpdate {
+    // 沿用原 meta_dir，其中的 checkpoint 继续有效
+    Keep(String),
+    // 按全局配置重新生成
+    Generate,
+    // 重新生成并清除原 checkpoint
+    Reset,
+}
+
+/// 更新前检查：运行中的任务不允许修改，任务类型不允许改变
+/// 已有 checkpoint 时沿用原 meta_dir，除非指定 reset_meta
+fn check_task_update(
+    task_id: &str,
+    living: bool,
+    old: Option<&Task>,
+    new: &Task,
+    has_checkpoint: bool,
+    reset_meta: bool,
+) -> Result<MetaDirUpdate> {
+    if living {
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    let old = match old {
+        Some(old) => old,
+        None => return Ok(MetaDirUpdate::Generate),
+    };
+    if old.task_type() != new.task_type() {
+        return Err(ApiError::InvalidRequest(format!(
+            "task {} type {:?} can not change to {:?}",
+            task_id,
+            old.task_type(),
+            new.task_type()
+        ))
+        .into());
+    }
+    match (has_checkpoint, reset_meta) {
+        (true, false) => Ok(MetaDirUpdate::Keep(old.meta_dir())),
+        (true, true) => Ok(MetaDirUpdate::Reset),
+        (false, _) => Ok(MetaDirUpdate::Generate),
+    }
+}
+
+pub fn service_update_task(
+    task_id: &str,
+    task: &mut Task,
+    actor: &str,
+    reset_meta: bool,
+) -> Result<()> {
+    let _guard = lock_task_names();
+    // 任务不存在时视为新建，不记录变更
+    let old = get_task(task_id).ok();
+    let has_checkpoint = old.is_some() && get_checkpoint(task_id).is_ok();
+    let meta_update = check_task_update(
+        task_id,
+        task_is_living(task_id),
+        old.as_ref(),
+        task,
+        has_checkpoint,
+        reset_meta,
+    )?;
+    task.validate_fields()?;
+    task.validate_consistency().into_result()?;
+    let old_name = old.as_ref().map(|t| t.name());
+    if old_name.as_ref() != Some(&task.name()) {
+        check_task_name_unique(&task.name(), task_id)?;
+    }
+    let meta_dir = match &meta_update {
+        MetaDirUpdate::Keep(meta_dir) => meta_dir.clone(),
+        _ => gen_file_path(&get_config()?.meta_dir, task_id, ""),
     };
-    let global_meta_dir = get_config()?.meta_dir;
-    let meta_dir = gen_file_path(&global_meta_dir, task_id, "");
     task.set_task_id(task_id);
     task.set_meta_dir(&meta_dir);
     let task_json = struct_to_json_string(task)?;
-    GLOBAL_ROCKSDB.put_cf(&cf, task_id.to_string().as_bytes(), task_json.as_bytes())?;
+    commit_task_writes(&task_update_writes(
+        task_id,
+        &task_json,
+        old_name.as_deref(),
+        &task.name(),
+    ))?;
+    if meta_update == MetaDirUpdate::Reset {
+        delete_checkpoint(task_id)?;
+        clear_task_file_positions(task_id);
+        log::info!("task {} meta_dir reset to {}", task_id, meta_dir);
+    }
+    clear_task_analysis(task_id);
+    if let Some(old) = old {
+        record_task_change(task_id, actor, &old, task)?;
+    }
     Ok(())
 }
 
-pub fn service_start_task(task_id: &str) -> Result<()> {
-    let task = get_task(task_id)?;
+// 任务类型、task_id 与 meta_dir 由服务端维护，不允许通过 patch 修改
+const IMMUTABLE_TASK_FIELDS: [&str; 3] = ["/type", "/task_id", "/attributes/meta_dir"];
+
+/// 以 json merge patch 修改任务定义，返回修改后的任务，不写入存储
+fn patch_task_definition(current: &Task, patch: &serde_json::Value) -> Result<Task> {
+    if !patch.is_object() {
+        return Err(ApiError::InvalidRequest("patch must be a json object".to_string()).into());
+    }
+    let current = serde_json::to_value(current)?;
+    let mut patched = current.clone();
+    json_merge_patch(&mut patched, patch);
+    for field in IMMUTABLE_TASK_FIELDS {
+        if current.pointer(field) != patched.pointer(field) {
+            return Err(ApiError::InvalidRequest(format!("{} is immutable", field)).into());
+        }
+    }
+    serde_json::from_value::<Task>(patched)
+        .map_err(|e| ApiError::InvalidRequest(format!("invalid task definition: {}", e)).into())
+}
+
+/// 部分修改任务定义，未出现在 patch 中的字段保持不变，运行中的任务不允许修改
+pub fn service_patch_task(task_id: &str, patch: &serde_json::Value, actor: &str) -> Result<Task> {
     if task_is_living(task_id) {
-        return Err(anyhow!("task {} is living", task_id));
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
     }
-    GLOBAL_TASK_RUNTIME.spawn(async move { task.execute().await });
-    // 检查任务生存状态
-    Ok(())
+    let mut task = patch_task_definition(&service_show_task(task_id)?, patch)?;
+    service_update_task(task_id, &mut task, actor, false)?;
+    Ok(task)
+}
+
+/// 清除任务执行进度，下次启动时重新生成对象列表并从头执行，运行中的任务不允许重置
+pub fn service_reset_checkpoint(
+    task_id: &str,
+    req: &ReqCheckpointReset,
+) -> Result<RespCheckpointReset> {
+    let task = service_show_task(task_id)?;
+    if task_is_living(task_id) {
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    let checkpoint_removed = delete_checkpoint(task_id)?;
+    // 停止状态不再对应执行进度，重置后视为未执行
+    delete_task_status(task_id)?;
+    let file_positions_removed = clear_task_file_positions(task_id);
+    let files_removed = remove_listing_files(&task.meta_dir(), req.hard)?;
+    log::info!(
+        "task {} checkpoint reset, hard: {}, files removed: {}",
+        task_id,
+        req.hard,
+        files_removed.len()
+    );
+    Ok(RespCheckpointReset {
+        task_id: task_id.to_string(),
+        checkpoint_removed,
+        file_positions_removed,
+        files_removed,
+    })
+}
+
+/// 导出 checkpoint 与内存中的列表文件位置，任务运行中时为导出时刻的快照
+pub fn service_export_checkpoint(task_id: &str) -> Result<CheckpointExport> {
+    service_show_task(task_id)?;
+    // 仅 checkpoint 不存在时为 404，损坏或读取失败按原因返回
+    let checkpoint = match get_checkpoint(task_id) {
+        Ok(c) => c,
+        Err(ResourceError::KeyNotFound { .. }) => {
+            return Err(ApiError::NotFound(format!("task {} checkpoint not exist", task_id)).into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(CheckpointExport {
+        version: CHECKPOINT_EXPORT_VERSION,
+        checkpoint,
+        file_positions: task_file_positions(task_id),
+    })
+}
+
+/// 以任务当前的列表文件校验后写入导入的 checkpoint，运行中的任务不允许导入
+/// 与启动共用启动锁，避免校验通过后任务被并发启动
+pub async fn service_import_checkpoint(
+    task_id: &str,
+    export: CheckpointExport,
+) -> Result<CheckPoint> {
+    let task = service_show_task(task_id)?;
+    let lock = task_start_lock(task_id);
+    let _guard = lock.lock().await;
+    if task_is_living(task_id) {
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    let export = export
+        .rebase(task_id, &task.meta_dir())
+        .map_err(|problems| {
+            ApiError::InvalidRequest(format!("checkpoint invalid: {}", problems.join(" | ")))
+        })?;
+    let mut checkpoint = export.checkpoint;
+    save_checkpoint_to_cf(&mut checkpoint)?;
+    restore_task_file_positions(task_id, export.file_positions);
+    log::info!(
+        "task {} checkpoint imported at {:?}",
+        task_id,
+        checkpoint.executing_file_position
+    );
+    Ok(checkpoint)
+}
+
+/// checkpoint 历史，由新到旧
+pub fn service_checkpoint_history(task_id: &str) -> Result<Vec<CheckPoint>> {
+    service_show_task(task_id)?;
+    Ok(list_checkpoint_history(task_id)?)
 }
 
-pub fn service_stop_task(task_id: &str) -> Result<()> {
+/// 以历史中的 checkpoint 覆盖当前 checkpoint，按当前列表文件校验，运行中的任务不允许回滚
+/// 内存中的列表文件位置可能领先于回滚后的位置，一并清除
+pub async fn service_rollback_checkpoint(
+    task_id: &str,
+    req: &ReqCheckpointRollback,
+) -> Result<CheckPoint> {
+    let task = service_show_task(task_id)?;
+    let lock = task_start_lock(task_id);
+    let _guard = lock.lock().await;
+    if task_is_living(task_id) {
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    let checkpoint = match get_checkpoint_history(task_id, i128::from(req.timestamp))? {
+        Some(c) => c,
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "task {} checkpoint at {} not exist",
+                task_id, req.timestamp
+            ))
+            .into())
+        }
+    };
+    let export = CheckpointExport {
+        version: CHECKPOINT_EXPORT_VERSION,
+        checkpoint,
+        file_positions: BTreeMap::new(),
+    }
+    .rebase(task_id, &task.meta_dir())
+    .map_err(|problems| {
+        ApiError::InvalidRequest(format!("checkpoint invalid: {}", problems.join(" | ")))
+    })?;
+    restore_checkpoint(&export.checkpoint)?;
+    clear_task_file_positions(task_id);
+    log::info!(
+        "task {} checkpoint rolled back to {} at {:?}",
+        task_id,
+        req.timestamp,
+        export.checkpoint.executing_file_position
+    );
+    Ok(export.checkpoint)
+}
+
+/// 立即写入运行中任务的 checkpoint，与周期快照共用写入锁
+pub async fn service_flush_checkpoint(task_id: &str) -> Result<CheckpointFlush> {
+    service_show_task(task_id)?;
     if !task_is_living(task_id) {
-        return Err(anyhow!("task not living"));
+        return Err(ApiError::TaskNotLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    flush_task_checkpoint(task_id).await
+}
+
+/// 逐个写入全部运行中任务的 checkpoint，单个任务失败不影响其余任务
+pub async fn service_flush_all_checkpoints() -> Result<RespCheckpointFlushAll> {
+    let mut resp = RespCheckpointFlushAll {
+        flushed: vec![],
+        failed: vec![],
+    };
+    for status in living_tasks()? {
+        match flush_task_checkpoint(&status.task_id).await {
+            Ok(flush) => resp.flushed.push(flush),
+            Err(e) => resp
+                .failed
+                .push(batch_item(status.task_id, Some(ApiError::from(e)))),
+        }
+    }
+    Ok(resp)
+}
+
+pub fn service_task_changes(task_id: &str) -> Result<Vec<TaskChangeEntry>> {
+    list_task_changes(task_id)
+}
+
+/// 反向应用第 seq 次修改，经由正常更新流程写入并记录为新的变更
+pub fn service_revert_task_change(task_id: &str, seq: u64, actor: &str) -> Result<Task> {
+    let entry = match get_task_change(task_id, seq)? {
+        Some(entry) => entry,
+        None => {
+            return Err(
+                ApiError::NotFound(format!("task {} change {} not exist", task_id, seq)).into(),
+            )
+        }
+    };
+    if json_changes_redacted(&entry.changes) {
+        return Err(ApiError::InvalidRequest(format!(
+            "task {} change {} contains credential fields, revert manually",
+            task_id, seq
+        ))
+        .into());
+    }
+    let mut current = serde_json::to_value(service_show_task(task_id)?)?;
+    revert_json_changes(&mut current, &entry.changes)?;
+    let mut task = serde_json::from_value::<Task>(current)?;
+    service_update_task(task_id, &mut task, actor, false)?;
+    Ok(task)
+}
+
+/// 完成启动准备后创建执行协程，返回本次运行的 run_id，达到并发上限时加入队列并返回队列位置
+/// 同一任务的启动请求经启动锁串行，避免并发请求都通过存活检查而重复启动
+pub async fn service_start_task(task_id: &str) -> Result<TaskStartOutcome> {
+    let task = service_show_task(task_id)?;
+    start_task_with(task_id, task, spawn_task_execute).await
+}
+
+// 持任务启动锁完成存活检查、启动准备与执行协程的创建，spawn 返回前任务须已登记为活动状态
+async fn start_task_with(
+    task_id: &str,
+    task: Task,
+    spawn: fn(Task) -> Result<String>,
+) -> Result<TaskStartOutcome> {
+    let lock = task_start_lock(task_id);
+    let outcome = {
+        let _guard = lock.lock().await;
+        start_task_locked(task_id, task, spawn).await
+    };
+    drop(lock);
+    remove_idle_task_start_lock(task_id);
+    outcome
+}
+
+async fn start_task_locked(
+    task_id: &str,
+    task: Task,
+    spawn: fn(Task) -> Result<String>,
+) -> Result<TaskStartOutcome> {
+    if server_is_draining() {
+        record_start_skipped(task_id, StartSkipReason::Draining);
+        return Err(ApiError::ServerDraining.into());
+    }
+    // 排队中的任务视为已在运行；比对任务不登记活动状态，执行协程未退出即视为运行中
+    let compare_executing = matches!(task, Task::Compare(_)) && task_is_executing(task_id);
+    if task_is_living(task_id) || compare_executing || task_queue_position(task_id).is_some() {
+        record_start_skipped(task_id, StartSkipReason::AlreadyLiving);
+        return Err(ApiError::TaskAlreadyLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    let reserved = try_reserve_task_slot(task_id, &task.namespace());
+    // 入队前同样完成启动准备，存储不可用的任务不进入队列
+    if let Err(e) = task.setup().await {
+        if reserved {
+            release_task_slot(task_id);
+        }
+        return Err(e.into());
+    }
+    clear_start_skipped(task_id);
+    match reserved {
+        true => match spawn(task) {
+            Ok(run_id) => Ok(TaskStartOutcome::Started { run_id }),
+            Err(e) => {
+                release_task_slot(task_id);
+                Err(e)
+            }
+        },
+        false => {
+            let position = enqueue_task(task_id, &task.namespace())?;
+            record_start_skipped(task_id, StartSkipReason::ConcurrencyLimit);
+            Ok(TaskStartOutcome::Queued { position })
+        }
+    }
+}
+
+/// 停止任务并等待执行协程退出，超时返回 stopping 状态
+pub async fn service_stop_task_wait(task_id: &str, timeout: Duration) -> Result<RespTaskStop> {
+    service_show_task(task_id)?;
+    // 排队中的任务出队即停止
+    if dequeue_task(task_id)? {
+        return Ok(RespTaskStop {
+            task_id: task_id.to_string(),
+            state: TaskStopState::Stopped,
+            run_id: "".to_string(),
+            status: persisted_status(task_id),
+            duration_secs: 0,
+        });
+    }
+    stop_task_with(task_id, timeout, stop_task_locked, persisted_status).await
+}
+
+/// 批量停止并等待各任务退出：先停止全部任务，再在同一截止时间内并发等待，
+/// 避免等待占用批量操作的并发名额而推迟后续任务的停止
+pub async fn service_stop_tasks_wait(
+    task_ids: Vec<String>,
+    timeout: Duration,
+) -> Vec<RespTaskBatchItem> {
+    let started = tokio::time::Instant::now();
+    let deadline = started + timeout.min(STOP_WAIT_MAX_TIMEOUT);
+    let mut results = service_batch_tasks(task_ids, |id| async move {
+        service_stop_task_wait(&id, Duration::ZERO).await
+    })
+    .await;
+    let stopping = serde_json::json!(TaskStopState::Stopping);
+    let waits = results
+        .iter_mut()
+        .filter(|item| matches!(&item.result, Some(r) if r["state"] == stopping))
+        .map(|item| async move {
+            let task_id = item.task_id.clone();
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if !wait_until(remaining, || task_run_exited(&task_id)).await {
+                return;
+            }
+            if let Some(r) = item.result.as_mut() {
+                let duration = r["duration_secs"].as_u64().unwrap_or(0);
+                r["state"] = serde_json::json!(TaskStopState::Stopped);
+                r["status"] = serde_json::json!(persisted_status(&task_id));
+                r["duration_secs"] = serde_json::json!(duration + started.elapsed().as_secs());
+            }
+        });
+    futures::future::join_all(waits).await;
+    results
+}
+
+fn persisted_status(task_id: &str) -> Option<Status> {
+    get_task_status(task_id).ok().map(|s| s.status)
+}
+
+async fn stop_task_with(
+    task_id: &str,
+    timeout: Duration,
+    stop: fn(&str) -> Result<()>,
+    final_status: fn(&str) -> Option<Status>,
+) -> Result<RespTaskStop> {
+    // 只在存活检查与停止期间持启动锁，执行协程退出时同样需要该锁
+    let lock = task_start_lock(task_id);
+    let living = {
+        let _guard = lock.lock().await;
+        let living = match get_live_transfer_task_status(task_id) {
+            Ok(s) => s,
+            Err(_) => {
+                return Err(ApiError::TaskNotLiving {
+                    task_id: task_id.to_string(),
+                }
+                .into())
+            }
+        };
+        stop(task_id)?;
+        living
+    };
+    drop(lock);
+    remove_idle_task_start_lock(task_id);
+    let exited = wait_until(timeout.min(STOP_WAIT_MAX_TIMEOUT), || {
+        task_run_exited(task_id)
+    })
+    .await;
+    let (state, status) = match exited {
+        true => (TaskStopState::Stopped, final_status(task_id)),
+        false => (TaskStopState::Stopping, None),
+    };
+    let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    Ok(RespTaskStop {
+        task_id: task_id.to_string(),
+        state,
+        run_id: living.run_id,
+        status,
+        duration_secs: now.saturating_sub(living.start_time),
+    })
+}
+
+/// 持任务启动锁停止任务，与并发的启动请求串行
+pub async fn service_stop_task(task_id: &str) -> Result<()> {
+    let lock = task_start_lock(task_id);
+    let result = {
+        let _guard = lock.lock().await;
+        stop_task_locked(task_id)
+    };
+    drop(lock);
+    remove_idle_task_start_lock(task_id);
+    result
+}
+
+fn stop_task_locked(task_id: &str) -> Result<()> {
+    // 任务不存在返回 404，而非未运行
+    let task = service_show_task(task_id)?;
+    if dequeue_task(task_id)? {
+        return Ok(());
+    }
+    if !task_is_living(task_id) {
+        return Err(ApiError::TaskNotLiving {
+            task_id: task_id.to_string(),
+        }
+        .into());
     }
-    let task = get_task(task_id)?;
     task.stop()
     // return match task_is_living(task_id) {
     //     true => match GLOBAL_TASK_STOP_MARK_MAP.get_mut(task_id) {
@@ -74,53 +866,1537 @@ pub fn service_stop_task(task_id: &str) -> Result<()> {
     // };
 }
 
-pub async fn service_analyze_task(task_id: &str) -> Result<BTreeMap<String, i128>> {
-    let task = service_show_task(task_id)?;
-    match task {
-        Task::Transfer(t) => {
-            let r = t.gen_transfer_actions().analyze_source().await?;
-            Ok(r)
+/// 暂停运行中的任务，执行协程在处理完当前对象后等待恢复
+pub fn service_pause_task(task_id: &str) -> Result<()> {
+    let pause = match GLOBAL_TASK_PAUSE_MAP.get(task_id) {
+        Some(kv) if task_is_living(task_id) => kv.value().clone(),
+        _ => {
+            return Err(ApiError::TaskNotLiving {
+                task_id: task_id.to_string(),
+            }
+            .into())
+        }
+    };
+    if !pause.pause() {
+        return Err(ApiError::TaskAlreadyPaused {
+            task_id: task_id.to_string(),
+        }
+        .into());
+    }
+    mark_living_task_paused(task_id, true);
+    Ok(())
+}
+
+/// 恢复已暂停的任务
+pub fn service_resume_task(task_id: &str) -> Result<()> {
+    let pause = match GLOBAL_TASK_PAUSE_MAP.get(task_id) {
+        Some(kv) if task_is_living(task_id) => kv.value().clone(),
+        _ => {
+            return Err(ApiError::TaskNotLiving {
+                task_id: task_id.to_string(),
+            }
+            .into())
+        }
+    };
+    if !pause.resume() {
+        return Err(ApiError::TaskNotPaused {
+            task_id: task_id.to_string(),
         }
-        _ => Err(anyhow!("task not transfer task")),
+        .into());
     }
+    mark_living_task_paused(task_id, false);
+    Ok(())
 }
 
+/// 在任务 runtime 上并发执行批量操作，单个任务失败不影响其余任务，结果顺序与请求一致
+/// 操作的返回值不为 null 时写入对应条目的 result
+pub async fn service_batch_tasks<F, Fut, T>(task_ids: Vec<String>, op: F) -> Vec<RespTaskBatchItem>
+where
+    F: Fn(String) -> Fut + Copy + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(TASK_BATCH_CONCURRENCY));
+    let handles = task_ids
+        .into_iter()
+        .map(|task_id| {
+            let semaphore = semaphore.clone();
+            let id = task_id.clone();
+            let handle = GLOBAL_TASK_RUNTIME.spawn(inherit_request_context(async move {
+                let _permit = semaphore.acquire_owned().await;
+                op(id).await
+            }));
+            (task_id, handle)
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = vec![];
+    for (task_id, handle) in handles {
+        let handle = match handle {
+            Ok(h) => h,
+            Err(e) => {
+                results.push(batch_item(task_id, Some(ApiError::from(e))));
+                continue;
+            }
+        };
+        let (value, error) = match handle.await {
+            Ok(Ok(value)) => (Some(value), None),
+            Ok(Err(e)) => (None, Some(ApiError::from(e))),
+            Err(e) => (
+                None,
+                Some(ApiError::Internal(format!(
+                    "task {} operation aborted: {}",
+                    task_id, e
+                ))),
+            ),
+        };
+        let mut item = batch_item(task_id, error);
+        item.result = value
+            .and_then(|v| serde_json::to_value(v).ok())
+            .filter(|v| !v.is_null());
+        results.push(item);
+    }
+    results
+}
+
+/// 任务不存在返回 TaskNotFound，rocksdb 读取失败返回 StorageUnavailable
 pub fn service_show_task(task_id: &str) -> Result<Task> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
-        Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+    match get_task(task_id) {
+        Ok(task) => Ok(task),
+        Err(ResourceError::KeyNotFound { .. }) => Err(ApiError::TaskNotFound {
+            task_id: task_id.to_string(),
+        }
+        .into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn service_task_checkpoint(task_id: &str) -> Result<RespTaskStatus> {
+    let task = service_show_task(task_id)?;
+    // 从未启动的任务没有 checkpoint，仍需返回启动被拒绝的原因
+    let checkpoint = get_checkpoint(task_id).ok();
+    let completed = completion_marker_exists(&task.meta_dir());
+    Ok(RespTaskStatus {
+        checkpoint,
+        completed,
+        last_skip_reason: get_start_skipped(task_id),
+        status: get_task_status(task_id).ok().map(|s| s.status),
+        executing_position: task_min_file_position(task_id),
+        queue_position: task_queue_position(task_id),
+        run_counters: task_is_living(task_id).then(|| task_run_counters(task_id)),
+    })
+}
+
+/// 合并持久化状态、内存状态与 checkpoint，供 http 接口与命令行共用
+pub fn service_task_unified_status(task_id: &str) -> Result<RespTaskUnifiedStatus> {
+    service_show_task(task_id)?;
+    let persisted = get_task_status(task_id).ok();
+    let live = get_live_transfer_task_status(task_id)
+        .ok()
+        .or_else(|| queued_task_status(task_id));
+    let (effective_state, stale) = effective_task_state(live.as_ref(), persisted.as_ref());
+    let checkpoint = get_checkpoint(task_id).ok().map(|c| RespCheckpointSummary {
+        executing_file_position: c.executing_file_position,
+        task_stage: c.task_stage,
+        timestamp: c.modify_checkpoint_timestamp,
+    });
+    Ok(RespTaskUnifiedStatus {
+        task_id: task_id.to_string(),
+        effective_state,
+        stale,
+        persisted,
+        live,
+        checkpoint,
+        queue_position: task_queue_position(task_id),
+        last_skip_reason: get_start_skipped(task_id),
+    })
+}
+
+/// 按 EffectiveTaskState 的判定顺序合并状态，同时返回两者是否不一致
+/// 比较任务不登记内存状态，仅以持久化状态为准
+fn effective_task_state(
+    live: Option<&TransferTaskStatus>,
+    persisted: Option<&TaskStatus>,
+) -> (EffectiveTaskState, bool) {
+    let is_compare = matches!(persisted.map(|p| &p.status), Some(Status::Compare(_)));
+    let persisted_active = persisted
+        .map(|p| p.is_starting() || p.is_running())
+        .unwrap_or(false);
+    let live_active = live
+        .map(|l| !l.status.is_stopped() && !l.status.is_queued())
+        .unwrap_or(false);
+    let stale = !is_compare && persisted.is_some() && persisted_active != live_active;
+
+    if let Some(l) = live {
+        match l.status {
+            TransferTaskStatusType::Queued => return (EffectiveTaskState::Queued, stale),
+            TransferTaskStatusType::Starting => return (EffectiveTaskState::Starting, stale),
+            TransferTaskStatusType::Running(_) => return (EffectiveTaskState::Running, stale),
+            TransferTaskStatusType::Paused(_) => return (EffectiveTaskState::Paused, stale),
+            TransferTaskStatusType::Stopped(_) => {}
+        }
+    }
+    let live_failed = live
+        .map(|l| l.status.is_stopped() && !l.status.is_stopped_finish())
+        .unwrap_or(false);
+    if live_failed || persisted.map(|p| p.is_failed()).unwrap_or(false) {
+        return (EffectiveTaskState::Failed, stale);
+    }
+    if live.is_some() || persisted.map(|p| p.is_stopped()).unwrap_or(false) {
+        return (EffectiveTaskState::Stopped, stale);
+    }
+    let state = match persisted {
+        Some(p) if is_compare && p.is_starting() => EffectiveTaskState::Starting,
+        Some(_) if is_compare => EffectiveTaskState::Running,
+        Some(_) => EffectiveTaskState::Interrupted,
+        None => EffectiveTaskState::NotStarted,
     };
+    (state, stale)
+}
+
+/// 等待运行中的任务停止，返回最终状态与本次运行记录；任务未运行时立即返回持久化状态
+/// 只持有状态事件的订阅，客户端断开时随请求一同释放
+pub async fn service_task_wait(task_id: &str, timeout: Duration) -> Result<RespTaskWait> {
+    service_show_task(task_id)?;
+    let run_id = get_live_transfer_task_status(task_id)
+        .ok()
+        .map(|s| s.run_id);
+    let mut subscription = match TaskEventSubscription::living(task_id) {
+        Some(s) => s.without_progress(),
+        None => {
+            return Ok(RespTaskWait {
+                task_id: task_id.to_string(),
+                waited: false,
+                status: persisted_status(task_id),
+                run: task_run_of(task_id, None)?,
+            })
+        }
+    };
+    let deadline = tokio::time::Instant::now() + timeout.min(TASK_WAIT_MAX_TIMEOUT);
+    let stopped = match tokio::time::timeout_at(deadline, subscription.wait_stopped()).await {
+        Ok(s) => s,
+        Err(_) => {
+            return Err(ApiError::WaitTimeout {
+                task_id: task_id.to_string(),
+                status: serde_json::to_value(persisted_status(task_id))?,
+            }
+            .into())
+        }
+    };
+    // 运行记录在执行协程退出后补全
+    wait_until(
+        deadline.saturating_duration_since(tokio::time::Instant::now()),
+        || task_run_exited(task_id),
+    )
+    .await;
+    Ok(RespTaskWait {
+        task_id: task_id.to_string(),
+        waited: true,
+        status: stopped.flatten().or_else(|| persisted_status(task_id)),
+        run: task_run_of(task_id, run_id.as_deref())?,
+    })
+}
+
+// 指定 run_id 的运行记录，未指定时为最近一次
+fn task_run_of(task_id: &str, run_id: Option<&str>) -> Result<Option<TaskRun>> {
+    let runs = list_task_runs(task_id)?;
+    Ok(match run_id {
+        Some(id) => runs.into_iter().find(|r| r.run_id == id),
+        None => runs.into_iter().next(),
+    })
+}
+
+/// 运行中的任务订阅事件通道，其余任务仅返回持久化状态与 checkpoint 位置
+pub fn service_task_events(task_id: &str) -> Result<TaskEventSubscription> {
+    service_show_task(task_id)?;
+    if let Some(s) = TaskEventSubscription::living(task_id) {
+        return Ok(s);
+    }
+    Ok(TaskEventSubscription::stopped(
+        task_id,
+        get_task_status(task_id).ok().map(|s| s.status),
+        get_checkpoint(task_id)
+            .ok()
+            .map(|c| c.executing_file_position),
+    ))
+}
+
+pub fn service_task_completion(task_id: &str) -> Result<CompletionMarker> {
+    let task = service_show_task(task_id)?;
+    match get_completion_marker(&task.meta_dir())? {
+        Some(marker) => Ok(marker),
+        None => Err(ApiError::NotFound(format!("task {} not completed", task_id)).into()),
+    }
+}
+
+// 仅解析任务类型与名称，过滤时避免反序列化完整任务定义
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TaskHead {
+    Transfer(TaskHeadName),
+    Compare(TaskHeadName),
+}
+
+#[derive(Deserialize)]
+struct TaskHeadName {
+    #[serde(default = "TaskDefaultParameters::name_default")]
+    name: String,
+}
+
+impl TaskHead {
+    fn task_type(&self) -> TaskType {
+        match self {
+            TaskHead::Transfer(_) => TaskType::Transfer,
+            TaskHead::Compare(_) => TaskType::Compare,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            TaskHead::Transfer(n) | TaskHead::Compare(n) => &n.name,
+        }
+    }
+}
 
-    let value = GLOBAL_ROCKSDB.get_cf(&cf, task_id)?;
-    return match value {
-        Some(v) => {
-            let task_json_str = String::from_utf8(v)?;
-            let task = json_to_struct::<Task>(task_json_str.as_str())?;
-            Ok(task)
+/// 运行中的任务以内存状态为准，其余取持久化状态，从未启动的任务视为 stopped
+fn task_list_status(task_id: &str) -> TaskListStatus {
+    if task_is_living(task_id) {
+        return TaskListStatus::Living;
+    }
+    match get_task_status(task_id) {
+        Ok(s) if !s.is_stopped() => TaskListStatus::Living,
+        Ok(s) if s.is_failed() => TaskListStatus::Error,
+        _ => TaskListStatus::Stopped,
+    }
+}
+
+fn task_head_matches(filter: &ReqTaskListFilter, head: &TaskHead) -> bool {
+    if let Some(t) = filter.task_type {
+        if head.task_type() != t {
+            return false;
         }
-        None => Err(anyhow!("task {} not exist", task_id)),
+    }
+    match &filter.name {
+        Some(n) => head.name().to_lowercase().contains(&n.to_lowercase()),
+        None => true,
+    }
+}
+
+/// 一次性列出任务，遇到损坏的条目时返回错误
+pub fn service_list_all_tasks(filter: &ReqTaskListFilter) -> Result<Vec<RespListTask>> {
+    let tasks = task_list_iter(filter)?;
+    match filter.limit {
+        Some(limit) => tasks.take(limit).collect(),
+        None => tasks.collect(),
+    }
+}
+
+/// 在任务列表上逐条匹配搜索词，损坏的条目与列表相同返回错误
+pub fn service_search_tasks(req: &ReqTaskSearch) -> Result<Vec<RespTaskSummary>> {
+    if let Some(name) = &req.name {
+        return search_tasks_by_name(name, req);
+    }
+    if req.q.trim().is_empty() {
+        return Err(ApiError::InvalidRequest("q is empty".to_string()).into());
+    }
+    let filter = ReqTaskListFilter {
+        after: req.after.clone(),
+        ..Default::default()
     };
+    let q = req.q.to_lowercase();
+    let summaries = task_list_iter(&filter)?
+        .filter(|item| match item {
+            Ok(t) => task_search_matches(&t.task, &q),
+            Err(_) => true,
+        })
+        .map(|item| {
+            item.map(|t| RespTaskSummary {
+                state: task_list_status(&t.cf_id),
+                task_id: t.cf_id,
+                name: t.task.name(),
+                task_type: t.task.task_type(),
+            })
+        });
+    match req.limit {
+        Some(limit) => summaries.take(limit).collect(),
+        None => summaries.collect(),
+    }
 }
 
-pub fn service_task_checkpoint(task_id: &str) -> Result<CheckPoint> {
-    get_checkpoint(task_id)
+// 经名称索引查找同名任务，不遍历全部任务；q 不为空时再按 q 过滤
+fn search_tasks_by_name(name: &str, req: &ReqTaskSearch) -> Result<Vec<RespTaskSummary>> {
+    let q = req.q.trim().to_lowercase();
+    let mut summaries = vec![];
+    for task_id in find_tasks_by_name(name)? {
+        if req.limit.map_or(false, |limit| summaries.len() >= limit) {
+            break;
+        }
+        if let Some(after) = &req.after {
+            if task_id.as_str() <= after.as_str() {
+                continue;
+            }
+        }
+        let task = get_task(&task_id)?;
+        if !q.is_empty() && !task_search_matches(&task, &q) {
+            continue;
+        }
+        summaries.push(RespTaskSummary {
+            state: task_list_status(&task_id),
+            name: task.name(),
+            task_type: task.task_type(),
+            task_id,
+        });
+    }
+    Ok(summaries)
 }
 
-pub fn service_list_all_tasks() -> Result<Vec<RespListTask>> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
+// q 须已转为小写
+fn task_search_matches(task: &Task, q: &str) -> bool {
+    let mut fields = vec![task.name()];
+    for storage in [task.task_source(), task.task_target()] {
+        match storage {
+            ObjectStorage::Local(path) => fields.push(path),
+            ObjectStorage::OSS(oss) => {
+                fields.push(oss.endpoint);
+                fields.push(oss.bucket);
+                fields.push(oss.region);
+            }
+        }
+    }
+    fields.iter().any(|f| f.to_lowercase().contains(q))
+}
+
+/// 从 rocksdb 迭代器逐条产生任务，after 存在时从其后开始
+pub fn task_list_iter(
+    filter: &ReqTaskListFilter,
+) -> Result<TaskListIter<impl Iterator<Item = (Box<[u8]>, Box<[u8]>)>>> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(cf_not_exist()),
+    };
+    let mode = match &filter.after {
+        Some(after) => IteratorMode::From(after.as_bytes(), Direction::Forward),
+        None => IteratorMode::Start,
+    };
+    let kvs = GLOBAL_ROCKSDB
+        .get()?
+        .iterator_cf(&cf, mode)
+        .filter_map(|item| item.ok());
+    Ok(TaskListIter::new(kvs, filter.clone()))
+}
+
+/// 按过滤条件逐条产生任务，损坏的条目以 Err 返回，由调用方决定中止或跳过
+pub struct TaskListIter<I> {
+    kvs: I,
+    filter: ReqTaskListFilter,
+}
+
+impl<I> TaskListIter<I> {
+    pub fn new(kvs: I, filter: ReqTaskListFilter) -> Self {
+        Self { kvs, filter }
+    }
+}
+
+impl<I> Iterator for TaskListIter<I>
+where
+    I: Iterator<Item = (Box<[u8]>, Box<[u8]>)>,
+{
+    type Item = Result<RespListTask>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, value) in self.kvs.by_ref() {
+            match list_task_entry(&self.filter, &key, &value) {
+                Ok(None) => continue,
+                Ok(Some(task)) => return Some(Ok(task)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// 先按游标、状态、再按类型与名称过滤，全部满足后才反序列化完整任务
+fn list_task_entry(
+    filter: &ReqTaskListFilter,
+    key: &[u8],
+    value: &[u8],
+) -> Result<Option<RespListTask>> {
+    let cf_id = String::from_utf8(key.to_vec())?;
+    if let Some(after) = &filter.after {
+        if cf_id.as_str() <= after.as_str() {
+            return Ok(None);
+        }
+    }
+    if let Some(status) = filter.status {
+        if task_list_status(&cf_id) != status {
+            return Ok(None);
+        }
+    }
+    if filter.task_type.is_some() || filter.name.is_some() {
+        let head = serde_json::from_slice::<TaskHead>(value)
+            .map_err(|e| anyhow!("task {} corrupt: {}", cf_id, e))?;
+        if !task_head_matches(filter, &head) {
+            return Ok(None);
+        }
+    }
+    let task_json_str = String::from_utf8(value.to_vec())?;
+    let task = json_to_struct::<Task>(task_json_str.as_str())
+        .map_err(|e| anyhow!("task {} corrupt: {}", cf_id, e))?;
+    Ok(Some(RespListTask { cf_id, task }))
+}
+
+/// 在阻塞线程中遍历任务，逐条序列化为 json 数组的片段经有界通道交给响应流
+/// 消费方落后时遍历随之阻塞，内存中最多缓存 TASK_LIST_STREAM_BUFFER 个片段
+/// redact 为 true 时任务定义中的凭证脱敏，v1 为 true 时输出 {"data","error"} 结构
+pub fn service_stream_tasks(
+    filter: ReqTaskListFilter,
+    redact: bool,
+    v1: bool,
+) -> Result<mpsc::Receiver<String>> {
+    if GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK).is_none() {
+        return Err(cf_not_exist());
+    }
+    let (tx, rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+    // rocksdb 迭代器在阻塞线程中创建与消费
+    tokio::task::spawn_blocking(move || match task_list_iter(&filter) {
+        Ok(tasks) => {
+            let tasks = tasks.map(|item| match redact {
+                true => item.and_then(|t| {
+                    Ok(RespListTask {
+                        task: redacted_task(&t.task)?,
+                        cf_id: t.cf_id,
+                    })
+                }),
+                false => item,
+            });
+            write_task_list(tasks, filter.limit, v1, tx)
+        }
+        // 发送端随之释放，响应提前结束，客户端收到不完整的 json
+        Err(e) => log::error!("stream task list error: {}", e),
+    });
+    Ok(rx)
+}
+
+fn task_export_header(redact: bool) -> Result<TaskExportHeader> {
+    Ok(TaskExportHeader {
+        schema_version: TASK_EXPORT_SCHEMA_VERSION,
+        exported_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        redacted: redact,
+    })
+}
+
+/// 导出全部任务定义，redact 为 true 时凭证脱敏；损坏的条目跳过
+pub fn service_export_tasks(redact: bool) -> Result<TaskExport> {
+    let mut tasks = vec![];
+    for item in task_list_iter(&ReqTaskListFilter::default())? {
+        match item {
+            Ok(t) => tasks.push(export_definition(&t.task, redact)?),
+            Err(e) => log::warn!("skip task in export: {}", e),
+        }
+    }
+    Ok(TaskExport {
+        header: task_export_header(redact)?,
+        tasks,
+    })
+}
+
+/// 以 ndjson 逐行导出，第一行为文件头，每个任务一行
+pub fn service_stream_export_tasks(redact: bool) -> Result<mpsc::Receiver<String>> {
+    if GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK).is_none() {
+        return Err(cf_not_exist());
+    }
+    let header = serde_json::to_string(&task_export_header(redact)?)?;
+    let (tx, rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+    tokio::task::spawn_blocking(move || {
+        if tx.blocking_send(format!("{}\n", header)).is_err() {
+            return;
+        }
+        let tasks = match task_list_iter(&ReqTaskListFilter::default()) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                log::error!("stream task export error: {}", e);
+                return;
+            }
+        };
+        for item in tasks {
+            let line =
+                item.and_then(|t| Ok(serde_json::to_string(&export_definition(&t.task, redact)?)?));
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => {
+                    log::warn!("skip task in export: {}", e);
+                    continue;
+                }
+            };
+            if tx.blocking_send(format!("{}\n", line)).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// 逐条校验并创建导入的任务，keep_ids 为 true 时保留原 id，id 已存在的条目失败
+pub fn service_import_tasks(export: TaskExport, keep_ids: bool) -> Vec<TaskImportResult> {
+    export
+        .tasks
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| import_task(index, value, keep_ids))
+        .collect()
+}
+
+fn import_task(index: usize, value: serde_json::Value, keep_ids: bool) -> TaskImportResult {
+    let mut result = TaskImportResult {
+        index,
+        source_task_id: None,
+        task_id: None,
+        error: None,
+    };
+    let created = serde_json::from_value::<Task>(value.clone())
+        .map_err(anyhow::Error::from)
+        .and_then(|mut task| {
+            let source_task_id = task.task_id();
+            result.source_task_id = Some(source_task_id.clone());
+            if definition_redacted(&value) {
+                return Err(anyhow!("credentials redacted, fill them in before import"));
+            }
+            let _guard = lock_task_names();
+            check_task_name_unique(&task.name(), "")?;
+            let id = match keep_ids {
+                true if source_task_id.is_empty() => return Err(anyhow!("task_id missing")),
+                true => {
+                    task.create_with_id(&source_task_id)?;
+                    source_task_id
+                }
+                false => task.create()?.to_string(),
+            };
+            Ok(id)
+        });
+    match created {
+        Ok(id) => result.task_id = Some(id),
+        Err(e) => result.error = Some(ApiError::from(e).to_string()),
+    }
+    result
+}
+
+// 损坏的条目跳过并计入结尾的 meta，客户端断开时停止遍历
+fn write_task_list<I>(tasks: I, limit: Option<usize>, v1: bool, tx: mpsc::Sender<String>)
+where
+    I: Iterator<Item = Result<RespListTask>>,
+{
+    let head = match v1 {
+        true => r#"{"data":["#,
+        false => r#"{"code":0,"msg":"OK","data":["#,
+    };
+    if tx.blocking_send(head.to_string()).is_err() {
+        return;
+    }
+    let mut meta = TaskListMeta::default();
+    let mut last_id = None;
+    for item in tasks {
+        let (json, cf_id) = match item.and_then(|t| Ok((serde_json::to_string(&t)?, t.cf_id))) {
+            Ok(task) => task,
+            Err(e) => {
+                log::warn!("skip task in list: {}", e);
+                meta.skipped_corrupt += 1;
+                continue;
+            }
+        };
+        if Some(meta.returned) == limit {
+            meta.next_after = last_id;
+            break;
+        }
+        let chunk = match meta.returned {
+            0 => json,
+            _ => format!(",{}", json),
+        };
+        if tx.blocking_send(chunk).is_err() {
+            return;
+        }
+        meta.returned += 1;
+        last_id = Some(cf_id);
+    }
+    let meta = match serde_json::to_string(&meta) {
+        Ok(m) => m,
+        Err(_) => "{}".to_string(),
+    };
+    let tail = match v1 {
+        true => format!(r#"],"error":null,"meta":{}}}"#, meta),
+        false => format!(r#"],"meta":{}}}"#, meta),
+    };
+    let _ = tx.blocking_send(tail);
+}
+
+/// 按 offset 分页读取错误记录，只读取到本页末尾的下一条
+pub fn service_task_errors(task_id: &str, req: &ReqTaskErrors) -> Result<RespTaskErrors> {
+    let task = service_show_task(task_id)?;
+    let files = error_record_files(&task.meta_dir())?;
+    let offset = req.offset.unwrap_or(0);
+    let limit = req
+        .limit
+        .unwrap_or(TASK_ERRORS_DEFAULT_LIMIT)
+        .min(TASK_ERRORS_MAX_LIMIT);
+    let mut records = ErrorRecordIter::new(files).skip(offset);
+    let page = records
+        .by_ref()
+        .take(limit)
+        .collect::<Result<Vec<TaskErrorRecord>>>()?;
+    let next_offset = match records.next() {
+        Some(_) => Some(offset + page.len()),
+        None => None,
+    };
+    Ok(RespTaskErrors {
+        records: page,
+        next_offset,
+    })
+}
+
+// 阻塞线程写出的数据经有界通道交给响应流，客户端断开时写入失败
+struct ChannelWriter(mpsc::Sender<Bytes>);
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.0.blocking_send(Bytes::copy_from_slice(buf)) {
+            Ok(()) => Ok(buf.len()),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "receiver closed",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 在阻塞线程中将错误记录文件打包为 tar.gz 并逐块写出，不在内存中缓存整个压缩包
+pub fn service_task_errors_archive(task_id: &str) -> Result<mpsc::Receiver<Bytes>> {
+    let task = service_show_task(task_id)?;
+    let files = error_record_files(&task.meta_dir())?;
+    let (tx, rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+    let task_id = task_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        let writer =
+            std::io::BufWriter::with_capacity(TASK_ERRORS_ARCHIVE_CHUNK, ChannelWriter(tx));
+        let result = write_error_archive(&files, writer)
+            .and_then(|mut w| std::io::Write::flush(&mut w).map_err(|e| e.into()));
+        // 发送端随之释放，响应提前结束，客户端收到不完整的压缩包
+        if let Err(e) = result {
+            log::warn!("task {} error records archive: {}", task_id, e);
+        }
+    });
+    Ok(rx)
+}
+
+pub fn service_task_progress(task_id: &str) -> Result<RespTaskProgress> {
+    service_show_task(task_id)?;
+    let list_file = get_checkpoint(task_id).ok().map(|c| c.executing_file);
+    let executing_position = task_min_file_position(task_id);
+    let overall_percent = match (&list_file, &executing_position) {
+        (Some(f), Some(p)) if f.total_lines > 0 => {
+            Some((p.line_num as f64 / f.total_lines as f64 * 100.0).min(100.0))
+        }
+        _ => None,
+    };
+    let batches = task_batch_progress(task_id);
+    let percents = batches
+        .iter()
+        .filter_map(|b| b.percent)
+        .collect::<Vec<f64>>();
+    Ok(RespTaskProgress {
+        task_id: task_id.to_string(),
+        list_file,
+        executing_position,
+        overall_percent,
+        summary: percent_summary(&percents),
+        stalled: batches.iter().filter(|b| b.stalled).count(),
+        batches,
+    })
+}
+
+pub fn service_task_runs(task_id: &str, req: &ReqTaskRuns) -> Result<RespTaskRuns> {
+    service_show_task(task_id)?;
+    let offset = req.offset.unwrap_or(0);
+    let limit = req
+        .limit
+        .unwrap_or(TASK_RUNS_DEFAULT_LIMIT)
+        .min(TASK_RUNS_MAX_LIMIT);
+    let runs = list_task_runs(task_id)?;
+    let page = runs
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .cloned()
+        .collect::<Vec<TaskRun>>();
+    let next_offset = match offset + page.len() < runs.len() {
+        true => Some(offset + page.len()),
+        false => None,
+    };
+    Ok(RespTaskRuns {
+        runs: page,
+        next_offset,
+    })
+}
+
+pub fn service_task_run_definition(task_id: &str, run_id: &str) -> Result<RespRunDefinition> {
+    let run = match get_run_definition(task_id, run_id)? {
+        Some(run) => run,
+        None => {
+            return Err(
+                ApiError::NotFound(format!("task {} run {} not exist", task_id, run_id)).into(),
+            )
+        }
+    };
+    // 当前定义按相同方式脱敏后比较，凭证变更不体现在差异中
+    let diff = match get_task(task_id) {
+        Ok(task) => Some(diff_definition(
+            &run.definition,
+            &redacted_definition(&task)?,
+        )),
+        Err(_) => None,
+    };
+    Ok(RespRunDefinition { run, diff })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        apply_clone_overrides, check_task_update, effective_task_state, patch_task_definition,
+        remove_meta_dir, remove_tasks_with, service_batch_tasks, service_task_create,
+        service_update_task, start_task_with, stop_task_with, task_head_matches,
+        task_search_matches, write_task_list, MetaDirUpdate, TaskHead, TaskListIter,
+        TASK_LIST_STREAM_BUFFER,
+    };
+    use crate::httpserver::module::{
+        ApiError, EffectiveTaskState, ReqTaskClone, ReqTaskListFilter, RespTaskStop, TaskStopState,
     };
-    let cf_task_iter = GLOBAL_ROCKSDB.iterator_cf(&cf, IteratorMode::Start);
-    let mut vec_task = vec![];
-    for item in cf_task_iter {
-        if let Ok(kv) = item {
-            let cf_id = String::from_utf8(kv.0.to_vec())?;
-            let task_json_str = String::from_utf8(kv.1.to_vec())?;
-            let task = json_to_struct::<Task>(task_json_str.as_str())?;
-            let resp = RespListTask { cf_id, task };
-            vec_task.push(resp);
+    use crate::resources::{
+        commit_task_writes, delete_task_all, get_checkpoint, get_task, open_test_rocksdb,
+        save_checkpoint_to_cf, task_update_writes,
+    };
+    use crate::s3::OSSDescription;
+    use crate::tasks::{
+        finish_task_executing, init_global_task_runtime, mark_task_executing,
+        register_task_cancellation, release_task_slot, task_run_exited,
+        unregister_task_cancellation, CheckPoint, CompareStatus, CompareTask, ObjectStorage,
+        Status, Task, TaskStartOutcome, TaskStatus, TaskStopReason, TaskType, TransferStage,
+        TransferStatus, TransferTask, TransferTaskStatus, TransferTaskStatusType,
+        GLOBAL_LIVING_TRANSFER_TASK_MAP, GLOBAL_TASK_START_LOCKS,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    //cargo test httpserver::service::service_task::test::test_effective_task_state -- --nocapture
+    #[test]
+    fn test_effective_task_state() {
+        let live = |status: TransferTaskStatusType| TransferTaskStatus {
+            task_id: "1".to_string(),
+            start_time: 0,
+            run_id: String::new(),
+            status,
+        };
+        let persisted = |status: Status| TaskStatus {
+            task_id: "1".to_string(),
+            start_time: 0,
+            status,
+            last_skip_reason: None,
+            run_id: None,
+        };
+        let running = TransferStatus::Running(TransferStage::Stock);
+
+        assert_eq!(
+            effective_task_state(None, None),
+            (EffectiveTaskState::NotStarted, false)
+        );
+        // 内存状态优先
+        assert_eq!(
+            effective_task_state(
+                Some(&live(TransferTaskStatusType::Running(TransferStage::Stock))),
+                Some(&persisted(Status::Transfer(running.clone())))
+            ),
+            (EffectiveTaskState::Running, false)
+        );
+        // 持久化为运行中但任务未运行
+        assert_eq!(
+            effective_task_state(None, Some(&persisted(Status::Transfer(running.clone())))),
+            (EffectiveTaskState::Interrupted, true)
+        );
+        // 排队中的任务与已停止的持久化状态一致
+        assert_eq!(
+            effective_task_state(
+                Some(&live(TransferTaskStatusType::Queued)),
+                Some(&persisted(Status::Transfer(TransferStatus::Stopped(
+                    TaskStopReason::Finish
+                ))))
+            ),
+            (EffectiveTaskState::Queued, false)
+        );
+        // 异常停止优先于正常停止
+        assert_eq!(
+            effective_task_state(
+                Some(&live(TransferTaskStatusType::Stopped(
+                    TaskStopReason::Finish
+                ))),
+                Some(&persisted(Status::Transfer(TransferStatus::Stopped(
+                    TaskStopReason::Broken
+                ))))
+            ),
+            (EffectiveTaskState::Failed, false)
+        );
+        assert_eq!(
+            effective_task_state(
+                None,
+                Some(&persisted(Status::Transfer(TransferStatus::Stopped(
+                    TaskStopReason::Finish
+                ))))
+            ),
+            (EffectiveTaskState::Stopped, false)
+        );
+        assert_eq!(
+            effective_task_state(
+                None,
+                Some(&persisted(Status::Compare(CompareStatus::Running)))
+            ),
+            (EffectiveTaskState::Running, false)
+        );
+    }
+
+    //cargo test httpserver::service::service_task::test::test_task_head_matches -- --nocapture
+    #[test]
+    fn test_task_head_matches() {
+        let mut transfer = TransferTask::default();
+        transfer.name = "Nightly Backup".to_string();
+        let json = serde_json::to_vec(&Task::Transfer(transfer)).unwrap();
+        let head = serde_json::from_slice::<TaskHead>(&json).unwrap();
+        assert_eq!(head.task_type(), TaskType::Transfer);
+
+        let mut filter = ReqTaskListFilter::default();
+        assert!(task_head_matches(&filter, &head));
+        filter.name = Some("backup".to_string());
+        assert!(task_head_matches(&filter, &head));
+        filter.task_type = Some(TaskType::Compare);
+        assert!(!task_head_matches(&filter, &head));
+
+        let json = serde_json::to_vec(&Task::Compare(CompareTask::default())).unwrap();
+        let head = serde_json::from_slice::<TaskHead>(&json).unwrap();
+        filter.name = None;
+        assert!(task_head_matches(&filter, &head));
+
+        let filter = serde_json::from_str::<ReqTaskListFilter>(
+            r#"{"status":"error","type":"transfer","name":"x"}"#,
+        )
+        .unwrap();
+        assert_eq!(filter.task_type, Some(TaskType::Transfer));
+    }
+
+    async fn fake_start(task_id: String) -> anyhow::Result<()> {
+        match task_id.starts_with("bad") {
+            true => Err(ApiError::TaskNotFound { task_id }.into()),
+            false => Ok(()),
+        }
+    }
+
+    //cargo test httpserver::service::service_task::test::test_service_batch_tasks -- --nocapture
+    #[tokio::test]
+    async fn test_service_batch_tasks() {
+        let ids = (0..20)
+            .map(|i| match i % 5 {
+                0 => format!("bad{}", i),
+                _ => i.to_string(),
+            })
+            .collect::<Vec<String>>();
+        let results = service_batch_tasks(ids.clone(), fake_start).await;
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r.task_id.clone())
+                .collect::<Vec<String>>(),
+            ids
+        );
+        assert_eq!(results.iter().filter(|r| !r.ok).count(), 4);
+        assert_eq!(results[0].error.as_deref(), Some("task bad0 not exist"));
+        assert_eq!(results[0].code.as_deref(), Some("task_not_found"));
+        assert!(results[1].ok && results[1].error.is_none());
+        assert!(results[1].result.is_none());
+
+        let results = service_batch_tasks(vec!["1".to_string()], |id| async move {
+            anyhow::Ok(RespTaskStop {
+                task_id: id,
+                state: TaskStopState::Stopping,
+                run_id: "run".to_string(),
+                status: None,
+                duration_secs: 1,
+            })
+        })
+        .await;
+        let result = results[0].result.clone().unwrap();
+        assert_eq!(result["state"], "stopping");
+        assert_eq!(result["run_id"], "run");
+    }
+
+    fn fake_exists(task_id: &str) -> anyhow::Result<bool> {
+        Ok(!task_id.starts_with("missing"))
+    }
+
+    fn fake_purge(_: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    //cargo test httpserver::service::service_task::test::test_remove_tasks_mixed -- --nocapture
+    #[tokio::test]
+    async fn test_remove_tasks_mixed() {
+        let living = "remove_test_living".to_string();
+        let stopped = "remove_test_stopped".to_string();
+        for (task_id, status) in [
+            (
+                &living,
+                TransferTaskStatusType::Running(TransferStage::Stock),
+            ),
+            (
+                &stopped,
+                TransferTaskStatusType::Stopped(TaskStopReason::Finish),
+            ),
+        ] {
+            GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+                task_id.clone(),
+                TransferTaskStatus {
+                    task_id: task_id.clone(),
+                    start_time: 0,
+                    run_id: String::new(),
+                    status,
+                },
+            );
+        }
+        let ids = vec![
+            living.clone(),
+            "missing_1".to_string(),
+            stopped.clone(),
+            "remove_test_idle".to_string(),
+        ];
+        let results = remove_tasks_with(ids.clone(), false, fake_exists, fake_purge).await;
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r.task_id.clone())
+                .collect::<Vec<String>>(),
+            ids
+        );
+        assert_eq!(results[0].code.as_deref(), Some("task_already_living"));
+        assert_eq!(results[1].code.as_deref(), Some("task_not_found"));
+        assert!(results[2].ok && results[3].ok);
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&living);
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&stopped);
+
+        // meta_dir 不存在视为已删除
+        let dir = "/tmp/mario_remove_test_meta_dir";
+        std::fs::create_dir_all(format!("{}/sub", dir)).unwrap();
+        std::fs::write(format!("{}/sub/list", dir), "a").unwrap();
+        remove_meta_dir(dir).unwrap();
+        assert!(!std::path::Path::new(dir).exists());
+        remove_meta_dir(dir).unwrap();
+    }
+
+    //cargo test httpserver::service::service_task::test::test_apply_clone_overrides -- --nocapture
+    #[test]
+    fn test_apply_clone_overrides() {
+        let mut transfer = TransferTask::default();
+        transfer.name = "src".to_string();
+        transfer.source = ObjectStorage::OSS(OSSDescription {
+            prefix: Some("a/".to_string()),
+            ..Default::default()
+        });
+        transfer.attributes.start_from_checkpoint = true;
+        let mut task = Task::Transfer(transfer);
+        let overrides = ReqTaskClone {
+            name: Some("copy".to_string()),
+            source_prefix: Some("b/".to_string()),
+            include: Some(vec!["\\.log$".to_string()]),
+            ..Default::default()
+        };
+        apply_clone_overrides(&mut task, &overrides).unwrap();
+        let Task::Transfer(t) = &task else {
+            panic!("task type changed")
+        };
+        assert_eq!(t.name, "copy");
+        assert!(matches!(&t.source, ObjectStorage::OSS(o) if o.prefix.as_deref() == Some("b/")));
+        assert_eq!(t.attributes.include, Some(vec!["\\.log$".to_string()]));
+        assert!(t.attributes.exclude.is_none());
+        assert!(!t.attributes.start_from_checkpoint);
+
+        // 本地存储不支持覆盖 prefix
+        let mut local = Task::Transfer(TransferTask {
+            target: ObjectStorage::Local("/tmp/t".to_string()),
+            ..Default::default()
+        });
+        let overrides = ReqTaskClone {
+            target_prefix: Some("c/".to_string()),
+            ..Default::default()
+        };
+        let err = ApiError::from(apply_clone_overrides(&mut local, &overrides).unwrap_err());
+        assert_eq!(err.code(), "invalid_request");
+    }
+
+    //cargo test httpserver::service::service_task::test::test_patch_task_definition -- --nocapture
+    #[test]
+    fn test_patch_task_definition() {
+        let mut transfer = TransferTask::default();
+        transfer.task_id = "1".to_string();
+        transfer.attributes.meta_dir = "/tmp/meta/1".to_string();
+        transfer.attributes.include = Some(vec!["a".to_string()]);
+        transfer.source = ObjectStorage::OSS(OSSDescription {
+            access_key_id: "ak".to_string(),
+            prefix: Some("p/".to_string()),
+            ..Default::default()
+        });
+        let task = Task::Transfer(transfer);
+
+        // 嵌套字段合并，未出现的字段保持不变，null 删除可选字段
+        let patch = serde_json::json!({
+            "attributes": {"task_parallelism": 3, "include": null},
+            "source": {"prefix": null}
+        });
+        let Task::Transfer(t) = patch_task_definition(&task, &patch).unwrap() else {
+            panic!("task type changed")
+        };
+        assert_eq!(t.attributes.task_parallelism, 3);
+        assert!(t.attributes.include.is_none());
+        assert_eq!(t.attributes.meta_dir, "/tmp/meta/1");
+        match &t.source {
+            ObjectStorage::OSS(o) => {
+                assert_eq!(o.access_key_id, "ak");
+                assert!(o.prefix.is_none());
+            }
+            _ => panic!("source storage changed"),
+        }
+
+        // 不可变字段
+        for patch in [
+            serde_json::json!({"type": "compare"}),
+            serde_json::json!({"task_id": "2"}),
+            serde_json::json!({"attributes": {"meta_dir": "/tmp"}}),
+            serde_json::json!({"attributes": {"meta_dir": null}}),
+        ] {
+            let err = ApiError::from(patch_task_definition(&task, &patch).unwrap_err());
+            assert_eq!(err.code(), "invalid_request", "{}", patch);
+        }
+
+        // 删除必填字段
+        let err = ApiError::from(
+            patch_task_definition(&task, &serde_json::json!({"target": null})).unwrap_err(),
+        );
+        assert_eq!(err.code(), "invalid_request");
+    }
+
+    // 模拟任务收到停止请求后延迟退出
+    fn fake_stop_exit(task_id: &str) -> anyhow::Result<()> {
+        let task_id = task_id.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&task_id);
+            unregister_task_cancellation(&task_id);
+        });
+        Ok(())
+    }
+
+    fn fake_stop_hang(_: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn fake_final_status(_: &str) -> Option<Status> {
+        Some(Status::Transfer(TransferStatus::Stopped(
+            TaskStopReason::Finish,
+        )))
+    }
+
+    //cargo test httpserver::service::service_task::test::test_stop_task_wait -- --nocapture
+    #[tokio::test]
+    async fn test_stop_task_wait() {
+        let (exit, hang) = ("stop_wait_test_exit", "stop_wait_test_hang");
+        for task_id in [exit, hang] {
+            register_task_cancellation(task_id);
+            GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+                task_id.to_string(),
+                TransferTaskStatus {
+                    task_id: task_id.to_string(),
+                    start_time: 0,
+                    run_id: "42".to_string(),
+                    status: TransferTaskStatusType::Running(TransferStage::Stock),
+                },
+            );
+        }
+
+        let resp = stop_task_with(
+            exit,
+            Duration::from_secs(5),
+            fake_stop_exit,
+            fake_final_status,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.state, TaskStopState::Stopped);
+        assert_eq!(resp.run_id, "42");
+        assert_eq!(resp.status, fake_final_status(exit));
+        assert!(task_run_exited(exit));
+
+        let resp = stop_task_with(
+            hang,
+            Duration::from_millis(300),
+            fake_stop_hang,
+            fake_final_status,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.state, TaskStopState::Stopping);
+        assert!(resp.status.is_none());
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(hang);
+        unregister_task_cancellation(hang);
+
+        let err = stop_task_with(
+            hang,
+            Duration::from_millis(300),
+            fake_stop_hang,
+            fake_final_status,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(ApiError::from(err).code(), "task_not_living");
+    }
+
+    static FAKE_SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+    // 与 spawn_task_execute 相同，返回前登记为活动状态
+    fn fake_spawn(task: Task) -> Result<String> {
+        FAKE_SPAWNED.fetch_add(1, Ordering::SeqCst);
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task.task_id(),
+            TransferTaskStatus {
+                task_id: task.task_id(),
+                start_time: 0,
+                run_id: "1".to_string(),
+                status: TransferTaskStatusType::Starting,
+            },
+        );
+        Ok("1".to_string())
+    }
+
+    //cargo test httpserver::service::service_task::test::test_concurrent_start -- --nocapture
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_start() {
+        open_test_rocksdb();
+        init_global_task_runtime().unwrap();
+        let task_id = "concurrent_start_test";
+        let dir = "/tmp/concurrent_start_test";
+        std::fs::create_dir_all(format!("{}/source", dir)).unwrap();
+        let mut transfer = TransferTask::default();
+        transfer.task_id = task_id.to_string();
+        transfer.source = ObjectStorage::Local(format!("{}/source", dir));
+        transfer.target = ObjectStorage::Local(format!("{}/target", dir));
+        transfer.attributes.meta_dir = format!("{}/meta", dir);
+        let task = Task::Transfer(transfer);
+
+        let handles = (0..50)
+            .map(|_| {
+                let task = task.clone();
+                tokio::spawn(async move { start_task_with(task_id, task, fake_spawn).await })
+            })
+            .collect::<Vec<_>>();
+        let mut started = 0;
+        for h in handles {
+            match h.await.unwrap() {
+                Ok(TaskStartOutcome::Started { .. }) => started += 1,
+                Ok(TaskStartOutcome::Queued { .. }) => panic!("task queued"),
+                Err(e) => assert_eq!(ApiError::from(e).code(), "task_already_living"),
+            }
+        }
+        assert_eq!(started, 1);
+        assert_eq!(FAKE_SPAWNED.load(Ordering::SeqCst), 1);
+        // 无持有方后启动锁被移除
+        assert!(!GLOBAL_TASK_START_LOCKS.contains_key(task_id));
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+        release_task_slot(task_id);
+    }
+
+    static FAKE_COMPARE_SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+    // 与 spawn_task_execute 相同，比对任务只登记执行中，不进入活动任务表
+    fn fake_spawn_compare(task: Task) -> Result<String> {
+        FAKE_COMPARE_SPAWNED.fetch_add(1, Ordering::SeqCst);
+        mark_task_executing(&task.task_id(), "1", &task.namespace());
+        Ok("1".to_string())
+    }
+
+    //cargo test httpserver::service::service_task::test::test_concurrent_start_compare -- --nocapture
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_start_compare() {
+        open_test_rocksdb();
+        init_global_task_runtime().unwrap();
+        let task_id = "concurrent_start_compare_test";
+        let dir = "/tmp/concurrent_start_compare_test";
+        std::fs::create_dir_all(format!("{}/source", dir)).unwrap();
+        std::fs::create_dir_all(format!("{}/target", dir)).unwrap();
+        let mut compare = CompareTask::default();
+        compare.task_id = task_id.to_string();
+        compare.source = ObjectStorage::Local(format!("{}/source", dir));
+        compare.target = ObjectStorage::Local(format!("{}/target", dir));
+        compare.attributes.meta_dir = format!("{}/meta", dir);
+        let task = Task::Compare(compare);
+
+        let handles = (0..50)
+            .map(|_| {
+                let task = task.clone();
+                tokio::spawn(
+                    async move { start_task_with(task_id, task, fake_spawn_compare).await },
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut started = 0;
+        for h in handles {
+            match h.await.unwrap() {
+                Ok(TaskStartOutcome::Started { .. }) => started += 1,
+                Ok(TaskStartOutcome::Queued { .. }) => panic!("task queued"),
+                Err(e) => assert_eq!(ApiError::from(e).code(), "task_already_living"),
+            }
+        }
+        assert_eq!(started, 1);
+        assert_eq!(FAKE_COMPARE_SPAWNED.load(Ordering::SeqCst), 1);
+
+        // 执行协程退出后可再次启动
+        finish_task_executing(task_id, "1");
+        let outcome = start_task_with(task_id, task, fake_spawn_compare)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, TaskStartOutcome::Started { .. }));
+        finish_task_executing(task_id, "1");
+    }
+
+    // 按序号生成任务，序号除以 1000 余 7 的条目损坏
+    fn synthetic_tasks(
+        count: usize,
+        produced: Arc<AtomicUsize>,
+    ) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> {
+        let task = serde_json::to_vec(&Task::Transfer(TransferTask::default())).unwrap();
+        (0..count).map(move |i| {
+            produced.fetch_add(1, Ordering::SeqCst);
+            let value = match i % 1000 {
+                7 => b"{broken".to_vec(),
+                _ => task.clone(),
+            };
+            (
+                format!("{:05}", i).into_bytes().into_boxed_slice(),
+                value.into_boxed_slice(),
+            )
+        })
+    }
+
+    async fn collect_task_list(filter: ReqTaskListFilter) -> serde_json::Value {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let tasks = TaskListIter::new(synthetic_tasks(10000, produced), filter.clone());
+        let (tx, mut rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || write_task_list(tasks, filter.limit, false, tx));
+        let mut body = String::new();
+        while let Some(chunk) = rx.recv().await {
+            body.push_str(&chunk);
+        }
+        serde_json::from_str(&body).unwrap()
+    }
+
+    //cargo test httpserver::service::service_task::test::test_stream_task_list -- --nocapture
+    #[tokio::test]
+    async fn test_stream_task_list() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let tasks = TaskListIter::new(
+            synthetic_tasks(10000, produced.clone()),
+            ReqTaskListFilter::default(),
+        );
+        let (tx, mut rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || write_task_list(tasks, None, false, tx));
+
+        // 消费方暂停读取时，生产方受通道容量限制，不会遍历全部任务
+        let mut body = rx.recv().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(produced.load(Ordering::SeqCst) < TASK_LIST_STREAM_BUFFER * 2);
+        while let Some(chunk) = rx.recv().await {
+            body.push_str(&chunk);
+        }
+        assert_eq!(produced.load(Ordering::SeqCst), 10000);
+        let list = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(list["code"], 0);
+        assert_eq!(list["data"].as_array().unwrap().len(), 9990);
+        assert_eq!(list["meta"]["returned"], 9990);
+        assert_eq!(list["meta"]["skipped_corrupt"], 10);
+        assert!(list["meta"]["next_after"].is_null());
+
+        // 游标分页
+        let page = collect_task_list(ReqTaskListFilter {
+            after: Some("00100".to_string()),
+            limit: Some(5),
+            ..Default::default()
+        })
+        .await;
+        let ids = page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["cf_id"].as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(ids, vec!["00101", "00102", "00103", "00104", "00105"]);
+        assert_eq!(page["meta"]["next_after"], "00105");
+        assert_eq!(page["meta"]["skipped_corrupt"], 0);
+
+        let last = collect_task_list(ReqTaskListFilter {
+            after: Some("09990".to_string()),
+            limit: Some(20),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(last["meta"]["returned"], 9);
+        assert!(last["meta"]["next_after"].is_null());
+
+        // 请求 v1 结构时为 {"data","error","meta"}
+        let tasks = TaskListIter::new(
+            synthetic_tasks(10, Arc::new(AtomicUsize::new(0))),
+            ReqTaskListFilter::default(),
+        );
+        let (tx, mut rx) = mpsc::channel(TASK_LIST_STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || write_task_list(tasks, Some(2), true, tx));
+        let mut body = String::new();
+        while let Some(chunk) = rx.recv().await {
+            body.push_str(&chunk);
+        }
+        let v1 = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert!(v1.get("code").is_none());
+        assert!(v1["error"].is_null());
+        assert_eq!(v1["data"].as_array().unwrap().len(), 2);
+        assert_eq!(v1["meta"]["returned"], 2);
+    }
+
+    //cargo test httpserver::service::service_task::test::test_check_task_update -- --nocapture
+    #[test]
+    fn test_check_task_update() {
+        let mut old = TransferTask::default();
+        old.attributes.meta_dir = "/data/meta/1".to_string();
+        let old = Task::Transfer(old);
+        let new = Task::Transfer(TransferTask::default());
+
+        // 运行中的任务不允许修改
+        let err = check_task_update("1", true, Some(&old), &new, false, false).unwrap_err();
+        assert_eq!(ApiError::from(err).code(), "task_already_living");
+
+        // 不允许改变任务类型
+        let compare = Task::Compare(CompareTask::default());
+        let err = check_task_update("1", false, Some(&old), &compare, false, false).unwrap_err();
+        assert_eq!(ApiError::from(err).code(), "invalid_request");
+
+        // 新建或无 checkpoint 时按配置生成 meta_dir
+        assert_eq!(
+            check_task_update("1", false, None, &compare, false, false).unwrap(),
+            MetaDirUpdate::Generate
+        );
+        assert_eq!(
+            check_task_update("1", false, Some(&old), &new, false, true).unwrap(),
+            MetaDirUpdate::Generate
+        );
+
+        // 已有 checkpoint 时沿用原 meta_dir，reset_meta 时重置
+        assert_eq!(
+            check_task_update("1", false, Some(&old), &new, true, false).unwrap(),
+            MetaDirUpdate::Keep("/data/meta/1".to_string())
+        );
+        assert_eq!(
+            check_task_update("1", false, Some(&old), &new, true, true).unwrap(),
+            MetaDirUpdate::Reset
+        );
+    }
+
+    //cargo test httpserver::service::service_task::test::test_service_update_task -- --nocapture
+    #[test]
+    fn test_service_update_task() {
+        open_test_rocksdb();
+        let dir = format!("/tmp/update_task_test/{}", uuid::Uuid::new_v4());
+        let mut transfer = TransferTask::default();
+        transfer.source = ObjectStorage::Local(format!("{}/source", dir));
+        transfer.target = ObjectStorage::Local(format!("{}/target", dir));
+        let mut task = Task::Transfer(transfer);
+        let task_id = service_task_create(&mut task).unwrap().to_string();
+        let generated = get_task(&task_id).unwrap().meta_dir();
+
+        // 运行中的任务不允许修改
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task_id.clone(),
+            TransferTaskStatus {
+                task_id: task_id.clone(),
+                start_time: 0,
+                run_id: "1".to_string(),
+                status: TransferTaskStatusType::Running(TransferStage::Stock),
+            },
+        );
+        let err = service_update_task(&task_id, &mut task.clone(), "", false).unwrap_err();
+        assert_eq!(ApiError::from(err).code(), "task_already_living");
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&task_id);
+
+        // 不允许改变任务类型
+        let mut compare = Task::Compare(CompareTask::default());
+        let err = service_update_task(&task_id, &mut compare, "", false).unwrap_err();
+        assert_eq!(ApiError::from(err).code(), "invalid_request");
+        assert!(matches!(get_task(&task_id).unwrap(), Task::Transfer(_)));
+
+        // 已有 checkpoint 时沿用原 meta_dir
+        let old_meta = format!("{}/old_meta", dir);
+        let mut stored = get_task(&task_id).unwrap();
+        stored.set_meta_dir(&old_meta);
+        let json = serde_json::to_string(&stored).unwrap();
+        commit_task_writes(&task_update_writes(
+            &task_id,
+            &json,
+            Some(stored.name().as_str()),
+            &stored.name(),
+        ))
+        .unwrap();
+        save_checkpoint_to_cf(&mut CheckPoint {
+            task_id: task_id.clone(),
+            ..CheckPoint::default()
+        })
+        .unwrap();
+        let mut renamed = task.clone();
+        if let Task::Transfer(t) = &mut renamed {
+            t.name = "update_task_test".to_string();
+        }
+        service_update_task(&task_id, &mut renamed, "", false).unwrap();
+        let updated = get_task(&task_id).unwrap();
+        assert_eq!(updated.name(), "update_task_test");
+        assert_eq!(updated.meta_dir(), old_meta);
+        assert!(get_checkpoint(&task_id).is_ok());
+
+        // reset_meta 时重新生成 meta_dir 并清除 checkpoint
+        service_update_task(&task_id, &mut renamed, "", true).unwrap();
+        assert_eq!(get_task(&task_id).unwrap().meta_dir(), generated);
+        assert!(get_checkpoint(&task_id).is_err());
+
+        delete_task_all(&task_id).unwrap();
+    }
+
+    //cargo test httpserver::service::service_task::test::test_task_search_matches -- --nocapture
+    #[test]
+    fn test_task_search_matches() {
+        let mut transfer = TransferTask::default();
+        transfer.name = "Nightly Backup".to_string();
+        transfer.source = ObjectStorage::Local("/data/Export".to_string());
+        transfer.target = ObjectStorage::OSS(OSSDescription {
+            endpoint: "https://oss-cn-beijing.aliyuncs.com".to_string(),
+            bucket: "bucket-x".to_string(),
+            region: "cn-beijing".to_string(),
+            ..Default::default()
+        });
+        let task = Task::Transfer(transfer);
+        for q in ["backup", "/data/export", "oss-cn", "bucket-x", "beijing"] {
+            assert!(task_search_matches(&task, q), "{}", q);
         }
+        assert!(!task_search_matches(&task, "region-y"));
     }
-    Ok(vec_task)
 }
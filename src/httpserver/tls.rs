@@ -0,0 +1,112 @@
+use crate::configure::HttpTlsConfig;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+// 当前生效的证书，重新加载时整体替换，已建立的连接不受影响
+static TLS_STATE: Lazy<RwLock<Option<TlsState>>> = Lazy::new(|| RwLock::new(None));
+
+struct TlsState {
+    tls: HttpTlsConfig,
+    server_config: Arc<ServerConfig>,
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|e| anyhow!("read cert file {} error: {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("parse cert file {} error: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no PEM certificate found in {}", path));
+    }
+    Ok(certs)
+}
+
+fn read_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|e| anyhow!("read key file {} error: {}", path, e))?;
+    match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => Err(anyhow!("no PEM private key found in {}", path)),
+        Err(e) => Err(anyhow!("parse key file {} error: {}", path, e)),
+    }
+}
+
+/// 读取证书与私钥，文件不可读、格式错误或二者不匹配时返回错误
+pub fn load_tls_config(tls: &HttpTlsConfig) -> Result<Arc<ServerConfig>> {
+    let certs = read_certs(&tls.cert)?;
+    let key = read_private_key(&tls.key)?;
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| anyhow!("invalid cert {} or key {}: {}", tls.cert, tls.key, e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// 启动时加载证书
+pub fn init_tls(tls: &HttpTlsConfig) -> Result<()> {
+    let server_config = load_tls_config(tls)?;
+    let mut state = TLS_STATE.write().map_err(|e| anyhow!("{}", e))?;
+    *state = Some(TlsState {
+        tls: tls.clone(),
+        server_config,
+    });
+    Ok(())
+}
+
+/// 新连接使用的 acceptor，未启用 tls 时为 None
+pub fn tls_acceptor() -> Option<TlsAcceptor> {
+    let state = TLS_STATE.read().ok()?;
+    state
+        .as_ref()
+        .map(|s| TlsAcceptor::from(s.server_config.clone()))
+}
+
+/// 按启动时的路径重新读取证书，失败时保留原证书；未启用 tls 时返回 false
+pub fn reload_tls_certs() -> Result<bool> {
+    let tls = match TLS_STATE.read().map_err(|e| anyhow!("{}", e))?.as_ref() {
+        Some(s) => s.tls.clone(),
+        None => return Ok(false),
+    };
+    let server_config = load_tls_config(&tls)?;
+    let mut state = TLS_STATE.write().map_err(|e| anyhow!("{}", e))?;
+    *state = Some(TlsState { tls, server_config });
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::load_tls_config;
+    use crate::configure::HttpTlsConfig;
+
+    //cargo test httpserver::tls::test::test_load_tls_config_invalid -- --nocapture
+    #[test]
+    fn test_load_tls_config_invalid() {
+        let dir = "/tmp/mario_tls_test";
+        std::fs::create_dir_all(dir).unwrap();
+        let empty = format!("{}/empty.pem", dir);
+        std::fs::write(&empty, "").unwrap();
+
+        let missing = HttpTlsConfig {
+            cert: format!("{}/no_such_cert.pem", dir),
+            key: empty.clone(),
+        };
+        let err = load_tls_config(&missing).unwrap_err().to_string();
+        assert!(err.contains("read cert file"));
+        assert!(err.contains("no_such_cert.pem"));
+
+        let no_cert = HttpTlsConfig {
+            cert: empty.clone(),
+            key: empty.clone(),
+        };
+        let err = load_tls_config(&no_cert).unwrap_err().to_string();
+        assert!(err.contains("no PEM certificate"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
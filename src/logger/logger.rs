@@ -1,3 +1,4 @@
+use anyhow::{anyhow, Result};
 use log::LevelFilter;
 use log4rs::append::console::ConsoleAppender;
 use log4rs::append::file::FileAppender;
@@ -8,10 +9,18 @@ use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::Config;
+use once_cell::sync::OnceCell;
+use std::sync::RwLock;
 use tracing_appender::rolling::{self};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::{fmt, Layer};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+pub const DEFAULT_LOG_LEVEL: &'static str = "info";
+
+// 运行时可替换的日志过滤器，支持 "info"、"file_pipe_server::tasks=debug,info" 等写法
+static LOG_FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+static LOG_LEVEL: RwLock<String> = RwLock::new(String::new());
 
 pub fn init_log() {
     let window_size = 3; // log0, log1, log2
@@ -58,13 +67,18 @@ pub fn init_log() {
 }
 
 pub fn tracing_init() {
+    // 全局日志等级过滤层，可通过 set_log_level 动态调整
+    let filter = EnvFilter::new(DEFAULT_LOG_LEVEL);
+    let (filter_layer, filter_handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER_HANDLE.set(filter_handle);
+    set_current_log_level(DEFAULT_LOG_LEVEL);
+
     // 格式化输出层，并且输出到终端。
     let formatting_layer = fmt::layer()
         .pretty()
         .with_file(true)
         .with_line_number(true)
         .with_writer(std::io::stdout)
-        .with_filter(tracing_subscriber::filter::LevelFilter::INFO)
         .boxed();
 
     // 文件输出层
@@ -74,12 +88,43 @@ pub fn tracing_init() {
         .with_file(true)
         .with_line_number(true)
         .with_writer(file_appender)
-        .with_filter(tracing_subscriber::filter::LevelFilter::INFO) // 文件输出日志等级
         .boxed();
 
     let registry = tracing_subscriber::registry()
+        .with(filter_layer)
         .with(file_layer)
         .with(formatting_layer);
 
     registry.init()
 }
+
+/// 校验日志等级表达式，如 "debug" 或 "file_pipe_server::tasks=debug,info"
+pub fn parse_log_level(directives: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(directives).map_err(|e| anyhow!("invalid log level {}: {}", directives, e))
+}
+
+pub fn set_log_level(directives: &str) -> Result<()> {
+    let filter = parse_log_level(directives)?;
+    let handle = match LOG_FILTER_HANDLE.get() {
+        Some(h) => h,
+        None => return Err(anyhow!("logger not initialized")),
+    };
+    handle.reload(filter)?;
+    set_current_log_level(directives);
+    log::info!("log level changed to {}", directives);
+    Ok(())
+}
+
+pub fn get_log_level() -> String {
+    match LOG_LEVEL.read() {
+        Ok(l) => l.clone(),
+        Err(_) => DEFAULT_LOG_LEVEL.to_string(),
+    }
+}
+
+fn set_current_log_level(directives: &str) {
+    if let Ok(mut l) = LOG_LEVEL.write() {
+        l.clear();
+        l.push_str(directives);
+    }
+}
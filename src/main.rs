@@ -6,10 +6,19 @@ mod httpserver;
 mod logger;
 mod resources;
 mod s3;
+mod server;
 mod tasks;
 
 fn main() {
     // init_log();
     tracing_init();
-    cmd::run_app();
+    let args: Vec<String> = std::env::args().collect();
+    let code = match cmd::run_from(args) {
+        Ok(status) => status.code(),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            1
+        }
+    };
+    std::process::exit(code);
 }
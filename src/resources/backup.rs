@@ -0,0 +1,191 @@
+use super::GLOBAL_ROCKSDB;
+use anyhow::{anyhow, Result};
+use rocksdb::backup::{BackupEngine, BackupEngineInfo, BackupEngineOptions, RestoreOptions};
+use rocksdb::{DBWithThreadMode, Env, MultiThreaded};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 备份信息，timestamp 为备份创建时的 unix 秒级时间戳，size 为字节
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub backup_id: u32,
+    pub timestamp: i64,
+    pub size: u64,
+    pub num_files: u32,
+}
+
+impl From<&BackupEngineInfo> for BackupInfo {
+    fn from(info: &BackupEngineInfo) -> Self {
+        Self {
+            backup_id: info.backup_id,
+            timestamp: info.timestamp,
+            size: info.size,
+            num_files: info.num_files,
+        }
+    }
+}
+
+fn open_backup_engine(dir: &str) -> Result<BackupEngine> {
+    std::fs::create_dir_all(dir)?;
+    let opts = BackupEngineOptions::new(dir)?;
+    let env = Env::new()?;
+    Ok(BackupEngine::open(&opts, &env)?)
+}
+
+// 备份按 id 递增，最新的备份 id 最大
+fn latest_backup(engine: &BackupEngine, dir: &str) -> Result<BackupInfo> {
+    engine
+        .get_backup_info()
+        .iter()
+        .max_by_key(|info| info.backup_id)
+        .map(BackupInfo::from)
+        .ok_or_else(|| anyhow!("backup not found in {}", dir))
+}
+
+/// 备份 GLOBAL_ROCKSDB 到 dir，服务运行中可执行
+pub fn create_backup(dir: &str) -> Result<BackupInfo> {
+    create_backup_of(GLOBAL_ROCKSDB.get()?.as_ref(), dir)
+}
+
+/// 备份前落盘 memtable，返回新备份的信息
+pub fn create_backup_of(db: &DBWithThreadMode<MultiThreaded>, dir: &str) -> Result<BackupInfo> {
+    let mut engine = open_backup_engine(dir)?;
+    engine.create_new_backup_flush(db, true)?;
+    latest_backup(&engine, dir)
+}
+
+/// dir 中的全部备份，按 id 升序；目录不存在时为空
+pub fn list_backups(dir: &str) -> Result<Vec<BackupInfo>> {
+    if !Path::new(dir).exists() {
+        return Ok(vec![]);
+    }
+    let engine = open_backup_engine(dir)?;
+    let mut backups = engine
+        .get_backup_info()
+        .iter()
+        .map(BackupInfo::from)
+        .collect::<Vec<BackupInfo>>();
+    backups.sort_by_key(|b| b.backup_id);
+    Ok(backups)
+}
+
+/// 保留最近 keep 个备份，0 表示不清理
+pub fn purge_backups(dir: &str, keep: usize) -> Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    let mut engine = open_backup_engine(dir)?;
+    engine.purge_old_backups(keep)?;
+    Ok(())
+}
+
+/// 以最新的备份恢复到 target_path，目标库不可处于打开状态
+pub fn restore_latest(dir: &str, target_path: &str) -> Result<BackupInfo> {
+    if !Path::new(dir).exists() {
+        return Err(anyhow!("backup dir {} not exist", dir));
+    }
+    let mut engine = open_backup_engine(dir)?;
+    let latest = latest_backup(&engine, dir)?;
+    std::fs::create_dir_all(target_path)?;
+    engine.restore_from_latest_backup(target_path, target_path, &RestoreOptions::default())?;
+    Ok(latest)
+}
+
+/// 以指定 id 的备份恢复到 target_path，目标库不可处于打开状态
+pub fn restore_backup(dir: &str, backup_id: u32, target_path: &str) -> Result<BackupInfo> {
+    if !Path::new(dir).exists() {
+        return Err(anyhow!("backup dir {} not exist", dir));
+    }
+    let mut engine = open_backup_engine(dir)?;
+    let backup = engine
+        .get_backup_info()
+        .iter()
+        .find(|info| info.backup_id == backup_id)
+        .map(BackupInfo::from)
+        .ok_or_else(|| anyhow!("backup {} not found in {}", backup_id, dir))?;
+    std::fs::create_dir_all(target_path)?;
+    engine.restore_from_backup(
+        target_path,
+        target_path,
+        &RestoreOptions::default(),
+        backup_id,
+    )?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_backup_of, list_backups, purge_backups, restore_backup, restore_latest};
+    use crate::resources::{init_rocksdb, CF_TASK, CF_TASK_CHECKPOINTS};
+    use crate::tasks::{CheckPoint, Task, TransferTask};
+
+    //cargo test resources::backup::test::test_backup_restore -- --nocapture
+    #[test]
+    fn test_backup_restore() {
+        let root = "/tmp/backup_restore_test";
+        let _ = std::fs::remove_dir_all(root);
+        let db_path = format!("{}/rocksdb", root);
+        let backup_dir = format!("{}/backup", root);
+        assert!(list_backups(&backup_dir).unwrap().is_empty());
+
+        let task = Task::Transfer(TransferTask::default());
+        let checkpoint = CheckPoint {
+            task_id: "t1".to_string(),
+            ..CheckPoint::default()
+        };
+        let db = init_rocksdb(&db_path).unwrap();
+        let cf = db.cf_handle(CF_TASK).unwrap();
+        db.put_cf(&cf, "t1", serde_json::to_string(&task).unwrap())
+            .unwrap();
+        let cf = db.cf_handle(CF_TASK_CHECKPOINTS).unwrap();
+        db.put_cf(&cf, "t1", bincode::serialize(&checkpoint).unwrap())
+            .unwrap();
+        let first = create_backup_of(&db, &backup_dir).unwrap();
+        let cf = db.cf_handle(CF_TASK).unwrap();
+        db.put_cf(&cf, "t2", serde_json::to_string(&task).unwrap())
+            .unwrap();
+        let second = create_backup_of(&db, &backup_dir).unwrap();
+        assert!(second.backup_id > first.backup_id);
+        drop(db);
+
+        // 按 id 恢复较早的备份，其后写入的任务不在其中
+        let earlier_path = format!("{}/earlier", root);
+        let restored = restore_backup(&backup_dir, first.backup_id, &earlier_path).unwrap();
+        assert_eq!(restored, first);
+        let earlier = init_rocksdb(&earlier_path).unwrap();
+        let cf = earlier.cf_handle(CF_TASK).unwrap();
+        assert!(earlier.get_cf(&cf, "t1").unwrap().is_some());
+        assert!(earlier.get_cf(&cf, "t2").unwrap().is_none());
+        drop(earlier);
+        assert!(restore_backup(&backup_dir, 999, &earlier_path).is_err());
+
+        let db = init_rocksdb(&db_path).unwrap();
+        purge_backups(&backup_dir, 1).unwrap();
+        let backups = list_backups(&backup_dir).unwrap();
+        assert_eq!(backups, vec![second.clone()]);
+        drop(db);
+
+        // 损坏原库后无法打开，恢复后任务与 checkpoint 仍在
+        std::fs::write(format!("{}/CURRENT", db_path), "corrupted\n").unwrap();
+        assert!(init_rocksdb(&db_path).is_err());
+        let restored = restore_latest(&backup_dir, &db_path).unwrap();
+        assert_eq!(restored.backup_id, second.backup_id);
+
+        let db = init_rocksdb(&db_path).unwrap();
+        let cf = db.cf_handle(CF_TASK).unwrap();
+        let value = db.get_cf(&cf, "t1").unwrap().unwrap();
+        let restored_task = serde_json::from_slice::<Task>(&value).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored_task).unwrap(),
+            serde_json::to_value(&task).unwrap()
+        );
+        let cf = db.cf_handle(CF_TASK_CHECKPOINTS).unwrap();
+        let value = db.get_cf(&cf, "t1").unwrap().unwrap();
+        assert_eq!(
+            bincode::deserialize::<CheckPoint>(&value).unwrap(),
+            checkpoint
+        );
+        drop(db);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
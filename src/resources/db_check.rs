@@ -0,0 +1,198 @@
+use super::{
+    decode_task_status, scan_task_statuses_in, ResourceError, ResourceResult, CF_TASK_STATUS,
+    GLOBAL_ROCKSDB,
+};
+use once_cell::sync::Lazy;
+use rocksdb::{DBWithThreadMode, MultiThreaded, WriteBatch};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// 无法解码的记录，error 为解码错误
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CorruptRow {
+    pub key: String,
+    pub error: String,
+}
+
+/// 检查结果
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DbCheckReport {
+    pub corrupt_statuses: Vec<CorruptRow>,
+}
+
+/// 修复结果，remaining 为删除后仍无法解码的记录
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DbRepairReport {
+    pub deleted: Vec<String>,
+    pub remaining: Vec<CorruptRow>,
+}
+
+// 最近一次扫描中无法解码的任务状态，key 为任务状态的 key，value 为解码错误
+static CORRUPT_TASK_STATUSES: Lazy<Mutex<BTreeMap<String, String>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+// 上次扫描未出现或错误发生变化的记录
+fn unreported<'a>(
+    reported: &BTreeMap<String, String>,
+    corrupt: &'a [CorruptRow],
+) -> Vec<&'a CorruptRow> {
+    corrupt
+        .iter()
+        .filter(|row| reported.get(&row.key) != Some(&row.error))
+        .collect()
+}
+
+/// 记录本次扫描的损坏记录，同一记录只在首次出现时告警，避免定时扫描重复输出
+pub fn report_corrupt_task_statuses(corrupt: &[CorruptRow]) {
+    let mut reported = CORRUPT_TASK_STATUSES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    for row in unreported(&reported, corrupt) {
+        log::warn!(
+            "{}, skipped",
+            ResourceError::corrupt(CF_TASK_STATUS, &row.key, row.error.clone())
+        );
+    }
+    *reported = corrupt
+        .iter()
+        .map(|row| (row.key.clone(), row.error.clone()))
+        .collect();
+}
+
+/// 最近一次扫描中无法解码的任务状态数
+pub fn corrupt_task_status_count() -> usize {
+    CORRUPT_TASK_STATUSES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .len()
+}
+
+/// 检查 GLOBAL_ROCKSDB 中无法解码的任务状态
+pub fn check_rocksdb() -> ResourceResult<DbCheckReport> {
+    let scan = scan_task_statuses_in(GLOBAL_ROCKSDB.get()?.as_ref())?;
+    report_corrupt_task_statuses(&scan.corrupt);
+    Ok(DbCheckReport {
+        corrupt_statuses: scan.corrupt,
+    })
+}
+
+/// 删除 keys 中仍无法解码的任务状态，返回删除的 key；已恢复或不存在的 key 跳过
+pub fn delete_corrupt_task_statuses_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+    keys: &[String],
+) -> ResourceResult<Vec<String>> {
+    let cf = match db.cf_handle(CF_TASK_STATUS) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_STATUS)),
+    };
+    let mut batch = WriteBatch::default();
+    let mut deleted = vec![];
+    for key in keys {
+        let value = db
+            .get_cf(&cf, key)
+            .map_err(|e| ResourceError::rocksdb("get", CF_TASK_STATUS, key, e))?;
+        if let Some(v) = value {
+            if decode_task_status(&v).is_err() {
+                batch.delete_cf(&cf, key);
+                deleted.push(key.clone());
+            }
+        }
+    }
+    db.write(batch).map_err(|e| {
+        ResourceError::io(
+            format!(
+                "delete {} corrupt keys in column family {}",
+                deleted.len(),
+                CF_TASK_STATUS
+            ),
+            e,
+        )
+    })?;
+    Ok(deleted)
+}
+
+/// 删除操作员确认的损坏记录后重新检查
+pub fn repair_rocksdb(keys: &[String]) -> ResourceResult<DbRepairReport> {
+    let deleted = delete_corrupt_task_statuses_in(GLOBAL_ROCKSDB.get()?.as_ref(), keys)?;
+    for key in deleted.iter() {
+        log::warn!(
+            "corrupt key {} in column family {} deleted",
+            key,
+            CF_TASK_STATUS
+        );
+    }
+    let report = check_rocksdb()?;
+    Ok(DbRepairReport {
+        deleted,
+        remaining: report.corrupt_statuses,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{delete_corrupt_task_statuses_in, unreported, CorruptRow};
+    use crate::resources::{
+        encode_task_status, init_rocksdb, scan_task_statuses_in, CF_TASK_STATUS,
+    };
+    use crate::tasks::{Status, TaskStatus, TransferStage, TransferStatus};
+    use std::collections::BTreeMap;
+
+    fn row(key: &str, error: &str) -> CorruptRow {
+        CorruptRow {
+            key: key.to_string(),
+            error: error.to_string(),
+        }
+    }
+
+    //cargo test resources::db_check::test::test_unreported -- --nocapture
+    #[test]
+    fn test_unreported() {
+        let corrupt = vec![row("t1", "bad"), row("t2", "bad")];
+        let mut reported = BTreeMap::new();
+        assert_eq!(unreported(&reported, &corrupt).len(), 2);
+        reported.insert("t1".to_string(), "bad".to_string());
+        assert_eq!(unreported(&reported, &corrupt), vec![&corrupt[1]]);
+        // 错误变化时再次告警
+        reported.insert("t2".to_string(), "other".to_string());
+        assert_eq!(unreported(&reported, &corrupt), vec![&corrupt[1]]);
+        reported.insert("t2".to_string(), "bad".to_string());
+        assert!(unreported(&reported, &corrupt).is_empty());
+    }
+
+    //cargo test resources::db_check::test::test_corrupt_task_statuses -- --nocapture
+    #[test]
+    fn test_corrupt_task_statuses() {
+        let db_path = "/tmp/db_check_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/db_check_test");
+        let db = init_rocksdb(db_path).unwrap();
+        let cf = db.cf_handle(CF_TASK_STATUS).unwrap();
+        let status = TaskStatus {
+            task_id: "good".to_string(),
+            start_time: 1000,
+            status: Status::Transfer(TransferStatus::Running(TransferStage::Stock)),
+            last_skip_reason: None,
+            run_id: None,
+        };
+        db.put_cf(&cf, "good", encode_task_status(&status).unwrap())
+            .unwrap();
+        db.put_cf(&cf, "bad", b"\xffMVE\x09").unwrap();
+
+        // 损坏的记录不影响其他记录
+        let scan = scan_task_statuses_in(&db).unwrap();
+        assert_eq!(scan.statuses.len(), 1);
+        assert_eq!(scan.statuses[0].task_id, "good");
+        assert_eq!(scan.corrupt.len(), 1);
+        assert_eq!(scan.corrupt[0].key, "bad");
+
+        // 只删除仍无法解码的记录
+        let keys = ["bad", "good", "missing"].map(|k| k.to_string());
+        let deleted = delete_corrupt_task_statuses_in(&db, &keys).unwrap();
+        assert_eq!(deleted, vec!["bad"]);
+        let scan = scan_task_statuses_in(&db).unwrap();
+        assert_eq!(scan.statuses.len(), 1);
+        assert!(scan.corrupt.is_empty());
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/db_check_test");
+    }
+}
@@ -0,0 +1,430 @@
+use super::{
+    create_backup, prune_rocksdb, purge_backups, DbPruneOptions, DbPruneReport, GLOBAL_ROCKSDB,
+    ROCKSDB_COLUMN_FAMILIES,
+};
+use crate::commons::CronSchedule;
+use crate::configure::get_config;
+use crate::server::rocksdb_cf_sizes;
+use crate::tasks::{rebuild_task_name_index, GLOBAL_TASK_RUNTIME};
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// 内存中保留的已结束作业数量
+const DB_JOBS_KEEP: usize = 32;
+
+// 定时备份关闭时重新读取配置的间隔，配置重新加载后生效
+const DB_BACKUP_DISABLED_RECHECK: Duration = Duration::from_secs(60);
+
+// 定时压缩重新读取配置的间隔，等待下次压缩期间同样按此间隔检查
+const DB_COMPACTION_RECHECK: Duration = Duration::from_secs(60);
+
+static GLOBAL_DB_JOBS: Lazy<DashMap<String, DbJob>> = Lazy::new(DashMap::new);
+
+// 运行中的压缩、备份、清理或重建索引作业 id，同一时间只允许一个
+static DB_JOB_RUNNING: Mutex<Option<String>> = Mutex::new(None);
+
+// 最近一次成功的压缩
+static DB_LAST_COMPACTION: Mutex<Option<DbCompactionRecord>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DbJobKind {
+    Compact,
+    Backup,
+    Prune,
+    Reindex,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DbJobState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// 压缩、备份、清理或重建索引作业，steps 为已完成与总步骤数，压缩按 column family 计，其他为 1 步
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DbJob {
+    pub job_id: String,
+    pub kind: DbJobKind,
+    // 仅压缩指定的 column family 时有值
+    pub cf: Option<String>,
+    pub state: DbJobState,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub steps_done: usize,
+    pub steps_total: usize,
+    pub backup_id: Option<u32>,
+    // 备份大小，字节
+    pub backup_size: Option<u64>,
+    // 清理作业或定时压缩前清理的结果
+    pub pruned: Option<DbPruneReport>,
+    // 重建名称索引后的名称数
+    pub reindexed_names: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl DbJob {
+    pub fn new(job_id: &str, kind: DbJobKind, cf: Option<String>, steps_total: usize) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            kind,
+            cf,
+            state: DbJobState::Running,
+            started_at: now_secs(),
+            finished_at: None,
+            steps_done: 0,
+            steps_total,
+            backup_id: None,
+            backup_size: None,
+            pruned: None,
+            reindexed_names: None,
+            error: None,
+        }
+    }
+}
+
+/// 一次成功的压缩，包括手动与定时触发
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DbCompactionRecord {
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub cfs: usize,
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+fn lock_running() -> MutexGuard<'static, Option<String>> {
+    match DB_JOB_RUNNING.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    }
+}
+
+/// 占用作业名额，已有作业运行时返回其 id
+pub fn acquire_job_slot(slot: &mut Option<String>, job_id: &str) -> Result<(), String> {
+    match slot {
+        Some(running) => Err(running.clone()),
+        None => {
+            *slot = Some(job_id.to_string());
+            Ok(())
+        }
+    }
+}
+
+/// 释放作业名额，名额已被其他作业占用时不变
+pub fn release_job_slot(slot: &mut Option<String>, job_id: &str) {
+    if slot.as_deref() == Some(job_id) {
+        *slot = None;
+    }
+}
+
+/// 需压缩的 column family，未指定时为全部
+pub fn resolve_compact_cfs(cf: Option<&str>) -> Result<Vec<&'static str>> {
+    match cf {
+        None => Ok(ROCKSDB_COLUMN_FAMILIES.to_vec()),
+        Some(name) => match ROCKSDB_COLUMN_FAMILIES.iter().find(|c| **c == name) {
+            Some(c) => Ok(vec![*c]),
+            None => Err(anyhow!("column family {} not exist", name)),
+        },
+    }
+}
+
+pub fn get_db_job(job_id: &str) -> Option<DbJob> {
+    GLOBAL_DB_JOBS.get(job_id).map(|j| j.value().clone())
+}
+
+// 登记作业并占用名额，已有作业运行时返回其 id
+fn begin_db_job(kind: DbJobKind, cf: Option<String>, steps_total: usize) -> Result<DbJob, String> {
+    let job = DbJob::new(&uuid::Uuid::new_v4().to_string(), kind, cf, steps_total);
+    acquire_job_slot(&mut lock_running(), &job.job_id)?;
+    prune_db_jobs();
+    GLOBAL_DB_JOBS.insert(job.job_id.clone(), job.clone());
+    Ok(job)
+}
+
+fn update_db_job(job_id: &str, f: impl FnOnce(&mut DbJob)) {
+    if let Some(mut job) = GLOBAL_DB_JOBS.get_mut(job_id) {
+        f(job.value_mut());
+    }
+}
+
+fn finish_db_job(job_id: &str, result: Result<()>) {
+    update_db_job(job_id, |job| {
+        job.finished_at = Some(now_secs());
+        match result {
+            Ok(_) => {
+                job.state = DbJobState::Succeeded;
+                job.steps_done = job.steps_total;
+            }
+            Err(e) => {
+                log::error!("db {:?} job {} failed: {:#}", job.kind, job.job_id, e);
+                job.state = DbJobState::Failed;
+                job.error = Some(format!("{:#}", e));
+            }
+        }
+    });
+    release_job_slot(&mut lock_running(), job_id);
+}
+
+// 超出保留数量时删除最早结束的作业
+fn prune_db_jobs() {
+    let mut finished = GLOBAL_DB_JOBS
+        .iter()
+        .filter(|j| j.state != DbJobState::Running)
+        .map(|j| (j.started_at, j.job_id.clone()))
+        .collect::<Vec<(u64, String)>>();
+    if finished.len() < DB_JOBS_KEEP {
+        return;
+    }
+    finished.sort();
+    for (_, job_id) in finished.iter().take(finished.len() + 1 - DB_JOBS_KEEP) {
+        GLOBAL_DB_JOBS.remove(job_id);
+    }
+}
+
+/// 在阻塞线程中依次压缩 column family，立即返回作业；已有作业运行时返回其 id
+/// prune 有值时压缩前先清理，清理失败不影响压缩
+pub fn start_db_compaction(
+    cf: Option<String>,
+    cfs: Vec<&'static str>,
+    prune: Option<DbPruneOptions>,
+) -> Result<DbJob, String> {
+    let job = begin_db_job(DbJobKind::Compact, cf, cfs.len())?;
+    let job_id = job.job_id.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Some(options) = prune {
+            match prune_rocksdb(options) {
+                Ok(report) => update_db_job(&job_id, |job| job.pruned = Some(report)),
+                Err(e) => log::warn!("db prune before compaction error: {:#}", e),
+            }
+        }
+        let result = compact_column_families(&job_id, &cfs);
+        finish_db_job(&job_id, result);
+    });
+    Ok(job)
+}
+
+/// 在阻塞线程中清理孤立及过期的条目，立即返回作业；已有作业运行时返回其 id
+pub fn start_db_prune(options: DbPruneOptions) -> Result<DbJob, String> {
+    let job = begin_db_job(DbJobKind::Prune, None, 1)?;
+    let job_id = job.job_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = prune_rocksdb(options)
+            .map(|report| update_db_job(&job_id, |job| job.pruned = Some(report)));
+        finish_db_job(&job_id, result);
+    });
+    Ok(job)
+}
+
+/// 在阻塞线程中按 CF_TASK 重建名称索引，立即返回作业；已有作业运行时返回其 id
+pub fn start_db_reindex() -> Result<DbJob, String> {
+    let job = begin_db_job(DbJobKind::Reindex, None, 1)?;
+    let job_id = job.job_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = rebuild_task_name_index().map(|names| {
+            log::info!("task name index rebuilt, {} names", names);
+            update_db_job(&job_id, |job| job.reindexed_names = Some(names))
+        });
+        finish_db_job(&job_id, result);
+    });
+    Ok(job)
+}
+
+// 压缩前后记录各 column family 估算的大小
+fn compact_column_families(job_id: &str, cfs: &[&str]) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let begin = Instant::now();
+    let before = rocksdb_cf_sizes()
+        .into_iter()
+        .collect::<BTreeMap<&str, u64>>();
+    for (i, name) in cfs.iter().enumerate() {
+        let cf = match db.cf_handle(name) {
+            Some(cf) => cf,
+            None => return Err(anyhow!("column family not exist")),
+        };
+        db.compact_range_cf::<&[u8], &[u8]>(&cf, None, None);
+        update_db_job(job_id, |job| job.steps_done = i + 1);
+    }
+    let after = rocksdb_cf_sizes()
+        .into_iter()
+        .collect::<BTreeMap<&str, u64>>();
+    for name in cfs {
+        log::info!(
+            "compact {} estimated size {} -> {} bytes",
+            name,
+            before.get(name).copied().unwrap_or(0),
+            after.get(name).copied().unwrap_or(0)
+        );
+    }
+    let record = DbCompactionRecord {
+        finished_at: now_secs(),
+        duration_ms: begin.elapsed().as_millis() as u64,
+        cfs: cfs.len(),
+    };
+    match DB_LAST_COMPACTION.lock() {
+        Ok(mut last) => *last = Some(record),
+        Err(e) => *e.into_inner() = Some(record),
+    }
+    Ok(())
+}
+
+pub fn last_db_compaction() -> Option<DbCompactionRecord> {
+    match DB_LAST_COMPACTION.lock() {
+        Ok(last) => *last,
+        Err(e) => *e.into_inner(),
+    }
+}
+
+/// 按 cron 表达式计算 now 之后的下次压缩时间，表达式为空时不压缩
+pub fn next_db_compaction(schedule: &str, now: u64) -> Result<Option<u64>> {
+    if schedule.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(CronSchedule::parse(schedule)?.next_after(now))
+}
+
+/// 按 db.compaction_schedule 定时清理并压缩全部 column family，已有作业运行时跳过本轮
+/// 每隔 DB_COMPACTION_RECHECK 重新读取配置，表达式变化后重新计算下次压缩时间
+pub fn spawn_db_compaction_scheduler() -> Result<()> {
+    GLOBAL_TASK_RUNTIME.spawn(async move {
+        let mut schedule = String::new();
+        let mut next = None;
+        loop {
+            let db = get_config().map(|c| c.db).unwrap_or_default();
+            if db.compaction_schedule != schedule {
+                schedule = db.compaction_schedule.clone();
+                next = match next_db_compaction(&schedule, now_secs()) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        log::warn!(
+                            "db.compaction_schedule invalid, scheduled compaction off: {}",
+                            e
+                        );
+                        None
+                    }
+                };
+            }
+            let due = match next {
+                Some(due) => due,
+                None => {
+                    tokio::time::sleep(DB_COMPACTION_RECHECK).await;
+                    continue;
+                }
+            };
+            let now = now_secs();
+            if now < due {
+                tokio::time::sleep(Duration::from_secs(due - now).min(DB_COMPACTION_RECHECK)).await;
+                continue;
+            }
+            let prune = DbPruneOptions {
+                dry_run: db.prune_dry_run,
+                status_retention_days: db.status_retention_days,
+            };
+            match start_db_compaction(None, ROCKSDB_COLUMN_FAMILIES.to_vec(), Some(prune)) {
+                Ok(job) => log::info!("scheduled db compaction job {} started", job.job_id),
+                Err(running) => {
+                    log::info!("scheduled db compaction skipped, job {} running", running)
+                }
+            }
+            next = next_db_compaction(&schedule, now).unwrap_or(None);
+        }
+    })?;
+    Ok(())
+}
+
+/// 在阻塞线程中备份到 backup_dir，保留最近 keep 个备份，0 表示不清理；已有作业运行时返回其 id
+pub fn start_db_backup(backup_dir: String, keep: usize) -> Result<DbJob, String> {
+    let job = begin_db_job(DbJobKind::Backup, None, 1)?;
+    let job_id = job.job_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = backup_rocksdb(&backup_dir, keep).map(|(backup_id, size)| {
+            update_db_job(&job_id, |job| {
+                job.backup_id = Some(backup_id);
+                job.backup_size = Some(size);
+            })
+        });
+        finish_db_job(&job_id, result);
+    });
+    Ok(job)
+}
+
+// 返回新备份的 id 与大小
+fn backup_rocksdb(backup_dir: &str, keep: usize) -> Result<(u32, u64)> {
+    let info = create_backup(backup_dir)?;
+    purge_backups(backup_dir, keep)?;
+    Ok((info.backup_id, info.size))
+}
+
+/// 每隔 db.backup_interval_secs 提交备份作业，每轮读取配置，0 表示不备份
+pub fn spawn_db_backup_scheduler() -> Result<()> {
+    GLOBAL_TASK_RUNTIME.spawn(async move {
+        loop {
+            let db = get_config().map(|c| c.db).unwrap_or_default();
+            if db.backup_interval_secs == 0 {
+                tokio::time::sleep(DB_BACKUP_DISABLED_RECHECK).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(db.backup_interval_secs)).await;
+            match start_db_backup(db.backup_dir, db.backup_keep) {
+                Ok(job) => log::info!("scheduled db backup job {} started", job.job_id),
+                Err(running) => log::info!("scheduled db backup skipped, job {} running", running),
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{acquire_job_slot, next_db_compaction, release_job_slot, resolve_compact_cfs};
+    use crate::resources::{CF_TASK, ROCKSDB_COLUMN_FAMILIES};
+
+    //cargo test resources::db_maintenance::test::test_job_slot -- --nocapture
+    #[test]
+    fn test_job_slot() {
+        let mut slot = None;
+        assert!(acquire_job_slot(&mut slot, "a").is_ok());
+        // 运行中时拒绝并返回运行中的作业
+        assert_eq!(acquire_job_slot(&mut slot, "b"), Err("a".to_string()));
+        release_job_slot(&mut slot, "b");
+        assert_eq!(slot.as_deref(), Some("a"));
+        release_job_slot(&mut slot, "a");
+        assert!(acquire_job_slot(&mut slot, "b").is_ok());
+    }
+
+    //cargo test resources::db_maintenance::test::test_resolve_compact_cfs -- --nocapture
+    #[test]
+    fn test_resolve_compact_cfs() {
+        assert_eq!(
+            resolve_compact_cfs(None).unwrap().len(),
+            ROCKSDB_COLUMN_FAMILIES.len()
+        );
+        assert_eq!(resolve_compact_cfs(Some(CF_TASK)).unwrap(), vec![CF_TASK]);
+        assert!(resolve_compact_cfs(Some("cf_unknown")).is_err());
+    }
+
+    //cargo test resources::db_maintenance::test::test_next_db_compaction -- --nocapture
+    #[test]
+    fn test_next_db_compaction() {
+        // 2024-01-02T03:04:05Z
+        let now = 1704164645;
+        assert_eq!(next_db_compaction("", now).unwrap(), None);
+        assert_eq!(
+            next_db_compaction("0 * * * *", now).unwrap(),
+            Some(1704168000)
+        );
+        assert!(next_db_compaction("0 * *", now).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use super::{decode_task_status, CF_TASK, CF_TASK_CHECKPOINTS, CF_TASK_STATUS, GLOBAL_ROCKSDB};
+use crate::server::record_db_prune;
+use crate::tasks::{list_task_runs, TaskStatus};
+use anyhow::{anyhow, Result};
+use rocksdb::{DBWithThreadMode, IteratorMode, MultiThreaded, WriteBatch};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 清理参数，status_retention_days 为 0 时不清理已停止任务的状态
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DbPruneOptions {
+    pub dry_run: bool,
+    pub status_retention_days: u64,
+}
+
+/// 清理结果，dry_run 时为将要删除的条目
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DbPruneReport {
+    pub dry_run: bool,
+    // 任务已不存在的状态与 checkpoint
+    pub orphan_statuses: Vec<String>,
+    pub orphan_checkpoints: Vec<String>,
+    // 停止超过保留天数的任务状态
+    pub expired_statuses: Vec<String>,
+}
+
+impl DbPruneReport {
+    pub fn total(&self) -> usize {
+        self.orphan_statuses.len() + self.orphan_checkpoints.len() + self.expired_statuses.len()
+    }
+}
+
+fn cf_keys(db: &DBWithThreadMode<MultiThreaded>, cf_name: &str) -> Result<Vec<String>> {
+    let cf = match db.cf_handle(cf_name) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut keys = vec![];
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (k, _) = item?;
+        keys.push(String::from_utf8_lossy(&k).to_string());
+    }
+    Ok(keys)
+}
+
+// 无法解析的状态不按保留天数清理
+fn task_statuses(
+    db: &DBWithThreadMode<MultiThreaded>,
+) -> Result<Vec<(String, Option<TaskStatus>)>> {
+    let cf = match db.cf_handle(CF_TASK_STATUS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut statuses = vec![];
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (k, v) = item?;
+        statuses.push((
+            String::from_utf8_lossy(&k).to_string(),
+            decode_task_status(&v).ok(),
+        ));
+    }
+    Ok(statuses)
+}
+
+/// 查找需清理的条目，stopped_at 为已停止任务的停止时间
+/// 任务定义先于状态与 checkpoint 写入，因此先读取状态与 checkpoint 再读取任务，避免新建任务的条目被误判为孤立
+pub fn plan_db_prune(
+    db: &DBWithThreadMode<MultiThreaded>,
+    options: DbPruneOptions,
+    now: u64,
+    stopped_at: impl Fn(&TaskStatus) -> u64,
+) -> Result<DbPruneReport> {
+    let statuses = task_statuses(db)?;
+    let checkpoints = cf_keys(db, CF_TASK_CHECKPOINTS)?;
+    let tasks = cf_keys(db, CF_TASK)?
+        .into_iter()
+        .collect::<HashSet<String>>();
+    let retention_secs = options.status_retention_days * 86400;
+    let mut report = DbPruneReport {
+        dry_run: options.dry_run,
+        ..DbPruneReport::default()
+    };
+    for (task_id, status) in statuses {
+        if !tasks.contains(&task_id) {
+            report.orphan_statuses.push(task_id);
+            continue;
+        }
+        let expired = match status {
+            Some(s) if retention_secs > 0 && s.is_stopped() => {
+                now.saturating_sub(stopped_at(&s)) > retention_secs
+            }
+            _ => false,
+        };
+        if expired {
+            report.expired_statuses.push(task_id);
+        }
+    }
+    report.orphan_checkpoints = checkpoints
+        .into_iter()
+        .filter(|task_id| !tasks.contains(task_id))
+        .collect();
+    Ok(report)
+}
+
+/// 删除清理结果中的条目
+pub fn apply_db_prune(db: &DBWithThreadMode<MultiThreaded>, report: &DbPruneReport) -> Result<()> {
+    let mut batch = WriteBatch::default();
+    for (cf_name, keys) in [
+        (CF_TASK_STATUS, &report.orphan_statuses),
+        (CF_TASK_STATUS, &report.expired_statuses),
+        (CF_TASK_CHECKPOINTS, &report.orphan_checkpoints),
+    ] {
+        let cf = match db.cf_handle(cf_name) {
+            Some(cf) => cf,
+            None => return Err(anyhow!("column family not exist")),
+        };
+        for key in keys {
+            batch.delete_cf(&cf, key);
+        }
+    }
+    db.write(batch)?;
+    Ok(())
+}
+
+// 停止时间取最近一次运行的结束时间，无运行记录时取启动时间
+fn task_stopped_at(status: &TaskStatus) -> u64 {
+    list_task_runs(&status.task_id)
+        .ok()
+        .and_then(|runs| runs.first().and_then(|r| r.end_ts))
+        .unwrap_or(status.start_time)
+}
+
+/// 清理 GLOBAL_ROCKSDB，dry_run 时只返回将要删除的条目
+pub fn prune_rocksdb(options: DbPruneOptions) -> Result<DbPruneReport> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let report = plan_db_prune(db.as_ref(), options, now, task_stopped_at)?;
+    if !options.dry_run {
+        apply_db_prune(db.as_ref(), &report)?;
+    }
+    log::info!(
+        "db prune{}: {} orphan statuses, {} orphan checkpoints, {} expired statuses",
+        match options.dry_run {
+            true => " (dry run)",
+            false => "",
+        },
+        report.orphan_statuses.len(),
+        report.orphan_checkpoints.len(),
+        report.expired_statuses.len()
+    );
+    record_db_prune(&report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_db_prune, cf_keys, plan_db_prune, DbPruneOptions};
+    use crate::resources::{init_rocksdb, CF_TASK, CF_TASK_CHECKPOINTS, CF_TASK_STATUS};
+    use crate::tasks::{
+        CheckPoint, Status, TaskStatus, TaskStopReason, TransferStage, TransferStatus,
+    };
+
+    fn status(task_id: &str, status: TransferStatus) -> Vec<u8> {
+        bincode::serialize(&TaskStatus {
+            task_id: task_id.to_string(),
+            start_time: 1000,
+            status: Status::Transfer(status),
+            last_skip_reason: None,
+            run_id: None,
+        })
+        .unwrap()
+    }
+
+    //cargo test resources::db_prune::test::test_db_prune_orphans -- --nocapture
+    #[test]
+    fn test_db_prune_orphans() {
+        let db_path = "/tmp/db_prune_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/db_prune_test");
+        let db = init_rocksdb(db_path).unwrap();
+        let task_cf = db.cf_handle(CF_TASK).unwrap();
+        let status_cf = db.cf_handle(CF_TASK_STATUS).unwrap();
+        let checkpoint_cf = db.cf_handle(CF_TASK_CHECKPOINTS).unwrap();
+        // live 与 stopped 为存在的任务，gone 为已删除任务遗留的状态与 checkpoint
+        for task_id in ["live", "stopped"] {
+            db.put_cf(&task_cf, task_id, "{}").unwrap();
+        }
+        db.put_cf(
+            &status_cf,
+            "live",
+            status("live", TransferStatus::Running(TransferStage::Stock)),
+        )
+        .unwrap();
+        db.put_cf(
+            &status_cf,
+            "stopped",
+            status("stopped", TransferStatus::Stopped(TaskStopReason::Finish)),
+        )
+        .unwrap();
+        db.put_cf(
+            &status_cf,
+            "gone",
+            status("gone", TransferStatus::Stopped(TaskStopReason::Finish)),
+        )
+        .unwrap();
+        for task_id in ["live", "gone"] {
+            let checkpoint = CheckPoint {
+                task_id: task_id.to_string(),
+                ..CheckPoint::default()
+            };
+            db.put_cf(
+                &checkpoint_cf,
+                task_id,
+                bincode::serialize(&checkpoint).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let options = DbPruneOptions {
+            dry_run: true,
+            status_retention_days: 0,
+        };
+        let report = plan_db_prune(&db, options, 100000, |s| s.start_time).unwrap();
+        assert_eq!(report.orphan_statuses, vec!["gone"]);
+        assert_eq!(report.orphan_checkpoints, vec!["gone"]);
+        assert!(report.expired_statuses.is_empty());
+
+        // 停止超过保留天数的状态清理，运行中的不清理
+        let options = DbPruneOptions {
+            dry_run: false,
+            status_retention_days: 1,
+        };
+        let report = plan_db_prune(&db, options, 1000 + 86400 + 1, |s| s.start_time).unwrap();
+        assert_eq!(report.expired_statuses, vec!["stopped"]);
+        assert_eq!(report.total(), 3);
+        let report = plan_db_prune(&db, options, 1000 + 86400, |s| s.start_time).unwrap();
+        assert!(report.expired_statuses.is_empty());
+
+        let report = plan_db_prune(&db, options, 1000 + 86400 + 1, |s| s.start_time).unwrap();
+        apply_db_prune(&db, &report).unwrap();
+        assert_eq!(cf_keys(&db, CF_TASK_STATUS).unwrap(), vec!["live"]);
+        assert_eq!(cf_keys(&db, CF_TASK_CHECKPOINTS).unwrap(), vec!["live"]);
+        assert_eq!(cf_keys(&db, CF_TASK).unwrap(), vec!["live", "stopped"]);
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/db_prune_test");
+    }
+}
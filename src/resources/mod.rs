@@ -1,5 +1,19 @@
+mod backup;
+mod db_check;
+mod db_maintenance;
+mod db_prune;
 mod init_resources;
+mod resource_error;
 mod resource_rocksdb;
+mod stored_format;
+mod write_latency;
 
+pub use backup::*;
+pub use db_check::*;
+pub use db_maintenance::*;
+pub use db_prune::*;
 pub use init_resources::*;
+pub use resource_error::*;
 pub use resource_rocksdb::*;
+pub use stored_format::*;
+pub use write_latency::*;
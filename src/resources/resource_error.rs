@@ -0,0 +1,120 @@
+use thiserror::Error;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type ResourceResult<T> = std::result::Result<T, ResourceError>;
+
+/// 资源层错误，调用方按变体区分记录不存在、记录损坏与存储故障
+/// 涉及具体记录时错误信息包含 column family 与 key
+#[derive(Debug, Error)]
+pub enum ResourceError {
+    #[error("rocksdb path not configured")]
+    PathNotConfigured,
+    #[error("rocksdb not opened")]
+    NotOpened,
+    // 调用方传入的参数不合法，如会删除整个 column family 的空前缀
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("column family {cf} not exist")]
+    ColumnFamilyMissing { cf: String },
+    #[error("key {key} not found in column family {cf}")]
+    KeyNotFound { cf: String, key: String },
+    // 值无法解码，如更高版本写入的格式或被截断的记录
+    #[error("key {key} in column family {cf} is corrupt: {source}")]
+    Corrupt {
+        cf: String,
+        key: String,
+        #[source]
+        source: BoxError,
+    },
+    // rocksdb 或文件系统操作失败，context 为操作及涉及的 column family、key 或路径
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("serialize key {key} for column family {cf} error: {source}")]
+    Serialization {
+        cf: String,
+        key: String,
+        #[source]
+        source: BoxError,
+    },
+}
+
+impl ResourceError {
+    pub fn cf_missing(cf: &str) -> Self {
+        Self::ColumnFamilyMissing { cf: cf.to_string() }
+    }
+
+    pub fn not_found(cf: &str, key: &str) -> Self {
+        Self::KeyNotFound {
+            cf: cf.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    pub fn corrupt(cf: &str, key: &str, source: impl Into<BoxError>) -> Self {
+        Self::Corrupt {
+            cf: cf.to_string(),
+            key: key.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn io(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        Self::Io {
+            context: context.into(),
+            source: source.into(),
+        }
+    }
+
+    /// 单条记录的 rocksdb 读写失败，op 为 get、put、delete 等
+    pub fn rocksdb(op: &str, cf: &str, key: &str, source: rocksdb::Error) -> Self {
+        Self::io(
+            format!("{} key {} in column family {}", op, key, cf),
+            source,
+        )
+    }
+
+    pub fn serialization(cf: &str, key: &str, source: impl Into<BoxError>) -> Self {
+        Self::Serialization {
+            cf: cf.to_string(),
+            key: key.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::KeyNotFound { .. })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ResourceError;
+    use crate::resources::CF_TASK_STATUS;
+    use anyhow::anyhow;
+
+    //cargo test resources::resource_error::test::test_resource_error_message -- --nocapture
+    #[test]
+    fn test_resource_error_message() {
+        let e = ResourceError::not_found(CF_TASK_STATUS, "t1");
+        assert!(e.is_not_found());
+        assert_eq!(
+            e.to_string(),
+            "key t1 not found in column family cf_task_status"
+        );
+        let e = ResourceError::corrupt(CF_TASK_STATUS, "t1", anyhow!("bad envelope"));
+        assert!(!e.is_not_found());
+        assert_eq!(
+            e.to_string(),
+            "key t1 in column family cf_task_status is corrupt: bad envelope"
+        );
+        assert!(std::error::Error::source(&e).is_some());
+        // 经 anyhow 传递后仍可还原为变体
+        let e = anyhow::Error::from(ResourceError::not_found(CF_TASK_STATUS, "t1"));
+        assert!(e.downcast_ref::<ResourceError>().unwrap().is_not_found());
+    }
+}
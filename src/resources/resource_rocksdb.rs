@@ -1,32 +1,155 @@
+use super::{
+    decode_checkpoint, decode_task_status, encode_checkpoint, encode_task_status,
+    record_write_latency, report_corrupt_task_statuses, CorruptRow, ResourceError, ResourceResult,
+    WriteKind,
+};
 use crate::commons::json_to_struct;
+use crate::configure::{get_config, Config, TaskConfig};
 use crate::tasks::CheckPoint;
 use crate::tasks::Task;
 use crate::tasks::TaskStatus;
-use anyhow::anyhow;
-use anyhow::Result;
-use once_cell::sync::Lazy;
-use rocksdb::IteratorMode;
-use rocksdb::{DBWithThreadMode, MultiThreaded, Options};
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use once_cell::sync::{Lazy, OnceCell};
+use rocksdb::{DBWithThreadMode, MultiThreaded, Options, WriteBatch};
+use rocksdb::{Direction, IteratorMode};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 pub const CF_TASK_CHECKPOINTS: &'static str = "cf_task_checkpoints";
 pub const CF_TASK: &'static str = "cf_task";
 pub const CF_TASK_STATUS: &'static str = "cf_task_status";
-pub static GLOBAL_ROCKSDB: Lazy<Arc<DBWithThreadMode<MultiThreaded>>> = Lazy::new(|| {
-    let rocksdb = match init_rocksdb("oss_pipe_rocksdb") {
-        Ok(db) => db,
-        Err(err) => panic!("{}", err),
+pub const CF_SELF_STATS: &'static str = "cf_self_stats";
+pub const CF_TASK_RUN_DEFINITION: &'static str = "cf_task_run_definition";
+pub const CF_TASK_CHANGES: &'static str = "cf_task_changes";
+pub const CF_SERVER_META: &'static str = "cf_server_meta";
+pub const CF_TASK_QUEUE: &'static str = "cf_task_queue";
+pub const CF_IDEMPOTENCY_KEYS: &'static str = "cf_idempotency_keys";
+pub const CF_TASK_RUNS: &'static str = "cf_task_runs";
+// 名称索引，key 为 {name}\0{task_id}，value 为空
+pub const CF_TASK_NAME_IDX: &'static str = "cf_task_name_idx";
+// checkpoint 历史，key 为 {task_id}:{timestamp}，每个任务只保留最近的若干条
+pub const CF_CHECKPOINT_HISTORY: &'static str = "cf_checkpoint_history";
+// 全部 column family，打开数据库、落盘及统计大小时按此顺序遍历
+pub const ROCKSDB_COLUMN_FAMILIES: [&'static str; 12] = [
+    CF_TASK_CHECKPOINTS,
+    CF_TASK,
+    CF_TASK_STATUS,
+    CF_SELF_STATS,
+    CF_TASK_RUN_DEFINITION,
+    CF_TASK_CHANGES,
+    CF_SERVER_META,
+    CF_TASK_QUEUE,
+    CF_IDEMPOTENCY_KEYS,
+    CF_TASK_RUNS,
+    CF_TASK_NAME_IDX,
+    CF_CHECKPOINT_HISTORY,
+];
+// 旧版本在当前目录下使用的 rocksdb 目录
+pub const LEGACY_ROCKSDB_PATH: &'static str = "oss_pipe_rocksdb";
+
+// GLOBAL_ROCKSDB 首次访问时使用的路径，为空表示尚未设置
+static ROCKSDB_PATH: Lazy<RwLock<String>> = Lazy::new(|| RwLock::new(String::new()));
+
+// 打开 rocksdb 时是否启用自动压缩，默认关闭，由定时压缩回收空间
+static ROCKSDB_AUTO_COMPACTION: AtomicBool = AtomicBool::new(false);
+
+// 由 open_global_rocksdb 打开，之后不再变化
+static ROCKSDB: OnceCell<Arc<DBWithThreadMode<MultiThreaded>>> = OnceCell::new();
+
+/// 全局 rocksdb，start 流程调用 open_global_rocksdb 打开，未打开时访问返回 NotOpened
+pub struct GlobalRocksdb(&'static OnceCell<Arc<DBWithThreadMode<MultiThreaded>>>);
+
+impl GlobalRocksdb {
+    pub fn get(&self) -> ResourceResult<&'static Arc<DBWithThreadMode<MultiThreaded>>> {
+        self.0.get().ok_or(ResourceError::NotOpened)
+    }
+}
+
+pub static GLOBAL_ROCKSDB: GlobalRocksdb = GlobalRocksdb(&ROCKSDB);
+
+/// 按已设置的路径打开全局 rocksdb，已打开时直接返回
+pub fn open_global_rocksdb() -> ResourceResult<()> {
+    ROCKSDB.get_or_try_init(|| {
+        let path = get_rocksdb_path();
+        if path.is_empty() {
+            return Err(ResourceError::PathNotConfigured);
+        }
+        init_rocksdb(&path).map(Arc::new)
+    })?;
+    Ok(())
+}
+
+/// 单元测试共用的全局 rocksdb，每个测试进程使用同一个临时目录
+/// 全局 rocksdb 只能打开一次，依赖 GLOBAL_ROCKSDB 的测试均经此打开，不再各自设置路径
+#[cfg(test)]
+pub fn open_test_rocksdb() -> &'static Arc<DBWithThreadMode<MultiThreaded>> {
+    ROCKSDB.get_or_init(|| {
+        let path = format!(
+            "{}/mario_test_{}/rocksdb",
+            std::env::temp_dir().display(),
+            std::process::id()
+        );
+        set_rocksdb_path(&path);
+        Arc::new(init_rocksdb(&path).expect("open test rocksdb error!"))
+    })
+}
+
+pub fn set_rocksdb_path(path: &str) {
+    let mut p = ROCKSDB_PATH.write().expect("set rocksdb path error!");
+    p.clear();
+    p.push_str(path);
+}
+
+/// 已打开或将要打开的 rocksdb 是否启用自动压缩
+pub fn rocksdb_auto_compaction() -> bool {
+    ROCKSDB_AUTO_COMPACTION.load(Ordering::SeqCst)
+}
+
+pub fn get_rocksdb_path() -> String {
+    ROCKSDB_PATH
+        .read()
+        .expect("get rocksdb path error!")
+        .clone()
+}
+
+/// 按已加载的配置设置 rocksdb 路径，需在 open_global_rocksdb 之前调用
+/// 已设置时不覆盖，rocksdb 打开后路径不可变
+pub fn init_rocksdb_with_config(config: &Config) {
+    ROCKSDB_AUTO_COMPACTION.store(config.db.auto_compaction, Ordering::SeqCst);
+    if !get_rocksdb_path().is_empty() {
+        return;
+    }
+    let path = config.rocksdb_path();
+    if let Some(legacy) = legacy_rocksdb_dir(&path) {
+        log::warn!(
+            "legacy rocksdb dir {} found but {} not exist, move it to {} to keep existing tasks",
+            legacy,
+            path,
+            path
+        );
+    }
+    set_rocksdb_path(&path);
+}
+
+/// 旧目录存在而新目录不存在时返回旧目录，需迁移
+pub fn legacy_rocksdb_dir(path: &str) -> Option<String> {
+    let legacy = match std::env::current_dir() {
+        Ok(d) => d.join(LEGACY_ROCKSDB_PATH),
+        Err(_) => return None,
     };
-    Arc::new(rocksdb)
-});
+    match legacy.is_dir() && !Path::new(path).exists() {
+        true => Some(legacy.to_string_lossy().to_string()),
+        false => None,
+    }
+}
 
-pub fn init_rocksdb(db_path: &str) -> Result<DBWithThreadMode<MultiThreaded>> {
+pub fn init_rocksdb(db_path: &str) -> ResourceResult<DBWithThreadMode<MultiThreaded>> {
     let mut cf_opts = Options::default();
     cf_opts.set_allow_concurrent_memtable_write(true);
     cf_opts.set_max_write_buffer_number(16);
     cf_opts.set_write_buffer_size(128 * 1024 * 1024);
-    cf_opts.set_disable_auto_compactions(true);
+    cf_opts.set_disable_auto_compactions(!ROCKSDB_AUTO_COMPACTION.load(Ordering::SeqCst));
 
     let mut db_opts = Options::default();
     db_opts.create_missing_column_families(true);
@@ -35,100 +158,1079 @@ pub fn init_rocksdb(db_path: &str) -> Result<DBWithThreadMode<MultiThreaded>> {
     let db = DBWithThreadMode::<MultiThreaded>::open_cf_with_opts(
         &db_opts,
         db_path,
-        vec![
-            (CF_TASK_CHECKPOINTS, cf_opts.clone()),
-            (CF_TASK, cf_opts.clone()),
-            (CF_TASK_STATUS, cf_opts.clone()),
-        ],
-    )?;
+        ROCKSDB_COLUMN_FAMILIES
+            .iter()
+            .map(|cf| (*cf, cf_opts.clone()))
+            .collect::<Vec<_>>(),
+    )
+    .map_err(|e| {
+        // 文件锁被占用时多为另一实例正在使用同一目录
+        let context = match e.to_string().contains("lock") {
+            true => format!(
+                "cannot open rocksdb at {}, is another instance running?",
+                db_path
+            ),
+            false => format!("cannot open rocksdb at {}", db_path),
+        };
+        ResourceError::io(context, e)
+    })?;
     Ok(db)
 }
 
-pub fn save_checkpoint_to_cf(checkpoint: &mut CheckPoint) -> Result<()> {
-    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
-    checkpoint.modify_checkpoint_timestamp = i128::from(now.as_secs());
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_CHECKPOINTS) {
+/// 将各 column family 的 memtable 落盘，用于停机前持久化
+pub fn flush_rocksdb() -> ResourceResult<()> {
+    for cf_name in ROCKSDB_COLUMN_FAMILIES {
+        let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(cf_name) {
+            Some(cf) => cf,
+            None => return Err(ResourceError::cf_missing(cf_name)),
+        };
+        GLOBAL_ROCKSDB
+            .get()?
+            .flush_cf(&cf)
+            .map_err(|e| ResourceError::io(format!("flush column family {}", cf_name), e))?;
+    }
+    Ok(())
+}
+
+// 写入记录时的 unix 秒级时间戳
+fn now_secs() -> ResourceResult<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| ResourceError::io("read system time", e))
+}
+
+// 遍历 column family 出错
+fn iterate_error(cf_name: &str, e: rocksdb::Error) -> ResourceError {
+    ResourceError::io(format!("iterate column family {}", cf_name), e)
+}
+
+// key 不是合法的 utf8 时视为记录损坏
+fn utf8_key(cf_name: &str, key: &[u8]) -> ResourceResult<String> {
+    String::from_utf8(key.to_vec())
+        .map_err(|e| ResourceError::corrupt(cf_name, &String::from_utf8_lossy(key), e))
+}
+
+/// 写入 checkpoint 并记录历史，历史条数上限为 task.checkpoint_history_keep
+pub fn save_checkpoint_to_cf(checkpoint: &mut CheckPoint) -> ResourceResult<()> {
+    checkpoint.modify_checkpoint_timestamp = i128::from(now_secs()?);
+    let keep = get_config()
+        .map(|c| c.task.checkpoint_history_keep)
+        .unwrap_or_else(|_| TaskConfig::checkpoint_history_keep_default());
+    let begin = Instant::now();
+    save_checkpoint_with_history(GLOBAL_ROCKSDB.get()?.as_ref(), checkpoint, keep)?;
+    record_write_latency(WriteKind::Checkpoint, begin);
+    Ok(())
+}
+
+// 时间戳补零，同一任务的历史按 key 排序即按时间排序
+fn checkpoint_history_key(task_id: &str, timestamp: i128) -> String {
+    format!("{}:{:020}", task_id, timestamp)
+}
+
+// 由新到旧遍历任务的历史，只访问该任务的 key
+fn checkpoint_history_iter<'a>(
+    db: &'a DBWithThreadMode<MultiThreaded>,
+    task_id: &str,
+) -> ResourceResult<impl Iterator<Item = ResourceResult<(Box<[u8]>, Box<[u8]>)>> + 'a> {
+    let cf = match db.cf_handle(CF_CHECKPOINT_HISTORY) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_CHECKPOINT_HISTORY)),
+    };
+    let prefix = format!("{}:", task_id);
+    // ';' 紧随 ':' 之后，从其前一条开始反向遍历即为该任务最新的历史
+    let upper = format!("{};", task_id);
+    let iter = db
+        .iterator_cf(
+            &cf,
+            IteratorMode::From(upper.as_bytes(), Direction::Reverse),
+        )
+        .map(|item| item.map_err(|e| iterate_error(CF_CHECKPOINT_HISTORY, e)))
+        .take_while(move |item| match item {
+            Ok((k, _)) => k.starts_with(prefix.as_bytes()),
+            Err(_) => true,
+        });
+    Ok(iter)
+}
+
+/// 同批写入 checkpoint 与历史，并删除超出 keep 条的旧历史；keep 为 0 时不记录历史并清空已有历史
+/// 只遍历该任务的历史，每次写入后不超过 keep 条，不随 column family 大小增长
+pub fn save_checkpoint_with_history(
+    db: &DBWithThreadMode<MultiThreaded>,
+    checkpoint: &CheckPoint,
+    keep: usize,
+) -> ResourceResult<()> {
+    let cf = match db.cf_handle(CF_TASK_CHECKPOINTS) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_TASK_CHECKPOINTS)),
     };
-    let encoded: Vec<u8> = bincode::serialize(checkpoint)?;
-    GLOBAL_ROCKSDB.put_cf(&cf, checkpoint.task_id.as_bytes(), encoded)?;
+    let cf_history = match db.cf_handle(CF_CHECKPOINT_HISTORY) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_CHECKPOINT_HISTORY)),
+    };
+    let encoded = encode_checkpoint(checkpoint)
+        .map_err(|e| ResourceError::serialization(CF_TASK_CHECKPOINTS, &checkpoint.task_id, e))?;
+    let key = checkpoint_history_key(&checkpoint.task_id, checkpoint.modify_checkpoint_timestamp);
+    let mut batch = WriteBatch::default();
+    batch.put_cf(&cf, checkpoint.task_id.as_bytes(), &encoded);
+    let mut kept = 0;
+    if keep > 0 {
+        batch.put_cf(&cf_history, &key, &encoded);
+        kept = 1;
+    }
+    for item in checkpoint_history_iter(db, &checkpoint.task_id)? {
+        let (k, _) = item?;
+        // 同一秒内的多次写入覆盖同一条历史
+        if keep > 0 && *k == *key.as_bytes() {
+            continue;
+        }
+        match kept < keep {
+            true => kept += 1,
+            false => batch.delete_cf(&cf_history, &k),
+        }
+    }
+    db.write(batch).map_err(|e| {
+        ResourceError::rocksdb("write", CF_TASK_CHECKPOINTS, &checkpoint.task_id, e)
+    })?;
     Ok(())
 }
 
-pub fn get_checkpoint(task_id: &str) -> Result<CheckPoint> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_CHECKPOINTS) {
+/// 任务的 checkpoint 历史，由新到旧
+pub fn list_checkpoint_history_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+    task_id: &str,
+) -> ResourceResult<Vec<CheckPoint>> {
+    let mut history = vec![];
+    for item in checkpoint_history_iter(db, task_id)? {
+        let (k, v) = item?;
+        let checkpoint = decode_checkpoint(&v).map_err(|e| {
+            ResourceError::corrupt(CF_CHECKPOINT_HISTORY, &String::from_utf8_lossy(&k), e)
+        })?;
+        history.push(checkpoint);
+    }
+    Ok(history)
+}
+
+pub fn list_checkpoint_history(task_id: &str) -> ResourceResult<Vec<CheckPoint>> {
+    list_checkpoint_history_in(GLOBAL_ROCKSDB.get()?.as_ref(), task_id)
+}
+
+/// 历史中 modify_checkpoint_timestamp 为 timestamp 的 checkpoint
+pub fn get_checkpoint_history(
+    task_id: &str,
+    timestamp: i128,
+) -> ResourceResult<Option<CheckPoint>> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_CHECKPOINT_HISTORY) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_CHECKPOINT_HISTORY)),
     };
-    let chekpoint_bytes = match GLOBAL_ROCKSDB.get_cf(&cf, task_id)? {
+    let key = checkpoint_history_key(task_id, timestamp);
+    let value = GLOBAL_ROCKSDB
+        .get()?
+        .get_cf(&cf, &key)
+        .map_err(|e| ResourceError::rocksdb("get", CF_CHECKPOINT_HISTORY, &key, e))?;
+    match value {
+        Some(v) => match decode_checkpoint(&v) {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(e) => Err(ResourceError::corrupt(CF_CHECKPOINT_HISTORY, &key, e)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// 以历史中的 checkpoint 覆盖当前 checkpoint，不更新时间戳也不记录新的历史
+pub fn restore_checkpoint(checkpoint: &CheckPoint) -> ResourceResult<()> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_CHECKPOINTS) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_CHECKPOINTS)),
+    };
+    let task_id = checkpoint.task_id.as_str();
+    let encoded = encode_checkpoint(checkpoint)
+        .map_err(|e| ResourceError::serialization(CF_TASK_CHECKPOINTS, task_id, e))?;
+    GLOBAL_ROCKSDB
+        .get()?
+        .put_cf(&cf, task_id.as_bytes(), encoded)
+        .map_err(|e| ResourceError::rocksdb("put", CF_TASK_CHECKPOINTS, task_id, e))?;
+    Ok(())
+}
+
+pub fn get_checkpoint(task_id: &str) -> ResourceResult<CheckPoint> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_CHECKPOINTS) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_CHECKPOINTS)),
+    };
+    let chekpoint_bytes = match GLOBAL_ROCKSDB
+        .get()?
+        .get_cf(&cf, task_id)
+        .map_err(|e| ResourceError::rocksdb("get", CF_TASK_CHECKPOINTS, task_id, e))?
+    {
         Some(b) => b,
-        None => return Err(anyhow!("checkpoint not exist")),
+        None => return Err(ResourceError::not_found(CF_TASK_CHECKPOINTS, task_id)),
+    };
+    decode_checkpoint(&chekpoint_bytes)
+        .map_err(|e| ResourceError::corrupt(CF_TASK_CHECKPOINTS, task_id, e))
+}
+
+/// 删除任务 checkpoint，返回 checkpoint 是否存在，不存在视为成功
+pub fn delete_checkpoint(task_id: &str) -> ResourceResult<bool> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_CHECKPOINTS) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_CHECKPOINTS)),
     };
-    let checkpoint: CheckPoint = bincode::deserialize(&chekpoint_bytes)?;
+    let exists = GLOBAL_ROCKSDB
+        .get()?
+        .get_cf(&cf, task_id)
+        .map_err(|e| ResourceError::rocksdb("get", CF_TASK_CHECKPOINTS, task_id, e))?
+        .is_some();
+    if exists {
+        GLOBAL_ROCKSDB
+            .get()?
+            .delete_cf(&cf, task_id)
+            .map_err(|e| ResourceError::rocksdb("delete", CF_TASK_CHECKPOINTS, task_id, e))?;
+    }
+    Ok(exists)
+}
+
+pub fn get_task(task_id: &str) -> ResourceResult<Task> {
+    get_task_from(GLOBAL_ROCKSDB.get()?.as_ref(), task_id)
+}
+
+pub fn get_task_from(db: &DBWithThreadMode<MultiThreaded>, task_id: &str) -> ResourceResult<Task> {
+    match find_task_from(db, task_id)? {
+        Some(task) => Ok(task),
+        None => Err(ResourceError::not_found(CF_TASK, task_id)),
+    }
+}
 
-    Ok(checkpoint)
+// 任务定义为 json，无法解析时视为记录损坏
+fn decode_task(task_id: &str, bytes: &[u8]) -> ResourceResult<Task> {
+    let task_json_str =
+        std::str::from_utf8(bytes).map_err(|e| ResourceError::corrupt(CF_TASK, task_id, e))?;
+    json_to_struct::<Task>(task_json_str).map_err(|e| ResourceError::corrupt(CF_TASK, task_id, e))
 }
 
-pub fn get_task(task_id: &str) -> Result<Task> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
+/// 任务不存在时为 None
+pub fn find_task_from(
+    db: &DBWithThreadMode<MultiThreaded>,
+    task_id: &str,
+) -> ResourceResult<Option<Task>> {
+    let cf = match db.cf_handle(CF_TASK) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_TASK)),
     };
 
-    let value = GLOBAL_ROCKSDB.get_cf(&cf, task_id)?;
-    return match value {
-        Some(v) => {
-            let task_json_str = String::from_utf8(v)?;
-            let task = json_to_struct::<Task>(task_json_str.as_str())?;
-            Ok(task)
-        }
-        None => Err(anyhow!("task {} not exist", task_id)),
+    let value = db
+        .get_cf(&cf, task_id)
+        .map_err(|e| ResourceError::rocksdb("get", CF_TASK, task_id, e))?;
+    match value {
+        Some(v) => Ok(Some(decode_task(task_id, &v)?)),
+        None => Ok(None),
+    }
+}
+
+/// db 中的全部任务，按 id 升序
+pub fn list_tasks_from(
+    db: &DBWithThreadMode<MultiThreaded>,
+) -> ResourceResult<Vec<(String, Task)>> {
+    let cf = match db.cf_handle(CF_TASK) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK)),
     };
+    let mut tasks = vec![];
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (k, v) = item.map_err(|e| iterate_error(CF_TASK, e))?;
+        let task_id = utf8_key(CF_TASK, &k)?;
+        let task = decode_task(&task_id, &v)?;
+        tasks.push((task_id, task));
+    }
+    Ok(tasks)
+}
+
+/// 以 secondary 模式只读打开 path 处的 rocksdb，追上主实例的最新写入后执行 f
+/// 不获取文件锁，服务运行或停止时均可使用；secondary 目录为临时目录，执行后删除
+pub fn with_secondary_rocksdb<T>(
+    path: &str,
+    f: impl FnOnce(&DBWithThreadMode<MultiThreaded>) -> ResourceResult<T>,
+) -> ResourceResult<T> {
+    if !Path::new(path).join("CURRENT").exists() {
+        return Err(ResourceError::io(
+            format!("cannot open rocksdb at {}", path),
+            "rocksdb not found",
+        ));
+    }
+    let mut opts = Options::default();
+    // secondary 模式要求保持全部文件打开
+    opts.set_max_open_files(-1);
+    // 旧版本创建的库可能缺少新增的 column family，只打开已存在的
+    let cfs = DBWithThreadMode::<MultiThreaded>::list_cf(&opts, path).map_err(|e| {
+        ResourceError::io(format!("list column families of rocksdb at {}", path), e)
+    })?;
+    let secondary =
+        std::env::temp_dir().join(format!("mario_rocksdb_secondary_{}", uuid::Uuid::new_v4()));
+    let result = DBWithThreadMode::<MultiThreaded>::open_cf_as_secondary(
+        &opts,
+        Path::new(path),
+        secondary.as_path(),
+        cfs,
+    )
+    .map_err(|e| ResourceError::io(format!("cannot open rocksdb at {} as secondary", path), e))
+    .and_then(|db| {
+        db.try_catch_up_with_primary().map_err(|e| {
+            ResourceError::io(format!("catch up rocksdb at {} with primary", path), e)
+        })?;
+        f(&db)
+    });
+    let _ = std::fs::remove_dir_all(&secondary);
+    result
 }
 
-pub fn get_task_status(task_id: &str) -> Result<TaskStatus> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_STATUS) {
+pub fn get_task_status(task_id: &str) -> ResourceResult<TaskStatus> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_STATUS) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_TASK_STATUS)),
     };
-    let status_bytes = match GLOBAL_ROCKSDB.get_cf(&cf, task_id)? {
+    let status_bytes = match GLOBAL_ROCKSDB
+        .get()?
+        .get_cf(&cf, task_id)
+        .map_err(|e| ResourceError::rocksdb("get", CF_TASK_STATUS, task_id, e))?
+    {
         Some(b) => b,
-        None => return Err(anyhow!("checkpoint not exist")),
+        None => return Err(ResourceError::not_found(CF_TASK_STATUS, task_id)),
     };
-    let status = bincode::deserialize(&status_bytes)?;
+    decode_task_status(&status_bytes)
+        .map_err(|e| ResourceError::corrupt(CF_TASK_STATUS, task_id, e))
+}
 
-    Ok(status)
+/// 删除任务状态，不存在视为成功
+pub fn delete_task_status(task_id: &str) -> ResourceResult<()> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_STATUS) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_STATUS)),
+    };
+    GLOBAL_ROCKSDB
+        .get()?
+        .delete_cf(&cf, task_id)
+        .map_err(|e| ResourceError::rocksdb("delete", CF_TASK_STATUS, task_id, e))?;
+    Ok(())
 }
 
-pub fn save_task_status(status: &mut TaskStatus) -> Result<()> {
+pub fn save_task_status(status: &mut TaskStatus) -> ResourceResult<()> {
     if status.is_starting() {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        status.start_time = now.as_secs();
+        status.start_time = now_secs()?;
     }
 
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_STATUS) {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_TASK_STATUS) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_TASK_STATUS)),
     };
-    let encoded: Vec<u8> = bincode::serialize(status)?;
-    GLOBAL_ROCKSDB.put_cf(&cf, status.task_id.as_bytes(), encoded)?;
+    let task_id = status.task_id.as_str();
+    let encoded: Vec<u8> = encode_task_status(status)
+        .map_err(|e| ResourceError::serialization(CF_TASK_STATUS, task_id, e))?;
+    let begin = Instant::now();
+    GLOBAL_ROCKSDB
+        .get()?
+        .put_cf(&cf, task_id.as_bytes(), encoded)
+        .map_err(|e| ResourceError::rocksdb("put", CF_TASK_STATUS, task_id, e))?;
+    record_write_latency(WriteKind::TaskStatus, begin);
     Ok(())
 }
 
-pub fn living_tasks() -> Result<Vec<TaskStatus>> {
-    let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_STATUS) {
+/// CF_TASK_STATUS 的扫描结果，无法解码的记录单独列出
+#[derive(Debug, Default)]
+pub struct TaskStatusScan {
+    pub statuses: Vec<TaskStatus>,
+    pub corrupt: Vec<CorruptRow>,
+}
+
+/// 解码全部任务状态，单条记录无法解码时不影响其他记录
+pub fn scan_task_statuses_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+) -> ResourceResult<TaskStatusScan> {
+    let cf = match db.cf_handle(CF_TASK_STATUS) {
         Some(cf) => cf,
-        None => return Err(anyhow!("column family not exist")),
+        None => return Err(ResourceError::cf_missing(CF_TASK_STATUS)),
     };
-    let mut vec_task_status = vec![];
-    for item in GLOBAL_ROCKSDB.iterator_cf(&cf, IteratorMode::Start) {
+    let mut scan = TaskStatusScan::default();
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
         if let Ok(kv) = item {
-            let status: TaskStatus = bincode::deserialize(&kv.1)?;
-            if !status.is_stopped() {
-                vec_task_status.push(status);
+            match decode_task_status(&kv.1) {
+                Ok(status) => scan.statuses.push(status),
+                Err(e) => scan.corrupt.push(CorruptRow {
+                    key: String::from_utf8_lossy(&kv.0).to_string(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+    Ok(scan)
+}
+
+/// 未停止的任务状态，无法解码的记录跳过，首次出现时告警并计入 corrupt_task_status_count
+pub fn living_tasks() -> ResourceResult<Vec<TaskStatus>> {
+    let scan = scan_task_statuses_in(GLOBAL_ROCKSDB.get()?.as_ref())?;
+    report_corrupt_task_statuses(&scan.corrupt);
+    Ok(scan
+        .statuses
+        .into_iter()
+        .filter(|s| !s.is_stopped())
+        .collect())
+}
+
+/// 任务批量写入中的单条记录，value 为 None 时删除
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfWrite {
+    pub cf: &'static str,
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+impl CfWrite {
+    fn put(cf: &'static str, key: &str, value: Vec<u8>) -> Self {
+        Self {
+            cf,
+            key: key.to_string(),
+            value: Some(value),
+        }
+    }
+
+    fn delete(cf: &'static str, key: &str) -> Self {
+        Self {
+            cf,
+            key: key.to_string(),
+            value: None,
+        }
+    }
+}
+
+/// CF_TASK_NAME_IDX 中的 key，同名任务各占一条，按名称前缀扫描得到全部同名任务
+pub fn task_name_index_key(name: &str, task_id: &str) -> String {
+    format!("{}\0{}", name, task_id)
+}
+
+/// 名称对应的任务 id，按 id 升序；未开启唯一名称前创建的同名任务可能有多个
+pub fn find_tasks_by_name_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+    name: &str,
+) -> ResourceResult<Vec<String>> {
+    let cf = match db.cf_handle(CF_TASK_NAME_IDX) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_NAME_IDX)),
+    };
+    let prefix = task_name_index_key(name, "");
+    let mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
+    let mut ids = vec![];
+    for item in db.iterator_cf(&cf, mode) {
+        let (k, _) = item.map_err(|e| iterate_error(CF_TASK_NAME_IDX, e))?;
+        let task_id = match k.strip_prefix(prefix.as_bytes()) {
+            Some(id) => id,
+            None => break,
+        };
+        // 名称本身含 \0 时前缀可能匹配到其他名称，task_id 不含 \0
+        if !task_id.contains(&0) {
+            let task_id = String::from_utf8(task_id.to_vec()).map_err(|e| {
+                ResourceError::corrupt(CF_TASK_NAME_IDX, &String::from_utf8_lossy(&k), e)
+            })?;
+            ids.push(task_id);
+        }
+    }
+    Ok(ids)
+}
+
+pub fn find_tasks_by_name(name: &str) -> ResourceResult<Vec<String>> {
+    find_tasks_by_name_in(GLOBAL_ROCKSDB.get()?.as_ref(), name)
+}
+
+fn task_name_put(name: &str, task_id: &str) -> CfWrite {
+    CfWrite::put(
+        CF_TASK_NAME_IDX,
+        &task_name_index_key(name, task_id),
+        vec![],
+    )
+}
+
+fn task_name_delete(name: &str, task_id: &str) -> CfWrite {
+    CfWrite::delete(CF_TASK_NAME_IDX, &task_name_index_key(name, task_id))
+}
+
+/// 创建任务：写入任务定义与名称索引，并清除同 id 遗留的状态与 checkpoint
+pub fn task_create_writes(task_id: &str, task_json: &str, name: &str) -> Vec<CfWrite> {
+    vec![
+        CfWrite::put(CF_TASK, task_id, task_json.as_bytes().to_vec()),
+        CfWrite::delete(CF_TASK_STATUS, task_id),
+        CfWrite::delete(CF_TASK_CHECKPOINTS, task_id),
+        task_name_put(name, task_id),
+    ]
+}
+
+/// 更新任务定义，名称变化时删除旧名称的索引；old_name 为 None 时任务原先不存在
+pub fn task_update_writes(
+    task_id: &str,
+    task_json: &str,
+    old_name: Option<&str>,
+    name: &str,
+) -> Vec<CfWrite> {
+    let mut writes = vec![CfWrite::put(
+        CF_TASK,
+        task_id,
+        task_json.as_bytes().to_vec(),
+    )];
+    if let Some(old_name) = old_name.filter(|n| *n != name) {
+        writes.push(task_name_delete(old_name, task_id));
+    }
+    writes.push(task_name_put(name, task_id));
+    writes
+}
+
+/// 删除任务：删除任务定义、状态与 checkpoint，name 为 None 时不更新名称索引
+pub fn task_remove_writes(task_id: &str, name: Option<&str>) -> Vec<CfWrite> {
+    let mut writes = vec![
+        CfWrite::delete(CF_TASK, task_id),
+        CfWrite::delete(CF_TASK_STATUS, task_id),
+        CfWrite::delete(CF_TASK_CHECKPOINTS, task_id),
+    ];
+    if let Some(name) = name {
+        writes.push(task_name_delete(name, task_id));
+    }
+    writes
+}
+
+/// 任务停止：最终 checkpoint 与停止状态同时写入
+pub fn task_finalize_writes(
+    checkpoint: &CheckPoint,
+    status: &TaskStatus,
+) -> ResourceResult<Vec<CfWrite>> {
+    let encoded_checkpoint = encode_checkpoint(checkpoint)
+        .map_err(|e| ResourceError::serialization(CF_TASK_CHECKPOINTS, &checkpoint.task_id, e))?;
+    let encoded_status = encode_task_status(status)
+        .map_err(|e| ResourceError::serialization(CF_TASK_STATUS, &status.task_id, e))?;
+    Ok(vec![
+        CfWrite::put(CF_TASK_CHECKPOINTS, &checkpoint.task_id, encoded_checkpoint),
+        CfWrite::put(CF_TASK_STATUS, &status.task_id, encoded_status),
+    ])
+}
+
+/// 以一个 WriteBatch 写入，全部写入或全部不写入；column family 不存在时不写入
+pub fn write_task_batch(
+    db: &DBWithThreadMode<MultiThreaded>,
+    writes: &[CfWrite],
+) -> ResourceResult<()> {
+    let first = match writes.first() {
+        Some(w) => w,
+        None => return Ok(()),
+    };
+    let mut batch = WriteBatch::default();
+    for w in writes {
+        let cf = match db.cf_handle(w.cf) {
+            Some(cf) => cf,
+            None => return Err(ResourceError::cf_missing(w.cf)),
+        };
+        match &w.value {
+            Some(v) => batch.put_cf(&cf, &w.key, v),
+            None => batch.delete_cf(&cf, &w.key),
+        }
+    }
+    // 错误信息中以首条记录定位批次
+    db.write(batch).map_err(|e| {
+        ResourceError::io(
+            format!(
+                "write batch of {} records from key {} in column family {}",
+                writes.len(),
+                first.key,
+                first.cf
+            ),
+            e,
+        )
+    })
+}
+
+pub fn commit_task_writes(writes: &[CfWrite]) -> ResourceResult<()> {
+    write_task_batch(GLOBAL_ROCKSDB.get()?.as_ref(), writes)
+}
+
+// 按任务存储子键的 column family 及 key 中 task_id 之后的分隔符
+const TASK_SUBKEY_COLUMN_FAMILIES: [(&'static str, &'static str); 4] = [
+    (CF_TASK_RUN_DEFINITION, "/"),
+    (CF_TASK_CHANGES, "/"),
+    (CF_TASK_RUNS, ":"),
+    (CF_CHECKPOINT_HISTORY, ":"),
+];
+
+// column family 中以 prefix 开头的全部 key
+fn prefix_keys(
+    db: &DBWithThreadMode<MultiThreaded>,
+    cf_name: &str,
+    prefix: &str,
+) -> ResourceResult<Vec<String>> {
+    let cf = match db.cf_handle(cf_name) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(cf_name)),
+    };
+    let mut keys = vec![];
+    let mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
+    for item in db.iterator_cf(&cf, mode) {
+        let (k, _) = item.map_err(|e| iterate_error(cf_name, e))?;
+        if !k.starts_with(prefix.as_bytes()) {
+            break;
+        }
+        keys.push(utf8_key(cf_name, &k)?);
+    }
+    Ok(keys)
+}
+
+/// 以一个批次删除 column family 中以 prefix 开头的全部 key，返回删除的条数
+pub fn delete_prefix_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+    cf_name: &'static str,
+    prefix: &str,
+) -> ResourceResult<usize> {
+    // 空前缀会删除整个 column family
+    if prefix.is_empty() {
+        return Err(ResourceError::InvalidArgument(format!(
+            "delete prefix in column family {} is empty",
+            cf_name
+        )));
+    }
+    let writes = prefix_keys(db, cf_name, prefix)?
+        .iter()
+        .map(|k| CfWrite::delete(cf_name, k))
+        .collect::<Vec<CfWrite>>();
+    write_task_batch(db, &writes)?;
+    Ok(writes.len())
+}
+
+pub fn delete_prefix(cf_name: &'static str, prefix: &str) -> ResourceResult<usize> {
+    delete_prefix_in(GLOBAL_ROCKSDB.get()?.as_ref(), cf_name, prefix)
+}
+
+// 任务定义无法读取时按 task_id 查找名称索引，需遍历整个索引
+fn task_name_index_keys_of(
+    db: &DBWithThreadMode<MultiThreaded>,
+    task_id: &str,
+) -> ResourceResult<Vec<String>> {
+    let cf = match db.cf_handle(CF_TASK_NAME_IDX) {
+        Some(cf) => cf,
+        None => return Err(ResourceError::cf_missing(CF_TASK_NAME_IDX)),
+    };
+    let suffix = task_name_index_key("", task_id);
+    let mut keys = vec![];
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (k, _) = item.map_err(|e| iterate_error(CF_TASK_NAME_IDX, e))?;
+        if k.ends_with(suffix.as_bytes()) {
+            keys.push(utf8_key(CF_TASK_NAME_IDX, &k)?);
+        }
+    }
+    Ok(keys)
+}
+
+/// 以一个批次删除任务在各 column family 中的全部记录，包括名称索引与按任务存储的子键，不存在视为成功
+/// 调用方需持有 lock_task_names
+pub fn delete_task_all_in(
+    db: &DBWithThreadMode<MultiThreaded>,
+    task_id: &str,
+) -> ResourceResult<()> {
+    let name = find_task_from(db, task_id).ok().flatten().map(|t| t.name());
+    let mut writes = task_remove_writes(task_id, name.as_deref());
+    if name.is_none() {
+        for key in task_name_index_keys_of(db, task_id)? {
+            writes.push(CfWrite::delete(CF_TASK_NAME_IDX, &key));
+        }
+    }
+    for (cf_name, separator) in TASK_SUBKEY_COLUMN_FAMILIES {
+        for key in prefix_keys(db, cf_name, &format!("{}{}", task_id, separator))? {
+            writes.push(CfWrite::delete(cf_name, &key));
+        }
+    }
+    write_task_batch(db, &writes)
+}
+
+pub fn delete_task_all(task_id: &str) -> ResourceResult<()> {
+    delete_task_all_in(GLOBAL_ROCKSDB.get()?.as_ref(), task_id)
+}
+
+/// 任务执行完成时同时保存最终 checkpoint 与停止状态
+pub fn save_task_finalization(
+    checkpoint: &mut CheckPoint,
+    status: &TaskStatus,
+) -> ResourceResult<()> {
+    checkpoint.modify_checkpoint_timestamp = i128::from(now_secs()?);
+    let writes = task_finalize_writes(checkpoint, status)?;
+    let begin = Instant::now();
+    commit_task_writes(&writes)?;
+    record_write_latency(WriteKind::Checkpoint, begin);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_checkpoint, delete_prefix_in, delete_task_all_in, find_tasks_by_name_in,
+        get_task_from, init_rocksdb, list_checkpoint_history_in, list_tasks_from,
+        save_checkpoint_with_history, task_create_writes, task_finalize_writes,
+        task_name_index_key, task_remove_writes, task_update_writes, with_secondary_rocksdb,
+        write_task_batch, CfWrite, GlobalRocksdb, ResourceError, ResourceResult,
+        CF_CHECKPOINT_HISTORY, CF_TASK, CF_TASK_CHANGES, CF_TASK_CHECKPOINTS, CF_TASK_NAME_IDX,
+        CF_TASK_RUNS, CF_TASK_RUN_DEFINITION, CF_TASK_STATUS, ROCKSDB_COLUMN_FAMILIES,
+    };
+    use crate::tasks::{
+        CheckPoint, Status, Task, TaskStatus, TaskStopReason, TransferStatus, TransferTask,
+    };
+    use anyhow::{anyhow, Result};
+    use once_cell::sync::OnceCell;
+    use rocksdb::{DBWithThreadMode, IteratorMode, MultiThreaded};
+    use std::sync::Arc;
+
+    // 模拟逐条写入，第 fail_at 条写入前进程退出
+    fn write_one_by_one(
+        db: &DBWithThreadMode<MultiThreaded>,
+        writes: &[CfWrite],
+        fail_at: usize,
+    ) -> Result<()> {
+        for (i, w) in writes.iter().enumerate() {
+            if i == fail_at {
+                return Err(anyhow!("injected failure"));
+            }
+            let cf = db.cf_handle(w.cf).unwrap();
+            match &w.value {
+                Some(v) => db.put_cf(&cf, &w.key, v)?,
+                None => db.delete_cf(&cf, &w.key)?,
+            }
+        }
+        Ok(())
+    }
+
+    // 模拟批量写入，fail 时批次提交前进程退出
+    fn write_batched(
+        db: &DBWithThreadMode<MultiThreaded>,
+        writes: &[CfWrite],
+        fail: bool,
+    ) -> Result<()> {
+        if fail {
+            return Err(anyhow!("injected failure"));
+        }
+        Ok(write_task_batch(db, writes)?)
+    }
+
+    fn exists(db: &DBWithThreadMode<MultiThreaded>, cf_name: &str, key: &str) -> bool {
+        let cf = db.cf_handle(cf_name).unwrap();
+        db.get_cf(&cf, key).unwrap().is_some()
+    }
+
+    // 任务要么完整存在，要么完全不存在：定义与名称索引同在，无定义时无状态与 checkpoint
+    fn task_consistent(db: &DBWithThreadMode<MultiThreaded>, task_id: &str, name: &str) -> bool {
+        let defined = exists(db, CF_TASK, task_id);
+        let named = exists(db, CF_TASK_NAME_IDX, &task_name_index_key(name, task_id));
+        let leftover =
+            exists(db, CF_TASK_STATUS, task_id) || exists(db, CF_TASK_CHECKPOINTS, task_id);
+        defined == named && (defined || !leftover)
+    }
+
+    fn reset(db: &DBWithThreadMode<MultiThreaded>, writes: &[CfWrite]) {
+        for w in writes {
+            let cf = db.cf_handle(w.cf).unwrap();
+            db.delete_cf(&cf, &w.key).unwrap();
+        }
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_task_writes_atomic -- --nocapture
+    #[test]
+    fn test_task_writes_atomic() {
+        let db_path = "/tmp/task_writes_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/task_writes_test");
+        let db = init_rocksdb(db_path).unwrap();
+        let create = task_create_writes("t1", "{}", "nightly");
+
+        // 逐条写入在中途失败时留下不完整的任务
+        let mut broken = 0;
+        for fail_at in 0..=create.len() {
+            reset(&db, &create);
+            let _ = write_one_by_one(&db, &create, fail_at);
+            if !task_consistent(&db, "t1", "nightly") {
+                broken += 1;
+            }
+        }
+        assert!(broken > 0);
+
+        // 批量写入失败时不留下任何记录，成功时完整存在
+        reset(&db, &create);
+        assert!(write_batched(&db, &create, true).is_err());
+        assert!(task_consistent(&db, "t1", "nightly"));
+        assert!(!exists(&db, CF_TASK, "t1"));
+        write_batched(&db, &create, false).unwrap();
+        assert!(task_consistent(&db, "t1", "nightly"));
+        assert!(exists(&db, CF_TASK, "t1"));
+
+        // 停止时 checkpoint 与状态同批写入
+        let checkpoint = CheckPoint {
+            task_id: "t1".to_string(),
+            ..CheckPoint::default()
+        };
+        let status = TaskStatus {
+            task_id: "t1".to_string(),
+            start_time: 1000,
+            status: Status::Transfer(TransferStatus::Stopped(TaskStopReason::Finish)),
+            last_skip_reason: None,
+            run_id: None,
+        };
+        let finalize = task_finalize_writes(&checkpoint, &status).unwrap();
+        assert!(write_batched(&db, &finalize, true).is_err());
+        assert!(!exists(&db, CF_TASK_STATUS, "t1") && !exists(&db, CF_TASK_CHECKPOINTS, "t1"));
+        write_batched(&db, &finalize, false).unwrap();
+        assert!(exists(&db, CF_TASK_STATUS, "t1") && exists(&db, CF_TASK_CHECKPOINTS, "t1"));
+
+        // 逐条删除在中途失败时留下孤立的状态与 checkpoint，批量删除不会
+        let remove = task_remove_writes("t1", Some("nightly"));
+        let _ = write_one_by_one(&db, &remove, 1);
+        assert!(!task_consistent(&db, "t1", "nightly"));
+        write_batched(&db, &create, false).unwrap();
+        write_batched(&db, &finalize, false).unwrap();
+        assert!(write_batched(&db, &remove, true).is_err());
+        assert!(task_consistent(&db, "t1", "nightly"));
+        write_batched(&db, &remove, false).unwrap();
+        assert!(task_consistent(&db, "t1", "nightly"));
+        assert!(!exists(&db, CF_TASK, "t1"));
+        assert!(find_tasks_by_name_in(&db, "nightly").unwrap().is_empty());
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/task_writes_test");
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_find_tasks_by_name -- --nocapture
+    #[test]
+    fn test_find_tasks_by_name() {
+        let db_path = "/tmp/task_name_idx_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/task_name_idx_test");
+        let db = init_rocksdb(db_path).unwrap();
+        for (task_id, name) in [("2", "nightly"), ("1", "nightly"), ("3", "nightly2")] {
+            write_task_batch(&db, &task_create_writes(task_id, "{}", name)).unwrap();
+        }
+        // 同名任务均可查到，名称为其他名称的前缀时不混淆
+        assert_eq!(
+            find_tasks_by_name_in(&db, "nightly").unwrap(),
+            vec!["1", "2"]
+        );
+        assert_eq!(find_tasks_by_name_in(&db, "nightly2").unwrap(), vec!["3"]);
+        assert!(find_tasks_by_name_in(&db, "night").unwrap().is_empty());
+
+        // 改名时删除旧名称的索引
+        let rename = task_update_writes("2", "{}", Some("nightly"), "weekly");
+        write_task_batch(&db, &rename).unwrap();
+        assert_eq!(find_tasks_by_name_in(&db, "nightly").unwrap(), vec!["1"]);
+        assert_eq!(find_tasks_by_name_in(&db, "weekly").unwrap(), vec!["2"]);
+        let keep = task_update_writes("2", "{}", Some("weekly"), "weekly");
+        write_task_batch(&db, &keep).unwrap();
+        assert_eq!(find_tasks_by_name_in(&db, "weekly").unwrap(), vec!["2"]);
+
+        write_task_batch(&db, &task_remove_writes("1", Some("nightly"))).unwrap();
+        assert!(find_tasks_by_name_in(&db, "nightly").unwrap().is_empty());
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/task_name_idx_test");
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_checkpoint_history -- --nocapture
+    #[test]
+    fn test_checkpoint_history() {
+        let db_path = "/tmp/checkpoint_history_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/checkpoint_history_test");
+        let db = init_rocksdb(db_path).unwrap();
+        let save = |task_id: &str, timestamp: i128, keep: usize| {
+            let checkpoint = CheckPoint {
+                task_id: task_id.to_string(),
+                modify_checkpoint_timestamp: timestamp,
+                ..CheckPoint::default()
+            };
+            save_checkpoint_with_history(&db, &checkpoint, keep).unwrap();
+        };
+        let timestamps = |task_id: &str| {
+            list_checkpoint_history_in(&db, task_id)
+                .unwrap()
+                .iter()
+                .map(|c| c.modify_checkpoint_timestamp)
+                .collect::<Vec<i128>>()
+        };
+        // 时间戳位数不同时仍按时间排序，只保留最近 3 条
+        for timestamp in [9, 10, 11, 100, 1000] {
+            save("t1", timestamp, 3);
+        }
+        save("t10", 5, 3);
+        assert_eq!(timestamps("t1"), vec![1000, 100, 11]);
+        assert_eq!(timestamps("t10"), vec![5]);
+        assert!(timestamps("t2").is_empty());
+        let cf = db.cf_handle(CF_TASK_CHECKPOINTS).unwrap();
+        let current = decode_checkpoint(&db.get_cf(&cf, "t1").unwrap().unwrap()).unwrap();
+        assert_eq!(current.modify_checkpoint_timestamp, 1000);
+
+        // 同一时间戳覆盖同一条，不挤掉旧历史
+        save("t1", 1000, 3);
+        assert_eq!(timestamps("t1"), vec![1000, 100, 11]);
+        // 调小保留数时多余的历史在下次写入时删除，0 表示清空
+        save("t1", 1001, 2);
+        assert_eq!(timestamps("t1"), vec![1001, 1000]);
+        save("t1", 1002, 0);
+        assert!(timestamps("t1").is_empty());
+        assert_eq!(timestamps("t10"), vec![5]);
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/checkpoint_history_test");
+    }
+
+    fn all_keys(db: &DBWithThreadMode<MultiThreaded>, cf_name: &str) -> Vec<String> {
+        let cf = db.cf_handle(cf_name).unwrap();
+        db.iterator_cf(&cf, IteratorMode::Start)
+            .map(|item| String::from_utf8(item.unwrap().0.to_vec()).unwrap())
+            .collect()
+    }
+
+    // 写入任务在各 column family 中的记录
+    fn put_task_all(db: &DBWithThreadMode<MultiThreaded>, task_id: &str, task_json: &str) {
+        write_task_batch(db, &task_create_writes(task_id, task_json, "nightly")).unwrap();
+        let status = TaskStatus {
+            task_id: task_id.to_string(),
+            start_time: 1000,
+            status: Status::Transfer(TransferStatus::Stopped(TaskStopReason::Finish)),
+            last_skip_reason: None,
+            run_id: None,
+        };
+        let checkpoint = CheckPoint {
+            task_id: task_id.to_string(),
+            modify_checkpoint_timestamp: 1000,
+            ..CheckPoint::default()
+        };
+        write_task_batch(db, &task_finalize_writes(&checkpoint, &status).unwrap()).unwrap();
+        save_checkpoint_with_history(db, &checkpoint, 3).unwrap();
+        for (cf_name, key) in [
+            (CF_TASK_RUNS, format!("{}:{:020}:r1", task_id, 1000)),
+            (CF_TASK_RUN_DEFINITION, format!("{}/r1", task_id)),
+            (CF_TASK_CHANGES, format!("{}/{:020}", task_id, 1)),
+        ] {
+            let cf = db.cf_handle(cf_name).unwrap();
+            db.put_cf(&cf, key, "{}").unwrap();
+        }
+    }
+
+    // 任务 id 本身或以 id 加分隔符开头的 key，以及以 \0 加 id 结尾的名称索引
+    fn task_keys(db: &DBWithThreadMode<MultiThreaded>, task_id: &str) -> Vec<String> {
+        let mut keys = vec![];
+        for cf_name in ROCKSDB_COLUMN_FAMILIES {
+            keys.extend(all_keys(db, cf_name).into_iter().filter(|k| {
+                k == task_id
+                    || k.starts_with(&format!("{}:", task_id))
+                    || k.starts_with(&format!("{}/", task_id))
+                    || k.ends_with(&task_name_index_key("", task_id))
+            }));
+        }
+        keys
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_delete_task_all -- --nocapture
+    #[test]
+    fn test_delete_task_all() {
+        let db_path = "/tmp/delete_task_all_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/delete_task_all_test");
+        let db = init_rocksdb(db_path).unwrap();
+        let task = serde_json::to_string(&Task::Transfer(TransferTask::default())).unwrap();
+        // t10 以 t1 开头，不受删除 t1 影响
+        put_task_all(&db, "t1", &task);
+        put_task_all(&db, "t10", &task);
+        let t10_keys = task_keys(&db, "t10");
+        assert_eq!(t10_keys.len(), 8);
+
+        delete_task_all_in(&db, "t1").unwrap();
+        assert!(task_keys(&db, "t1").is_empty());
+        assert_eq!(task_keys(&db, "t10"), t10_keys);
+        // 不存在时视为成功
+        delete_task_all_in(&db, "t1").unwrap();
+
+        // 任务定义无法解析时按 task_id 删除名称索引
+        put_task_all(&db, "t2", "not json");
+        delete_task_all_in(&db, "t2").unwrap();
+        assert!(task_keys(&db, "t2").is_empty());
+        assert_eq!(find_tasks_by_name_in(&db, "nightly").unwrap(), vec!["t10"]);
+
+        assert_eq!(
+            delete_prefix_in(&db, CF_CHECKPOINT_HISTORY, "t10:").unwrap(),
+            1
+        );
+        assert_eq!(task_keys(&db, "t10").len(), 7);
+        assert!(delete_prefix_in(&db, CF_TASK, "").is_err());
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/delete_task_all_test");
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_secondary_read_with_primary_open -- --nocapture
+    #[test]
+    fn test_secondary_read_with_primary_open() {
+        let db_path = "/tmp/secondary_read_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/secondary_read_test");
+        assert!(with_secondary_rocksdb(db_path, |_| Ok(())).is_err());
+
+        let task = serde_json::to_string(&Task::Transfer(TransferTask::default())).unwrap();
+        let primary = init_rocksdb(db_path).unwrap();
+        let cf = primary.cf_handle(CF_TASK).unwrap();
+        primary.put_cf(&cf, "t1", &task).unwrap();
+        // 主实例持有文件锁，再次以主实例打开失败
+        assert!(init_rocksdb(db_path).is_err());
+
+        let ids = |db: &DBWithThreadMode<MultiThreaded>| -> ResourceResult<Vec<String>> {
+            Ok(list_tasks_from(db)?
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect::<Vec<String>>())
+        };
+        assert_eq!(with_secondary_rocksdb(db_path, ids).unwrap(), vec!["t1"]);
+        // 未落盘的写入在追上主实例后可见
+        primary.put_cf(&cf, "t2", &task).unwrap();
+        let reader = std::thread::spawn(move || with_secondary_rocksdb(db_path, ids));
+        assert_eq!(reader.join().unwrap().unwrap(), vec!["t1", "t2"]);
+        assert!(with_secondary_rocksdb(db_path, |db| get_task_from(db, "t2")).is_ok());
+        assert!(
+            with_secondary_rocksdb(db_path, |db| get_task_from(db, "t3"))
+                .unwrap_err()
+                .is_not_found()
+        );
+        drop(primary);
+        let _ = std::fs::remove_dir_all("/tmp/secondary_read_test");
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_resource_error_variants -- --nocapture
+    #[test]
+    fn test_resource_error_variants() {
+        let db_path = "/tmp/resource_error_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/resource_error_test");
+        let db = init_rocksdb(db_path).unwrap();
+        match get_task_from(&db, "t1") {
+            Err(ResourceError::KeyNotFound { cf, key }) => {
+                assert_eq!(cf, CF_TASK);
+                assert_eq!(key, "t1");
             }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
         }
+
+        // 无法解析的定义为损坏，错误信息含 column family 与 key
+        let cf = db.cf_handle(CF_TASK).unwrap();
+        db.put_cf(&cf, "t2", "not json").unwrap();
+        let e = get_task_from(&db, "t2").unwrap_err();
+        assert!(matches!(e, ResourceError::Corrupt { .. }));
+        assert!(e.to_string().contains("key t2 in column family cf_task"));
+        assert!(list_tasks_from(&db).is_err());
+
+        assert!(matches!(
+            delete_prefix_in(&db, CF_TASK_CHANGES, ""),
+            Err(ResourceError::InvalidArgument(_))
+        ));
+        drop(db);
+        let _ = std::fs::remove_dir_all("/tmp/resource_error_test");
+    }
+
+    //cargo test resources::resource_rocksdb::test::test_global_rocksdb_not_opened -- --nocapture
+    #[test]
+    fn test_global_rocksdb_not_opened() {
+        static CELL: OnceCell<Arc<DBWithThreadMode<MultiThreaded>>> = OnceCell::new();
+        let global = GlobalRocksdb(&CELL);
+        // 打开前访问返回 NotOpened，不 panic
+        assert!(matches!(global.get(), Err(ResourceError::NotOpened)));
+
+        let db_path = "/tmp/global_rocksdb_not_opened_test/rocksdb";
+        let _ = std::fs::remove_dir_all("/tmp/global_rocksdb_not_opened_test");
+        let db = CELL.get_or_init(|| Arc::new(init_rocksdb(db_path).unwrap()));
+        assert!(Arc::ptr_eq(global.get().unwrap(), db));
+        let _ = std::fs::remove_dir_all("/tmp/global_rocksdb_not_opened_test");
     }
-    Ok(vec_task_status)
 }
@@ -0,0 +1,232 @@
+use crate::tasks::{CheckPoint, Status, TaskSkipRecord, TaskStatus};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+// 带版本的存储格式以魔数开头，其后为 bincode 编码的 StoredEnvelope
+// 旧格式首 8 字节为 task_id 长度，与魔数相同时 task_id 超过 1GB，不会混淆
+const STORED_ENVELOPE_MAGIC: [u8; 4] = [0xff, b'M', b'V', b'E'];
+
+// 修改 CheckPoint 或 TaskStatus 的结构时递增版本，并在解码时将旧版本升级为当前结构
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 1;
+pub const TASK_STATUS_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct StoredEnvelope {
+    version: u32,
+    payload: Vec<u8>,
+}
+
+fn seal(version: u32, payload: Vec<u8>) -> Result<Vec<u8>> {
+    let mut encoded = STORED_ENVELOPE_MAGIC.to_vec();
+    encoded.extend(bincode::serialize(&StoredEnvelope { version, payload })?);
+    Ok(encoded)
+}
+
+// 无魔数时为引入版本前写入的裸 bincode，返回 None
+fn open(bytes: &[u8]) -> Result<Option<StoredEnvelope>> {
+    match bytes.strip_prefix(&STORED_ENVELOPE_MAGIC[..]) {
+        Some(rest) => Ok(Some(bincode::deserialize::<StoredEnvelope>(rest)?)),
+        None => Ok(None),
+    }
+}
+
+fn unsupported(kind: &str, version: u32, current: u32) -> anyhow::Error {
+    anyhow!(
+        "{} format version {} not supported, current version {}",
+        kind,
+        version,
+        current
+    )
+}
+
+pub fn encode_checkpoint(checkpoint: &CheckPoint) -> Result<Vec<u8>> {
+    seal(CHECKPOINT_FORMAT_VERSION, bincode::serialize(checkpoint)?)
+}
+
+/// 解码 CF_TASK_CHECKPOINTS 中的 checkpoint，兼容无版本的旧格式
+pub fn decode_checkpoint(bytes: &[u8]) -> Result<CheckPoint> {
+    let envelope = match open(bytes)? {
+        Some(e) => e,
+        // 旧格式与版本 1 结构相同
+        None => return Ok(bincode::deserialize::<CheckPoint>(bytes)?),
+    };
+    match envelope.version {
+        1 => Ok(bincode::deserialize::<CheckPoint>(&envelope.payload)?),
+        v => Err(unsupported("checkpoint", v, CHECKPOINT_FORMAT_VERSION)),
+    }
+}
+
+// 记录启动被拒原因之前写入的状态
+#[derive(Deserialize)]
+struct TaskStatusWithoutSkip {
+    task_id: String,
+    start_time: u64,
+    status: Status,
+}
+
+// 记录运行 id 之前写入的状态
+#[derive(Deserialize)]
+struct TaskStatusWithoutRunId {
+    task_id: String,
+    start_time: u64,
+    status: Status,
+    last_skip_reason: Option<TaskSkipRecord>,
+}
+
+// 无版本的旧格式按字段由多到少依次尝试，缺少的末尾字段取默认值
+fn decode_legacy_task_status(bytes: &[u8]) -> Result<TaskStatus> {
+    let err = match bincode::deserialize::<TaskStatus>(bytes) {
+        Ok(s) => return Ok(s),
+        Err(e) => e,
+    };
+    if let Ok(s) = bincode::deserialize::<TaskStatusWithoutRunId>(bytes) {
+        return Ok(TaskStatus {
+            task_id: s.task_id,
+            start_time: s.start_time,
+            status: s.status,
+            last_skip_reason: s.last_skip_reason,
+            run_id: None,
+        });
+    }
+    if let Ok(s) = bincode::deserialize::<TaskStatusWithoutSkip>(bytes) {
+        return Ok(TaskStatus {
+            task_id: s.task_id,
+            start_time: s.start_time,
+            status: s.status,
+            last_skip_reason: None,
+            run_id: None,
+        });
+    }
+    Err(err.into())
+}
+
+pub fn encode_task_status(status: &TaskStatus) -> Result<Vec<u8>> {
+    seal(TASK_STATUS_FORMAT_VERSION, bincode::serialize(status)?)
+}
+
+/// 解码 CF_TASK_STATUS 中的任务状态，兼容无版本的旧格式
+pub fn decode_task_status(bytes: &[u8]) -> Result<TaskStatus> {
+    let envelope = match open(bytes)? {
+        Some(e) => e,
+        None => return decode_legacy_task_status(bytes),
+    };
+    match envelope.version {
+        1 => Ok(bincode::deserialize::<TaskStatus>(&envelope.payload)?),
+        v => Err(unsupported("task status", v, TASK_STATUS_FORMAT_VERSION)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_checkpoint, decode_task_status, encode_checkpoint, encode_task_status,
+        StoredEnvelope, CHECKPOINT_FORMAT_VERSION, STORED_ENVELOPE_MAGIC,
+        TASK_STATUS_FORMAT_VERSION,
+    };
+    use crate::tasks::{
+        CheckPoint, FileDescription, FilePosition, StartSkipReason, Status, TaskSkipRecord,
+        TaskStatus, TransferStage, TransferStatus,
+    };
+
+    // 版本 1 的 checkpoint 编码，结构变化导致此测试失败时须递增版本并编写升级逻辑
+    const CHECKPOINT_V1: [u8; 88] = [
+        2, 0, 0, 0, 0, 0, 0, 0, 116, 49, 1, 0, 0, 0, 0, 0, 0, 0, 102, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0,
+        0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 6, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    // 版本 1 的任务状态编码，同上
+    const TASK_STATUS_V1: [u8; 54] = [
+        2, 0, 0, 0, 0, 0, 0, 0, 116, 49, 232, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0,
+        0, 1, 1, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 114, 49,
+    ];
+
+    // 记录启动被拒原因之前的任务状态，Transfer(Stopped(Finish))
+    const TASK_STATUS_WITHOUT_SKIP: [u8; 30] = [
+        2, 0, 0, 0, 0, 0, 0, 0, 116, 49, 232, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0,
+        0,
+    ];
+
+    fn checkpoint() -> CheckPoint {
+        CheckPoint {
+            task_id: "t1".to_string(),
+            executing_file: FileDescription {
+                path: "f".to_string(),
+                size: 2,
+                total_lines: 3,
+            },
+            executing_file_position: FilePosition {
+                offset: 4,
+                line_num: 5,
+            },
+            file_for_notify: None,
+            task_stage: TransferStage::Increment,
+            modify_checkpoint_timestamp: 6,
+            task_begin_timestamp: 7,
+        }
+    }
+
+    fn task_status() -> TaskStatus {
+        TaskStatus {
+            task_id: "t1".to_string(),
+            start_time: 1000,
+            status: Status::Transfer(TransferStatus::Running(TransferStage::Stock)),
+            last_skip_reason: Some(TaskSkipRecord {
+                reason: StartSkipReason::Draining,
+                timestamp: 9,
+            }),
+            run_id: Some("r1".to_string()),
+        }
+    }
+
+    fn sealed(version: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = STORED_ENVELOPE_MAGIC.to_vec();
+        bytes.extend(
+            bincode::serialize(&StoredEnvelope {
+                version,
+                payload: payload.to_vec(),
+            })
+            .unwrap(),
+        );
+        bytes
+    }
+
+    //cargo test resources::stored_format::test::test_checkpoint_format -- --nocapture
+    #[test]
+    fn test_checkpoint_format() {
+        assert_eq!(bincode::serialize(&checkpoint()).unwrap(), CHECKPOINT_V1);
+        let encoded = encode_checkpoint(&checkpoint()).unwrap();
+        assert_eq!(encoded, sealed(CHECKPOINT_FORMAT_VERSION, &CHECKPOINT_V1));
+        assert_eq!(decode_checkpoint(&encoded).unwrap(), checkpoint());
+        // 无版本的旧格式
+        assert_eq!(decode_checkpoint(&CHECKPOINT_V1).unwrap(), checkpoint());
+        // 更高版本无法解码
+        assert!(decode_checkpoint(&sealed(CHECKPOINT_FORMAT_VERSION + 1, &CHECKPOINT_V1)).is_err());
+    }
+
+    //cargo test resources::stored_format::test::test_task_status_format -- --nocapture
+    #[test]
+    fn test_task_status_format() {
+        assert_eq!(bincode::serialize(&task_status()).unwrap(), TASK_STATUS_V1);
+        let encoded = encode_task_status(&task_status()).unwrap();
+        assert_eq!(encoded, sealed(TASK_STATUS_FORMAT_VERSION, &TASK_STATUS_V1));
+        for bytes in [encoded.as_slice(), &TASK_STATUS_V1] {
+            let decoded = decode_task_status(bytes).unwrap();
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&task_status()).unwrap()
+            );
+        }
+        assert!(
+            decode_task_status(&sealed(TASK_STATUS_FORMAT_VERSION + 1, &TASK_STATUS_V1)).is_err()
+        );
+
+        // 缺少末尾字段的旧格式取默认值
+        let legacy = decode_task_status(&TASK_STATUS_WITHOUT_SKIP).unwrap();
+        assert_eq!(legacy.task_id, "t1");
+        assert_eq!(legacy.start_time, 1000);
+        assert!(legacy.is_stopped());
+        assert!(legacy.last_skip_reason.is_none());
+        assert!(legacy.run_id.is_none());
+    }
+}
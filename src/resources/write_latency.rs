@@ -0,0 +1,210 @@
+use crate::configure::HealthConfig;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 每类写入保留的最大样本数，避免高频写入时窗口无限增长
+const MAX_LATENCY_SAMPLES: usize = 4096;
+
+pub static GLOBAL_WRITE_LATENCY: Lazy<WriteLatencyMonitor> =
+    Lazy::new(WriteLatencyMonitor::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteKind {
+    Checkpoint,
+    TaskStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WriteLatencyReadiness {
+    pub degraded: bool,
+    pub reason: Option<String>,
+    pub checkpoint_write: LatencyStats,
+    pub status_write: LatencyStats,
+}
+
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    samples: VecDeque<(Instant, Duration)>,
+    // p99 首次超过阈值的时间，恢复后清空
+    exceeded_since: Option<Instant>,
+}
+
+impl LatencyWindow {
+    fn record(&mut self, latency: Duration) {
+        if self.samples.len() >= MAX_LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((Instant::now(), latency));
+    }
+
+    fn stats(&mut self, window: Duration) -> LatencyStats {
+        while let Some((at, _)) = self.samples.front() {
+            if at.elapsed() <= window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        let mut latencies = self
+            .samples
+            .iter()
+            .map(|(_, l)| l.as_secs_f64() * 1000.0)
+            .collect::<Vec<f64>>();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+        LatencyStats {
+            samples: latencies.len(),
+            p50_ms: percentile(&latencies, 0.50),
+            p99_ms: percentile(&latencies, 0.99),
+        }
+    }
+
+    /// 返回 p99 是否已持续超过阈值
+    fn evaluate(&mut self, stats: &LatencyStats, threshold_ms: u64, sustained: Duration) -> bool {
+        if stats.samples == 0 || stats.p99_ms <= threshold_ms as f64 {
+            self.exceeded_since = None;
+            return false;
+        }
+        let since = *self.exceeded_since.get_or_insert_with(Instant::now);
+        since.elapsed() >= sustained
+    }
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let idx = ((sorted.len() as f64 * p).ceil() as usize).saturating_sub(1);
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+/// 统计 checkpoint 与任务状态写入 rocksdb 的延迟，磁盘劣化时最先体现在这里
+#[derive(Debug, Default)]
+pub struct WriteLatencyMonitor {
+    checkpoint: Mutex<LatencyWindow>,
+    task_status: Mutex<LatencyWindow>,
+}
+
+impl WriteLatencyMonitor {
+    fn window(&self, kind: WriteKind) -> &Mutex<LatencyWindow> {
+        match kind {
+            WriteKind::Checkpoint => &self.checkpoint,
+            WriteKind::TaskStatus => &self.task_status,
+        }
+    }
+
+    pub fn record(&self, kind: WriteKind, latency: Duration) {
+        if let Ok(mut w) = self.window(kind).lock() {
+            w.record(latency);
+        }
+    }
+
+    /// 窗口内的延迟分位数，不影响降级判断
+    pub fn stats(&self, kind: WriteKind, window: Duration) -> LatencyStats {
+        match self.window(kind).lock() {
+            Ok(mut w) => w.stats(window),
+            Err(_) => LatencyStats::default(),
+        }
+    }
+
+    pub fn readiness(&self, health: &HealthConfig) -> WriteLatencyReadiness {
+        let window = Duration::from_secs(health.latency_window_secs);
+        let sustained = Duration::from_secs(health.degraded_after_secs);
+        let mut reasons = vec![];
+
+        let checkpoint_write = match self.checkpoint.lock() {
+            Ok(mut w) => {
+                let stats = w.stats(window);
+                if w.evaluate(&stats, health.checkpoint_write_p99_ms, sustained) {
+                    reasons.push(format!(
+                        "checkpoint write p99 {:.1}ms exceeds {}ms",
+                        stats.p99_ms, health.checkpoint_write_p99_ms
+                    ));
+                }
+                stats
+            }
+            Err(_) => LatencyStats::default(),
+        };
+        let status_write = match self.task_status.lock() {
+            Ok(mut w) => {
+                let stats = w.stats(window);
+                if w.evaluate(&stats, health.status_write_p99_ms, sustained) {
+                    reasons.push(format!(
+                        "task status write p99 {:.1}ms exceeds {}ms",
+                        stats.p99_ms, health.status_write_p99_ms
+                    ));
+                }
+                stats
+            }
+            Err(_) => LatencyStats::default(),
+        };
+
+        WriteLatencyReadiness {
+            degraded: !reasons.is_empty(),
+            reason: match reasons.is_empty() {
+                true => None,
+                false => Some(reasons.join("; ")),
+            },
+            checkpoint_write,
+            status_write,
+        }
+    }
+}
+
+pub fn record_write_latency(kind: WriteKind, begin: Instant) {
+    GLOBAL_WRITE_LATENCY.record(kind, begin.elapsed());
+}
+
+#[cfg(test)]
+mod test {
+    use super::{WriteKind, WriteLatencyMonitor};
+    use crate::configure::HealthConfig;
+    use std::time::Duration;
+
+    //cargo test resources::write_latency::test::test_write_latency_readiness -- --nocapture
+    #[test]
+    fn test_write_latency_readiness() {
+        let monitor = WriteLatencyMonitor::default();
+        let mut health = HealthConfig::default();
+        health.checkpoint_write_p99_ms = 100;
+        health.degraded_after_secs = 0;
+
+        for _ in 0..99 {
+            monitor.record(WriteKind::Checkpoint, Duration::from_millis(1));
+        }
+        let r = monitor.readiness(&health);
+        assert!(!r.degraded);
+        assert_eq!(r.checkpoint_write.samples, 99);
+        assert_eq!(r.checkpoint_write.p50_ms, 1.0);
+
+        for _ in 0..10 {
+            monitor.record(WriteKind::Checkpoint, Duration::from_millis(500));
+        }
+        let r = monitor.readiness(&health);
+        assert!(r.degraded);
+        assert!(r.reason.unwrap().starts_with("checkpoint write"));
+        let stats = monitor.stats(WriteKind::Checkpoint, Duration::from_secs(60));
+        assert_eq!(stats, r.checkpoint_write);
+        assert_eq!(
+            monitor
+                .stats(WriteKind::TaskStatus, Duration::from_secs(60))
+                .samples,
+            0
+        );
+
+        // 窗口内样本过期后自动恢复
+        health.latency_window_secs = 0;
+        std::thread::sleep(Duration::from_millis(5));
+        let r = monitor.readiness(&health);
+        assert!(!r.degraded);
+        assert_eq!(r.checkpoint_write.samples, 0);
+    }
+}
@@ -1,5 +1,7 @@
+mod network;
 mod oss;
 mod oss_client;
 
+pub use network::*;
 pub use oss::*;
 pub use oss_client::*;
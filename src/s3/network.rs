@@ -0,0 +1,215 @@
+use crate::configure::NetworkConfig;
+use crate::server::{record_dns_failure, record_dns_lookup, record_s3_request, record_s3_retry};
+use anyhow::{anyhow, Result};
+use aws_config::retry::RetryConfig;
+use aws_sdk_s3::config::interceptors::{
+    BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
+use aws_smithy_http_client::tls;
+use aws_smithy_runtime_api::client::dns::{DnsFuture, ResolveDns, ResolveDnsError};
+use aws_smithy_runtime_api::client::http::SharedHttpClient;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::{
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+pub static GLOBAL_DNS_CACHE: Lazy<DnsCache> = Lazy::new(DnsCache::default);
+
+// 标记请求已发起过一次尝试，之后的尝试均为重试
+#[derive(Debug, Clone)]
+struct AttemptStarted;
+
+impl Storable for AttemptStarted {
+    type Storer = StoreReplace<Self>;
+}
+
+/// 统计请求与重试次数，计入 /metrics
+#[derive(Debug)]
+pub struct RetryCountInterceptor;
+
+impl Intercept for RetryCountInterceptor {
+    fn name(&self) -> &'static str {
+        "RetryCountInterceptor"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        record_s3_request();
+        Ok(())
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        match cfg.load::<AttemptStarted>() {
+            Some(_) => record_s3_retry(),
+            None => cfg.interceptor_state().store_put(AttemptStarted),
+        }
+        Ok(())
+    }
+}
+
+/// 连接级失败（dns、连接超时等）由 sdk 按退避策略重试，重试耗尽后才返回对象错误
+pub fn gen_retry_config(network: &NetworkConfig) -> RetryConfig {
+    RetryConfig::standard()
+        .with_max_attempts(network.connect_retries + 1)
+        .with_initial_backoff(Duration::from_millis(network.connect_backoff_ms))
+}
+
+/// s3 客户端使用的 http client，建立连接时经 GLOBAL_DNS_CACHE 解析 endpoint
+pub fn gen_http_client(network: &NetworkConfig) -> SharedHttpClient {
+    aws_smithy_http_client::Builder::new()
+        .tls_provider(tls::Provider::Rustls(
+            tls::rustls_provider::CryptoMode::AwsLc,
+        ))
+        .build_with_resolver(CachedDnsResolver {
+            network: network.clone(),
+        })
+}
+
+/// 按 network 配置的缓存有效期与地址排序解析 endpoint
+#[derive(Debug, Clone)]
+pub struct CachedDnsResolver {
+    network: NetworkConfig,
+}
+
+impl ResolveDns for CachedDnsResolver {
+    fn resolve_dns<'a>(&'a self, name: &'a str) -> DnsFuture<'a> {
+        let host = name.to_string();
+        let network = self.network.clone();
+        DnsFuture::new(async move {
+            // 系统解析为阻塞调用
+            let addrs =
+                tokio::task::spawn_blocking(move || GLOBAL_DNS_CACHE.resolve(&host, &network))
+                    .await
+                    .map_err(ResolveDnsError::new)?
+                    .map_err(|e| ResolveDnsError::new(BoxError::from(e)))?;
+            Ok(addrs.into_iter().map(|a| a.ip()).collect::<Vec<IpAddr>>())
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DnsCacheEntry {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct DnsCache {
+    entries: DashMap<String, DnsCacheEntry>,
+}
+
+impl DnsCache {
+    /// 解析 host，在 ttl 内直接返回缓存结果，地址按 sort_addrs 排序
+    pub fn resolve(&self, host: &str, network: &NetworkConfig) -> Result<Vec<SocketAddr>> {
+        let ttl = Duration::from_secs(network.dns_cache_ttl_secs);
+        if let Some(entry) = self.entries.get(host) {
+            if entry.resolved_at.elapsed() < ttl {
+                record_dns_lookup(true);
+                return Ok(entry.addrs.clone());
+            }
+        }
+
+        record_dns_lookup(false);
+        let addrs = match (host, 0).to_socket_addrs() {
+            Ok(a) => a.collect::<Vec<SocketAddr>>(),
+            Err(e) => {
+                record_dns_failure();
+                // 解析失败时若存在过期缓存则继续使用，避免瞬时 dns 故障放大为对象错误
+                if let Some(entry) = self.entries.get(host) {
+                    log::warn!("resolve {} error: {}, use stale cache", host, e);
+                    return Ok(entry.addrs.clone());
+                }
+                return Err(anyhow!("resolve {} error: {}", host, e));
+            }
+        };
+        let addrs = sort_addrs(addrs, network.happy_eyeballs);
+        if network.dns_cache_ttl_secs > 0 {
+            self.entries.insert(
+                host.to_string(),
+                DnsCacheEntry {
+                    addrs: addrs.clone(),
+                    resolved_at: Instant::now(),
+                },
+            );
+        }
+        Ok(addrs)
+    }
+}
+
+/// happy eyeballs 开启时按 ipv6、ipv4 交替排列（RFC 8305），否则 ipv4 优先
+pub fn sort_addrs(addrs: Vec<SocketAddr>, happy_eyeballs: bool) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.into_iter().partition(|a| a.is_ipv6());
+    if !happy_eyeballs {
+        return v4.into_iter().chain(v6).collect();
+    }
+    let mut sorted = vec![];
+    let mut v6_iter = v6.into_iter();
+    let mut v4_iter = v4.into_iter();
+    loop {
+        let a = v6_iter.next();
+        let b = v4_iter.next();
+        if a.is_none() && b.is_none() {
+            break;
+        }
+        sorted.extend(a);
+        sorted.extend(b);
+    }
+    sorted
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sort_addrs, CachedDnsResolver, DnsCache, GLOBAL_DNS_CACHE};
+    use crate::configure::NetworkConfig;
+    use aws_smithy_runtime_api::client::dns::ResolveDns;
+    use std::net::{IpAddr, SocketAddr};
+
+    //cargo test s3::network::test::test_sort_addrs -- --nocapture
+    #[test]
+    fn test_sort_addrs() {
+        let addrs: Vec<SocketAddr> = vec![
+            "[::1]:80".parse().unwrap(),
+            "[::2]:80".parse().unwrap(),
+            "127.0.0.1:80".parse().unwrap(),
+        ];
+        let sorted = sort_addrs(addrs.clone(), true);
+        assert!(sorted[0].is_ipv6() && sorted[1].is_ipv4() && sorted[2].is_ipv6());
+        let sorted = sort_addrs(addrs, false);
+        assert!(sorted[0].is_ipv4());
+    }
+
+    //cargo test s3::network::test::test_dns_cache -- --nocapture
+    #[test]
+    fn test_dns_cache() {
+        let cache = DnsCache::default();
+        let network = NetworkConfig::default();
+        let first = cache.resolve("127.0.0.1", &network).unwrap();
+        let second = cache.resolve("127.0.0.1", &network).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    //cargo test s3::network::test::test_cached_dns_resolver -- --nocapture
+    #[tokio::test]
+    async fn test_cached_dns_resolver() {
+        let resolver = CachedDnsResolver {
+            network: NetworkConfig::default(),
+        };
+        let addrs = resolver.resolve_dns("127.0.0.1").await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1".parse::<IpAddr>().unwrap()]);
+        assert!(GLOBAL_DNS_CACHE.entries.contains_key("127.0.0.1"));
+    }
+}
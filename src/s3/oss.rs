@@ -1,4 +1,6 @@
+use super::network::{gen_http_client, gen_retry_config, RetryCountInterceptor};
 use super::oss_client::OssClient;
+use crate::configure::{get_config, NetworkConfig};
 use anyhow::{Ok, Result};
 use aws_config::{BehaviorVersion, SdkConfig};
 use aws_credential_types::{provider::SharedCredentialsProvider, Credentials};
@@ -57,6 +59,7 @@ impl OSSDescription {
 
 impl OSSDescription {
     pub fn gen_oss_client(&self) -> Result<OssClient> {
+        let network = get_config()?.network;
         match self.provider {
             OssProvider::JD => {
                 let shared_config = SdkConfig::builder()
@@ -72,7 +75,10 @@ impl OSSDescription {
                     .behavior_version(BehaviorVersion::latest())
                     .build();
 
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
 
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
 
@@ -93,7 +99,10 @@ impl OSSDescription {
                     .behavior_version(BehaviorVersion::latest())
                     .build();
 
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -113,8 +122,11 @@ impl OSSDescription {
                     .behavior_version(BehaviorVersion::latest())
                     .build();
 
-                let s3_config_builder =
-                    aws_sdk_s3::config::Builder::from(&shared_config).force_path_style(true);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                )
+                .force_path_style(true);
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -133,7 +145,10 @@ impl OSSDescription {
                     .region(Region::new(self.region.clone()))
                     .behavior_version(BehaviorVersion::latest())
                     .build();
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -151,7 +166,10 @@ impl OSSDescription {
                     .region(Region::new(self.region.clone()))
                     .behavior_version(BehaviorVersion::latest())
                     .build();
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -170,7 +188,10 @@ impl OSSDescription {
                     .region(Region::new(self.region.clone()))
                     .behavior_version(BehaviorVersion::latest())
                     .build();
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -189,7 +210,10 @@ impl OSSDescription {
                     .region(Region::new(self.region.clone()))
                     .behavior_version(BehaviorVersion::latest())
                     .build();
-                let s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config);
+                let s3_config_builder = apply_network_config(
+                    aws_sdk_s3::config::Builder::from(&shared_config),
+                    &network,
+                );
                 let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
                 let oss_client = OssClient { client };
                 Ok(oss_client)
@@ -198,6 +222,16 @@ impl OSSDescription {
     }
 }
 
+fn apply_network_config(
+    builder: aws_sdk_s3::config::Builder,
+    network: &NetworkConfig,
+) -> aws_sdk_s3::config::Builder {
+    builder
+        .http_client(gen_http_client(network))
+        .retry_config(gen_retry_config(network))
+        .interceptor(RetryCountInterceptor)
+}
+
 #[cfg(test)]
 mod test {
 
@@ -1,7 +1,8 @@
+use crate::commons::inherit_request_context;
 use crate::{
     commons::{
-        fill_file_with_zero, gen_file_part_plan, size_distributed, FilePart, LastModifyFilter,
-        RegexFilter,
+        fill_file_with_zero, gen_file_part_plan, AnalyzeProgress, FilePart, LastModifyFilter,
+        RegexFilter, GLOBAL_BUFFER_POOL,
     },
     tasks::FileDescription,
     tasks::DOWNLOAD_TMP_FILE_SUBFFIX,
@@ -20,17 +21,16 @@ use aws_sdk_s3::Client;
 use aws_sdk_s3::{
     operation::create_multipart_upload::CreateMultipartUploadOutput, presigning::PresigningConfig,
 };
-use aws_smithy_types::{body::SdkBody, byte_stream::ByteStream};
+use aws_smithy_types::byte_stream::ByteStream;
 
 use std::{
     collections::BTreeMap,
     fs::{self, File, OpenOptions},
-    io::{LineWriter, Read, Seek, SeekFrom, Write},
+    io::{LineWriter, Seek, SeekFrom, Write},
     path::Path,
     sync::{atomic::AtomicBool, Arc},
 };
 use tokio::{
-    io::AsyncReadExt,
     sync::{Mutex, RwLock},
     task::{self, JoinSet},
 };
@@ -280,7 +280,7 @@ impl OssClient {
                 let v_o_r = obj_range_batch.clone();
                 let e_m = Arc::clone(&err_mark);
 
-                joinset.spawn(async move {
+                joinset.spawn(inherit_request_context(async move {
                     {
                         let mut num = e_t.write().await;
                         *num += 1;
@@ -302,7 +302,7 @@ impl OssClient {
 
                     let mut num = e_t.write().await;
                     *num -= 1;
-                });
+                }));
                 obj_range_batch.clear();
             }
         }
@@ -462,20 +462,11 @@ impl OssClient {
         };
 
         let mut part_number = 0;
+        let mut pooled = GLOBAL_BUFFER_POOL.checkout(chunk_size);
         loop {
-            let buffer = match content_len >= chunk_size {
-                true => {
-                    let mut buffer = vec![0; chunk_size];
-                    let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
-                    content_len -= chunk_size;
-                    buffer
-                }
-                false => {
-                    let mut buffer = vec![0; content_len];
-                    let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
-                    buffer
-                }
-            };
+            let len = content_len.min(chunk_size);
+            let buffer = pooled.fill_from(&mut byte_stream_async_reader, len).await?;
+            content_len -= len;
             let buf_len = buffer.len();
             let stream = ByteStream::from(buffer);
             part_number += 1;
@@ -536,17 +527,17 @@ impl OssClient {
         };
 
         //分段上传文件并记录completer_part
+        let mut pooled = GLOBAL_BUFFER_POOL.checkout(chuck_size);
         loop {
-            let mut buf = vec![0; chuck_size];
-            let read_count = file.read(&mut buf)?;
+            let body = pooled.fill_from_file(file, chuck_size)?;
+            let read_count = body.len();
             part_number += 1;
 
             if read_count == 0 {
                 break;
             }
 
-            let body = &buf[..read_count];
-            let stream = ByteStream::from(body.to_vec());
+            let stream = ByteStream::from(body);
 
             let completed_part = self
                 .upload_part(upload_id, part_number, bucket, key, stream)
@@ -735,7 +726,7 @@ impl OssClient {
                     task::yield_now().await;
                 }
 
-                joinset.spawn(async move {
+                joinset.spawn(inherit_request_context(async move {
                     {
                         let mut num = e_t.write().await;
                         *num += 1;
@@ -759,7 +750,7 @@ impl OssClient {
 
                     let mut num = e_t.write().await;
                     *num -= 1;
-                });
+                }));
 
                 parts_vec.clear();
             }
@@ -830,6 +821,7 @@ impl OssClient {
         Ok(exist)
     }
 
+    /// 统计结果累计到 progress，取消时中断进行中的 list 请求并提前返回
     pub async fn analyze_objects_size(
         &self,
         bucket: &str,
@@ -837,62 +829,15 @@ impl OssClient {
         regex_filter: Option<RegexFilter>,
         last_modify_filter: Option<LastModifyFilter>,
         batch_size: i32,
-    ) -> Result<BTreeMap<String, i128>> {
-        let mut size_map = BTreeMap::<String, i128>::new();
-        let resp = self
-            .list_objects(bucket.to_string(), prefix.clone(), batch_size, None)
-            .await?;
-        let mut token = resp.next_token;
-
-        if let Some(objects) = resp.object_list {
-            for obj in objects.into_iter() {
-                let key = match obj.key() {
-                    Some(k) => k,
-                    None => {
-                        continue;
-                    }
-                };
-
-                if let Some(p) = &prefix {
-                    if !key.starts_with(p) {
-                        continue;
-                    }
-                }
-
-                if let Some(f) = &regex_filter {
-                    if !f.filter(key) {
-                        continue;
-                    }
-                }
-
-                if let Some(f) = last_modify_filter {
-                    if let Some(d) = obj.last_modified() {
-                        if !f.filter(i128::from(d.secs())) {
-                            continue;
-                        }
-                    }
-                }
-
-                let obj_size = match obj.size() {
-                    Some(s) => s,
-                    None => return Err(anyhow!("object length is None")),
-                };
-
-                let obj_size_i128 = i128::from(obj_size);
-                let map_key = size_distributed(obj_size_i128);
-                let mut map_val = match size_map.get(&map_key) {
-                    Some(m) => *m,
-                    None => 0,
-                };
-                map_val += 1;
-                size_map.insert(map_key, map_val);
-            }
-        }
+        progress: &AnalyzeProgress,
+    ) -> Result<()> {
+        let mut token = None;
+        loop {
+            let resp = tokio::select! {
+                resp = self.list_objects(bucket.to_string(), prefix.clone(), batch_size, token) => resp?,
+                _ = progress.cancelled() => return Ok(()),
+            };
 
-        while token.is_some() {
-            let resp = self
-                .list_objects(bucket.to_string(), prefix.clone(), batch_size, token)
-                .await?;
             if let Some(objects) = resp.object_list {
                 for obj in objects.into_iter() {
                     let key = match obj.key() {
@@ -926,20 +871,15 @@ impl OssClient {
                         Some(s) => s,
                         None => return Err(anyhow!("object length is None")),
                     };
-                    let obj_size_i128 = i128::from(obj_size);
-                    let map_key = size_distributed(obj_size_i128);
-                    let mut map_val = match size_map.get(&map_key) {
-                        Some(m) => *m,
-                        None => 0,
-                    };
-                    map_val += 1;
-                    size_map.insert(map_key, map_val);
+                    progress.record(i128::from(obj_size));
                 }
             }
+
             token = resp.next_token;
+            if token.is_none() {
+                return Ok(());
+            }
         }
-
-        Ok(size_map)
     }
 }
 
@@ -1053,7 +993,7 @@ pub async fn transfer_object_parts_by_range(
             let c_b_t = Arc::clone(&completed_parts_btree);
             let e_m = Arc::clone(&err_mark);
 
-            joinset.spawn(async move {
+            joinset.spawn(inherit_request_context(async move {
                 {
                     let mut num = e_t.write().await;
                     *num += 1;
@@ -1069,7 +1009,7 @@ pub async fn transfer_object_parts_by_range(
 
                 let mut num = e_t.write().await;
                 *num -= 1;
-            });
+            }));
             vec_obj_range_tmp.clear();
         }
     }
@@ -1154,14 +1094,13 @@ pub async fn upload_file_parts_batch(
     chunk_size: usize,
     completed_parts_btree: Arc<Mutex<BTreeMap<i32, CompletedPart>>>,
 ) -> Result<()> {
+    let mut pooled = GLOBAL_BUFFER_POOL.checkout(chunk_size);
     for p in parts_vec {
         let mut f = File::open(file_name)?;
         f.seek(SeekFrom::Start(p.offset))?;
-        let mut buf = vec![0; chunk_size];
-        let read_count = f.read(&mut buf)?;
-        let body = &buf[..read_count];
+        let body = pooled.fill_from_file(&mut f, chunk_size)?;
 
-        let stream = ByteStream::new(SdkBody::from(body));
+        let stream = ByteStream::from(body);
         let presigning = PresigningConfig::expires_in(std::time::Duration::from_secs(3000))?;
 
         let upload_part_res = target_client
@@ -1210,16 +1149,19 @@ pub async fn download_object(
     }
 
     let mut byte_stream_async_reader = get_object.body.into_async_read();
+    let mut pooled = GLOBAL_BUFFER_POOL.checkout(chunk_size);
     loop {
         if content_len_usize > chunk_size {
-            let mut buffer = vec![0; chunk_size];
-            let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
+            let buffer = pooled
+                .fill_from(&mut byte_stream_async_reader, chunk_size)
+                .await?;
             t_file.write_all(&buffer)?;
             content_len_usize -= chunk_size;
             continue;
         } else {
-            let mut buffer = vec![0; content_len_usize];
-            let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
+            let buffer = pooled
+                .fill_from(&mut byte_stream_async_reader, content_len_usize)
+                .await?;
             t_file.write_all(&buffer)?;
             break;
         }
@@ -1259,16 +1201,19 @@ pub async fn fill_parts_to_file_batch_by_range(
         let mut content_len_usize: usize = content_len.try_into()?;
 
         let mut byte_stream_async_reader = s_obj.body.into_async_read();
+        let mut pooled = GLOBAL_BUFFER_POOL.checkout(chunk_size);
         loop {
             if content_len_usize > chunk_size {
-                let mut buffer = vec![0; chunk_size];
-                let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
+                let buffer = pooled
+                    .fill_from(&mut byte_stream_async_reader, chunk_size)
+                    .await?;
                 t_file.write_all(&buffer)?;
                 content_len_usize -= chunk_size;
                 continue;
             } else {
-                let mut buffer = vec![0; content_len_usize];
-                let _ = byte_stream_async_reader.read_exact(&mut buffer).await?;
+                let buffer = pooled
+                    .fill_from(&mut byte_stream_async_reader, content_len_usize)
+                    .await?;
                 t_file.write_all(&buffer)?;
                 break;
             }
@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+/// 收到 SIGUSR1 时通知 dump 任务输出运行时任务状态
+#[cfg(unix)]
+pub async fn dump_state_on_signal() -> Result<()> {
+    use crate::tasks::spawn_runtime_state_dumper;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let dumper = spawn_runtime_state_dumper()?;
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+    while sigusr1.recv().await.is_some() {
+        log::info!("Received signal SIGUSR1, dumping runtime state ...");
+        if let Err(e) = dumper.send(()) {
+            log::error!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+/// windows 无 SIGUSR1，通过 admin dump 接口获取运行时状态
+#[cfg(not(unix))]
+pub async fn dump_state_on_signal() -> Result<()> {
+    Ok(())
+}
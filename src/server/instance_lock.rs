@@ -0,0 +1,96 @@
+use anyhow::Result;
+use fs2::FileExt;
+use once_cell::sync::OnceCell;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const INSTANCE_LOCK_FILE: &'static str = "mario.lock";
+
+// 锁文件句柄在进程生命周期内持有，进程退出时由系统释放
+static INSTANCE_LOCK: OnceCell<File> = OnceCell::new();
+
+/// 锁已被其他进程持有
+#[derive(Debug)]
+pub struct InstanceLockedError {
+    pub lock_file: String,
+    pub holder_pid: Option<String>,
+}
+
+impl std::error::Error for InstanceLockedError {}
+
+impl Display for InstanceLockedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.holder_pid {
+            Some(pid) => write!(
+                f,
+                "another server instance (pid {}) holds {}",
+                pid, self.lock_file
+            ),
+            None => write!(f, "another server instance holds {}", self.lock_file),
+        }
+    }
+}
+
+/// 在 rocksdb 目录下获取排他文件锁，避免多个实例同时打开同一 rocksdb
+/// 需在 open_global_rocksdb 之前调用
+pub fn acquire_instance_lock(dir: &str) -> Result<()> {
+    if INSTANCE_LOCK.get().is_some() {
+        return Ok(());
+    }
+    let file = lock_dir(dir)?;
+    let _ = INSTANCE_LOCK.set(file);
+    Ok(())
+}
+
+fn lock_dir(dir: &str) -> Result<File> {
+    fs::create_dir_all(dir)?;
+    let lock_file = Path::new(dir).join(INSTANCE_LOCK_FILE);
+    // 不可 truncate，否则会清除持有者写入的 pid
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&lock_file)?;
+
+    if file.try_lock_exclusive().is_err() {
+        let mut content = String::new();
+        let holder_pid = match file.read_to_string(&mut content) {
+            Ok(_) if !content.trim().is_empty() => Some(content.trim().to_string()),
+            _ => None,
+        };
+        return Err(InstanceLockedError {
+            lock_file: lock_file.to_string_lossy().to_string(),
+            holder_pid,
+        }
+        .into());
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(std::process::id().to_string().as_bytes())?;
+    file.sync_all()?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lock_dir, InstanceLockedError};
+
+    //cargo test server::instance_lock::test::test_lock_dir -- --nocapture
+    #[test]
+    fn test_lock_dir() {
+        let dir = "/tmp/instance_lock_test";
+        let _ = std::fs::remove_dir_all(dir);
+        let held = lock_dir(dir).unwrap();
+
+        let err = lock_dir(dir).unwrap_err();
+        let locked = err.downcast_ref::<InstanceLockedError>().unwrap();
+        assert_eq!(locked.holder_pid, Some(std::process::id().to_string()));
+
+        drop(held);
+        assert!(lock_dir(dir).is_ok());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,537 @@
+use crate::configure::get_config;
+use crate::resources::{
+    corrupt_task_status_count, last_db_compaction, DbPruneReport, WriteKind, GLOBAL_ROCKSDB,
+    GLOBAL_WRITE_LATENCY, ROCKSDB_COLUMN_FAMILIES,
+};
+use crate::server::clear_task_run_baseline;
+use crate::tasks::GLOBAL_LIVING_TRANSFER_TASK_MAP;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// rocksdb 对每个 column family 估算的有效数据大小
+const ROCKSDB_CF_SIZE_PROPERTY: &str = "rocksdb.estimate-live-data-size";
+
+pub static GLOBAL_METRICS: Lazy<Metrics> = Lazy::new(|| match Metrics::new() {
+    Ok(m) => m,
+    Err(e) => panic!("register metrics error: {}", e),
+});
+
+/// 服务暴露给 Prometheus 的全部指标，注册在独立的 registry 中
+pub struct Metrics {
+    registry: Registry,
+    task_objects_transferred: IntCounterVec,
+    task_bytes_transferred: IntCounterVec,
+    task_errors: IntCounterVec,
+    living_tasks: IntGauge,
+    checkpoint_snapshot_duration: Histogram,
+    checkpoint_snapshot_last_success: IntGauge,
+    rocksdb_cf_size: IntGaugeVec,
+    rocksdb_last_compaction: IntGauge,
+    rocksdb_last_compaction_duration: Gauge,
+    rocksdb_corrupt_task_statuses: IntGauge,
+    rocksdb_write_latency: GaugeVec,
+    db_pruned_entries: IntCounterVec,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    http_rate_limited: IntCounterVec,
+    webhook_failures: IntCounterVec,
+    s3_requests: IntCounter,
+    s3_retries: IntCounter,
+    dns_cache_lookups: IntCounterVec,
+    dns_failures: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Result<Self> {
+        let registry = Registry::new();
+        let task_objects_transferred = IntCounterVec::new(
+            Opts::new(
+                "mario_task_objects_transferred_total",
+                "Objects transferred successfully by task",
+            ),
+            &["task_id"],
+        )?;
+        let task_bytes_transferred = IntCounterVec::new(
+            Opts::new(
+                "mario_task_bytes_transferred_total",
+                "Bytes transferred successfully by task",
+            ),
+            &["task_id"],
+        )?;
+        let task_errors = IntCounterVec::new(
+            Opts::new(
+                "mario_task_errors_total",
+                "Objects failed to transfer by task",
+            ),
+            &["task_id"],
+        )?;
+        let living_tasks = IntGauge::new("mario_living_tasks", "Transfer tasks currently living")?;
+        let checkpoint_snapshot_duration = Histogram::with_opts(HistogramOpts::new(
+            "mario_checkpoint_snapshot_duration_seconds",
+            "Duration of checkpoint snapshots taken by the status saver",
+        ))?;
+        let checkpoint_snapshot_last_success = IntGauge::new(
+            "mario_checkpoint_snapshot_last_success_timestamp_seconds",
+            "Unix time of the last successful checkpoint snapshot",
+        )?;
+        let rocksdb_cf_size = IntGaugeVec::new(
+            Opts::new(
+                "mario_rocksdb_cf_estimated_size_bytes",
+                "Estimated live data size of rocksdb column families",
+            ),
+            &["cf"],
+        )?;
+        let rocksdb_last_compaction = IntGauge::new(
+            "mario_rocksdb_last_compaction_timestamp_seconds",
+            "Unix time of the last successful rocksdb compaction",
+        )?;
+        let rocksdb_last_compaction_duration = Gauge::new(
+            "mario_rocksdb_last_compaction_duration_seconds",
+            "Duration of the last successful rocksdb compaction",
+        )?;
+        let rocksdb_corrupt_task_statuses = IntGauge::new(
+            "mario_rocksdb_corrupt_task_statuses",
+            "Task status rows that cannot be decoded in the latest scan",
+        )?;
+        let rocksdb_write_latency = GaugeVec::new(
+            Opts::new(
+                "mario_rocksdb_write_latency_seconds",
+                "Checkpoint and task status write latency quantiles over health.latency_window_secs",
+            ),
+            &["kind", "quantile"],
+        )?;
+        let db_pruned_entries = IntCounterVec::new(
+            Opts::new(
+                "mario_db_pruned_entries_total",
+                "Rocksdb entries removed by the retention sweep",
+            ),
+            &["kind"],
+        )?;
+        let http_requests = IntCounterVec::new(
+            Opts::new("mario_http_requests_total", "HTTP requests handled"),
+            &["method", "path", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "mario_http_request_duration_seconds",
+                "HTTP request latency",
+            ),
+            &["method", "path"],
+        )?;
+
+        let http_rate_limited = IntCounterVec::new(
+            Opts::new(
+                "mario_http_rate_limited_total",
+                "HTTP requests rejected by the rate limiter",
+            ),
+            &["group"],
+        )?;
+
+        let webhook_failures = IntCounterVec::new(
+            Opts::new(
+                "mario_webhook_deliveries_failed_total",
+                "Webhook notifications not delivered after all retries",
+            ),
+            &["event"],
+        )?;
+
+        let s3_requests = IntCounter::new(
+            "mario_s3_requests_total",
+            "Object storage requests issued by the s3 client",
+        )?;
+        let s3_retries = IntCounter::new(
+            "mario_s3_retries_total",
+            "Object storage request attempts retried by the s3 client",
+        )?;
+        let dns_cache_lookups = IntCounterVec::new(
+            Opts::new(
+                "mario_dns_cache_lookups_total",
+                "Endpoint dns lookups of the s3 client by cache result",
+            ),
+            &["result"],
+        )?;
+        let dns_failures = IntCounter::new(
+            "mario_dns_resolve_failures_total",
+            "Endpoint dns resolutions failed, including those served from stale cache",
+        )?;
+
+        registry.register(Box::new(task_objects_transferred.clone()))?;
+        registry.register(Box::new(task_bytes_transferred.clone()))?;
+        registry.register(Box::new(task_errors.clone()))?;
+        registry.register(Box::new(living_tasks.clone()))?;
+        registry.register(Box::new(checkpoint_snapshot_duration.clone()))?;
+        registry.register(Box::new(checkpoint_snapshot_last_success.clone()))?;
+        registry.register(Box::new(rocksdb_cf_size.clone()))?;
+        registry.register(Box::new(rocksdb_last_compaction.clone()))?;
+        registry.register(Box::new(rocksdb_last_compaction_duration.clone()))?;
+        registry.register(Box::new(rocksdb_corrupt_task_statuses.clone()))?;
+        registry.register(Box::new(rocksdb_write_latency.clone()))?;
+        registry.register(Box::new(db_pruned_entries.clone()))?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(http_rate_limited.clone()))?;
+        registry.register(Box::new(webhook_failures.clone()))?;
+        registry.register(Box::new(s3_requests.clone()))?;
+        registry.register(Box::new(s3_retries.clone()))?;
+        registry.register(Box::new(dns_cache_lookups.clone()))?;
+        registry.register(Box::new(dns_failures.clone()))?;
+        // 进程 cpu、内存、文件句柄等指标，仅 linux 支持
+        #[cfg(target_os = "linux")]
+        registry.register(Box::new(
+            prometheus::process_collector::ProcessCollector::for_self(),
+        ))?;
+
+        Ok(Self {
+            registry,
+            task_objects_transferred,
+            task_bytes_transferred,
+            task_errors,
+            living_tasks,
+            checkpoint_snapshot_duration,
+            checkpoint_snapshot_last_success,
+            rocksdb_cf_size,
+            rocksdb_last_compaction,
+            rocksdb_last_compaction_duration,
+            rocksdb_corrupt_task_statuses,
+            rocksdb_write_latency,
+            db_pruned_entries,
+            http_requests,
+            http_request_duration,
+            http_rate_limited,
+            webhook_failures,
+            s3_requests,
+            s3_retries,
+            dns_cache_lookups,
+            dns_failures,
+        })
+    }
+
+    /// 刷新按需采集的指标后输出 Prometheus 文本格式
+    pub fn render(&self) -> Result<String> {
+        self.living_tasks
+            .set(GLOBAL_LIVING_TRANSFER_TASK_MAP.len() as i64);
+        for (cf_name, size) in rocksdb_cf_sizes() {
+            self.rocksdb_cf_size
+                .with_label_values(&[cf_name])
+                .set(size as i64);
+        }
+        if let Some(c) = last_db_compaction() {
+            self.rocksdb_last_compaction.set(c.finished_at as i64);
+            self.rocksdb_last_compaction_duration
+                .set(c.duration_ms as f64 / 1000.0);
+        }
+        self.rocksdb_corrupt_task_statuses
+            .set(corrupt_task_status_count() as i64);
+        if let Ok(config) = get_config() {
+            self.refresh_write_latency(Duration::from_secs(config.health.latency_window_secs));
+        }
+        self.encode()
+    }
+
+    // 与 /readyz 使用同一统计窗口
+    fn refresh_write_latency(&self, window: Duration) {
+        for (kind, label) in [
+            (WriteKind::Checkpoint, "checkpoint"),
+            (WriteKind::TaskStatus, "task_status"),
+        ] {
+            let stats = GLOBAL_WRITE_LATENCY.stats(kind, window);
+            self.rocksdb_write_latency
+                .with_label_values(&[label, "0.5"])
+                .set(stats.p50_ms / 1000.0);
+            self.rocksdb_write_latency
+                .with_label_values(&[label, "0.99"])
+                .set(stats.p99_ms / 1000.0);
+        }
+    }
+
+    fn encode(&self) -> Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| anyhow!("{}", e))
+    }
+}
+
+/// 各 column family 估算的有效数据大小，读取失败的 column family 不返回，rocksdb 未打开时为空
+pub fn rocksdb_cf_sizes() -> Vec<(&'static str, u64)> {
+    let mut sizes = vec![];
+    let db = match GLOBAL_ROCKSDB.get() {
+        Ok(db) => db,
+        Err(_) => return sizes,
+    };
+    for cf_name in ROCKSDB_COLUMN_FAMILIES {
+        let cf = match db.cf_handle(cf_name) {
+            Some(cf) => cf,
+            None => continue,
+        };
+        match db.property_int_value_cf(&cf, ROCKSDB_CF_SIZE_PROPERTY) {
+            Ok(Some(size)) => sizes.push((cf_name, size)),
+            Ok(None) => {}
+            Err(e) => log::warn!("read rocksdb {} size error: {}", cf_name, e),
+        }
+    }
+    sizes
+}
+
+/// 任务的传输计数，服务启动后累计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskCounters {
+    pub objects_transferred: u64,
+    pub bytes_transferred: u64,
+    pub errors: u64,
+}
+
+impl TaskCounters {
+    pub fn saturating_sub(&self, other: &TaskCounters) -> TaskCounters {
+        TaskCounters {
+            objects_transferred: self
+                .objects_transferred
+                .saturating_sub(other.objects_transferred),
+            bytes_transferred: self
+                .bytes_transferred
+                .saturating_sub(other.bytes_transferred),
+            errors: self.errors.saturating_sub(other.errors),
+        }
+    }
+
+    pub fn add(&mut self, other: &TaskCounters) {
+        self.objects_transferred += other.objects_transferred;
+        self.bytes_transferred += other.bytes_transferred;
+        self.errors += other.errors;
+    }
+}
+
+/// 读取任务的传输计数
+pub fn task_counters(task_id: &str) -> TaskCounters {
+    TaskCounters {
+        objects_transferred: GLOBAL_METRICS
+            .task_objects_transferred
+            .with_label_values(&[task_id])
+            .get(),
+        bytes_transferred: GLOBAL_METRICS
+            .task_bytes_transferred
+            .with_label_values(&[task_id])
+            .get(),
+        errors: GLOBAL_METRICS
+            .task_errors
+            .with_label_values(&[task_id])
+            .get(),
+    }
+}
+
+/// 初始化指标 registry，服务启动时调用
+pub fn init_metrics() {
+    Lazy::force(&GLOBAL_METRICS);
+}
+
+/// 记录任务成功传输的一个对象
+pub fn record_task_transferred(task_id: &str, bytes: u64) {
+    GLOBAL_METRICS
+        .task_objects_transferred
+        .with_label_values(&[task_id])
+        .inc();
+    GLOBAL_METRICS
+        .task_bytes_transferred
+        .with_label_values(&[task_id])
+        .inc_by(bytes);
+}
+
+/// 记录任务传输失败的一个对象
+pub fn record_task_error(task_id: &str) {
+    GLOBAL_METRICS
+        .task_errors
+        .with_label_values(&[task_id])
+        .inc();
+}
+
+/// 任务删除后移除其指标，避免标签无限增长
+pub fn remove_task_metrics(task_id: &str) {
+    let _ = GLOBAL_METRICS
+        .task_objects_transferred
+        .remove_label_values(&[task_id]);
+    let _ = GLOBAL_METRICS
+        .task_bytes_transferred
+        .remove_label_values(&[task_id]);
+    let _ = GLOBAL_METRICS.task_errors.remove_label_values(&[task_id]);
+    clear_task_run_baseline(task_id);
+}
+
+/// 记录 TasksStatusSaver 一轮 checkpoint 快照，成功时更新最近成功时间
+pub fn record_checkpoint_snapshot(duration: Duration, success: bool) {
+    GLOBAL_METRICS
+        .checkpoint_snapshot_duration
+        .observe(duration.as_secs_f64());
+    if !success {
+        return;
+    }
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        GLOBAL_METRICS
+            .checkpoint_snapshot_last_success
+            .set(now.as_secs() as i64);
+    }
+}
+
+/// 记录一次保留清理删除的条目数，dry run 不计入
+pub fn record_db_prune(report: &DbPruneReport) {
+    if report.dry_run {
+        return;
+    }
+    for (kind, count) in [
+        ("orphan_status", report.orphan_statuses.len()),
+        ("orphan_checkpoint", report.orphan_checkpoints.len()),
+        ("expired_status", report.expired_statuses.len()),
+    ] {
+        GLOBAL_METRICS
+            .db_pruned_entries
+            .with_label_values(&[kind])
+            .inc_by(count as u64);
+    }
+}
+
+/// 记录一次 http 请求，path 为路由模板，避免路径参数产生过多标签
+pub fn record_http_request(method: &str, path: &str, status: u16, duration: Duration) {
+    GLOBAL_METRICS
+        .http_requests
+        .with_label_values(&[method, path, &status.to_string()])
+        .inc();
+    GLOBAL_METRICS
+        .http_request_duration
+        .with_label_values(&[method, path])
+        .observe(duration.as_secs_f64());
+}
+
+/// 记录一次被限流拒绝的请求
+pub fn record_rate_limited(group: &str) {
+    GLOBAL_METRICS
+        .http_rate_limited
+        .with_label_values(&[group])
+        .inc();
+}
+
+/// 记录一次重试后仍失败的 webhook 通知
+pub fn record_webhook_failure(event: &str) {
+    GLOBAL_METRICS
+        .webhook_failures
+        .with_label_values(&[event])
+        .inc();
+}
+
+/// 记录 s3 客户端发起的一次请求
+pub fn record_s3_request() {
+    GLOBAL_METRICS.s3_requests.inc();
+}
+
+/// 记录 s3 客户端按重试策略的一次重试
+pub fn record_s3_retry() {
+    GLOBAL_METRICS.s3_retries.inc();
+}
+
+/// 记录一次 endpoint dns 查询是否命中缓存
+pub fn record_dns_lookup(hit: bool) {
+    let result = match hit {
+        true => "hit",
+        false => "miss",
+    };
+    GLOBAL_METRICS
+        .dns_cache_lookups
+        .with_label_values(&[result])
+        .inc();
+}
+
+/// 记录一次 endpoint dns 解析失败
+pub fn record_dns_failure() {
+    GLOBAL_METRICS.dns_failures.inc();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        record_checkpoint_snapshot, record_dns_failure, record_dns_lookup, record_http_request,
+        record_s3_request, record_s3_retry, record_task_error, record_task_transferred,
+        remove_task_metrics, GLOBAL_METRICS,
+    };
+    use crate::resources::{WriteKind, GLOBAL_WRITE_LATENCY};
+    use std::time::Duration;
+
+    //cargo test server::metrics::test::test_metrics_encode -- --nocapture
+    #[test]
+    fn test_metrics_encode() {
+        record_task_transferred("metrics_test_task", 1024);
+        record_task_transferred("metrics_test_task", 1024);
+        record_task_error("metrics_test_task");
+        record_checkpoint_snapshot(Duration::from_millis(5), true);
+        record_http_request(
+            "GET",
+            "/api/v1/task/:task_id/status",
+            200,
+            Duration::from_millis(3),
+        );
+
+        let text = GLOBAL_METRICS.encode().unwrap();
+        println!("{}", text);
+        assert!(
+            text.contains(r#"mario_task_objects_transferred_total{task_id="metrics_test_task"} 2"#)
+        );
+        assert!(text
+            .contains(r#"mario_task_bytes_transferred_total{task_id="metrics_test_task"} 2048"#));
+        assert!(text.contains(r#"mario_task_errors_total{task_id="metrics_test_task"} 1"#));
+        assert!(text.contains("mario_checkpoint_snapshot_duration_seconds_count"));
+        assert!(text.contains(r#"path="/api/v1/task/:task_id/status""#));
+
+        remove_task_metrics("metrics_test_task");
+        let text = GLOBAL_METRICS.encode().unwrap();
+        assert!(!text.contains("metrics_test_task"));
+    }
+
+    //cargo test server::metrics::test::test_write_latency_metrics -- --nocapture
+    #[test]
+    fn test_write_latency_metrics() {
+        for _ in 0..10 {
+            GLOBAL_WRITE_LATENCY.record(WriteKind::TaskStatus, Duration::from_millis(250));
+        }
+        GLOBAL_METRICS.refresh_write_latency(Duration::from_secs(60));
+        let p99 = GLOBAL_METRICS
+            .rocksdb_write_latency
+            .with_label_values(&["task_status", "0.99"])
+            .get();
+        assert!(p99 >= 0.25);
+        let text = GLOBAL_METRICS.encode().unwrap();
+        assert!(text
+            .contains(r#"mario_rocksdb_write_latency_seconds{kind="checkpoint",quantile="0.5"}"#));
+    }
+
+    //cargo test server::metrics::test::test_network_metrics -- --nocapture
+    #[test]
+    fn test_network_metrics() {
+        let requests = GLOBAL_METRICS.s3_requests.get();
+        let retries = GLOBAL_METRICS.s3_retries.get();
+        let misses = GLOBAL_METRICS
+            .dns_cache_lookups
+            .with_label_values(&["miss"])
+            .get();
+        record_s3_request();
+        record_s3_retry();
+        record_dns_lookup(true);
+        record_dns_lookup(false);
+        record_dns_failure();
+        assert!(GLOBAL_METRICS.s3_requests.get() > requests);
+        assert!(GLOBAL_METRICS.s3_retries.get() > retries);
+        assert!(
+            GLOBAL_METRICS
+                .dns_cache_lookups
+                .with_label_values(&["miss"])
+                .get()
+                > misses
+        );
+        let text = GLOBAL_METRICS.encode().unwrap();
+        assert!(text.contains("mario_s3_requests_total"));
+        assert!(text.contains("mario_s3_retries_total"));
+        assert!(text.contains(r#"mario_dns_cache_lookups_total{result="hit"}"#));
+        assert!(text.contains("mario_dns_resolve_failures_total"));
+    }
+}
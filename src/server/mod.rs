@@ -0,0 +1,33 @@
+mod dump;
+mod instance_lock;
+mod metrics;
+mod notify;
+mod panic_hook;
+mod pidfile;
+mod preflight;
+mod process;
+mod readiness;
+mod reload;
+mod runtime_threads;
+mod self_stats;
+mod shutdown;
+mod stats;
+mod systemd;
+mod uptime;
+
+pub use dump::*;
+pub use instance_lock::*;
+pub use metrics::*;
+pub use notify::*;
+pub use panic_hook::*;
+pub use pidfile::*;
+pub use preflight::*;
+pub use process::*;
+pub use readiness::*;
+pub use reload::*;
+pub use runtime_threads::*;
+pub use self_stats::*;
+pub use shutdown::*;
+pub use stats::*;
+pub use systemd::*;
+pub use uptime::*;
@@ -0,0 +1,379 @@
+use crate::configure::{get_config, WebhookConfig, WebhookEvent};
+use crate::server::{record_webhook_failure, task_run_counters, TaskCounters};
+use crate::tasks::{
+    task_cancellation_token, TaskStopReason, TransferTaskStatusType,
+    GLOBAL_LIVING_TRANSFER_TASK_MAP, GLOBAL_TASK_RUNTIME,
+};
+use anyhow::{anyhow, Result};
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use curl::easy::{Easy, List};
+use dashmap::DashMap;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+pub const WEBHOOK_EVENT_HEADER: &str = "X-Mario-Event";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Mario-Signature";
+// 通知队列容量，队列满时丢弃新通知，不阻塞任务执行
+const NOTIFY_CHANNEL_CAPACITY: usize = 1024;
+const WEBHOOK_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const WEBHOOK_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+// 分发协程启动前为空，此时通知直接丢弃
+static NOTIFY_SENDER: OnceCell<mpsc::Sender<TaskNotification>> = OnceCell::new();
+
+// 已发送 error_rate_exceeded 的任务及其运行开始时间，每次运行只通知一次
+static ERROR_RATE_NOTIFIED: Lazy<DashMap<String, u64>> = Lazy::new(DashMap::new);
+
+/// 发送给 webhook 的通知内容
+#[derive(Debug, Serialize, Clone)]
+pub struct TaskNotification {
+    pub task_id: String,
+    pub event: WebhookEvent,
+    pub timestamp: u64,
+    // 本次运行的开始时间
+    pub start_time: u64,
+    // 本次运行的计数
+    pub summary: TaskCounters,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<TaskStopReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_rate: Option<f64>,
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// 状态变化对应的生命周期事件，正常结束与人为停止的状态相同，按取消标识区分
+pub fn lifecycle_event(status: &TransferTaskStatusType, cancelled: bool) -> Option<WebhookEvent> {
+    match status {
+        TransferTaskStatusType::Starting => Some(WebhookEvent::Started),
+        TransferTaskStatusType::Stopped(TaskStopReason::Finish) => match cancelled {
+            true => Some(WebhookEvent::Stopped),
+            false => Some(WebhookEvent::Completed),
+        },
+        TransferTaskStatusType::Stopped(_) => Some(WebhookEvent::Failed),
+        _ => None,
+    }
+}
+
+/// 失败对象占比超过阈值时返回错误率，处理对象数不足 min_objects 时不判定
+pub fn error_rate_exceeded(
+    counters: &TaskCounters,
+    threshold: f64,
+    min_objects: u64,
+) -> Option<f64> {
+    let total = counters.objects_transferred + counters.errors;
+    if total == 0 || total < min_objects {
+        return None;
+    }
+    let rate = counters.errors as f64 / total as f64;
+    match rate > threshold {
+        true => Some(rate),
+        false => None,
+    }
+}
+
+/// 请求体的 HMAC-SHA256 签名，十六进制小写
+pub fn webhook_signature(secret: &str, body: &[u8]) -> String {
+    let mut hmac = Hmac::new(Sha256::new(), secret.as_bytes());
+    hmac.input(body);
+    hmac.result()
+        .code()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn publish(notification: TaskNotification) {
+    let sender = match NOTIFY_SENDER.get() {
+        Some(s) => s,
+        None => return,
+    };
+    let event = notification.event;
+    if let Err(e) = sender.try_send(notification) {
+        log::warn!("drop webhook notification {}: {}", event.name(), e);
+        record_webhook_failure(event.name());
+    }
+}
+
+/// 任务状态变化时调用，只入队不等待发送
+pub fn notify_task_state(task_id: &str, start_time: u64, status: &TransferTaskStatusType) {
+    let cancelled = task_cancellation_token(task_id).is_cancelled();
+    let event = match lifecycle_event(status, cancelled) {
+        Some(e) => e,
+        None => return,
+    };
+    let stop_reason = match status {
+        TransferTaskStatusType::Stopped(reason) => Some(reason.clone()),
+        _ => None,
+    };
+    publish(TaskNotification {
+        task_id: task_id.to_string(),
+        event,
+        timestamp: now_secs(),
+        start_time,
+        summary: task_run_counters(task_id),
+        stop_reason,
+        error_rate: None,
+    });
+}
+
+/// 检查运行中任务的错误率，由 TasksStatusSaver 每轮调用
+pub fn notify_error_rates() {
+    let config = match get_config() {
+        Ok(c) => c.notifications,
+        Err(_) => return,
+    };
+    if config.webhooks.is_empty() {
+        return;
+    }
+    let living = GLOBAL_LIVING_TRANSFER_TASK_MAP
+        .iter()
+        .filter(|kv| !kv.value().status.is_stopped())
+        .map(|kv| (kv.key().clone(), kv.value().start_time))
+        .collect::<Vec<_>>();
+    for (task_id, start_time) in living {
+        let notified = ERROR_RATE_NOTIFIED
+            .get(&task_id)
+            .map(|kv| *kv.value() == start_time)
+            .unwrap_or(false);
+        if notified {
+            continue;
+        }
+        let summary = task_run_counters(&task_id);
+        let rate = match error_rate_exceeded(
+            &summary,
+            config.error_rate_threshold,
+            config.error_rate_min_objects,
+        ) {
+            Some(r) => r,
+            None => continue,
+        };
+        ERROR_RATE_NOTIFIED.insert(task_id.clone(), start_time);
+        publish(TaskNotification {
+            task_id,
+            event: WebhookEvent::ErrorRateExceeded,
+            timestamp: now_secs(),
+            start_time,
+            summary,
+            stop_reason: None,
+            error_rate: Some(rate),
+        });
+    }
+}
+
+/// 任务删除时清除错误率通知记录
+pub fn clear_task_notified(task_id: &str) {
+    ERROR_RATE_NOTIFIED.remove(task_id);
+}
+
+fn post_webhook(webhook: &WebhookConfig, event: WebhookEvent, body: &[u8]) -> Result<()> {
+    let mut easy = Easy::new();
+    easy.url(&webhook.url)?;
+    easy.timeout(Duration::from_secs(webhook.timeout_secs))?;
+    let mut headers = List::new();
+    headers.append("Content-Type: application/json")?;
+    headers.append(&format!("{}: {}", WEBHOOK_EVENT_HEADER, event.name()))?;
+    if let Some(secret) = &webhook.secret {
+        headers.append(&format!(
+            "{}: sha256={}",
+            WEBHOOK_SIGNATURE_HEADER,
+            webhook_signature(secret, body)
+        ))?;
+    }
+    easy.http_headers(headers)?;
+    easy.post(true)?;
+    easy.post_field_size(body.len() as u64)?;
+    {
+        let mut payload = body;
+        let mut transfer = easy.transfer();
+        transfer.read_function(|buf| Ok(payload.read(buf).unwrap_or(0)))?;
+        // 响应内容不使用
+        transfer.write_function(|data| Ok(data.len()))?;
+        transfer.perform()?;
+    }
+    let status = easy.response_code()?;
+    if !(200..300).contains(&status) {
+        return Err(anyhow!("response status {}", status));
+    }
+    Ok(())
+}
+
+// 失败后按指数退避重试，全部失败时记录日志与指标
+async fn deliver(webhook: WebhookConfig, event: WebhookEvent, body: Arc<Vec<u8>>) {
+    let mut backoff = WEBHOOK_RETRY_BACKOFF;
+    for attempt in 0..=webhook.max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(WEBHOOK_RETRY_BACKOFF_MAX);
+        }
+        let (w, b) = (webhook.clone(), body.clone());
+        let result = match tokio::task::spawn_blocking(move || post_webhook(&w, event, &b)).await {
+            Ok(r) => r,
+            Err(e) => Err(anyhow!("{}", e)),
+        };
+        match result {
+            Ok(_) => return,
+            Err(e) => log::warn!(
+                "webhook {} event {} attempt {} failed: {}",
+                webhook.url,
+                event.name(),
+                attempt + 1,
+                e
+            ),
+        }
+    }
+    log::error!(
+        "webhook {} event {} not delivered after {} retries",
+        webhook.url,
+        event.name(),
+        webhook.max_retries
+    );
+    record_webhook_failure(event.name());
+}
+
+/// 启动 webhook 分发协程，每个通知按发送时的配置投递给订阅该事件的接收端
+pub fn spawn_webhook_dispatcher() -> Result<()> {
+    let runtime = GLOBAL_TASK_RUNTIME.get()?;
+    let (sender, mut receiver) = mpsc::channel::<TaskNotification>(NOTIFY_CHANNEL_CAPACITY);
+    if NOTIFY_SENDER.set(sender).is_err() {
+        return Ok(());
+    }
+    runtime.spawn(async move {
+        while let Some(notification) = receiver.recv().await {
+            let webhooks = match get_config() {
+                Ok(c) => c.notifications.webhooks,
+                Err(_) => continue,
+            };
+            let subscribers = webhooks
+                .into_iter()
+                .filter(|w| w.subscribes(notification.event))
+                .collect::<Vec<_>>();
+            if subscribers.is_empty() {
+                continue;
+            }
+            let body = match serde_json::to_vec(&notification) {
+                Ok(b) => Arc::new(b),
+                Err(e) => {
+                    log::error!("encode webhook notification error: {}", e);
+                    continue;
+                }
+            };
+            // 各接收端独立重试，互不阻塞
+            for webhook in subscribers {
+                tokio::spawn(deliver(webhook, notification.event, body.clone()));
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{error_rate_exceeded, lifecycle_event, webhook_signature, TaskNotification};
+    use crate::configure::WebhookEvent;
+    use crate::server::TaskCounters;
+    use crate::tasks::{
+        CriteriaBreach, TaskFailure, TaskStopReason, TransferStage, TransferTaskStatusType,
+    };
+
+    //cargo test server::notify::test::test_lifecycle_event -- --nocapture
+    #[test]
+    fn test_lifecycle_event() {
+        let finish = TransferTaskStatusType::Stopped(TaskStopReason::Finish);
+        assert_eq!(
+            lifecycle_event(&TransferTaskStatusType::Starting, false),
+            Some(WebhookEvent::Started)
+        );
+        assert_eq!(
+            lifecycle_event(&finish, false),
+            Some(WebhookEvent::Completed)
+        );
+        assert_eq!(lifecycle_event(&finish, true), Some(WebhookEvent::Stopped));
+        assert_eq!(
+            lifecycle_event(
+                &TransferTaskStatusType::Stopped(TaskStopReason::Broken),
+                false
+            ),
+            Some(WebhookEvent::Failed)
+        );
+        assert_eq!(
+            lifecycle_event(
+                &TransferTaskStatusType::Stopped(TaskStopReason::Failed(TaskFailure::Panicked(
+                    "boom".to_string()
+                ))),
+                true
+            ),
+            Some(WebhookEvent::Failed)
+        );
+        assert_eq!(
+            lifecycle_event(
+                &TransferTaskStatusType::Running(TransferStage::Stock),
+                false
+            ),
+            None
+        );
+    }
+
+    //cargo test server::notify::test::test_error_rate_exceeded -- --nocapture
+    #[test]
+    fn test_error_rate_exceeded() {
+        let counters = TaskCounters {
+            objects_transferred: 90,
+            bytes_transferred: 0,
+            errors: 10,
+        };
+        assert_eq!(error_rate_exceeded(&counters, 0.05, 100), Some(0.1));
+        assert_eq!(error_rate_exceeded(&counters, 0.1, 100), None);
+        // 处理对象数不足时不判定
+        assert_eq!(error_rate_exceeded(&counters, 0.05, 1000), None);
+        assert_eq!(error_rate_exceeded(&TaskCounters::default(), 0.05, 0), None);
+    }
+
+    //cargo test server::notify::test::test_criteria_not_met_notification -- --nocapture
+    #[test]
+    fn test_criteria_not_met_notification() {
+        let reason = TaskStopReason::Failed(TaskFailure::CriteriaNotMet(
+            CriteriaBreach::MaxFailedRatio {
+                limit: 0.1,
+                ratio: 0.3,
+            },
+        ));
+        let status = TransferTaskStatusType::Stopped(reason.clone());
+        assert_eq!(lifecycle_event(&status, false), Some(WebhookEvent::Failed));
+        let body = serde_json::to_value(TaskNotification {
+            task_id: "1".to_string(),
+            event: WebhookEvent::Failed,
+            timestamp: 0,
+            start_time: 0,
+            summary: TaskCounters::default(),
+            stop_reason: Some(reason),
+            error_rate: None,
+        })
+        .unwrap();
+        assert_eq!(
+            body["stop_reason"]["Failed"]["CriteriaNotMet"]["max_failed_ratio"]["ratio"],
+            0.3
+        );
+    }
+
+    //cargo test server::notify::test::test_webhook_signature -- --nocapture
+    #[test]
+    fn test_webhook_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            webhook_signature("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
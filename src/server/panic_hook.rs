@@ -0,0 +1,57 @@
+use super::record_dirty_shutdown;
+use crate::resources::{flush_rocksdb, GLOBAL_ROCKSDB};
+use crate::tasks::snapshot_living_tasks_checkpoints;
+use anyhow::Result;
+use std::backtrace::Backtrace;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// panic 时 checkpoint 与落盘的最长等待时间
+const PANIC_PERSIST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 安装 panic hook：记录 panic 与 backtrace，尽力保存活动任务 checkpoint、rocksdb 落盘并写入异常标识
+/// 执行原有 hook 前返回，不改变 panic 后的行为
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}\n{}", info, Backtrace::force_capture());
+        persist_on_panic(&info.to_string());
+        previous(info);
+    }));
+}
+
+// panic 线程可能持有任务状态相关的锁，在独立线程中执行并限制等待时间
+fn persist_on_panic(message: &str) {
+    let message = message.to_string();
+    let (tx, rx) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("panic-persist".to_string())
+        .spawn(move || {
+            let _ = tx.send(persist_state(&message));
+        });
+    if let Err(e) = spawned {
+        log::error!("spawn panic persist thread error: {}", e);
+        return;
+    }
+    match rx.recv_timeout(PANIC_PERSIST_TIMEOUT) {
+        Ok(Ok(_)) => log::info!("checkpoints and rocksdb flushed after panic"),
+        Ok(Err(e)) => log::error!("persist state after panic error: {}", e),
+        Err(_) => log::error!(
+            "persist state after panic not finished in {:?}",
+            PANIC_PERSIST_TIMEOUT
+        ),
+    }
+}
+
+fn persist_state(message: &str) -> Result<()> {
+    // rocksdb 未打开时没有需要持久化的状态
+    let db = GLOBAL_ROCKSDB.get()?;
+    if let Err(e) = snapshot_living_tasks_checkpoints() {
+        log::error!("{}", e);
+    }
+    record_dirty_shutdown(message)?;
+    flush_rocksdb()?;
+    db.flush_wal(true)?;
+    Ok(())
+}
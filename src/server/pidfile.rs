@@ -0,0 +1,198 @@
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use sysinfo::{Pid, Process, ProcessStatus, System};
+
+/// pid 文件记录的进程状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum PidFileStatus {
+    Missing,
+    // 进程存在且为本程序
+    Running(Pid),
+    // 进程已退出，如被 OOM kill
+    NotRunning(Pid),
+    // pid 已被其他程序的进程复用
+    Stale { pid: Pid, name: String },
+}
+
+impl PidFileStatus {
+    pub fn is_stale(&self) -> bool {
+        match self {
+            PidFileStatus::NotRunning(_) | PidFileStatus::Stale { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for PidFileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PidFileStatus::Missing => write!(f, "pid file not exist"),
+            PidFileStatus::Running(pid) => write!(f, "server running (pid {})", pid),
+            PidFileStatus::NotRunning(pid) => write!(f, "stale pid file (pid {} not running)", pid),
+            PidFileStatus::Stale { pid, name } => {
+                write!(f, "stale pid file (pid {} belongs to {})", pid, name)
+            }
+        }
+    }
+}
+
+/// 进程存在且不是僵尸进程
+pub fn process_alive(sys: &mut System, pid: Pid) -> bool {
+    if !sys.refresh_process(pid) {
+        return false;
+    }
+    match sys.process(pid) {
+        Some(p) => p.status() != ProcessStatus::Zombie,
+        None => false,
+    }
+}
+
+fn file_name_of(path: &Path) -> Option<&str> {
+    path.file_name().and_then(|n| n.to_str())
+}
+
+// linux 下进程名最长 15 字节，依次比较进程名、可执行文件与命令行第一个参数
+fn process_matches_binary(process: &Process, binary: &str) -> bool {
+    if process.name() == binary {
+        return true;
+    }
+    if let Some(exe) = process.exe() {
+        if file_name_of(exe) == Some(binary) {
+            return true;
+        }
+    }
+    match process.cmd().first() {
+        Some(c) => file_name_of(Path::new(c)) == Some(binary),
+        None => false,
+    }
+}
+
+/// 当前程序的可执行文件名
+pub fn current_binary_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    file_name_of(&exe).map(|n| n.to_string())
+}
+
+pub fn read_pid_file(path: &str) -> Result<Option<Pid>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).context("read pid file error")?;
+    let pid =
+        Pid::from_str(content.trim()).map_err(|e| anyhow!("invalid pid file {}: {}", path, e))?;
+    Ok(Some(pid))
+}
+
+/// 按可执行文件名校验 pid 文件记录的进程
+pub fn check_pid_file_with_binary(path: &str, binary: &str) -> Result<PidFileStatus> {
+    let pid = match read_pid_file(path)? {
+        Some(p) => p,
+        None => return Ok(PidFileStatus::Missing),
+    };
+    let mut sys = System::new();
+    if !process_alive(&mut sys, pid) {
+        return Ok(PidFileStatus::NotRunning(pid));
+    }
+    match sys.process(pid) {
+        Some(p) if process_matches_binary(p, binary) => Ok(PidFileStatus::Running(pid)),
+        Some(p) => Ok(PidFileStatus::Stale {
+            pid,
+            name: p.name().to_string(),
+        }),
+        None => Ok(PidFileStatus::NotRunning(pid)),
+    }
+}
+
+/// 校验 pid 文件记录的进程是否为本程序，无法获取当前程序名时不校验进程名
+pub fn check_pid_file(path: &str) -> Result<PidFileStatus> {
+    match current_binary_name() {
+        Some(binary) => check_pid_file_with_binary(path, &binary),
+        None => match read_pid_file(path)? {
+            Some(pid) => match process_alive(&mut System::new(), pid) {
+                true => Ok(PidFileStatus::Running(pid)),
+                false => Ok(PidFileStatus::NotRunning(pid)),
+            },
+            None => Ok(PidFileStatus::Missing),
+        },
+    }
+}
+
+/// pid 文件已失效时删除，返回检查结果
+pub fn clean_stale_pid_file(path: &str) -> Result<PidFileStatus> {
+    let status = check_pid_file(path)?;
+    if status.is_stale() {
+        fs::remove_file(path).with_context(|| format!("remove pid file {} error", path))?;
+        log::warn!("{}, removed", status);
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::{check_pid_file_with_binary, read_pid_file, PidFileStatus};
+    use std::process::Command;
+    use sysinfo::Pid;
+
+    fn pid_file(name: &str, content: &str) -> String {
+        let path = format!("/tmp/mario_pidfile_test_{}_{}", name, std::process::id());
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    //cargo test server::pidfile::test::test_check_pid_file -- --nocapture
+    #[test]
+    fn test_check_pid_file() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = Pid::from_u32(child.id());
+        let path = pid_file("running", &child.id().to_string());
+
+        // 进程属于本程序
+        assert_eq!(
+            check_pid_file_with_binary(&path, "sleep").unwrap(),
+            PidFileStatus::Running(pid)
+        );
+        // pid 被其他程序复用
+        let status = check_pid_file_with_binary(&path, "mario").unwrap();
+        assert_eq!(
+            status,
+            PidFileStatus::Stale {
+                pid,
+                name: "sleep".to_string()
+            }
+        );
+        assert_eq!(
+            status.to_string(),
+            format!("stale pid file (pid {} belongs to sleep)", pid)
+        );
+
+        // 进程已退出
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let status = check_pid_file_with_binary(&path, "sleep").unwrap();
+        assert_eq!(status, PidFileStatus::NotRunning(pid));
+        assert!(status.is_stale());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    //cargo test server::pidfile::test::test_read_pid_file -- --nocapture
+    #[test]
+    fn test_read_pid_file() {
+        assert!(read_pid_file("/tmp/mario_pidfile_not_exist")
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            check_pid_file_with_binary("/tmp/mario_pidfile_not_exist", "mario").unwrap(),
+            PidFileStatus::Missing
+        );
+        let path = pid_file("invalid", "not a pid");
+        assert!(read_pid_file(&path).is_err());
+        let path_ok = pid_file("valid", "123\n");
+        assert_eq!(read_pid_file(&path_ok).unwrap(), Some(Pid::from_u32(123)));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&path_ok);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::configure::{Config, HttpEndpoint};
+use crate::httpserver::{cors_layer, load_tls_config};
+use crate::logger::parse_log_level;
+use crate::resources::{get_rocksdb_path, init_rocksdb, next_db_compaction};
+use std::fmt::Display;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+
+// checkpoint 快照间隔允许范围，单位秒
+const CHECKPOINT_INTERVAL_RANGE: (u64, u64) = (1, 3600);
+
+/// 单项启动前检查失败
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreflightFailure {
+    pub check: &'static str,
+    pub message: String,
+}
+
+impl Display for PreflightFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.check, self.message)
+    }
+}
+
+fn failure(check: &'static str, message: String) -> PreflightFailure {
+    PreflightFailure { check, message }
+}
+
+/// 配置项自身的检查，供 start 与 config validate 共用
+pub fn preflight_config(config: &Config) -> Vec<PreflightFailure> {
+    let mut failures = vec![];
+    if let Err(e) = config.http.endpoints() {
+        failures.push(failure(http_listen_check(config), e.to_string()));
+    }
+    if config.http.unix.is_some() {
+        if let Err(e) = config.http.unix_socket_mode() {
+            failures.push(failure("http.unix_mode", e.to_string()));
+        }
+    }
+    if let Some(tls) = &config.http.tls {
+        if let Err(e) = load_tls_config(tls) {
+            failures.push(failure("http.tls", e.to_string()));
+        }
+    }
+    if let Some(cors) = &config.http.cors {
+        if let Err(e) = cors_layer(cors) {
+            failures.push(failure("http.cors", e.to_string()));
+        }
+    }
+    if let Err(e) = config.http.rate_limit.validate() {
+        failures.push(failure("http.rate_limit", e.to_string()));
+    }
+    if let Err(e) = parse_log_level(&config.log.level) {
+        failures.push(failure("log.level", e.to_string()));
+    }
+    let (min, max) = CHECKPOINT_INTERVAL_RANGE;
+    if config.task.checkpoint_interval < min || config.task.checkpoint_interval > max {
+        failures.push(failure(
+            "task.checkpoint_interval",
+            format!(
+                "{} out of range, must be between {} and {} seconds",
+                config.task.checkpoint_interval, min, max
+            ),
+        ));
+    }
+    if let Err(e) = next_db_compaction(&config.db.compaction_schedule, 0) {
+        failures.push(failure("db.compaction_schedule", e.to_string()));
+    }
+    if let Err(e) = check_dir_writable(&config.meta_dir) {
+        failures.push(failure("meta_dir", e));
+    }
+    if let Err(e) = check_dir_writable(&config.rocksdb_path()) {
+        failures.push(failure("rocksdb.path", e));
+    }
+    failures
+}
+
+/// 依赖运行环境的检查：端口是否可用、rocksdb 能否打开，仅在启动时执行
+pub fn preflight_runtime(config: &Config) -> Vec<PreflightFailure> {
+    let mut failures = vec![];
+    for endpoint in config.http.endpoints().unwrap_or_default() {
+        match endpoint {
+            HttpEndpoint::Tcp(addr) => failures.extend(check_port(addr, http_port_check(config))),
+            HttpEndpoint::Unix(path) => failures.extend(check_unix_socket(&path)),
+        }
+    }
+    // 仅验证能否打开，立即释放，随后由 open_global_rocksdb 打开
+    // 错误信息已包含路径
+    if let Err(e) = init_rocksdb(&get_rocksdb_path()) {
+        failures.push(failure("rocksdb", e.to_string()));
+    }
+    failures
+}
+
+// listeners 为空时监听地址来自 bind 与 port
+fn http_listen_check(config: &Config) -> &'static str {
+    match config.http.listeners.is_empty() {
+        true => "http.bind",
+        false => "http.listeners",
+    }
+}
+
+fn http_port_check(config: &Config) -> &'static str {
+    match config.http.listeners.is_empty() {
+        true => "http.port",
+        false => "http.listeners",
+    }
+}
+
+fn check_port(addr: SocketAddr, check: &'static str) -> Option<PreflightFailure> {
+    let e = match TcpListener::bind(addr) {
+        Ok(_) => return None,
+        Err(e) => e,
+    };
+    let message = match (e.kind(), port_holder(addr.port())) {
+        (ErrorKind::AddrInUse, Some(holder)) => format!(
+            "{} already in use by {}, change `{}` in config",
+            addr, holder, check
+        ),
+        (ErrorKind::AddrInUse, None) => {
+            format!("{} already in use, change `{}` in config", addr, check)
+        }
+        _ => format!("bind {} error: {}", addr, e),
+    };
+    Some(failure(check, message))
+}
+
+/// socket 所在目录需可写，已有进程监听时报错，残留文件在绑定时删除
+#[cfg(unix)]
+fn check_unix_socket(path: &str) -> Option<PreflightFailure> {
+    let dir = match Path::new(path).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_string_lossy().to_string(),
+        _ => ".".to_string(),
+    };
+    if let Err(e) = check_dir_writable(&dir) {
+        return Some(failure("http.unix", e));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Some(failure(
+            "http.unix",
+            format!("{} already in use, change `http.unix` in config", path),
+        ));
+    }
+    None
+}
+
+#[cfg(not(unix))]
+fn check_unix_socket(path: &str) -> Option<PreflightFailure> {
+    Some(failure(
+        "http.unix",
+        format!("unix socket {} not supported on this platform", path),
+    ))
+}
+
+/// 目录不存在时创建，并写入探测文件确认可写
+fn check_dir_writable(dir: &str) -> Result<(), String> {
+    if dir.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let path = Path::new(dir);
+    if path.exists() && !path.is_dir() {
+        return Err(format!("{} is not a directory", dir));
+    }
+    fs::create_dir_all(path).map_err(|e| format!("create {} error: {}", dir, e))?;
+    let probe = path.join(".preflight_probe");
+    fs::write(&probe, b"ok").map_err(|e| format!("{} not writable: {}", dir, e))?;
+    let _ = fs::remove_file(probe);
+    Ok(())
+}
+
+/// 查找监听指定端口的进程，仅 linux 支持
+#[cfg(target_os = "linux")]
+fn port_holder(port: u16) -> Option<String> {
+    let mut inodes = vec![];
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let content = match fs::read_to_string(table) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        for line in content.lines().skip(1) {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            // 字段依次为 sl local_address rem_address st ... inode，st 为 0A 表示 LISTEN
+            if fields.len() < 10 || fields[3] != "0A" {
+                continue;
+            }
+            let local_port = fields[1]
+                .rsplit(':')
+                .next()
+                .and_then(|p| u16::from_str_radix(p, 16).ok());
+            if local_port == Some(port) {
+                inodes.push(format!("socket:[{}]", fields[9]));
+            }
+        }
+    }
+    if inodes.is_empty() {
+        return None;
+    }
+
+    for entry in fs::read_dir("/proc").ok()?.filter_map(Result::ok) {
+        let pid = entry.file_name().to_string_lossy().to_string();
+        if !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        for fd in fds.filter_map(Result::ok) {
+            if let Ok(link) = fs::read_link(fd.path()) {
+                if inodes.contains(&link.to_string_lossy().to_string()) {
+                    let name = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+                    return Some(format!("pid {} ({})", pid, name.trim()));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn port_holder(_port: u16) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_port, preflight_config};
+    use crate::configure::{Config, HttpCorsConfig, HttpTlsConfig};
+
+    //cargo test server::preflight::test::test_preflight_config -- --nocapture
+    #[test]
+    fn test_preflight_config() {
+        let mut config = Config::default();
+        config.meta_dir = "/tmp/preflight_test_meta_dir".to_string();
+        assert!(preflight_config(&config).is_empty());
+
+        // 多个错误需一并返回
+        config.http.bind = "not a host!".to_string();
+        config.task.checkpoint_interval = 0;
+        config.http.rate_limit.per_second = 0.0;
+        config.http.cors = Some(HttpCorsConfig {
+            allowed_origins: vec!["*".to_string()],
+            allow_credentials: true,
+            ..HttpCorsConfig::default()
+        });
+        config.db.compaction_schedule = "every hour".to_string();
+        config.http.tls = Some(HttpTlsConfig {
+            cert: "/tmp/preflight_test_no_such_cert.pem".to_string(),
+            key: "/tmp/preflight_test_no_such_key.pem".to_string(),
+        });
+        let failures = preflight_config(&config);
+        let checks = failures.iter().map(|f| f.check).collect::<Vec<&str>>();
+        assert!(checks.contains(&"http.bind"));
+        assert!(checks.contains(&"task.checkpoint_interval"));
+        assert!(checks.contains(&"http.tls"));
+        assert!(checks.contains(&"http.rate_limit"));
+        assert!(checks.contains(&"http.cors"));
+        assert!(checks.contains(&"db.compaction_schedule"));
+        let _ = std::fs::remove_dir_all("/tmp/preflight_test_meta_dir");
+    }
+
+    //cargo test server::preflight::test::test_check_port_in_use -- --nocapture
+    #[test]
+    fn test_check_port_in_use() {
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = occupied.local_addr().unwrap();
+        let failure = check_port(addr, "http.port").unwrap();
+        assert_eq!(failure.check, "http.port");
+        assert!(failure.message.contains(&addr.port().to_string()));
+
+        drop(occupied);
+        assert!(check_port(addr, "http.port").is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use super::PID_FILE;
+use anyhow::{Context, Result};
+use std::fs;
+use std::process::Command;
+
+/// 以后台方式重新启动当前命令，去除后台启动参数避免重复启动
+fn background_command(args: &[String]) -> Command {
+    let mut cmd = Command::new(&args[0]);
+    for arg in args.iter().skip(1) {
+        if arg.eq("-d") || arg.eq("--daemon") || arg.eq("-daemon") {
+            continue;
+        }
+        cmd.arg(arg);
+    }
+    cmd
+}
+
+/// unix 下 fork 后由子进程启动服务并写入 pid 文件
+#[cfg(unix)]
+pub fn start_daemon(args: &[String]) -> Result<()> {
+    use fork::{daemon, Fork};
+    if let Ok(Fork::Child) = daemon(true, true) {
+        let child = background_command(args)
+            .spawn()
+            .context("Child process failed to start.")?;
+        fs::write(PID_FILE, child.id().to_string()).context("Write pid file error!")?;
+    }
+    Ok(())
+}
+
+/// windows 下无 fork，以脱离控制台的独立进程组启动服务并写入 pid 文件
+#[cfg(windows)]
+pub fn start_daemon(args: &[String]) -> Result<()> {
+    use std::os::windows::process::CommandExt;
+    use windows_sys::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    let child = background_command(args)
+        .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+        .spawn()
+        .context("Child process failed to start.")?;
+    fs::write(PID_FILE, child.id().to_string()).context("Write pid file error!")?;
+    Ok(())
+}
+
+/// 发送 SIGTERM，由服务端执行优雅停机流程
+#[cfg(unix)]
+pub fn terminate_process(pid: u32) -> Result<()> {
+    Command::new("kill")
+        .args(["-15", pid.to_string().as_str()])
+        .output()
+        .context("failed to execute process")?;
+    Ok(())
+}
+
+/// 发送 SIGHUP，由服务端重新加载配置
+#[cfg(unix)]
+pub fn send_reload_signal(pid: u32) -> Result<()> {
+    let pid = i32::try_from(pid).context("invalid pid")?;
+    if unsafe { libc::kill(pid, libc::SIGHUP) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("send SIGHUP error");
+    }
+    Ok(())
+}
+
+/// 发送 SIGKILL，用于停机超时后强制结束，服务端不执行停机流程
+#[cfg(unix)]
+pub fn kill_process(pid: u32) -> Result<()> {
+    Command::new("kill")
+        .args(["-9", pid.to_string().as_str()])
+        .output()
+        .context("failed to execute process")?;
+    let _ = fs::remove_file(PID_FILE);
+    Ok(())
+}
+
+// 服务端按自身 pid 创建的停机事件名，stop 命令按 pid 打开
+#[cfg(windows)]
+fn shutdown_event_name(pid: u32) -> Vec<u16> {
+    format!("Local\\mario_shutdown_{}", pid)
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+/// 服务端创建停机事件，事件每次置位向返回的通道发送一次
+/// windows 下后台进程没有控制台，无法接收 ctrl 事件，stop 命令经此事件触发优雅停机
+#[cfg(windows)]
+pub fn watch_shutdown_event() -> Result<tokio::sync::mpsc::Receiver<()>> {
+    use anyhow::anyhow;
+    use windows_sys::Win32::Foundation::WAIT_OBJECT_0;
+    use windows_sys::Win32::System::Threading::{CreateEventW, WaitForSingleObject, INFINITE};
+
+    let name = shutdown_event_name(std::process::id());
+    // 自动复位，每次置位只唤醒一次等待
+    let handle = unsafe { CreateEventW(std::ptr::null(), 0, 0, name.as_ptr()) };
+    if handle == 0 {
+        return Err(anyhow!(
+            "create shutdown event error: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    std::thread::spawn(move || loop {
+        if unsafe { WaitForSingleObject(handle, INFINITE) } != WAIT_OBJECT_0 {
+            break;
+        }
+        if tx.blocking_send(()).is_err() {
+            break;
+        }
+    });
+    Ok(rx)
+}
+
+/// 置位服务端的停机事件，由服务端执行与 ctrl 事件相同的优雅停机流程
+#[cfg(windows)]
+pub fn terminate_process(pid: u32) -> Result<()> {
+    use anyhow::anyhow;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenEventW, SetEvent, EVENT_MODIFY_STATE};
+
+    let name = shutdown_event_name(pid);
+    unsafe {
+        let handle = OpenEventW(EVENT_MODIFY_STATE, 0, name.as_ptr());
+        if handle == 0 {
+            return Err(anyhow!(
+                "open shutdown event of process {} error: {}",
+                pid,
+                std::io::Error::last_os_error()
+            ));
+        }
+        let ok = SetEvent(handle);
+        CloseHandle(handle);
+        if ok == 0 {
+            return Err(anyhow!(
+                "set shutdown event of process {} error: {}",
+                pid,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 停机超时后强制结束进程并清理 pid 文件，服务端不执行停机流程
+/// rocksdb 依赖 wal 恢复，checkpoint 以最近一次快照为准
+#[cfg(windows)]
+pub fn kill_process(pid: u32) -> Result<()> {
+    use anyhow::anyhow;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if handle == 0 {
+            return Err(anyhow!(
+                "open process {} error: {}",
+                pid,
+                std::io::Error::last_os_error()
+            ));
+        }
+        let ok = TerminateProcess(handle, 1);
+        CloseHandle(handle);
+        if ok == 0 {
+            return Err(anyhow!(
+                "terminate process {} error: {}",
+                pid,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    let _ = fs::remove_file(PID_FILE);
+    Ok(())
+}
+
+/// windows 无 SIGHUP
+#[cfg(windows)]
+pub fn send_reload_signal(_pid: u32) -> Result<()> {
+    Err(anyhow::anyhow!("reload by signal not supported on windows"))
+}
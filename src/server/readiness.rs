@@ -0,0 +1,211 @@
+use crate::configure::get_config;
+use crate::resources::{WriteLatencyReadiness, CF_TASK, GLOBAL_ROCKSDB, GLOBAL_WRITE_LATENCY};
+use crate::tasks::{server_is_draining, status_saver_heartbeat_age, GLOBAL_TASK_RUNTIME};
+use serde::Serialize;
+use std::time::Duration;
+
+// 任务 runtime 接收新任务的等待上限
+const RUNTIME_SPAWN_TIMEOUT: Duration = Duration::from_secs(1);
+// TasksStatusSaver 超过该轮数未执行视为卡死
+const STATUS_SAVER_MAX_MISSED_ROUNDS: u64 = 3;
+// rocksdb 读检查使用的 key，不要求存在
+const ROCKSDB_PROBE_KEY: &[u8] = b"__readyz_probe";
+
+/// 单项就绪检查结果
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ReadinessCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub message: Option<String>,
+}
+
+impl ReadinessCheck {
+    pub fn pass(name: &'static str) -> Self {
+        Self {
+            name,
+            ok: true,
+            message: None,
+        }
+    }
+
+    pub fn fail(name: &'static str, message: impl ToString) -> Self {
+        Self {
+            name,
+            ok: false,
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+/// 就绪检查汇总，任一检查未通过即为未就绪
+#[derive(Debug, Serialize, Clone)]
+pub struct Readiness {
+    pub ready: bool,
+    pub failing: Vec<&'static str>,
+    pub checks: Vec<ReadinessCheck>,
+    pub write_latency: Option<WriteLatencyReadiness>,
+}
+
+impl Readiness {
+    /// 未通过检查的说明，用于响应的 message
+    pub fn reason(&self) -> Option<String> {
+        let reasons = self
+            .checks
+            .iter()
+            .filter(|c| !c.ok)
+            .map(|c| match &c.message {
+                Some(m) => format!("{}: {}", c.name, m),
+                None => c.name.to_string(),
+            })
+            .collect::<Vec<String>>();
+        match reasons.is_empty() {
+            true => None,
+            false => Some(reasons.join("; ")),
+        }
+    }
+}
+
+pub fn aggregate_readiness(
+    checks: Vec<ReadinessCheck>,
+    write_latency: Option<WriteLatencyReadiness>,
+) -> Readiness {
+    let failing = checks
+        .iter()
+        .filter(|c| !c.ok)
+        .map(|c| c.name)
+        .collect::<Vec<&'static str>>();
+    Readiness {
+        ready: failing.is_empty(),
+        failing,
+        checks,
+        write_latency,
+    }
+}
+
+fn check_draining(draining: bool) -> ReadinessCheck {
+    match draining {
+        true => ReadinessCheck::fail("shutdown", "server is shutting down"),
+        false => ReadinessCheck::pass("shutdown"),
+    }
+}
+
+fn check_rocksdb() -> ReadinessCheck {
+    let db = match GLOBAL_ROCKSDB.get() {
+        Ok(db) => db,
+        Err(e) => return ReadinessCheck::fail("rocksdb", e),
+    };
+    let cf = match db.cf_handle(CF_TASK) {
+        Some(cf) => cf,
+        None => return ReadinessCheck::fail("rocksdb", "column family not exist"),
+    };
+    match db.get_cf(&cf, ROCKSDB_PROBE_KEY) {
+        Ok(_) => ReadinessCheck::pass("rocksdb"),
+        Err(e) => ReadinessCheck::fail("rocksdb", e),
+    }
+}
+
+async fn check_task_runtime() -> ReadinessCheck {
+    let handle = match GLOBAL_TASK_RUNTIME.spawn(async {}) {
+        Ok(h) => h,
+        Err(e) => return ReadinessCheck::fail("task_runtime", e),
+    };
+    match tokio::time::timeout(RUNTIME_SPAWN_TIMEOUT, handle).await {
+        Ok(Ok(_)) => ReadinessCheck::pass("task_runtime"),
+        Ok(Err(e)) => ReadinessCheck::fail("task_runtime", e),
+        Err(_) => ReadinessCheck::fail(
+            "task_runtime",
+            format!("spawned task not run within {:?}", RUNTIME_SPAWN_TIMEOUT),
+        ),
+    }
+}
+
+fn check_status_saver(age: Option<Duration>, checkpoint_interval: u64) -> ReadinessCheck {
+    let max_age = Duration::from_secs(checkpoint_interval * STATUS_SAVER_MAX_MISSED_ROUNDS);
+    match age {
+        Some(age) if age <= max_age => ReadinessCheck::pass("status_saver"),
+        Some(age) => ReadinessCheck::fail(
+            "status_saver",
+            format!(
+                "last run {}s ago, exceeds {}s",
+                age.as_secs(),
+                max_age.as_secs()
+            ),
+        ),
+        None => ReadinessCheck::fail("status_saver", "not started"),
+    }
+}
+
+fn check_write_latency(latency: &WriteLatencyReadiness) -> ReadinessCheck {
+    match &latency.reason {
+        Some(reason) => ReadinessCheck::fail("write_latency", reason),
+        None => ReadinessCheck::pass("write_latency"),
+    }
+}
+
+/// 执行全部就绪检查；停机过程中直接返回未就绪，使负载均衡摘除流量
+pub async fn check_readiness() -> Readiness {
+    if server_is_draining() {
+        return aggregate_readiness(vec![check_draining(true)], None);
+    }
+    let config = match get_config() {
+        Ok(c) => c,
+        Err(e) => return aggregate_readiness(vec![ReadinessCheck::fail("config", e)], None),
+    };
+    let write_latency = GLOBAL_WRITE_LATENCY.readiness(&config.health);
+    let checks = vec![
+        check_draining(false),
+        check_rocksdb(),
+        check_task_runtime().await,
+        check_status_saver(
+            status_saver_heartbeat_age(),
+            config.task.checkpoint_interval,
+        ),
+        check_write_latency(&write_latency),
+    ];
+    aggregate_readiness(checks, Some(write_latency))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aggregate_readiness, check_draining, check_status_saver, ReadinessCheck};
+    use std::time::Duration;
+
+    //cargo test server::readiness::test::test_aggregate_readiness -- --nocapture
+    #[test]
+    fn test_aggregate_readiness() {
+        let ok = aggregate_readiness(
+            vec![
+                check_draining(false),
+                ReadinessCheck::pass("rocksdb"),
+                check_status_saver(Some(Duration::from_secs(30)), 10),
+            ],
+            None,
+        );
+        assert!(ok.ready);
+        assert!(ok.failing.is_empty());
+        assert!(ok.reason().is_none());
+
+        let failed = aggregate_readiness(
+            vec![
+                check_draining(false),
+                ReadinessCheck::fail("rocksdb", "io error"),
+                check_status_saver(Some(Duration::from_secs(31)), 10),
+                ReadinessCheck::pass("task_runtime"),
+            ],
+            None,
+        );
+        assert!(!failed.ready);
+        assert_eq!(failed.failing, vec!["rocksdb", "status_saver"]);
+        assert_eq!(
+            failed.reason().unwrap(),
+            "rocksdb: io error; status_saver: last run 31s ago, exceeds 30s"
+        );
+
+        let not_started = aggregate_readiness(vec![check_status_saver(None, 10)], None);
+        assert_eq!(not_started.failing, vec!["status_saver"]);
+
+        let draining = aggregate_readiness(vec![check_draining(true)], None);
+        assert!(!draining.ready);
+        assert_eq!(draining.failing, vec!["shutdown"]);
+    }
+}
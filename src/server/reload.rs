@@ -0,0 +1,51 @@
+use crate::configure::{reload_config, ConfigReload};
+use crate::httpserver::reload_tls_certs;
+use crate::logger::set_log_level;
+use anyhow::Result;
+
+/// 收到 SIGHUP 时重新加载配置
+#[cfg(unix)]
+pub async fn reload_config_on_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sighup = signal(SignalKind::hangup())?;
+    while sighup.recv().await.is_some() {
+        log::info!("Received signal SIGHUP, reloading config ...");
+        if let Err(e) = apply_config_reload() {
+            log::error!("reload config error, keep running config: {}", e);
+        }
+        // 证书续期后无需重启，监听不中断
+        match reload_tls_certs() {
+            Ok(true) => log::info!("tls certs reloaded"),
+            Ok(false) => {}
+            Err(e) => log::error!("reload tls certs error, keep running certs: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// windows 无 SIGHUP，配置变更需重启生效
+#[cfg(not(unix))]
+pub async fn reload_config_on_signal() -> Result<()> {
+    Ok(())
+}
+
+/// 重新加载配置并应用可热更新的部分，checkpoint 间隔等由使用方每次读取配置生效
+pub fn apply_config_reload() -> Result<ConfigReload> {
+    let reload = reload_config()?;
+    if reload.old.log.level != reload.new.log.level {
+        set_log_level(&reload.new.log.level)?;
+    }
+    if reload.applied.is_empty() && reload.restart_required.is_empty() {
+        log::info!("config reloaded, nothing changed");
+    }
+    if !reload.applied.is_empty() {
+        log::info!("config reloaded, applied: {}", reload.applied.join(", "));
+    }
+    if !reload.restart_required.is_empty() {
+        log::warn!(
+            "config reloaded, keep running values until restart: {}",
+            reload.restart_required.join(", ")
+        );
+    }
+    Ok(reload)
+}
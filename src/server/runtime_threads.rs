@@ -0,0 +1,79 @@
+use anyhow::Result;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::runtime::{self, Runtime};
+
+// tokio 默认值
+const MAX_IO_EVENTS_PER_TICK_DEFAULT: usize = 1024;
+
+// 各 runtime 构建时实际使用的参数，配置重载不影响已构建的 runtime
+static GLOBAL_RUNTIME_THREADS: Lazy<DashMap<String, RuntimeThreads>> = Lazy::new(DashMap::new);
+
+/// runtime 实际使用的线程参数
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RuntimeThreads {
+    pub worker_threads: usize,
+    pub max_io_events_per_tick: usize,
+}
+
+impl RuntimeThreads {
+    /// 配置值为 0 时 worker_threads 取 cpu 核数，max_io_events_per_tick 取 tokio 默认值
+    pub fn resolve(worker_threads: usize, max_io_events_per_tick: usize) -> Self {
+        Self {
+            worker_threads: match worker_threads {
+                0 => num_cpus::get(),
+                n => n,
+            },
+            max_io_events_per_tick: match max_io_events_per_tick {
+                0 => MAX_IO_EVENTS_PER_TICK_DEFAULT,
+                n => n,
+            },
+        }
+    }
+}
+
+/// 构建多线程 runtime 并记录实际参数
+pub fn build_runtime(name: &str, threads: RuntimeThreads) -> Result<Runtime> {
+    let rt = runtime::Builder::new_multi_thread()
+        .worker_threads(threads.worker_threads)
+        .enable_all()
+        .max_io_events_per_tick(threads.max_io_events_per_tick)
+        .build()?;
+    log::info!(
+        "{} runtime worker_threads={} max_io_events_per_tick={}",
+        name,
+        threads.worker_threads,
+        threads.max_io_events_per_tick
+    );
+    GLOBAL_RUNTIME_THREADS.insert(name.to_string(), threads);
+    Ok(rt)
+}
+
+pub fn runtime_threads() -> BTreeMap<String, RuntimeThreads> {
+    GLOBAL_RUNTIME_THREADS
+        .iter()
+        .map(|kv| (kv.key().clone(), *kv.value()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::RuntimeThreads;
+
+    //cargo test server::runtime_threads::test::test_resolve_runtime_threads -- --nocapture
+    #[test]
+    fn test_resolve_runtime_threads() {
+        let t = RuntimeThreads::resolve(0, 0);
+        assert_eq!(t.worker_threads, num_cpus::get());
+        assert_eq!(t.max_io_events_per_tick, 1024);
+        assert_eq!(
+            RuntimeThreads::resolve(4, 32),
+            RuntimeThreads {
+                worker_threads: 4,
+                max_io_events_per_tick: 32
+            }
+        );
+    }
+}
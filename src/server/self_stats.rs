@@ -0,0 +1,268 @@
+use crate::configure::{get_config, SelfStatsConfig};
+use crate::resources::{CF_SELF_STATS, GLOBAL_ROCKSDB};
+use crate::tasks::{GLOBAL_TASKS_EXEC_JOINSET, GLOBAL_TASK_RUNTIME};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use rocksdb::{Direction, IteratorMode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sysinfo::{Pid, System};
+use tokio::runtime::Runtime;
+
+// /server/info 与 /admin/self-stats 汇总的时间范围
+pub const SELF_STATS_SUMMARY_WINDOW: Duration = Duration::from_secs(3600);
+
+static SELF_STATS_RING: Lazy<RwLock<VecDeque<SelfStatsSample>>> =
+    Lazy::new(|| RwLock::new(VecDeque::new()));
+
+/// 服务进程资源使用采样点，平台不支持的指标为 None
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SelfStatsSample {
+    pub timestamp: u64,
+    pub cpu_percent: Option<f32>,
+    pub rss_bytes: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub runtime_workers: usize,
+    pub runtime_alive_tasks: usize,
+    pub living_tasks: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MinAvgMax {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SelfStatsSummary {
+    pub samples: usize,
+    pub cpu_percent: Option<MinAvgMax>,
+    pub rss_bytes: Option<MinAvgMax>,
+    pub open_fds: Option<MinAvgMax>,
+}
+
+struct SelfSampler {
+    sys: System,
+    pid: Option<Pid>,
+}
+
+impl SelfSampler {
+    fn new() -> Self {
+        let pid = match sysinfo::get_current_pid() {
+            Ok(p) => Some(p),
+            Err(e) => {
+                log::warn!("get current pid error: {}", e);
+                None
+            }
+        };
+        Self {
+            sys: System::new(),
+            pid,
+        }
+    }
+
+    // cpu 使用率依赖两次刷新的差值，sampler 需跨轮次复用
+    fn sample(&mut self, runtime: &Runtime) -> SelfStatsSample {
+        let (cpu_percent, rss_bytes) = match self.pid {
+            Some(pid) if self.sys.refresh_process(pid) => match self.sys.process(pid) {
+                Some(p) => (Some(p.cpu_usage()), Some(p.memory())),
+                None => (None, None),
+            },
+            _ => (None, None),
+        };
+        let metrics = runtime.metrics();
+        SelfStatsSample {
+            timestamp: now_secs(),
+            cpu_percent,
+            rss_bytes,
+            open_fds: open_fds(),
+            runtime_workers: metrics.num_workers(),
+            runtime_alive_tasks: metrics.num_alive_tasks(),
+            living_tasks: GLOBAL_TASKS_EXEC_JOINSET.len(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<u64> {
+    match std::fs::read_dir("/proc/self/fd") {
+        Ok(dir) => Some(dir.count() as u64),
+        Err(_) => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<u64> {
+    None
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+fn record_sample(sample: SelfStatsSample, config: &SelfStatsConfig) {
+    if let Ok(mut ring) = SELF_STATS_RING.write() {
+        while ring.len() >= config.ring_size.max(1) {
+            ring.pop_front();
+        }
+        ring.push_back(sample.clone());
+    }
+    if config.persist {
+        if let Err(e) = persist_sample(&sample, config.retention_secs) {
+            log::warn!("persist self stats error: {}", e);
+        }
+    }
+}
+
+// key 使用大端时间戳，保证按时间有序
+fn persist_sample(sample: &SelfStatsSample, retention_secs: u64) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_SELF_STATS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let encoded = serde_json::to_vec(sample)?;
+    db.put_cf(&cf, sample.timestamp.to_be_bytes(), encoded)?;
+    let expired = sample.timestamp.saturating_sub(retention_secs);
+    db.delete_range_cf(&cf, 0u64.to_be_bytes(), expired.to_be_bytes())?;
+    Ok(())
+}
+
+/// 每隔 interval_secs 采样一次，每轮读取配置，关闭采样后不再记录
+pub fn spawn_self_stats_sampler() -> Result<()> {
+    let runtime = GLOBAL_TASK_RUNTIME.get()?;
+    runtime.spawn(async move {
+        let mut sampler = SelfSampler::new();
+        loop {
+            let config = match get_config() {
+                Ok(c) => c.self_stats,
+                Err(_) => SelfStatsConfig::default(),
+            };
+            if config.enabled {
+                let sample = sampler.sample(runtime);
+                record_sample(sample, &config);
+            }
+            tokio::time::sleep(Duration::from_secs(config.interval_secs.max(1))).await;
+        }
+    });
+    Ok(())
+}
+
+/// 获取 since 之后的采样点，开启持久化时从 cf_self_stats 读取
+pub fn self_stats_since(since: u64) -> Result<Vec<SelfStatsSample>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let persist = get_config()?.self_stats.persist;
+    if !persist {
+        let ring = SELF_STATS_RING
+            .read()
+            .map_err(|e| anyhow!("{}", e.to_string()))?;
+        return Ok(ring
+            .iter()
+            .filter(|s| s.timestamp >= since)
+            .cloned()
+            .collect());
+    }
+
+    let cf = match db.cf_handle(CF_SELF_STATS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut samples = vec![];
+    let start = since.to_be_bytes();
+    for item in db.iterator_cf(&cf, IteratorMode::From(&start, Direction::Forward)) {
+        let (_, value) = item?;
+        samples.push(serde_json::from_slice::<SelfStatsSample>(&value)?);
+    }
+    Ok(samples)
+}
+
+/// 汇总最近 window 内的采样点
+pub fn self_stats_summary(window: Duration) -> Result<SelfStatsSummary> {
+    let since = now_secs().saturating_sub(window.as_secs());
+    Ok(summarize(&self_stats_since(since)?))
+}
+
+pub fn summarize(samples: &[SelfStatsSample]) -> SelfStatsSummary {
+    SelfStatsSummary {
+        samples: samples.len(),
+        cpu_percent: min_avg_max(samples.iter().filter_map(|s| s.cpu_percent.map(f64::from))),
+        rss_bytes: min_avg_max(samples.iter().filter_map(|s| s.rss_bytes.map(|v| v as f64))),
+        open_fds: min_avg_max(samples.iter().filter_map(|s| s.open_fds.map(|v| v as f64))),
+    }
+}
+
+fn min_avg_max(values: impl Iterator<Item = f64>) -> Option<MinAvgMax> {
+    let mut count = 0;
+    let mut m = MinAvgMax {
+        min: f64::MAX,
+        avg: 0.0,
+        max: f64::MIN,
+    };
+    for v in values {
+        count += 1;
+        m.min = m.min.min(v);
+        m.max = m.max.max(v);
+        m.avg += v;
+    }
+    if count == 0 {
+        return None;
+    }
+    m.avg /= count as f64;
+    Some(m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        now_secs, record_sample, self_stats_summary, summarize, SelfStatsSample,
+        SELF_STATS_SUMMARY_WINDOW,
+    };
+    use crate::configure::SelfStatsConfig;
+
+    //cargo test server::self_stats::test::test_summarize -- --nocapture
+    #[test]
+    fn test_summarize() {
+        let sample = |cpu: f32, rss: u64| SelfStatsSample {
+            timestamp: 0,
+            cpu_percent: Some(cpu),
+            rss_bytes: Some(rss),
+            open_fds: None,
+            runtime_workers: 1,
+            runtime_alive_tasks: 0,
+            living_tasks: 0,
+        };
+        let summary = summarize(&[sample(10.0, 100), sample(30.0, 300)]);
+        assert_eq!(summary.samples, 2);
+        let cpu = summary.cpu_percent.unwrap();
+        assert_eq!((cpu.min, cpu.avg, cpu.max), (10.0, 20.0, 30.0));
+        assert_eq!(summary.rss_bytes.unwrap().avg, 200.0);
+        assert!(summary.open_fds.is_none());
+    }
+
+    //cargo test server::self_stats::test::test_self_stats_summary_window -- --nocapture
+    #[test]
+    fn test_self_stats_summary_window() {
+        let sample = |timestamp: u64| SelfStatsSample {
+            timestamp,
+            cpu_percent: Some(50.0),
+            rss_bytes: None,
+            open_fds: None,
+            runtime_workers: 1,
+            runtime_alive_tasks: 0,
+            living_tasks: 0,
+        };
+        let config = SelfStatsConfig::default();
+        // 一小时以前的采样点不计入汇总
+        record_sample(sample(0), &config);
+        record_sample(sample(now_secs()), &config);
+        let summary = self_stats_summary(SELF_STATS_SUMMARY_WINDOW).unwrap();
+        assert_eq!(summary.samples, 1);
+        assert_eq!(summary.cpu_percent.unwrap().max, 50.0);
+    }
+}
@@ -0,0 +1,278 @@
+use super::{notify_stopping, record_clean_stop};
+use crate::cmd::{ExitStatus, EXIT_CODE_FAILURE};
+use crate::resources::flush_rocksdb;
+use crate::tasks::{
+    checkpoint_tasks_on_quiesce, drain_tasks, set_server_draining, stop_tasks_by_cost,
+    TaskShutdownCost,
+};
+use anyhow::Result;
+use std::time::Instant;
+use std::{fs, path::Path, time::Duration};
+use tokio::sync::{mpsc, watch};
+use tokio::task::AbortHandle;
+
+pub const PID_FILE: &'static str = "pid";
+
+#[cfg(unix)]
+pub struct TermSignals {
+    sigterm: tokio::signal::unix::Signal,
+    sigint: tokio::signal::unix::Signal,
+    sigquit: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl TermSignals {
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            sigterm: signal(SignalKind::terminate())?,
+            sigint: signal(SignalKind::interrupt())?,
+            sigquit: signal(SignalKind::quit())?,
+        })
+    }
+
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.sigterm.recv() => "SIGTERM",
+            _ = self.sigint.recv() => "SIGINT",
+            _ = self.sigquit.recv() => "SIGQUIT",
+        }
+    }
+}
+
+#[cfg(windows)]
+pub struct TermSignals {
+    ctrl_c: tokio::signal::windows::CtrlC,
+    ctrl_break: tokio::signal::windows::CtrlBreak,
+    ctrl_close: tokio::signal::windows::CtrlClose,
+    // 后台进程没有控制台，stop 命令经停机事件触发
+    shutdown_event: mpsc::Receiver<()>,
+}
+
+#[cfg(windows)]
+impl TermSignals {
+    pub fn new() -> Result<Self> {
+        use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close};
+        Ok(Self {
+            ctrl_c: ctrl_c()?,
+            ctrl_break: ctrl_break()?,
+            ctrl_close: ctrl_close()?,
+            shutdown_event: super::watch_shutdown_event()?,
+        })
+    }
+
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.ctrl_c.recv() => "CTRL_C",
+            _ = self.ctrl_break.recv() => "CTRL_BREAK",
+            _ = self.ctrl_close.recv() => "CTRL_CLOSE",
+            Some(_) = self.shutdown_event.recv() => "SHUTDOWN_EVENT",
+        }
+    }
+}
+
+/// 将终止信号转发到通道，接收端释放后停止转发
+pub fn forward_term_signals() -> Result<mpsc::Receiver<&'static str>> {
+    let mut signals = TermSignals::new()?;
+    let (tx, rx) = mpsc::channel(2);
+    tokio::spawn(async move {
+        loop {
+            let sig = signals.recv().await;
+            if tx.send(sig).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// 等待终止信号并执行停机流程：
+/// 停止接收 http 请求 -> 按重做代价降序通知任务停止 -> 任务退出后立即保存 checkpoint -> rocksdb 落盘 -> 删除 pid 文件
+/// 宽限期内再次收到终止信号则立即退出
+pub async fn graceful_shutdown_on_signal(
+    http_shutdown: watch::Sender<bool>,
+    grace: Duration,
+) -> Result<ExitStatus> {
+    let triggers = forward_term_signals()?;
+    let status = graceful_shutdown(triggers, grace, move || {
+        notify_stopping();
+        // 先置为未就绪，使负载均衡在 http 排空期间摘除流量
+        set_server_draining();
+        let _ = http_shutdown.send(true);
+        stop_tasks_by_cost()
+    })
+    .await;
+    Ok(status)
+}
+
+/// 收到停机触发后由 begin 停止接收请求并通知任务停止，再按其返回的顺序保存 checkpoint
+/// 宽限期内再次触发则立即退出
+pub async fn graceful_shutdown<B>(
+    mut triggers: mpsc::Receiver<&'static str>,
+    grace: Duration,
+    begin: B,
+) -> ExitStatus
+where
+    B: FnOnce() -> Vec<TaskShutdownCost>,
+{
+    // 发送端全部释放同样视为停机触发
+    let sig = triggers.recv().await.unwrap_or("CLOSED");
+    log::info!("Received signal {}, shutting down ...", sig);
+    let order = begin();
+
+    tokio::select! {
+        r = shutdown_sequence(order, grace) => {
+            if let Err(e) = r {
+                log::error!("{}", e);
+            }
+            log::info!("server shutdown");
+            ExitStatus::Success
+        }
+        Some(sig) = triggers.recv() => {
+            log::warn!("Received signal {} again, force exit", sig);
+            ExitStatus::Failure(EXIT_CODE_FAILURE)
+        }
+    }
+}
+
+/// http 服务启动失败时停止已启动的任务与 TasksStatusSaver，保存 checkpoint 并落盘
+pub async fn shutdown_on_bootstrap_failure(status_saver: AbortHandle, grace: Duration) {
+    notify_stopping();
+    set_server_draining();
+    let order = stop_tasks_by_cost();
+    status_saver.abort();
+    if let Err(e) = shutdown_sequence(order, grace).await {
+        log::error!("{}", e);
+    }
+}
+
+async fn shutdown_sequence(order: Vec<TaskShutdownCost>, grace: Duration) -> Result<()> {
+    let deadline = Instant::now() + grace;
+    checkpoint_tasks_on_quiesce(order, deadline).await;
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    // 任务全部退出才视为正常停机，否则下次启动提示 checkpoint 可能落后
+    match drain_tasks(remaining).await {
+        Ok(_) => {
+            if let Err(e) = record_clean_stop() {
+                log::warn!("record clean stop error: {}", e);
+            }
+        }
+        Err(e) => log::warn!("{}", e),
+    }
+    flush_rocksdb()?;
+    remove_pid_file();
+    Ok(())
+}
+
+/// 仅当 pid 文件记录的是当前进程时删除
+pub fn remove_pid_file() {
+    if !Path::new(PID_FILE).exists() {
+        return;
+    }
+    match fs::read_to_string(PID_FILE) {
+        Ok(pid) if pid.trim().eq(&std::process::id().to_string()) => {
+            if let Err(e) = fs::remove_file(PID_FILE) {
+                log::error!("remove pid file error: {}", e);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::graceful_shutdown;
+    use crate::cmd::ExitStatus;
+    use crate::resources::{get_checkpoint, open_test_rocksdb};
+    use crate::tasks::{
+        cancel_task, register_task_cancellation, register_task_offset_map, remove_exec_joinset,
+        CheckPoint, FileDescription, FilePosition, TaskShutdownCost, TransferStage,
+        TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIVING_TRANSFER_TASK_MAP,
+        GLOBAL_TASKS_EXEC_JOINSET,
+    };
+    use dashmap::DashMap;
+    use std::sync::{atomic::Ordering, Arc};
+    use std::time::Duration;
+    use tokio::sync::{mpsc, RwLock};
+    use tokio::task::JoinSet;
+
+    // 停机经通道触发，只停止并保存本测试的任务，不影响并行运行的其他测试
+    //cargo test server::shutdown::test::test_shutdown_checkpoint_before_exit -- --nocapture
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shutdown_checkpoint_before_exit() {
+        open_test_rocksdb();
+        let task_id = format!("shutdown_checkpoint_test_{}", uuid::Uuid::new_v4());
+        // 最近一次周期快照停留在 offset 100
+        let mut checkpoint = CheckPoint {
+            task_id: task_id.clone(),
+            executing_file: FileDescription::default(),
+            executing_file_position: FilePosition {
+                offset: 100,
+                line_num: 10,
+            },
+            file_for_notify: None,
+            task_stage: TransferStage::Stock,
+            modify_checkpoint_timestamp: 0,
+            task_begin_timestamp: 0,
+        };
+        checkpoint.save_to_rocksdb_cf().unwrap();
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task_id.clone(),
+            TransferTaskStatus {
+                task_id: task_id.clone(),
+                start_time: 0,
+                run_id: String::new(),
+                status: TransferTaskStatusType::Running(TransferStage::Stock),
+            },
+        );
+
+        // 模拟长时间运行的任务：持续推进执行位置直至收到停止标识
+        let (stop_mark, _) = register_task_cancellation(&task_id);
+        let offsets = Arc::new(DashMap::<String, FilePosition>::new());
+        register_task_offset_map(&task_id, offsets.clone());
+        let exec_set = Arc::new(RwLock::new(JoinSet::<()>::new()));
+        GLOBAL_TASKS_EXEC_JOINSET.insert(task_id.clone(), exec_set.clone());
+        exec_set.write().await.spawn(async move {
+            let mut offset = 100;
+            while !stop_mark.load(Ordering::SeqCst) {
+                offset += 10;
+                offsets.insert(
+                    "batch".to_string(),
+                    FilePosition {
+                        offset,
+                        line_num: offset as u64 / 10,
+                    },
+                );
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let id = task_id.clone();
+        tokio::spawn(async move {
+            while exec_set.write().await.join_next().await.is_some() {}
+            remove_exec_joinset(&id);
+        });
+
+        let (trigger, triggers) = mpsc::channel(2);
+        let id = task_id.clone();
+        let shutdown = tokio::spawn(graceful_shutdown(
+            triggers,
+            Duration::from_secs(5),
+            move || {
+                cancel_task(&id);
+                vec![TaskShutdownCost {
+                    task_id: id,
+                    cost: 0,
+                }]
+            },
+        ));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        trigger.send("TEST").await.unwrap();
+
+        let status = shutdown.await.unwrap();
+        assert_eq!(status, ExitStatus::Success);
+        let saved = get_checkpoint(&task_id).unwrap();
+        assert!(saved.executing_file_position.offset > 100);
+        assert!(GLOBAL_TASKS_EXEC_JOINSET.get(&task_id).is_none());
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(&task_id);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::configure::{get_config, StatsConfig};
+use crate::server::{rocksdb_cf_sizes, task_counters, TaskCounters};
+use crate::tasks::{TransferTaskStatusType, GLOBAL_LIVING_TRANSFER_TASK_MAP, GLOBAL_TASK_RUNTIME};
+use anyhow::Result;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+static SERVER_STATS: Lazy<RwLock<Option<(Instant, ServerStats)>>> = Lazy::new(|| RwLock::new(None));
+
+// 任务本次运行开始时的计数，计数器在服务运行期间跨多次运行累计
+static TASK_RUN_BASELINE: Lazy<DashMap<String, TaskCounters>> = Lazy::new(DashMap::new);
+
+/// 运行中任务的汇总统计
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ServerStats {
+    pub timestamp: u64,
+    pub living_tasks: usize,
+    pub tasks_by_state: BTreeMap<String, usize>,
+    // 运行中任务本次运行的累计值
+    pub objects_transferred: u64,
+    pub bytes_transferred: u64,
+    pub errors: u64,
+    // 最近一个刷新周期内的传输速率
+    pub throughput_bytes_per_sec: f64,
+    pub rocksdb_size_bytes: u64,
+    pub meta_dir_size_bytes: u64,
+}
+
+/// 缓存的统计快照及其距今秒数，后台尚未完成首次刷新时为 None
+pub fn server_stats() -> Option<(u64, ServerStats)> {
+    let cached = SERVER_STATS.read().ok()?;
+    cached
+        .as_ref()
+        .map(|(at, stats)| (at.elapsed().as_secs(), stats.clone()))
+}
+
+/// 任务启动时记录计数基线
+pub fn mark_task_run_start(task_id: &str) {
+    TASK_RUN_BASELINE.insert(task_id.to_string(), task_counters(task_id));
+}
+
+/// 任务删除时清除计数基线
+pub fn clear_task_run_baseline(task_id: &str) {
+    TASK_RUN_BASELINE.remove(task_id);
+}
+
+/// 任务本次运行的计数
+pub fn task_run_counters(task_id: &str) -> TaskCounters {
+    let current = task_counters(task_id);
+    match TASK_RUN_BASELINE.get(task_id) {
+        Some(baseline) => current.saturating_sub(baseline.value()),
+        None => current,
+    }
+}
+
+fn task_state_name(status: &TransferTaskStatusType) -> &'static str {
+    match status {
+        TransferTaskStatusType::Queued => "queued",
+        TransferTaskStatusType::Starting => "starting",
+        TransferTaskStatusType::Running(_) => "running",
+        TransferTaskStatusType::Paused(_) => "paused",
+        TransferTaskStatusType::Stopped(_) => "stopped",
+    }
+}
+
+fn dir_size(path: &str) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// 按任务汇总计数，速率取各任务相对上一轮的增量，上一轮不存在的任务从本次运行开始计
+fn aggregate_tasks(
+    living: &[(String, TransferTaskStatusType)],
+    last_bytes: &mut HashMap<String, u64>,
+    elapsed: Duration,
+) -> (BTreeMap<String, usize>, TaskCounters, f64) {
+    let mut by_state = BTreeMap::new();
+    let mut total = TaskCounters::default();
+    let mut delta_bytes = 0;
+    let mut current_bytes = HashMap::new();
+    for (task_id, status) in living {
+        *by_state
+            .entry(task_state_name(status).to_string())
+            .or_insert(0) += 1;
+        let counters = task_run_counters(task_id);
+        total.add(&counters);
+        let last = last_bytes.get(task_id).copied().unwrap_or(0);
+        delta_bytes += counters.bytes_transferred.saturating_sub(last);
+        current_bytes.insert(task_id.clone(), counters.bytes_transferred);
+    }
+    *last_bytes = current_bytes;
+    let throughput = match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => delta_bytes as f64 / secs,
+        _ => 0.0,
+    };
+    (by_state, total, throughput)
+}
+
+/// 每隔 refresh_interval_secs 刷新统计快照，每轮读取配置
+pub fn spawn_stats_refresher() -> Result<()> {
+    GLOBAL_TASK_RUNTIME.spawn(async move {
+        let mut last_bytes = HashMap::new();
+        let mut last_refresh = Instant::now();
+        loop {
+            let (config, meta_dir) = match get_config() {
+                Ok(c) => (c.stats, c.meta_dir),
+                Err(_) => (StatsConfig::default(), "meta_dir".to_string()),
+            };
+            let living = GLOBAL_LIVING_TRANSFER_TASK_MAP
+                .iter()
+                .map(|kv| (kv.key().clone(), kv.value().status.clone()))
+                .collect::<Vec<_>>();
+            let (tasks_by_state, total, throughput) =
+                aggregate_tasks(&living, &mut last_bytes, last_refresh.elapsed());
+            last_refresh = Instant::now();
+            // 磁盘扫描可能较慢，避免阻塞 runtime 工作线程
+            let meta_dir_size_bytes =
+                match tokio::task::spawn_blocking(move || dir_size(&meta_dir)).await {
+                    Ok(size) => size,
+                    Err(e) => {
+                        log::warn!("scan meta_dir size error: {}", e);
+                        0
+                    }
+                };
+            let stats = ServerStats {
+                timestamp: now_secs(),
+                living_tasks: living.len(),
+                tasks_by_state,
+                objects_transferred: total.objects_transferred,
+                bytes_transferred: total.bytes_transferred,
+                errors: total.errors,
+                throughput_bytes_per_sec: throughput,
+                rocksdb_size_bytes: rocksdb_cf_sizes().iter().map(|(_, s)| s).sum(),
+                meta_dir_size_bytes,
+            };
+            if let Ok(mut cached) = SERVER_STATS.write() {
+                *cached = Some((Instant::now(), stats));
+            }
+            tokio::time::sleep(Duration::from_secs(config.refresh_interval_secs.max(1))).await;
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aggregate_tasks, mark_task_run_start, task_run_counters};
+    use crate::server::{record_task_error, record_task_transferred, remove_task_metrics};
+    use crate::tasks::{TaskStopReason, TransferStage, TransferTaskStatusType};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    //cargo test server::stats::test::test_aggregate_tasks -- --nocapture
+    #[test]
+    fn test_aggregate_tasks() {
+        let (a, b) = ("stats_test_a".to_string(), "stats_test_b".to_string());
+        // 上次运行的计数不计入本次运行
+        record_task_transferred(&a, 4096);
+        mark_task_run_start(&a);
+        record_task_transferred(&a, 100);
+        record_task_transferred(&a, 100);
+        record_task_error(&a);
+        mark_task_run_start(&b);
+        record_task_transferred(&b, 300);
+        assert_eq!(task_run_counters(&a).bytes_transferred, 200);
+
+        let living = vec![
+            (
+                a.clone(),
+                TransferTaskStatusType::Running(TransferStage::Stock),
+            ),
+            (
+                b.clone(),
+                TransferTaskStatusType::Paused(TransferStage::Stock),
+            ),
+        ];
+        let mut last_bytes = HashMap::new();
+        let (by_state, total, throughput) =
+            aggregate_tasks(&living, &mut last_bytes, Duration::from_secs(10));
+        assert_eq!(by_state.get("running"), Some(&1));
+        assert_eq!(by_state.get("paused"), Some(&1));
+        assert_eq!(total.objects_transferred, 3);
+        assert_eq!(total.bytes_transferred, 500);
+        assert_eq!(total.errors, 1);
+        assert_eq!(throughput, 50.0);
+
+        // 速率只计算两轮之间的增量
+        record_task_transferred(&a, 1000);
+        let living = vec![
+            (
+                a.clone(),
+                TransferTaskStatusType::Running(TransferStage::Stock),
+            ),
+            (
+                b.clone(),
+                TransferTaskStatusType::Stopped(TaskStopReason::Finish),
+            ),
+        ];
+        let (by_state, _, throughput) =
+            aggregate_tasks(&living, &mut last_bytes, Duration::from_secs(10));
+        assert_eq!(by_state.get("stopped"), Some(&1));
+        assert_eq!(throughput, 100.0);
+
+        remove_task_metrics(&a);
+        remove_task_metrics(&b);
+    }
+}
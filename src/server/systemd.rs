@@ -0,0 +1,136 @@
+use crate::configure::get_config;
+use crate::tasks::{status_saver_heartbeat_age, GLOBAL_TASK_RUNTIME};
+use anyhow::Result;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// http 服务存活标识，watchdog 仅在 http 服务与 TasksStatusSaver 均存活时发送心跳
+static HTTP_SERVER_ALIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_http_server_alive(alive: bool) {
+    HTTP_SERVER_ALIVE.store(alive, Ordering::SeqCst);
+}
+
+/// 按 systemd NOTIFY_SOCKET 协议发送状态，未设置 NOTIFY_SOCKET 时返回 false
+#[cfg(target_os = "linux")]
+pub fn sd_notify(state: &str) -> Result<bool> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(p) => p,
+        None => return Ok(false),
+    };
+    let socket = UnixDatagram::unbound()?;
+    let bytes = path.as_bytes();
+    // '@' 开头为 abstract namespace socket
+    match bytes.strip_prefix(b"@") {
+        Some(name) => {
+            let addr = SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        None => {
+            socket.send_to(state.as_bytes(), &path)?;
+        }
+    }
+    Ok(true)
+}
+
+/// 非 linux 平台无 systemd
+#[cfg(not(target_os = "linux"))]
+pub fn sd_notify(_state: &str) -> Result<bool> {
+    Ok(false)
+}
+
+fn notify(state: &str) {
+    match sd_notify(state) {
+        Ok(true) => log::debug!("sd_notify {}", state),
+        Ok(false) => {}
+        Err(e) => log::warn!("sd_notify {} error: {}", state, e),
+    }
+}
+
+pub fn notify_ready() {
+    notify("READY=1");
+}
+
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// 读取 systemd 设置的 WatchdogSec，WATCHDOG_PID 不是当前进程时视为未开启
+pub fn watchdog_interval() -> Option<Duration> {
+    env::var_os("NOTIFY_SOCKET")?;
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.trim() != std::process::id().to_string() {
+            return None;
+        }
+    }
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    match usec {
+        0 => None,
+        u => Some(Duration::from_micros(u)),
+    }
+}
+
+fn services_alive(watchdog: Duration) -> bool {
+    if !HTTP_SERVER_ALIVE.load(Ordering::SeqCst) {
+        return false;
+    }
+    let checkpoint_interval = match get_config() {
+        Ok(c) => c.task.checkpoint_interval,
+        Err(_) => 10,
+    };
+    // TasksStatusSaver 每轮间隔 checkpoint_interval，超过两轮未更新视为卡死
+    let max_age = Duration::from_secs(checkpoint_interval * 2) + watchdog;
+    match status_saver_heartbeat_age() {
+        Some(age) => age <= max_age,
+        None => false,
+    }
+}
+
+/// 开启 WatchdogSec 时以一半间隔发送 WATCHDOG=1
+pub fn spawn_systemd_watchdog() -> Result<()> {
+    let interval = match watchdog_interval() {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    log::info!("systemd watchdog enabled, interval {:?}", interval);
+    GLOBAL_TASK_RUNTIME.spawn(async move {
+        loop {
+            tokio::time::sleep(interval / 2).await;
+            if services_alive(interval) {
+                notify("WATCHDOG=1");
+            } else {
+                log::warn!("http server or task status saver not alive, skip watchdog ping");
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::sd_notify;
+    use std::os::unix::net::UnixDatagram;
+
+    //cargo test server::systemd::test::test_sd_notify -- --nocapture
+    #[test]
+    fn test_sd_notify() {
+        let path = "/tmp/sd_notify_test.sock";
+        let _ = std::fs::remove_file(path);
+        let receiver = UnixDatagram::bind(path).unwrap();
+
+        std::env::set_var("NOTIFY_SOCKET", path);
+        assert!(sd_notify("READY=1").unwrap());
+        let mut buf = [0; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::env::remove_var("NOTIFY_SOCKET");
+        assert!(!sd_notify("READY=1").unwrap());
+        let _ = std::fs::remove_file(path);
+    }
+}
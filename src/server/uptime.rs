@@ -0,0 +1,186 @@
+use crate::commons::unix_secs_to_rfc3339;
+use crate::resources::{CF_SERVER_META, GLOBAL_ROCKSDB};
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SERVER_LIFECYCLE_KEY: &'static str = "server_lifecycle";
+const DIRTY_SHUTDOWN_KEY: &'static str = "dirty_shutdown";
+
+static SERVER_START: OnceCell<ServerStart> = OnceCell::new();
+
+struct ServerStart {
+    instant: Instant,
+    timestamp: u64,
+    // 启动时读取的上次停机情况，首次启动为 None
+    last_stop: Option<LastStop>,
+}
+
+// 启动时写入 started_at，正常停机完成后写入 stopped_at
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ServerLifecycle {
+    started_at: u64,
+    stopped_at: Option<u64>,
+}
+
+/// 上次停机情况，非正常停机时任务 checkpoint 可能落后于实际进度
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LastStop {
+    pub clean: bool,
+    pub started_at: String,
+    pub stopped_at: Option<String>,
+    // 上次运行期间发生 panic
+    pub dirty_shutdown: Option<DirtyShutdown>,
+}
+
+/// panic hook 写入的异常标识，下次启动时读取后删除
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DirtyShutdown {
+    pub panicked_at: String,
+    pub message: String,
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+fn get_lifecycle() -> Result<Option<ServerLifecycle>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_SERVER_META) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    match db.get_cf(&cf, SERVER_LIFECYCLE_KEY)? {
+        Some(v) => Ok(Some(serde_json::from_slice::<ServerLifecycle>(&v)?)),
+        None => Ok(None),
+    }
+}
+
+fn put_lifecycle(lifecycle: &ServerLifecycle) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_SERVER_META) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    db.put_cf(&cf, SERVER_LIFECYCLE_KEY, serde_json::to_vec(lifecycle)?)?;
+    Ok(())
+}
+
+fn last_stop_of(lifecycle: &ServerLifecycle, dirty_shutdown: Option<DirtyShutdown>) -> LastStop {
+    LastStop {
+        clean: lifecycle.stopped_at.is_some(),
+        started_at: unix_secs_to_rfc3339(lifecycle.started_at),
+        stopped_at: lifecycle.stopped_at.map(unix_secs_to_rfc3339),
+        dirty_shutdown,
+    }
+}
+
+/// 读取并删除异常标识
+fn take_dirty_shutdown() -> Result<Option<DirtyShutdown>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_SERVER_META) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let dirty = match db.get_cf(&cf, DIRTY_SHUTDOWN_KEY)? {
+        Some(v) => Some(serde_json::from_slice::<DirtyShutdown>(&v)?),
+        None => None,
+    };
+    if dirty.is_some() {
+        db.delete_cf(&cf, DIRTY_SHUTDOWN_KEY)?;
+    }
+    Ok(dirty)
+}
+
+/// 由 panic hook 调用，同一次运行中多次 panic 时保留最后一次
+pub fn record_dirty_shutdown(message: &str) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_SERVER_META) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let dirty = DirtyShutdown {
+        panicked_at: unix_secs_to_rfc3339(now_secs()),
+        message: message.to_string(),
+    };
+    db.put_cf(&cf, DIRTY_SHUTDOWN_KEY, serde_json::to_vec(&dirty)?)?;
+    Ok(())
+}
+
+/// 记录启动时刻并读取上次停机情况，重复调用不生效
+pub fn record_server_start() -> Result<()> {
+    let timestamp = now_secs();
+    let previous = get_lifecycle();
+    let dirty_shutdown = match take_dirty_shutdown() {
+        Ok(d) => d,
+        Err(e) => {
+            log::warn!("read dirty shutdown marker error: {}", e);
+            None
+        }
+    };
+    let last_stop = match &previous {
+        Ok(p) => p.as_ref().map(|l| last_stop_of(l, dirty_shutdown.clone())),
+        Err(_) => None,
+    };
+    if let Some(s) = &last_stop {
+        match s.clean {
+            true => log::info!("last stop was clean"),
+            false => log::warn!(
+                "last stop was unclean, server started at {} did not shut down gracefully",
+                s.started_at
+            ),
+        }
+    }
+    if let Some(d) = &dirty_shutdown {
+        log::warn!(
+            "dirty shutdown, previous run panicked at {}: {}",
+            d.panicked_at,
+            d.message
+        );
+    }
+    if SERVER_START
+        .set(ServerStart {
+            instant: Instant::now(),
+            timestamp,
+            last_stop,
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+    previous?;
+    put_lifecycle(&ServerLifecycle {
+        started_at: timestamp,
+        stopped_at: None,
+    })
+}
+
+/// 停机流程完成任务退出后调用
+pub fn record_clean_stop() -> Result<()> {
+    let start = match SERVER_START.get() {
+        Some(s) => s,
+        None => return Err(anyhow!("server start not recorded")),
+    };
+    put_lifecycle(&ServerLifecycle {
+        started_at: start.timestamp,
+        stopped_at: Some(now_secs()),
+    })
+}
+
+pub fn server_start_time() -> Option<String> {
+    SERVER_START
+        .get()
+        .map(|s| unix_secs_to_rfc3339(s.timestamp))
+}
+
+pub fn server_uptime() -> Option<Duration> {
+    SERVER_START.get().map(|s| s.instant.elapsed())
+}
+
+pub fn server_last_stop() -> Option<LastStop> {
+    SERVER_START.get().and_then(|s| s.last_stop.clone())
+}
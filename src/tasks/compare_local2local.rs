@@ -4,6 +4,7 @@ use super::{
     ObjectDiff, Opt, RecordDescription, COMPARE_ERROR_RECORD_PREFIX, COMPARE_RESULT_PREFIX,
     OFFSET_PREFIX,
 };
+use crate::commons::inherit_request_context;
 use crate::commons::scan_folder_files_to_file;
 use crate::commons::LastModifyFilter;
 use anyhow::Result;
@@ -61,12 +62,12 @@ impl CompareTaskActions for CompareLocal2Local {
             list_file_path: source_objects_list_file,
         };
 
-        joinset.spawn(async move {
+        joinset.spawn(inherit_request_context(async move {
             if let Err(e) = comparator.compare_listed_records(records).await {
                 stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
                 log::error!("{}", e);
             };
-        });
+        }));
     }
 }
 
@@ -135,12 +136,14 @@ impl Local2LocalRecordsComparator {
                             line_num: record.line_num,
                         },
                         option: Opt::COMPARE,
+                        attempts: 0,
                     };
                     recorddesc.handle_error(
                         &self.err_counter,
                         &self.offset_map,
                         &mut error_file,
                         offset_key.as_str(),
+                        &e.to_string(),
                     );
                     log::error!("{}", e);
                 }
@@ -4,6 +4,7 @@ use super::{
     FilePosition, ListedRecord, ObjectDiff, Opt, RecordDescription, COMPARE_ERROR_RECORD_PREFIX,
     COMPARE_RESULT_PREFIX, OFFSET_PREFIX,
 };
+use crate::commons::inherit_request_context;
 use crate::commons::LastModifyFilter;
 use crate::s3::{OSSDescription, OssClient};
 use anyhow::anyhow;
@@ -72,12 +73,12 @@ impl CompareTaskActions for CompareOss2Oss {
             list_file_path: source_objects_list_file,
         };
 
-        joinset.spawn(async move {
+        joinset.spawn(inherit_request_context(async move {
             if let Err(e) = comparator.compare_listed_records(records).await {
                 stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
                 log::error!("{}", e);
             };
-        });
+        }));
     }
 }
 
@@ -155,12 +156,14 @@ impl Oss2OssRecordsComparator {
                             line_num: record.line_num,
                         },
                         option: Opt::PUT,
+                        attempts: 0,
                     };
                     recorddesc.handle_error(
                         &self.err_counter,
                         &self.offset_map,
                         &mut error_file,
                         offset_key.as_str(),
+                        &e.to_string(),
                     );
                     log::error!("{}", e);
                 }
@@ -9,9 +9,18 @@ mod task;
 mod task_actions;
 mod task_assistant;
 mod task_compare;
+mod task_connectivity;
+mod task_consistency;
+mod task_dump;
+mod task_events;
+mod task_progress;
+mod task_queue;
 mod task_server;
+mod task_setup;
+mod task_shutdown;
 mod task_status;
 mod task_transfer;
+mod task_validation;
 mod transfer_local2local;
 mod transfer_local2oss;
 mod transfer_oss2local;
@@ -27,9 +36,18 @@ pub use modules::*;
 pub use task::*;
 pub use task_assistant::*;
 pub use task_compare::*;
+pub use task_connectivity::*;
+pub use task_consistency::*;
+pub use task_dump::*;
+pub use task_events::*;
+pub use task_progress::*;
+pub use task_queue::*;
 pub use task_server::*;
+pub use task_setup::*;
+pub use task_shutdown::*;
 pub use task_status::*;
 pub use task_transfer::*;
+pub use task_validation::*;
 pub use transfer_local2local::*;
 pub use transfer_local2oss::*;
 pub use transfer_oss2local::*;
@@ -0,0 +1,236 @@
+//! 任务取消
+//!
+//! 新增任务类型应优先使用 CancellationToken：执行协程在 await 点通过 select! 响应取消，
+//! 停止延迟不再取决于轮询 AtomicBool 的位置。
+//! GLOBAL_TASK_STOP_MARK_MAP 中的 AtomicBool 仅为兼容现有轮询点保留，由 cancel_task 同步置位。
+//!
+//! 暂停与停止标识并列注册，执行协程在对象之间调用 wait_if_paused，暂停期间协程与 joinset 保持存活。
+use crate::tasks::GLOBAL_TASK_STOP_MARK_MAP;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::sync::{atomic::AtomicBool, Arc};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+pub static GLOBAL_TASK_CANCEL_TOKEN_MAP: Lazy<Arc<DashMap<String, CancellationToken>>> =
+    Lazy::new(|| {
+        let map = DashMap::<String, CancellationToken>::new();
+        Arc::new(map)
+    });
+
+pub static GLOBAL_TASK_PAUSE_MAP: Lazy<Arc<DashMap<String, TaskPause>>> = Lazy::new(|| {
+    let map = DashMap::<String, TaskPause>::new();
+    Arc::new(map)
+});
+
+/// 任务暂停标识
+#[derive(Debug, Clone)]
+pub struct TaskPause {
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl Default for TaskPause {
+    fn default() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self {
+            paused: Arc::new(tx),
+        }
+    }
+}
+
+impl TaskPause {
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// 置位暂停，已暂停时返回 false
+    pub fn pause(&self) -> bool {
+        self.paused
+            .send_if_modified(|p| !std::mem::replace(p, true))
+    }
+
+    /// 取消暂停，未暂停时返回 false
+    pub fn resume(&self) -> bool {
+        self.paused
+            .send_if_modified(|p| std::mem::replace(p, false))
+    }
+
+    /// 等待直至未暂停或任务被取消
+    pub async fn wait_resumed(&self, cancel: &CancellationToken) {
+        let mut rx = self.paused.subscribe();
+        tokio::select! {
+            _ = cancel.cancelled() => {}
+            _ = rx.wait_for(|p| !*p) => {}
+        }
+    }
+}
+
+/// 任务启动时注册停止标识、取消 token 与暂停标识，重复启动时替换上次运行的记录
+pub fn register_task_cancellation(task_id: &str) -> (Arc<AtomicBool>, CancellationToken) {
+    let stop_mark = Arc::new(AtomicBool::new(false));
+    let token = CancellationToken::new();
+    GLOBAL_TASK_STOP_MARK_MAP.insert(task_id.to_string(), stop_mark.clone());
+    GLOBAL_TASK_CANCEL_TOKEN_MAP.insert(task_id.to_string(), token.clone());
+    GLOBAL_TASK_PAUSE_MAP.insert(task_id.to_string(), TaskPause::default());
+    (stop_mark, token)
+}
+
+/// 取得任务本次运行已注册的停止标识与取消 token，未注册时注册
+pub fn registered_task_cancellation(task_id: &str) -> (Arc<AtomicBool>, CancellationToken) {
+    let stop_mark = GLOBAL_TASK_STOP_MARK_MAP
+        .get(task_id)
+        .map(|kv| kv.value().clone());
+    let token = GLOBAL_TASK_CANCEL_TOKEN_MAP
+        .get(task_id)
+        .map(|kv| kv.value().clone());
+    match (stop_mark, token) {
+        (Some(stop_mark), Some(token)) => (stop_mark, token),
+        _ => register_task_cancellation(task_id),
+    }
+}
+
+/// 任务删除时移除其停止标识、取消 token 与暂停标识
+pub fn unregister_task_cancellation(task_id: &str) {
+    GLOBAL_TASK_STOP_MARK_MAP.remove(task_id);
+    GLOBAL_TASK_CANCEL_TOKEN_MAP.remove(task_id);
+    GLOBAL_TASK_PAUSE_MAP.remove(task_id);
+}
+
+/// 任务是否处于暂停状态
+pub fn task_is_paused(task_id: &str) -> bool {
+    GLOBAL_TASK_PAUSE_MAP
+        .get(task_id)
+        .map(|kv| kv.value().is_paused())
+        .unwrap_or(false)
+}
+
+/// 任务暂停时阻塞直至恢复或被取消，未注册或未暂停时立即返回
+pub async fn wait_if_paused(task_id: &str) {
+    let pause = match GLOBAL_TASK_PAUSE_MAP.get(task_id) {
+        Some(kv) if kv.value().is_paused() => kv.value().clone(),
+        _ => return,
+    };
+    pause.wait_resumed(&task_cancellation_token(task_id)).await;
+}
+
+/// 获取任务取消 token，未注册时返回不会被取消的 token
+pub fn task_cancellation_token(task_id: &str) -> CancellationToken {
+    match GLOBAL_TASK_CANCEL_TOKEN_MAP.get(task_id) {
+        Some(kv) => kv.value().clone(),
+        None => CancellationToken::new(),
+    }
+}
+
+/// 取消任务，同时置位停止标识并解除暂停，返回任务是否已注册
+pub fn cancel_task(task_id: &str) -> bool {
+    let mut registered = false;
+    if let Some(kv) = GLOBAL_TASK_PAUSE_MAP.get(task_id) {
+        kv.value().resume();
+    }
+    if let Some(kv) = GLOBAL_TASK_STOP_MARK_MAP.get(task_id) {
+        kv.value().store(true, std::sync::atomic::Ordering::SeqCst);
+        registered = true;
+    }
+    if let Some(kv) = GLOBAL_TASK_CANCEL_TOKEN_MAP.get(task_id) {
+        kv.value().cancel();
+        registered = true;
+    }
+    registered
+}
+
+/// 执行 future 直至完成或 token 被取消，取消时返回 None
+pub async fn run_until_cancelled<F: Future>(
+    cancel: &CancellationToken,
+    fut: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        _ = cancel.cancelled() => None,
+        r = fut => Some(r),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run_until_cancelled, TaskPause};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+    use std::time::{Duration, Instant};
+    use tokio_util::sync::CancellationToken;
+
+    // 模拟慢速 io：每个分片耗时 300ms，仅在分片之间可以轮询停止标识
+    async fn slow_chunks(stop_mark: Option<Arc<AtomicBool>>) {
+        for _ in 0..20 {
+            if let Some(s) = &stop_mark {
+                if s.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+    }
+
+    //cargo test tasks::modules::cancellation::test::test_stop_latency -- --nocapture
+    #[tokio::test]
+    async fn test_stop_latency() {
+        // 轮询停止标识
+        let stop_mark = Arc::new(AtomicBool::new(false));
+        let worker = tokio::spawn(slow_chunks(Some(stop_mark.clone())));
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        let begin = Instant::now();
+        stop_mark.store(true, Ordering::SeqCst);
+        worker.await.unwrap();
+        let poll_latency = begin.elapsed();
+
+        // await 点响应取消
+        let token = CancellationToken::new();
+        let child = token.child_token();
+        let worker =
+            tokio::spawn(async move { run_until_cancelled(&child, slow_chunks(None)).await });
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        let begin = Instant::now();
+        token.cancel();
+        assert!(worker.await.unwrap().is_none());
+        let token_latency = begin.elapsed();
+
+        println!("poll: {:?}, token: {:?}", poll_latency, token_latency);
+        assert!(token_latency < Duration::from_millis(50));
+        assert!(token_latency < poll_latency);
+    }
+
+    //cargo test tasks::modules::cancellation::test::test_task_pause -- --nocapture
+    #[tokio::test]
+    async fn test_task_pause() {
+        let pause = TaskPause::default();
+        let token = CancellationToken::new();
+        assert!(pause.pause());
+        assert!(!pause.pause());
+        assert!(pause.is_paused());
+
+        let p = pause.clone();
+        let t = token.clone();
+        let worker = tokio::spawn(async move { p.wait_resumed(&t).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!worker.is_finished());
+        assert!(pause.resume());
+        assert!(!pause.resume());
+        tokio::time::timeout(Duration::from_millis(100), worker)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // 暂停期间取消任务，等待立即结束
+        assert!(pause.pause());
+        let p = pause.clone();
+        let t = token.clone();
+        let worker = tokio::spawn(async move { p.wait_resumed(&t).await });
+        token.cancel();
+        tokio::time::timeout(Duration::from_millis(100), worker)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(pause.is_paused());
+    }
+}
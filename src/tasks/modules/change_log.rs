@@ -0,0 +1,94 @@
+use super::TASK_CREDENTIAL_FIELDS;
+use crate::commons::{json_diff, redact_json_changes, JsonChange};
+use crate::resources::{CF_TASK_CHANGES, GLOBAL_ROCKSDB};
+use crate::tasks::Task;
+use anyhow::{anyhow, Result};
+use rocksdb::{Direction, IteratorMode};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 任务定义的一次修改，凭证字段只记录发生变更，不记录值
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskChangeEntry {
+    pub task_id: String,
+    // 从 1 开始递增
+    pub seq: u64,
+    pub timestamp: u64,
+    pub actor: String,
+    pub changes: Vec<JsonChange>,
+}
+
+// seq 定长补零，保证按 key 遍历即为时间顺序；删除任务时由 delete_task_all 按 {task_id}/ 前缀删除
+fn task_change_key(task_id: &str, seq: u64) -> String {
+    format!("{}/{:020}", task_id, seq)
+}
+
+/// 记录修改前后的差异，无差异时不记录
+pub fn record_task_change(
+    task_id: &str,
+    actor: &str,
+    old: &Task,
+    new: &Task,
+) -> Result<Option<TaskChangeEntry>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_CHANGES) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut changes = json_diff(&serde_json::to_value(old)?, &serde_json::to_value(new)?);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    redact_json_changes(&mut changes, &TASK_CREDENTIAL_FIELDS);
+    let seq = match list_task_changes(task_id)?.last() {
+        Some(e) => e.seq + 1,
+        None => 1,
+    };
+    let entry = TaskChangeEntry {
+        task_id: task_id.to_string(),
+        seq,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        actor: actor.to_string(),
+        changes,
+    };
+    db.put_cf(
+        &cf,
+        task_change_key(task_id, seq),
+        serde_json::to_vec(&entry)?,
+    )?;
+    Ok(Some(entry))
+}
+
+pub fn list_task_changes(task_id: &str) -> Result<Vec<TaskChangeEntry>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_CHANGES) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let prefix = format!("{}/", task_id);
+    let mut entries = vec![];
+    for item in db.iterator_cf(
+        &cf,
+        IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+    ) {
+        let (k, v) = item?;
+        if !k.starts_with(prefix.as_bytes()) {
+            break;
+        }
+        entries.push(serde_json::from_slice::<TaskChangeEntry>(&v)?);
+    }
+    Ok(entries)
+}
+
+/// 变更记录不存在时返回 None
+pub fn get_task_change(task_id: &str, seq: u64) -> Result<Option<TaskChangeEntry>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_CHANGES) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    match db.get_cf(&cf, task_change_key(task_id, seq))? {
+        Some(v) => Ok(Some(serde_json::from_slice::<TaskChangeEntry>(&v)?)),
+        None => Ok(None),
+    }
+}
@@ -1,19 +1,26 @@
 use super::FilePosition;
 use crate::{
     commons::{read_yaml_file, struct_to_yaml_string},
-    resources::{CF_TASK_CHECKPOINTS, GLOBAL_ROCKSDB},
-    tasks::{TaskDefaultParameters, TransferStage},
+    resources::save_checkpoint_to_cf,
+    tasks::{
+        TaskDefaultParameters, TransferStage, COMPARE_CHECK_POINT_FILE,
+        COMPARE_ERROR_RECORD_PREFIX, COMPARE_SOURCE_OBJECT_LIST_FILE_PREFIX, MODIFIED_PREFIX,
+        NOTIFY_FILE_PREFIX, REMOVED_PREFIX, TRANSFER_CHECK_POINT_FILE,
+        TRANSFER_ERROR_RECORD_PREFIX, TRANSFER_OBJECT_LIST_FILE_PREFIX,
+    },
 };
-use anyhow::{anyhow, Error, Result};
+use anyhow::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{File, OpenOptions},
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
     io::{Seek, SeekFrom, Write},
+    path::Path,
     str::FromStr,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FileDescription {
     pub path: String,
     pub size: u64,
@@ -30,7 +37,7 @@ impl Default for FileDescription {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CheckPoint {
     pub task_id: String,
     //当前全量对象列表
@@ -102,27 +109,191 @@ impl CheckPoint {
     }
 
     pub fn save_to_rocksdb_cf(&mut self) -> Result<()> {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        self.modify_checkpoint_timestamp = i128::from(now.as_secs());
-        let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK_CHECKPOINTS) {
-            Some(cf) => cf,
-            None => return Err(anyhow!("content length is None")),
-        };
-        let encoded: Vec<u8> = bincode::serialize(self)?;
-        GLOBAL_ROCKSDB.put_cf(&cf, self.task_id.as_bytes(), encoded)?;
+        Ok(save_checkpoint_to_cf(self)?)
+    }
+}
 
-        Ok(())
+// checkpoint 导出格式的版本，格式不兼容时递增
+pub const CHECKPOINT_EXPORT_VERSION: u32 = 1;
+
+/// 可在服务间迁移的 checkpoint，file_positions 为执行中各批次在列表文件中的位置
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CheckpointExport {
+    pub version: u32,
+    pub checkpoint: CheckPoint,
+    #[serde(default)]
+    pub file_positions: BTreeMap<String, FilePosition>,
+}
+
+impl CheckpointExport {
+    /// 按目标任务的 task_id 与 meta_dir 重写文件路径与位置的键，并以当前列表文件校验位置
+    /// 列表文件以文件名在 meta_dir 中查找，校验失败时返回全部问题
+    pub fn rebase(self, task_id: &str, meta_dir: &str) -> Result<Self, Vec<String>> {
+        let mut problems = vec![];
+        if self.version != CHECKPOINT_EXPORT_VERSION {
+            problems.push(format!(
+                "unsupported version {}, expected {}",
+                self.version, CHECKPOINT_EXPORT_VERSION
+            ));
+            return Err(problems);
+        }
+        let old_task_id = self.checkpoint.task_id.clone();
+        let mut checkpoint = self.checkpoint;
+        checkpoint.task_id = task_id.to_string();
+
+        let mut file_size = None;
+        if !checkpoint.executing_file.path.is_empty() {
+            let path = rebase_path(&checkpoint.executing_file.path, meta_dir);
+            match fs::metadata(&path) {
+                Ok(m)
+                    if checkpoint.executing_file.size > 0
+                        && m.len() != checkpoint.executing_file.size =>
+                {
+                    problems.push(format!(
+                        "executing_file {} size {} differs from checkpoint size {}",
+                        path,
+                        m.len(),
+                        checkpoint.executing_file.size
+                    ))
+                }
+                Ok(m) => file_size = Some(m.len()),
+                Err(e) => problems.push(format!("executing_file {}: {}", path, e)),
+            }
+            checkpoint.executing_file.path = path;
+        }
+        if let Some(notify) = &checkpoint.file_for_notify {
+            let path = rebase_path(notify, meta_dir);
+            if !Path::new(&path).is_file() {
+                problems.push(format!("file_for_notify {} not exist", path));
+            }
+            checkpoint.file_for_notify = Some(path);
+        }
+
+        let total_lines = checkpoint.executing_file.total_lines;
+        let mut positions = vec![(
+            "executing_file_position".to_string(),
+            checkpoint.executing_file_position,
+        )];
+        let mut file_positions = BTreeMap::new();
+        for (key, position) in self.file_positions {
+            match key.strip_prefix(&old_task_id) {
+                Some(suffix) => {
+                    positions.push((format!("file_positions.{}", key), position));
+                    file_positions.insert(format!("{}{}", task_id, suffix), position);
+                }
+                None => problems.push(format!(
+                    "file_positions.{} not belong to task {}",
+                    key, old_task_id
+                )),
+            }
+        }
+        for (field, position) in positions {
+            problems.extend(position_problems(&field, &position, file_size, total_lines));
+        }
+
+        match problems.is_empty() {
+            true => Ok(Self {
+                version: self.version,
+                checkpoint,
+                file_positions,
+            }),
+            false => Err(problems),
+        }
+    }
+}
+
+// 导出的路径属于原服务的 meta_dir，导入时取文件名放到目标任务的 meta_dir 中
+fn rebase_path(path: &str, meta_dir: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(name) => Path::new(meta_dir).join(name).to_string_lossy().to_string(),
+        None => path.to_string(),
     }
 }
 
+// offset 不超过文件大小，行号不超过总行数，且每行至少一个字节
+fn position_problems(
+    field: &str,
+    position: &FilePosition,
+    file_size: Option<u64>,
+    total_lines: u64,
+) -> Vec<String> {
+    let mut problems = vec![];
+    let offset = position.offset as u64;
+    if let Some(size) = file_size {
+        if offset > size {
+            problems.push(format!(
+                "{} offset {} beyond file size {}",
+                field, offset, size
+            ));
+        }
+    }
+    if total_lines > 0 && position.line_num > total_lines {
+        problems.push(format!(
+            "{} line_num {} beyond total lines {}",
+            field, position.line_num, total_lines
+        ));
+    }
+    if position.line_num > offset + 1 {
+        problems.push(format!(
+            "{} line_num {} implausible for offset {}",
+            field, position.line_num, offset
+        ));
+    }
+    problems
+}
+
 pub fn get_task_checkpoint(checkpoint_file: &str) -> Result<CheckPoint> {
     let checkpoint = read_yaml_file::<CheckPoint>(checkpoint_file)?;
     Ok(checkpoint)
 }
 
+// 对象列表、增量通知与 checkpoint 文件，删除后任务启动时重新生成对象列表
+const LISTING_FILE_PREFIXES: [&'static str; 7] = [
+    TRANSFER_OBJECT_LIST_FILE_PREFIX,
+    COMPARE_SOURCE_OBJECT_LIST_FILE_PREFIX,
+    NOTIFY_FILE_PREFIX,
+    REMOVED_PREFIX,
+    MODIFIED_PREFIX,
+    TRANSFER_CHECK_POINT_FILE,
+    COMPARE_CHECK_POINT_FILE,
+];
+pub(super) const ERROR_RECORD_PREFIXES: [&'static str; 2] =
+    [TRANSFER_ERROR_RECORD_PREFIX, COMPARE_ERROR_RECORD_PREFIX];
+
+/// 删除 meta_dir 中的对象列表等文件，with_error_records 时同时删除错误记录，返回删除的文件路径
+pub fn remove_listing_files(meta_dir: &str, with_error_records: bool) -> Result<Vec<String>> {
+    let mut removed = vec![];
+    let entries = match fs::read_dir(meta_dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(removed),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let listing = LISTING_FILE_PREFIXES.iter().any(|p| name.starts_with(p));
+        let error_record =
+            with_error_records && ERROR_RECORD_PREFIXES.iter().any(|p| name.starts_with(p));
+        if listing || error_record {
+            fs::remove_file(entry.path())?;
+            removed.push(entry.path().to_string_lossy().to_string());
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::tasks::modules::get_task_checkpoint;
+    use super::{
+        remove_listing_files, CheckPoint, CheckpointExport, FileDescription,
+        CHECKPOINT_EXPORT_VERSION,
+    };
+    use crate::tasks::modules::{get_task_checkpoint, FilePosition};
+    use std::{collections::BTreeMap, fs};
 
     //cargo test checkpoint::checkpoint::test::test_get_task_checkpoint -- --nocapture
     #[test]
@@ -131,4 +302,114 @@ mod test {
         let c = get_task_checkpoint("/tmp/meta_dir/checkpoint.yml");
         println!("{:?}", c);
     }
+
+    //cargo test tasks::modules::checkpoint::test::test_remove_listing_files -- --nocapture
+    #[test]
+    fn test_remove_listing_files() {
+        let meta_dir = format!("/tmp/mario_checkpoint_reset_test_{}", std::process::id());
+        fs::create_dir_all(format!("{}/sub", meta_dir)).unwrap();
+        for name in [
+            "transfer_objects_list_1",
+            "notify_1",
+            "checkpoint_transfer.yml",
+            "transfer_error_record_1",
+            "runtime_dump_1",
+        ] {
+            fs::write(format!("{}/{}", meta_dir, name), "x").unwrap();
+        }
+
+        let removed = remove_listing_files(&meta_dir, false).unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(fs::metadata(format!("{}/transfer_error_record_1", meta_dir)).is_ok());
+
+        // hard 模式同时删除错误记录，其余文件与子目录保留
+        let removed = remove_listing_files(&meta_dir, true).unwrap();
+        assert_eq!(
+            removed,
+            vec![format!("{}/transfer_error_record_1", meta_dir)]
+        );
+        assert!(fs::metadata(format!("{}/runtime_dump_1", meta_dir)).is_ok());
+        assert!(fs::metadata(format!("{}/sub", meta_dir)).is_ok());
+
+        fs::remove_dir_all(&meta_dir).unwrap();
+        assert!(remove_listing_files(&meta_dir, true).unwrap().is_empty());
+    }
+
+    //cargo test tasks::modules::checkpoint::test::test_checkpoint_export_round_trip -- --nocapture
+    #[test]
+    fn test_checkpoint_export_round_trip() {
+        let root = format!("/tmp/mario_checkpoint_export_test_{}", std::process::id());
+        let (old_meta, new_meta) = (format!("{}/old", root), format!("{}/new", root));
+        fs::create_dir_all(&old_meta).unwrap();
+        fs::create_dir_all(&new_meta).unwrap();
+        let list = "a\nbb\nccc\n";
+        for dir in [&old_meta, &new_meta] {
+            fs::write(format!("{}/transfer_objects_list_1", dir), list).unwrap();
+        }
+        let checkpoint = CheckPoint {
+            task_id: "100".to_string(),
+            executing_file: FileDescription {
+                path: format!("{}/transfer_objects_list_1", old_meta),
+                size: list.len() as u64,
+                total_lines: 3,
+            },
+            executing_file_position: FilePosition {
+                offset: 2,
+                line_num: 1,
+            },
+            modify_checkpoint_timestamp: 1700000000,
+            task_begin_timestamp: 1690000000,
+            ..Default::default()
+        };
+        let mut file_positions = BTreeMap::new();
+        file_positions.insert(
+            "100_5".to_string(),
+            FilePosition {
+                offset: 5,
+                line_num: 2,
+            },
+        );
+        let export = CheckpointExport {
+            version: CHECKPOINT_EXPORT_VERSION,
+            checkpoint,
+            file_positions,
+        };
+
+        // 导出后原样导入不丢失任何字段
+        let json = serde_json::to_string(&export).unwrap();
+        let parsed = serde_json::from_str::<CheckpointExport>(&json).unwrap();
+        assert_eq!(parsed.clone().rebase("100", &old_meta).unwrap(), export);
+
+        // 导入到其他服务上的任务时路径与位置的键随之改写
+        let moved = parsed.rebase("200", &new_meta).unwrap();
+        assert_eq!(moved.checkpoint.task_id, "200");
+        assert_eq!(
+            moved.checkpoint.executing_file.path,
+            format!("{}/transfer_objects_list_1", new_meta)
+        );
+        assert_eq!(
+            moved.checkpoint.executing_file_position,
+            export.checkpoint.executing_file_position
+        );
+        assert_eq!(
+            moved.file_positions.get("200_5"),
+            export.file_positions.get("100_5")
+        );
+
+        let mut invalid = export.clone();
+        invalid.checkpoint.executing_file_position.offset = 100;
+        invalid.file_positions.insert(
+            "999_1".to_string(),
+            FilePosition {
+                offset: 1,
+                line_num: 1,
+            },
+        );
+        let problems = invalid.rebase("100", &old_meta).unwrap_err();
+        assert_eq!(problems.len(), 2);
+
+        fs::write(format!("{}/transfer_objects_list_1", new_meta), "changed").unwrap();
+        assert!(export.rebase("200", &new_meta).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,198 @@
+use crate::commons::sync_parent_dir;
+use crate::resources::ResourceError;
+use crate::tasks::{gen_file_path, TRANSFER_SUCCESS_MARKER_FILE};
+use anyhow::{anyhow, Result};
+use crypto::{digest::Digest, sha2::Sha256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use walkdir::WalkDir;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestFile {
+    // 相对于 meta_dir 的路径
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompletionCounters {
+    pub total_objects: u64,
+    pub executed_lines: u64,
+    pub errors: usize,
+}
+
+/// 任务完成标识，任务成功结束且所有文件落盘后写入 meta_dir
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompletionMarker {
+    pub task_id: String,
+    pub run_id: String,
+    pub completed_timestamp: u64,
+    pub counters: CompletionCounters,
+    pub manifests: Vec<ManifestFile>,
+}
+
+impl CompletionMarker {
+    pub fn new(task_id: &str, run_id: &str, counters: CompletionCounters) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            run_id: run_id.to_string(),
+            completed_timestamp: 0,
+            counters,
+            manifests: vec![],
+        }
+    }
+
+    /// 扫描 meta_dir 生成 manifest 列表，并以 临时文件 + rename 的方式原子写入标识文件
+    pub fn write_to(&mut self, meta_dir: &str) -> Result<()> {
+        self.completed_timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.manifests = scan_manifest_files(meta_dir)?;
+
+        let marker_path = gen_file_path(meta_dir, TRANSFER_SUCCESS_MARKER_FILE, "");
+        let tmp_path = gen_file_path(meta_dir, TRANSFER_SUCCESS_MARKER_FILE, ".tmp");
+        let content = serde_json::to_string_pretty(self)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.flush()?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp_path, &marker_path)?;
+        sync_parent_dir(&marker_path)?;
+        Ok(())
+    }
+}
+
+/// 标识文件不存在时返回 None，读取失败为存储错误，内容无法解析为内部错误
+pub fn get_completion_marker(meta_dir: &str) -> Result<Option<CompletionMarker>> {
+    let marker_path = gen_file_path(meta_dir, TRANSFER_SUCCESS_MARKER_FILE, "");
+    let content = match fs::read_to_string(&marker_path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(
+                ResourceError::io(format!("read completion marker {}", marker_path), e).into(),
+            )
+        }
+    };
+    match serde_json::from_str::<CompletionMarker>(&content) {
+        Ok(marker) => Ok(Some(marker)),
+        Err(e) => Err(anyhow!(
+            "completion marker {} is corrupt: {}",
+            marker_path,
+            e
+        )),
+    }
+}
+
+pub fn completion_marker_exists(meta_dir: &str) -> bool {
+    Path::new(&gen_file_path(meta_dir, TRANSFER_SUCCESS_MARKER_FILE, "")).exists()
+}
+
+/// 任务启动时清理上次运行遗留的完成标识
+pub fn remove_completion_marker(meta_dir: &str) -> Result<()> {
+    for suffix in ["", ".tmp"] {
+        let path = gen_file_path(meta_dir, TRANSFER_SUCCESS_MARKER_FILE, suffix);
+        if Path::new(&path).exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn scan_manifest_files(meta_dir: &str) -> Result<Vec<ManifestFile>> {
+    let mut manifests = vec![];
+    for entry in WalkDir::new(meta_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+    {
+        let rel = match entry.path().strip_prefix(meta_dir) {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(_) => continue,
+        };
+        if rel.starts_with(TRANSFER_SUCCESS_MARKER_FILE) {
+            continue;
+        }
+        let size = entry.metadata()?.len();
+        let sha256 = file_sha256(entry.path())?;
+        manifests.push(ManifestFile {
+            path: rel,
+            size,
+            sha256,
+        });
+    }
+    Ok(manifests)
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read_count = file.read(&mut buffer)?;
+        if read_count == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read_count]);
+    }
+    Ok(hasher.result_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        completion_marker_exists, get_completion_marker, remove_completion_marker,
+        CompletionCounters, CompletionMarker,
+    };
+    use crate::httpserver::module::ApiError;
+    use std::fs;
+
+    //cargo test tasks::modules::completion::test::test_completion_marker -- --nocapture
+    #[test]
+    fn test_completion_marker() {
+        let meta_dir = "/tmp/completion_test_meta_dir";
+        let _ = fs::remove_dir_all(meta_dir);
+        fs::create_dir_all(meta_dir).unwrap();
+        fs::write(format!("{}/transfer_objects_list_1", meta_dir), "a\nb\n").unwrap();
+
+        let counters = CompletionCounters {
+            total_objects: 2,
+            executed_lines: 2,
+            errors: 0,
+        };
+        let mut marker = CompletionMarker::new("1", "2", counters);
+        marker.write_to(meta_dir).unwrap();
+        assert!(completion_marker_exists(meta_dir));
+
+        let m = get_completion_marker(meta_dir).unwrap().unwrap();
+        assert_eq!(m.manifests.len(), 1);
+        assert_eq!(m.manifests[0].path, "transfer_objects_list_1");
+        assert_eq!(m.manifests[0].size, 4);
+
+        // 内容无法解析时报错，不视为不存在
+        fs::write(format!("{}/_SUCCESS.json", meta_dir), "{").unwrap();
+        let e = ApiError::from(get_completion_marker(meta_dir).unwrap_err());
+        assert_eq!(e.code(), "internal");
+        // 路径不是文件时读取失败，视为存储不可用
+        fs::remove_file(format!("{}/_SUCCESS.json", meta_dir)).unwrap();
+        fs::create_dir(format!("{}/_SUCCESS.json", meta_dir)).unwrap();
+        let e = ApiError::from(get_completion_marker(meta_dir).unwrap_err());
+        assert_eq!(e.code(), "storage_unavailable");
+        fs::remove_dir(format!("{}/_SUCCESS.json", meta_dir)).unwrap();
+
+        remove_completion_marker(meta_dir).unwrap();
+        assert!(!completion_marker_exists(meta_dir));
+        assert!(get_completion_marker(meta_dir).unwrap().is_none());
+        let _ = fs::remove_dir_all(meta_dir);
+    }
+}
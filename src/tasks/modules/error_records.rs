@@ -0,0 +1,204 @@
+use super::{checkpoint::ERROR_RECORD_PREFIXES, ErrorRecord, Opt};
+use anyhow::Result;
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Lines, Write},
+    path::PathBuf,
+};
+
+/// 错误记录文件中的一条记录，file 为文件名，line_num 从 1 开始
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskErrorRecord {
+    pub file: String,
+    pub line_num: u64,
+    pub key: String,
+    pub target_key: String,
+    pub option: Opt,
+    pub error: Option<String>,
+    pub timestamp: Option<u64>,
+    pub attempts: u32,
+}
+
+/// meta_dir 中的错误记录文件，按文件名排序，目录不存在时视为没有错误
+pub fn error_record_files(meta_dir: &str) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(meta_dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if ERROR_RECORD_PREFIXES.iter().any(|p| name.starts_with(p)) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 逐个文件逐行读取错误记录，不在内存中缓存整个文件，无法解析的行跳过
+pub struct ErrorRecordIter {
+    files: std::vec::IntoIter<PathBuf>,
+    current: Option<(String, Lines<BufReader<File>>, u64)>,
+}
+
+impl ErrorRecordIter {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self {
+            files: files.into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl Iterator for ErrorRecordIter {
+    type Item = Result<TaskErrorRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let path = self.files.next()?;
+                // 任务运行中重试时错误记录文件可能已被删除
+                let file = match File::open(&path) {
+                    Ok(f) => f,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                let name = match path.file_name() {
+                    Some(n) => n.to_string_lossy().to_string(),
+                    None => continue,
+                };
+                self.current = Some((name, BufReader::new(file).lines(), 0));
+            }
+            let (file, lines, line_num) = self.current.as_mut()?;
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e.into())),
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            *line_num += 1;
+            match serde_json::from_str::<ErrorRecord>(&line) {
+                Ok(r) => {
+                    return Some(Ok(TaskErrorRecord {
+                        file: file.clone(),
+                        line_num: *line_num,
+                        key: r.record.source_key,
+                        target_key: r.record.target_key,
+                        option: r.record.option,
+                        error: r.error,
+                        timestamp: r.timestamp,
+                        attempts: r.record.attempts,
+                    }))
+                }
+                Err(e) => log::warn!("skip error record {} line {}: {}", file, line_num, e),
+            }
+        }
+    }
+}
+
+/// 将错误记录文件原样打包为 tar.gz 写入 writer，文件在包内以文件名存放
+pub fn write_error_archive<W: Write>(files: &[PathBuf], writer: W) -> Result<W> {
+    let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    for path in files {
+        let name = match path.file_name() {
+            Some(n) => n.to_owned(),
+            None => continue,
+        };
+        match builder.append_path_with_name(path, name) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let gz = builder.into_inner()?;
+    Ok(gz.finish()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{error_record_files, write_error_archive, ErrorRecordIter};
+    use crate::tasks::{FilePosition, Opt, RecordDescription};
+    use dashmap::DashMap;
+    use flate2::read::GzDecoder;
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        sync::{atomic::AtomicUsize, Arc},
+    };
+
+    //cargo test tasks::modules::error_records::test::test_error_records -- --nocapture
+    #[test]
+    fn test_error_records() {
+        let meta_dir = "/tmp/mario_error_records_test";
+        let _ = fs::remove_dir_all(meta_dir);
+        assert!(error_record_files(meta_dir).unwrap().is_empty());
+        fs::create_dir_all(meta_dir).unwrap();
+
+        // 早期格式的记录没有错误信息
+        let old = r#"{"source_key":"a","target_key":"a","list_file_path":"l","list_file_position":{"offset":0,"line_num":1},"option":"PUT"}"#;
+        fs::write(
+            format!("{}/transfer_error_record_1", meta_dir),
+            format!("{}\nnot json\n", old),
+        )
+        .unwrap();
+        fs::write(format!("{}/objlist_1", meta_dir), "x").unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(format!("{}/transfer_error_record_2", meta_dir))
+            .unwrap();
+        let record = RecordDescription {
+            source_key: "b".to_string(),
+            target_key: "b".to_string(),
+            list_file_path: "l".to_string(),
+            list_file_position: FilePosition::default(),
+            option: Opt::PUT,
+            attempts: 1,
+        };
+        record.handle_error(
+            &Arc::new(AtomicUsize::new(0)),
+            &Arc::new(DashMap::new()),
+            &mut file,
+            "k",
+            "access denied",
+        );
+        file.flush().unwrap();
+
+        let files = error_record_files(meta_dir).unwrap();
+        assert_eq!(files.len(), 2);
+        let records = ErrorRecordIter::new(files.clone())
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].key, "a");
+        assert_eq!(records[0].attempts, 0);
+        assert!(records[0].error.is_none());
+        assert_eq!(records[1].file, "transfer_error_record_2");
+        assert_eq!(records[1].error.as_deref(), Some("access denied"));
+        assert_eq!(records[1].attempts, 2);
+        assert!(records[1].timestamp.is_some());
+
+        let archive = write_error_archive(&files, vec![]).unwrap();
+        let mut names = tar::Archive::new(GzDecoder::new(archive.as_slice()))
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["transfer_error_record_1", "transfer_error_record_2"]
+        );
+        let _ = fs::remove_dir_all(meta_dir);
+    }
+}
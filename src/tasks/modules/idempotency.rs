@@ -0,0 +1,164 @@
+use crate::resources::{CF_IDEMPOTENCY_KEYS, GLOBAL_ROCKSDB};
+use crate::tasks::Task;
+use anyhow::{anyhow, Result};
+use crypto::{digest::Digest, sha2::Sha256};
+use rocksdb::IteratorMode;
+use serde::{Deserialize, Serialize};
+
+/// 幂等键对应的创建请求，body_hash 为解析后任务定义的 sha256
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IdempotencyRecord {
+    pub task_id: String,
+    pub body_hash: String,
+    pub created_at: u64,
+}
+
+impl IdempotencyRecord {
+    pub fn expired(&self, now: u64, ttl_secs: u64) -> bool {
+        now.saturating_sub(self.created_at) >= ttl_secs
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyCheck {
+    // 未使用或已过期的键
+    New,
+    // 同一请求的重试，返回原任务 id
+    Replay(String),
+    // 键已用于不同的请求体
+    Conflict(String),
+}
+
+/// 按已有记录判定本次请求，过期记录视为不存在
+pub fn check_idempotency(
+    record: Option<&IdempotencyRecord>,
+    body_hash: &str,
+    now: u64,
+    ttl_secs: u64,
+) -> IdempotencyCheck {
+    match record {
+        Some(r) if r.expired(now, ttl_secs) => IdempotencyCheck::New,
+        Some(r) if r.body_hash == body_hash => IdempotencyCheck::Replay(r.task_id.clone()),
+        Some(r) => IdempotencyCheck::Conflict(r.task_id.clone()),
+        None => IdempotencyCheck::New,
+    }
+}
+
+/// 以解析后的任务定义计算摘要，字段顺序不同但内容相同的请求体视为同一请求
+/// 未指定 task_id 时每次解析生成的 id 不同，不参与摘要
+pub fn idempotency_body_hash(task: &Task) -> Result<String> {
+    let mut task = task.clone();
+    task.set_task_id("");
+    let mut hasher = Sha256::new();
+    hasher.input(&serde_json::to_vec(&task)?);
+    Ok(hasher.result_str())
+}
+
+pub fn get_idempotency_record(key: &str) -> Result<Option<IdempotencyRecord>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_IDEMPOTENCY_KEYS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    match db.get_cf(&cf, key)? {
+        Some(v) => Ok(Some(serde_json::from_slice::<IdempotencyRecord>(&v)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn save_idempotency_record(key: &str, record: &IdempotencyRecord) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_IDEMPOTENCY_KEYS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    db.put_cf(&cf, key, serde_json::to_vec(record)?)?;
+    Ok(())
+}
+
+/// 删除过期的幂等键，返回删除的条数
+pub fn remove_expired_idempotency_records(now: u64, ttl_secs: u64) -> Result<usize> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_IDEMPOTENCY_KEYS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut removed = 0;
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (k, v) = item?;
+        // 无法解析的记录同样删除
+        let expired = match serde_json::from_slice::<IdempotencyRecord>(&v) {
+            Ok(r) => r.expired(now, ttl_secs),
+            Err(_) => true,
+        };
+        if expired {
+            db.delete_cf(&cf, k)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_idempotency, idempotency_body_hash, IdempotencyCheck, IdempotencyRecord};
+    use crate::tasks::{Task, TransferTask};
+
+    fn record(body_hash: &str) -> IdempotencyRecord {
+        IdempotencyRecord {
+            task_id: "100".to_string(),
+            body_hash: body_hash.to_string(),
+            created_at: 1000,
+        }
+    }
+
+    //cargo test tasks::modules::idempotency::test::test_check_idempotency -- --nocapture
+    #[test]
+    fn test_check_idempotency() {
+        let ttl = 60;
+        assert_eq!(
+            check_idempotency(None, "a", 1000, ttl),
+            IdempotencyCheck::New
+        );
+        // 重试返回原任务
+        assert_eq!(
+            check_idempotency(Some(&record("a")), "a", 1030, ttl),
+            IdempotencyCheck::Replay("100".to_string())
+        );
+        // 请求体不同
+        assert_eq!(
+            check_idempotency(Some(&record("a")), "b", 1030, ttl),
+            IdempotencyCheck::Conflict("100".to_string())
+        );
+        // 过期后可重新使用，请求体不同也不冲突
+        assert_eq!(
+            check_idempotency(Some(&record("a")), "a", 1060, ttl),
+            IdempotencyCheck::New
+        );
+        assert_eq!(
+            check_idempotency(Some(&record("a")), "b", 2000, ttl),
+            IdempotencyCheck::New
+        );
+    }
+
+    //cargo test tasks::modules::idempotency::test::test_idempotency_body_hash -- --nocapture
+    #[test]
+    fn test_idempotency_body_hash() {
+        // 请求未指定 task_id 时每次解析得到的缺省 id 不同
+        let mut task = Task::Transfer(TransferTask::default());
+        let mut same = task.clone();
+        task.set_task_id("1");
+        same.set_task_id("2");
+        assert_eq!(
+            idempotency_body_hash(&task).unwrap(),
+            idempotency_body_hash(&same).unwrap()
+        );
+
+        let mut transfer = TransferTask::default();
+        transfer.name = "other".to_string();
+        assert_ne!(
+            idempotency_body_hash(&task).unwrap(),
+            idempotency_body_hash(&Task::Transfer(transfer)).unwrap()
+        );
+    }
+}
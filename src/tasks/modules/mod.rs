@@ -1,4 +1,24 @@
+mod cancellation;
+mod change_log;
 mod checkpoint;
+mod completion;
+mod error_records;
+mod idempotency;
+mod name_index;
 mod record;
+mod run_definition;
+mod run_history;
+mod success_criteria;
+mod task_export;
+pub use cancellation::*;
+pub use change_log::*;
 pub use checkpoint::*;
+pub use completion::*;
+pub use error_records::*;
+pub use idempotency::*;
+pub use name_index::*;
 pub use record::*;
+pub use run_definition::*;
+pub use run_history::*;
+pub use success_criteria::*;
+pub use task_export::*;
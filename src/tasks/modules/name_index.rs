@@ -0,0 +1,121 @@
+use crate::commons::json_to_struct;
+use crate::resources::{
+    task_name_index_key, CF_SERVER_META, CF_TASK, CF_TASK_NAME_IDX, GLOBAL_ROCKSDB,
+};
+use crate::tasks::Task;
+use anyhow::{anyhow, Result};
+use rocksdb::{IteratorMode, WriteBatch};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+// CF_SERVER_META 中标识 CF_TASK_NAME_IDX 已建立的 key，早期安装或升级前不存在时启动时重建
+const TASK_NAME_INDEX_BUILT_KEY: &'static str = "task_name_idx_built";
+
+// 名称检查与索引写入串行，避免并发创建同名任务都通过检查
+static TASK_NAME_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock_task_names() -> MutexGuard<'static, ()> {
+    match TASK_NAME_INDEX_LOCK.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    }
+}
+
+/// 同名的其他任务
+pub fn name_conflicts(ids: &[String], task_id: &str) -> Vec<String> {
+    ids.iter().filter(|id| *id != task_id).cloned().collect()
+}
+
+/// 按 (task_id, name) 生成名称索引，同名任务的 id 升序排列
+pub fn build_name_index(
+    tasks: impl Iterator<Item = (String, String)>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut index: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (task_id, name) in tasks {
+        index.entry(name).or_default().push(task_id);
+    }
+    for ids in index.values_mut() {
+        ids.sort();
+    }
+    index
+}
+
+/// 清空后按 CF_TASK 重建 CF_TASK_NAME_IDX，返回索引的名称数
+pub fn rebuild_task_name_index() -> Result<usize> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let _guard = lock_task_names();
+    let (cf_task, cf_idx, cf_meta) = match (
+        db.cf_handle(CF_TASK),
+        db.cf_handle(CF_TASK_NAME_IDX),
+        db.cf_handle(CF_SERVER_META),
+    ) {
+        (Some(t), Some(i), Some(m)) => (t, i, m),
+        _ => return Err(anyhow!("column family not exist")),
+    };
+    let mut tasks = vec![];
+    for item in db.iterator_cf(&cf_task, IteratorMode::Start) {
+        let (k, v) = item?;
+        let task = json_to_struct::<Task>(std::str::from_utf8(&v)?)?;
+        tasks.push((String::from_utf8(k.to_vec())?, task.name()));
+    }
+    let index = build_name_index(tasks.into_iter());
+
+    let mut batch = WriteBatch::default();
+    for item in db.iterator_cf(&cf_idx, IteratorMode::Start) {
+        let (k, _) = item?;
+        batch.delete_cf(&cf_idx, k);
+    }
+    for (name, ids) in index.iter() {
+        for task_id in ids {
+            batch.put_cf(&cf_idx, task_name_index_key(name, task_id), b"");
+        }
+    }
+    batch.put_cf(&cf_meta, TASK_NAME_INDEX_BUILT_KEY, b"1");
+    db.write(batch)?;
+    Ok(index.len())
+}
+
+/// 名称索引未建立时重建，已建立时返回 None
+pub fn ensure_task_name_index() -> Result<Option<usize>> {
+    let cf = match GLOBAL_ROCKSDB.get()?.cf_handle(CF_SERVER_META) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    if GLOBAL_ROCKSDB
+        .get()?
+        .get_cf(&cf, TASK_NAME_INDEX_BUILT_KEY)?
+        .is_some()
+    {
+        return Ok(None);
+    }
+    Ok(Some(rebuild_task_name_index()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_name_index, name_conflicts};
+
+    //cargo test tasks::modules::name_index::test::test_build_name_index -- --nocapture
+    #[test]
+    fn test_build_name_index() {
+        let tasks = vec![
+            ("3".to_string(), "nightly".to_string()),
+            ("1".to_string(), "nightly".to_string()),
+            ("2".to_string(), "weekly".to_string()),
+        ];
+        let index = build_name_index(tasks.into_iter());
+        assert_eq!(index.len(), 2);
+        assert_eq!(index["nightly"], vec!["1", "3"]);
+        assert_eq!(index["weekly"], vec!["2"]);
+    }
+
+    //cargo test tasks::modules::name_index::test::test_name_conflicts -- --nocapture
+    #[test]
+    fn test_name_conflicts() {
+        let ids = vec!["1".to_string(), "3".to_string()];
+        assert_eq!(name_conflicts(&ids, "1"), vec!["3"]);
+        assert_eq!(name_conflicts(&ids, "2"), vec!["1", "3"]);
+        // 任务保留原名称不冲突
+        assert!(name_conflicts(&["1".to_string()], "1").is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     io::Write,
     str::FromStr,
     sync::{atomic::AtomicUsize, Arc},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -41,7 +42,7 @@ pub enum Opt {
     UNKOWN,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct FilePosition {
     pub offset: usize,
     pub line_num: u64,
@@ -63,6 +64,21 @@ pub struct RecordDescription {
     pub list_file_path: String,
     pub list_file_position: FilePosition,
     pub option: Opt,
+    // 已失败的次数，错误记录重试时沿用
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// 错误记录文件中的条目，在对象描述之外记录最近一次的错误信息与时间
+/// 早期写入的条目没有 error 与 timestamp
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErrorRecord {
+    #[serde(flatten)]
+    pub record: RecordDescription,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
 }
 
 impl FromStr for RecordDescription {
@@ -80,15 +96,38 @@ impl RecordDescription {
         offset_map: &Arc<DashMap<String, FilePosition>>,
         save_to: &mut File,
         file_position_key: &str,
+        error: &str,
     ) {
         offset_map.insert(
             file_position_key.to_string(),
             self.list_file_position.clone(),
         );
         err_counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        let _ = self.save_json_to_file(save_to);
+        let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => Some(d.as_secs()),
+            Err(_) => None,
+        };
+        let record = ErrorRecord {
+            record: RecordDescription {
+                attempts: self.attempts + 1,
+                ..self.clone()
+            },
+            error: Some(error.to_string()),
+            timestamp,
+        };
+        let _ = record.save_json_to_file(save_to);
+    }
+
+    pub fn save_json_to_file(&self, mut file: &File) -> Result<()> {
+        let mut json = serde_json::to_string(self)?;
+        json.push_str("\n");
+        file.write_all(json.as_bytes())?;
+        file.flush()?;
+        Ok(())
     }
+}
 
+impl ErrorRecord {
     pub fn save_json_to_file(&self, mut file: &File) -> Result<()> {
         let mut json = serde_json::to_string(self)?;
         json.push_str("\n");
@@ -0,0 +1,139 @@
+use crate::commons::{json_diff, redact_json};
+use crate::resources::{CF_TASK_RUN_DEFINITION, GLOBAL_ROCKSDB};
+use crate::tasks::Task;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 任务定义中需脱敏的字段
+pub const TASK_CREDENTIAL_FIELDS: [&'static str; 2] = ["access_key_id", "secret_access_key"];
+
+/// 单次运行实际执行的任务定义，凭证已脱敏
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskRunDefinition {
+    pub task_id: String,
+    pub run_id: String,
+    pub timestamp: u64,
+    pub definition: Value,
+}
+
+/// 快照与当前定义的差异，字段不存在时为 None
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DefinitionChange {
+    pub path: String,
+    pub snapshot: Option<Value>,
+    pub current: Option<Value>,
+}
+
+// 删除任务时由 delete_task_all 按 {task_id}/ 前缀删除
+fn run_definition_key(task_id: &str, run_id: &str) -> String {
+    format!("{}/{}", task_id, run_id)
+}
+
+/// 序列化后脱敏，定义中其余字段保持原样
+pub fn redacted_definition(task: &Task) -> Result<Value> {
+    let mut value = serde_json::to_value(task)?;
+    redact_json(&mut value, &TASK_CREDENTIAL_FIELDS);
+    Ok(value)
+}
+
+/// 凭证脱敏后的任务，供 read 范围的调用方查看
+pub fn redacted_task(task: &Task) -> Result<Task> {
+    Ok(serde_json::from_value(redacted_definition(task)?)?)
+}
+
+/// 保存运行快照，task 需为传入 execute 的同一实例，以保留运行时覆盖的参数
+pub fn save_run_definition(task: &Task, run_id: &str) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_RUN_DEFINITION) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let run = TaskRunDefinition {
+        task_id: task.task_id(),
+        run_id: run_id.to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        definition: redacted_definition(task)?,
+    };
+    db.put_cf(
+        &cf,
+        run_definition_key(&run.task_id, run_id),
+        serde_json::to_vec(&run)?,
+    )?;
+    Ok(())
+}
+
+/// 运行快照不存在时返回 None
+pub fn get_run_definition(task_id: &str, run_id: &str) -> Result<Option<TaskRunDefinition>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_RUN_DEFINITION) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    match db.get_cf(&cf, run_definition_key(task_id, run_id))? {
+        Some(v) => Ok(Some(serde_json::from_slice::<TaskRunDefinition>(&v)?)),
+        None => Ok(None),
+    }
+}
+
+/// 快照与当前定义的字段差异
+pub fn diff_definition(snapshot: &Value, current: &Value) -> Vec<DefinitionChange> {
+    json_diff(snapshot, current)
+        .into_iter()
+        .map(|c| DefinitionChange {
+            path: c.path,
+            snapshot: c.old,
+            current: c.new,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_definition, redacted_definition, redacted_task};
+    use crate::commons::REDACTED_VALUE;
+    use crate::tasks::{Task, TransferTask};
+
+    //cargo test tasks::modules::run_definition::test::test_redact_and_diff -- --nocapture
+    #[test]
+    fn test_redact_and_diff() {
+        let mut transfer = TransferTask::default();
+        transfer.attributes.task_parallelism = 4;
+        let snapshot = redacted_definition(&Task::Transfer(transfer.clone())).unwrap();
+        assert_eq!(
+            snapshot["source"]["secret_access_key"].as_str(),
+            Some(REDACTED_VALUE)
+        );
+        assert_eq!(
+            snapshot["target"]["access_key_id"].as_str(),
+            Some(REDACTED_VALUE)
+        );
+
+        transfer.attributes.task_parallelism = 8;
+        transfer.name = "renamed".to_string();
+        let current = redacted_definition(&Task::Transfer(transfer)).unwrap();
+        let changes = diff_definition(&snapshot, &current);
+        let paths = changes
+            .iter()
+            .map(|c| c.path.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&"/name"));
+        assert!(paths.contains(&"/attributes/task_parallelism"));
+    }
+
+    //cargo test tasks::modules::run_definition::test::test_redacted_task -- --nocapture
+    #[test]
+    fn test_redacted_task() {
+        let mut transfer = TransferTask::default();
+        transfer.name = "nightly".to_string();
+        let task = redacted_task(&Task::Transfer(transfer)).unwrap();
+        let value = serde_json::to_value(&task).unwrap();
+        assert_eq!(
+            value["source"]["secret_access_key"].as_str(),
+            Some(REDACTED_VALUE)
+        );
+        assert_eq!(task.name(), "nightly");
+    }
+}
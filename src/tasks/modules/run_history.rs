@@ -0,0 +1,258 @@
+use crate::resources::{CF_TASK_RUNS, GLOBAL_ROCKSDB};
+use crate::server::TaskCounters;
+use crate::tasks::{CompareStatus, Status, TaskStatus, TaskStopReason, TransferStatus};
+use anyhow::{anyhow, Result};
+use rocksdb::{Direction, IteratorMode};
+use serde::{Deserialize, Serialize};
+
+/// 运行结束时的状态，执行协程退出时任务未停止视为被中断（停机或进程异常退出）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskRunState {
+    Running,
+    Completed,
+    Stopped,
+    Failed,
+    Interrupted,
+}
+
+/// 任务的一次运行，启动时写入，执行协程退出后补充结束状态与计数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskRun {
+    pub run_id: String,
+    pub task_id: String,
+    pub start_ts: u64,
+    pub end_ts: Option<u64>,
+    pub state: TaskRunState,
+    pub objects_transferred: u64,
+    pub bytes_transferred: u64,
+    pub errors: u64,
+    pub stop_reason: Option<TaskStopReason>,
+}
+
+impl TaskRun {
+    pub fn start(task_id: &str, run_id: &str, start_ts: u64) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            task_id: task_id.to_string(),
+            start_ts,
+            end_ts: None,
+            state: TaskRunState::Running,
+            objects_transferred: 0,
+            bytes_transferred: 0,
+            errors: 0,
+            stop_reason: None,
+        }
+    }
+
+    pub fn finish(
+        &mut self,
+        state: TaskRunState,
+        stop_reason: Option<TaskStopReason>,
+        counters: &TaskCounters,
+        end_ts: u64,
+    ) {
+        self.state = state;
+        self.stop_reason = stop_reason;
+        self.objects_transferred = counters.objects_transferred;
+        self.bytes_transferred = counters.bytes_transferred;
+        self.errors = counters.errors;
+        self.end_ts = Some(end_ts);
+    }
+
+    fn key(&self) -> String {
+        task_run_key(&self.task_id, self.start_ts, &self.run_id)
+    }
+}
+
+// 开始时间补零，同一任务的运行按开始时间排序；删除任务时由 delete_task_all 按 {task_id}: 前缀删除
+fn task_run_key(task_id: &str, start_ts: u64, run_id: &str) -> String {
+    format!("{}:{:020}:{}", task_id, start_ts, run_id)
+}
+
+/// 执行协程退出后按持久化状态与取消标识判定本次运行的结束状态
+/// 正常结束与人为停止的状态相同，按取消标识区分
+pub fn run_final_state(
+    status: Option<&TaskStatus>,
+    cancelled: bool,
+) -> (TaskRunState, Option<TaskStopReason>) {
+    let reason = match status.map(|s| &s.status) {
+        Some(Status::Transfer(TransferStatus::Stopped(r))) => r.clone(),
+        Some(Status::Compare(CompareStatus::Stopped)) => TaskStopReason::Finish,
+        _ => return (TaskRunState::Interrupted, None),
+    };
+    let state = match (&reason, cancelled) {
+        (TaskStopReason::Finish, true) => TaskRunState::Stopped,
+        (TaskStopReason::Finish, false) => TaskRunState::Completed,
+        _ => TaskRunState::Failed,
+    };
+    (state, Some(reason))
+}
+
+pub fn save_task_run(run: &TaskRun) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_RUNS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    db.put_cf(&cf, run.key(), serde_json::to_vec(run)?)?;
+    Ok(())
+}
+
+/// 任务全部运行记录，按开始时间倒序
+pub fn list_task_runs(task_id: &str) -> Result<Vec<TaskRun>> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_RUNS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let prefix = format!("{}:", task_id);
+    let mut runs = vec![];
+    for item in db.iterator_cf(
+        &cf,
+        IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+    ) {
+        let (k, v) = item?;
+        if !k.starts_with(prefix.as_bytes()) {
+            break;
+        }
+        let run = serde_json::from_slice::<TaskRun>(&v)?;
+        // task_id 含 ':' 时前缀可能匹配到其他任务
+        if run.task_id == task_id {
+            runs.push(run);
+        }
+    }
+    runs.reverse();
+    Ok(runs)
+}
+
+/// 写入新一次运行，上次进程退出前未结束的运行标记为被中断
+pub fn record_task_run_start(run: &TaskRun) -> Result<()> {
+    for mut stale in list_task_runs(&run.task_id)? {
+        if stale.state == TaskRunState::Running && stale.run_id != run.run_id {
+            stale.state = TaskRunState::Interrupted;
+            save_task_run(&stale)?;
+        }
+    }
+    save_task_run(run)
+}
+
+/// 按保留策略需删除的运行，runs 按开始时间倒序；超过 retention_days 天或超出每任务 retention_count 条的删除
+/// 0 表示不限制，运行中的记录不删除
+pub fn runs_to_prune<'a>(
+    runs: &'a [TaskRun],
+    now: u64,
+    retention_days: u64,
+    retention_count: usize,
+) -> Vec<&'a TaskRun> {
+    runs.iter()
+        .enumerate()
+        .filter(|(_, r)| r.state != TaskRunState::Running)
+        .filter(|(i, r)| {
+            let expired =
+                retention_days > 0 && now.saturating_sub(r.start_ts) > retention_days * 86400;
+            let overflow = retention_count > 0 && *i >= retention_count;
+            expired || overflow
+        })
+        .map(|(_, r)| r)
+        .collect()
+}
+
+/// 按保留策略清理任务的运行记录，返回删除的条数
+pub fn prune_task_runs(
+    task_id: &str,
+    now: u64,
+    retention_days: u64,
+    retention_count: usize,
+) -> Result<usize> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_RUNS) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let runs = list_task_runs(task_id)?;
+    let pruned = runs_to_prune(&runs, now, retention_days, retention_count);
+    for run in pruned.iter() {
+        db.delete_cf(&cf, run.key())?;
+    }
+    Ok(pruned.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run_final_state, runs_to_prune, TaskRun, TaskRunState};
+    use crate::tasks::{
+        CompareStatus, Status, TaskStatus, TaskStopReason, TransferStage, TransferStatus,
+    };
+
+    fn status(status: Status) -> TaskStatus {
+        TaskStatus {
+            task_id: "1".to_string(),
+            start_time: 0,
+            status,
+            last_skip_reason: None,
+            run_id: None,
+        }
+    }
+
+    //cargo test tasks::modules::run_history::test::test_run_final_state -- --nocapture
+    #[test]
+    fn test_run_final_state() {
+        let finish = status(Status::Transfer(TransferStatus::Stopped(
+            TaskStopReason::Finish,
+        )));
+        assert_eq!(
+            run_final_state(Some(&finish), false).0,
+            TaskRunState::Completed
+        );
+        assert_eq!(
+            run_final_state(Some(&finish), true).0,
+            TaskRunState::Stopped
+        );
+        let broken = status(Status::Transfer(TransferStatus::Stopped(
+            TaskStopReason::Broken,
+        )));
+        let (state, reason) = run_final_state(Some(&broken), true);
+        assert_eq!(state, TaskRunState::Failed);
+        assert!(matches!(reason, Some(TaskStopReason::Broken)));
+        let compare = status(Status::Compare(CompareStatus::Stopped));
+        assert_eq!(
+            run_final_state(Some(&compare), false).0,
+            TaskRunState::Completed
+        );
+        // 停机时任务保持运行状态以便恢复
+        let running = status(Status::Transfer(TransferStatus::Running(
+            TransferStage::Stock,
+        )));
+        let (state, reason) = run_final_state(Some(&running), true);
+        assert_eq!(state, TaskRunState::Interrupted);
+        assert!(reason.is_none());
+        assert_eq!(run_final_state(None, false).0, TaskRunState::Interrupted);
+    }
+
+    //cargo test tasks::modules::run_history::test::test_runs_to_prune -- --nocapture
+    #[test]
+    fn test_runs_to_prune() {
+        let day = 86400;
+        let now = 100 * day;
+        let mut runs = vec![];
+        for (i, age) in [0, 1, 2, 40].iter().enumerate() {
+            let mut run = TaskRun::start("1", &i.to_string(), now - age * day);
+            run.state = TaskRunState::Completed;
+            runs.push(run);
+        }
+        let ids = |pruned: Vec<&TaskRun>| {
+            pruned
+                .iter()
+                .map(|r| r.run_id.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(ids(runs_to_prune(&runs, now, 30, 0)), vec!["3"]);
+        assert_eq!(ids(runs_to_prune(&runs, now, 0, 2)), vec!["2", "3"]);
+        assert_eq!(ids(runs_to_prune(&runs, now, 1, 3)), vec!["2", "3"]);
+        assert!(runs_to_prune(&runs, now, 0, 0).is_empty());
+        // 运行中的记录不删除
+        runs[3].state = TaskRunState::Running;
+        assert_eq!(ids(runs_to_prune(&runs, now, 30, 1)), vec!["1", "2"]);
+    }
+}
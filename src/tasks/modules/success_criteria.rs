@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 任务成功判定条件，运行结束时评估，默认不设限制与原有行为一致
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SuccessCriteria {
+    // 允许失败的对象数上限
+    #[serde(default)]
+    pub max_failed_objects: Option<u64>,
+    // 允许失败的对象比例上限，取值 0.0 ~ 1.0
+    #[serde(default)]
+    pub max_failed_ratio: Option<f64>,
+    // 要求运行结束时完成校验
+    #[serde(default)]
+    pub require_verification: bool,
+}
+
+/// 未满足的判定条件及实际值
+/// 任务状态以 bincode 持久化，不使用 internally tagged 表示
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CriteriaBreach {
+    MaxFailedObjects { limit: u64, failed: u64 },
+    MaxFailedRatio { limit: f64, ratio: f64 },
+    RequireVerification,
+}
+
+impl fmt::Display for CriteriaBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CriteriaBreach::MaxFailedObjects { limit, failed } => write!(
+                f,
+                "max_failed_objects not met, failed {} objects, limit {}",
+                failed, limit
+            ),
+            CriteriaBreach::MaxFailedRatio { limit, ratio } => write!(
+                f,
+                "max_failed_ratio not met, failed ratio {}, limit {}",
+                ratio, limit
+            ),
+            CriteriaBreach::RequireVerification => {
+                write!(f, "require_verification not met, run not verified")
+            }
+        }
+    }
+}
+
+/// 运行结束时判定条件未满足，Task::execute 据此将任务置为 Failed(CriteriaNotMet)
+#[derive(Debug, Clone)]
+pub struct CriteriaNotMetError {
+    pub task_id: String,
+    pub breach: CriteriaBreach,
+}
+
+impl fmt::Display for CriteriaNotMetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {} {}", self.task_id, self.breach)
+    }
+}
+
+impl std::error::Error for CriteriaNotMetError {}
+
+impl SuccessCriteria {
+    /// 按 max_failed_objects、max_failed_ratio、require_verification 顺序评估，返回首个未满足的条件
+    /// 对象总数为 0 时失败比例按 0 计算，存在失败对象时按 1 计算
+    pub fn evaluate(&self, total: u64, failed: u64, verified: bool) -> Result<(), CriteriaBreach> {
+        if let Some(limit) = self.max_failed_objects {
+            if failed > limit {
+                return Err(CriteriaBreach::MaxFailedObjects { limit, failed });
+            }
+        }
+        if let Some(limit) = self.max_failed_ratio {
+            let ratio = match total {
+                0 if failed == 0 => 0.0,
+                0 => 1.0,
+                _ => failed as f64 / total as f64,
+            };
+            if ratio > limit {
+                return Err(CriteriaBreach::MaxFailedRatio { limit, ratio });
+            }
+        }
+        if self.require_verification && !verified {
+            return Err(CriteriaBreach::RequireVerification);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CriteriaBreach, SuccessCriteria};
+
+    //cargo test tasks::modules::success_criteria::test::test_default_criteria -- --nocapture
+    #[test]
+    fn test_default_criteria() {
+        let criteria = SuccessCriteria::default();
+        assert!(criteria.evaluate(0, 0, false).is_ok());
+        assert!(criteria.evaluate(100, 100, false).is_ok());
+        let parsed = serde_json::from_str::<SuccessCriteria>("{}").unwrap();
+        assert_eq!(parsed, criteria);
+    }
+
+    //cargo test tasks::modules::success_criteria::test::test_max_failed_objects -- --nocapture
+    #[test]
+    fn test_max_failed_objects() {
+        let criteria = SuccessCriteria {
+            max_failed_objects: Some(2),
+            ..Default::default()
+        };
+        assert!(criteria.evaluate(10, 2, false).is_ok());
+        assert_eq!(
+            criteria.evaluate(10, 3, false),
+            Err(CriteriaBreach::MaxFailedObjects {
+                limit: 2,
+                failed: 3
+            })
+        );
+
+        let zero = SuccessCriteria {
+            max_failed_objects: Some(0),
+            ..Default::default()
+        };
+        assert!(zero.evaluate(0, 0, false).is_ok());
+        assert!(zero.evaluate(10, 1, false).is_err());
+    }
+
+    //cargo test tasks::modules::success_criteria::test::test_max_failed_ratio_boundary -- --nocapture
+    #[test]
+    fn test_max_failed_ratio_boundary() {
+        let criteria = SuccessCriteria {
+            max_failed_ratio: Some(0.1),
+            ..Default::default()
+        };
+        // 恰好等于上限视为满足
+        assert!(criteria.evaluate(100, 10, false).is_ok());
+        assert!(criteria.evaluate(1000, 100, false).is_ok());
+        assert_eq!(
+            criteria.evaluate(100, 11, false),
+            Err(CriteriaBreach::MaxFailedRatio {
+                limit: 0.1,
+                ratio: 0.11
+            })
+        );
+        assert!(criteria.evaluate(1000, 101, false).is_err());
+
+        let strict = SuccessCriteria {
+            max_failed_ratio: Some(0.0),
+            ..Default::default()
+        };
+        assert!(strict.evaluate(100, 0, false).is_ok());
+        assert!(strict.evaluate(100, 1, false).is_err());
+
+        let loose = SuccessCriteria {
+            max_failed_ratio: Some(1.0),
+            ..Default::default()
+        };
+        assert!(loose.evaluate(100, 100, false).is_ok());
+    }
+
+    //cargo test tasks::modules::success_criteria::test::test_zero_object_run -- --nocapture
+    #[test]
+    fn test_zero_object_run() {
+        let criteria = SuccessCriteria {
+            max_failed_objects: Some(0),
+            max_failed_ratio: Some(0.0),
+            require_verification: false,
+        };
+        assert!(criteria.evaluate(0, 0, false).is_ok());
+        // 对象总数为 0 但存在失败（如列举失败），比例按 1 计算
+        let ratio_only = SuccessCriteria {
+            max_failed_ratio: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(
+            ratio_only.evaluate(0, 1, false),
+            Err(CriteriaBreach::MaxFailedRatio {
+                limit: 0.5,
+                ratio: 1.0
+            })
+        );
+    }
+
+    //cargo test tasks::modules::success_criteria::test::test_breach_bincode -- --nocapture
+    #[test]
+    fn test_breach_bincode() {
+        let breach = CriteriaBreach::MaxFailedRatio {
+            limit: 0.1,
+            ratio: 0.5,
+        };
+        let encoded = bincode::serialize(&breach).unwrap();
+        assert_eq!(
+            bincode::deserialize::<CriteriaBreach>(&encoded).unwrap(),
+            breach
+        );
+    }
+
+    //cargo test tasks::modules::success_criteria::test::test_require_verification -- --nocapture
+    #[test]
+    fn test_require_verification() {
+        let criteria = SuccessCriteria {
+            max_failed_objects: Some(5),
+            require_verification: true,
+            ..Default::default()
+        };
+        assert!(criteria.evaluate(10, 0, true).is_ok());
+        assert_eq!(
+            criteria.evaluate(10, 0, false),
+            Err(CriteriaBreach::RequireVerification)
+        );
+        // 多个条件未满足时记录首个
+        assert!(matches!(
+            criteria.evaluate(10, 6, false),
+            Err(CriteriaBreach::MaxFailedObjects { .. })
+        ));
+    }
+}
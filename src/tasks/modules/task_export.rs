@@ -0,0 +1,145 @@
+use super::{redacted_definition, TASK_CREDENTIAL_FIELDS};
+use crate::commons::REDACTED_VALUE;
+use crate::tasks::Task;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// 导出格式版本，结构不兼容地变化时递增
+pub const TASK_EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// 导出文件头，ndjson 格式时为第一行
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaskExportHeader {
+    pub schema_version: u32,
+    pub exported_at: u64,
+    // 凭证是否已脱敏，脱敏后的定义不能直接导入
+    pub redacted: bool,
+}
+
+/// 导出的任务定义，导入时逐条解析，单条解析失败不影响其他条目
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaskExport {
+    #[serde(flatten)]
+    pub header: TaskExportHeader,
+    pub tasks: Vec<Value>,
+}
+
+/// 单条任务的导入结果，source_task_id 为导出时的 id
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaskImportResult {
+    pub index: usize,
+    pub source_task_id: Option<String>,
+    pub task_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 导出用的任务定义，redact 为 true 时凭证脱敏
+pub fn export_definition(task: &Task, redact: bool) -> Result<Value> {
+    match redact {
+        true => redacted_definition(task),
+        false => Ok(serde_json::to_value(task)?),
+    }
+}
+
+/// 解析导出文件，支持单个 json 文档与 ndjson；版本高于当前支持的版本时返回错误
+pub fn parse_task_export(body: &[u8]) -> Result<TaskExport> {
+    let export = match serde_json::from_slice::<TaskExport>(body) {
+        Ok(e) => e,
+        Err(doc_err) => {
+            let text = std::str::from_utf8(body)?;
+            let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+            let header = match lines.next() {
+                Some(l) => serde_json::from_str::<TaskExportHeader>(l)
+                    .map_err(|_| anyhow!("invalid task export: {}", doc_err))?,
+                None => return Err(anyhow!("empty task export")),
+            };
+            let tasks = lines
+                .enumerate()
+                .map(|(i, l)| {
+                    serde_json::from_str::<Value>(l)
+                        .map_err(|e| anyhow!("invalid task export line {}: {}", i + 2, e))
+                })
+                .collect::<Result<Vec<Value>>>()?;
+            TaskExport { header, tasks }
+        }
+    };
+    if export.header.schema_version > TASK_EXPORT_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "task export schema version {} not supported, max {}",
+            export.header.schema_version,
+            TASK_EXPORT_SCHEMA_VERSION
+        ));
+    }
+    Ok(export)
+}
+
+/// 定义中是否含脱敏后的凭证
+pub fn definition_redacted(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.iter().any(|(k, v)| {
+            (TASK_CREDENTIAL_FIELDS.contains(&k.as_str()) && v.as_str() == Some(REDACTED_VALUE))
+                || definition_redacted(v)
+        }),
+        Value::Array(arr) => arr.iter().any(definition_redacted),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        definition_redacted, export_definition, parse_task_export, TaskExport, TaskExportHeader,
+        TASK_EXPORT_SCHEMA_VERSION,
+    };
+    use crate::tasks::{Task, TransferTask};
+
+    fn header(schema_version: u32) -> TaskExportHeader {
+        TaskExportHeader {
+            schema_version,
+            exported_at: 1000,
+            redacted: false,
+        }
+    }
+
+    //cargo test tasks::modules::task_export::test::test_parse_task_export -- --nocapture
+    #[test]
+    fn test_parse_task_export() {
+        let task = export_definition(&Task::Transfer(TransferTask::default()), false).unwrap();
+        let export = TaskExport {
+            header: header(TASK_EXPORT_SCHEMA_VERSION),
+            tasks: vec![task.clone(), task.clone()],
+        };
+        let doc = serde_json::to_vec(&export).unwrap();
+        assert_eq!(parse_task_export(&doc).unwrap(), export);
+
+        // ndjson 首行为文件头，空行忽略
+        let ndjson = format!(
+            "{}\n{}\n\n{}\n",
+            serde_json::to_string(&export.header).unwrap(),
+            task,
+            task
+        );
+        assert_eq!(parse_task_export(ndjson.as_bytes()).unwrap(), export);
+
+        let newer = TaskExport {
+            header: header(TASK_EXPORT_SCHEMA_VERSION + 1),
+            tasks: vec![],
+        };
+        assert!(parse_task_export(&serde_json::to_vec(&newer).unwrap()).is_err());
+        assert!(parse_task_export(b"").is_err());
+        assert!(parse_task_export(b"not json").is_err());
+    }
+
+    //cargo test tasks::modules::task_export::test::test_definition_redacted -- --nocapture
+    #[test]
+    fn test_definition_redacted() {
+        let task = Task::Transfer(TransferTask::default());
+        assert!(!definition_redacted(
+            &export_definition(&task, false).unwrap()
+        ));
+        assert!(definition_redacted(
+            &export_definition(&task, true).unwrap()
+        ));
+    }
+}
@@ -1,6 +1,5 @@
 use super::{
-    CompareTask, ObjectStorage, TransferTask, TransferType, GLOBAL_TASK_JOINSET,
-    GLOBAL_TASK_STOP_MARK_MAP,
+    cancel_task, CompareTask, ObjectStorage, TransferTask, TransferType, GLOBAL_TASK_JOINSET,
 };
 use crate::{
     commons::{
@@ -8,11 +7,11 @@ use crate::{
         LastModifyFilter,
     },
     configure::get_config,
-    resources::{CF_TASK, GLOBAL_ROCKSDB},
+    resources::{commit_task_writes, task_create_writes, CF_TASK, GLOBAL_ROCKSDB},
     s3::OSSDescription,
     tasks::{
-        get_live_transfer_task_status, remove_exec_joinset, save_task_status, LogInfo,
-        TransferTaskStatusType,
+        get_live_transfer_task_status, remove_exec_joinset, save_task_status, CriteriaBreach,
+        CriteriaNotMetError, LogInfo, SuccessCriteria, TaskPanicError, TransferTaskStatusType,
     },
 };
 use anyhow::{anyhow, Result};
@@ -41,6 +40,7 @@ pub const OFFSET_PREFIX: &'static str = "offset_";
 pub const NOTIFY_FILE_PREFIX: &'static str = "notify_";
 pub const REMOVED_PREFIX: &'static str = "removed_";
 pub const MODIFIED_PREFIX: &'static str = "modified_";
+pub const TRANSFER_SUCCESS_MARKER_FILE: &'static str = "_SUCCESS.json";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -68,8 +68,26 @@ pub struct AnalyzedResult {
     pub max: i128,
     pub min: i128,
 }
+
+/// 任务已存在：源与目标均相同的同类任务已创建，或指定的 task_id 已被使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskExistsError {
+    // 以指定 id 创建时为该 id
+    pub task_id: Option<String>,
+}
+
+impl std::fmt::Display for TaskExistsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.task_id {
+            Some(id) => write!(f, "task {} already exist", id),
+            None => write!(f, "task with same source and target already created"),
+        }
+    }
+}
+
+impl std::error::Error for TaskExistsError {}
 /// 任务阶段，包括存量曾量全量
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum TransferStage {
     Stock,
     Increment,
@@ -82,13 +100,27 @@ pub enum TaskStopReason {
     Finish,
     // 任务重错误容忍度达到上线
     Broken,
+    // 运行结束但未满足成功判定条件
+    Failed(TaskFailure),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+// 任务失败原因
+pub enum TaskFailure {
+    // 记录未满足的具体条件
+    CriteriaNotMet(CriteriaBreach),
+    // 执行协程或子任务 panic，记录 panic 信息
+    Panicked(String),
 }
 
 /// 任务类别，根据传输方式划分
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum TaskType {
+    #[serde(alias = "transfer")]
     Transfer,
+    #[serde(alias = "truncate_bucket")]
     TruncateBucket,
+    #[serde(alias = "compare")]
     Compare,
 }
 
@@ -114,7 +146,7 @@ impl Task {
     pub fn task_source(&self) -> ObjectStorage {
         match self {
             Task::Transfer(t) => t.source.clone(),
-            Task::Compare(c) => c.target.clone(),
+            Task::Compare(c) => c.source.clone(),
             // Task::TruncateBucket(_) => todo!(),
         }
     }
@@ -148,6 +180,13 @@ impl Task {
         }
     }
 
+    pub fn meta_dir(&self) -> String {
+        return match self {
+            Task::Transfer(transfer) => transfer.attributes.meta_dir.clone(),
+            Task::Compare(compare) => compare.attributes.meta_dir.clone(),
+        };
+    }
+
     pub fn task_id(&self) -> String {
         return match self {
             Task::Transfer(transfer) => transfer.task_id.clone(),
@@ -155,18 +194,46 @@ impl Task {
         };
     }
 
+    pub fn name(&self) -> String {
+        match self {
+            Task::Transfer(transfer) => transfer.name.clone(),
+            Task::Compare(compare) => compare.name.clone(),
+        }
+    }
+
+    pub fn namespace(&self) -> String {
+        match self {
+            Task::Transfer(transfer) => transfer.namespace.clone(),
+            Task::Compare(compare) => compare.namespace.clone(),
+        }
+    }
+
+    /// 是否已有源与目标均相同的同类任务，无法解析的任务定义跳过
     pub fn already_created(&self) -> Result<bool> {
+        let db = GLOBAL_ROCKSDB.get()?;
         let mut created = false;
-        let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
+        let cf = match db.cf_handle(CF_TASK) {
             Some(cf) => cf,
             None => return Err(anyhow!("column family not exist")),
         };
-        let cf_task_iter = GLOBAL_ROCKSDB.iterator_cf(&cf, IteratorMode::Start);
+        let cf_task_iter = db.iterator_cf(&cf, IteratorMode::Start);
 
         for item in cf_task_iter {
             if let Ok(kv) = item {
-                let task_json_str = String::from_utf8(kv.1.to_vec())?;
-                let task = json_to_struct::<Task>(task_json_str.as_str())?;
+                let task = match String::from_utf8(kv.1.to_vec())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|s| json_to_struct::<Task>(s.as_str()))
+                {
+                    Ok(t) => t,
+                    Err(e) => {
+                        log::warn!(
+                            "skip undecodable task {} when checking duplicates: {}",
+                            String::from_utf8_lossy(&kv.0),
+                            e
+                        );
+                        continue;
+                    }
+                };
                 if self.task_type().eq(&task.task_type()) {
                     if self.task_source().eq(&task.task_source())
                         && self.task_target().eq(&task.task_target())
@@ -181,47 +248,56 @@ impl Task {
 
     pub fn stop(&self) -> Result<()> {
         return match self {
-            Task::Transfer(_) => {
-                let kv = match GLOBAL_TASK_STOP_MARK_MAP.get(&self.task_id()) {
-                    Some(kv) => kv,
-                    None => {
-                        return Err(anyhow!("task {} stop mark not exist", self.task_id()));
-                    }
-                };
-                kv.value().store(true, std::sync::atomic::Ordering::SeqCst);
-                Ok(())
-            }
+            Task::Transfer(_) => match cancel_task(&self.task_id()) {
+                true => Ok(()),
+                false => Err(anyhow!("task {} stop mark not exist", self.task_id())),
+            },
             _ => Err(anyhow!("task not transfer task")),
         };
     }
 
     pub fn create(&mut self) -> Result<i64> {
-        if self.already_created()? {
-            return Err(anyhow!("task created"));
-        }
         let id = task_id_generator();
-        let global_meta_dir = get_config()?.meta_dir;
-        let meta_dir = gen_file_path(&global_meta_dir, id.to_string().as_str(), "");
-        self.set_task_id(id.to_string().as_str());
-        self.set_meta_dir(&meta_dir);
+        self.create_with_id(&id.to_string())?;
+        Ok(id)
+    }
 
-        let cf = match GLOBAL_ROCKSDB.cf_handle(CF_TASK) {
+    /// 以指定 id 创建任务，导入时保留原 id 使用；id 已存在时返回错误
+    /// 任务定义与名称索引同批写入，调用方需持有 lock_task_names
+    pub fn create_with_id(&mut self, id: &str) -> Result<()> {
+        let db = GLOBAL_ROCKSDB.get()?;
+        self.validate_fields()?;
+        self.validate_consistency().into_result()?;
+        if self.already_created()? {
+            return Err(TaskExistsError { task_id: None }.into());
+        }
+        let cf = match db.cf_handle(CF_TASK) {
             Some(cf) => cf,
             None => return Err(anyhow!("column family not exist")),
         };
+        if db.get_cf(&cf, id)?.is_some() {
+            return Err(TaskExistsError {
+                task_id: Some(id.to_string()),
+            }
+            .into());
+        }
+        let global_meta_dir = get_config()?.meta_dir;
+        let meta_dir = gen_file_path(&global_meta_dir, id, "");
+        self.set_task_id(id);
+        self.set_meta_dir(&meta_dir);
 
         let task_json = struct_to_json_string(self)?;
-        GLOBAL_ROCKSDB.put_cf(&cf, id.to_string().as_bytes(), task_json.as_bytes())?;
-        Ok(id)
+        commit_task_writes(&task_create_writes(id, &task_json, &self.name()))?;
+        Ok(())
     }
 
-    pub async fn execute(&self) {
+    pub async fn execute(&self, run_id: &str) {
         match self {
             //Todo
             // 重构task status，使用cf记录
             // 主动停止任务时更新任务为停止状态，执行完成时不更新任务状态
             Task::Transfer(transfer) => {
-                match transfer.execute().await {
+                match transfer.execute(run_id).await {
                     Ok(_) => {
                         //Todo 增加清理逻辑，清理joinset，标志等等
                         let mut transfer_task_status =
@@ -251,8 +327,21 @@ impl Task {
                                     return;
                                 }
                             };
-                        transfer_task_status.status =
-                            TransferTaskStatusType::Stopped(TaskStopReason::Broken);
+                        let reason = match (
+                            e.downcast_ref::<CriteriaNotMetError>(),
+                            e.downcast_ref::<TaskPanicError>(),
+                        ) {
+                            (Some(c), _) => TaskStopReason::Failed(TaskFailure::CriteriaNotMet(
+                                c.breach.clone(),
+                            )),
+                            (_, Some(p)) => {
+                                // 停止其余子任务与进度条
+                                cancel_task(&transfer.task_id);
+                                TaskStopReason::Failed(TaskFailure::Panicked(p.message.clone()))
+                            }
+                            _ => TaskStopReason::Broken,
+                        };
+                        transfer_task_status.status = TransferTaskStatusType::Stopped(reason);
                         save_task_status(&transfer.task_id, transfer_task_status);
                         log::error!("{}", e);
                     }
@@ -286,6 +375,10 @@ impl TaskDefaultParameters {
         "default_name".to_string()
     }
 
+    pub fn namespace_default() -> String {
+        "default".to_string()
+    }
+
     pub fn objects_per_batch_default() -> i32 {
         100
     }
@@ -343,6 +436,10 @@ impl TaskDefaultParameters {
     pub fn last_modify_filter_default() -> Option<LastModifyFilter> {
         None
     }
+
+    pub fn success_criteria_default() -> SuccessCriteria {
+        SuccessCriteria::default()
+    }
 }
 
 pub fn de_usize_from_str<'de, D>(deserializer: D) -> Result<usize, D::Error>
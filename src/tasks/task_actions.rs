@@ -1,14 +1,11 @@
 use super::{FileDescription, FilePosition, IncrementAssistant, ListedRecord, RecordDescription};
-use crate::commons::LastModifyFilter;
+use crate::commons::{AnalyzeProgress, LastModifyFilter};
 use anyhow::Result;
 use async_trait::async_trait;
 use dashmap::DashMap;
-use std::{
-    collections::BTreeMap,
-    sync::{
-        atomic::{AtomicBool, AtomicUsize},
-        Arc,
-    },
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    Arc,
 };
 use tokio::{
     sync::{Mutex, RwLock},
@@ -21,7 +18,7 @@ use tokio::{
 // 设计 incrementparameter struct 用于统一存储 lastmodif notify file 以及 notify file size 等原子数据
 #[async_trait]
 pub trait TransferTaskActions {
-    async fn analyze_source(&self) -> Result<BTreeMap<String, i128>>;
+    async fn analyze_source(&self, progress: &AnalyzeProgress) -> Result<()>;
     // 错误记录重试
     fn error_record_retry(&self, executing_transfers: Arc<RwLock<usize>>) -> Result<()>;
     // 记录列表执行器
@@ -76,7 +73,7 @@ pub trait TransferTaskActions {
         err_counter: Arc<AtomicUsize>,
         offset_map: Arc<DashMap<String, FilePosition>>,
         snapshot_stop_mark: Arc<AtomicBool>,
-    );
+    ) -> Result<()>;
 }
 
 #[async_trait]
@@ -1,10 +1,13 @@
+use super::{
+    check_join_panic, get_task_checkpoint, CheckPoint, FileDescription, FilePosition, ListedRecord,
+};
 use super::{
     gen_file_path, task_actions::CompareTaskActions, CompareLocal2Local, CompareLocal2Oss,
     CompareOss2Local, CompareOss2Oss, ObjectStorage, TaskDefaultParameters, TasksStatusSaver,
     TransferStage, COMPARE_CHECK_POINT_FILE, COMPARE_RESULT_PREFIX,
     COMPARE_SOURCE_OBJECT_LIST_FILE_PREFIX, OFFSET_PREFIX,
 };
-use super::{get_task_checkpoint, CheckPoint, FileDescription, FilePosition, ListedRecord};
+use crate::commons::inherit_request_context;
 use crate::commons::{
     json_to_struct, promote_processbar, quantify_processbar, LastModifyFilter, RegexFilter,
 };
@@ -130,6 +133,7 @@ impl ObjectDiff {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct CompareTaskAttributes {
     #[serde(default = "TaskDefaultParameters::objects_per_batch_default")]
     pub objects_per_batch: i32,
@@ -238,12 +242,15 @@ impl CompareCheckOption {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub struct CompareTask {
     #[serde(default = "TaskDefaultParameters::id_default")]
     pub task_id: String,
     #[serde(default = "TaskDefaultParameters::name_default")]
     pub name: String,
+    // 并发调度按 namespace 分组
+    #[serde(default = "TaskDefaultParameters::namespace_default")]
+    pub namespace: String,
     pub source: ObjectStorage,
     pub target: ObjectStorage,
     pub check_option: CompareCheckOption,
@@ -255,6 +262,7 @@ impl Default for CompareTask {
         Self {
             task_id: TaskDefaultParameters::id_default(),
             name: TaskDefaultParameters::name_default(),
+            namespace: TaskDefaultParameters::namespace_default(),
             source: ObjectStorage::default(),
             target: ObjectStorage::default(),
             check_option: CompareCheckOption::default(),
@@ -400,6 +408,7 @@ impl CompareTask {
         };
 
         let task_id = self.task_id.clone();
+        // 比对子任务 panic 时结束本次比对，不再写入 checkpoint
         rt.block_on(async {
             // 启动checkpoint记录线程
             // let stock_status_saver = TaskStatusSaver {
@@ -420,10 +429,10 @@ impl CompareTask {
             let stop_mark = Arc::clone(&snapshot_stop_mark);
             let total = compare_source_list.total_lines;
             let id = self.task_id.clone();
-            sys_set.spawn(async move {
+            sys_set.spawn(inherit_request_context(async move {
                 // Todo 调整进度条
                 quantify_processbar(id, total, stop_mark, map, OFFSET_PREFIX).await;
-            });
+            }));
             let task_compare = self.gen_compare_actions();
             let mut vec_keys = vec![];
             // 按列表传输object from source to target
@@ -459,7 +468,7 @@ impl CompareTask {
                     .eq(&self.attributes.objects_per_batch.to_string())
                 {
                     while execut_set.len() >= self.attributes.task_parallelism {
-                        execut_set.join_next().await;
+                        check_join_panic(execut_set.join_next().await)?;
                     }
                     let vk = vec_keys.clone();
                     task_compare
@@ -484,7 +493,7 @@ impl CompareTask {
                     < self.attributes.max_errors
             {
                 while execut_set.len() >= self.attributes.task_parallelism {
-                    execut_set.join_next().await;
+                    check_join_panic(execut_set.join_next().await)?;
                 }
 
                 let vk = vec_keys.clone();
@@ -501,7 +510,7 @@ impl CompareTask {
             }
 
             while execut_set.len() > 0 {
-                execut_set.join_next().await;
+                check_join_panic(execut_set.join_next().await)?;
             }
             // 配置停止 offset save 标识为 true
             snapshot_stop_mark.store(true, std::sync::atomic::Ordering::Relaxed);
@@ -523,7 +532,8 @@ impl CompareTask {
             while sys_set.len() > 0 {
                 sys_set.join_next().await;
             }
-        });
+            anyhow::Ok(())
+        })?;
 
         // Todo
         // 持续同步逻辑，循环比较不相等记录，并指定校验次数
@@ -0,0 +1,240 @@
+use super::{ObjectStorage, SetupStage, Task};
+use crate::s3::OSSDescription;
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use aws_smithy_types::byte_stream::ByteStream;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// 每项检查的超时时间，http 接口整体超时为 2s，各项检查并发执行且须在此之前结束
+const CONNECTIVITY_CHECK_TIMEOUT: Duration = Duration::from_millis(1500);
+
+// 写入探测对象的名称前缀，探测后立即删除
+const WRITE_PROBE_PREFIX: &'static str = ".mario_probe_";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityCheckKind {
+    // 列出一个对象，验证凭证与读权限
+    List,
+    // 写入并删除空对象，验证凭证与写权限
+    WriteProbe,
+}
+
+/// 单项连通性检查的结果，失败时 stage 与 message 有值
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConnectivityCheck {
+    // source 或 target
+    pub storage: String,
+    pub check: ConnectivityCheckKind,
+    pub ok: bool,
+    pub stage: Option<SetupStage>,
+    pub message: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl ConnectivityCheck {
+    fn new(
+        storage: &str,
+        check: ConnectivityCheckKind,
+        result: Result<(), (SetupStage, String)>,
+        duration: Duration,
+    ) -> Self {
+        let (stage, message) = match result {
+            Ok(_) => (None, None),
+            Err((stage, message)) => (Some(stage), Some(message)),
+        };
+        Self {
+            storage: storage.to_string(),
+            check,
+            ok: stage.is_none(),
+            stage,
+            message,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+impl Task {
+    /// 源端列出一个对象，迁移任务的目标端写入探测、比对任务的目标端列出一个对象；各项并发执行，不修改任务
+    pub async fn check_connectivity(&self) -> Vec<ConnectivityCheck> {
+        let target_check = match self {
+            Task::Transfer(_) => ConnectivityCheckKind::WriteProbe,
+            Task::Compare(_) => ConnectivityCheckKind::List,
+        };
+        let (source, target) = tokio::join!(
+            run_check("source", ConnectivityCheckKind::List, self.task_source()),
+            run_check("target", target_check, self.task_target()),
+        );
+        vec![source, target]
+    }
+}
+
+async fn run_check(
+    storage: &str,
+    check: ConnectivityCheckKind,
+    object_storage: ObjectStorage,
+) -> ConnectivityCheck {
+    let begin = Instant::now();
+    let result = match tokio::time::timeout(
+        CONNECTIVITY_CHECK_TIMEOUT,
+        check_storage(check, &object_storage),
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(_) => Err((
+            SetupStage::Unreachable,
+            format!("timeout after {}ms", CONNECTIVITY_CHECK_TIMEOUT.as_millis()),
+        )),
+    };
+    ConnectivityCheck::new(storage, check, result, begin.elapsed())
+}
+
+async fn check_storage(
+    check: ConnectivityCheckKind,
+    object_storage: &ObjectStorage,
+) -> Result<(), (SetupStage, String)> {
+    match (check, object_storage) {
+        (ConnectivityCheckKind::List, ObjectStorage::Local(path)) => list_local(path),
+        (ConnectivityCheckKind::WriteProbe, ObjectStorage::Local(path)) => probe_local_write(path),
+        (ConnectivityCheckKind::List, ObjectStorage::OSS(oss)) => list_oss(oss).await,
+        (ConnectivityCheckKind::WriteProbe, ObjectStorage::OSS(oss)) => probe_oss_write(oss).await,
+    }
+}
+
+fn list_local(path: &str) -> Result<(), (SetupStage, String)> {
+    match std::fs::read_dir(path) {
+        Ok(mut entries) => match entries.next() {
+            Some(Err(e)) => Err((SetupStage::LocalPath, format!("read {} error: {}", path, e))),
+            _ => Ok(()),
+        },
+        Err(e) => Err((SetupStage::LocalPath, format!("read {} error: {}", path, e))),
+    }
+}
+
+/// 路径自身或最近的已存在上级目录，迁移任务的本地目标目录在执行时创建
+pub fn nearest_existing_dir(path: &str) -> Option<&Path> {
+    Path::new(path).ancestors().find(|p| p.is_dir())
+}
+
+fn probe_local_write(path: &str) -> Result<(), (SetupStage, String)> {
+    let dir = match nearest_existing_dir(path) {
+        Some(d) => d,
+        None => {
+            return Err((
+                SetupStage::LocalPath,
+                format!("no existing directory for {}", path),
+            ))
+        }
+    };
+    let probe = dir.join(format!("{}{}", WRITE_PROBE_PREFIX, uuid::Uuid::new_v4()));
+    if let Err(e) = std::fs::File::create(&probe) {
+        return Err((
+            SetupStage::LocalPath,
+            format!("write {} error: {}", dir.display(), e),
+        ));
+    }
+    std::fs::remove_file(&probe).map_err(|e| {
+        (
+            SetupStage::LocalPath,
+            format!("remove probe file {} error: {}", probe.display(), e),
+        )
+    })
+}
+
+// 服务端拒绝视为凭证或权限问题，其余为不可达
+fn sdk_failure<E, R>(e: SdkError<E, R>) -> (SetupStage, String)
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug,
+{
+    let stage = match e {
+        SdkError::ServiceError(_) => SetupStage::Credentials,
+        _ => SetupStage::Unreachable,
+    };
+    (stage, format!("{}", DisplayErrorContext(&e)))
+}
+
+async fn list_oss(oss: &OSSDescription) -> Result<(), (SetupStage, String)> {
+    let client = oss
+        .gen_oss_client()
+        .map_err(|e| (SetupStage::Credentials, e.to_string()))?;
+    client
+        .client
+        .list_objects_v2()
+        .bucket(oss.bucket.clone())
+        .set_prefix(oss.prefix.clone())
+        .max_keys(1)
+        .send()
+        .await
+        .map_err(sdk_failure)?;
+    Ok(())
+}
+
+// 在前缀下写入空对象后立即删除，删除失败时报告残留的对象
+async fn probe_oss_write(oss: &OSSDescription) -> Result<(), (SetupStage, String)> {
+    let client = oss
+        .gen_oss_client()
+        .map_err(|e| (SetupStage::Credentials, e.to_string()))?;
+    let key = format!(
+        "{}{}{}",
+        oss.prefix.clone().unwrap_or_default(),
+        WRITE_PROBE_PREFIX,
+        uuid::Uuid::new_v4()
+    );
+    client
+        .client
+        .put_object()
+        .bucket(oss.bucket.clone())
+        .key(key.clone())
+        .body(ByteStream::from_static(b""))
+        .send()
+        .await
+        .map_err(sdk_failure)?;
+    client.remove_object(&oss.bucket, &key).await.map_err(|e| {
+        let (stage, message) = sdk_failure(e);
+        (
+            stage,
+            format!("remove probe object {} error: {}", key, message),
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{nearest_existing_dir, ConnectivityCheckKind};
+    use crate::tasks::{ObjectStorage, SetupStage, Task, TransferTask};
+    use std::path::Path;
+
+    //cargo test tasks::task_connectivity::test::test_nearest_existing_dir -- --nocapture
+    #[test]
+    fn test_nearest_existing_dir() {
+        assert_eq!(nearest_existing_dir("/tmp"), Some(Path::new("/tmp")));
+        assert_eq!(
+            nearest_existing_dir("/tmp/mario_missing_dir/a/b"),
+            Some(Path::new("/tmp"))
+        );
+    }
+
+    //cargo test tasks::task_connectivity::test::test_check_connectivity_local -- --nocapture
+    #[tokio::test]
+    async fn test_check_connectivity_local() {
+        let mut transfer = TransferTask::default();
+        transfer.source = ObjectStorage::Local("/tmp".to_string());
+        transfer.target = ObjectStorage::Local("/tmp/mario_connectivity_test/target".to_string());
+        let checks = Task::Transfer(transfer.clone()).check_connectivity().await;
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(|c| c.ok), "{:?}", checks);
+        assert_eq!(checks[1].check, ConnectivityCheckKind::WriteProbe);
+        // 探测不创建目标目录
+        assert!(!Path::new("/tmp/mario_connectivity_test").exists());
+
+        transfer.source = ObjectStorage::Local("/tmp/mario_connectivity_test/missing".to_string());
+        let checks = Task::Transfer(transfer).check_connectivity().await;
+        assert!(!checks[0].ok);
+        assert_eq!(checks[0].stage, Some(SetupStage::LocalPath));
+        assert!(checks[1].ok);
+    }
+}
@@ -0,0 +1,420 @@
+use super::{CompareTask, ObjectStorage, SuccessCriteria, Task, TransferTask, TransferType};
+use crate::commons::LastModifyFilter;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::Path;
+
+// s3 协议要求除最后一个分片外每个分片不小于 5MiB
+pub const OSS_MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+pub const RULE_SOURCE_TARGET_OVERLAP: &'static str = "source_target_overlap";
+pub const RULE_CHUNK_BELOW_OSS_MIN_PART: &'static str = "chunk_below_oss_min_part";
+pub const RULE_CHUNK_EXCEEDS_LARGE_FILE_SIZE: &'static str = "chunk_exceeds_large_file_size";
+pub const RULE_INCLUDE_EXCLUDE_CONFLICT: &'static str = "include_exclude_conflict";
+pub const RULE_LAST_MODIFY_FILTER_IN_INCREMENT: &'static str = "last_modify_filter_in_increment";
+pub const RULE_FAILED_RATIO_OUT_OF_RANGE: &'static str = "failed_ratio_out_of_range";
+pub const RULE_VERIFICATION_UNSUPPORTED: &'static str = "verification_unsupported";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsistencySeverity {
+    // 阻止任务创建与更新
+    Error,
+    // 仅提示，随任务展示
+    Warning,
+}
+
+/// 字段组合冲突，rule_id 供前端关联文档
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConsistencyIssue {
+    pub rule_id: String,
+    pub severity: ConsistencySeverity,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl ConsistencyIssue {
+    fn error(rule_id: &str, message: String, suggestion: &str) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            severity: ConsistencySeverity::Error,
+            message,
+            suggestion: suggestion.to_string(),
+        }
+    }
+
+    fn warning(rule_id: &str, message: String, suggestion: &str) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            severity: ConsistencySeverity::Warning,
+            message,
+            suggestion: suggestion.to_string(),
+        }
+    }
+}
+
+impl Display for ConsistencyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}; {}",
+            self.rule_id, self.message, self.suggestion
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ConsistencyReport {
+    pub errors: Vec<ConsistencyIssue>,
+    pub warnings: Vec<ConsistencyIssue>,
+}
+
+impl ConsistencyReport {
+    fn push(&mut self, issue: ConsistencyIssue) {
+        match issue.severity {
+            ConsistencySeverity::Error => self.errors.push(issue),
+            ConsistencySeverity::Warning => self.warnings.push(issue),
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// 存在 error 时返回包含全部 error 的错误
+    pub fn into_result(self) -> Result<Vec<ConsistencyIssue>, TaskConsistencyError> {
+        match self.has_errors() {
+            true => Err(TaskConsistencyError {
+                errors: self.errors,
+            }),
+            false => Ok(self.warnings),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskConsistencyError {
+    pub errors: Vec<ConsistencyIssue>,
+}
+
+impl Display for TaskConsistencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors = self
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "task definition inconsistent: {}", errors.join(" | "))
+    }
+}
+
+impl std::error::Error for TaskConsistencyError {}
+
+impl Task {
+    /// 校验字段组合，单个字段的合法性由反序列化保证
+    pub fn validate_consistency(&self) -> ConsistencyReport {
+        let mut report = ConsistencyReport::default();
+        let issues = match self {
+            Task::Transfer(t) => transfer_issues(t),
+            Task::Compare(c) => compare_issues(c),
+        };
+        for issue in issues {
+            report.push(issue);
+        }
+        report
+    }
+}
+
+fn transfer_issues(task: &TransferTask) -> Vec<ConsistencyIssue> {
+    let attributes = &task.attributes;
+    let mut issues = vec![];
+    issues.extend(check_source_target_overlap(&task.source, &task.target));
+    issues.extend(check_chunk_below_oss_min_part(
+        &task.target,
+        attributes.multi_part_chunk_size,
+    ));
+    issues.extend(check_chunk_exceeds_large_file_size(
+        attributes.multi_part_chunk_size,
+        attributes.large_file_size,
+    ));
+    issues.extend(check_include_exclude_conflict(
+        &attributes.include,
+        &attributes.exclude,
+    ));
+    issues.extend(check_last_modify_filter_in_increment(
+        &attributes.transfer_type,
+        &attributes.last_modify_filter,
+    ));
+    issues.extend(check_failed_ratio_out_of_range(
+        &attributes.success_criteria,
+    ));
+    issues.extend(check_verification_unsupported(&attributes.success_criteria));
+    issues
+}
+
+fn compare_issues(task: &CompareTask) -> Vec<ConsistencyIssue> {
+    let attributes = &task.attributes;
+    let mut issues = vec![];
+    issues.extend(check_source_target_overlap(&task.source, &task.target));
+    issues.extend(check_chunk_exceeds_large_file_size(
+        attributes.multi_part_chunk,
+        attributes.large_file_size,
+    ));
+    issues.extend(check_include_exclude_conflict(
+        &attributes.include,
+        &attributes.exclude,
+    ));
+    issues
+}
+
+// 源与目标相同或互相包含时，写入目标的文件会被再次列举
+fn check_source_target_overlap(
+    source: &ObjectStorage,
+    target: &ObjectStorage,
+) -> Option<ConsistencyIssue> {
+    let overlap = match (source, target) {
+        (ObjectStorage::Local(s), ObjectStorage::Local(t)) => {
+            let (s, t) = (Path::new(s), Path::new(t));
+            s.starts_with(t) || t.starts_with(s)
+        }
+        (ObjectStorage::OSS(s), ObjectStorage::OSS(t)) => {
+            let s_prefix = s.prefix.clone().unwrap_or_default();
+            let t_prefix = t.prefix.clone().unwrap_or_default();
+            s.endpoint.eq(&t.endpoint)
+                && s.bucket.eq(&t.bucket)
+                && (s_prefix.starts_with(&t_prefix) || t_prefix.starts_with(&s_prefix))
+        }
+        _ => false,
+    };
+    match overlap {
+        true => Some(ConsistencyIssue::error(
+            RULE_SOURCE_TARGET_OVERLAP,
+            "source and target are the same location or contain each other".to_string(),
+            "use a target path or prefix outside of the source",
+        )),
+        false => None,
+    }
+}
+
+fn check_chunk_below_oss_min_part(
+    target: &ObjectStorage,
+    chunk_size: usize,
+) -> Option<ConsistencyIssue> {
+    match target {
+        ObjectStorage::OSS(_) if chunk_size < OSS_MIN_PART_SIZE => Some(ConsistencyIssue::error(
+            RULE_CHUNK_BELOW_OSS_MIN_PART,
+            format!(
+                "multi_part_chunk_size {} is below the minimum part size {} of object storage",
+                chunk_size, OSS_MIN_PART_SIZE
+            ),
+            "set multi_part_chunk_size to at least 5242880",
+        )),
+        _ => None,
+    }
+}
+
+// 大于 large_file_size 的文件走分片上传，分片大于阈值时部分大文件只有一个分片
+fn check_chunk_exceeds_large_file_size(
+    chunk_size: usize,
+    large_file_size: usize,
+) -> Option<ConsistencyIssue> {
+    match chunk_size > large_file_size {
+        true => Some(ConsistencyIssue::warning(
+            RULE_CHUNK_EXCEEDS_LARGE_FILE_SIZE,
+            format!(
+                "multi_part_chunk_size {} is greater than large_file_size {}, files between them are uploaded as a single part",
+                chunk_size, large_file_size
+            ),
+            "set multi_part_chunk_size less than or equal to large_file_size",
+        )),
+        false => None,
+    }
+}
+
+fn check_include_exclude_conflict(
+    include: &Option<Vec<String>>,
+    exclude: &Option<Vec<String>>,
+) -> Option<ConsistencyIssue> {
+    let (include, exclude) = match (include, exclude) {
+        (Some(i), Some(e)) => (i, e),
+        _ => return None,
+    };
+    let conflicts = include
+        .iter()
+        .filter(|p| exclude.contains(p))
+        .cloned()
+        .collect::<Vec<String>>();
+    match conflicts.is_empty() {
+        true => None,
+        false => Some(ConsistencyIssue::warning(
+            RULE_INCLUDE_EXCLUDE_CONFLICT,
+            format!(
+                "patterns {:?} appear in both include and exclude, matching objects are excluded",
+                conflicts
+            ),
+            "remove the patterns from include or exclude",
+        )),
+    }
+}
+
+// 增量阶段使用启动时间戳生成过滤条件，用户配置的 last_modify_filter 不生效
+fn check_last_modify_filter_in_increment(
+    transfer_type: &TransferType,
+    last_modify_filter: &Option<LastModifyFilter>,
+) -> Option<ConsistencyIssue> {
+    match (transfer_type, last_modify_filter) {
+        (TransferType::Increment, Some(_)) => Some(ConsistencyIssue::warning(
+            RULE_LAST_MODIFY_FILTER_IN_INCREMENT,
+            "last_modify_filter is ignored when transfer_type is increment".to_string(),
+            "remove last_modify_filter or use transfer_type full",
+        )),
+        _ => None,
+    }
+}
+
+fn check_failed_ratio_out_of_range(criteria: &SuccessCriteria) -> Option<ConsistencyIssue> {
+    match criteria.max_failed_ratio {
+        Some(r) if !(0.0..=1.0).contains(&r) => Some(ConsistencyIssue::error(
+            RULE_FAILED_RATIO_OUT_OF_RANGE,
+            format!("success_criteria.max_failed_ratio {} out of range", r),
+            "set max_failed_ratio between 0.0 and 1.0",
+        )),
+        _ => None,
+    }
+}
+
+// 尚无校验流程，要求校验的任务永远无法满足判定条件
+fn check_verification_unsupported(criteria: &SuccessCriteria) -> Option<ConsistencyIssue> {
+    match criteria.require_verification {
+        true => Some(ConsistencyIssue::error(
+            RULE_VERIFICATION_UNSUPPORTED,
+            "success_criteria.require_verification is not supported, no verification runs after transfer".to_string(),
+            "remove require_verification or set it to false",
+        )),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commons::LastModifyFilterType;
+    use crate::s3::OSSDescription;
+
+    fn local_transfer(source: &str, target: &str) -> TransferTask {
+        let mut task = TransferTask::default();
+        task.source = ObjectStorage::Local(source.to_string());
+        task.target = ObjectStorage::Local(target.to_string());
+        task
+    }
+
+    fn rule_ids(task: TransferTask) -> Vec<String> {
+        let report = Task::Transfer(task).validate_consistency();
+        report
+            .errors
+            .iter()
+            .chain(report.warnings.iter())
+            .map(|i| i.rule_id.clone())
+            .collect()
+    }
+
+    //cargo test tasks::task_consistency::test::test_default_transfer_consistent -- --nocapture
+    #[test]
+    fn test_default_transfer_consistent() {
+        let task = local_transfer("/tmp/source", "/tmp/target");
+        assert!(rule_ids(task).is_empty());
+    }
+
+    //cargo test tasks::task_consistency::test::test_source_target_overlap -- --nocapture
+    #[test]
+    fn test_source_target_overlap() {
+        let task = local_transfer("/tmp/source", "/tmp/source/target");
+        let report = Task::Transfer(task).validate_consistency();
+        assert!(report.has_errors());
+        assert_eq!(report.errors[0].rule_id, RULE_SOURCE_TARGET_OVERLAP);
+
+        let mut task = TransferTask::default();
+        let mut target = OSSDescription::default();
+        target.prefix = Some("backup/".to_string());
+        task.target = ObjectStorage::OSS(target);
+        task.attributes.multi_part_chunk_size = OSS_MIN_PART_SIZE;
+        assert_eq!(rule_ids(task), vec![RULE_SOURCE_TARGET_OVERLAP]);
+    }
+
+    //cargo test tasks::task_consistency::test::test_chunk_below_oss_min_part -- --nocapture
+    #[test]
+    fn test_chunk_below_oss_min_part() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.target = ObjectStorage::OSS(OSSDescription::default());
+        task.attributes.multi_part_chunk_size = 1024;
+        let report = Task::Transfer(task).validate_consistency();
+        assert_eq!(report.errors[0].rule_id, RULE_CHUNK_BELOW_OSS_MIN_PART);
+        assert!(report.into_result().is_err());
+    }
+
+    //cargo test tasks::task_consistency::test::test_chunk_exceeds_large_file_size -- --nocapture
+    #[test]
+    fn test_chunk_exceeds_large_file_size() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.attributes.large_file_size = 1024;
+        task.attributes.multi_part_chunk_size = 2048;
+        let report = Task::Transfer(task).validate_consistency();
+        assert!(!report.has_errors());
+        assert_eq!(
+            report.warnings[0].rule_id,
+            RULE_CHUNK_EXCEEDS_LARGE_FILE_SIZE
+        );
+    }
+
+    //cargo test tasks::task_consistency::test::test_include_exclude_conflict -- --nocapture
+    #[test]
+    fn test_include_exclude_conflict() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.attributes.include = Some(vec!["\\.log$".to_string(), "\\.txt$".to_string()]);
+        task.attributes.exclude = Some(vec!["\\.log$".to_string()]);
+        assert_eq!(rule_ids(task), vec![RULE_INCLUDE_EXCLUDE_CONFLICT]);
+    }
+
+    //cargo test tasks::task_consistency::test::test_last_modify_filter_in_increment -- --nocapture
+    #[test]
+    fn test_last_modify_filter_in_increment() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.attributes.last_modify_filter = Some(LastModifyFilter {
+            filter_type: LastModifyFilterType::Greater,
+            timestamp: 0,
+        });
+        assert!(rule_ids(task.clone()).is_empty());
+
+        task.attributes.transfer_type = TransferType::Increment;
+        assert_eq!(rule_ids(task), vec![RULE_LAST_MODIFY_FILTER_IN_INCREMENT]);
+    }
+
+    //cargo test tasks::task_consistency::test::test_failed_ratio_out_of_range -- --nocapture
+    #[test]
+    fn test_failed_ratio_out_of_range() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.attributes.success_criteria.max_failed_ratio = Some(1.0);
+        assert!(rule_ids(task.clone()).is_empty());
+
+        task.attributes.success_criteria.max_failed_ratio = Some(1.5);
+        assert_eq!(rule_ids(task.clone()), vec![RULE_FAILED_RATIO_OUT_OF_RANGE]);
+        task.attributes.success_criteria.max_failed_ratio = Some(f64::NAN);
+        assert_eq!(rule_ids(task), vec![RULE_FAILED_RATIO_OUT_OF_RANGE]);
+    }
+
+    //cargo test tasks::task_consistency::test::test_verification_unsupported -- --nocapture
+    #[test]
+    fn test_verification_unsupported() {
+        let mut task = local_transfer("/tmp/source", "/tmp/target");
+        task.attributes.success_criteria.require_verification = true;
+        let report = Task::Transfer(task).validate_consistency();
+        assert_eq!(
+            report
+                .errors
+                .iter()
+                .map(|i| i.rule_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec![RULE_VERIFICATION_UNSUPPORTED]
+        );
+    }
+}
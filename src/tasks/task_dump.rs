@@ -0,0 +1,253 @@
+use super::{
+    gen_file_path, TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIST_FILE_POSITON_MAP,
+    GLOBAL_LIVING_TRANSFER_TASK_MAP, GLOBAL_TASKS_BIGFILE_JOINSET, GLOBAL_TASKS_EXEC_JOINSET,
+    GLOBAL_TASK_RUNTIME, GLOBAL_TASK_STOP_MARK_MAP,
+};
+use crate::configure::get_config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinSet;
+
+pub const RUNTIME_DUMP_FILE_PREFIX: &'static str = "runtime_dump_";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskStateDump {
+    pub task_id: String,
+    pub status: Option<TransferTaskStatusType>,
+    pub stop_mark: Option<bool>,
+    pub exec_joinset_len: Option<usize>,
+    pub bigfile_joinset_len: Option<usize>,
+    pub file_positions: usize,
+    pub min_file_offset: Option<usize>,
+    pub max_file_offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuntimeStateDump {
+    pub timestamp: u64,
+    pub tasks: Vec<TaskStateDump>,
+    // 写入的 dump 文件路径
+    pub file: Option<String>,
+}
+
+/// 汇总各全局任务表的运行时状态
+pub async fn collect_runtime_state() -> Result<RuntimeStateDump> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut task_ids = BTreeSet::new();
+    task_ids.extend(
+        GLOBAL_LIVING_TRANSFER_TASK_MAP
+            .iter()
+            .map(|kv| kv.key().clone()),
+    );
+    task_ids.extend(GLOBAL_TASK_STOP_MARK_MAP.iter().map(|kv| kv.key().clone()));
+    task_ids.extend(GLOBAL_TASKS_EXEC_JOINSET.iter().map(|kv| kv.key().clone()));
+    task_ids.extend(
+        GLOBAL_TASKS_BIGFILE_JOINSET
+            .iter()
+            .map(|kv| kv.key().clone()),
+    );
+
+    let mut tasks = vec![];
+    for task_id in task_ids {
+        let status = GLOBAL_LIVING_TRANSFER_TASK_MAP
+            .get(&task_id)
+            .map(|kv| kv.value().status.clone());
+        let stop_mark = GLOBAL_TASK_STOP_MARK_MAP
+            .get(&task_id)
+            .map(|kv| kv.value().load(std::sync::atomic::Ordering::SeqCst));
+        // 先取出 Arc 再 await，避免持有 dashmap 引用跨越 await
+        let exec_set: Option<Arc<RwLock<JoinSet<()>>>> = GLOBAL_TASKS_EXEC_JOINSET
+            .get(&task_id)
+            .map(|kv| kv.value().clone());
+        let bigfile_set: Option<Arc<RwLock<JoinSet<()>>>> = GLOBAL_TASKS_BIGFILE_JOINSET
+            .get(&task_id)
+            .map(|kv| kv.value().clone());
+        let exec_joinset_len = match exec_set {
+            Some(s) => Some(s.read().await.len()),
+            None => None,
+        };
+        let bigfile_joinset_len = match bigfile_set {
+            Some(s) => Some(s.read().await.len()),
+            None => None,
+        };
+
+        let offsets = GLOBAL_LIST_FILE_POSITON_MAP
+            .iter()
+            .filter(|item| item.key().starts_with(&task_id))
+            .map(|item| item.value().offset)
+            .collect::<Vec<usize>>();
+
+        tasks.push(TaskStateDump {
+            task_id,
+            status,
+            stop_mark,
+            exec_joinset_len,
+            bigfile_joinset_len,
+            file_positions: offsets.len(),
+            min_file_offset: offsets.iter().min().copied(),
+            max_file_offset: offsets.iter().max().copied(),
+        });
+    }
+
+    Ok(RuntimeStateDump {
+        timestamp,
+        tasks,
+        file: None,
+    })
+}
+
+/// 键以任务 id 开头的列表文件位置
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LiveFilePosition {
+    pub key: String,
+    pub offset: usize,
+    pub line_num: u64,
+}
+
+/// 运行中任务在内存中的完整状态，用于与持久化的 checkpoint 对照
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LiveTaskState {
+    pub task_id: String,
+    pub status: TransferTaskStatus,
+    pub file_positions: Vec<LiveFilePosition>,
+    pub stop_mark: Option<bool>,
+    pub exec_joinset_len: Option<usize>,
+    pub bigfile_joinset_len: Option<usize>,
+}
+
+/// 先复制出各全局表的内容再组装，序列化时不持有 dashmap 的分片锁
+pub async fn live_task_states() -> Vec<LiveTaskState> {
+    let living = GLOBAL_LIVING_TRANSFER_TASK_MAP
+        .iter()
+        .map(|kv| kv.value().clone())
+        .collect::<Vec<TransferTaskStatus>>();
+    let mut positions = GLOBAL_LIST_FILE_POSITON_MAP
+        .iter()
+        .map(|kv| LiveFilePosition {
+            key: kv.key().clone(),
+            offset: kv.value().offset,
+            line_num: kv.value().line_num,
+        })
+        .collect::<Vec<LiveFilePosition>>();
+    positions.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut states = vec![];
+    for status in living {
+        let task_id = status.task_id.clone();
+        let stop_mark = GLOBAL_TASK_STOP_MARK_MAP
+            .get(&task_id)
+            .map(|kv| kv.value().load(std::sync::atomic::Ordering::SeqCst));
+        let exec_set = GLOBAL_TASKS_EXEC_JOINSET
+            .get(&task_id)
+            .map(|kv| kv.value().clone());
+        let bigfile_set = GLOBAL_TASKS_BIGFILE_JOINSET
+            .get(&task_id)
+            .map(|kv| kv.value().clone());
+        states.push(LiveTaskState {
+            file_positions: positions
+                .iter()
+                .filter(|p| p.key.starts_with(&task_id))
+                .cloned()
+                .collect(),
+            task_id,
+            status,
+            stop_mark,
+            exec_joinset_len: joinset_len(exec_set).await,
+            bigfile_joinset_len: joinset_len(bigfile_set).await,
+        });
+    }
+    states.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+    states
+}
+
+async fn joinset_len(set: Option<Arc<RwLock<JoinSet<()>>>>) -> Option<usize> {
+    match set {
+        Some(s) => Some(s.read().await.len()),
+        None => None,
+    }
+}
+
+/// 收集运行时状态，输出到日志并写入 meta_dir 下带时间戳的文件
+pub async fn dump_runtime_state() -> Result<RuntimeStateDump> {
+    let mut dump = collect_runtime_state().await?;
+    let meta_dir = get_config()?.meta_dir;
+    fs::create_dir_all(&meta_dir)?;
+    let file = gen_file_path(
+        &meta_dir,
+        RUNTIME_DUMP_FILE_PREFIX,
+        format!("{}.json", dump.timestamp).as_str(),
+    );
+    dump.file = Some(file.clone());
+    let json = serde_json::to_string_pretty(&dump)?;
+    fs::write(&file, &json)?;
+    log::info!("runtime state dump:\n{}", json);
+    Ok(dump)
+}
+
+/// 在 GLOBAL_TASK_RUNTIME 上启动 dump 任务，返回的 sender 用于触发 dump，发送方不会被阻塞
+pub fn spawn_runtime_state_dumper() -> Result<mpsc::UnboundedSender<()>> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+    GLOBAL_TASK_RUNTIME.spawn(async move {
+        while rx.recv().await.is_some() {
+            if let Err(e) = dump_runtime_state().await {
+                log::error!("dump runtime state error: {}", e);
+            }
+        }
+    })?;
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod test {
+    use super::live_task_states;
+    use crate::tasks::{
+        FilePosition, TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIST_FILE_POSITON_MAP,
+        GLOBAL_LIVING_TRANSFER_TASK_MAP,
+    };
+
+    //cargo test tasks::task_dump::test::test_live_task_states -- --nocapture
+    #[tokio::test]
+    async fn test_live_task_states() {
+        let task_id = "live_state_test";
+        let status = TransferTaskStatus {
+            task_id: task_id.to_string(),
+            start_time: 0,
+            run_id: "1".to_string(),
+            status: TransferTaskStatusType::Starting,
+        };
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(task_id.to_string(), status);
+        for (key, offset) in [("live_state_test_20", 20), ("live_state_test_10", 10)] {
+            GLOBAL_LIST_FILE_POSITON_MAP.insert(
+                key.to_string(),
+                FilePosition {
+                    offset,
+                    line_num: 1,
+                },
+            );
+        }
+        GLOBAL_LIST_FILE_POSITON_MAP.insert("other_task_1".to_string(), FilePosition::default());
+
+        let states = live_task_states().await;
+        let state = states.iter().find(|s| s.task_id == task_id).unwrap();
+        let keys = state
+            .file_positions
+            .iter()
+            .map(|p| p.key.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(keys, vec!["live_state_test_10", "live_state_test_20"]);
+        assert_eq!(state.stop_mark, None);
+        assert_eq!(state.exec_joinset_len, None);
+
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+        for key in ["live_state_test_20", "live_state_test_10", "other_task_1"] {
+            GLOBAL_LIST_FILE_POSITON_MAP.remove(key);
+        }
+    }
+}
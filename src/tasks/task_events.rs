@@ -0,0 +1,381 @@
+use super::{
+    task_is_living, task_min_file_position, transfer_status_of, CompareStatus, FilePosition,
+    Status, TransferStatus, TransferTaskStatusType, GLOBAL_TASK_RUNTIME,
+};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+// 单次运行的状态变化不超过 4 次（Starting、Stock、Increment、Stopped），容量足够时状态事件不会因消费慢而丢失
+const TASK_STATE_EVENT_CAPACITY: usize = 16;
+// 进度采样间隔，仅读取内存中的执行位置
+const TASK_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// 活动任务的事件通道，任务停止后移除
+static GLOBAL_TASK_EVENT_CHANNELS: Lazy<DashMap<String, Arc<TaskEventChannel>>> =
+    Lazy::new(DashMap::new);
+
+/// 任务进度，对象数为列表文件已处理行数，字节数为列表文件读取位置
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct TaskProgress {
+    pub objects_done: u64,
+    pub list_bytes_done: u64,
+}
+
+impl From<FilePosition> for TaskProgress {
+    fn from(p: FilePosition) -> Self {
+        Self {
+            objects_done: p.line_num,
+            list_bytes_done: p.offset as u64,
+        }
+    }
+}
+
+/// 推送给订阅方的任务事件，finished 为最后一个事件
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TaskEvent {
+    State {
+        task_id: String,
+        status: Status,
+        timestamp: u64,
+    },
+    Progress {
+        task_id: String,
+        #[serde(flatten)]
+        progress: TaskProgress,
+        timestamp: u64,
+    },
+    Finished {
+        task_id: String,
+        // 从未启动的任务为 None
+        status: Option<Status>,
+        progress: Option<TaskProgress>,
+        timestamp: u64,
+    },
+}
+
+impl TaskEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskEvent::State { .. } => "state",
+            TaskEvent::Progress { .. } => "progress",
+            TaskEvent::Finished { .. } => "finished",
+        }
+    }
+}
+
+// 状态事件经 broadcast 推送，进度只保留最新值，消费慢时丢弃中间进度
+struct TaskEventChannel {
+    state: broadcast::Sender<TaskEvent>,
+    progress: watch::Sender<Option<TaskProgress>>,
+    last_state: std::sync::Mutex<Option<TaskEvent>>,
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+fn current_progress(task_id: &str) -> Option<TaskProgress> {
+    task_min_file_position(task_id).map(TaskProgress::from)
+}
+
+fn task_event_channel(task_id: &str) -> Arc<TaskEventChannel> {
+    if let Some(c) = GLOBAL_TASK_EVENT_CHANNELS.get(task_id) {
+        return c.value().clone();
+    }
+    let (state, _) = broadcast::channel(TASK_STATE_EVENT_CAPACITY);
+    let (progress, _) = watch::channel(None);
+    let channel = Arc::new(TaskEventChannel {
+        state,
+        progress,
+        last_state: std::sync::Mutex::new(None),
+    });
+    GLOBAL_TASK_EVENT_CHANNELS.insert(task_id.to_string(), channel.clone());
+    // 进度推送协程不可用时仍推送状态事件
+    if let Err(e) =
+        GLOBAL_TASK_RUNTIME.spawn(publish_progress(task_id.to_string(), channel.clone()))
+    {
+        log::warn!("task {} progress events disabled: {}", task_id, e);
+    }
+    channel
+}
+
+// 任务存活且有订阅方时按间隔采样进度，位置未变化时不推送
+async fn publish_progress(task_id: String, channel: Arc<TaskEventChannel>) {
+    let mut ticker = tokio::time::interval(TASK_PROGRESS_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let registered = GLOBAL_TASK_EVENT_CHANNELS
+            .get(&task_id)
+            .map(|c| Arc::ptr_eq(c.value(), &channel))
+            .unwrap_or(false);
+        if !registered {
+            break;
+        }
+        if channel.progress.receiver_count() == 0 {
+            continue;
+        }
+        let progress = current_progress(&task_id);
+        channel.progress.send_if_modified(|p| match *p != progress {
+            true => {
+                *p = progress;
+                true
+            }
+            false => false,
+        });
+    }
+}
+
+/// 任务状态变化时调用，停止状态推送 finished 事件后关闭通道
+pub fn publish_task_state(task_id: &str, status: &TransferTaskStatusType) {
+    let channel = task_event_channel(task_id);
+    let timestamp = now_secs();
+    let state = TaskEvent::State {
+        task_id: task_id.to_string(),
+        status: Status::Transfer(transfer_status_of(status)),
+        timestamp,
+    };
+    if let Ok(mut last) = channel.last_state.lock() {
+        *last = Some(state.clone());
+    }
+    // 无订阅方时发送失败，忽略
+    let _ = channel.state.send(state);
+    if status.is_stopped() {
+        let _ = channel.state.send(TaskEvent::Finished {
+            task_id: task_id.to_string(),
+            status: Some(Status::Transfer(transfer_status_of(status))),
+            progress: current_progress(task_id),
+            timestamp,
+        });
+        GLOBAL_TASK_EVENT_CHANNELS.remove_if(task_id, |_, c| Arc::ptr_eq(c, &channel));
+    }
+}
+
+/// 任务事件订阅，任务未运行时仅返回一个 finished 事件
+pub struct TaskEventSubscription {
+    task_id: String,
+    pending: Option<TaskEvent>,
+    state_rx: Option<broadcast::Receiver<TaskEvent>>,
+    progress_rx: Option<watch::Receiver<Option<TaskProgress>>>,
+    finished: bool,
+}
+
+impl TaskEventSubscription {
+    /// 订阅活动任务的事件，首个事件为当前状态
+    pub fn living(task_id: &str) -> Option<Self> {
+        if !task_is_living(task_id) {
+            return None;
+        }
+        let channel = GLOBAL_TASK_EVENT_CHANNELS.get(task_id)?.value().clone();
+        let pending = channel.last_state.lock().ok().and_then(|s| s.clone());
+        Some(Self {
+            task_id: task_id.to_string(),
+            pending,
+            state_rx: Some(channel.state.subscribe()),
+            progress_rx: Some(channel.progress.subscribe()),
+            finished: false,
+        })
+    }
+
+    /// 已停止或从未启动的任务，finished 事件取持久化的状态与 checkpoint 位置
+    pub fn stopped(task_id: &str, status: Option<Status>, position: Option<FilePosition>) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            pending: Some(TaskEvent::Finished {
+                task_id: task_id.to_string(),
+                status,
+                progress: position.map(TaskProgress::from),
+                timestamp: now_secs(),
+            }),
+            state_rx: None,
+            progress_rx: None,
+            finished: false,
+        }
+    }
+
+    /// 只接收状态事件，不触发进度采样
+    pub fn without_progress(mut self) -> Self {
+        self.progress_rx = None;
+        self
+    }
+
+    /// 等待任务停止，返回最终状态；通道关闭时返回 None
+    pub async fn wait_stopped(&mut self) -> Option<Option<Status>> {
+        while let Some(e) = self.next().await {
+            if let Some(status) = stopped_status(&e) {
+                return Some(status);
+            }
+        }
+        None
+    }
+
+    /// 下一个事件，finished 事件之后返回 None
+    pub async fn next(&mut self) -> Option<TaskEvent> {
+        if self.finished {
+            return None;
+        }
+        if let Some(e) = self.pending.take() {
+            self.finished = matches!(e, TaskEvent::Finished { .. });
+            return Some(e);
+        }
+        loop {
+            let progress_open = self.progress_rx.is_some();
+            let state_rx = self.state_rx.as_mut()?;
+            let progress_rx = &mut self.progress_rx;
+            let received = tokio::select! {
+                // 状态事件优先于进度
+                biased;
+                r = state_rx.recv() => Received::State(r),
+                r = async { progress_rx.as_mut()?.changed().await.ok() }, if progress_open => {
+                    Received::Progress(r.is_some())
+                }
+            };
+            match received {
+                Received::State(Ok(e)) => {
+                    self.finished = matches!(e, TaskEvent::Finished { .. });
+                    return Some(e);
+                }
+                Received::State(Err(RecvError::Lagged(n))) => {
+                    log::warn!(
+                        "task {} events subscriber lagged {} state events",
+                        self.task_id,
+                        n
+                    );
+                }
+                Received::State(Err(RecvError::Closed)) => return None,
+                Received::Progress(true) => {
+                    let progress = self
+                        .progress_rx
+                        .as_mut()
+                        .and_then(|rx| *rx.borrow_and_update());
+                    if let Some(progress) = progress {
+                        return Some(TaskEvent::Progress {
+                            task_id: self.task_id.clone(),
+                            progress,
+                            timestamp: now_secs(),
+                        });
+                    }
+                }
+                // 进度通道关闭后只等待状态事件
+                Received::Progress(false) => self.progress_rx = None,
+            }
+        }
+    }
+}
+
+// finished 事件或停止状态的事件，订阅时任务恰好停止则首个事件即为停止状态
+fn stopped_status(e: &TaskEvent) -> Option<Option<Status>> {
+    match e {
+        TaskEvent::Finished { status, .. } => Some(status.clone()),
+        TaskEvent::State { status, .. } => match status {
+            Status::Transfer(TransferStatus::Stopped(_))
+            | Status::Compare(CompareStatus::Stopped) => Some(Some(status.clone())),
+            _ => None,
+        },
+        TaskEvent::Progress { .. } => None,
+    }
+}
+
+enum Received {
+    State(Result<TaskEvent, RecvError>),
+    Progress(bool),
+}
+
+#[cfg(test)]
+mod test {
+    use super::{publish_task_state, TaskEvent, TaskEventSubscription};
+    use crate::tasks::{
+        init_global_task_runtime, Status, TaskStopReason, TransferStage, TransferStatus,
+        TransferTaskStatus, TransferTaskStatusType, GLOBAL_LIVING_TRANSFER_TASK_MAP,
+    };
+
+    //cargo test tasks::task_events::test::test_task_events_until_finished -- --nocapture
+    #[tokio::test]
+    async fn test_task_events_until_finished() {
+        init_global_task_runtime().unwrap();
+        let task_id = "task_events_test";
+        let running = TransferTaskStatusType::Running(TransferStage::Stock);
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task_id.to_string(),
+            TransferTaskStatus {
+                task_id: task_id.to_string(),
+                start_time: 0,
+                run_id: String::new(),
+                status: running.clone(),
+            },
+        );
+        publish_task_state(task_id, &TransferTaskStatusType::Starting);
+        publish_task_state(task_id, &running);
+
+        let mut sub = TaskEventSubscription::living(task_id).unwrap();
+        publish_task_state(
+            task_id,
+            &TransferTaskStatusType::Stopped(TaskStopReason::Finish),
+        );
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+
+        let names = [
+            sub.next().await.unwrap(),
+            sub.next().await.unwrap(),
+            sub.next().await.unwrap(),
+        ]
+        .iter()
+        .map(|e| e.name())
+        .collect::<Vec<&str>>();
+        // 订阅时的当前状态、停止状态、finished
+        assert_eq!(names, vec!["state", "state", "finished"]);
+        assert!(sub.next().await.is_none());
+        assert!(TaskEventSubscription::living(task_id).is_none());
+
+        let mut stopped = TaskEventSubscription::stopped(task_id, None, None);
+        let e = stopped.next().await.unwrap();
+        assert!(matches!(e, TaskEvent::Finished { .. }));
+        let json = serde_json::to_value(&e).unwrap();
+        assert_eq!(json["event"], "finished");
+        assert!(stopped.next().await.is_none());
+    }
+
+    //cargo test tasks::task_events::test::test_task_events_wait_stopped -- --nocapture
+    #[tokio::test]
+    async fn test_task_events_wait_stopped() {
+        init_global_task_runtime().unwrap();
+        let task_id = "task_events_wait_test";
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(
+            task_id.to_string(),
+            TransferTaskStatus {
+                task_id: task_id.to_string(),
+                start_time: 0,
+                run_id: String::new(),
+                status: TransferTaskStatusType::Starting,
+            },
+        );
+        publish_task_state(task_id, &TransferTaskStatusType::Starting);
+        let mut sub = TaskEventSubscription::living(task_id)
+            .unwrap()
+            .without_progress();
+        publish_task_state(
+            task_id,
+            &TransferTaskStatusType::Running(TransferStage::Stock),
+        );
+        publish_task_state(
+            task_id,
+            &TransferTaskStatusType::Stopped(TaskStopReason::Broken),
+        );
+        GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+
+        let status = sub.wait_stopped().await.unwrap().unwrap();
+        assert!(matches!(
+            status,
+            Status::Transfer(TransferStatus::Stopped(TaskStopReason::Broken))
+        ));
+        assert!(sub.wait_stopped().await.is_none());
+    }
+}
@@ -0,0 +1,286 @@
+use super::{FilePosition, ListedRecord, GLOBAL_LIST_FILE_POSITON_MAP, GLOBAL_TASK_OFFSET_MAP};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// 各任务已派发批次在列表文件中的范围，按批次首条记录的 offset 索引
+static GLOBAL_TASK_BATCH_RANGES: Lazy<DashMap<String, BTreeMap<usize, BatchRange>>> =
+    Lazy::new(DashMap::new);
+
+// 各任务最近一轮 checkpoint 快照时的批次位置
+static GLOBAL_TASK_BATCH_SNAPSHOTS: Lazy<DashMap<String, BTreeMap<String, BatchSnapshot>>> =
+    Lazy::new(DashMap::new);
+
+/// 批次在列表文件中的范围，位置为首尾记录所在行结束处
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BatchRange {
+    pub first_offset: usize,
+    pub first_line: u64,
+    pub last_offset: usize,
+    pub last_line: u64,
+    pub objects: usize,
+}
+
+/// 快照时批次的位置，stalled 表示与再上一轮快照相比没有前进
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSnapshot {
+    pub position: FilePosition,
+    pub stalled: bool,
+}
+
+/// 执行中批次的进度，批次范围未登记时 range 与 percent 为 None
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchProgress {
+    pub key: String,
+    pub offset: usize,
+    pub line_num: u64,
+    pub range: Option<BatchRange>,
+    pub percent: Option<f64>,
+    // 最近两轮快照之间及之后均未前进
+    pub stalled: bool,
+}
+
+/// 各批次完成百分比的分布
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PercentSummary {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+/// 派发批次时登记其范围
+pub fn register_batch_range(task_id: &str, records: &[ListedRecord]) {
+    let (first, last) = match (records.first(), records.last()) {
+        (Some(f), Some(l)) => (f, l),
+        _ => return,
+    };
+    GLOBAL_TASK_BATCH_RANGES
+        .entry(task_id.to_string())
+        .or_default()
+        .insert(
+            first.offset,
+            BatchRange {
+                first_offset: first.offset,
+                first_line: first.line_num,
+                last_offset: last.offset,
+                last_line: last.line_num,
+                objects: records.len(),
+            },
+        );
+}
+
+/// 任务重新启动或删除时清除批次范围与快照
+pub fn clear_task_batch_progress(task_id: &str) {
+    GLOBAL_TASK_BATCH_RANGES.remove(task_id);
+    GLOBAL_TASK_BATCH_SNAPSHOTS.remove(task_id);
+}
+
+/// 任务执行中各批次的列表文件位置，来源与 task_min_file_position 一致
+pub fn task_batch_positions(task_id: &str) -> BTreeMap<String, FilePosition> {
+    match GLOBAL_TASK_OFFSET_MAP
+        .get(task_id)
+        .map(|kv| kv.value().clone())
+    {
+        Some(m) => m.iter().map(|kv| (kv.key().clone(), *kv.value())).collect(),
+        None => GLOBAL_LIST_FILE_POSITON_MAP
+            .iter()
+            .filter(|item| item.key().starts_with(task_id))
+            .map(|item| (item.key().clone(), *item.value()))
+            .collect(),
+    }
+}
+
+/// 位置所在的批次范围
+pub fn batch_range_of(ranges: &BTreeMap<usize, BatchRange>, offset: usize) -> Option<&BatchRange> {
+    ranges
+        .range(..=offset)
+        .next_back()
+        .map(|(_, r)| r)
+        .filter(|r| offset <= r.last_offset)
+}
+
+/// 以本轮位置生成快照，位置与上一轮相同的批次标记为未前进
+pub fn next_batch_snapshots(
+    previous: Option<&BTreeMap<String, BatchSnapshot>>,
+    current: &BTreeMap<String, FilePosition>,
+) -> BTreeMap<String, BatchSnapshot> {
+    current
+        .iter()
+        .map(|(key, position)| {
+            let stalled = previous
+                .and_then(|p| p.get(key))
+                .map(|s| s.position == *position)
+                .unwrap_or(false);
+            (
+                key.clone(),
+                BatchSnapshot {
+                    position: *position,
+                    stalled,
+                },
+            )
+        })
+        .collect()
+}
+
+/// 每轮 checkpoint 快照时记录批次位置，并清除已全部执行完的批次范围
+pub fn snapshot_batch_progress(task_id: &str) {
+    let positions = task_batch_positions(task_id);
+    let snapshots = next_batch_snapshots(
+        GLOBAL_TASK_BATCH_SNAPSHOTS.get(task_id).as_deref(),
+        &positions,
+    );
+    GLOBAL_TASK_BATCH_SNAPSHOTS.insert(task_id.to_string(), snapshots);
+    // 批次按 offset 递增派发，整体位于最小执行位置之前的批次已结束
+    if let Some(min) = positions.values().map(|p| p.offset).min() {
+        if let Some(mut ranges) = GLOBAL_TASK_BATCH_RANGES.get_mut(task_id) {
+            ranges.retain(|_, r| r.last_offset >= min);
+        }
+    }
+}
+
+/// 单个批次的进度，按行计算，当前位置所在行视为未完成
+pub fn batch_progress(
+    key: &str,
+    position: FilePosition,
+    range: Option<&BatchRange>,
+    snapshot: Option<&BatchSnapshot>,
+) -> BatchProgress {
+    let percent = range.map(|r| {
+        let total = r.last_line.saturating_sub(r.first_line) + 1;
+        let done = position.line_num.saturating_sub(r.first_line);
+        (done as f64 / total as f64 * 100.0).min(100.0)
+    });
+    BatchProgress {
+        key: key.to_string(),
+        offset: position.offset,
+        line_num: position.line_num,
+        range: range.copied(),
+        percent,
+        stalled: snapshot
+            .map(|s| s.stalled && s.position == position)
+            .unwrap_or(false),
+    }
+}
+
+/// 百分比的最小值、中位数与最大值，偶数个时中位数取中间两个的平均
+pub fn percent_summary(percents: &[f64]) -> Option<PercentSummary> {
+    let mut sorted = percents.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let n = sorted.len();
+    if n == 0 {
+        return None;
+    }
+    let median = match n % 2 {
+        0 => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+        _ => sorted[n / 2],
+    };
+    Some(PercentSummary {
+        min: sorted[0],
+        median,
+        max: sorted[n - 1],
+    })
+}
+
+/// 任务执行中各批次的进度，按执行位置排序
+pub fn task_batch_progress(task_id: &str) -> Vec<BatchProgress> {
+    let ranges = GLOBAL_TASK_BATCH_RANGES
+        .get(task_id)
+        .map(|kv| kv.value().clone())
+        .unwrap_or_default();
+    let snapshots = GLOBAL_TASK_BATCH_SNAPSHOTS
+        .get(task_id)
+        .map(|kv| kv.value().clone())
+        .unwrap_or_default();
+    let mut batches = task_batch_positions(task_id)
+        .into_iter()
+        .map(|(key, position)| {
+            batch_progress(
+                &key,
+                position,
+                batch_range_of(&ranges, position.offset),
+                snapshots.get(&key),
+            )
+        })
+        .collect::<Vec<BatchProgress>>();
+    batches.sort_by_key(|b| b.offset);
+    batches
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        batch_progress, batch_range_of, next_batch_snapshots, percent_summary, BatchRange,
+    };
+    use crate::tasks::FilePosition;
+    use std::collections::BTreeMap;
+
+    fn range(
+        first_offset: usize,
+        first_line: u64,
+        last_offset: usize,
+        last_line: u64,
+    ) -> BatchRange {
+        BatchRange {
+            first_offset,
+            first_line,
+            last_offset,
+            last_line,
+            objects: (last_line - first_line + 1) as usize,
+        }
+    }
+
+    //cargo test tasks::task_progress::test::test_batch_range_of -- --nocapture
+    #[test]
+    fn test_batch_range_of() {
+        let mut ranges = BTreeMap::new();
+        ranges.insert(10, range(10, 1, 100, 10));
+        ranges.insert(110, range(110, 11, 200, 20));
+        assert_eq!(batch_range_of(&ranges, 10).unwrap().first_line, 1);
+        assert_eq!(batch_range_of(&ranges, 100).unwrap().first_line, 1);
+        assert_eq!(batch_range_of(&ranges, 150).unwrap().first_line, 11);
+        assert!(batch_range_of(&ranges, 5).is_none());
+        assert!(batch_range_of(&ranges, 105).is_none());
+        assert!(batch_range_of(&ranges, 300).is_none());
+    }
+
+    //cargo test tasks::task_progress::test::test_batch_progress -- --nocapture
+    #[test]
+    fn test_batch_progress() {
+        let r = range(10, 1, 100, 10);
+        let position = FilePosition {
+            offset: 55,
+            line_num: 6,
+        };
+        let p = batch_progress("t_10", position, Some(&r), None);
+        assert_eq!(p.percent, Some(50.0));
+        assert!(!p.stalled);
+        assert_eq!(batch_progress("t_10", position, None, None).percent, None);
+
+        // 两轮快照间未前进且之后仍未前进
+        let mut current = BTreeMap::new();
+        current.insert("t_10".to_string(), position);
+        let first = next_batch_snapshots(None, &current);
+        assert!(!first["t_10"].stalled);
+        let second = next_batch_snapshots(Some(&first), &current);
+        assert!(second["t_10"].stalled);
+        assert!(batch_progress("t_10", position, Some(&r), second.get("t_10")).stalled);
+        let advanced = FilePosition {
+            offset: 64,
+            line_num: 7,
+        };
+        assert!(!batch_progress("t_10", advanced, Some(&r), second.get("t_10")).stalled);
+    }
+
+    //cargo test tasks::task_progress::test::test_percent_summary -- --nocapture
+    #[test]
+    fn test_percent_summary() {
+        assert!(percent_summary(&[]).is_none());
+        let s = percent_summary(&[80.0, 10.0, 40.0]).unwrap();
+        assert_eq!((s.min, s.median, s.max), (10.0, 40.0, 80.0));
+        assert_eq!(
+            percent_summary(&[10.0, 40.0, 20.0, 90.0]).unwrap().median,
+            30.0
+        );
+    }
+}
@@ -0,0 +1,574 @@
+use super::{
+    clear_start_skipped, record_start_skipped, server_is_draining, spawn_task_execute,
+    task_is_living, task_start_lock, StartSkipReason, TaskDefaultParameters, TransferTaskStatus,
+    TransferTaskStatusType, GLOBAL_TASK_RUNTIME,
+};
+use crate::configure::{get_config, DispatchPolicy, TaskConfig};
+use crate::resources::{get_task, CF_TASK_QUEUE, GLOBAL_ROCKSDB};
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rocksdb::IteratorMode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Mutex, MutexGuard, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 排队等待启动的任务，与 CF_TASK_QUEUE 在同一把锁内修改
+static GLOBAL_TASK_QUEUE: Lazy<Mutex<TaskQueue>> = Lazy::new(|| Mutex::new(TaskQueue::default()));
+
+// 执行协程尚未退出的任务，已占用名额但尚未创建执行协程时 run_id 为空
+pub static GLOBAL_EXECUTING_TASKS: Lazy<DashMap<String, ExecutingTask>> = Lazy::new(DashMap::new);
+
+// 管理接口设置的并发上限，优先于配置
+static MAX_CONCURRENT_TASKS_OVERRIDE: Lazy<RwLock<Option<usize>>> = Lazy::new(|| RwLock::new(None));
+
+/// 队列中的任务，seq 为入队顺序，出队顺序由 task.dispatch_policy 决定
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QueuedTask {
+    pub task_id: String,
+    pub seq: u64,
+    pub enqueued_at: u64,
+    // 早期写入的排队记录没有 namespace
+    #[serde(default = "TaskDefaultParameters::namespace_default")]
+    pub namespace: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutingTask {
+    pub run_id: String,
+    pub namespace: String,
+}
+
+/// 单个 namespace 的运行与排队任务数
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NamespaceQueueStats {
+    pub namespace: String,
+    pub running: usize,
+    pub queued: usize,
+    pub weight: u32,
+}
+
+/// 启动请求的结果，超过并发上限时进入队列，position 从 1 开始
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskStartOutcome {
+    Started { run_id: String },
+    Queued { position: usize },
+}
+
+#[derive(Debug, Default)]
+pub struct TaskQueue {
+    entries: VecDeque<QueuedTask>,
+    next_seq: u64,
+    // round_robin_namespace 上次出队的 namespace
+    last_namespace: Option<String>,
+}
+
+impl TaskQueue {
+    pub fn from_entries(mut entries: Vec<QueuedTask>) -> Self {
+        entries.sort_by_key(|e| e.seq);
+        let next_seq = entries.last().map(|e| e.seq + 1).unwrap_or(0);
+        Self {
+            entries: entries.into(),
+            next_seq,
+            last_namespace: None,
+        }
+    }
+
+    /// 加入队尾，已在队列中时返回 None
+    pub fn push(&mut self, task_id: &str, namespace: &str, enqueued_at: u64) -> Option<QueuedTask> {
+        if self.position(task_id).is_some() {
+            return None;
+        }
+        let entry = QueuedTask {
+            task_id: task_id.to_string(),
+            seq: self.next_seq,
+            enqueued_at,
+            namespace: namespace.to_string(),
+        };
+        self.next_seq += 1;
+        self.entries.push_back(entry.clone());
+        Some(entry)
+    }
+
+    pub fn remove(&mut self, task_id: &str) -> Option<QueuedTask> {
+        let index = self.entries.iter().position(|e| e.task_id == task_id)?;
+        self.entries.remove(index)
+    }
+
+    pub fn pop_front(&mut self) -> Option<QueuedTask> {
+        self.entries.pop_front()
+    }
+
+    /// 按调度策略取出下一个任务，running 为各 namespace 执行中的任务数
+    /// 同一 namespace 内始终按入队顺序
+    pub fn pop_next(
+        &mut self,
+        policy: DispatchPolicy,
+        running: &BTreeMap<String, usize>,
+        config: &TaskConfig,
+    ) -> Option<QueuedTask> {
+        let namespace = match policy {
+            DispatchPolicy::Fifo => return self.entries.pop_front(),
+            DispatchPolicy::RoundRobinNamespace => self.next_round_robin_namespace()?,
+            DispatchPolicy::Weighted => self.next_weighted_namespace(running, config)?,
+        };
+        let index = self.entries.iter().position(|e| e.namespace == namespace)?;
+        self.last_namespace = Some(namespace);
+        self.entries.remove(index)
+    }
+
+    fn queued_namespaces(&self) -> BTreeSet<&str> {
+        self.entries.iter().map(|e| e.namespace.as_str()).collect()
+    }
+
+    // 按名称顺序取上次出队之后的下一个 namespace，到末尾后从头开始
+    fn next_round_robin_namespace(&self) -> Option<String> {
+        let namespaces = self.queued_namespaces();
+        let next = match &self.last_namespace {
+            Some(last) => namespaces.iter().find(|n| **n > last.as_str()),
+            None => None,
+        };
+        next.or(namespaces.iter().next()).map(|n| n.to_string())
+    }
+
+    // 运行数与权重之比最小的 namespace，相同时取队首任务入队较早的
+    fn next_weighted_namespace(
+        &self,
+        running: &BTreeMap<String, usize>,
+        config: &TaskConfig,
+    ) -> Option<String> {
+        let mut best: Option<(&str, u64, u64)> = None;
+        let mut seen = BTreeSet::new();
+        for entry in self.entries.iter() {
+            let namespace = entry.namespace.as_str();
+            if !seen.insert(namespace) {
+                continue;
+            }
+            let running = *running.get(namespace).unwrap_or(&0) as u64;
+            let weight = config.namespace_weight(namespace).max(1) as u64;
+            // entries 按 seq 排列，先遇到的 namespace 队首入队较早，比值相同时保留
+            let better = match best {
+                None => true,
+                Some((_, r, w)) => running * w < r * weight,
+            };
+            if better {
+                best = Some((namespace, running, weight));
+            }
+        }
+        best.map(|(n, _, _)| n.to_string())
+    }
+
+    /// 在队列中的位置，从 1 开始
+    pub fn position(&self, task_id: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.task_id == task_id)
+            .map(|i| i + 1)
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<&QueuedTask> {
+        self.entries.iter().find(|e| e.task_id == task_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> Vec<QueuedTask> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+fn lock_queue() -> MutexGuard<'static, TaskQueue> {
+    match GLOBAL_TASK_QUEUE.lock() {
+        Ok(q) => q,
+        Err(e) => e.into_inner(),
+    }
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+// seq 定长补零，按 key 遍历即为入队顺序
+fn task_queue_key(seq: u64) -> String {
+    format!("{:020}", seq)
+}
+
+fn put_queued_task(entry: &QueuedTask) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_QUEUE) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    db.put_cf(&cf, task_queue_key(entry.seq), serde_json::to_vec(entry)?)?;
+    Ok(())
+}
+
+fn delete_queued_task(seq: u64) -> Result<()> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_QUEUE) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    db.delete_cf(&cf, task_queue_key(seq))?;
+    Ok(())
+}
+
+/// 服务启动时从 CF_TASK_QUEUE 恢复队列，返回排队任务数
+pub fn load_task_queue() -> Result<usize> {
+    let db = GLOBAL_ROCKSDB.get()?;
+    let cf = match db.cf_handle(CF_TASK_QUEUE) {
+        Some(cf) => cf,
+        None => return Err(anyhow!("column family not exist")),
+    };
+    let mut entries = vec![];
+    for item in db.iterator_cf(&cf, IteratorMode::Start) {
+        let (_, v) = item?;
+        entries.push(serde_json::from_slice::<QueuedTask>(&v)?);
+    }
+    let mut queue = lock_queue();
+    *queue = TaskQueue::from_entries(entries);
+    Ok(queue.len())
+}
+
+/// 生效的并发上限，0 表示不限制
+pub fn max_concurrent_tasks() -> usize {
+    if let Ok(limit) = MAX_CONCURRENT_TASKS_OVERRIDE.read() {
+        if let Some(limit) = *limit {
+            return limit;
+        }
+    }
+    match get_config() {
+        Ok(c) => c.task.max_concurrent_tasks,
+        Err(_) => 0,
+    }
+}
+
+/// 运行时调整并发上限，上限提高时立即启动排队任务
+pub fn set_max_concurrent_tasks(limit: usize) -> Result<()> {
+    if let Ok(mut current) = MAX_CONCURRENT_TASKS_OVERRIDE.write() {
+        *current = Some(limit);
+    }
+    GLOBAL_TASK_RUNTIME.spawn(schedule_queued_tasks())?;
+    Ok(())
+}
+
+pub fn executing_task_count() -> usize {
+    GLOBAL_EXECUTING_TASKS.len()
+}
+
+fn slot_available(limit: usize, executing: usize) -> bool {
+    limit == 0 || executing < limit
+}
+
+// 占用名额但尚未创建执行协程的任务
+fn reserve_executing(task_id: &str, namespace: &str) {
+    GLOBAL_EXECUTING_TASKS
+        .entry(task_id.to_string())
+        .or_insert_with(|| ExecutingTask {
+            run_id: "".to_string(),
+            namespace: namespace.to_string(),
+        });
+}
+
+/// 队列为空且未达到并发上限时为任务占用一个名额，排队中的任务优先
+pub fn try_reserve_task_slot(task_id: &str, namespace: &str) -> bool {
+    let queue = lock_queue();
+    if !queue.is_empty() || !slot_available(max_concurrent_tasks(), executing_task_count()) {
+        return false;
+    }
+    reserve_executing(task_id, namespace);
+    true
+}
+
+/// 启动准备失败时释放已占用但未创建执行协程的名额
+pub fn release_task_slot(task_id: &str) {
+    GLOBAL_EXECUTING_TASKS.remove_if(task_id, |_, t| t.run_id.is_empty());
+}
+
+/// 执行协程尚未退出或已占用名额的任务，比对任务不登记活动状态，以此判断是否在运行
+pub fn task_is_executing(task_id: &str) -> bool {
+    GLOBAL_EXECUTING_TASKS.contains_key(task_id)
+}
+
+pub fn mark_task_executing(task_id: &str, run_id: &str, namespace: &str) {
+    GLOBAL_EXECUTING_TASKS.insert(
+        task_id.to_string(),
+        ExecutingTask {
+            run_id: run_id.to_string(),
+            namespace: namespace.to_string(),
+        },
+    );
+}
+
+/// 执行协程退出后释放名额，紧接着启动的新一次运行已登记时不释放
+pub fn finish_task_executing(task_id: &str, run_id: &str) {
+    GLOBAL_EXECUTING_TASKS.remove_if(task_id, |_, t| t.run_id == run_id);
+}
+
+// 各 namespace 执行中的任务数
+fn running_by_namespace() -> BTreeMap<String, usize> {
+    let mut running = BTreeMap::new();
+    for t in GLOBAL_EXECUTING_TASKS.iter() {
+        *running.entry(t.value().namespace.clone()).or_insert(0) += 1;
+    }
+    running
+}
+
+/// 加入队尾并写入 CF_TASK_QUEUE，返回按入队顺序的位置
+pub fn enqueue_task(task_id: &str, namespace: &str) -> Result<usize> {
+    let mut queue = lock_queue();
+    if let Some(entry) = queue.push(task_id, namespace, now_secs()) {
+        if let Err(e) = put_queued_task(&entry) {
+            queue.remove(task_id);
+            return Err(e);
+        }
+    }
+    match queue.position(task_id) {
+        Some(p) => Ok(p),
+        None => Err(anyhow!("task {} not queued", task_id)),
+    }
+}
+
+/// 从队列中移除，任务不在队列中时返回 false
+pub fn dequeue_task(task_id: &str) -> Result<bool> {
+    let mut queue = lock_queue();
+    match queue.remove(task_id) {
+        Some(entry) => {
+            delete_queued_task(entry.seq)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub fn task_queue_position(task_id: &str) -> Option<usize> {
+    lock_queue().position(task_id)
+}
+
+pub fn queued_tasks() -> Vec<QueuedTask> {
+    lock_queue().entries()
+}
+
+/// 排队中任务的状态，start_time 为入队时间
+pub fn queued_task_status(task_id: &str) -> Option<TransferTaskStatus> {
+    let queue = lock_queue();
+    let entry = queue.get(task_id)?;
+    Some(TransferTaskStatus {
+        task_id: task_id.to_string(),
+        start_time: entry.enqueued_at,
+        run_id: "".to_string(),
+        status: TransferTaskStatusType::Queued,
+    })
+}
+
+/// 排队任务的调度策略及各 namespace 的运行与排队任务数，按 namespace 排序
+pub fn namespace_queue_stats() -> (DispatchPolicy, Vec<NamespaceQueueStats>) {
+    let config = get_config().map(|c| c.task).unwrap_or_default();
+    let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (namespace, running) in running_by_namespace() {
+        counts.entry(namespace).or_default().0 = running;
+    }
+    for entry in lock_queue().entries.iter() {
+        counts.entry(entry.namespace.clone()).or_default().1 += 1;
+    }
+    let stats = counts
+        .into_iter()
+        .map(|(namespace, (running, queued))| NamespaceQueueStats {
+            weight: config.namespace_weight(&namespace),
+            namespace,
+            running,
+            queued,
+        })
+        .collect();
+    (config.dispatch_policy, stats)
+}
+
+// 未停机且未达到并发上限时按调度策略取出任务并占用名额
+fn reserve_queued_task() -> Option<QueuedTask> {
+    let mut queue = lock_queue();
+    if server_is_draining() || !slot_available(max_concurrent_tasks(), executing_task_count()) {
+        return None;
+    }
+    let config = get_config().map(|c| c.task).unwrap_or_default();
+    let entry = queue.pop_next(config.dispatch_policy, &running_by_namespace(), &config)?;
+    if let Err(e) = delete_queued_task(entry.seq) {
+        log::error!("remove queued task {} error: {}", entry.task_id, e);
+    }
+    reserve_executing(&entry.task_id, &entry.namespace);
+    Some(entry)
+}
+
+/// 按调度策略启动排队任务，直到队列为空或达到并发上限
+pub async fn schedule_queued_tasks() {
+    while let Some(entry) = reserve_queued_task() {
+        match launch_queued_task(&entry.task_id).await {
+            Ok(run_id) => log::info!("queued task {} started, run {}", entry.task_id, run_id),
+            Err(e) => {
+                log::warn!("queued task {} not started: {}", entry.task_id, e);
+                release_task_slot(&entry.task_id);
+            }
+        }
+    }
+}
+
+async fn launch_queued_task(task_id: &str) -> Result<String> {
+    let task = get_task(task_id)?;
+    let lock = task_start_lock(task_id);
+    let _guard = lock.lock().await;
+    if task_is_living(task_id) {
+        record_start_skipped(task_id, StartSkipReason::AlreadyLiving);
+        return Err(anyhow!("task {} is living", task_id));
+    }
+    if let Err(e) = task.setup().await {
+        record_start_skipped(task_id, StartSkipReason::SetupFailed(e.to_string()));
+        return Err(e.into());
+    }
+    clear_start_skipped(task_id);
+    spawn_task_execute(task)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{slot_available, QueuedTask, TaskQueue};
+    use crate::configure::{DispatchPolicy, NamespaceQuota, TaskConfig};
+    use std::collections::BTreeMap;
+
+    fn synthetic_queue(tasks: &[(&str, &str)]) -> TaskQueue {
+        let mut queue = TaskQueue::default();
+        for (task_id, namespace) in tasks {
+            queue.push(task_id, namespace, 0);
+        }
+        queue
+    }
+
+    // 依次出队，每个出队的任务计入其 namespace 的运行数
+    fn dispatch_order(
+        mut queue: TaskQueue,
+        policy: DispatchPolicy,
+        config: &TaskConfig,
+    ) -> Vec<String> {
+        let mut running = BTreeMap::new();
+        let mut order = vec![];
+        while let Some(entry) = queue.pop_next(policy, &running, config) {
+            *running.entry(entry.namespace.clone()).or_insert(0) += 1;
+            order.push(entry.task_id);
+        }
+        order
+    }
+
+    //cargo test tasks::task_queue::test::test_task_queue -- --nocapture
+    #[test]
+    fn test_task_queue() {
+        let mut queue = TaskQueue::default();
+        assert_eq!(queue.push("a", "default", 1).unwrap().seq, 0);
+        assert_eq!(queue.push("b", "default", 2).unwrap().seq, 1);
+        assert!(queue.push("a", "default", 3).is_none());
+        assert_eq!(queue.push("c", "default", 4).unwrap().seq, 2);
+        assert_eq!(queue.position("a"), Some(1));
+        assert_eq!(queue.position("c"), Some(3));
+
+        // 移除后之后的任务前移
+        assert_eq!(queue.remove("b").unwrap().task_id, "b");
+        assert_eq!(queue.position("c"), Some(2));
+        assert!(queue.remove("b").is_none());
+        assert_eq!(queue.pop_front().unwrap().task_id, "a");
+        assert_eq!(queue.position("c"), Some(1));
+        assert_eq!(queue.len(), 1);
+
+        // 恢复时按 seq 排序，新入队的 seq 接续最大值
+        let entries = vec![
+            QueuedTask {
+                task_id: "y".to_string(),
+                seq: 7,
+                enqueued_at: 2,
+                namespace: "default".to_string(),
+            },
+            QueuedTask {
+                task_id: "x".to_string(),
+                seq: 3,
+                enqueued_at: 1,
+                namespace: "default".to_string(),
+            },
+        ];
+        let mut restored = TaskQueue::from_entries(entries);
+        assert_eq!(restored.position("x"), Some(1));
+        assert_eq!(restored.push("z", "default", 3).unwrap().seq, 8);
+    }
+
+    //cargo test tasks::task_queue::test::test_slot_available -- --nocapture
+    #[test]
+    fn test_slot_available() {
+        assert!(slot_available(0, 100));
+        assert!(slot_available(2, 1));
+        assert!(!slot_available(2, 2));
+    }
+
+    //cargo test tasks::task_queue::test::test_dispatch_policy -- --nocapture
+    #[test]
+    fn test_dispatch_policy() {
+        let tasks = [
+            ("a1", "a"),
+            ("a2", "a"),
+            ("a3", "a"),
+            ("b1", "b"),
+            ("c1", "c"),
+            ("b2", "b"),
+        ];
+        let config = TaskConfig::default();
+
+        let order = dispatch_order(synthetic_queue(&tasks), DispatchPolicy::Fifo, &config);
+        assert_eq!(order, vec!["a1", "a2", "a3", "b1", "c1", "b2"]);
+
+        // 在有排队任务的 namespace 间轮流，namespace 内按入队顺序
+        let order = dispatch_order(
+            synthetic_queue(&tasks),
+            DispatchPolicy::RoundRobinNamespace,
+            &config,
+        );
+        assert_eq!(order, vec!["a1", "b1", "c1", "a2", "b2", "a3"]);
+
+        // a 的权重为 3，运行数与权重之比相同时取入队较早的
+        let mut config = TaskConfig::default();
+        config
+            .namespace_quotas
+            .insert("a".to_string(), NamespaceQuota { weight: 3 });
+        let tasks = [
+            ("a1", "a"),
+            ("a2", "a"),
+            ("a3", "a"),
+            ("a4", "a"),
+            ("b1", "b"),
+            ("b2", "b"),
+            ("b3", "b"),
+        ];
+        let order = dispatch_order(synthetic_queue(&tasks), DispatchPolicy::Weighted, &config);
+        assert_eq!(order, vec!["a1", "b1", "a2", "a3", "a4", "b2", "b3"]);
+
+        // 已有运行中的任务时优先运行数少的 namespace
+        let mut queue = synthetic_queue(&[("a1", "a"), ("b1", "b")]);
+        let running = BTreeMap::from([("a".to_string(), 4)]);
+        let config = TaskConfig::default();
+        let entry = queue
+            .pop_next(DispatchPolicy::Weighted, &running, &config)
+            .unwrap();
+        assert_eq!(entry.task_id, "b1");
+    }
+
+    //cargo test tasks::task_queue::test::test_queued_task_namespace_default -- --nocapture
+    #[test]
+    fn test_queued_task_namespace_default() {
+        let entry: QueuedTask =
+            serde_json::from_str(r#"{"task_id":"t","seq":1,"enqueued_at":2}"#).unwrap();
+        assert_eq!(entry.namespace, "default");
+    }
+}
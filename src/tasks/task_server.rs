@@ -1,25 +1,73 @@
-use super::TransferTaskStatus;
+use super::{
+    cancel_task, clear_task_batch_progress, finish_task_executing, mark_task_executing,
+    prune_task_runs, record_task_run_start, register_task_cancellation, run_final_state,
+    save_task_run, schedule_queued_tasks, snapshot_batch_progress, task_cancellation_token,
+    task_is_paused, unregister_task_cancellation, CompareStatus, StartSkipReason, Status, Task,
+    TaskFailure, TaskRun, TaskSkipRecord, TaskStatus, TaskStopReason, TaskType, TransferStatus,
+    TransferTaskStatusType, GLOBAL_TASK_CANCEL_TOKEN_MAP,
+};
+use super::{publish_task_state, TransferTaskStatus};
+use crate::commons::inherit_request_context;
+use crate::configure::get_config;
 use crate::resources::get_checkpoint;
+use crate::resources::get_task;
+use crate::resources::get_task_status;
 use crate::resources::living_tasks;
 use crate::resources::CF_TASK_STATUS;
 use crate::resources::GLOBAL_ROCKSDB;
-use crate::tasks::FilePosition;
+use crate::server::{
+    build_runtime, mark_task_run_start, notify_error_rates, notify_task_state,
+    record_checkpoint_snapshot, task_run_counters, RuntimeThreads,
+};
+use crate::tasks::{CheckPoint, FilePosition};
 use anyhow::anyhow;
 use anyhow::Result;
 use dashmap::DashMap;
-use once_cell::sync::Lazy;
-use std::sync::{atomic::AtomicBool, Arc};
-use tokio::runtime;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64},
+    Arc,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::runtime::Runtime;
+use tokio::task::{JoinError, JoinHandle};
 use tokio::{sync::RwLock, task::JoinSet};
 
-pub static GLOBAL_TASK_RUNTIME: Lazy<Arc<Runtime>> = Lazy::new(|| {
-    let rocksdb = match init_task_runtime() {
-        Ok(db) => db,
-        Err(err) => panic!("{}", err),
-    };
-    Arc::new(rocksdb)
-});
+// 由 init_global_task_runtime 创建，之后不再变化
+static TASK_RUNTIME: OnceCell<Arc<Runtime>> = OnceCell::new();
+
+/// 全局任务 runtime，start 流程调用 init_global_task_runtime 创建，未创建时访问返回错误
+pub struct GlobalTaskRuntime(&'static OnceCell<Arc<Runtime>>);
+
+impl GlobalTaskRuntime {
+    pub fn get(&self) -> Result<&'static Arc<Runtime>> {
+        self.0
+            .get()
+            .ok_or_else(|| anyhow!("task runtime not initialized"))
+    }
+
+    pub fn spawn<F>(&self, future: F) -> Result<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        Ok(self.get()?.spawn(future))
+    }
+}
+
+pub static GLOBAL_TASK_RUNTIME: GlobalTaskRuntime = GlobalTaskRuntime(&TASK_RUNTIME);
+
+/// 按配置创建全局任务 runtime，已创建时直接返回
+pub fn init_global_task_runtime() -> Result<()> {
+    TASK_RUNTIME.get_or_try_init(|| match init_task_runtime() {
+        Ok(rt) => Ok(Arc::new(rt)),
+        Err(e) => Err(anyhow!("cannot build task runtime: {}", e)),
+    })?;
+    Ok(())
+}
 
 pub static GLOBAL_TASK_JOINSET: Lazy<Arc<RwLock<JoinSet<()>>>> = Lazy::new(|| {
     let joinset = init_global_joinset();
@@ -62,19 +110,52 @@ pub static GLOBAL_LIVING_TRANSFER_TASK_MAP: Lazy<Arc<DashMap<String, TransferTas
 //         Arc::new(map)
 //     });
 
+// 各任务最近一次启动被拒绝的原因
+pub static GLOBAL_TASK_SKIP_REASON_MAP: Lazy<Arc<DashMap<String, TaskSkipRecord>>> =
+    Lazy::new(|| {
+        let map = DashMap::<String, TaskSkipRecord>::new();
+        Arc::new(map)
+    });
+
+// 任务启动锁，串行化同一任务的存活检查、启动准备与执行协程的创建
+pub static GLOBAL_TASK_START_LOCKS: Lazy<Arc<DashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Arc::new(DashMap::new()));
+
+// 任务 checkpoint 写入锁，周期快照与按需快照串行，避免交错写入执行位置
+pub static GLOBAL_TASK_CHECKPOINT_LOCKS: Lazy<Arc<DashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Arc::new(DashMap::new()));
+
+// TasksStatusSaver 最近一轮执行的 unix 时间戳，用于存活检查
+pub static GLOBAL_STATUS_SAVER_HEARTBEAT: Lazy<Arc<AtomicU64>> =
+    Lazy::new(|| Arc::new(AtomicU64::new(0)));
+
+// 服务停机标识，置位后拒绝启动新任务
+pub static GLOBAL_SERVER_DRAINING: Lazy<Arc<AtomicBool>> =
+    Lazy::new(|| Arc::new(AtomicBool::new(false)));
+
 pub static GLOBAL_LIST_FILE_POSITON_MAP: Lazy<Arc<DashMap<String, FilePosition>>> =
     Lazy::new(|| {
         let map = DashMap::<String, FilePosition>::new();
         Arc::new(map)
     });
 
+// 各任务执行中批次的列表文件位置，由任务启动时注册，重复启动时替换
+pub static GLOBAL_TASK_OFFSET_MAP: Lazy<DashMap<String, Arc<DashMap<String, FilePosition>>>> =
+    Lazy::new(|| {
+        let map: DashMap<String, Arc<DashMap<String, FilePosition>>> = DashMap::new();
+        map
+    });
+
+// 首次访问时读取配置，需在加载配置文件之后访问
 fn init_task_runtime() -> Result<Runtime> {
-    let rt = runtime::Builder::new_multi_thread()
-        .worker_threads(num_cpus::get())
-        .enable_all()
-        .max_io_events_per_tick(32)
-        .build()?;
-    Ok(rt)
+    let task_runtime = get_config()?.task_runtime;
+    build_runtime(
+        "task",
+        RuntimeThreads::resolve(
+            task_runtime.worker_threads,
+            task_runtime.max_io_events_per_tick,
+        ),
+    )
 }
 
 fn init_global_joinset() -> JoinSet<()> {
@@ -89,6 +170,10 @@ pub struct TasksStatusSaver {
 impl TasksStatusSaver {
     pub async fn run(&self) {
         loop {
+            if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+                GLOBAL_STATUS_SAVER_HEARTBEAT
+                    .store(now.as_secs(), std::sync::atomic::Ordering::SeqCst);
+            }
             //Todo 改造成函数或同步线程
             // for kv in GLOBAL_LIVING_TRANSFER_TASK_MAP.iter() {
             //     // 获取最小offset的FilePosition
@@ -124,31 +209,214 @@ impl TasksStatusSaver {
             //     };
             // }
 
-            if let Err(e) = snapshot_living_tasks_checkpoints_to_cf().await {
+            let snapshot_start = Instant::now();
+            let snapshot = snapshot_living_tasks_checkpoints_to_cf().await;
+            record_checkpoint_snapshot(snapshot_start.elapsed(), snapshot.is_ok());
+            if let Err(e) = snapshot {
                 log::error!("{}", e);
             };
-            tokio::time::sleep(tokio::time::Duration::from_secs(self.interval)).await;
+            notify_error_rates();
+            // 每轮读取配置，使重载后的间隔生效
+            let interval = match get_config() {
+                Ok(c) => c.task.checkpoint_interval,
+                Err(_) => self.interval,
+            };
+            tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
         }
     }
 }
 
 pub async fn init_tasks_status_server() {
-    let server = TasksStatusSaver { interval: 10 };
+    let interval = match get_config() {
+        Ok(c) => c.task.checkpoint_interval,
+        Err(_) => 10,
+    };
+    let server = TasksStatusSaver { interval };
     server.run().await
 }
 
-pub fn save_task_status(task_id: &str, task_status: TransferTaskStatus) {
+/// 距 TasksStatusSaver 上一轮执行的时长，尚未运行时返回 None
+pub fn status_saver_heartbeat_age() -> Option<Duration> {
+    let last = GLOBAL_STATUS_SAVER_HEARTBEAT.load(std::sync::atomic::Ordering::SeqCst);
+    if last == 0 {
+        return None;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(now.saturating_sub(last)))
+}
+
+pub fn save_task_status(task_id: &str, mut task_status: TransferTaskStatus) {
+    // 启动阶段即被暂停的任务，进入运行阶段时保持暂停状态
+    if let TransferTaskStatusType::Running(stage) = task_status.status {
+        if task_is_paused(task_id) {
+            task_status.status = TransferTaskStatusType::Paused(stage);
+        }
+    }
+    persist_transfer_status(task_id, &task_status);
+    publish_task_state(task_id, &task_status.status);
+    notify_task_state(task_id, task_status.start_time, &task_status.status);
     GLOBAL_LIVING_TRANSFER_TASK_MAP.insert(task_id.to_string(), task_status);
 }
 
+/// 按暂停标识切换内存中的运行状态，持久化状态不变
+pub fn mark_living_task_paused(task_id: &str, paused: bool) {
+    if let Some(mut kv) = GLOBAL_LIVING_TRANSFER_TASK_MAP.get_mut(task_id) {
+        kv.status = match (&kv.status, paused) {
+            (TransferTaskStatusType::Running(stage), true) => {
+                TransferTaskStatusType::Paused(*stage)
+            }
+            (TransferTaskStatusType::Paused(stage), false) => {
+                TransferTaskStatusType::Running(*stage)
+            }
+            (status, _) => status.clone(),
+        };
+    }
+}
+
 pub fn log_out_living_task(task_id: &str) {
+    if let Some((_, mut status)) = GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id) {
+        if !status.status.is_stopped() {
+            let finish = TransferTaskStatusType::Stopped(TaskStopReason::Finish);
+            status.status = finish.clone();
+            persist_transfer_status(task_id, &status);
+            publish_task_state(task_id, &finish);
+            notify_task_state(task_id, status.start_time, &finish);
+        }
+    }
+}
+
+/// 存量任务执行完成：最终 checkpoint 与停止状态同批写入后登出活动任务
+/// 任务已停止或未登记时只保存 checkpoint
+pub fn finish_living_task(task_id: &str, checkpoint: &mut CheckPoint) -> Result<()> {
+    let mut status = match GLOBAL_LIVING_TRANSFER_TASK_MAP
+        .get(task_id)
+        .map(|kv| kv.value().clone())
+    {
+        Some(s) if !s.status.is_stopped() => s,
+        _ => {
+            checkpoint.save_to_rocksdb_cf()?;
+            log_out_living_task(task_id);
+            return Ok(());
+        }
+    };
+    let finish = TransferTaskStatusType::Stopped(TaskStopReason::Finish);
+    status.status = finish.clone();
+    crate::resources::save_task_finalization(
+        checkpoint,
+        &transfer_status_record(task_id, &status),
+    )?;
     GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+    publish_task_state(task_id, &finish);
+    notify_task_state(task_id, status.start_time, &finish);
+    Ok(())
+}
+
+pub fn transfer_status_of(status: &TransferTaskStatusType) -> TransferStatus {
+    match status {
+        // 排队状态保存在 CF_TASK_QUEUE，不写入任务状态
+        TransferTaskStatusType::Queued | TransferTaskStatusType::Starting => {
+            TransferStatus::Starting
+        }
+        TransferTaskStatusType::Running(stage) | TransferTaskStatusType::Paused(stage) => {
+            TransferStatus::Running(*stage)
+        }
+        TransferTaskStatusType::Stopped(reason) => TransferStatus::Stopped(reason.clone()),
+    }
+}
+
+// 写入 CF_TASK_STATUS 的状态记录，保留已记录的跳过原因
+fn transfer_status_record(task_id: &str, transfer: &TransferTaskStatus) -> TaskStatus {
+    let transfer_status = transfer_status_of(&transfer.status);
+    let (last_skip_reason, last_run_id) = match get_task_status(task_id) {
+        Ok(s) => (s.last_skip_reason, s.run_id),
+        // 首次写入时状态不存在，损坏或读取失败时记录后覆盖
+        Err(e) => {
+            if !e.is_not_found() {
+                log::warn!("{}", e);
+            }
+            (None, None)
+        }
+    };
+    // 未携带 run_id 的状态沿用已记录的 run_id
+    let run_id = match transfer.run_id.is_empty() {
+        true => last_run_id,
+        false => Some(transfer.run_id.clone()),
+    };
+    TaskStatus {
+        task_id: task_id.to_string(),
+        start_time: transfer.start_time,
+        status: Status::Transfer(transfer_status),
+        last_skip_reason,
+        run_id,
+    }
+}
+
+/// 任务状态同步写入 CF_TASK_STATUS，服务异常退出后据此判断需要恢复的任务
+fn persist_transfer_status(task_id: &str, transfer: &TransferTaskStatus) {
+    let mut task_status = transfer_status_record(task_id, transfer);
+    if let Err(e) = crate::resources::save_task_status(&mut task_status) {
+        log::error!("{}", e);
+    }
+}
+
+fn mark_task_broken(status: &TaskStatus) {
+    let mut broken = status.clone();
+    broken.status = match status.status_type() {
+        TaskType::Compare => Status::Compare(CompareStatus::Stopped),
+        _ => Status::Transfer(TransferStatus::Stopped(TaskStopReason::Broken)),
+    };
+    if let Err(e) = crate::resources::save_task_status(&mut broken) {
+        log::error!("{}", e);
+    }
+}
+
+/// 服务启动时恢复异常中断的任务，返回恢复与跳过的任务数
+/// 任务定义或 checkpoint 缺失的任务标记为异常停止，不阻塞启动
+pub fn resume_interrupted_tasks() -> Result<(usize, usize)> {
+    let mut resumed = 0;
+    let mut skipped = 0;
+    for status in living_tasks()? {
+        let task_id = status.task_id.clone();
+        let mut task = match get_task(&task_id) {
+            Ok(t) => t,
+            Err(e) => {
+                log::warn!("task {} not resumed, load task error: {}", task_id, e);
+                mark_task_broken(&status);
+                skipped += 1;
+                continue;
+            }
+        };
+        match &mut task {
+            Task::Transfer(transfer) => {
+                if let Err(e) = get_checkpoint(&task_id) {
+                    log::warn!("task {} not resumed, load checkpoint error: {}", task_id, e);
+                    mark_task_broken(&status);
+                    skipped += 1;
+                    continue;
+                }
+                transfer.attributes.start_from_checkpoint = true;
+            }
+            Task::Compare(_) => {
+                log::warn!(
+                    "task {} not resumed, compare task not support resume",
+                    task_id
+                );
+                mark_task_broken(&status);
+                skipped += 1;
+                continue;
+            }
+        }
+        let run_id = spawn_task_execute(task)?;
+        log::info!("resume task {} from checkpoint, run {}", task_id, run_id);
+        resumed += 1;
+    }
+    Ok((resumed, skipped))
 }
 
 pub fn task_is_living(task_id: &str) -> bool {
     return match GLOBAL_LIVING_TRANSFER_TASK_MAP.get(task_id) {
         Some(ts) => match ts.status {
-            super::TransferTaskStatusType::Stopped(_) => false,
+            TransferTaskStatusType::Stopped(_) => false,
             _ => true,
         },
         None => false,
@@ -177,39 +445,469 @@ pub fn remove_exec_joinset(task_id: &str) {
     GLOBAL_TASKS_EXEC_JOINSET.remove(task_id);
 }
 
+pub fn set_server_draining() {
+    GLOBAL_SERVER_DRAINING.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+pub fn server_is_draining() -> bool {
+    GLOBAL_SERVER_DRAINING.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// 记录任务启动被拒绝的原因，同时写入任务状态记录
+pub fn record_start_skipped(task_id: &str, reason: StartSkipReason) {
+    let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    let record = TaskSkipRecord { reason, timestamp };
+    log::info!("task {} start skipped: {}", task_id, record.reason);
+    GLOBAL_TASK_SKIP_REASON_MAP.insert(task_id.to_string(), record.clone());
+    if let Ok(mut status) = get_task_status(task_id) {
+        status.last_skip_reason = Some(record);
+        if let Err(e) = crate::resources::save_task_status(&mut status) {
+            log::error!("{}", e);
+        }
+    }
+}
+
+pub fn clear_start_skipped(task_id: &str) {
+    if GLOBAL_TASK_SKIP_REASON_MAP.remove(task_id).is_none() {
+        return;
+    }
+    if let Ok(mut status) = get_task_status(task_id) {
+        status.last_skip_reason = None;
+        if let Err(e) = crate::resources::save_task_status(&mut status) {
+            log::error!("{}", e);
+        }
+    }
+}
+
+pub fn get_start_skipped(task_id: &str) -> Option<TaskSkipRecord> {
+    if let Some(kv) = GLOBAL_TASK_SKIP_REASON_MAP.get(task_id) {
+        return Some(kv.value().clone());
+    }
+    match get_task_status(task_id) {
+        Ok(status) => status.last_skip_reason,
+        Err(_) => None,
+    }
+}
+
+/// 通知所有任务停止
+pub fn stop_all_tasks() {
+    for kv in GLOBAL_TASK_STOP_MARK_MAP.iter() {
+        kv.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+    for kv in GLOBAL_TASK_CANCEL_TOKEN_MAP.iter() {
+        kv.cancel();
+    }
+}
+
+/// 在宽限期内等待全部任务退出，超时返回错误
+pub async fn drain_tasks(grace: Duration) -> Result<()> {
+    let drain = async {
+        while GLOBAL_TASK_JOINSET
+            .write()
+            .await
+            .join_next()
+            .await
+            .is_some()
+        {}
+        // 任务执行结束后会注销执行 joinset，注册表为空即全部任务已退出
+        while !GLOBAL_TASKS_EXEC_JOINSET.is_empty() {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    };
+    match tokio::time::timeout(grace, drain).await {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!(
+            "{} tasks still running after {:?}",
+            GLOBAL_TASKS_EXEC_JOINSET.len(),
+            grace
+        )),
+    }
+}
+
+pub fn register_task_offset_map(task_id: &str, offset_map: Arc<DashMap<String, FilePosition>>) {
+    GLOBAL_TASK_OFFSET_MAP.insert(task_id.to_string(), offset_map);
+    clear_task_batch_progress(task_id);
+}
+
+/// 执行中批次的最小列表文件位置，即可安全续传的位置；无执行中批次时返回 None
+pub fn task_min_file_position(task_id: &str) -> Option<FilePosition> {
+    let offset_map = GLOBAL_TASK_OFFSET_MAP
+        .get(task_id)
+        .map(|kv| kv.value().clone());
+    match offset_map {
+        Some(m) => m
+            .iter()
+            .map(|kv| kv.value().clone())
+            .min_by_key(|p| p.offset),
+        None => GLOBAL_LIST_FILE_POSITON_MAP
+            .iter()
+            .filter(|item| item.key().starts_with(task_id))
+            .map(|kv| kv.value().clone())
+            .min_by_key(|p| p.offset),
+    }
+}
+
+/// 任务删除后清除其在内存中的运行记录，需在任务停止后调用
+pub fn forget_task_state(task_id: &str) {
+    GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id);
+    GLOBAL_TASK_SKIP_REASON_MAP.remove(task_id);
+    GLOBAL_TASKS_SYS_JOINSET.remove(task_id);
+    GLOBAL_TASKS_EXEC_JOINSET.remove(task_id);
+    GLOBAL_TASKS_BIGFILE_JOINSET.remove(task_id);
+    unregister_task_cancellation(task_id);
+    GLOBAL_TASK_START_LOCKS.remove(task_id);
+    GLOBAL_TASK_CHECKPOINT_LOCKS.remove(task_id);
+    clear_task_file_positions(task_id);
+    clear_task_batch_progress(task_id);
+}
+
+/// 任务的启动锁，不存在时创建
+pub fn task_start_lock(task_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+    GLOBAL_TASK_START_LOCKS
+        .entry(task_id.to_string())
+        .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+        .clone()
+}
+
+/// 没有其他持有方时移除任务的启动锁，避免映射随任务数增长；调用前须释放自己持有的锁
+/// 移除与创建在同一分片锁内，不会出现两个请求持有不同的锁
+pub fn remove_idle_task_start_lock(task_id: &str) {
+    GLOBAL_TASK_START_LOCKS.remove_if(task_id, |_, lock| Arc::strong_count(lock) == 1);
+}
+
+/// 清除任务的执行位置记录，返回清除的条目数
+pub fn clear_task_file_positions(task_id: &str) -> usize {
+    let keys = GLOBAL_LIST_FILE_POSITON_MAP
+        .iter()
+        .filter(|item| item.key().starts_with(task_id))
+        .map(|item| item.key().clone())
+        .collect::<Vec<String>>();
+    let mut removed = keys
+        .iter()
+        .filter(|k| GLOBAL_LIST_FILE_POSITON_MAP.remove(*k).is_some())
+        .count();
+    if let Some((_, offsets)) = GLOBAL_TASK_OFFSET_MAP.remove(task_id) {
+        removed += offsets.len();
+    }
+    removed
+}
+
+/// 任务在内存中记录的各批次列表文件位置
+pub fn task_file_positions(task_id: &str) -> BTreeMap<String, FilePosition> {
+    GLOBAL_LIST_FILE_POSITON_MAP
+        .iter()
+        .filter(|item| item.key().starts_with(task_id))
+        .map(|item| (item.key().clone(), item.value().clone()))
+        .collect()
+}
+
+/// 以导入的位置替换任务在内存中的列表文件位置
+pub fn restore_task_file_positions(task_id: &str, positions: BTreeMap<String, FilePosition>) {
+    clear_task_file_positions(task_id);
+    for (key, position) in positions {
+        GLOBAL_LIST_FILE_POSITON_MAP.insert(key, position);
+    }
+}
+
+/// 以当前执行位置更新任务 checkpoint，返回写入的位置
+pub fn snapshot_task_checkpoint(task_id: &str) -> Result<FilePosition> {
+    let (_, checkpoint) = snapshot_checkpoint(task_id)?;
+    Ok(checkpoint.executing_file_position)
+}
+
+// 返回写入前的执行位置与写入后的 checkpoint
+fn snapshot_checkpoint(task_id: &str) -> Result<(FilePosition, CheckPoint)> {
+    let mut checkpoint = get_checkpoint(task_id)?;
+    let previous = checkpoint.executing_file_position;
+    if let Some(position) = task_min_file_position(task_id) {
+        checkpoint.executing_file_position = position;
+    }
+    checkpoint.save_to_rocksdb_cf()?;
+    log::debug!("checkpoint:\n{:?}", checkpoint);
+    Ok((previous, checkpoint))
+}
+
+/// 任务的 checkpoint 写入锁，不存在时创建
+pub fn task_checkpoint_lock(task_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+    GLOBAL_TASK_CHECKPOINT_LOCKS
+        .entry(task_id.to_string())
+        .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+        .clone()
+}
+
+/// 一次快照写入的 checkpoint，advanced 为相对上次持久化位置的推进量，位置回退时为负数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointFlush {
+    pub task_id: String,
+    pub checkpoint: CheckPoint,
+    pub previous_position: FilePosition,
+    pub advanced_offset: i64,
+    pub advanced_lines: i64,
+}
+
+/// 持有 checkpoint 写入锁写入任务快照
+pub async fn flush_task_checkpoint(task_id: &str) -> Result<CheckpointFlush> {
+    let lock = task_checkpoint_lock(task_id);
+    let _guard = lock.lock().await;
+    let (previous, checkpoint) = snapshot_checkpoint(task_id)?;
+    let current = checkpoint.executing_file_position;
+    Ok(CheckpointFlush {
+        task_id: task_id.to_string(),
+        advanced_offset: current.offset as i64 - previous.offset as i64,
+        advanced_lines: current.line_num as i64 - previous.line_num as i64,
+        previous_position: previous,
+        checkpoint,
+    })
+}
+
 pub async fn snapshot_living_tasks_checkpoints_to_cf() -> Result<()> {
     for status in living_tasks()? {
-        // 获取最小offset的FilePosition
-        let taskid = status.task_id;
-        let mut checkpoint = match get_checkpoint(&taskid) {
-            Ok(c) => c,
-            Err(e) => {
-                log::error!("{:?}", e);
-                continue;
-            }
-        };
-        let mut file_position = FilePosition {
-            offset: 0,
-            line_num: 0,
-        };
+        if let Err(e) = flush_task_checkpoint(&status.task_id).await {
+            log::error!("{},{}", e, status.task_id);
+        }
+        snapshot_batch_progress(&status.task_id);
+    }
+    GLOBAL_LIST_FILE_POSITON_MAP.shrink_to_fit();
+    Ok(())
+}
 
-        GLOBAL_LIST_FILE_POSITON_MAP
-            .iter()
-            .filter(|item| item.key().starts_with(&taskid))
-            .map(|m| {
-                file_position = m.clone();
-                m.offset
-            })
-            .min();
-
-        GLOBAL_LIST_FILE_POSITON_MAP.shrink_to_fit();
-        checkpoint.executing_file_position = file_position.clone();
-
-        if let Err(e) = checkpoint.save_to_rocksdb_cf() {
-            log::error!("{},{}", e, taskid);
-        } else {
-            log::debug!("checkpoint:\n{:?}", checkpoint);
-        };
+/// 同步写入全部活动任务的 checkpoint，供 panic hook 等无法 await 的场景使用
+pub fn snapshot_living_tasks_checkpoints() -> Result<()> {
+    for status in living_tasks()? {
+        if let Err(e) = snapshot_task_checkpoint(&status.task_id) {
+            log::error!("{},{}", e, status.task_id);
+        }
     }
     Ok(())
 }
+
+/// 子任务 panic，Task::execute 据此将任务置为 Failed(Panicked)
+#[derive(Debug, Clone)]
+pub struct TaskPanicError {
+    pub message: String,
+}
+
+impl std::fmt::Display for TaskPanicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "subtask panicked: {}", self.message)
+    }
+}
+
+impl std::error::Error for TaskPanicError {}
+
+/// panic payload 为 &str 或 String 时返回其内容
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// joinset 中子任务的结束结果，子任务 panic 时返回 TaskPanicError，被取消时视为正常结束
+pub fn check_join_panic(joined: Option<std::result::Result<(), JoinError>>) -> Result<()> {
+    match joined {
+        Some(Err(e)) if e.is_panic() => Err(anyhow::Error::new(TaskPanicError {
+            message: panic_message(e.into_panic().as_ref()),
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// 等待执行 joinset 中一个子任务结束，子任务 panic 时返回 TaskPanicError
+pub async fn join_exec_next(exec_set: &Arc<RwLock<JoinSet<()>>>) -> Result<()> {
+    check_join_panic(exec_set.write().await.join_next().await)
+}
+
+/// 在任务 runtime 中执行任务并返回 run_id，执行协程 panic 时将任务标记为失败，避免任务一直处于活动状态
+/// 传输任务在返回前登记为活动状态并注册取消 token，返回后即可查询与停止
+pub fn spawn_task_execute(task: Task) -> Result<String> {
+    let runtime = GLOBAL_TASK_RUNTIME.get()?;
+    let task_id = task.task_id();
+    let run_id = uuid::Uuid::new_v4().to_string();
+    mark_task_run_start(&task_id);
+    mark_task_executing(&task_id, &run_id, &task.namespace());
+    let start_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    let mut task_run = TaskRun::start(&task_id, &run_id, start_time);
+    if let Err(e) = record_task_run_start(&task_run) {
+        log::warn!("task {} record run {}: {}", task_id, run_id, e);
+    }
+    if let Task::Transfer(_) = task {
+        register_task_cancellation(&task_id);
+        let task_status = TransferTaskStatus {
+            task_id: task_id.clone(),
+            start_time,
+            run_id: run_id.clone(),
+            status: TransferTaskStatusType::Starting,
+        };
+        save_task_status(&task_id, task_status);
+    }
+    let exec_run_id = run_id.clone();
+    let finished_run_id = run_id.clone();
+    // 任务日志继承发起启动的请求的 request id
+    let handle = runtime.spawn(inherit_request_context(async move {
+        task.execute(&exec_run_id).await
+    }));
+    runtime.spawn(inherit_request_context(async move {
+        if let Err(e) = handle.await {
+            if e.is_panic() {
+                mark_task_panicked(&task_id, &panic_message(e.into_panic().as_ref()));
+            }
+        }
+        // 取消标识在 release_task_run 中注销，需先读取
+        finish_task_run_record(&mut task_run);
+        release_task_run(&task_id).await;
+        // 释放名额后启动排队中的任务
+        finish_task_executing(&task_id, &finished_run_id);
+        schedule_queued_tasks().await;
+    }));
+    Ok(run_id)
+}
+
+/// 补充运行记录的结束状态与计数，并按保留策略清理该任务的历史运行
+fn finish_task_run_record(run: &mut TaskRun) {
+    let cancelled = task_cancellation_token(&run.task_id).is_cancelled();
+    let status = get_task_status(&run.task_id).ok();
+    let (state, reason) = run_final_state(status.as_ref(), cancelled);
+    let end_ts = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    run.finish(state, reason, &task_run_counters(&run.task_id), end_ts);
+    if let Err(e) = save_task_run(run) {
+        log::warn!("task {} save run {}: {}", run.task_id, run.run_id, e);
+        return;
+    }
+    let config = get_config().map(|c| c.task).unwrap_or_default();
+    if let Err(e) = prune_task_runs(
+        &run.task_id,
+        end_ts,
+        config.run_retention_days,
+        config.run_retention_count,
+    ) {
+        log::warn!("task {} prune runs: {}", run.task_id, e);
+    }
+}
+
+/// 执行协程退出后释放本次运行的 joinset 与停止标识，避免映射随运行次数增长
+/// 持启动锁释放，避免误删紧接着启动的新一次运行注册的条目
+async fn release_task_run(task_id: &str) {
+    let lock = task_start_lock(task_id);
+    {
+        let _guard = lock.lock().await;
+        if !task_is_living(task_id) {
+            GLOBAL_TASKS_SYS_JOINSET.remove(task_id);
+            GLOBAL_TASKS_EXEC_JOINSET.remove(task_id);
+            GLOBAL_TASKS_BIGFILE_JOINSET.remove(task_id);
+            unregister_task_cancellation(task_id);
+        }
+    }
+    drop(lock);
+    remove_idle_task_start_lock(task_id);
+}
+
+/// 任务已停止且执行协程已退出
+pub fn task_run_exited(task_id: &str) -> bool {
+    !task_is_living(task_id) && !GLOBAL_TASK_STOP_MARK_MAP.contains_key(task_id)
+}
+
+fn mark_task_panicked(task_id: &str, message: &str) {
+    log::error!("task {} panicked: {}", task_id, message);
+    cancel_task(task_id);
+    match GLOBAL_LIVING_TRANSFER_TASK_MAP.remove(task_id) {
+        Some((_, mut status)) => {
+            let panicked = TransferTaskStatusType::Stopped(TaskStopReason::Failed(
+                TaskFailure::Panicked(message.to_string()),
+            ));
+            status.status = panicked.clone();
+            persist_transfer_status(task_id, &status);
+            publish_task_state(task_id, &panicked);
+            notify_task_state(task_id, status.start_time, &panicked);
+        }
+        None => {
+            if let Ok(status) = get_task_status(task_id) {
+                if !status.is_stopped() {
+                    mark_task_broken(&status);
+                }
+            }
+        }
+    }
+    remove_exec_joinset(task_id);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_join_panic, forget_task_state, join_exec_next, task_checkpoint_lock,
+        GlobalTaskRuntime, TaskPanicError, GLOBAL_TASK_CHECKPOINT_LOCKS,
+    };
+    use once_cell::sync::OnceCell;
+    use std::sync::Arc;
+    use tokio::runtime::Runtime;
+    use tokio::sync::RwLock;
+    use tokio::task::JoinSet;
+
+    //cargo test tasks::task_server::test::test_join_exec_next_panic -- --nocapture
+    #[tokio::test]
+    async fn test_join_exec_next_panic() {
+        let exec_set = Arc::new(RwLock::new(JoinSet::<()>::new()));
+        exec_set.write().await.spawn(async {});
+        assert!(join_exec_next(&exec_set).await.is_ok());
+
+        exec_set.write().await.spawn(async {
+            panic!("subtask {} failed", 1);
+        });
+        let err = join_exec_next(&exec_set).await.unwrap_err();
+        let panic = err.downcast_ref::<TaskPanicError>().unwrap();
+        assert_eq!(panic.message, "subtask 1 failed");
+
+        // joinset 为空
+        assert!(join_exec_next(&exec_set).await.is_ok());
+
+        // 比对任务使用的本地 joinset，被取消的子任务不视为失败
+        let mut set = JoinSet::<()>::new();
+        set.spawn(std::future::pending());
+        set.abort_all();
+        assert!(check_join_panic(set.join_next().await).is_ok());
+        set.spawn(async { panic!("compare failed") });
+        assert!(check_join_panic(set.join_next().await).is_err());
+    }
+
+    //cargo test tasks::task_server::test::test_task_checkpoint_lock -- --nocapture
+    #[tokio::test]
+    async fn test_task_checkpoint_lock() {
+        let task_id = "checkpoint_lock_test";
+        let lock = task_checkpoint_lock(task_id);
+        assert!(Arc::ptr_eq(&lock, &task_checkpoint_lock(task_id)));
+        // 持有锁时同一任务的其他快照须等待
+        let guard = lock.lock().await;
+        assert!(task_checkpoint_lock(task_id).try_lock().is_err());
+        drop(guard);
+        assert!(task_checkpoint_lock(task_id).try_lock().is_ok());
+        forget_task_state(task_id);
+        assert!(!GLOBAL_TASK_CHECKPOINT_LOCKS.contains_key(task_id));
+    }
+
+    //cargo test tasks::task_server::test::test_global_task_runtime_not_initialized -- --nocapture
+    #[test]
+    fn test_global_task_runtime_not_initialized() {
+        static CELL: OnceCell<Arc<Runtime>> = OnceCell::new();
+        let global = GlobalTaskRuntime(&CELL);
+        // 创建前访问返回错误，不 panic
+        assert!(global.get().is_err());
+        assert!(global.spawn(async {}).is_err());
+
+        let rt = CELL.get_or_init(|| Arc::new(Runtime::new().unwrap()));
+        assert!(Arc::ptr_eq(global.get().unwrap(), rt));
+        let handle = global.spawn(async { 1 }).unwrap();
+        assert_eq!(rt.block_on(handle).unwrap(), 1);
+    }
+}
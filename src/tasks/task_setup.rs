@@ -0,0 +1,160 @@
+use super::{ObjectStorage, Task};
+use crate::s3::OSSDescription;
+use aws_sdk_s3::error::{DisplayErrorContext, SdkError};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::Path;
+use std::time::Duration;
+
+// 启动前探测存储的超时时间
+const SETUP_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 启动准备失败的阶段
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStage {
+    // 凭证或权限被存储端拒绝
+    Credentials,
+    // 存储端不可达或超时
+    Unreachable,
+    // 本地路径不存在
+    LocalPath,
+    // meta_dir 无法创建
+    MetaDir,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskSetupError {
+    pub stage: SetupStage,
+    // source 或 target，meta_dir 阶段为空
+    pub storage: String,
+    pub message: String,
+}
+
+impl Display for TaskSetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.storage.is_empty() {
+            true => write!(f, "task setup failed: {}", self.message),
+            false => write!(f, "task setup failed on {}: {}", self.storage, self.message),
+        }
+    }
+}
+
+impl std::error::Error for TaskSetupError {}
+
+impl TaskSetupError {
+    fn new(stage: SetupStage, storage: &str, message: String) -> Self {
+        Self {
+            stage,
+            storage: storage.to_string(),
+            message,
+        }
+    }
+}
+
+impl Task {
+    /// 启动前的准备：探测源与目标存储、创建 meta_dir，失败时任务不进入运行状态
+    pub async fn setup(&self) -> Result<(), TaskSetupError> {
+        // 比对任务的目标端为本地时同样需要已存在
+        let target_must_exist = matches!(self, Task::Compare(_));
+        probe_storage("source", &self.task_source(), true).await?;
+        probe_storage("target", &self.task_target(), target_must_exist).await?;
+        let meta_dir = self.meta_dir();
+        if let Err(e) = std::fs::create_dir_all(&meta_dir) {
+            return Err(TaskSetupError::new(
+                SetupStage::MetaDir,
+                "",
+                format!("create meta_dir {} error: {}", meta_dir, e),
+            ));
+        }
+        Ok(())
+    }
+}
+
+async fn probe_storage(
+    storage: &str,
+    object_storage: &ObjectStorage,
+    local_must_exist: bool,
+) -> Result<(), TaskSetupError> {
+    match object_storage {
+        ObjectStorage::Local(path) => match !local_must_exist || Path::new(path).exists() {
+            true => Ok(()),
+            false => Err(TaskSetupError::new(
+                SetupStage::LocalPath,
+                storage,
+                format!("local path {} not exist", path),
+            )),
+        },
+        ObjectStorage::OSS(oss) => probe_oss(storage, oss).await,
+    }
+}
+
+// 以 max_keys 为 1 的 list 请求验证凭证与 bucket 可达
+async fn probe_oss(storage: &str, oss: &OSSDescription) -> Result<(), TaskSetupError> {
+    let client = oss
+        .gen_oss_client()
+        .map_err(|e| TaskSetupError::new(SetupStage::Credentials, storage, e.to_string()))?;
+    let list = client
+        .client
+        .list_objects_v2()
+        .bucket(oss.bucket.clone())
+        .max_keys(1)
+        .send();
+    match tokio::time::timeout(SETUP_PROBE_TIMEOUT, list).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => {
+            let stage = match e {
+                SdkError::ServiceError(_) => SetupStage::Credentials,
+                _ => SetupStage::Unreachable,
+            };
+            Err(TaskSetupError::new(
+                stage,
+                storage,
+                format!("{}", DisplayErrorContext(&e)),
+            ))
+        }
+        Err(_) => Err(TaskSetupError::new(
+            SetupStage::Unreachable,
+            storage,
+            format!(
+                "list bucket {} timeout after {}s",
+                oss.bucket,
+                SETUP_PROBE_TIMEOUT.as_secs()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SetupStage;
+    use crate::tasks::{CompareTask, ObjectStorage, Task, TransferTask};
+
+    //cargo test tasks::task_setup::test::test_setup_local -- --nocapture
+    #[tokio::test]
+    async fn test_setup_local() {
+        let meta_dir = "/tmp/mario_setup_test/meta";
+        let _ = std::fs::remove_dir_all("/tmp/mario_setup_test");
+        let mut transfer = TransferTask::default();
+        transfer.source = ObjectStorage::Local("/tmp".to_string());
+        transfer.target = ObjectStorage::Local("/tmp/mario_setup_test/target".to_string());
+        transfer.attributes.meta_dir = meta_dir.to_string();
+        Task::Transfer(transfer.clone()).setup().await.unwrap();
+        assert!(std::path::Path::new(meta_dir).is_dir());
+
+        transfer.source = ObjectStorage::Local("/tmp/mario_setup_test/missing".to_string());
+        let err = Task::Transfer(transfer).setup().await.unwrap_err();
+        assert_eq!(err.stage, SetupStage::LocalPath);
+        assert_eq!(err.storage, "source");
+
+        // 比对任务的本地目标端须已存在
+        let mut compare = CompareTask::default();
+        compare.source = ObjectStorage::Local("/tmp".to_string());
+        compare.target = ObjectStorage::Local("/tmp/mario_setup_test/missing".to_string());
+        compare.attributes.meta_dir = meta_dir.to_string();
+        let err = Task::Compare(compare).setup().await.unwrap_err();
+        assert_eq!(err.stage, SetupStage::LocalPath);
+        assert_eq!(err.storage, "target");
+        let _ = std::fs::remove_dir_all("/tmp/mario_setup_test");
+    }
+}
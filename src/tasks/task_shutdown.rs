@@ -0,0 +1,233 @@
+use super::{
+    cancel_task, snapshot_task_checkpoint, stop_all_tasks, task_min_file_position,
+    GLOBAL_LIVING_TRANSFER_TASK_MAP, GLOBAL_TASKS_EXEC_JOINSET,
+};
+use crate::resources::{get_checkpoint, living_tasks};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// 停机时单个任务的重做代价
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskShutdownCost {
+    pub task_id: String,
+    // 上次 checkpoint 之后列表文件推进的字节数，未写入 checkpoint 时任务重启需重做这部分对象
+    pub cost: usize,
+}
+
+/// 最终 checkpoint 写入情况
+#[derive(Debug, Clone)]
+pub struct ShutdownCheckpoint {
+    pub task_id: String,
+    pub cost: usize,
+    // 写入时任务执行协程是否已全部退出
+    pub quiesced: bool,
+    // 距停机开始的时长
+    pub elapsed: Duration,
+    pub saved: bool,
+}
+
+fn checkpoint_cost(task_id: &str) -> usize {
+    let saved = match get_checkpoint(task_id) {
+        Ok(c) => c.executing_file_position.offset,
+        Err(_) => 0,
+    };
+    match task_min_file_position(task_id) {
+        Some(p) => p.offset.saturating_sub(saved),
+        None => 0,
+    }
+}
+
+/// 活动任务按重做代价降序排列
+pub fn tasks_by_shutdown_cost() -> Vec<TaskShutdownCost> {
+    let mut task_ids = match living_tasks() {
+        Ok(v) => v.into_iter().map(|s| s.task_id).collect::<Vec<String>>(),
+        Err(e) => {
+            log::warn!("{}", e);
+            vec![]
+        }
+    };
+    for kv in GLOBAL_LIVING_TRANSFER_TASK_MAP.iter() {
+        if !task_ids.contains(kv.key()) {
+            task_ids.push(kv.key().clone());
+        }
+    }
+    let mut costs = task_ids
+        .into_iter()
+        .map(|task_id| TaskShutdownCost {
+            cost: checkpoint_cost(&task_id),
+            task_id,
+        })
+        .collect::<Vec<TaskShutdownCost>>();
+    costs.sort_by(|a, b| b.cost.cmp(&a.cost));
+    costs
+}
+
+/// 按代价降序通知任务停止，返回停止顺序
+pub fn stop_tasks_by_cost() -> Vec<TaskShutdownCost> {
+    let order = tasks_by_shutdown_cost();
+    for t in order.iter() {
+        cancel_task(&t.task_id);
+    }
+    // 未记录状态的任务同样需要停止
+    stop_all_tasks();
+    order
+}
+
+// 执行 joinset 已注销或为空即视为执行协程已退出，持有写锁时视为仍在执行
+fn task_workers_quiesced(task_id: &str) -> bool {
+    let exec_set = GLOBAL_TASKS_EXEC_JOINSET
+        .get(task_id)
+        .map(|kv| kv.value().clone());
+    match exec_set {
+        Some(s) => match s.try_read() {
+            Ok(set) => set.is_empty(),
+            Err(_) => false,
+        },
+        None => true,
+    }
+}
+
+/// 任务执行协程退出后立即写入 checkpoint；截止时仍未退出的任务按代价降序写入
+pub async fn checkpoint_tasks_on_quiesce(
+    order: Vec<TaskShutdownCost>,
+    deadline: Instant,
+) -> Vec<ShutdownCheckpoint> {
+    let written = checkpoint_on_quiesce(order, deadline, task_workers_quiesced, |task_id| {
+        snapshot_task_checkpoint(task_id).map(|_| ())
+    })
+    .await;
+    for w in written.iter() {
+        // 写入后执行位置仍在推进说明 checkpoint 已落后
+        let lag = match (
+            get_checkpoint(&w.task_id),
+            task_min_file_position(&w.task_id),
+        ) {
+            (Ok(c), Some(p)) => p.offset.saturating_sub(c.executing_file_position.offset),
+            _ => 0,
+        };
+        log::info!(
+            "task {} final checkpoint saved:{} quiesced:{} cost:{} bytes lag:{} bytes written {:?} after shutdown start",
+            w.task_id,
+            w.saved,
+            w.quiesced,
+            w.cost,
+            lag,
+            w.elapsed
+        );
+    }
+    written
+}
+
+pub async fn checkpoint_on_quiesce<Q, S>(
+    order: Vec<TaskShutdownCost>,
+    deadline: Instant,
+    is_quiesced: Q,
+    mut save: S,
+) -> Vec<ShutdownCheckpoint>
+where
+    Q: Fn(&str) -> bool,
+    S: FnMut(&str) -> Result<()>,
+{
+    let begin = Instant::now();
+    let mut pending = order;
+    let mut written = vec![];
+    let mut record =
+        |t: TaskShutdownCost, quiesced: bool, written: &mut Vec<ShutdownCheckpoint>| {
+            let saved = match save(&t.task_id) {
+                Ok(_) => true,
+                Err(e) => {
+                    log::error!("task {} save final checkpoint error: {}", t.task_id, e);
+                    false
+                }
+            };
+            written.push(ShutdownCheckpoint {
+                task_id: t.task_id,
+                cost: t.cost,
+                quiesced,
+                elapsed: begin.elapsed(),
+                saved,
+            });
+        };
+
+    while !pending.is_empty() && Instant::now() < deadline {
+        // pending 保持代价降序，同一轮内先写代价高的任务
+        let (quiesced, rest): (Vec<TaskShutdownCost>, Vec<TaskShutdownCost>) =
+            pending.into_iter().partition(|t| is_quiesced(&t.task_id));
+        for t in quiesced {
+            record(t, true, &mut written);
+        }
+        pending = rest;
+        if !pending.is_empty() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+    for t in pending {
+        record(t, false, &mut written);
+    }
+    written
+}
+
+#[cfg(test)]
+mod test {
+    use super::{checkpoint_on_quiesce, TaskShutdownCost};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    //cargo test tasks::task_shutdown::test::test_checkpoint_on_quiesce -- --nocapture
+    #[tokio::test]
+    async fn test_checkpoint_on_quiesce() {
+        let begin = Instant::now();
+        let grace = Duration::from_millis(500);
+        let deadline = begin + grace;
+        // 各任务执行协程退出的时间，None 表示宽限期内不退出
+        let quiesce_at: HashMap<&str, Option<Duration>> = HashMap::from([
+            ("high", Some(Duration::from_millis(100))),
+            ("medium", Some(Duration::from_millis(200))),
+            ("low", None),
+            ("stuck_high", None),
+        ]);
+        let order = vec![
+            TaskShutdownCost {
+                task_id: "stuck_high".to_string(),
+                cost: 4096,
+            },
+            TaskShutdownCost {
+                task_id: "high".to_string(),
+                cost: 2048,
+            },
+            TaskShutdownCost {
+                task_id: "medium".to_string(),
+                cost: 1024,
+            },
+            TaskShutdownCost {
+                task_id: "low".to_string(),
+                cost: 1,
+            },
+        ];
+
+        let mut saved_at = vec![];
+        let written = checkpoint_on_quiesce(
+            order,
+            deadline,
+            |id| match quiesce_at[id] {
+                Some(d) => begin.elapsed() >= d,
+                None => false,
+            },
+            |id| {
+                saved_at.push((id.to_string(), Instant::now()));
+                Ok(())
+            },
+        )
+        .await;
+
+        let ids = written
+            .iter()
+            .map(|w| w.task_id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, vec!["high", "medium", "stuck_high", "low"]);
+        // 高代价任务退出后立即写入，不等待宽限期结束
+        assert!(saved_at[0].1 < deadline);
+        assert!(written[0].quiesced && written[0].elapsed < Duration::from_millis(300));
+        assert!(!written[2].quiesced);
+    }
+}
@@ -1,5 +1,6 @@
 use super::{TaskStopReason, TaskType, TransferStage};
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Status {
@@ -12,6 +13,45 @@ pub struct TaskStatus {
     pub task_id: String,
     pub start_time: u64,
     pub status: Status,
+    // 最近一次启动被拒绝的原因，任务真正启动时清除
+    #[serde(default)]
+    pub last_skip_reason: Option<TaskSkipRecord>,
+    // 最近一次运行的 id
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+/// 任务启动请求被拒绝的原因
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartSkipReason {
+    // 任务已在运行
+    AlreadyLiving,
+    // 服务正在停机，不再接收新任务
+    Draining,
+    // 排队任务出队后启动准备失败
+    SetupFailed(String),
+    // 达到任务并发上限，进入队列等待
+    ConcurrencyLimit,
+}
+
+impl Display for StartSkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartSkipReason::AlreadyLiving => write!(f, "task is living"),
+            StartSkipReason::Draining => write!(f, "server is draining"),
+            StartSkipReason::SetupFailed(e) => write!(f, "{}", e),
+            StartSkipReason::ConcurrencyLimit => {
+                write!(f, "max concurrent tasks reached, task queued")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskSkipRecord {
+    pub reason: StartSkipReason,
+    pub timestamp: u64,
 }
 
 impl TaskStatus {
@@ -48,6 +88,17 @@ impl TaskStatus {
         }
     }
 
+    /// 异常中止或未满足成功判定条件
+    pub fn is_failed(&self) -> bool {
+        match &self.status {
+            Status::Transfer(TransferStatus::Stopped(reason)) => match reason {
+                TaskStopReason::Finish => false,
+                TaskStopReason::Broken | TaskStopReason::Failed(_) => true,
+            },
+            _ => false,
+        }
+    }
+
     pub fn is_stopped(&self) -> bool {
         match &self.status {
             Status::Transfer(t) => match t {
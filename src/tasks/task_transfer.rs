@@ -4,36 +4,41 @@ use super::RecordDescription;
 use super::TaskStopReason;
 use super::{
     de_usize_from_str, gen_file_path, se_usize_to_str, CheckPoint, FilePosition, ListedRecord,
-    TaskDefaultParameters, TransferStage, OFFSET_PREFIX, TRANSFER_OBJECT_LIST_FILE_PREFIX,
+    Task, TaskDefaultParameters, TransferStage, OFFSET_PREFIX, TRANSFER_OBJECT_LIST_FILE_PREFIX,
+};
+use super::{
+    remove_completion_marker, save_run_definition, CompletionCounters, CompletionMarker,
+    CriteriaNotMetError, SuccessCriteria,
 };
 use super::{
     task_actions::TransferTaskActions, IncrementAssistant, TransferLocal2Local, TransferLocal2Oss,
     TransferOss2Local, TransferOss2Oss,
 };
+use crate::commons::inherit_request_context;
 use crate::commons::quantify_processbar;
-use crate::commons::{json_to_struct, LastModifyFilter};
+use crate::commons::{json_to_struct, AnalyzeProgress, LastModifyFilter};
 use crate::resources::get_checkpoint;
-use crate::tasks::log_out_living_task;
+use crate::tasks::join_exec_next;
 use crate::tasks::save_task_status;
 use crate::tasks::task_is_living;
 use crate::tasks::GLOBAL_TASKS_EXEC_JOINSET;
 use crate::tasks::GLOBAL_TASKS_SYS_JOINSET;
-use crate::tasks::GLOBAL_TASK_STOP_MARK_MAP;
+use crate::tasks::{finish_living_task, log_out_living_task};
+use crate::tasks::{
+    register_batch_range, register_task_offset_map, registered_task_cancellation,
+    run_until_cancelled, wait_if_paused,
+};
 use crate::{commons::RegexFilter, s3::OSSDescription, tasks::NOTIFY_FILE_PREFIX};
 use anyhow::anyhow;
 use anyhow::Result;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 use std::{
     fs::{self, File},
     io::{self, BufRead},
-    sync::{
-        atomic::{AtomicBool, AtomicUsize},
-        Arc,
-    },
+    sync::{atomic::AtomicUsize, Arc},
 };
 use tokio::sync::RwLock;
 use tokio::{
@@ -44,8 +49,12 @@ use walkdir::WalkDir;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TransferTaskStatusType {
+    // 超过并发上限，在队列中等待启动，不进入活动任务表
+    Queued,
     Starting,
     Running(TransferStage),
+    // 暂停中，执行协程保持存活，持久化状态仍为运行中
+    Paused(TransferStage),
     Stopped(TaskStopReason),
 }
 
@@ -53,6 +62,9 @@ pub enum TransferTaskStatusType {
 pub struct TransferTaskStatus {
     pub task_id: String,
     pub start_time: u64,
+    // 本次运行的 id，与运行快照、完成标识中的 run_id 一致
+    #[serde(default)]
+    pub run_id: String,
     pub status: TransferTaskStatusType,
 }
 
@@ -66,7 +78,7 @@ impl TransferTaskStatusType {
 
     pub fn is_stock_running(&self) -> bool {
         match self {
-            TransferTaskStatusType::Running(ts) => match ts {
+            TransferTaskStatusType::Running(ts) | TransferTaskStatusType::Paused(ts) => match ts {
                 TransferStage::Stock => true,
                 TransferStage::Increment => false,
             },
@@ -83,7 +95,7 @@ impl TransferTaskStatusType {
 
     pub fn is_increment_running(&self) -> bool {
         match self {
-            TransferTaskStatusType::Running(ts) => match ts {
+            TransferTaskStatusType::Running(ts) | TransferTaskStatusType::Paused(ts) => match ts {
                 TransferStage::Stock => false,
                 TransferStage::Increment => true,
             },
@@ -91,6 +103,20 @@ impl TransferTaskStatusType {
         }
     }
 
+    pub fn is_paused(&self) -> bool {
+        match self {
+            TransferTaskStatusType::Paused(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_queued(&self) -> bool {
+        match self {
+            TransferTaskStatusType::Queued => true,
+            _ => false,
+        }
+    }
+
     pub fn is_stopped(&self) -> bool {
         match self {
             TransferTaskStatusType::Stopped(_) => true,
@@ -102,7 +128,7 @@ impl TransferTaskStatusType {
         match self {
             TransferTaskStatusType::Stopped(s) => match s {
                 TaskStopReason::Finish => true,
-                TaskStopReason::Broken => false,
+                TaskStopReason::Broken | TaskStopReason::Failed(_) => false,
             },
             _ => false,
         }
@@ -111,7 +137,7 @@ impl TransferTaskStatusType {
     pub fn is_stopped_broken(&self) -> bool {
         match self {
             TransferTaskStatusType::Stopped(s) => match s {
-                TaskStopReason::Finish => false,
+                TaskStopReason::Finish | TaskStopReason::Failed(_) => false,
                 TaskStopReason::Broken => true,
             },
             _ => false,
@@ -167,7 +193,9 @@ impl Default for ObjectStorage {
 }
 
 // ToDo 规范属性名称
+// 拒绝未知字段，避免拼写错误的属性被静默忽略
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TransferTaskAttributes {
     #[serde(default = "TaskDefaultParameters::objects_per_batch_default")]
     pub objects_per_batch: i32,
@@ -201,6 +229,8 @@ pub struct TransferTaskAttributes {
     pub transfer_type: TransferType,
     #[serde(default = "TaskDefaultParameters::last_modify_filter_default")]
     pub last_modify_filter: Option<LastModifyFilter>,
+    #[serde(default = "TaskDefaultParameters::success_criteria_default")]
+    pub success_criteria: SuccessCriteria,
 }
 
 impl Default for TransferTaskAttributes {
@@ -221,17 +251,21 @@ impl Default for TransferTaskAttributes {
             include: TaskDefaultParameters::filter_default(),
             transfer_type: TaskDefaultParameters::transfer_type_default(),
             last_modify_filter: TaskDefaultParameters::last_modify_filter_default(),
+            success_criteria: TaskDefaultParameters::success_criteria_default(),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub struct TransferTask {
     #[serde(default = "TaskDefaultParameters::id_default")]
     pub task_id: String,
     #[serde(default = "TaskDefaultParameters::name_default")]
     pub name: String,
+    // 并发调度按 namespace 分组
+    #[serde(default = "TaskDefaultParameters::namespace_default")]
+    pub namespace: String,
     pub source: ObjectStorage,
     pub target: ObjectStorage,
     pub attributes: TransferTaskAttributes,
@@ -242,6 +276,7 @@ impl Default for TransferTask {
         Self {
             task_id: TaskDefaultParameters::id_default(),
             name: TaskDefaultParameters::name_default(),
+            namespace: TaskDefaultParameters::namespace_default(),
             source: ObjectStorage::OSS(OSSDescription::default()),
             target: ObjectStorage::OSS(OSSDescription::default()),
             attributes: TransferTaskAttributes::default(),
@@ -299,32 +334,36 @@ impl TransferTask {
         }
     }
 
-    pub async fn analyze(&self) -> Result<BTreeMap<String, i128>> {
+    pub async fn analyze(&self, progress: &AnalyzeProgress) -> Result<()> {
         let task = self.gen_transfer_actions();
-        task.analyze_source().await
+        task.analyze_source(progress).await
     }
 
     //Todo
     // 使用全局joinset，任务启动注册执行joinset和大文件joinset，任务启动时查看承载任务数量是否达到上线
-    pub async fn execute(&self) -> Result<()> {
+    pub async fn execute(&self, run_id: &str) -> Result<()> {
         let task = self.gen_transfer_actions();
         // 从checkpoint 执行，且taskstage 处于增量模式时该标识为true，从上次任务起始时间戳开始抓取变化数据并同步
         let mut exec_modified = false;
         // 执行过程中错误数统计
         let err_counter = Arc::new(AtomicUsize::new(0));
-        // 任务停止标准，用于通知所有协程任务结束
-        let stop_mark = Arc::new(AtomicBool::new(false));
-        GLOBAL_TASK_STOP_MARK_MAP.insert(self.task_id.clone(), stop_mark.clone());
+        // 任务停止标识与取消 token，用于通知所有协程任务结束，由 spawn_task_execute 在启动时注册
+        let (stop_mark, cancel) = registered_task_cancellation(&self.task_id);
 
         let offset_map = Arc::new(DashMap::<String, FilePosition>::new());
+        register_task_offset_map(&self.task_id, offset_map.clone());
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
-        //注册活动任务
-        let task_status = TransferTaskStatus {
-            task_id: self.task_id.clone(),
-            start_time: now.as_secs(),
-            status: TransferTaskStatusType::Starting,
-        };
-        save_task_status(&self.task_id, task_status);
+        // 记录本次运行实际执行的任务定义，失败不影响任务执行
+        if let Err(e) = save_run_definition(&Task::Transfer(self.clone()), run_id) {
+            log::error!(
+                "task {} save run {} definition error: {}",
+                self.task_id,
+                run_id,
+                e
+            );
+        }
+        // 清理上次运行的完成标识，避免下游误判
+        remove_completion_marker(&self.attributes.meta_dir)?;
 
         let mut executed_file = FileDescription {
             path: gen_file_path(
@@ -388,9 +427,18 @@ impl TransferTask {
                     // 清理文件重新生成object list 文件需大于指定时间戳,并根据原始object list 删除位于目标端但源端不存在的文件
                     // 流程逻辑
                     // 扫描target 文件list-> 抓取自扫描时间开始，源端的变动数据 -> 生成objlist，action 新增target change capture
-                    let modified: FileDescription = task
-                        .changed_object_capture_based_target(checkpoint.task_begin_timestamp)
-                        .await?;
+                    let modified: FileDescription = match run_until_cancelled(
+                        &cancel,
+                        task.changed_object_capture_based_target(checkpoint.task_begin_timestamp),
+                    )
+                    .await
+                    {
+                        Some(r) => r?,
+                        None => {
+                            log_out_living_task(&self.task_id);
+                            return Ok(());
+                        }
+                    };
                     list_file = Some(File::open(&modified.path)?);
                     exec_modified = true;
                 }
@@ -399,9 +447,19 @@ impl TransferTask {
             // 清理 meta 目录
             // 重新生成object list file
             let _ = fs::remove_dir_all(self.attributes.meta_dir.as_str());
-            executed_file = task
-                .gen_source_object_list_file(None, &executed_file.path)
-                .await?;
+            // 列举源端对象耗时较长，需在列举过程中响应取消
+            executed_file = match run_until_cancelled(
+                &cancel,
+                task.gen_source_object_list_file(None, &executed_file.path),
+            )
+            .await
+            {
+                Some(r) => r?,
+                None => {
+                    log_out_living_task(&self.task_id);
+                    return Ok(());
+                }
+            };
         }
 
         let log_info = LogInfo::<String> {
@@ -415,6 +473,7 @@ impl TransferTask {
         let task_status = TransferTaskStatus {
             task_id: self.task_id.clone(),
             start_time: now.as_secs(),
+            run_id: run_id.to_string(),
             status: TransferTaskStatusType::Running(TransferStage::Stock),
         };
         save_task_status(&self.task_id, task_status);
@@ -442,11 +501,11 @@ impl TransferTask {
 
         if self.attributes.transfer_type.is_full() || self.attributes.transfer_type.is_increment() {
             let assistant = Arc::clone(&increment_assistant);
-            task::spawn(async move {
+            task::spawn(inherit_request_context(async move {
                 if let Err(e) = task_increment_prelude.increment_prelude(assistant).await {
                     log::error!("{}", e);
                 }
-            });
+            }));
 
             // 当源存储为本地时，获取notify文件
             if let ObjectStorage::Local(_) = self.source {
@@ -494,8 +553,9 @@ impl TransferTask {
                     .to_string()
                     .eq(&self.attributes.objects_per_batch.to_string())
                 {
+                    wait_if_paused(&self.task_id).await;
                     while task_exec_set.read().await.len() >= self.attributes.task_parallelism {
-                        task_exec_set.write().await.join_next().await;
+                        join_exec_next(&task_exec_set).await?;
                     }
 
                     let vk: Vec<RecordDescription> = vec_keys.clone();
@@ -523,7 +583,7 @@ impl TransferTask {
                     < self.attributes.max_errors
             {
                 while task_exec_set.read().await.len() >= self.attributes.task_parallelism {
-                    task_exec_set.write().await.join_next().await;
+                    join_exec_next(&task_exec_set).await?;
                 }
                 let vk = vec_keys.clone();
                 task_modify
@@ -562,10 +622,13 @@ impl TransferTask {
                 let s_m = Arc::clone(&stop_mark);
                 let total = executed_file.total_lines;
                 let id = self.task_id.clone();
-                sys_set.write().await.spawn(async move {
-                    // Todo 调整进度条
-                    quantify_processbar(id, total, s_m, map, OFFSET_PREFIX).await;
-                });
+                sys_set
+                    .write()
+                    .await
+                    .spawn(inherit_request_context(async move {
+                        // Todo 调整进度条
+                        quantify_processbar(id, total, s_m, map, OFFSET_PREFIX).await;
+                    }));
 
                 let task_stock = self.gen_transfer_actions();
                 let mut vec_keys: Vec<ListedRecord> = vec![];
@@ -607,6 +670,7 @@ impl TransferTask {
                             && err_counter.load(std::sync::atomic::Ordering::SeqCst)
                                 < self.attributes.max_errors
                     {
+                        wait_if_paused(&self.task_id).await;
                         if stop_mark.load(std::sync::atomic::Ordering::SeqCst) {
                             match err_counter
                                 .load(std::sync::atomic::Ordering::SeqCst)
@@ -618,9 +682,10 @@ impl TransferTask {
                         }
 
                         while task_exec_set.read().await.len() >= self.attributes.task_parallelism {
-                            task_exec_set.write().await.join_next().await;
+                            join_exec_next(&task_exec_set).await?;
                         }
                         let vk = vec_keys.clone();
+                        register_batch_range(&self.task_id, &vk);
 
                         task_stock
                             .listed_records_transfor(
@@ -643,7 +708,7 @@ impl TransferTask {
         }
 
         while task_exec_set.read().await.len() > 0 {
-            task_exec_set.write().await.join_next().await;
+            join_exec_next(&task_exec_set).await?;
         }
 
         // 配置停止 offset save 标识为 true
@@ -671,8 +736,32 @@ impl TransferTask {
             task_begin_timestamp: i128::from(now.as_secs()),
         };
         if self.attributes.transfer_type.is_stock() {
-            checkpoint.save_to_rocksdb_cf()?;
-            log_out_living_task(&self.task_id);
+            // 所有文件落盘后写入完成标识，最终 checkpoint 与停止状态随后同批写入
+            let counters = CompletionCounters {
+                total_objects: executed_file.total_lines,
+                executed_lines: list_file_position.line_num,
+                errors: err_counter.load(std::sync::atomic::Ordering::SeqCst),
+            };
+            // 未满足成功判定条件时不写入完成标识，由 Task::execute 置为 Failed(CriteriaNotMet)
+            // 目前没有校验流程，verified 恒为 false，require_verification 在任务校验时即被拒绝
+            if let Err(breach) = self.attributes.success_criteria.evaluate(
+                counters.total_objects,
+                counters.errors as u64,
+                false,
+            ) {
+                checkpoint.save_to_rocksdb_cf()?;
+                return Err(anyhow::Error::new(CriteriaNotMetError {
+                    task_id: self.task_id.clone(),
+                    breach,
+                }));
+            }
+            if let Err(e) = CompletionMarker::new(&self.task_id, run_id, counters)
+                .write_to(&self.attributes.meta_dir)
+            {
+                checkpoint.save_to_rocksdb_cf()?;
+                return Err(e);
+            }
+            finish_living_task(&self.task_id, &mut checkpoint)?;
             return Ok(());
         } else {
             checkpoint.task_stage = TransferStage::Increment;
@@ -693,7 +782,7 @@ impl TransferTask {
             let executing_transfers = Arc::new(RwLock::new(0));
             let task_increment = self.gen_transfer_actions();
 
-            let _ = task_increment
+            let increment = task_increment
                 .execute_increment(
                     // &mut execut_set,
                     task_exec_set.clone(),
@@ -706,6 +795,8 @@ impl TransferTask {
                 .await;
             // 配置停止 offset save 标识为 true
             stop_mark.store(true, std::sync::atomic::Ordering::Relaxed);
+            // 子任务 panic 时由 Task::execute 置为 Failed(Panicked)
+            increment?;
         }
 
         Ok(())
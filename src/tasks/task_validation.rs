@@ -0,0 +1,206 @@
+use super::{CompareTask, ObjectStorage, Task, TransferTask};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::{Component, Path};
+
+// 并行度上限，超出时多为误填
+pub const MAX_PARALLELISM: usize = 1024;
+
+/// 单个字段的问题，field 为以点分隔的字段路径
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldProblem {
+    pub field: String,
+    pub problem: String,
+}
+
+impl FieldProblem {
+    fn new(field: &str, problem: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            problem: problem.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskValidationError {
+    pub problems: Vec<FieldProblem>,
+}
+
+impl Display for TaskValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problems = self
+            .problems
+            .iter()
+            .map(|p| format!("{}: {}", p.field, p.problem))
+            .collect::<Vec<String>>();
+        write!(f, "task definition invalid: {}", problems.join(" | "))
+    }
+}
+
+impl std::error::Error for TaskValidationError {}
+
+impl Task {
+    /// 反序列化后逐个校验字段取值，字段组合由 validate_consistency 校验
+    pub fn validate_fields(&self) -> Result<(), TaskValidationError> {
+        let problems = match self {
+            Task::Transfer(t) => transfer_problems(t),
+            Task::Compare(c) => compare_problems(c),
+        };
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(TaskValidationError { problems }),
+        }
+    }
+}
+
+fn transfer_problems(task: &TransferTask) -> Vec<FieldProblem> {
+    let attributes = &task.attributes;
+    let mut problems = vec![];
+    problems.extend(storage_problems("source", &task.source));
+    problems.extend(storage_problems("target", &task.target));
+    problems.extend(meta_dir_problems(&attributes.meta_dir));
+    problems.extend(parallelism_problems(
+        "attributes.task_parallelism",
+        attributes.task_parallelism,
+    ));
+    problems.extend(parallelism_problems(
+        "attributes.multi_part_parallelism",
+        attributes.multi_part_parallelism,
+    ));
+    if attributes.objects_per_batch <= 0 {
+        problems.push(FieldProblem::new(
+            "attributes.objects_per_batch",
+            "must be greater than 0",
+        ));
+    }
+    if attributes.multi_part_chunks_per_batch == 0 {
+        problems.push(FieldProblem::new(
+            "attributes.multi_part_chunks_per_batch",
+            "must be greater than 0",
+        ));
+    }
+    problems
+}
+
+fn compare_problems(task: &CompareTask) -> Vec<FieldProblem> {
+    let attributes = &task.attributes;
+    let mut problems = vec![];
+    problems.extend(storage_problems("source", &task.source));
+    problems.extend(storage_problems("target", &task.target));
+    problems.extend(meta_dir_problems(&attributes.meta_dir));
+    problems.extend(parallelism_problems(
+        "attributes.task_parallelism",
+        attributes.task_parallelism,
+    ));
+    if attributes.objects_per_batch <= 0 {
+        problems.push(FieldProblem::new(
+            "attributes.objects_per_batch",
+            "must be greater than 0",
+        ));
+    }
+    problems
+}
+
+fn storage_problems(field: &str, storage: &ObjectStorage) -> Vec<FieldProblem> {
+    let mut problems = vec![];
+    match storage {
+        ObjectStorage::Local(path) => {
+            if path.trim().is_empty() {
+                problems.push(FieldProblem::new(field, "local path is empty"));
+            }
+        }
+        ObjectStorage::OSS(oss) => {
+            if oss.endpoint.trim().is_empty() {
+                problems.push(FieldProblem::new(
+                    &format!("{}.endpoint", field),
+                    "is empty",
+                ));
+            }
+            if oss.bucket.trim().is_empty() {
+                problems.push(FieldProblem::new(&format!("{}.bucket", field), "is empty"));
+            }
+        }
+    }
+    problems
+}
+
+fn parallelism_problems(field: &str, parallelism: usize) -> Option<FieldProblem> {
+    match parallelism {
+        0 => Some(FieldProblem::new(field, "must be greater than 0")),
+        p if p > MAX_PARALLELISM => Some(FieldProblem::new(
+            field,
+            &format!("must not exceed {}", MAX_PARALLELISM),
+        )),
+        _ => None,
+    }
+}
+
+// meta_dir 由服务端按全局配置重新生成，仍拒绝含 .. 的路径，避免误写到其他目录
+fn meta_dir_problems(meta_dir: &str) -> Option<FieldProblem> {
+    if meta_dir.trim().is_empty() {
+        return Some(FieldProblem::new("attributes.meta_dir", "is empty"));
+    }
+    match Path::new(meta_dir)
+        .components()
+        .any(|c| c == Component::ParentDir)
+    {
+        true => Some(FieldProblem::new(
+            "attributes.meta_dir",
+            "must not contain '..'",
+        )),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tasks::{ObjectStorage, Task, TransferTask};
+
+    //cargo test tasks::task_validation::test::test_validate_fields -- --nocapture
+    #[test]
+    fn test_validate_fields() {
+        let mut transfer = TransferTask::default();
+        assert!(Task::Transfer(transfer.clone()).validate_fields().is_ok());
+
+        transfer.source = ObjectStorage::Local("".to_string());
+        if let ObjectStorage::OSS(oss) = &mut transfer.target {
+            oss.endpoint = " ".to_string();
+        }
+        transfer.attributes.task_parallelism = 0;
+        transfer.attributes.multi_part_parallelism = 100000;
+        transfer.attributes.meta_dir = "/tmp/../etc".to_string();
+        let err = Task::Transfer(transfer).validate_fields().unwrap_err();
+        let fields = err
+            .problems
+            .iter()
+            .map(|p| p.field.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            fields,
+            vec![
+                "source",
+                "target.endpoint",
+                "attributes.meta_dir",
+                "attributes.task_parallelism",
+                "attributes.multi_part_parallelism"
+            ]
+        );
+    }
+
+    //cargo test tasks::task_validation::test::test_deny_unknown_fields -- --nocapture
+    #[test]
+    fn test_deny_unknown_fields() {
+        let mut task = serde_json::to_value(Task::Transfer(TransferTask::default())).unwrap();
+        assert!(serde_json::from_value::<Task>(task.clone()).is_ok());
+        task["attributes"]["paralellism"] = serde_json::json!(4);
+        let err = serde_json::from_value::<Task>(task.clone()).unwrap_err();
+        assert!(err.to_string().contains("paralellism"));
+        task["attributes"]
+            .as_object_mut()
+            .unwrap()
+            .remove("paralellism");
+        task["nmae"] = serde_json::json!("typo");
+        assert!(serde_json::from_value::<Task>(task).is_err());
+    }
+}
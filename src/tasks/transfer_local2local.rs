@@ -4,11 +4,15 @@ use super::{
     MODIFIED_PREFIX, NOTIFY_FILE_PREFIX, OFFSET_PREFIX, REMOVED_PREFIX,
     TRANSFER_ERROR_RECORD_PREFIX,
 };
+use super::{run_until_cancelled, task_cancellation_token};
+use crate::commons::inherit_request_context;
 use crate::commons::{
-    analyze_folder_files_size, copy_file, json_to_struct, merge_file, read_lines,
-    scan_folder_files_to_file, struct_to_json_string, LastModifyFilter, Modified, ModifyType,
-    NotifyWatcher, PathType, RegexFilter,
+    analyze_folder_files_size, copy_file, file_len, json_to_struct, merge_file, read_lines,
+    scan_folder_files_to_file, struct_to_json_string, AnalyzeProgress, LastModifyFilter, Modified,
+    ModifyType, NotifyWatcher, PathType, RegexFilter,
 };
+use crate::server::{record_task_error, record_task_transferred};
+use crate::tasks::wait_if_paused;
 use crate::tasks::TaskDefaultParameters;
 use anyhow::anyhow;
 use anyhow::Result;
@@ -16,7 +20,6 @@ use async_trait::async_trait;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
-use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
@@ -45,12 +48,13 @@ pub struct TransferLocal2Local {
 
 #[async_trait]
 impl TransferTaskActions for TransferLocal2Local {
-    async fn analyze_source(&self) -> Result<BTreeMap<String, i128>> {
+    async fn analyze_source(&self, progress: &AnalyzeProgress) -> Result<()> {
         let filter = RegexFilter::from_vec(&self.attributes.exclude, &self.attributes.include)?;
         analyze_folder_files_size(
             &self.source,
             Some(filter),
             self.attributes.last_modify_filter,
+            progress,
         )
     }
 
@@ -90,6 +94,7 @@ impl TransferTaskActions for TransferLocal2Local {
 
                     if record_vec.len() > 0 {
                         let local2local = Local2LocalExecutor {
+                            task_id: self.task_id.clone(),
                             source: self.source.clone(),
                             target: self.target.clone(),
                             err_counter: Arc::new(AtomicUsize::new(0)),
@@ -119,6 +124,7 @@ impl TransferTaskActions for TransferLocal2Local {
         list_file: String,
     ) {
         let local2local = Local2LocalExecutor {
+            task_id: self.task_id.clone(),
             source: self.source.clone(),
             target: self.target.clone(),
             err_counter,
@@ -127,12 +133,19 @@ impl TransferTaskActions for TransferLocal2Local {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = local2local.exec_listed_records(records).await {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) =
+                    run_until_cancelled(&cancel, local2local.exec_listed_records(records)).await
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     async fn record_descriptions_transfor(
@@ -147,6 +160,7 @@ impl TransferTaskActions for TransferLocal2Local {
         list_file: String,
     ) {
         let local2local = Local2LocalExecutor {
+            task_id: self.task_id.clone(),
             source: self.source.clone(),
             target: self.target.clone(),
             err_counter,
@@ -155,12 +169,20 @@ impl TransferTaskActions for TransferLocal2Local {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = local2local.exec_record_descriptions(records).await {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) =
+                    run_until_cancelled(&cancel, local2local.exec_record_descriptions(records))
+                        .await
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     async fn gen_source_object_list_file(
@@ -236,6 +258,7 @@ impl TransferTaskActions for TransferLocal2Local {
                         list_file_path: "".to_string(),
                         list_file_position: FilePosition::default(),
                         option: Opt::REMOVE,
+                        attempts: 0,
                     };
                     let record_str = struct_to_json_string(&record)?;
                     let _ = removed_file.write_all(record_str.as_bytes());
@@ -274,6 +297,7 @@ impl TransferTaskActions for TransferLocal2Local {
                         list_file_path: "".to_string(),
                         list_file_position: FilePosition::default(),
                         option: Opt::PUT,
+                        attempts: 0,
                     };
                     let record_str = struct_to_json_string(&record)?;
                     let _ = modified_file.write_all(record_str.as_bytes());
@@ -345,11 +369,11 @@ impl TransferTaskActions for TransferLocal2Local {
         err_counter: Arc<AtomicUsize>,
         offset_map: Arc<DashMap<String, FilePosition>>,
         snapshot_stop_mark: Arc<AtomicBool>,
-    ) {
+    ) -> Result<()> {
         let lock = assistant.lock().await;
         let local_notify = match lock.local_notify.clone() {
             Some(n) => n,
-            None => return,
+            None => return Ok(()),
         };
         drop(lock);
 
@@ -364,7 +388,7 @@ impl TransferTaskActions for TransferLocal2Local {
             Ok(o) => o,
             Err(e) => {
                 log::error!("{}", e);
-                return;
+                return Ok(());
             }
         };
         let mut line_num = file_position.line_num;
@@ -400,7 +424,7 @@ impl TransferTaskActions for TransferLocal2Local {
                 Ok(r) => r,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -419,14 +443,14 @@ impl TransferTaskActions for TransferLocal2Local {
                 .le(&err_counter.load(std::sync::atomic::Ordering::Relaxed))
             {
                 snapshot_stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                return;
+                return Ok(());
             }
 
             let mut file = match File::open(&local_notify.notify_file_path) {
                 Ok(f) => f,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -444,7 +468,7 @@ impl TransferTaskActions for TransferLocal2Local {
                 Ok(f) => f,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -481,13 +505,16 @@ impl TransferTaskActions for TransferLocal2Local {
                                     line_num,
                                 },
                                 option: Opt::UNKOWN,
+                                attempts: 0,
                             };
                             r.handle_error(
                                 &err_counter,
                                 &offset_map,
                                 &mut error_file,
                                 offset_key.as_str(),
+                                &e.to_string(),
                             );
+                            record_task_error(&self.task_id);
                             log::error!("{}", e);
                         }
                     }
@@ -496,6 +523,7 @@ impl TransferTaskActions for TransferLocal2Local {
 
             if records.len() > 0 {
                 let copy = Local2LocalExecutor {
+                    task_id: self.task_id.clone(),
                     source: self.source.clone(),
                     target: self.target.clone(),
                     err_counter: Arc::clone(&err_counter),
@@ -565,6 +593,7 @@ impl TransferLocal2Local {
                         list_file_path: list_file_path.to_string(),
                         list_file_position: FilePosition { offset, line_num },
                         option: Opt::PUT,
+                        attempts: 0,
                     };
                     return Ok(record);
                 }
@@ -575,6 +604,7 @@ impl TransferLocal2Local {
                         list_file_path: list_file_path.to_string(),
                         list_file_position: FilePosition { offset, line_num },
                         option: Opt::REMOVE,
+                        attempts: 0,
                     };
                     return Ok(record);
                 }
@@ -588,6 +618,7 @@ impl TransferLocal2Local {
 
 #[derive(Debug, Clone)]
 pub struct Local2LocalExecutor {
+    pub task_id: String,
     pub source: String,
     pub target: String,
     pub err_counter: Arc<AtomicUsize>,
@@ -616,6 +647,8 @@ impl Local2LocalExecutor {
             .open(error_file_name.as_str())?;
 
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             // 记录文件执行位置
             self.offset_map.insert(
                 offset_key.clone(),
@@ -642,13 +675,16 @@ impl Local2LocalExecutor {
                         line_num: record.line_num,
                     },
                     option: Opt::PUT,
+                    attempts: 0,
                 };
                 recorddesc.handle_error(
                     &self.err_counter,
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
             };
         }
@@ -691,7 +727,9 @@ impl Local2LocalExecutor {
             target_file,
             self.attributes.large_file_size,
             self.attributes.multi_part_chunk_size,
-        )
+        )?;
+        record_task_transferred(&self.task_id, file_len(source_file));
+        Ok(())
     }
 
     pub async fn exec_record_descriptions(&self, records: Vec<RecordDescription>) -> Result<()> {
@@ -716,13 +754,17 @@ impl Local2LocalExecutor {
             .open(error_file_name.as_str())?;
 
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             if let Err(e) = self.record_description_handler(&record).await {
                 record.handle_error(
                     &self.err_counter,
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
             }
         }
@@ -749,6 +791,7 @@ impl Local2LocalExecutor {
                     self.attributes.large_file_size,
                     self.attributes.multi_part_chunk_size,
                 )?;
+                record_task_transferred(&self.task_id, file_len(&record.source_key));
             }
             Opt::REMOVE => fs::remove_file(record.target_key.as_str())?,
             _ => return Err(anyhow!("unknow option")),
@@ -4,14 +4,19 @@ use super::{
     MODIFIED_PREFIX, NOTIFY_FILE_PREFIX, OFFSET_PREFIX, REMOVED_PREFIX,
     TRANSFER_ERROR_RECORD_PREFIX,
 };
+use super::{run_until_cancelled, task_cancellation_token};
+use crate::commons::file_len;
+use crate::commons::inherit_request_context;
 use crate::commons::merge_file;
 use crate::commons::struct_to_json_string;
 use crate::commons::{
     analyze_folder_files_size, json_to_struct, read_lines, scan_folder_files_to_file,
-    LastModifyFilter, Modified, ModifyType, NotifyWatcher, PathType, RegexFilter,
+    AnalyzeProgress, LastModifyFilter, Modified, ModifyType, NotifyWatcher, PathType, RegexFilter,
 };
 use crate::s3::OSSDescription;
 use crate::s3::OssClient;
+use crate::server::{record_task_error, record_task_transferred};
+use crate::tasks::wait_if_paused;
 use crate::tasks::TaskDefaultParameters;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -19,7 +24,6 @@ use aws_sdk_s3::types::Object;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
-use std::collections::BTreeMap;
 use std::io::{BufRead, Seek, SeekFrom};
 use std::sync::Arc;
 use std::{
@@ -60,12 +64,13 @@ impl Default for TransferLocal2Oss {
 
 #[async_trait]
 impl TransferTaskActions for TransferLocal2Oss {
-    async fn analyze_source(&self) -> Result<BTreeMap<String, i128>> {
+    async fn analyze_source(&self, progress: &AnalyzeProgress) -> Result<()> {
         let filter = RegexFilter::from_vec(&self.attributes.exclude, &self.attributes.include)?;
         analyze_folder_files_size(
             &self.source,
             Some(filter),
             self.attributes.last_modify_filter.clone(),
+            progress,
         )
     }
     // 错误记录重试
@@ -105,6 +110,7 @@ impl TransferTaskActions for TransferLocal2Oss {
 
                     if record_vec.len() > 0 {
                         let upload = Local2OssExecuter {
+                            task_id: self.task_id.clone(),
                             source: self.source.clone(),
                             target: self.target.clone(),
                             err_counter: Arc::new(AtomicUsize::new(0)),
@@ -135,6 +141,7 @@ impl TransferTaskActions for TransferLocal2Oss {
         list_file: String,
     ) {
         let local2oss = Local2OssExecuter {
+            task_id: self.task_id.clone(),
             source: self.source.clone(),
             target: self.target.clone(),
             err_counter,
@@ -143,15 +150,22 @@ impl TransferTaskActions for TransferLocal2Oss {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = local2oss
-                .exec_listed_records(records, executing_transfers)
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) = run_until_cancelled(
+                    &cancel,
+                    local2oss.exec_listed_records(records, executing_transfers),
+                )
                 .await
-            {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     async fn record_descriptions_transfor(
@@ -166,6 +180,7 @@ impl TransferTaskActions for TransferLocal2Oss {
         list_file: String,
     ) {
         let local2oss = Local2OssExecuter {
+            task_id: self.task_id.clone(),
             source: self.source.clone(),
             target: self.target.clone(),
             err_counter,
@@ -174,12 +189,19 @@ impl TransferTaskActions for TransferLocal2Oss {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = local2oss.exec_record_descriptions(records).await {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) =
+                    run_until_cancelled(&cancel, local2oss.exec_record_descriptions(records)).await
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     // 生成对象列表
@@ -247,6 +269,7 @@ impl TransferTaskActions for TransferLocal2Oss {
                             list_file_path: "".to_string(),
                             list_file_position: FilePosition::default(),
                             option: Opt::REMOVE,
+                            attempts: 0,
                         };
                         let record_str = match struct_to_json_string(&record) {
                             Ok(r) => r,
@@ -325,6 +348,7 @@ impl TransferTaskActions for TransferLocal2Oss {
                         list_file_path: "".to_string(),
                         list_file_position: FilePosition::default(),
                         option: Opt::PUT,
+                        attempts: 0,
                     };
                     let record_str = match struct_to_json_string(&record) {
                         Ok(r) => r,
@@ -401,12 +425,12 @@ impl TransferTaskActions for TransferLocal2Oss {
         err_counter: Arc<AtomicUsize>,
         offset_map: Arc<DashMap<String, FilePosition>>,
         snapshot_stop_mark: Arc<AtomicBool>,
-    ) {
+    ) -> Result<()> {
         let lock = assistant.lock().await;
         let local_notify = match lock.local_notify.clone() {
             Some(n) => n,
             None => {
-                return;
+                return Ok(());
             }
         };
         drop(lock);
@@ -449,7 +473,7 @@ impl TransferTaskActions for TransferLocal2Oss {
                 Ok(r) => r,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -468,14 +492,14 @@ impl TransferTaskActions for TransferLocal2Oss {
                 .le(&err_counter.load(std::sync::atomic::Ordering::Relaxed))
             {
                 snapshot_stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                return;
+                return Ok(());
             }
 
             let mut file = match File::open(&local_notify.notify_file_path) {
                 Ok(f) => f,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -523,13 +547,16 @@ impl TransferTaskActions for TransferLocal2Oss {
                                     line_num,
                                 },
                                 option: Opt::UNKOWN,
+                                attempts: 0,
                             };
                             r.handle_error(
                                 &err_counter,
                                 &offset_map,
                                 &mut error_file,
                                 offset_key.as_str(),
+                                &e.to_string(),
                             );
+                            record_task_error(&self.task_id);
                             log::error!("{}", e);
                         }
                     }
@@ -537,6 +564,7 @@ impl TransferTaskActions for TransferLocal2Oss {
             }
 
             let local_2_oss = Local2OssExecuter {
+                task_id: self.task_id.clone(),
                 source: self.source.clone(),
                 target: self.target.clone(),
                 err_counter: Arc::clone(&err_counter),
@@ -610,6 +638,7 @@ impl TransferLocal2Oss {
                         list_file_path: list_file_path.to_string(),
                         list_file_position: FilePosition { offset, line_num },
                         option: Opt::PUT,
+                        attempts: 0,
                     };
                     return Ok(record);
                 }
@@ -620,6 +649,7 @@ impl TransferLocal2Oss {
                         list_file_path: list_file_path.to_string(),
                         list_file_position: FilePosition { offset, line_num },
                         option: Opt::REMOVE,
+                        attempts: 0,
                     };
                     return Ok(record);
                 }
@@ -633,6 +663,7 @@ impl TransferLocal2Oss {
 
 #[derive(Debug, Clone)]
 pub struct Local2OssExecuter {
+    pub task_id: String,
     pub source: String,
     pub target: OSSDescription,
     pub err_counter: Arc<AtomicUsize>,
@@ -669,6 +700,8 @@ impl Local2OssExecuter {
         let target_oss_client = self.target.gen_oss_client()?;
 
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             // 文件位置提前记录，避免漏记
             self.offset_map.insert(
                 offset_key.clone(),
@@ -700,13 +733,16 @@ impl Local2OssExecuter {
                         line_num: record.line_num,
                     },
                     option: Opt::PUT,
+                    attempts: 0,
                 };
                 record_desc.handle_error(
                     &self.err_counter,
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
             }
             self.offset_map.remove(&offset_key);
@@ -773,7 +809,9 @@ impl Local2OssExecuter {
                 self.attributes.multi_part_chunks_per_batch,
                 self.attributes.multi_part_parallelism,
             )
-            .await
+            .await?;
+        record_task_transferred(&self.task_id, file_len(source_file));
+        Ok(())
     }
 
     pub async fn exec_record_descriptions(&self, records: Vec<RecordDescription>) -> Result<()> {
@@ -803,6 +841,8 @@ impl Local2OssExecuter {
         // 增加去重逻辑，当两条记录相邻为 create和modif时只put一次
         // 增加目录删除逻辑，对应oss删除指定prefix下的所有文件，文件系统删除目录
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             // 记录执行文件位置
             self.offset_map
                 .insert(offset_key.clone(), record.list_file_position.clone());
@@ -824,7 +864,9 @@ impl Local2OssExecuter {
                             &self.offset_map,
                             &mut error_file,
                             offset_key.as_str(),
+                            &e.to_string(),
                         );
+                        record_task_error(&self.task_id);
                         log::error!("{}", e);
                         continue;
                     }
@@ -839,14 +881,19 @@ impl Local2OssExecuter {
                         continue;
                     }
 
-                    c_t.upload_local_file(
-                        self.target.bucket.as_str(),
-                        &record.target_key,
-                        &record.source_key,
-                        self.attributes.large_file_size,
-                        self.attributes.multi_part_chunk_size,
-                    )
-                    .await
+                    let uploaded = c_t
+                        .upload_local_file(
+                            self.target.bucket.as_str(),
+                            &record.target_key,
+                            &record.source_key,
+                            self.attributes.large_file_size,
+                            self.attributes.multi_part_chunk_size,
+                        )
+                        .await;
+                    if uploaded.is_ok() {
+                        record_task_transferred(&self.task_id, file_len(&record.source_key));
+                    }
+                    uploaded
                 }
                 Opt::REMOVE => {
                     match c_t
@@ -864,7 +911,9 @@ impl Local2OssExecuter {
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
                 continue;
             }
@@ -6,12 +6,16 @@ use super::{
 use super::{
     get_task_checkpoint, FileDescription, FilePosition, ListedRecord, Opt, RecordDescription,
 };
+use super::{join_exec_next, run_until_cancelled, task_cancellation_token};
+use crate::commons::inherit_request_context;
 use crate::resources::get_checkpoint;
+use crate::server::{record_task_error, record_task_transferred};
+use crate::tasks::wait_if_paused;
 use crate::tasks::TaskDefaultParameters;
 use crate::{
     commons::{
         json_to_struct, merge_file, promote_processbar, read_lines, struct_to_json_string,
-        LastModifyFilter, RegexFilter,
+        AnalyzeProgress, LastModifyFilter, RegexFilter,
     },
     s3::{download_object, OSSDescription, OssClient},
 };
@@ -21,7 +25,6 @@ use aws_sdk_s3::types::Object;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
-use std::collections::BTreeMap;
 use std::{
     fs::{self, File, OpenOptions},
     io::{self, BufRead, Write},
@@ -64,7 +67,7 @@ impl Default for TransferOss2Local {
 
 #[async_trait]
 impl TransferTaskActions for TransferOss2Local {
-    async fn analyze_source(&self) -> Result<BTreeMap<String, i128>> {
+    async fn analyze_source(&self, progress: &AnalyzeProgress) -> Result<()> {
         let regex_filter =
             RegexFilter::from_vec(&self.attributes.exclude, &self.attributes.include)?;
         let client = self.source.gen_oss_client()?;
@@ -75,6 +78,7 @@ impl TransferTaskActions for TransferOss2Local {
                 Some(regex_filter),
                 self.attributes.last_modify_filter.clone(),
                 self.attributes.objects_per_batch,
+                progress,
             )
             .await
     }
@@ -115,6 +119,7 @@ impl TransferTaskActions for TransferOss2Local {
 
                     if record_vec.len() > 0 {
                         let download = Oss2LocalListedRecordsExecutor {
+                            task_id: self.task_id.clone(),
                             target: self.target.clone(),
                             source: self.source.clone(),
                             err_counter: Arc::new(AtomicUsize::new(0)),
@@ -228,6 +233,7 @@ impl TransferTaskActions for TransferOss2Local {
                         list_file_path: "".to_string(),
                         list_file_position: FilePosition::default(),
                         option: Opt::REMOVE,
+                        attempts: 0,
                     };
                     let record_str = struct_to_json_string(&record)?;
                     let _ = removed_file.write_all(record_str.as_bytes());
@@ -249,6 +255,7 @@ impl TransferTaskActions for TransferOss2Local {
                                 list_file_path: "".to_string(),
                                 list_file_position: FilePosition::default(),
                                 option: Opt::PUT,
+                                attempts: 0,
                             };
 
                             let record_str = struct_to_json_string(&record)?;
@@ -319,6 +326,7 @@ impl TransferTaskActions for TransferOss2Local {
         list_file: String,
     ) {
         let oss2local = Oss2LocalListedRecordsExecutor {
+            task_id: self.task_id.clone(),
             target: self.target.clone(),
             source: self.source.clone(),
             err_counter,
@@ -327,15 +335,22 @@ impl TransferTaskActions for TransferOss2Local {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = oss2local
-                .exec_listed_records(records, executing_transfers)
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) = run_until_cancelled(
+                    &cancel,
+                    oss2local.exec_listed_records(records, executing_transfers),
+                )
                 .await
-            {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     async fn record_descriptions_transfor(
@@ -350,6 +365,7 @@ impl TransferTaskActions for TransferOss2Local {
         list_file: String,
     ) {
         let oss2local = Oss2LocalListedRecordsExecutor {
+            task_id: self.task_id.clone(),
             target: self.target.clone(),
             source: self.source.clone(),
             err_counter,
@@ -358,12 +374,19 @@ impl TransferTaskActions for TransferOss2Local {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = oss2local.exec_record_descriptions(records).await {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) =
+                    run_until_cancelled(&cancel, oss2local.exec_record_descriptions(records)).await
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 
     async fn increment_prelude(&self, assistant: Arc<Mutex<IncrementAssistant>>) -> Result<()> {
@@ -385,7 +408,7 @@ impl TransferTaskActions for TransferOss2Local {
         err_counter: Arc<AtomicUsize>,
         offset_map: Arc<DashMap<String, FilePosition>>,
         snapshot_stop_mark: Arc<AtomicBool>,
-    ) {
+    ) -> Result<()> {
         // 循环执行获取lastmodify 大于checkpoint指定的时间戳的对象
         // let lock = assistant.lock().await;
         // let checkpoint_path = lock.check_point_path.clone();
@@ -393,7 +416,7 @@ impl TransferTaskActions for TransferOss2Local {
         //     Ok(c) => c,
         //     Err(e) => {
         //         log::error!("{}", e);
-        //         return;
+        //         return Ok(());
         //     }
         // };
         // checkpoint.task_stage = TransferStage::Increment;
@@ -402,7 +425,7 @@ impl TransferTaskActions for TransferOss2Local {
             Ok(c) => c,
             Err(e) => {
                 log::error!("{}", e);
-                return;
+                return Ok(());
             }
         };
         checkpoint.task_stage = TransferStage::Increment;
@@ -410,7 +433,7 @@ impl TransferTaskActions for TransferOss2Local {
             Ok(_) => {}
             Err(e) => {
                 log::error!("{}", e);
-                return;
+                return Ok(());
             }
         };
 
@@ -419,7 +442,7 @@ impl TransferTaskActions for TransferOss2Local {
                 Ok(r) => r,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -440,7 +463,7 @@ impl TransferTaskActions for TransferOss2Local {
                 Ok(f) => f,
                 Err(e) => {
                     log::error!("{}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -465,7 +488,7 @@ impl TransferTaskActions for TransferOss2Local {
                 if err_counter.load(std::sync::atomic::Ordering::SeqCst)
                     >= self.attributes.max_errors
                 {
-                    return;
+                    return Ok(());
                 }
                 if let Result::Ok(line_str) = line {
                     let len = line_str.bytes().len() + "\n".bytes().len();
@@ -495,7 +518,7 @@ impl TransferTaskActions for TransferOss2Local {
                     .eq(&self.attributes.objects_per_batch.to_string())
                 {
                     while execute_set.read().await.len() >= self.attributes.task_parallelism {
-                        execute_set.write().await.join_next().await;
+                        join_exec_next(&execute_set).await?;
                     }
                     let vk = vec_keys.clone();
                     self.record_discriptions_excutor(
@@ -519,7 +542,7 @@ impl TransferTaskActions for TransferOss2Local {
                     < self.attributes.max_errors
             {
                 while execute_set.read().await.len() >= self.attributes.task_parallelism {
-                    execute_set.write().await.join_next().await;
+                    join_exec_next(&execute_set).await?;
                 }
 
                 let vk = vec_keys.clone();
@@ -535,7 +558,7 @@ impl TransferTaskActions for TransferOss2Local {
             }
 
             while execute_set.read().await.len() > 0 {
-                execute_set.write().await.join_next().await;
+                join_exec_next(&execute_set).await?;
             }
 
             finished_total_objects += modified.total_lines;
@@ -571,6 +594,7 @@ impl TransferTaskActions for TransferOss2Local {
             }
             tokio::time::sleep(tokio::time::Duration::from_secs(sleep_time)).await;
         }
+        Ok(())
     }
 }
 
@@ -585,6 +609,7 @@ impl TransferOss2Local {
         list_file: String,
     ) {
         let download = Oss2LocalListedRecordsExecutor {
+            task_id: self.task_id.clone(),
             target: self.target.clone(),
             source: self.source.clone(),
             err_counter,
@@ -593,19 +618,23 @@ impl TransferOss2Local {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = download.exec_record_descriptions(records).await {
-                download
-                    .err_counter
-                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Err(e) = download.exec_record_descriptions(records).await {
+                    download
+                        .err_counter
+                        .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Oss2LocalListedRecordsExecutor {
+    pub task_id: String,
     pub source: OSSDescription,
     pub target: String,
     pub err_counter: Arc<AtomicUsize>,
@@ -637,6 +666,8 @@ impl Oss2LocalListedRecordsExecutor {
 
         let c_s = self.source.gen_oss_client()?;
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             // 文件位置提前记录避免漏记
             self.offset_map.insert(
                 offset_key.clone(),
@@ -661,13 +692,16 @@ impl Oss2LocalListedRecordsExecutor {
                         line_num: record.line_num,
                     },
                     option: Opt::PUT,
+                    attempts: 0,
                 };
                 record_desc.handle_error(
                     &self.err_counter,
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
             }
         }
@@ -729,7 +763,7 @@ impl Oss2LocalListedRecordsExecutor {
         };
         let content_len_usize: usize = content_len.try_into()?;
 
-        match content_len_usize.le(&self.attributes.large_file_size) {
+        let downloaded = match content_len_usize.le(&self.attributes.large_file_size) {
             true => {
                 download_object(
                     s_obj_output,
@@ -753,7 +787,10 @@ impl Oss2LocalListedRecordsExecutor {
                     )
                     .await
             }
-        }
+        };
+        downloaded?;
+        record_task_transferred(&self.task_id, content_len_usize as u64);
+        Ok(())
 
         // download_object(
         //     s_obj_output,
@@ -789,6 +826,8 @@ impl Oss2LocalListedRecordsExecutor {
         let source_client = self.source.gen_oss_client()?;
 
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             // 记录执行文件位置
             self.offset_map
                 .insert(offset_key.clone(), record.list_file_position.clone());
@@ -815,7 +854,9 @@ impl Oss2LocalListedRecordsExecutor {
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
             };
         }
         self.offset_map.remove(&offset_key);
@@ -862,6 +903,7 @@ impl Oss2LocalListedRecordsExecutor {
                 //     .create(true)
                 //     .write(true)
                 //     .open(&record.target_key)?;
+                let content_len = obj.content_length().unwrap_or(0);
                 download_object(
                     obj,
                     // &mut t_file,
@@ -869,7 +911,8 @@ impl Oss2LocalListedRecordsExecutor {
                     self.attributes.large_file_size,
                     self.attributes.multi_part_chunk_size,
                 )
-                .await?
+                .await?;
+                record_task_transferred(&self.task_id, content_len.max(0) as u64);
             }
             Opt::REMOVE => {
                 let _ = fs::remove_file(record.target_key.as_str());
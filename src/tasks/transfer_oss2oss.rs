@@ -3,16 +3,19 @@ use super::{
     TransferTaskAttributes, MODIFIED_PREFIX, OFFSET_PREFIX, REMOVED_PREFIX,
     TRANSFER_ERROR_RECORD_PREFIX,
 };
+use super::{join_exec_next, run_until_cancelled, task_cancellation_token};
+use crate::commons::inherit_request_context;
 use crate::{
     commons::{
         json_to_struct, merge_file, promote_processbar, read_lines, struct_to_json_string,
-        LastModifyFilter, RegexFilter,
+        AnalyzeProgress, LastModifyFilter, RegexFilter,
     },
     resources::get_checkpoint,
     s3::{multipart_transfer_obj_paralle_by_range, OSSDescription, OssClient},
+    server::{record_task_error, record_task_transferred},
     tasks::{
-        FileDescription, FilePosition, ListedRecord, LogInfo, Opt, RecordDescription,
-        TaskDefaultParameters,
+        wait_if_paused, FileDescription, FilePosition, ListedRecord, LogInfo, Opt,
+        RecordDescription, TaskDefaultParameters,
     },
 };
 use anyhow::{anyhow, Context, Result};
@@ -22,7 +25,6 @@ use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
 use std::{
-    collections::BTreeMap,
     fs::{self, File, OpenOptions},
     io::{self, BufRead, Write},
     sync::{
@@ -63,7 +65,7 @@ impl Default for TransferOss2Oss {
 
 #[async_trait]
 impl TransferTaskActions for TransferOss2Oss {
-    async fn analyze_source(&self) -> Result<BTreeMap<String, i128>> {
+    async fn analyze_source(&self, progress: &AnalyzeProgress) -> Result<()> {
         let regex_filter =
             RegexFilter::from_vec(&self.attributes.exclude, &self.attributes.include)?;
         let client = self.source.gen_oss_client()?;
@@ -74,6 +76,7 @@ impl TransferTaskActions for TransferOss2Oss {
                 Some(regex_filter),
                 self.attributes.last_modify_filter.clone(),
                 self.attributes.objects_per_batch,
+                progress,
             )
             .await
     }
@@ -154,15 +157,22 @@ impl TransferTaskActions for TransferOss2Oss {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = transfer
-                .exec_listed_records(records, executing_transfers)
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) = run_until_cancelled(
+                    &cancel,
+                    transfer.exec_listed_records(records, executing_transfers),
+                )
                 .await
-            {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
 
         // execute_set.spawn(async move {
         //     if let Err(e) = transfer
@@ -197,15 +207,22 @@ impl TransferTaskActions for TransferOss2Oss {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = transfer
-                .exec_record_descriptions(executing_transfers, records)
+        let cancel = task_cancellation_token(&self.task_id).child_token();
+
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Some(Err(e)) = run_until_cancelled(
+                    &cancel,
+                    transfer.exec_record_descriptions(executing_transfers, records),
+                )
                 .await
-            {
-                stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+                {
+                    stop_mark.store(true, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
     // 生成对象列表
     async fn gen_source_object_list_file(
@@ -285,6 +302,7 @@ impl TransferTaskActions for TransferOss2Oss {
                                 list_file_path: "".to_string(),
                                 list_file_position: FilePosition::default(),
                                 option: Opt::PUT,
+                                attempts: 0,
                             };
 
                             let record_str = struct_to_json_string(&record)?;
@@ -334,6 +352,7 @@ impl TransferTaskActions for TransferOss2Oss {
                             list_file_path: "".to_string(),
                             list_file_position: FilePosition::default(),
                             option: Opt::REMOVE,
+                            attempts: 0,
                         };
 
                         let record_str = struct_to_json_string(&record)?;
@@ -378,6 +397,7 @@ impl TransferTaskActions for TransferOss2Oss {
                                 list_file_path: "".to_string(),
                                 list_file_position: FilePosition::default(),
                                 option: Opt::REMOVE,
+                                attempts: 0,
                             };
 
                             let record_str = struct_to_json_string(&record)?;
@@ -464,13 +484,13 @@ impl TransferTaskActions for TransferOss2Oss {
         err_counter: Arc<AtomicUsize>,
         offset_map: Arc<DashMap<String, FilePosition>>,
         snapshot_stop_mark: Arc<AtomicBool>,
-    ) {
+    ) -> Result<()> {
         // 循环执行获取lastmodify 大于checkpoint指定的时间戳的对象
         let mut checkpoint = match get_checkpoint(&self.task_id) {
             Ok(c) => c,
             Err(e) => {
                 log::error!("{}", e);
-                return;
+                return Ok(());
             }
         };
         checkpoint.task_stage = TransferStage::Increment;
@@ -480,7 +500,7 @@ impl TransferTaskActions for TransferOss2Oss {
                 Ok(r) => r,
                 Err(e) => {
                     log::error!("{:?}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -502,7 +522,7 @@ impl TransferTaskActions for TransferOss2Oss {
                 Ok(f) => f,
                 Err(e) => {
                     log::error!("{:?}", e);
-                    return;
+                    return Ok(());
                 }
             };
 
@@ -527,7 +547,7 @@ impl TransferTaskActions for TransferOss2Oss {
                 if err_counter.load(std::sync::atomic::Ordering::SeqCst)
                     >= self.attributes.max_errors
                 {
-                    return;
+                    return Ok(());
                 }
                 if let Result::Ok(line_str) = line {
                     let len = line_str.bytes().len() + "\n".bytes().len();
@@ -554,7 +574,7 @@ impl TransferTaskActions for TransferOss2Oss {
                     .eq(&self.attributes.objects_per_batch.to_string())
                 {
                     while execute_set.read().await.len() >= self.attributes.task_parallelism {
-                        execute_set.write().await.join_next().await;
+                        join_exec_next(&execute_set).await?;
                     }
                     let vk = vec_keys.clone();
                     self.record_discriptions_excutor(
@@ -579,7 +599,7 @@ impl TransferTaskActions for TransferOss2Oss {
                     < self.attributes.max_errors
             {
                 while execute_set.read().await.len() >= self.attributes.task_parallelism {
-                    execute_set.write().await.join_next().await;
+                    join_exec_next(&execute_set).await?;
                 }
 
                 let vk = vec_keys.clone();
@@ -596,7 +616,7 @@ impl TransferTaskActions for TransferOss2Oss {
             }
 
             while execute_set.read().await.len() > 0 {
-                execute_set.write().await.join_next().await;
+                join_exec_next(&execute_set).await?;
             }
 
             finished_total_objects += modified.total_lines;
@@ -638,6 +658,7 @@ impl TransferTaskActions for TransferOss2Oss {
             tokio::time::sleep(tokio::time::Duration::from_secs(sleep_time)).await;
         }
         pd.finish();
+        Ok(())
     }
 }
 
@@ -665,17 +686,20 @@ impl TransferOss2Oss {
             list_file_path: list_file,
         };
 
-        execute_set.write().await.spawn(async move {
-            if let Err(e) = oss2oss
-                .exec_record_descriptions(executing_transfers, records)
-                .await
-            {
-                oss2oss
-                    .err_counter
-                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-                log::error!("{}", e);
-            };
-        });
+        execute_set
+            .write()
+            .await
+            .spawn(inherit_request_context(async move {
+                if let Err(e) = oss2oss
+                    .exec_record_descriptions(executing_transfers, records)
+                    .await
+                {
+                    oss2oss
+                        .err_counter
+                        .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    log::error!("{}", e);
+                };
+            }));
     }
 }
 
@@ -728,6 +752,8 @@ impl TransferOss2OssRecordsExecutor {
         let s_c = Arc::new(source_client);
         let t_c = Arc::new(target_client);
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             //判断任务停止标识是否为true，为true 停止任务
             if self.stop_mark.load(std::sync::atomic::Ordering::SeqCst) {
                 match self
@@ -768,13 +794,16 @@ impl TransferOss2OssRecordsExecutor {
                         line_num: record.line_num,
                     },
                     option: Opt::PUT,
+                    attempts: 0,
                 };
                 recorddesc.handle_error(
                     &self.err_counter,
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
                 log::error!("{}", e);
             }
         }
@@ -839,7 +868,7 @@ impl TransferOss2OssRecordsExecutor {
             Some(d) => Some(*d),
             None => None,
         };
-        return match content_len_usize.le(&self.attributes.large_file_size) {
+        let transferred = match content_len_usize.le(&self.attributes.large_file_size) {
             true => {
                 target_oss
                     .upload_object_bytes(
@@ -869,6 +898,9 @@ impl TransferOss2OssRecordsExecutor {
                 .await
             }
         };
+        transferred?;
+        record_task_transferred(&self.task_id, content_len_usize as u64);
+        Ok(())
     }
 
     pub async fn exec_record_descriptions(
@@ -904,6 +936,8 @@ impl TransferOss2OssRecordsExecutor {
         let t_c = Arc::new(t_client);
 
         for record in records {
+            // 任务暂停时在对象之间等待恢复
+            wait_if_paused(&self.task_id).await;
             //判断任务停止标识是否为true，为true 停止任务
             if self.stop_mark.load(std::sync::atomic::Ordering::SeqCst) {
                 match self
@@ -929,7 +963,9 @@ impl TransferOss2OssRecordsExecutor {
                     &self.offset_map,
                     &mut error_file,
                     offset_key.as_str(),
+                    &e.to_string(),
                 );
+                record_task_error(&self.task_id);
             };
         }
         self.offset_map.remove(&offset_key);
@@ -1001,7 +1037,7 @@ impl TransferOss2OssRecordsExecutor {
                     None => None,
                 };
 
-                return match content_len_usize.le(&self.attributes.large_file_size) {
+                let transferred = match content_len_usize.le(&self.attributes.large_file_size) {
                     true => {
                         target_oss
                             .upload_object_bytes(
@@ -1031,6 +1067,8 @@ impl TransferOss2OssRecordsExecutor {
                         .await
                     }
                 };
+                transferred?;
+                record_task_transferred(&self.task_id, content_len_usize as u64);
             }
             Opt::REMOVE => {
                 target_oss